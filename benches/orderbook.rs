@@ -0,0 +1,125 @@
+// Compares the OrderBook implementations on a 50 level book: applying depth deltas,
+// applying a snapshot, and reading the top levels. Run with cargo bench --bench orderbook.
+use std::hint::black_box;
+
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkGroup, Criterion};
+use woox::dense::DenseBook;
+use woox::exchange_api_types::{OrderBookDelta, RestQuote, SnapshotData, WsQuote};
+use woox::ladder::LadderBook;
+use woox::orderbook::{LocalOrderBook, OrderBook};
+
+const LEVELS: usize = 50;
+const DELTAS: usize = 100_000;
+const TOP: usize = 10;
+const MID: f64 = 2000.0;
+const TICK: f64 = 0.01;
+
+// Rng is a small seeded generator, so every implementation is given the same deltas.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // below returns a number in [0, n), skewed towards 0 like updates are towards the touch.
+    fn below(&mut self, n: u64) -> u64 {
+        let a = self.next() % n;
+        let b = self.next() % n;
+        a.min(b)
+    }
+}
+
+fn snapshot() -> SnapshotData {
+    let level = |i: usize, sign: f64| RestQuote { price: MID + sign * (i + 1) as f64 * TICK, quantity: 1.0 + i as f64 };
+    SnapshotData {
+        bids: (0..LEVELS).map(|i| level(i, -1.0)).collect(),
+        asks: (0..LEVELS).map(|i| level(i, 1.0)).collect(),
+    }
+}
+
+// deltas returns single level updates within the 50 levels either side of mid, a tenth of
+// them removing the level.
+fn deltas() -> Vec<OrderBookDelta> {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    (0..DELTAS)
+        .map(|_| {
+            let offset = (rng.below(LEVELS as u64) + 1) as f64 * TICK;
+            let quantity = if rng.next().is_multiple_of(10) { 0.0 } else { (rng.next() % 100) as f64 / 10.0 + 0.1 };
+            let (bids, asks) = match rng.next() % 2 {
+                0 => (vec![WsQuote { price: MID - offset, quantity }], Vec::new()),
+                _ => (Vec::new(), vec![WsQuote { price: MID + offset, quantity }]),
+            };
+            OrderBookDelta { prev_ts: 0, bids, asks }
+        })
+        .collect()
+}
+
+// seeded returns a book from new with the snapshot and the first deltas applied, so it has
+// the gaps a live book does.
+fn seeded<B: OrderBook>(new: &impl Fn() -> B, deltas: &[OrderBookDelta]) -> B {
+    let mut book = new();
+    book.apply_snapshot(snapshot());
+    book.apply_deltas(&deltas[..DELTAS / 10]);
+    book
+}
+
+// apply_delta benches applying the deltas in turn, reading the touch after each as a
+// consumer would.
+fn apply_delta<B: OrderBook>(group: &mut BenchmarkGroup<WallTime>, name: &str, new: impl Fn() -> B, deltas: &[OrderBookDelta]) {
+    let mut book = seeded(&new, deltas);
+    let mut next = deltas.iter().cycle();
+    group.bench_function(name, |b| {
+        b.iter(|| {
+            book.apply_delta(black_box(next.next().unwrap()));
+            black_box((book.best_bid(), book.best_ask()))
+        })
+    });
+}
+
+fn apply_snapshot<B: OrderBook>(group: &mut BenchmarkGroup<WallTime>, name: &str, new: impl Fn() -> B, deltas: &[OrderBookDelta]) {
+    let mut book = seeded(&new, deltas);
+    group.bench_function(name, |b| b.iter_batched(snapshot, |snapshot| book.apply_snapshot(snapshot), BatchSize::SmallInput));
+}
+
+// top_n benches reading the top TOP levels of both sides.
+fn top_n<B: OrderBook>(group: &mut BenchmarkGroup<WallTime>, name: &str, new: impl Fn() -> B, deltas: &[OrderBookDelta]) {
+    let book = seeded(&new, deltas);
+    group.bench_function(name, |b| {
+        b.iter(|| {
+            let bids: f64 = book.bids().take(TOP).map(|(_, quantity)| quantity.value()).sum();
+            let asks: f64 = book.asks().take(TOP).map(|(_, quantity)| quantity.value()).sum();
+            black_box(bids + asks)
+        })
+    });
+}
+
+fn books(c: &mut Criterion) {
+    let deltas = deltas();
+    let dense = || DenseBook::new(TICK, 4 * LEVELS);
+
+    let mut group = c.benchmark_group("apply_delta");
+    apply_delta(&mut group, "btree", LocalOrderBook::new, &deltas);
+    apply_delta(&mut group, "ladder", LadderBook::new, &deltas);
+    apply_delta(&mut group, "dense", dense, &deltas);
+    group.finish();
+
+    let mut group = c.benchmark_group("apply_snapshot");
+    apply_snapshot(&mut group, "btree", LocalOrderBook::new, &deltas);
+    apply_snapshot(&mut group, "ladder", LadderBook::new, &deltas);
+    apply_snapshot(&mut group, "dense", dense, &deltas);
+    group.finish();
+
+    let mut group = c.benchmark_group("top_n");
+    top_n(&mut group, "btree", LocalOrderBook::new, &deltas);
+    top_n(&mut group, "ladder", LadderBook::new, &deltas);
+    top_n(&mut group, "dense", dense, &deltas);
+    group.finish();
+}
+
+criterion_group!(benches, books);
+criterion_main!(benches);
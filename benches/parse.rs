@@ -0,0 +1,76 @@
+// Measures parsing a 50 level Woo X depth message into a WsMessage, and counts the
+// allocations parsing one makes. Run it with and without the fast-parse feature to compare
+// the two quote parsers:
+// cargo bench --bench parse, then cargo bench --bench parse --features fast-parse.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use woox::exchange_api_types::WsMessage;
+
+const LEVELS: usize = 50;
+// FAST_PARSE_ALLOCATIONS is the most allocations the fast-parse path may make parsing the
+// message, growing its bids and asks vecs. The bench fails if it makes more.
+const FAST_PARSE_ALLOCATIONS: u64 = 16;
+
+// CountingAllocator is the system allocator, counting the allocations made through it.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// message returns a depth message of LEVELS levels a side around a mid of 2000.
+fn message() -> String {
+    let levels = |sign: f64| {
+        (0..LEVELS)
+            .map(|i| format!("[\"{:.2}\",\"{:.4}\"]", 2000.0 + sign * (i + 1) as f64 * 0.01, 0.5 + i as f64 * 0.1234))
+            .collect::<Vec<String>>()
+            .join(",")
+    };
+    format!(
+        "{{\"topic\":\"SPOT_ETH_USDT@orderbookupdate\",\"ts\":1700000000100,\"data\":{{\"symbol\":\"SPOT_ETH_USDT\",\"prevTs\":1700000000000,\"bids\":[{}],\"asks\":[{}]}}}}",
+        levels(-1.0),
+        levels(1.0)
+    )
+}
+
+fn parse(text: &str) -> WsMessage {
+    serde_json::from_str(text).expect("parse")
+}
+
+fn parse_delta(c: &mut Criterion) {
+    let text = message();
+    let parser = if cfg!(feature = "fast-parse") { "fast-parse" } else { "serde" };
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(parse(&text));
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!("{}: {} allocations parsing a message of {} levels a side", parser, allocations, LEVELS);
+    if cfg!(feature = "fast-parse") {
+        assert!(allocations <= FAST_PARSE_ALLOCATIONS, "fast-parse made {} allocations, more than {}", allocations, FAST_PARSE_ALLOCATIONS);
+    }
+
+    c.bench_function(&format!("parse_delta/{}", parser), |b| b.iter(|| parse(black_box(&text))));
+}
+
+criterion_group!(benches, parse_delta);
+criterion_main!(benches);
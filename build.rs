@@ -0,0 +1,15 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "proto")]
+    {
+        println!("cargo:rerun-if-changed=proto/woox.proto");
+        // protox compiles the proto in process, so building doesn't need protoc installed.
+        // The service is only generated for the grpc feature, the messages for both.
+        let descriptors = protox::compile(["proto/woox.proto"], ["proto"]).expect("Failed to parse proto/woox.proto");
+        tonic_build::configure()
+            .build_server(cfg!(feature = "grpc"))
+            .build_client(cfg!(feature = "grpc"))
+            .compile_fds(descriptors)
+            .expect("Failed to generate the protobuf messages");
+    }
+}
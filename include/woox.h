@@ -0,0 +1,120 @@
+/*
+ * woox.h is the C interface of the woox cdylib, built with cargo build --release
+ * --features ffi as target/release/libwoox.so. It embeds the feed handler: a WooxFeed
+ * follows the book for one symbol on background threads, calling back after every update
+ * applied, and its top of book can be read from any thread while it runs.
+ *
+ *     WooxFeed *feed = woox_feed_new("SPOT_BTC_USDT", 50);
+ *     woox_feed_on_update(feed, on_update, state);
+ *     woox_feed_start(feed);
+ *     ...
+ *     WooxLevel bid;
+ *     if (woox_feed_best_bid(feed, &bid)) { ... }
+ *     ...
+ *     woox_feed_free(feed);
+ */
+#ifndef WOOX_H
+#define WOOX_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct WooxFeed WooxFeed;
+
+typedef struct WooxLevel {
+    double price;
+    double quantity;
+} WooxLevel;
+
+/*
+ * WooxBookUpdate is what an update callback is given after each update is applied. Its
+ * pointers are only valid for the duration of the callback.
+ */
+typedef struct WooxBookUpdate {
+    const char *symbol;
+    uint64_t ts;
+    uint64_t prev_ts;
+    /* synced is true for the first update after the book synced from its snapshot. */
+    bool synced;
+    /* bids and asks are the levels the update changed, a zero quantity removing the level. */
+    const WooxLevel *bids;
+    size_t bids_len;
+    const WooxLevel *asks;
+    size_t asks_len;
+} WooxBookUpdate;
+
+/*
+ * WooxUpdateCallback is called on the feed's book thread, so it should return quickly. The
+ * woox_feed getters may be called from it, and see the update applied.
+ */
+typedef void (*WooxUpdateCallback)(const WooxBookUpdate *update, void *user_data);
+
+/*
+ * woox_feed_new returns a feed of the book for symbol on Woo X production, subscribed to
+ * and sharing depth levels a side, or NULL if symbol is NULL or isn't valid UTF-8. It
+ * isn't started.
+ */
+WooxFeed *woox_feed_new(const char *symbol, size_t depth);
+
+/*
+ * woox_feed_set_environment connects the feed to the Woo X environment named, "prod" or
+ * "staging". It returns 0, or -1 if the name isn't known or the feed has started.
+ */
+int woox_feed_set_environment(WooxFeed *feed, const char *name);
+
+/*
+ * woox_feed_set_endpoints connects the feed to the websocket at ws_url, fetching snapshots
+ * from the orderbook endpoint at orderbook_url. It returns 0, or -1 if either isn't valid
+ * UTF-8 or the feed has started.
+ */
+int woox_feed_set_endpoints(WooxFeed *feed, const char *ws_url, const char *orderbook_url);
+
+/*
+ * woox_feed_on_update registers callback to be called with user_data after every update,
+ * replacing any callback registered before. It returns 0, or -1 if the feed has started.
+ */
+int woox_feed_on_update(WooxFeed *feed, WooxUpdateCallback callback, void *user_data);
+
+/*
+ * woox_feed_start connects and follows the book on background threads. It returns 0, or
+ * -1 if the feed has already started.
+ */
+int woox_feed_start(WooxFeed *feed);
+
+/*
+ * woox_feed_is_running returns true while the feed is following the book, false before it
+ * is started and once it has stopped, such as when the book couldn't be synced.
+ */
+bool woox_feed_is_running(const WooxFeed *feed);
+
+/* woox_feed_ts returns the exchange timestamp the book was last updated at, 0 before it has synced. */
+uint64_t woox_feed_ts(const WooxFeed *feed);
+
+/* woox_feed_best_bid sets out to the best bid, returning false, leaving out alone, while there is none. */
+bool woox_feed_best_bid(const WooxFeed *feed, WooxLevel *out);
+bool woox_feed_best_ask(const WooxFeed *feed, WooxLevel *out);
+
+/*
+ * woox_feed_top copies up to n of the top levels a side, read together, into bids and
+ * asks, each with room for n levels, and sets bids_len and asks_len to how many were
+ * copied. It returns the exchange timestamp the levels are as of, 0 before the book has
+ * synced.
+ */
+uint64_t woox_feed_top(const WooxFeed *feed, size_t n, WooxLevel *bids, size_t *bids_len, WooxLevel *asks, size_t *asks_len);
+
+/*
+ * woox_feed_free stops the feed, waiting for its threads to finish, and frees it. A feed
+ * still fetching its first snapshot finishes that first. NULL is ignored.
+ */
+void woox_feed_free(WooxFeed *feed);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,172 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::client::DEFAULT_MAX_LEVEL;
+use crate::feed::FeedConfig;
+use crate::orderbook::LocalOrderBook;
+use crate::snapshot::SnapshotSource;
+use crate::standby::{PollError, WarmBook, WarmPoll};
+use crate::units::Price;
+
+// DepthTier is a subscription depth and the activity, in bps, from which it is used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthTier {
+    pub min_activity_bps: f64,
+    pub depth: usize,
+}
+
+// DepthPolicy chooses the snapshot and subscription depth of a book from its activity,
+// re-evaluated every interval.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthPolicy {
+    // tiers are the depths to choose from, the deepest tier whose min_activity_bps is met
+    // is used, and the first tier if none is.
+    pub tiers: Vec<DepthTier>,
+    pub interval: Duration,
+}
+
+impl Default for DepthPolicy {
+    fn default() -> Self {
+        Self {
+            tiers: vec![
+                DepthTier { min_activity_bps: 0.0, depth: 50 },
+                DepthTier { min_activity_bps: 20.0, depth: 100 },
+            ],
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl DepthPolicy {
+    // depth_for returns the depth for a book with the given activity, or None if there are
+    // no tiers.
+    pub fn depth_for(&self, activity: &Activity) -> Option<usize> {
+        let score = activity.score_bps();
+        self.tiers
+            .iter()
+            .filter(|tier| score >= tier.min_activity_bps)
+            .max_by_key(|tier| tier.depth)
+            .or(self.tiers.first())
+            .map(|tier| tier.depth)
+    }
+}
+
+// Activity summarizes how much a book moved over a window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Activity {
+    // range_bps is the range of the mid over the window, in bps of its midpoint.
+    pub range_bps: f64,
+    pub mean_spread_bps: f64,
+    pub samples: u64,
+}
+
+impl Activity {
+    // score_bps is the larger of the mid range and the mean spread: a book that moves a lot
+    // or quotes wide both have their liquidity spread over more levels.
+    pub fn score_bps(&self) -> f64 {
+        self.range_bps.max(self.mean_spread_bps)
+    }
+}
+
+// ActivityWindow accumulates the mids and spreads of a book until it is taken.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityWindow {
+    min_mid: Option<Price>,
+    max_mid: Option<Price>,
+    spread_bps_total: f64,
+    samples: u64,
+}
+
+impl ActivityWindow {
+    // observe records the top of book, ignoring one-sided and crossed books.
+    pub fn observe(&mut self, book: &LocalOrderBook) {
+        let (Some((bid, _)), Some((ask, _))) = (book.best_bid(), book.best_ask()) else {
+            return;
+        };
+        if bid >= ask {
+            return;
+        }
+        let mid = bid.midpoint(ask);
+        self.min_mid = Some(self.min_mid.map_or(mid, |min| min.min(mid)));
+        self.max_mid = Some(self.max_mid.map_or(mid, |max| max.max(mid)));
+        self.spread_bps_total += (ask - bid).bps_of(mid);
+        self.samples += 1;
+    }
+
+    // take returns the activity observed so far and starts a new window, or None if
+    // nothing was observed.
+    pub fn take(&mut self) -> Option<Activity> {
+        let window = std::mem::take(self);
+        let (min, max) = (window.min_mid?, window.max_mid?);
+        Some(Activity {
+            range_bps: (max - min).bps_of(min.midpoint(max)),
+            mean_spread_bps: window.spread_bps_total / window.samples as f64,
+            samples: window.samples,
+        })
+    }
+}
+
+// AdaptiveBook is a WarmBook whose depth follows a DepthPolicy. Every policy interval the
+// activity of the active book is evaluated and, if it calls for another depth, the book is
+// rebuilt at that depth on a standby feed, so depth changes never leave a gap.
+pub struct AdaptiveBook {
+    book: WarmBook,
+    policy: DepthPolicy,
+    window: ActivityWindow,
+    window_start: Instant,
+    last_activity: Option<Activity>,
+}
+
+impl AdaptiveBook {
+    // start starts following symbol at the depth of the policy's first tier.
+    pub fn start(
+        config: &FeedConfig,
+        symbol: &str,
+        policy: DepthPolicy,
+        source: Arc<dyn SnapshotSource>,
+    ) -> Self {
+        let depth = policy.tiers.first().map_or(DEFAULT_MAX_LEVEL, |tier| tier.depth);
+        Self {
+            book: WarmBook::start(config, symbol, depth, source),
+            policy,
+            window: ActivityWindow::default(),
+            window_start: Instant::now(),
+            last_activity: None,
+        }
+    }
+
+    pub fn book(&self) -> &WarmBook {
+        &self.book
+    }
+
+    pub fn book_mut(&mut self) -> &mut WarmBook {
+        &mut self.book
+    }
+
+    // last_activity returns the activity the depth was last evaluated on.
+    pub fn last_activity(&self) -> Option<&Activity> {
+        self.last_activity.as_ref()
+    }
+
+    // poll polls the book, observing its activity after updates and changing depth when an
+    // evaluation is due.
+    pub fn poll(&mut self) -> Result<WarmPoll, PollError> {
+        let poll = self.book.poll()?;
+        if poll.applied > 0 {
+            if let Some(book) = self.book.active().book() {
+                self.window.observe(book);
+            }
+        }
+
+        if self.window_start.elapsed() >= self.policy.interval {
+            self.window_start = Instant::now();
+            if let Some(activity) = self.window.take() {
+                if let Some(depth) = self.policy.depth_for(&activity) {
+                    self.book.change_depth(depth);
+                }
+                self.last_activity = Some(activity);
+            }
+        }
+        Ok(poll)
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+use crate::orderbook::LocalOrderBook;
+use crate::units::{Price, Qty};
+
+// BucketSize is the width of the price buckets an AggregatedBook merges levels into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BucketSize {
+    // Absolute buckets are a fixed price width, e.g. 0.5 for $0.50 buckets.
+    Absolute(f64),
+    // Bps buckets are a width in basis points of the mid price, e.g. 5.0 for 5 bps.
+    Bps(f64),
+}
+
+impl BucketSize {
+    // width returns the price width of a bucket given the current mid price.
+    pub fn width(&self, mid: Price) -> f64 {
+        match *self {
+            BucketSize::Absolute(width) => width,
+            BucketSize::Bps(bps) => mid.value() * bps / 10_000.0,
+        }
+    }
+}
+
+// AggregatedBook is a view of a LocalOrderBook with levels merged into price buckets.
+// Bids are floored and asks are ceiled to their bucket so the aggregated book never crosses.
+#[derive(Debug, Clone, Default)]
+pub struct AggregatedBook {
+    pub bucket_width: f64,
+    bids: BTreeMap<Price, Qty>,
+    asks: BTreeMap<Price, Qty>,
+}
+
+impl AggregatedBook {
+    // from builds the aggregated view of book. Bps buckets are sized against the book's mid
+    // price, so an empty or one sided book yields an empty view for them.
+    pub fn from(book: &LocalOrderBook, bucket_size: BucketSize) -> Self {
+        let width = match (bucket_size, book.mid_price()) {
+            (BucketSize::Absolute(width), _) => width,
+            (BucketSize::Bps(_), Some(mid)) => bucket_size.width(mid),
+            (BucketSize::Bps(_), None) => return Self::default(),
+        };
+        if !(width > 0.0 && width.is_finite()) {
+            return Self::default();
+        }
+
+        // Buckets are counted in whole widths, so a level on a bucket boundary is in that
+        // bucket and bid and ask buckets at the same boundary key the same price.
+        let mut aggregated = Self { bucket_width: width, ..Self::default() };
+        for (price, quantity) in book.bids() {
+            if let Some(bucket) = price.floor_ticks(width) {
+                *aggregated.bids.entry(Price::from_ticks(bucket, width)).or_default() += quantity;
+            }
+        }
+        for (price, quantity) in book.asks() {
+            if let Some(bucket) = price.ceil_ticks(width) {
+                *aggregated.asks.entry(Price::from_ticks(bucket, width)).or_default() += quantity;
+            }
+        }
+        aggregated
+    }
+
+    // bids returns the (bucket price, total quantity) bid buckets, best (highest) price first.
+    pub fn bids(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        self.bids.iter().rev().map(|(&price, &quantity)| (price, quantity))
+    }
+
+    // asks returns the (bucket price, total quantity) ask buckets, best (lowest) price first.
+    pub fn asks(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        self.asks.iter().map(|(&price, &quantity)| (price, quantity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange_api_types::{RestQuote, SnapshotData};
+
+    fn book(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> LocalOrderBook {
+        let quotes = |levels: &[(f64, f64)]| levels.iter().map(|&(price, quantity)| RestQuote { price, quantity }).collect();
+        let mut book = LocalOrderBook::new();
+        book.apply_snapshot(SnapshotData { bids: quotes(bids), asks: quotes(asks) });
+        book
+    }
+
+    fn buckets(levels: impl Iterator<Item = (Price, Qty)>) -> Vec<(f64, f64)> {
+        levels.map(|(price, quantity)| (price.value(), quantity.value())).collect()
+    }
+
+    #[test]
+    fn keeps_levels_on_a_boundary_in_that_bucket() {
+        let book = book(&[(2.3, 1.0), (2.25, 2.0), (2.2, 3.0)], &[(2.4, 1.0), (2.45, 2.0)]);
+        let aggregated = AggregatedBook::from(&book, BucketSize::Absolute(0.1));
+        assert_eq!(buckets(aggregated.bids()), vec![(2.3, 1.0), (2.2, 5.0)]);
+        assert_eq!(buckets(aggregated.asks()), vec![(2.4, 1.0), (2.5, 2.0)]);
+    }
+
+    #[test]
+    fn keys_bid_and_ask_buckets_at_a_boundary_alike() {
+        let bids = AggregatedBook::from(&book(&[(100.15, 1.0)], &[(100.3, 1.0)]), BucketSize::Absolute(0.1));
+        let asks = AggregatedBook::from(&book(&[(99.9, 1.0)], &[(100.05, 1.0), (100.1, 1.0)]), BucketSize::Absolute(0.1));
+        let (bid, _) = bids.bids().next().unwrap();
+        let (ask, quantity) = asks.asks().next().unwrap();
+        assert_eq!(bid, ask);
+        assert_eq!(ask, Price::new(100.1));
+        assert_eq!(quantity, Qty::new(2.0));
+    }
+
+    #[test]
+    fn sizes_bps_buckets_against_the_mid() {
+        let book = book(&[(99.5, 1.0), (99.0, 1.0)], &[(100.5, 1.0)]);
+        let aggregated = AggregatedBook::from(&book, BucketSize::Bps(100.0));
+        assert_eq!(aggregated.bucket_width, 1.0);
+        assert_eq!(buckets(aggregated.bids()), vec![(99.0, 2.0)]);
+        assert_eq!(buckets(aggregated.asks()), vec![(101.0, 1.0)]);
+        assert!(AggregatedBook::from(&LocalOrderBook::new(), BucketSize::Bps(5.0)).bids().next().is_none());
+    }
+}
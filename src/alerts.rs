@@ -0,0 +1,377 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::feed::MarketEvent;
+use crate::notifiers::NotifierConfig;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+use crate::units::{Price, Qty};
+
+pub type NotifyResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+// AlertCondition is what an AlertRule watches for, e.g. {"kind": "spread_above", "bps": 10}.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    // PriceCross triggers each time the book's mid crosses level, in either direction.
+    PriceCross { level: f64 },
+    // SpreadAbove triggers when the spread widens past bps of the mid, until it narrows again.
+    SpreadAbove { bps: f64 },
+    // DepthBelow triggers when the quantity in the top levels of either side falls below
+    // quantity, until both sides recover.
+    DepthBelow { levels: usize, quantity: f64 },
+    // Stale triggers when the book goes without an update for the client's stale threshold,
+    // until the next update.
+    Stale,
+    // Desync triggers when the book falls out of sync and is resynced from a snapshot.
+    Desync,
+}
+
+// AlertRule is a named condition on the books of symbol, or every symbol if not given.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AlertRule {
+    pub name: String,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(flatten)]
+    pub condition: AlertCondition,
+}
+
+impl AlertRule {
+    fn matches(&self, symbol: &str) -> bool {
+        self.symbol.as_deref().is_none_or(|rule_symbol| rule_symbol == symbol)
+    }
+}
+
+// AlertConfig is the JSON config file of the alert rules and the notifiers their events are
+// sent to, e.g.
+// {"rules": [{"name": "wide", "symbol": "PERP_ETH_USDT", "kind": "spread_above", "bps": 10}],
+//  "notifiers": [{"kind": "webhook", "url": "http://localhost:9000/alerts"}]}
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AlertConfig {
+    pub rules: Vec<AlertRule>,
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+}
+
+#[derive(Debug)]
+pub enum AlertError {
+    Io(io::Error),
+    Config(serde_json::Error),
+}
+
+impl fmt::Display for AlertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertError::Io(e) => write!(f, "{}", e),
+            AlertError::Config(e) => write!(f, "invalid alert config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AlertError {}
+
+impl From<io::Error> for AlertError {
+    fn from(e: io::Error) -> Self {
+        AlertError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for AlertError {
+    fn from(e: serde_json::Error) -> Self {
+        AlertError::Config(e)
+    }
+}
+
+impl AlertConfig {
+    pub fn load(path: &Path) -> Result<Self, AlertError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    // check_reload returns why config can't replace this one in a running process, if it
+    // can't. Rules are swapped in live, but the notifiers are started with the process.
+    pub fn check_reload(&self, config: &AlertConfig) -> Result<(), String> {
+        match self.notifiers == config.notifiers {
+            true => Ok(()),
+            false => Err("the alert notifiers changed".to_string()),
+        }
+    }
+
+    // notifiers returns the configured notifiers, after a LogNotifier.
+    pub fn notifiers(&self) -> Vec<Box<dyn Notifier>> {
+        let mut notifiers: Vec<Box<dyn Notifier>> = vec![Box::new(LogNotifier)];
+        notifiers.extend(self.notifiers.iter().map(NotifierConfig::build));
+        notifiers
+    }
+}
+
+// AlertKind is whether an alert opens or closes a condition. Conditions that happen at a
+// point in time, a price cross or a resync, only trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    Triggered,
+    Resolved,
+}
+
+// AlertEvent is a rule triggering or resolving on symbol's book at ts, in ms since the epoch,
+// with a message describing it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub symbol: String,
+    pub kind: AlertKind,
+    pub message: String,
+    pub ts: u64,
+}
+
+impl fmt::Display for AlertEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.kind {
+            AlertKind::Triggered => "triggered",
+            AlertKind::Resolved => "resolved",
+        };
+        write!(f, "[{}] {} {}: {}", self.symbol, self.rule, state, self.message)
+    }
+}
+
+// Notifier is a destination alert events are sent to, such as a chat or a pager.
+pub trait Notifier: Send {
+    // name identifies the notifier in logs.
+    fn name(&self) -> &str;
+
+    fn notify(&mut self, event: &AlertEvent) -> NotifyResult;
+}
+
+// LogNotifier logs alert events.
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    fn notify(&mut self, event: &AlertEvent) -> NotifyResult {
+        match event.kind {
+            AlertKind::Triggered => warn!(rule = %event.rule, symbol = %event.symbol, "Alert triggered: {}", event.message),
+            AlertKind::Resolved => info!(rule = %event.rule, symbol = %event.symbol, "Alert resolved: {}", event.message),
+        }
+        Ok(())
+    }
+}
+
+// RuleState is what an AlertEngine remembers of a rule on one symbol between updates.
+#[derive(Debug, Clone, PartialEq)]
+struct RuleState {
+    symbol: String,
+    active: bool,
+    // above is whether the mid was above a PriceCross rule's level at the last update.
+    above: Option<bool>,
+}
+
+// AlertEngine evaluates alert rules against book updates, staleness and resyncs, returning
+// the events of the rules that trigger or resolve. Rules with a duration trigger once on
+// the update that opens them and resolve on the one that closes them, rather than on every
+// update in between.
+#[derive(Debug, Clone)]
+pub struct AlertEngine {
+    rules: Vec<AlertRule>,
+    states: Vec<Vec<RuleState>>,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        let states = vec![Vec::new(); rules.len()];
+        Self { rules, states }
+    }
+
+    pub fn rules(&self) -> &[AlertRule] {
+        &self.rules
+    }
+
+    // set_rules replaces the rules, keeping the state of those that are unchanged so an open
+    // condition doesn't trigger again.
+    pub fn set_rules(&mut self, rules: Vec<AlertRule>) {
+        let mut previous: Vec<(AlertRule, Vec<RuleState>)> = self.rules.drain(..).zip(self.states.drain(..)).collect();
+        for rule in rules {
+            let states = match previous.iter().position(|(kept, _)| *kept == rule) {
+                Some(i) => previous.swap_remove(i).1,
+                None => Vec::new(),
+            };
+            self.rules.push(rule);
+            self.states.push(states);
+        }
+    }
+
+    // on_book evaluates the price, spread and depth rules against symbol's book, and resolves
+    // its stale rules.
+    pub fn on_book(&mut self, symbol: &str, book: &LocalOrderBook) -> Vec<AlertEvent> {
+        let mid = book.mid_price();
+        let spread_bps = match (book.best_bid(), book.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) if bid < ask => Some((ask - bid).bps_of(bid.midpoint(ask))),
+            _ => None,
+        };
+        self.evaluate(symbol, |condition, state| match *condition {
+            AlertCondition::PriceCross { level } => {
+                let mid = mid?;
+                let above = mid > Price::new(level);
+                let crossed = state.above.is_some_and(|was_above| was_above != above);
+                state.above = Some(above);
+                let direction = if above { "above" } else { "below" };
+                crossed.then(|| (AlertKind::Triggered, format!("mid {} crossed {} {}", mid, direction, level)))
+            }
+            AlertCondition::SpreadAbove { bps } => {
+                let spread_bps = spread_bps?;
+                let message = format!("spread {:.2} bps, threshold {} bps", spread_bps, bps);
+                transition(state, spread_bps > bps, message)
+            }
+            AlertCondition::DepthBelow { levels, quantity } => {
+                let bid: Qty = book.bids().take(levels).map(|(_, quantity)| quantity).sum();
+                let ask: Qty = book.asks().take(levels).map(|(_, quantity)| quantity).sum();
+                let message = format!("top {} levels bid {} ask {}, threshold {}", levels, bid, ask, quantity);
+                transition(state, bid.min(ask) < Qty::new(quantity), message)
+            }
+            AlertCondition::Stale => transition(state, false, "book updated".to_string()),
+            AlertCondition::Desync => None,
+        })
+    }
+
+    // on_stale triggers symbol's stale rules, the book having gone without an update for age.
+    pub fn on_stale(&mut self, symbol: &str, age: Duration) -> Vec<AlertEvent> {
+        self.evaluate(symbol, |condition, state| match condition {
+            AlertCondition::Stale => transition(state, true, format!("no update for {:?}", age)),
+            _ => None,
+        })
+    }
+
+    // on_resync triggers symbol's desync rules, the book having been resynced from a snapshot.
+    pub fn on_resync(&mut self, symbol: &str) -> Vec<AlertEvent> {
+        self.evaluate(symbol, |condition, _| match condition {
+            AlertCondition::Desync => Some((AlertKind::Triggered, "book resynced from a snapshot".to_string())),
+            _ => None,
+        })
+    }
+
+    fn evaluate<F>(&mut self, symbol: &str, mut f: F) -> Vec<AlertEvent>
+    where
+        F: FnMut(&AlertCondition, &mut RuleState) -> Option<(AlertKind, String)>,
+    {
+        let ts = now_ms();
+        let mut events = Vec::new();
+        for (rule, states) in self.rules.iter().zip(&mut self.states) {
+            if !rule.matches(symbol) {
+                continue;
+            }
+            let state = match states.iter().position(|state| state.symbol == symbol) {
+                Some(i) => &mut states[i],
+                None => {
+                    states.push(RuleState { symbol: symbol.to_string(), active: false, above: None });
+                    states.last_mut().unwrap()
+                }
+            };
+            if let Some((kind, message)) = f(&rule.condition, state) {
+                events.push(AlertEvent { rule: rule.name.clone(), symbol: symbol.to_string(), kind, message, ts });
+            }
+        }
+        events
+    }
+}
+
+// transition moves a rule with a duration to active, returning the event if that opens or
+// closes it.
+fn transition(state: &mut RuleState, active: bool, message: String) -> Option<(AlertKind, String)> {
+    if state.active == active {
+        return None;
+    }
+    state.active = active;
+    Some((if active { AlertKind::Triggered } else { AlertKind::Resolved }, message))
+}
+
+// AlertSink evaluates alert rules against the books written to it, dispatching the events
+// to its notifiers on a background thread so a slow notifier doesn't hold up the book. The
+// first snapshot of each symbol is the initial sync rather than a resync, so it doesn't
+// trigger the desync rules.
+pub struct AlertSink {
+    engine: AlertEngine,
+    synced: Vec<String>,
+    events: Sender<AlertEvent>,
+    updates: Option<Receiver<Vec<AlertRule>>>,
+}
+
+impl AlertSink {
+    pub fn new(rules: Vec<AlertRule>, mut notifiers: Vec<Box<dyn Notifier>>) -> Self {
+        let (events, rx) = mpsc::channel::<AlertEvent>();
+        thread::spawn(move || {
+            for event in rx {
+                for notifier in &mut notifiers {
+                    if let Err(e) = notifier.notify(&event) {
+                        warn!(notifier = notifier.name(), rule = %event.rule, error = %e, "Failed to send alert");
+                    }
+                }
+            }
+        });
+        Self { engine: AlertEngine::new(rules), synced: Vec::new(), events, updates: None }
+    }
+
+    // with_updates replaces the rules with each set received from updates, e.g. as the alert
+    // config is reloaded.
+    pub fn with_updates(self, updates: Receiver<Vec<AlertRule>>) -> Self {
+        Self { updates: Some(updates), ..self }
+    }
+
+    fn update_rules(&mut self) {
+        let Some(rules) = self.updates.as_ref().and_then(|updates| updates.try_iter().last()) else { return };
+        info!(rules = rules.len(), "Alert rules updated");
+        self.engine.set_rules(rules);
+    }
+
+    fn dispatch(&self, events: Vec<AlertEvent>) {
+        for event in events {
+            let _ = self.events.send(event);
+        }
+    }
+}
+
+impl Sink for AlertSink {
+    fn name(&self) -> &str {
+        "alerts"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, _ts: u64, _book: &LocalOrderBook) -> SinkResult {
+        self.update_rules();
+        if self.synced.iter().any(|synced| synced == symbol) {
+            let events = self.engine.on_resync(symbol);
+            self.dispatch(events);
+        } else {
+            self.synced.push(symbol.to_string());
+        }
+        Ok(())
+    }
+
+    fn record_delta(&mut self, symbol: &str, _event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        self.update_rules();
+        let events = self.engine.on_book(symbol, book);
+        self.dispatch(events);
+        Ok(())
+    }
+
+    fn record_stale(&mut self, symbol: &str, age: Duration, _book: &LocalOrderBook) -> SinkResult {
+        self.update_rules();
+        let events = self.engine.on_stale(symbol, age);
+        self.dispatch(events);
+        Ok(())
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
@@ -0,0 +1,345 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::orderbook::LocalOrderBook;
+use crate::units::{Price, Qty};
+
+// MS_PER_YEAR annualizes volatility over a 365 day year, as crypto trades every day.
+const MS_PER_YEAR: f64 = 365.0 * 24.0 * 3_600_000.0;
+
+// WindowVolatility is the realized volatility of the mid over one window, from the returns
+// sampled in it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WindowVolatility {
+    pub window_ms: u64,
+    // realized is the standard deviation of the mid's log return over the time sampled, and
+    // annualized the same scaled to a year.
+    pub realized: f64,
+    pub annualized: f64,
+    pub samples: usize,
+}
+
+// RealizedVolatility samples the mid at a fixed interval and estimates its realized
+// volatility over rolling windows from the squared log returns between samples. Each sample
+// is the first mid on or after the interval since the last, so a gap in the feed is one
+// return over the whole gap.
+#[derive(Debug, Clone)]
+pub struct RealizedVolatility {
+    sample_interval_ms: u64,
+    windows: Vec<Duration>,
+    // last is the time and mid of the last sample.
+    last: Option<(u64, f64)>,
+    // returns are the exchange time, log return and time covered of each sample, oldest first,
+    // as far back as the longest window.
+    returns: VecDeque<(u64, f64, u64)>,
+}
+
+impl RealizedVolatility {
+    // new samples every sample_interval, e.g. 1s, for volatility over each of windows.
+    pub fn new(sample_interval: Duration, windows: &[Duration]) -> Self {
+        Self {
+            sample_interval_ms: (sample_interval.as_millis() as u64).max(1),
+            windows: windows.to_vec(),
+            last: None,
+            returns: VecDeque::new(),
+        }
+    }
+
+    pub fn windows(&self) -> &[Duration] {
+        &self.windows
+    }
+
+    // on_mid samples mid at exchange time ts, if an interval has passed since the last sample.
+    pub fn on_mid(&mut self, mid: Price, ts: u64) {
+        let mid = mid.value();
+        if mid <= 0.0 || !mid.is_finite() {
+            return;
+        }
+        match self.last {
+            Some((last_ts, _)) if ts < last_ts + self.sample_interval_ms => return,
+            Some((last_ts, last_mid)) => self.returns.push_back((ts, (mid / last_mid).ln(), ts - last_ts)),
+            None => {}
+        }
+        self.last = Some((ts, mid));
+
+        let longest = self.windows.iter().max().map_or(0, |window| window.as_millis() as u64);
+        while self.returns.front().is_some_and(|&(sampled, _, _)| sampled + longest <= ts) {
+            self.returns.pop_front();
+        }
+    }
+
+    // on_book samples the mid of book at its last update.
+    pub fn on_book(&mut self, book: &LocalOrderBook) {
+        if let (Some(mid), Some(ts)) = (book.mid_price(), book.last_update_ts()) {
+            self.on_mid(mid, ts);
+        }
+    }
+
+    // volatility returns the volatility over window up to the last sample, or None without
+    // a return sampled in it.
+    pub fn volatility(&self, window: Duration) -> Option<WindowVolatility> {
+        let (now, _) = self.last?;
+        let window_ms = window.as_millis() as u64;
+        let (variance, covered, samples) = self
+            .returns
+            .iter()
+            .rev()
+            .take_while(|&&(sampled, _, _)| sampled + window_ms > now)
+            .fold((0.0, 0, 0), |(variance, covered, samples), &(_, r, elapsed)| (variance + r * r, covered + elapsed, samples + 1));
+        if samples == 0 || covered == 0 {
+            return None;
+        }
+        Some(WindowVolatility {
+            window_ms,
+            realized: variance.sqrt(),
+            annualized: (variance * MS_PER_YEAR / covered as f64).sqrt(),
+            samples,
+        })
+    }
+
+    // volatilities returns the volatility over each window with a return sampled in it.
+    pub fn volatilities(&self) -> Vec<WindowVolatility> {
+        self.windows.iter().filter_map(|&window| self.volatility(window)).collect()
+    }
+}
+
+// BookAnalytics are statistics of a book at a point in time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BookAnalytics {
+    pub symbol: String,
+    pub ts: u64,
+    pub mid: Option<Price>,
+    pub microprice: Option<Price>,
+    pub spread_bps: Option<f64>,
+    // imbalance is the bid quantity less the ask quantity over their sum, in the top depth
+    // levels, from -1 with only asks to 1 with only bids.
+    pub imbalance: Option<f64>,
+    pub volatility: Vec<WindowVolatility>,
+}
+
+// BookAnalyzer computes BookAnalytics from a symbol's book updates, emitting them every
+// interval of exchange time.
+#[derive(Debug, Clone)]
+pub struct BookAnalyzer {
+    symbol: String,
+    depth: usize,
+    interval_ms: u64,
+    last_emit: Option<u64>,
+    volatility: RealizedVolatility,
+}
+
+impl BookAnalyzer {
+    // new analyzes the top depth levels of symbol's book every interval, with volatility
+    // estimated by volatility.
+    pub fn new(symbol: &str, depth: usize, interval: Duration, volatility: RealizedVolatility) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            depth,
+            interval_ms: interval.as_millis() as u64,
+            last_emit: None,
+            volatility,
+        }
+    }
+
+    pub fn volatility(&self) -> &RealizedVolatility {
+        &self.volatility
+    }
+
+    // on_book samples book, returning its analytics if they are due.
+    pub fn on_book(&mut self, book: &LocalOrderBook) -> Option<BookAnalytics> {
+        self.volatility.on_book(book);
+        let ts = book.last_update_ts()?;
+        if self.last_emit.is_some_and(|last| ts < last + self.interval_ms) {
+            return None;
+        }
+        self.last_emit = Some(ts);
+        Some(self.analyze(book, ts))
+    }
+
+    // analyze returns the analytics of book now, at exchange time ts.
+    pub fn analyze(&self, book: &LocalOrderBook, ts: u64) -> BookAnalytics {
+        let spread_bps = match (book.best_bid(), book.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) => Some((ask - bid).bps_of(bid.midpoint(ask))),
+            _ => None,
+        };
+        let bids: Qty = book.bids().take(self.depth).map(|(_, quantity)| quantity).sum();
+        let asks: Qty = book.asks().take(self.depth).map(|(_, quantity)| quantity).sum();
+        let total = bids + asks;
+        BookAnalytics {
+            symbol: self.symbol.clone(),
+            ts,
+            mid: book.mid_price(),
+            microprice: book.microprice(),
+            spread_bps,
+            imbalance: (total > Qty::ZERO).then(|| (bids - asks).ratio(total)),
+            volatility: self.volatility.volatilities(),
+        }
+    }
+}
+
+// DEFAULT_LIQUIDITY_DEPTH is the number of levels on each side liquidity is averaged over.
+pub const DEFAULT_LIQUIDITY_DEPTH: usize = 5;
+// DEFAULT_SPREAD_THRESHOLDS_BPS are the spreads the share of time over is tracked for.
+pub const DEFAULT_SPREAD_THRESHOLDS_BPS: [f64; 3] = [1.0, 5.0, 10.0];
+
+// SpreadThreshold is the share of a window the spread was wider than threshold_bps.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SpreadThreshold {
+    pub threshold_bps: f64,
+    pub ratio: f64,
+}
+
+// LiquiditySummary is the time weighted liquidity of a book over a window of exchange time,
+// start_ts up to end_ts. Time the book was crossed or one sided isn't counted.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LiquiditySummary {
+    pub start_ts: u64,
+    pub end_ts: u64,
+    // counted_ms is the time averaged over.
+    pub counted_ms: u64,
+    pub avg_spread_bps: f64,
+    // avg_bid_depth and avg_ask_depth are the quantity in the top levels of each side.
+    pub avg_bid_depth: Qty,
+    pub avg_ask_depth: Qty,
+    pub spread_above: Vec<SpreadThreshold>,
+}
+
+// Liquidity is a book's spread and top of book depth, held until its next update.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Liquidity {
+    spread_bps: f64,
+    bid_depth: Qty,
+    ask_depth: Qty,
+}
+
+// LiquidityWindow accumulates the time each Liquidity held within one window, in ms.
+#[derive(Debug, Clone, PartialEq)]
+struct LiquidityWindow {
+    start_ts: u64,
+    end_ts: u64,
+    counted_ms: u64,
+    spread_bps_ms: f64,
+    bid_depth_ms: f64,
+    ask_depth_ms: f64,
+    above_ms: Vec<u64>,
+}
+
+impl LiquidityWindow {
+    fn new(start_ts: u64, end_ts: u64, thresholds: usize) -> Self {
+        Self { start_ts, end_ts, counted_ms: 0, spread_bps_ms: 0.0, bid_depth_ms: 0.0, ask_depth_ms: 0.0, above_ms: vec![0; thresholds] }
+    }
+
+    fn credit(&mut self, liquidity: Liquidity, ms: u64, thresholds: &[f64]) {
+        self.counted_ms += ms;
+        self.spread_bps_ms += liquidity.spread_bps * ms as f64;
+        self.bid_depth_ms += liquidity.bid_depth.value() * ms as f64;
+        self.ask_depth_ms += liquidity.ask_depth.value() * ms as f64;
+        for (above, &threshold) in self.above_ms.iter_mut().zip(thresholds) {
+            if liquidity.spread_bps > threshold {
+                *above += ms;
+            }
+        }
+    }
+
+    // summary returns the averages of the window, ending at end_ts, or None if no time was
+    // counted.
+    fn summary(&self, end_ts: u64, thresholds: &[f64]) -> Option<LiquiditySummary> {
+        if self.counted_ms == 0 {
+            return None;
+        }
+        let counted = self.counted_ms as f64;
+        Some(LiquiditySummary {
+            start_ts: self.start_ts,
+            end_ts,
+            counted_ms: self.counted_ms,
+            avg_spread_bps: self.spread_bps_ms / counted,
+            avg_bid_depth: Qty::new(self.bid_depth_ms / counted),
+            avg_ask_depth: Qty::new(self.ask_depth_ms / counted),
+            spread_above: thresholds
+                .iter()
+                .zip(&self.above_ms)
+                .map(|(&threshold_bps, &above)| SpreadThreshold { threshold_bps, ratio: above as f64 / counted })
+                .collect(),
+        })
+    }
+}
+
+// LiquidityStats tracks the time weighted average spread and top of book depth of a book,
+// and the share of time the spread was over each threshold, in windows of interval aligned
+// to the epoch, e.g. every minute or hour, or over one window for the session. Each update
+// is weighted by the exchange time until the next.
+#[derive(Debug, Clone)]
+pub struct LiquidityStats {
+    depth: usize,
+    thresholds: Vec<f64>,
+    interval_ms: Option<u64>,
+    window: Option<LiquidityWindow>,
+    // last is the exchange time of the last update and the liquidity since, None while the
+    // book is crossed or one sided.
+    last: Option<(u64, Option<Liquidity>)>,
+}
+
+impl LiquidityStats {
+    // new tracks the top depth levels against thresholds_bps in windows of interval, or over
+    // the whole session if None.
+    pub fn new(depth: usize, thresholds_bps: &[f64], interval: Option<Duration>) -> Self {
+        Self {
+            depth,
+            thresholds: thresholds_bps.to_vec(),
+            interval_ms: interval.map(|interval| (interval.as_millis() as u64).max(1)),
+            window: None,
+            last: None,
+        }
+    }
+
+    // on_book credits the time since the last update to the liquidity then, and records
+    // book's, returning the summaries of the windows that ended.
+    pub fn on_book(&mut self, book: &LocalOrderBook) -> Vec<LiquiditySummary> {
+        let Some(ts) = book.last_update_ts() else { return Vec::new() };
+        let mut closed = Vec::new();
+        if let Some((mut from, liquidity)) = self.last.filter(|&(last, _)| ts > last) {
+            while from < ts {
+                let window = self.window.get_or_insert_with(|| {
+                    let (start, end) = match self.interval_ms {
+                        Some(interval) => (from / interval * interval, from / interval * interval + interval),
+                        None => (from, u64::MAX),
+                    };
+                    LiquidityWindow::new(start, end, self.thresholds.len())
+                });
+                let to = ts.min(window.end_ts);
+                if let Some(liquidity) = liquidity {
+                    window.credit(liquidity, to - from, &self.thresholds);
+                }
+                if to == window.end_ts {
+                    let ended = self.window.take().and_then(|window| window.summary(window.end_ts, &self.thresholds));
+                    closed.extend(ended);
+                }
+                from = to;
+            }
+        }
+        if self.last.is_none_or(|(last, _)| ts >= last) {
+            self.last = Some((ts, self.liquidity(book)));
+        }
+        closed
+    }
+
+    // current returns the summary of the window so far, up to the last update.
+    pub fn current(&self) -> Option<LiquiditySummary> {
+        let (last, _) = self.last?;
+        self.window.as_ref()?.summary(last, &self.thresholds)
+    }
+
+    fn liquidity(&self, book: &LocalOrderBook) -> Option<Liquidity> {
+        let (Some((bid, _)), Some((ask, _))) = (book.best_bid(), book.best_ask()) else { return None };
+        if bid >= ask {
+            return None;
+        }
+        Some(Liquidity {
+            spread_bps: (ask - bid).bps_of(bid.midpoint(ask)),
+            bid_depth: book.bids().take(self.depth).map(|(_, quantity)| quantity).sum(),
+            ask_depth: book.asks().take(self.depth).map(|(_, quantity)| quantity).sum(),
+        })
+    }
+}
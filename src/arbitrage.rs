@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use crate::exchange_api_types::Side;
+use crate::feed::FeedConfig;
+use crate::fees::FeeSchedule;
+use crate::orderbook::{FillEstimate, LocalOrderBook};
+use crate::snapshot::SnapshotSource;
+use crate::standby::{PollError, WarmBook};
+use crate::units::{Price, Qty};
+
+// ArbLeg is one of the two books an arbitrage spread is taken between, on any venue: the
+// venue's feed and snapshot source, the symbol, and the fees paid trading it. Both legs
+// are taken, so only the taker rate is paid.
+pub struct ArbLeg {
+    pub name: String,
+    pub config: FeedConfig,
+    pub source: Arc<dyn SnapshotSource>,
+    pub symbol: String,
+    pub fees: FeeSchedule,
+}
+
+// ArbConfig is what an arbitrage spread is measured and alerted on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArbConfig {
+    // size is the quantity both legs are swept for, so the spread is what that size could
+    // actually be executed at.
+    pub size: Qty,
+    // threshold_bps is the net spread an opportunity opens at.
+    pub threshold_bps: f64,
+}
+
+// ArbDirection is which leg is bought and which sold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArbDirection {
+    // BuyASellB buys the first leg at its asks and sells the second at its bids.
+    BuyASellB,
+    BuyBSellA,
+}
+
+// ArbSpread is the executable spread of one direction: the average prices size fills at
+// when bought on one leg and sold on the other, and the spread between them in bps of the
+// buy price, before and after both legs' taker fees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArbSpread {
+    pub direction: ArbDirection,
+    pub size: Qty,
+    pub buy_price: Price,
+    pub sell_price: Price,
+    pub gross_bps: f64,
+    pub net_bps: f64,
+}
+
+// ArbAlertKind is whether an alert opens or closes an opportunity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArbAlertKind {
+    Opened,
+    Closed,
+}
+
+// ArbAlert reports the net spread crossing the threshold, with the spread that crossed it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArbAlert {
+    pub kind: ArbAlertKind,
+    pub spread: ArbSpread,
+}
+
+// SpreadMonitor computes the executable arbitrage spread between two books, alerting when
+// the better direction's net spread rises to the threshold and when it falls back below.
+#[derive(Debug, Clone)]
+pub struct SpreadMonitor {
+    config: ArbConfig,
+    fees: (FeeSchedule, FeeSchedule),
+    latest: Option<ArbSpread>,
+    open: bool,
+}
+
+impl SpreadMonitor {
+    // new measures spreads by config, paying the taker rates of fees_a and fees_b on the two
+    // legs.
+    pub fn new(config: ArbConfig, fees_a: FeeSchedule, fees_b: FeeSchedule) -> Self {
+        Self { config, fees: (fees_a, fees_b), latest: None, open: false }
+    }
+
+    // latest returns the best spread at the last update, if either direction could fill.
+    pub fn latest(&self) -> Option<ArbSpread> {
+        self.latest
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // spread returns the executable spread of direction between books a and b, or None if
+    // either side is too thin to fill size.
+    pub fn spread(&self, direction: ArbDirection, a: &LocalOrderBook, b: &LocalOrderBook) -> Option<ArbSpread> {
+        let size = self.config.size;
+        let (buy, sell) = match direction {
+            ArbDirection::BuyASellB => (sweep(a, Side::Buy, size)?, sweep(b, Side::Sell, size)?),
+            ArbDirection::BuyBSellA => (sweep(b, Side::Buy, size)?, sweep(a, Side::Sell, size)?),
+        };
+        let gross_bps = (sell - buy).bps_of(buy);
+        let fees_bps = self.fees.0.taker_bps + self.fees.1.taker_bps;
+        Some(ArbSpread {
+            direction,
+            size,
+            buy_price: buy,
+            sell_price: sell,
+            gross_bps,
+            net_bps: gross_bps - fees_bps,
+        })
+    }
+
+    // update recomputes the spread from books a and b, returning an alert if it crossed the
+    // threshold.
+    pub fn update(&mut self, a: &LocalOrderBook, b: &LocalOrderBook) -> Option<ArbAlert> {
+        let best = [ArbDirection::BuyASellB, ArbDirection::BuyBSellA]
+            .into_iter()
+            .filter_map(|direction| self.spread(direction, a, b))
+            .max_by(|x, y| x.net_bps.total_cmp(&y.net_bps));
+        let previous = self.latest;
+        self.latest = best;
+
+        let open = best.is_some_and(|spread| spread.net_bps >= self.config.threshold_bps);
+        if open == self.open {
+            return None;
+        }
+        self.open = open;
+        let (kind, spread) = match open {
+            true => (ArbAlertKind::Opened, best?),
+            // A spread that can no longer fill closes on its last known value.
+            false => (ArbAlertKind::Closed, best.or(previous)?),
+        };
+        Some(ArbAlert { kind, spread })
+    }
+}
+
+// sweep returns the average price size fills at taking book on side, or None if it doesn't
+// hold size.
+fn sweep(book: &LocalOrderBook, side: Side, size: Qty) -> Option<Price> {
+    book.estimate_fill(side, size).filter(FillEstimate::is_complete).map(|estimate| estimate.average_price)
+}
+
+// ArbitrageMonitor follows the books of two legs and measures the spread between them as
+// they update. Books that fall out of sync are restarted.
+pub struct ArbitrageMonitor {
+    names: (String, String),
+    books: (WarmBook, WarmBook),
+    monitor: SpreadMonitor,
+}
+
+impl ArbitrageMonitor {
+    pub fn start(a: ArbLeg, b: ArbLeg, config: ArbConfig, max_level: usize) -> Self {
+        let monitor = SpreadMonitor::new(config, a.fees, b.fees);
+        let book_a = WarmBook::start(&a.config, &a.symbol, max_level, a.source);
+        let book_b = WarmBook::start(&b.config, &b.symbol, max_level, b.source);
+        Self { names: (a.name, b.name), books: (book_a, book_b), monitor }
+    }
+
+    // names returns the names of the two legs.
+    pub fn names(&self) -> (&str, &str) {
+        (&self.names.0, &self.names.1)
+    }
+
+    pub fn monitor(&self) -> &SpreadMonitor {
+        &self.monitor
+    }
+
+    // poll applies the deltas available on both books and, if either changed while both
+    // are synced, updates the spread, returning any alert. Errors other than falling out of
+    // sync are returned, with the leg they came from.
+    pub fn poll(&mut self) -> Result<Option<ArbAlert>, (String, PollError)> {
+        let applied_a = self.books.0.poll_restarting().map_err(|e| (self.names.0.clone(), e))?;
+        let applied_b = self.books.1.poll_restarting().map_err(|e| (self.names.1.clone(), e))?;
+        if applied_a + applied_b == 0 {
+            return Ok(None);
+        }
+        let (a, b) = (self.books.0.active(), self.books.1.active());
+        match (a.is_synced(), a.book(), b.is_synced(), b.book()) {
+            (true, Some(a), true, Some(b)) => Ok(self.monitor.update(a, b)),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+
+use tracing::warn;
+
+use crate::feed::MarketEvent;
+
+// MAX_PENDING is the number of out of sequence events held back while waiting for the
+// other connection to fill a gap before the gap is accepted as unrecoverable.
+const MAX_PENDING: usize = 32;
+
+const PRIMARY: usize = 0;
+
+// ArbiterInput is a message sent from a feed connection to the arbitrator.
+pub enum ArbiterInput {
+    Event(usize, MarketEvent),
+    Closed(usize),
+}
+
+// ArbitrationMetrics counts how events from the redundant connections were resolved.
+#[derive(Default)]
+pub struct ArbitrationMetrics {
+    // forwarded is the number of events passed downstream.
+    pub forwarded: AtomicU64,
+    // duplicates is the number of events dropped because they were already forwarded.
+    pub duplicates: AtomicU64,
+    // secondary_first is the number of events the secondary delivered before the primary.
+    pub secondary_first: AtomicU64,
+    // secondary_saved is the number of events the primary never delivered at all.
+    pub secondary_saved: AtomicU64,
+    // gaps is the number of times neither connection delivered the next event in sequence.
+    pub gaps: AtomicU64,
+}
+
+impl ArbitrationMetrics {
+    // summary returns a single line description of the metrics.
+    pub fn summary(&self) -> String {
+        format!(
+            "Arbitration: forwarded {} | duplicates {} | secondary first {} | secondary saved {} | gaps {}",
+            self.forwarded.load(Ordering::Relaxed),
+            self.duplicates.load(Ordering::Relaxed),
+            self.secondary_first.load(Ordering::Relaxed),
+            self.secondary_saved.load(Ordering::Relaxed),
+            self.gaps.load(Ordering::Relaxed),
+        )
+    }
+}
+
+// Arbitrator merges the events of several connections to the same stream into a single
+// sequence. An event is forwarded when it continues the sequence (its prev_seq is the seq of
+// the last forwarded event), whichever connection it arrives on first.
+pub struct Arbitrator {
+    last_seq: Option<u64>,
+    pending: BTreeMap<u64, (usize, MarketEvent)>,
+    // last_seen is the newest seq seen on each connection, or None once it has closed.
+    last_seen: [Option<u64>; Arbitrator::CONNECTIONS],
+    // awaiting_primary holds the seq of events forwarded from the secondary that the
+    // primary has not delivered yet.
+    awaiting_primary: VecDeque<u64>,
+    metrics: Arc<ArbitrationMetrics>,
+}
+
+impl Default for Arbitrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Arbitrator {
+    pub const CONNECTIONS: usize = 2;
+
+    pub fn new() -> Self {
+        Self {
+            last_seq: None,
+            pending: BTreeMap::new(),
+            last_seen: [Some(0); Arbitrator::CONNECTIONS],
+            awaiting_primary: VecDeque::new(),
+            metrics: Arc::new(ArbitrationMetrics::default()),
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<ArbitrationMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    // run arbitrates the events received on input and passes the winners to output until
+    // every connection has closed or output returns false.
+    pub fn run<F>(mut self, input: Receiver<ArbiterInput>, mut output: F)
+    where
+        F: FnMut(MarketEvent) -> bool,
+    {
+        for message in input {
+            let forward = match message {
+                ArbiterInput::Event(connection, event) => self.on_event(connection, event),
+                ArbiterInput::Closed(connection) => {
+                    warn!(connection, "Feed connection closed");
+                    self.last_seen[connection] = None;
+                    self.flush_gaps()
+                }
+            };
+
+            for event in forward {
+                if !output(event) { return; }
+            }
+
+            if self.last_seen.iter().all(Option::is_none) { return; }
+        }
+    }
+
+    // on_event handles an event from connection and returns the events that are now
+    // ready to be forwarded, in order.
+    fn on_event(&mut self, connection: usize, event: MarketEvent) -> Vec<MarketEvent> {
+        if let Some(seen) = self.last_seen[connection].as_mut() {
+            *seen = (*seen).max(event.seq);
+        }
+        if connection == PRIMARY {
+            self.track_primary(event.seq);
+        }
+
+        let mut ready = Vec::new();
+        match self.last_seq {
+            Some(last) if event.seq <= last || event.prev_seq < last => {
+                self.metrics.duplicates.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(last) if event.prev_seq > last => {
+                self.pending.entry(event.prev_seq).or_insert((connection, event));
+            }
+            _ => {
+                self.accept(connection, event, &mut ready);
+                self.drain_pending(&mut ready);
+            }
+        }
+
+        ready.extend(self.flush_gaps());
+        ready
+    }
+
+    // flush_gaps forwards pending events once it is clear that no connection can deliver
+    // the missing event, either because every live connection has moved past it or too
+    // many events are waiting on it.
+    fn flush_gaps(&mut self) -> Vec<MarketEvent> {
+        let mut ready = Vec::new();
+        while let Some((&prev_seq, _)) = self.pending.iter().next() {
+            let last = self.last_seq.unwrap_or(0);
+            let all_past = self.last_seen.iter().flatten().all(|&seen| seen > last);
+            if !all_past && self.pending.len() <= MAX_PENDING {
+                break;
+            }
+
+            let (connection, event) = self.pending.remove(&prev_seq).unwrap();
+            self.metrics.gaps.fetch_add(1, Ordering::Relaxed);
+            self.accept(connection, event, &mut ready);
+            self.drain_pending(&mut ready);
+        }
+        ready
+    }
+
+    // drain_pending forwards pending events that now continue the sequence.
+    fn drain_pending(&mut self, ready: &mut Vec<MarketEvent>) {
+        while let Some(last) = self.last_seq {
+            match self.pending.remove(&last) {
+                Some((connection, event)) => self.accept(connection, event, ready),
+                None => break,
+            }
+        }
+        if let Some(last) = self.last_seq {
+            self.pending.retain(|&prev_seq, _| prev_seq > last);
+        }
+    }
+
+    fn accept(&mut self, connection: usize, event: MarketEvent, ready: &mut Vec<MarketEvent>) {
+        if connection != PRIMARY {
+            self.metrics.secondary_first.fetch_add(1, Ordering::Relaxed);
+            if self.last_seen[PRIMARY].is_some() {
+                self.awaiting_primary.push_back(event.seq);
+            }
+        }
+        self.metrics.forwarded.fetch_add(1, Ordering::Relaxed);
+        self.last_seq = Some(event.seq);
+        ready.push(event);
+    }
+
+    // track_primary resolves events forwarded from the secondary once the primary catches
+    // up to them. Any the primary skipped over were saved by the secondary.
+    fn track_primary(&mut self, seq: u64) {
+        while let Some(&awaited) = self.awaiting_primary.front() {
+            if awaited > seq { break; }
+            self.awaiting_primary.pop_front();
+            if awaited < seq {
+                self.metrics.secondary_saved.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::exchange_api_types::OrderBookDelta;
+
+    const SECONDARY: usize = 1;
+
+    // event returns the event numbered seq continuing prev_seq as delivered on connection,
+    // which it is tagged with in ts, so which copy was forwarded can be told apart.
+    fn event(connection: usize, seq: u64, prev_seq: u64) -> ArbiterInput {
+        let event = MarketEvent {
+            ts: connection as u64,
+            prev_ts: 0,
+            seq,
+            prev_seq,
+            delta: OrderBookDelta { prev_ts: 0, bids: Vec::new(), asks: Vec::new() },
+            snapshot: false,
+            checksum: None,
+            received_at: Instant::now(),
+            raw: None,
+            text: None,
+        };
+        ArbiterInput::Event(connection, event)
+    }
+
+    // arbitrate runs script through an arbitrator, returning the seq and connection of every
+    // event forwarded, in order, and the metrics.
+    fn arbitrate(script: Vec<ArbiterInput>) -> (Vec<(u64, usize)>, Arc<ArbitrationMetrics>) {
+        let (sender, receiver) = mpsc::channel();
+        for input in script {
+            sender.send(input).unwrap();
+        }
+        drop(sender);
+        let arbitrator = Arbitrator::new();
+        let metrics = arbitrator.metrics();
+        let mut forwarded = Vec::new();
+        arbitrator.run(receiver, |event| {
+            forwarded.push((event.seq, event.ts as usize));
+            true
+        });
+        (forwarded, metrics)
+    }
+
+    fn count(metric: &AtomicU64) -> u64 {
+        metric.load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn forwards_whichever_copy_arrives_first_and_drops_the_duplicates() {
+        let (forwarded, metrics) = arbitrate(vec![
+            event(SECONDARY, 1, 0),
+            event(PRIMARY, 1, 0),
+            event(PRIMARY, 2, 1),
+            event(SECONDARY, 2, 1),
+            event(PRIMARY, 3, 2),
+            event(SECONDARY, 3, 2),
+        ]);
+        assert_eq!(forwarded, vec![(1, SECONDARY), (2, PRIMARY), (3, PRIMARY)]);
+        assert_eq!(count(&metrics.forwarded), 3);
+        assert_eq!(count(&metrics.duplicates), 3);
+        assert_eq!(count(&metrics.secondary_first), 1);
+        // The primary delivered 1 in the end, so the secondary only beat it.
+        assert_eq!(count(&metrics.secondary_saved), 0);
+        assert_eq!(count(&metrics.gaps), 0);
+    }
+
+    #[test]
+    fn holds_events_past_a_gap_until_the_secondary_fills_it() {
+        let (forwarded, metrics) = arbitrate(vec![
+            event(PRIMARY, 1, 0),
+            event(SECONDARY, 1, 0),
+            // The primary loses 2, so its 3 waits for the secondary's.
+            event(PRIMARY, 3, 2),
+            event(SECONDARY, 2, 1),
+            event(SECONDARY, 3, 2),
+            event(PRIMARY, 4, 3),
+        ]);
+        assert_eq!(forwarded, vec![(1, PRIMARY), (2, SECONDARY), (3, PRIMARY), (4, PRIMARY)]);
+        assert_eq!(count(&metrics.secondary_first), 1);
+        // The primary moved on past 2 without ever delivering it.
+        assert_eq!(count(&metrics.secondary_saved), 1);
+        assert_eq!(count(&metrics.gaps), 0);
+    }
+
+    #[test]
+    fn accepts_a_gap_once_every_connection_has_moved_past_it() {
+        let (forwarded, metrics) = arbitrate(vec![
+            event(PRIMARY, 1, 0),
+            event(SECONDARY, 1, 0),
+            event(PRIMARY, 3, 2),
+            event(SECONDARY, 3, 2),
+            event(PRIMARY, 4, 3),
+        ]);
+        assert_eq!(forwarded, vec![(1, PRIMARY), (3, PRIMARY), (4, PRIMARY)]);
+        assert_eq!(count(&metrics.gaps), 1);
+        // Only the secondary's 1 counts, its 3 finding the primary's already waiting.
+        assert_eq!(count(&metrics.duplicates), 1);
+    }
+
+    #[test]
+    fn accepts_a_gap_once_the_connection_that_could_fill_it_closes() {
+        let (forwarded, metrics) = arbitrate(vec![
+            event(PRIMARY, 1, 0),
+            event(SECONDARY, 1, 0),
+            event(PRIMARY, 3, 2),
+            ArbiterInput::Closed(SECONDARY),
+            event(PRIMARY, 4, 3),
+        ]);
+        assert_eq!(forwarded, vec![(1, PRIMARY), (3, PRIMARY), (4, PRIMARY)]);
+        assert_eq!(count(&metrics.gaps), 1);
+    }
+
+    #[test]
+    fn carries_on_from_the_secondary_once_the_primary_closes() {
+        let (forwarded, metrics) = arbitrate(vec![
+            event(PRIMARY, 1, 0),
+            ArbiterInput::Closed(PRIMARY),
+            event(SECONDARY, 2, 1),
+            event(SECONDARY, 3, 2),
+            ArbiterInput::Closed(SECONDARY),
+            // Nothing is read once every connection has closed.
+            event(SECONDARY, 4, 3),
+        ]);
+        assert_eq!(forwarded, vec![(1, PRIMARY), (2, SECONDARY), (3, SECONDARY)]);
+        assert_eq!(count(&metrics.secondary_first), 2);
+        // A closed primary won't catch up, so nothing is counted as saved for it.
+        assert_eq!(count(&metrics.secondary_saved), 0);
+    }
+
+    #[test]
+    fn accepts_a_gap_once_too_many_events_wait_on_it() {
+        let mut arbitrator = Arbitrator::new();
+        let mut on_event = |input| match input {
+            ArbiterInput::Event(connection, event) => arbitrator.on_event(connection, event),
+            ArbiterInput::Closed(_) => unreachable!(),
+        };
+        assert_eq!(on_event(event(PRIMARY, 1, 0)).len(), 1);
+        // The secondary stays at 1, so could still fill 2, until the buffer overflows.
+        assert!(on_event(event(SECONDARY, 1, 0)).is_empty());
+        let first_pending = 3;
+        for seq in first_pending..first_pending + MAX_PENDING as u64 {
+            assert!(on_event(event(PRIMARY, seq, seq - 1)).is_empty(), "{} forwarded before the buffer was full", seq);
+        }
+        let seq = first_pending + MAX_PENDING as u64;
+        let forwarded: Vec<u64> = on_event(event(PRIMARY, seq, seq - 1)).iter().map(|event| event.seq).collect();
+        assert_eq!(forwarded, (first_pending..=seq).collect::<Vec<_>>());
+        assert_eq!(count(&arbitrator.metrics.gaps), 1);
+        assert!(arbitrator.pending.is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::exchange_api_types::WsTrade;
+use crate::feed::{self, FeedConfig, SocketBackend};
+use crate::rest::{RestClient, RestError, RestTrade};
+
+// MAX_BACKFILL is the most recent trades requested to cover a gap. Trades older than the
+// oldest one returned are lost.
+const MAX_BACKFILL: usize = 500;
+
+// RECONNECT_DELAY is how long to wait before reconnecting a dropped trades connection.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// TradeBackfill fetches the trades missed during a websocket gap from the Woo X REST
+// market trades endpoint.
+#[derive(Default)]
+pub struct TradeBackfill {
+    rest: RestClient,
+}
+
+impl TradeBackfill {
+    pub fn new(rest: RestClient) -> Self {
+        Self { rest }
+    }
+
+    // fetch_between returns the trades for symbol after after_ts, and before before_ts if
+    // given, oldest first and flagged as backfilled.
+    pub fn fetch_between(&self, symbol: &str, after_ts: u64, before_ts: Option<u64>) -> Result<Vec<WsTrade>, RestError> {
+        let rows = self.rest.market_trades(symbol, MAX_BACKFILL)?;
+        let mut trades: Vec<WsTrade> = rows
+            .into_iter()
+            .filter(|trade| trade.timestamp > after_ts && before_ts.is_none_or(|before| trade.timestamp < before))
+            .map(RestTrade::into_backfilled)
+            .collect();
+        trades.sort_by_key(|trade| trade.ts);
+        Ok(trades)
+    }
+}
+
+// connect_trades_backfilled is connect_trades that reconnects when the websocket drops.
+// With a backfill, the trades missed while reconnecting are fetched over REST and sent,
+// flagged as backfilled, ahead of the first live trade after the gap. Trades sharing the
+// timestamp of the last trade before or the first trade after the gap aren't backfilled.
+pub fn connect_trades_backfilled(config: &FeedConfig, symbol: &str, backfill: Option<TradeBackfill>) -> Receiver<WsTrade> {
+    let (tx, rx) = mpsc::channel();
+    let config = config.clone();
+    let symbol = symbol.to_string();
+
+    thread::spawn(move || {
+        let mut last_ts: Option<u64> = None;
+        let mut gap = false;
+        loop {
+            for trade in feed::connect_trades(&config, &symbol) {
+                if let (true, Some(after), Some(backfill)) = (gap, last_ts, backfill.as_ref()) {
+                    match backfill.fetch_between(&symbol, after, Some(trade.ts)) {
+                        Ok(missed) => {
+                            info!(%symbol, trades = missed.len(), "Backfilled trades missed during a gap");
+                            for missed_trade in missed {
+                                if tx.send(missed_trade).is_err() { return; }
+                            }
+                        }
+                        Err(e) => warn!(%symbol, error = %e, "Failed to backfill trades"),
+                    }
+                }
+                gap = false;
+                last_ts = Some(trade.ts);
+                if tx.send(trade).is_err() { return; }
+            }
+
+            // A replay ends for good, reconnecting would only replay it again.
+            if matches!(config.backend, SocketBackend::Replay(_)) { return; }
+            warn!(%symbol, "Trades connection dropped, reconnecting");
+            gap = true;
+            thread::sleep(RECONNECT_DELAY);
+        }
+    });
+    rx
+}
@@ -0,0 +1,132 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use tracing::warn;
+
+use crate::client::{Symbol, WooxClient, WooxClientBuilder};
+use crate::compact::CompactReader;
+use crate::exchange_api_types::{RestQuote, RestSnapshot, SnapshotData, WsQuote};
+use crate::feed::{FeedConfig, MarketEvent, SocketBackend};
+use crate::orderbook::LocalOrderBook;
+use crate::queue::{self, QueueConfig};
+use crate::recorder::{ReplayConfig, ReplaySource, ReplaySpeed};
+use crate::render::Precision;
+use crate::transport::ScriptedSource;
+use crate::units::Price;
+
+// backtest returns a client builder that rebuilds the book for symbol from a frame
+// recording instead of the live endpoints: deltas are replayed as fast as they can be
+// applied and the book is seeded from the recorded snapshots, so every run over the same
+// recording sees the same sequence of books.
+pub fn backtest(path: impl Into<PathBuf>, symbol: &str, max_level: usize) -> WooxClientBuilder<Symbol> {
+    let path = path.into();
+    let feed = FeedConfig {
+        backend: SocketBackend::Replay(ReplayConfig { path: path.clone(), speed: ReplaySpeed::Max }),
+        ..FeedConfig::default()
+    };
+    WooxClient::builder()
+        .symbol(symbol)
+        .depth(max_level)
+        .feed_config(feed)
+        .snapshot_source(Box::new(ReplaySource::new(path)))
+}
+
+// backtest_compact is backtest over a compact recording of symbol. The book is seeded from
+// the recording's first snapshot and its later records are replayed as the stream, later
+// snapshots resyncing the book as they come.
+pub fn backtest_compact(path: &Path, symbol: &str, max_level: usize) -> io::Result<WooxClientBuilder<Symbol>> {
+    let mut reader = CompactReader::open(path)?;
+    if reader.symbol() != symbol {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is a recording of {}", path.display(), reader.symbol())));
+    }
+    let first = match reader.next() {
+        Some(Ok(event)) if event.snapshot => event,
+        Some(Err(e)) => return Err(e),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "compact recording doesn't start with a snapshot")),
+    };
+    let quotes = |quotes: Vec<WsQuote>| quotes.into_iter().map(|quote| RestQuote { price: quote.price, quantity: quote.quantity }).collect();
+    let data = SnapshotData { bids: quotes(first.delta.bids), asks: quotes(first.delta.asks) };
+    let snapshot = RestSnapshot::new(first.ts, first.seq, data);
+
+    let (sender, events) = queue::bounded(QueueConfig::default(), None);
+    let path = path.to_path_buf();
+    thread::spawn(move || {
+        for event in reader {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => return warn!(path = %path.display(), error = %e, "Compact recording ended early"),
+            };
+            if sender.send(event).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(WooxClient::builder()
+        .symbol(symbol)
+        .depth(max_level)
+        .snapshot_source(Box::new(ScriptedSource::new(vec![snapshot])))
+        .events(events))
+}
+
+// BacktestStats summarizes the books seen over a backtest.
+#[derive(Debug, Clone, Default)]
+pub struct BacktestStats {
+    pub updates: u64,
+    pub first_ts: Option<u64>,
+    pub last_ts: Option<u64>,
+    pub min_mid: Option<Price>,
+    pub max_mid: Option<Price>,
+    pub last_mid: Option<Price>,
+    // crossed is the number of updates that left the best bid at or above the best ask,
+    // which points at a sync bug.
+    pub crossed: u64,
+    spread_bps_total: f64,
+    spread_samples: u64,
+}
+
+impl BacktestStats {
+    // record records book, just updated by event.
+    pub fn record(&mut self, book: &LocalOrderBook, event: &MarketEvent) {
+        self.updates += 1;
+        self.first_ts.get_or_insert(event.ts);
+        self.last_ts = Some(event.ts);
+
+        let (Some((bid, _)), Some((ask, _))) = (book.best_bid(), book.best_ask()) else {
+            return;
+        };
+        if bid >= ask {
+            self.crossed += 1;
+            return;
+        }
+
+        let mid = bid.midpoint(ask);
+        self.min_mid = Some(self.min_mid.map_or(mid, |min| min.min(mid)));
+        self.max_mid = Some(self.max_mid.map_or(mid, |max| max.max(mid)));
+        self.last_mid = Some(mid);
+        self.spread_bps_total += (ask - bid).bps_of(mid);
+        self.spread_samples += 1;
+    }
+
+    // mean_spread_bps returns the mean spread over uncrossed updates, in bps of the mid.
+    pub fn mean_spread_bps(&self) -> Option<f64> {
+        (self.spread_samples > 0).then(|| self.spread_bps_total / self.spread_samples as f64)
+    }
+
+    // summary returns a short multi line description of the stats, with mids formatted to
+    // precision.
+    pub fn summary(&self, precision: Precision) -> String {
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        format!(
+            "Updates: {} | ts {} to {}\nMid: min {} | max {} | last {}\nMean spread: {} bps | crossed updates: {}",
+            self.updates,
+            or_dash(self.first_ts.map(|ts| ts.to_string())),
+            or_dash(self.last_ts.map(|ts| ts.to_string())),
+            or_dash(self.min_mid.map(|mid| precision.format_price(mid))),
+            or_dash(self.max_mid.map(|mid| precision.format_price(mid))),
+            or_dash(self.last_mid.map(|mid| precision.format_price(mid))),
+            or_dash(self.mean_spread_bps().map(|bps| format!("{:.2}", bps))),
+            self.crossed,
+        )
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::exchange_api_types::FundingRate;
+use crate::feed::{self, FeedConfig};
+use crate::orderbook::LocalOrderBook;
+use crate::snapshot::SnapshotSource;
+use crate::standby::{PollError, WarmBook};
+
+const YEAR: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+// BasisConfig is how basis and carry are annualized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BasisConfig {
+    // funding_interval is how often the perpetual charges funding.
+    pub funding_interval: Duration,
+    // horizon is how long a carry position is expected to be held, over which the basis is
+    // assumed to converge.
+    pub horizon: Duration,
+}
+
+impl Default for BasisConfig {
+    fn default() -> Self {
+        Self {
+            funding_interval: Duration::from_secs(8 * 60 * 60),
+            horizon: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+// BasisEvent is the spot perp basis at a point in time and the carry it implies. Rates are
+// fractions, e.g. 0.1 for 10% a year. Carry is that of a cash and carry position, long spot
+// and short the perpetual: it receives the funding rate, paying it when it is negative, and
+// earns the basis as it converges over the horizon.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BasisEvent {
+    pub spot_symbol: String,
+    pub perp_symbol: String,
+    pub ts: u64,
+    pub spot_mid: f64,
+    pub perp_mid: f64,
+    // basis_bps is the perp mid over the spot mid, in bps of the spot mid.
+    pub basis_bps: f64,
+    pub annualized_basis: f64,
+    // funding_rate is the last rate seen, per funding interval, or None before the first.
+    pub funding_rate: Option<f64>,
+    pub annualized_funding: Option<f64>,
+    // expected_carry is the return of the position over the horizon, and annualized_carry
+    // the same annualized. Without a funding rate they are the basis alone.
+    pub expected_carry: f64,
+    pub annualized_carry: f64,
+}
+
+// BasisCalculator computes the basis between a spot and a perpetual book, and the carry
+// implied by it and the perpetual's funding rate.
+#[derive(Debug, Clone)]
+pub struct BasisCalculator {
+    spot_symbol: String,
+    perp_symbol: String,
+    config: BasisConfig,
+    funding: Option<FundingRate>,
+}
+
+impl BasisCalculator {
+    pub fn new(spot_symbol: &str, perp_symbol: &str, config: BasisConfig) -> Self {
+        Self { spot_symbol: spot_symbol.to_string(), perp_symbol: perp_symbol.to_string(), config, funding: None }
+    }
+
+    // funding returns the last funding rate recorded, if any.
+    pub fn funding(&self) -> Option<&FundingRate> {
+        self.funding.as_ref()
+    }
+
+    pub fn record_funding(&mut self, rate: FundingRate) {
+        self.funding = Some(rate);
+    }
+
+    // compute returns the basis between the spot and perp books, or None if either has no
+    // mid price.
+    pub fn compute(&self, spot: &LocalOrderBook, perp: &LocalOrderBook) -> Option<BasisEvent> {
+        let (spot_mid, perp_mid) = (spot.mid_price()?, perp.mid_price()?);
+        let basis = (perp_mid - spot_mid).value() / spot_mid.value();
+        let horizons_per_year = YEAR.as_secs_f64() / self.config.horizon.as_secs_f64();
+        let intervals_per_year = YEAR.as_secs_f64() / self.config.funding_interval.as_secs_f64();
+        let intervals_per_horizon = intervals_per_year / horizons_per_year;
+
+        let funding_rate = self.funding.as_ref().map(|funding| funding.rate);
+        let funding_carry = funding_rate.map_or(0.0, |rate| rate * intervals_per_horizon);
+        let expected_carry = basis + funding_carry;
+        Some(BasisEvent {
+            spot_symbol: self.spot_symbol.clone(),
+            perp_symbol: self.perp_symbol.clone(),
+            ts: now_ms(),
+            spot_mid: spot_mid.value(),
+            perp_mid: perp_mid.value(),
+            basis_bps: basis * 10_000.0,
+            annualized_basis: basis * horizons_per_year,
+            funding_rate,
+            annualized_funding: funding_rate.map(|rate| rate * intervals_per_year),
+            expected_carry,
+            annualized_carry: expected_carry * horizons_per_year,
+        })
+    }
+}
+
+// BasisMonitor follows a spot and a perpetual book on one venue and the perpetual's funding
+// rate stream, computing the basis as they update.
+pub struct BasisMonitor {
+    spot: WarmBook,
+    perp: WarmBook,
+    funding: Option<Receiver<FundingRate>>,
+    calculator: BasisCalculator,
+}
+
+impl BasisMonitor {
+    // start follows spot_symbol and perp_symbol on the venue of config. Without a funding
+    // rate stream for perp_symbol, carry is computed from the basis alone.
+    pub fn start(
+        config: &FeedConfig,
+        source: Arc<dyn SnapshotSource>,
+        spot_symbol: &str,
+        perp_symbol: &str,
+        max_level: usize,
+        basis: BasisConfig,
+    ) -> Self {
+        Self {
+            spot: WarmBook::start(config, spot_symbol, max_level, Arc::clone(&source)),
+            perp: WarmBook::start(config, perp_symbol, max_level, source),
+            funding: feed::connect_funding(config, perp_symbol),
+            calculator: BasisCalculator::new(spot_symbol, perp_symbol, basis),
+        }
+    }
+
+    pub fn calculator(&self) -> &BasisCalculator {
+        &self.calculator
+    }
+
+    // poll applies the deltas and funding rates available and, if anything changed while
+    // both books are synced, returns the new basis. Books that fall out of sync are restarted.
+    pub fn poll(&mut self) -> Result<Option<BasisEvent>, PollError> {
+        let mut changed = self.spot.poll_restarting()? + self.perp.poll_restarting()? > 0;
+        if let Some(funding) = &self.funding {
+            loop {
+                match funding.try_recv() {
+                    Ok(rate) => {
+                        self.calculator.record_funding(rate);
+                        changed = true;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Err(PollError::Disconnected),
+                }
+            }
+        }
+        if !changed {
+            return Ok(None);
+        }
+
+        let (spot, perp) = (self.spot.active(), self.perp.active());
+        match (spot.is_synced(), spot.book(), perp.is_synced(), perp.book()) {
+            (true, Some(spot), true, Some(perp)) => Ok(self.calculator.compute(spot, perp)),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
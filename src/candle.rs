@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use crate::exchange_api_types::WsTrade;
+use crate::orderbook::LocalOrderBook;
+use crate::units::{Notional, Price, Qty};
+
+// MAX_QUIET_CANDLES is the most flat candles filled in for intervals without a sample, so a
+// feed resuming after a long gap doesn't emit a day of one second bars. The bars of a longer
+// gap are skipped.
+const MAX_QUIET_CANDLES: u64 = 1000;
+
+// CandleSource is what a CandleBuilder builds its candles from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CandleSource {
+    // Trades builds candles from public trades, with their volume.
+    Trades,
+    // Mid builds candles from the book's mid price at each update, without volume.
+    Mid,
+}
+
+// Candle is a bar built locally, covering start_ts up to end_ts, exclusive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candle {
+    pub symbol: String,
+    pub interval: Duration,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    // volume is the base quantity traded, and amount the quote notional traded.
+    pub volume: Qty,
+    pub amount: Notional,
+    pub trades: u64,
+    pub start_ts: u64,
+    pub end_ts: u64,
+}
+
+impl Candle {
+    fn open_at(symbol: &str, interval_ms: u64, start_ts: u64, price: Price) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            interval: Duration::from_millis(interval_ms),
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: Qty::ZERO,
+            amount: Notional::ZERO,
+            trades: 0,
+            start_ts,
+            end_ts: start_ts + interval_ms,
+        }
+    }
+
+    // is_flat returns true for a candle without trades whose price didn't move, like those
+    // filled in for the intervals without a sample.
+    pub fn is_flat(&self) -> bool {
+        self.trades == 0 && self.open == self.high && self.open == self.low && self.open == self.close
+    }
+}
+
+// Series is the candles of one interval.
+#[derive(Debug, Clone)]
+struct Series {
+    interval_ms: u64,
+    current: Option<Candle>,
+    // last is the end and close of the last candle closed.
+    last: Option<(u64, Price)>,
+}
+
+impl Series {
+    // advance closes the current candle if ts is past it, and fills in flat candles at the
+    // last close for the intervals before ts without a sample.
+    fn advance(&mut self, symbol: &str, ts: u64) -> Vec<Candle> {
+        let mut closed = Vec::new();
+        if let Some(candle) = self.current.take_if(|candle| ts >= candle.end_ts) {
+            self.last = Some((candle.end_ts, candle.close));
+            closed.push(candle);
+        }
+        let Some((end, close)) = self.last.filter(|_| self.current.is_none()) else {
+            return closed;
+        };
+        let quiet = ts.saturating_sub(end) / self.interval_ms;
+        if quiet > MAX_QUIET_CANDLES {
+            self.last = Some((end + quiet * self.interval_ms, close));
+            return closed;
+        }
+        for i in 0..quiet {
+            closed.push(Candle::open_at(symbol, self.interval_ms, end + i * self.interval_ms, close));
+        }
+        self.last = Some((end + quiet * self.interval_ms, close));
+        closed
+    }
+
+    // sample adds a price at ts to the current candle, opening one if there is none.
+    // Samples from before the current candle are dropped.
+    fn sample(&mut self, symbol: &str, price: Price, quantity: Qty, ts: u64) -> Vec<Candle> {
+        let closed = self.advance(symbol, ts);
+        let candle = match &mut self.current {
+            Some(candle) if ts < candle.start_ts => return closed,
+            Some(candle) => candle,
+            None => self.current.insert(Candle::open_at(symbol, self.interval_ms, ts / self.interval_ms * self.interval_ms, price)),
+        };
+        candle.high = candle.high.max(price);
+        candle.low = candle.low.min(price);
+        candle.close = price;
+        if !quantity.is_zero() {
+            candle.volume += quantity;
+            candle.amount += price * quantity;
+            candle.trades += 1;
+        }
+        closed
+    }
+}
+
+// CandleBuilder aggregates trades or mid prices into candles of each of its intervals,
+// aligned to multiples of the interval since the epoch, so strategies get the same bars
+// whether or not the exchange's kline channel is subscribed. A candle closes with the first
+// sample or time past its end, and the intervals without a sample are filled in with flat
+// candles at the last close.
+#[derive(Debug, Clone)]
+pub struct CandleBuilder {
+    symbol: String,
+    source: CandleSource,
+    series: Vec<Series>,
+}
+
+impl CandleBuilder {
+    // new builds candles of symbol from source for each of intervals, e.g. 1s, 1m and 5m.
+    pub fn new(symbol: &str, source: CandleSource, intervals: &[Duration]) -> Self {
+        let series = intervals
+            .iter()
+            .map(|interval| Series { interval_ms: (interval.as_millis() as u64).max(1), current: None, last: None })
+            .collect();
+        Self { symbol: symbol.to_string(), source, series }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn source(&self) -> CandleSource {
+        self.source
+    }
+
+    // current returns the open candle of interval, if one has a sample.
+    pub fn current(&self, interval: Duration) -> Option<&Candle> {
+        let interval_ms = interval.as_millis() as u64;
+        self.series.iter().find(|series| series.interval_ms == interval_ms)?.current.as_ref()
+    }
+
+    // on_trade adds trade to the candles built from trades, returning the candles it closed.
+    // Trades in other symbols are ignored.
+    pub fn on_trade(&mut self, trade: &WsTrade) -> Vec<Candle> {
+        if self.source != CandleSource::Trades || trade.symbol != self.symbol {
+            return Vec::new();
+        }
+        let (price, quantity) = (Price::new(trade.price), Qty::new(trade.quantity));
+        self.each(|symbol, series| series.sample(symbol, price, quantity, trade.ts))
+    }
+
+    // on_mid adds a mid price sample at ts to the candles built from mid prices, returning
+    // the candles it closed.
+    pub fn on_mid(&mut self, price: Price, ts: u64) -> Vec<Candle> {
+        if self.source != CandleSource::Mid {
+            return Vec::new();
+        }
+        self.each(|symbol, series| series.sample(symbol, price, Qty::ZERO, ts))
+    }
+
+    // on_book samples the mid of book at its last update, or only closes the candles its
+    // update time is past when they're built from trades.
+    pub fn on_book(&mut self, book: &LocalOrderBook) -> Vec<Candle> {
+        let Some(ts) = book.last_update_ts() else { return Vec::new() };
+        match (self.source, book.mid_price()) {
+            (CandleSource::Mid, Some(mid)) => self.on_mid(mid, ts),
+            _ => self.on_time(ts),
+        }
+    }
+
+    // on_time closes the candles ts is past, without a sample, returning them. ts has to be
+    // on the clock of the samples, the exchange's.
+    pub fn on_time(&mut self, ts: u64) -> Vec<Candle> {
+        self.each(|symbol, series| series.advance(symbol, ts))
+    }
+
+    fn each<F>(&mut self, mut f: F) -> Vec<Candle>
+    where
+        F: FnMut(&str, &mut Series) -> Vec<Candle>,
+    {
+        let symbol = &self.symbol;
+        self.series.iter_mut().flat_map(|series| f(symbol, series)).collect()
+    }
+}
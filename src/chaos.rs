@@ -0,0 +1,291 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+// ChaosConfig is how often faults are injected into the data messages of feed connections,
+// each a probability per message in [0, 1]: a message may be dropped, delivered after a delay
+// of up to max_delay, delivered twice, or held back and delivered after the next one, and the
+// connection may be killed. Faults are drawn from a generator seeded with seed and the number
+// of the connection, so a run with the same seed and the same messages injects the same faults.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChaosConfig {
+    pub seed: u64,
+    pub drop: f64,
+    pub delay: f64,
+    pub max_delay: Duration,
+    pub duplicate: f64,
+    pub reorder: f64,
+    pub kill: f64,
+}
+
+impl ChaosConfig {
+    // new returns a config injecting no faults, to enable them with the with_ methods.
+    pub const fn new(seed: u64) -> Self {
+        Self { seed, drop: 0.0, delay: 0.0, max_delay: Duration::ZERO, duplicate: 0.0, reorder: 0.0, kill: 0.0 }
+    }
+
+    pub fn with_drop(self, drop: f64) -> Self {
+        Self { drop, ..self }
+    }
+
+    pub fn with_delay(self, delay: f64, max_delay: Duration) -> Self {
+        Self { delay, max_delay, ..self }
+    }
+
+    pub fn with_duplicate(self, duplicate: f64) -> Self {
+        Self { duplicate, ..self }
+    }
+
+    pub fn with_reorder(self, reorder: f64) -> Self {
+        Self { reorder, ..self }
+    }
+
+    pub fn with_kill(self, kill: f64) -> Self {
+        Self { kill, ..self }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+}
+
+// Chaos injects faults into every connection made with the FeedConfig it is set on, and
+// counts them.
+#[derive(Debug)]
+pub struct Chaos {
+    config: ChaosConfig,
+    connections: AtomicU64,
+    stats: ChaosStats,
+}
+
+// ChaosStats counts the faults injected across connections.
+#[derive(Debug, Default)]
+pub struct ChaosStats {
+    pub dropped: AtomicU64,
+    pub delayed: AtomicU64,
+    pub duplicated: AtomicU64,
+    pub reordered: AtomicU64,
+    pub killed: AtomicU64,
+}
+
+impl Chaos {
+    pub fn new(config: ChaosConfig) -> Self {
+        Self { config, connections: AtomicU64::new(0), stats: ChaosStats::default() }
+    }
+
+    pub fn config(&self) -> &ChaosConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> &ChaosStats {
+        &self.stats
+    }
+
+    // injector returns the injector of the next connection.
+    pub fn injector(self: &Arc<Self>) -> ChaosInjector {
+        let connection = self.connections.fetch_add(1, Ordering::Relaxed);
+        let rng = SplitMix64(self.config.seed ^ connection.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        ChaosInjector { chaos: Arc::clone(self), rng, held: None }
+    }
+}
+
+// ChaosInjector injects faults into the messages of a single connection.
+pub struct ChaosInjector {
+    chaos: Arc<Chaos>,
+    rng: SplitMix64,
+    // held is a message held back to be delivered after the next one.
+    held: Option<String>,
+}
+
+impl ChaosInjector {
+    // inject passes text on to deliver, unless a fault is injected in its place, and returns
+    // false once the connection should end: because deliver returned false, or the
+    // connection was killed.
+    pub fn inject<F>(&mut self, text: &str, mut deliver: F) -> bool
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let config = &self.chaos.config;
+        let stats = &self.chaos.stats;
+        if self.rng.chance(config.kill) {
+            stats.killed.fetch_add(1, Ordering::Relaxed);
+            warn!("Chaos killed the connection");
+            return false;
+        }
+        if self.rng.chance(config.drop) {
+            stats.dropped.fetch_add(1, Ordering::Relaxed);
+            debug!(data = text, "Chaos dropped a message");
+            return true;
+        }
+        if self.held.is_none() && self.rng.chance(config.reorder) {
+            stats.reordered.fetch_add(1, Ordering::Relaxed);
+            debug!(data = text, "Chaos held a message back");
+            self.held = Some(text.to_string());
+            return true;
+        }
+        if self.rng.chance(config.delay) {
+            let delay = config.max_delay.mul_f64(self.rng.next_f64());
+            stats.delayed.fetch_add(1, Ordering::Relaxed);
+            debug!(?delay, "Chaos delayed a message");
+            thread::sleep(delay);
+        }
+        let copies = match self.rng.chance(config.duplicate) {
+            true => {
+                stats.duplicated.fetch_add(1, Ordering::Relaxed);
+                2
+            }
+            false => 1,
+        };
+        for _ in 0..copies {
+            if !deliver(text) {
+                return false;
+            }
+        }
+        match self.held.take() {
+            Some(held) => deliver(&held),
+            None => true,
+        }
+    }
+}
+
+// SplitMix64 is a small seeded generator, so faults are reproducible from the seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // next_f64 returns a number in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.next_f64() < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::client::{ClientError, WooxClient};
+    use crate::exchange_api_types::{RestQuote, RestSnapshot, SnapshotData};
+    use crate::feed::{FeedConfig, SocketBackend};
+    use crate::orderbook::LocalOrderBook;
+    use crate::transport::{ScriptedConnector, ScriptedSource};
+    use crate::units::{Price, Qty};
+
+    const SYMBOL: &str = "SPOT_BTC_USDT";
+    const DELTAS: u64 = 40;
+
+    // Levels are a book's bids and asks, best first.
+    type Levels = (Vec<(f64, f64)>, Vec<(f64, f64)>);
+
+    fn levels(book: &LocalOrderBook) -> Levels {
+        let side = |levels: &mut dyn Iterator<Item = (Price, Qty)>| levels.map(|(price, quantity)| (price.value(), quantity.value())).collect();
+        (side(&mut book.bids()), side(&mut book.asks()))
+    }
+
+    // level returns the bid and ask set by delta n, each a price and quantity, a zero
+    // quantity removing the level. Each changes one of a few levels a side, so a delta lost
+    // or applied out of order leaves a different book.
+    fn level(n: u64) -> ((u64, u64), (u64, u64)) {
+        ((95 + n % 5, n), (101 + n % 3, n % 4))
+    }
+
+    // frames are DELTAS deltas continuing a snapshot at 1000, a ts of 100 apart.
+    fn frames() -> Vec<String> {
+        (1..=DELTAS)
+            .map(|n| {
+                let (ts, ((bid, bid_quantity), (ask, ask_quantity))) = (1000 + n * 100, level(n));
+                format!(
+                    r#"{{"topic":"orderbookupdate@{}@50","ts":{},"data":{{"prevTs":{},"bids":[["{}","{}"]],"asks":[["{}","{}"]]}}}}"#,
+                    SYMBOL,
+                    ts,
+                    ts - 100,
+                    bid,
+                    bid_quantity,
+                    ask,
+                    ask_quantity
+                )
+            })
+            .collect()
+    }
+
+    // fault_free returns the book after the delta at ts, with every delta before it applied
+    // in order.
+    fn fault_free(ts: u64) -> Levels {
+        let (mut bids, mut asks) = (BTreeMap::from([(95, 1)]), BTreeMap::from([(101, 1)]));
+        for n in 1..=(ts - 1000) / 100 {
+            let (bid, ask) = level(n);
+            for (side, (price, quantity)) in [(&mut bids, bid), (&mut asks, ask)] {
+                match quantity {
+                    0 => side.remove(&price),
+                    quantity => side.insert(price, quantity),
+                };
+            }
+        }
+        let side = |levels: &mut dyn Iterator<Item = (&u64, &u64)>| levels.map(|(&price, &quantity)| (price as f64, quantity as f64)).collect();
+        (side(&mut bids.iter().rev()), side(&mut asks.iter()))
+    }
+
+    // follow follows frames with faults injected by chaos, if any, returning the client's
+    // result and the book after every update, by the ts of its last delta.
+    fn follow(chaos: Option<Arc<Chaos>>) -> (Result<(), ClientError>, Vec<(u64, Levels)>) {
+        let connector = Arc::new(ScriptedConnector::default().with_script(frames()));
+        let feed = FeedConfig { backend: SocketBackend::Transport(connector), chaos, ..FeedConfig::default() };
+        let data = SnapshotData { bids: vec![RestQuote { price: 95.0, quantity: 1.0 }], asks: vec![RestQuote { price: 101.0, quantity: 1.0 }] };
+        let source = ScriptedSource::new(vec![RestSnapshot::new(1000, 1000, data)]);
+        let client = WooxClient::builder().symbol(SYMBOL).feed_config(feed).snapshot_source(Box::new(source)).build();
+        let mut books = Vec::new();
+        let result = client.run_with(|book, event| books.push((event.ts, levels(book))));
+        (result, books)
+    }
+
+    #[test]
+    fn seeded_faults_leave_the_fault_free_book_or_resync() {
+        let (result, books) = follow(None);
+        assert!(result.is_ok(), "{:?}", result);
+        let last = 1000 + DELTAS * 100;
+        assert_eq!(books.last(), Some(&(last, fault_free(last))));
+
+        let (mut completed, mut resynced) = (0, 0);
+        for seed in 0..20 {
+            let config = ChaosConfig::new(seed).with_drop(0.03).with_duplicate(0.1).with_reorder(0.03);
+            let chaos = Arc::new(Chaos::new(config));
+            let (result, books) = follow(Some(Arc::clone(&chaos)));
+            let stats = chaos.stats();
+            let faults = [&stats.dropped, &stats.duplicated, &stats.reordered].map(|count| count.load(Ordering::Relaxed));
+            match result {
+                // Every book the client reported must be the fault-free book as of the same
+                // delta: duplicates are skipped, and nothing lost was papered over.
+                Ok(()) => {
+                    for (ts, book) in &books {
+                        assert_eq!(book, &fault_free(*ts), "seed {} at {} with faults {:?}", seed, ts, faults);
+                    }
+                    completed += 1;
+                }
+                // A lost or reordered delta must be caught, for the book to be resynced: out
+                // of sync once synced, or before, a refetch of the snapshot the stream never
+                // continued, which finds none left.
+                Err(ClientError::OutOfSync | ClientError::Snapshot(_)) => {
+                    assert!(faults[0] + faults[2] > 0, "seed {} out of sync without a lost or reordered delta", seed);
+                    resynced += 1;
+                }
+                Err(e) => panic!("seed {} failed with {:?}", seed, e),
+            }
+        }
+        // The seeds cover both outcomes, so neither check is vacuous.
+        assert!(completed > 0 && resynced > 0, "{} completed, {} resynced", completed, resynced);
+    }
+}
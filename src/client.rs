@@ -0,0 +1,1209 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+#[cfg(feature = "async")]
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "async")]
+use tokio::sync::mpsc::UnboundedReceiver;
+#[cfg(feature = "async")]
+use tracing::Instrument;
+use tracing::{info, info_span, trace, warn};
+
+use crate::arbitrator::ArbitrationMetrics;
+use crate::exchange::{ExchangeFeed, ExchangeSource, WsError};
+use crate::exchange_api_types::RestSnapshot;
+use crate::feed::{self, FeedConfig, MarketEvent};
+use crate::invariants::{InvariantChecker, Violation};
+use crate::orderbook::LocalOrderBook;
+use crate::peer::SnapshotRegistry;
+use crate::poll::{self, PollMode};
+use crate::queue;
+use crate::shared_book::SharedBookWriter;
+use crate::sink::Sink;
+use crate::snapshot::{self, CheckpointSource, SnapshotError, SnapshotSource};
+use crate::sync::{BookSync, SnapshotAttempts, SnapshotCheck, SyncOutcome};
+
+pub const DEFAULT_MAX_LEVEL: usize = 50;
+pub const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+// WARM_START_TIMEOUT bounds how long a quiet stream is read for whether it continues the
+// checkpoint warm started from.
+const WARM_START_TIMEOUT: Duration = Duration::from_secs(4);
+
+// FollowState is the book a client is following, when it was last checkpointed and
+// whether it has been reported stale since the last delta.
+struct FollowState {
+    sync: BookSync,
+    last_checkpoint: Instant,
+    // last_event is the ts and seq of the last event applied.
+    last_event: Option<(u64, u64)>,
+    stale: bool,
+}
+
+// ClientError is returned when a WooxClient stops following its symbol.
+#[derive(Debug)]
+pub enum ClientError {
+    Snapshot(SnapshotError),
+    // OutOfSync means the stream moved past the snapshot before the book could sync, or
+    // stopped continuing the synced book.
+    OutOfSync,
+    // ChecksumMismatch means the book diverged from the exchange's, failing the checksum
+    // sent with an event.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    // InvariantViolated means the book broke an invariant checked by the invariant checker.
+    InvariantViolated(Vec<Violation>),
+    // Rejected means the exchange rejected the subscription, such as for a misspelled
+    // symbol, so nothing would ever be streamed.
+    Rejected(WsError),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Snapshot(e) => write!(f, "snapshot failed: {}", e),
+            ClientError::OutOfSync => write!(f, "book out of sync with the stream"),
+            ClientError::ChecksumMismatch { expected, actual } => {
+                write!(f, "book checksum mismatch, expected {} but computed {}", expected, actual)
+            }
+            ClientError::InvariantViolated(violations) => {
+                write!(f, "book invariant violated")?;
+                for violation in violations {
+                    write!(f, "; {}", violation)?;
+                }
+                Ok(())
+            }
+            ClientError::Rejected(e) => write!(f, "stream rejected: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<SnapshotError> for ClientError {
+    fn from(e: SnapshotError) -> Self {
+        ClientError::Snapshot(e)
+    }
+}
+
+// Rejections is the errors a client's own connections report, forwarded on to the sender
+// the feed config had, if any, as they are checked.
+struct Rejections {
+    errors: Receiver<WsError>,
+    forward: Option<Sender<WsError>>,
+}
+
+impl Rejections {
+    // check forwards the errors reported since the last check, returning the first fatal
+    // one as the client's error.
+    fn check(&self) -> Result<(), ClientError> {
+        for error in self.errors.try_iter() {
+            if let Some(forward) = &self.forward {
+                let _ = forward.send(error.clone());
+            }
+            if error.is_fatal() {
+                return Err(ClientError::Rejected(error));
+            }
+        }
+        Ok(())
+    }
+}
+
+// BookUpdate is passed to the update callback after each delta is applied to the book.
+pub struct BookUpdate<'a> {
+    pub symbol: &'a str,
+    pub event: &'a MarketEvent,
+    pub book: &'a LocalOrderBook,
+    // synced is true for the first update after the book synced from its snapshot.
+    pub synced: bool,
+    // recovered is true for the first update after the book was reported stale.
+    pub recovered: bool,
+    // arbitration holds the redundant feed metrics, if the client is redundant.
+    pub arbitration: Option<&'a ArbitrationMetrics>,
+    // coalesced is how many deltas were applied for this update, event being the last. It
+    // is 1 unless the client coalesces queued deltas.
+    pub coalesced: usize,
+}
+
+type UpdateCallback = Box<dyn FnMut(&BookUpdate) + Send>;
+type IdleCallback = Box<dyn FnMut(&LocalOrderBook, Option<&ArbitrationMetrics>) + Send>;
+
+// NoSymbol and Symbol track whether a WooxClientBuilder has been given its symbol, so a
+// client can't be built without one.
+pub struct NoSymbol;
+pub struct Symbol(String);
+
+// WooxClientBuilder configures a WooxClient. Everything but the symbol has a default: a
+// single 50 level Woo X feed, seeded from the exchange's snapshot endpoint once the stream
+// has started.
+pub struct WooxClientBuilder<S> {
+    symbol: S,
+    max_level: usize,
+    feed: FeedConfig,
+    redundant: bool,
+    source: Option<Box<dyn SnapshotSource>>,
+    sinks: Vec<Box<dyn Sink>>,
+    registry: Option<SnapshotRegistry>,
+    checkpoint: Option<(PathBuf, Duration)>,
+    stale_after: Option<Duration>,
+    invariants: Option<InvariantChecker>,
+    max_batch: usize,
+    shared: Option<SharedBookWriter>,
+    events: Option<queue::Receiver<MarketEvent>>,
+    book_core: Option<usize>,
+    warm_start: Option<(PathBuf, Duration)>,
+    on_update: Option<UpdateCallback>,
+    on_idle: Option<(Duration, IdleCallback)>,
+}
+
+impl<S> WooxClientBuilder<S> {
+    pub fn symbol(self, symbol: impl Into<String>) -> WooxClientBuilder<Symbol> {
+        WooxClientBuilder {
+            symbol: Symbol(symbol.into()),
+            max_level: self.max_level,
+            feed: self.feed,
+            redundant: self.redundant,
+            source: self.source,
+            sinks: self.sinks,
+            registry: self.registry,
+            checkpoint: self.checkpoint,
+            stale_after: self.stale_after,
+            invariants: self.invariants,
+            max_batch: self.max_batch,
+            shared: self.shared,
+            events: self.events,
+            book_core: self.book_core,
+            warm_start: self.warm_start,
+            on_update: self.on_update,
+            on_idle: self.on_idle,
+        }
+    }
+
+    // depth sets the number of levels subscribed to and fetched in the snapshot.
+    pub fn depth(mut self, max_level: usize) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    pub fn feed_config(mut self, feed: FeedConfig) -> Self {
+        self.feed = feed;
+        self
+    }
+
+    // exchange sets the venue the book is followed on.
+    pub fn exchange(mut self, exchange: Arc<dyn ExchangeFeed>) -> Self {
+        self.feed.exchange = exchange;
+        self
+    }
+
+    // poll_mode sets whether the reader and book threads block or busy poll for events.
+    pub fn poll_mode(mut self, poll_mode: PollMode) -> Self {
+        self.feed.poll_mode = poll_mode;
+        self
+    }
+
+    // pin pins the reader threads to reader_core and the thread run follows the book on to
+    // book_core, for busy polling each on a core of its own.
+    pub fn pin(mut self, reader_core: Option<usize>, book_core: Option<usize>) -> Self {
+        self.feed.reader_core = reader_core;
+        self.book_core = book_core;
+        self
+    }
+
+    // redundant opens a second websocket connection and arbitrates between the two.
+    pub fn redundant(mut self, redundant: bool) -> Self {
+        self.redundant = redundant;
+        self
+    }
+
+    pub fn snapshot_source(mut self, source: Box<dyn SnapshotSource>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    // sink adds a sink the snapshot and every applied delta are written to.
+    pub fn sink(mut self, sink: Box<dyn Sink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    // registry publishes the synced book to registry, for serving to peer instances.
+    pub fn registry(mut self, registry: SnapshotRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    // checkpoint saves the synced book to path every interval.
+    pub fn checkpoint(mut self, path: impl Into<PathBuf>, interval: Duration) -> Self {
+        self.checkpoint = Some((path.into(), interval));
+        self
+    }
+
+    // warm_start seeds the book from the checkpoint at path, if it is younger than max_age,
+    // as long as the stream continues from it, rather than fetching a snapshot. If the
+    // stream has moved past the checkpoint, the deltas read meanwhile are applied on top of
+    // the snapshot fetched as usual.
+    pub fn warm_start(mut self, path: impl Into<PathBuf>, max_age: Duration) -> Self {
+        self.warm_start = Some((path.into(), max_age));
+        self
+    }
+
+    // stale_after reports the book stale to the sinks when no delta has been applied to it
+    // for threshold, so consumers don't act on a frozen book.
+    pub fn stale_after(mut self, threshold: Duration) -> Self {
+        self.stale_after = Some(threshold);
+        self
+    }
+
+    // check_invariants checks the book after every delta, stopping with the violations and
+    // logging the delta's raw message and the book if it is crossed, has a level without a
+    // positive quantity or more than max_levels levels a side, or a delta goes back in time.
+    // The feed keeps the raw messages for it whatever feed config is set.
+    pub fn check_invariants(mut self, max_levels: usize) -> Self {
+        self.invariants = Some(InvariantChecker::new(max_levels));
+        self
+    }
+
+    // coalesce applies up to max_batch deltas already queued when one is received before
+    // passing the book on once for them all, to the update callbacks and registry, so a burst
+    // isn't rendered and published delta by delta. Sinks are still given every delta.
+    pub fn coalesce(mut self, max_batch: usize) -> Self {
+        self.max_batch = max_batch.max(1);
+        self
+    }
+
+    // shared_book publishes the top of the book to writer's readers after every update, for
+    // other threads to read without blocking the client.
+    pub fn shared_book(mut self, writer: SharedBookWriter) -> Self {
+        self.shared = Some(writer);
+        self
+    }
+
+    // events follows the book from events, such as a Fanout subscription to a stream shared
+    // with other consumers, rather than connecting a stream of its own.
+    pub fn events(mut self, events: queue::Receiver<MarketEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn on_update<F>(mut self, on_update: F) -> Self
+    where
+        F: FnMut(&BookUpdate) + Send + 'static,
+    {
+        self.on_update = Some(Box::new(on_update));
+        self
+    }
+
+    // on_idle calls on_idle with the book, and the redundant feed metrics if the client is
+    // redundant, each time interval passes without an update once the book is synced, such
+    // as to catch up on a render held back by a frame rate limit.
+    pub fn on_idle<F>(mut self, interval: Duration, on_idle: F) -> Self
+    where
+        F: FnMut(&LocalOrderBook, Option<&ArbitrationMetrics>) + Send + 'static,
+    {
+        self.on_idle = Some((interval, Box::new(on_idle)));
+        self
+    }
+}
+
+impl WooxClientBuilder<Symbol> {
+    pub fn build(self) -> WooxClient {
+        let exchange = Arc::clone(&self.feed.exchange);
+        let keep_raw = self.feed.keep_raw || self.invariants.is_some();
+        WooxClient {
+            symbol: self.symbol.0,
+            max_level: self.max_level,
+            feed: FeedConfig { keep_raw, ..self.feed },
+            redundant: self.redundant,
+            source: self.source.map_or_else(
+                || Arc::new(ExchangeSource::new(exchange)) as Arc<dyn SnapshotSource>,
+                Arc::from,
+            ),
+            sinks: self.sinks,
+            registry: self.registry,
+            checkpoint: self.checkpoint,
+            stale_after: self.stale_after,
+            invariants: self.invariants,
+            max_batch: self.max_batch,
+            shared: self.shared,
+            events: self.events,
+            book_core: self.book_core,
+            warm_start: self.warm_start,
+            on_update: self.on_update,
+            on_idle: self.on_idle,
+            rejections: None,
+        }
+    }
+}
+
+// WooxClient follows the order book for a single symbol: it connects to the websocket,
+// seeds the book from a snapshot fetched once the stream starts, buffering the deltas read
+// meanwhile, and then applies every delta, passing the book to the update callback, sinks,
+// registry and checkpoint as configured.
+pub struct WooxClient {
+    symbol: String,
+    max_level: usize,
+    feed: FeedConfig,
+    redundant: bool,
+    source: Arc<dyn SnapshotSource>,
+    sinks: Vec<Box<dyn Sink>>,
+    registry: Option<SnapshotRegistry>,
+    checkpoint: Option<(PathBuf, Duration)>,
+    stale_after: Option<Duration>,
+    invariants: Option<InvariantChecker>,
+    max_batch: usize,
+    shared: Option<SharedBookWriter>,
+    events: Option<queue::Receiver<MarketEvent>>,
+    book_core: Option<usize>,
+    warm_start: Option<(PathBuf, Duration)>,
+    on_update: Option<UpdateCallback>,
+    on_idle: Option<(Duration, IdleCallback)>,
+    rejections: Option<Rejections>,
+}
+
+impl WooxClient {
+    pub fn builder() -> WooxClientBuilder<NoSymbol> {
+        WooxClientBuilder {
+            symbol: NoSymbol,
+            max_level: DEFAULT_MAX_LEVEL,
+            feed: FeedConfig::default(),
+            redundant: false,
+            source: None,
+            sinks: Vec::new(),
+            registry: None,
+            checkpoint: None,
+            stale_after: None,
+            invariants: None,
+            max_batch: 1,
+            shared: None,
+            events: None,
+            book_core: None,
+            warm_start: None,
+            on_update: None,
+            on_idle: None,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn max_level(&self) -> usize {
+        self.max_level
+    }
+
+    pub fn feed_config(&self) -> &FeedConfig {
+        &self.feed
+    }
+
+    // run connects and follows the book until the stream ends, returning an error if the
+    // book can't be synced.
+    pub fn run(self) -> Result<(), ClientError> {
+        self.follow(|_| {})
+    }
+
+    // run_with is run with a callback called after every update is applied, in addition to
+    // the update callback. Unlike the update callback it can borrow from the caller, for a
+    // simple synchronous loop without channels.
+    pub fn run_with<F>(self, mut on_update: F) -> Result<(), ClientError>
+    where
+        F: FnMut(&LocalOrderBook, &MarketEvent),
+    {
+        self.follow(|update| on_update(update.book, update.event))
+    }
+
+    fn follow<F>(mut self, mut on_update: F) -> Result<(), ClientError>
+    where
+        F: FnMut(&BookUpdate),
+    {
+        let _span = info_span!("book", symbol = %self.symbol).entered();
+        if let Some(core) = self.book_core {
+            poll::pin_current_thread(core);
+        }
+        let (receiver, arbitration) = self.connect();
+
+        let mut batch = Vec::with_capacity(self.max_batch);
+        let snapshot = match self.warm_start(&receiver, &mut batch) {
+            Some(checkpoint) => checkpoint,
+            None => match self.sync_snapshot(&receiver, &mut batch)? {
+                Some(snapshot) => snapshot,
+                None => return self.check_rejected(),
+            },
+        };
+        let mut state = self.seed(snapshot);
+        let applied = self.apply(&mut state, &batch, arbitration.as_deref(), &mut on_update);
+        batch.clear();
+        applied?;
+
+        loop {
+            self.check_rejected()?;
+            let event = match self.idle_timeout() {
+                None => poll::recv(&receiver, self.feed.poll_mode).ok_or(RecvTimeoutError::Disconnected),
+                Some(timeout) => poll::recv_timeout(&receiver, self.feed.poll_mode, timeout),
+            };
+            match event {
+                Ok(event) => {
+                    batch.push(event);
+                    batch.extend(receiver.try_iter().take(self.max_batch - 1));
+                    let applied = self.apply(&mut state, &batch, arbitration.as_deref(), &mut on_update);
+                    batch.clear();
+                    applied?
+                }
+                Err(RecvTimeoutError::Timeout) => self.on_idle(&mut state, arbitration.as_deref()),
+                Err(RecvTimeoutError::Disconnected) => {
+                    // The book is checkpointed as it stops, for a restart to warm start from.
+                    self.save_checkpoint(&mut state, true);
+                    return self.check_rejected();
+                }
+            }
+        }
+    }
+
+    // warm_start returns the checkpoint to warm start from, if there is a fresh one and the
+    // stream continues from it, reading events into buffered until that is known.
+    fn warm_start(&self, receiver: &queue::Receiver<MarketEvent>, buffered: &mut Vec<MarketEvent>) -> Option<RestSnapshot> {
+        let (checkpoint, mut probe) = self.warm_start_probe()?;
+        let deadline = Instant::now() + WARM_START_TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let Ok(event) = poll::recv_timeout(receiver, self.feed.poll_mode, timeout) else { return None };
+            let continues = continues_checkpoint(&mut probe, &checkpoint, &event);
+            buffered.push(event);
+            match continues {
+                Some(true) => return Some(checkpoint),
+                Some(false) => return None,
+                None => {}
+            }
+        }
+    }
+
+    // warm_start_probe returns the checkpoint to warm start from if there is a fresh one,
+    // with a probe syncing a copy of it, so the book is only seeded from it once the stream
+    // is known to continue it.
+    fn warm_start_probe(&self) -> Option<(RestSnapshot, BookSync)> {
+        let (path, max_age) = self.warm_start.as_ref()?;
+        match CheckpointSource::new(path, *max_age).fetch(&self.symbol, self.max_level) {
+            Ok(checkpoint) => {
+                let probe = BookSync::for_exchange(checkpoint.clone(), self.feed.exchange.as_ref());
+                Some((checkpoint, probe))
+            }
+            Err(e) => {
+                info!(path = %path.display(), error = %e, "Not warm starting from the checkpoint");
+                None
+            }
+        }
+    }
+
+    // sync_snapshot fetches the snapshot to seed the book from once the stream has started,
+    // reading events into buffered until then and while the fetch is in flight, and
+    // refetching snapshots the stream has moved past as SnapshotAttempts directs. It
+    // returns None if the stream ends before it starts.
+    fn sync_snapshot(&self, receiver: &queue::Receiver<MarketEvent>, buffered: &mut Vec<MarketEvent>) -> Result<Option<RestSnapshot>, ClientError> {
+        let mut attempts = SnapshotAttempts::new(self.feed.exchange.sync_rule());
+        loop {
+            self.check_rejected()?;
+            if !attempts.ready(buffered) {
+                if buffered.is_empty() {
+                    info!("Waiting for the stream to start before fetching the snapshot");
+                }
+                match poll::recv(receiver, self.feed.poll_mode) {
+                    Some(event) => buffered.push(event),
+                    None if buffered.is_empty() => return Ok(None),
+                    None => break,
+                }
+                continue;
+            }
+            attempts.fetching(buffered);
+            let snapshot = fetch_snapshot(self.source.as_ref(), &self.symbol, self.max_level)?;
+            buffered.extend(receiver.try_iter());
+            match attempts.check(snapshot, buffered) {
+                SnapshotCheck::Sync(snapshot) => return Ok(Some(snapshot)),
+                SnapshotCheck::Refetch => {}
+                SnapshotCheck::OutOfSync => break,
+            }
+        }
+        self.check_rejected()?;
+        Err(ClientError::OutOfSync)
+    }
+
+    // seed syncs a new book from snapshot, which is written to the sinks.
+    fn seed(&mut self, snapshot: RestSnapshot) -> FollowState {
+        let snapshot_ts = snapshot.timestamp;
+        let sync = BookSync::for_exchange(snapshot, self.feed.exchange.as_ref());
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.record_snapshot(&self.symbol, snapshot_ts, sync.book()) {
+                warn!(sink = sink.name(), error = %e, "Sink failed");
+            }
+        }
+
+        info!("Syncing book with the stream");
+        FollowState { sync, last_checkpoint: Instant::now(), last_event: None, stale: false }
+    }
+
+    // idle_timeout is how long to wait for an event before the stream is idle, for the stale
+    // check and idle callback, or None to wait as long as it takes if neither is set.
+    fn idle_timeout(&self) -> Option<Duration> {
+        let idle = self.on_idle.as_ref().map(|(interval, _)| *interval);
+        match (self.stale_after, idle) {
+            (Some(threshold), Some(interval)) => Some(threshold.min(interval)),
+            (threshold, interval) => threshold.or(interval),
+        }
+    }
+
+    // on_idle checks whether the book has gone stale and calls the idle callback, once no
+    // event has arrived within the idle timeout.
+    fn on_idle(&mut self, state: &mut FollowState, arbitration: Option<&ArbitrationMetrics>) {
+        self.check_stale(state);
+        if let Some((_, on_idle)) = &mut self.on_idle {
+            on_idle(state.sync.book(), arbitration);
+        }
+    }
+
+    // check_stale reports the book stale to the sinks if no delta has been applied to it
+    // within the stale threshold and it hasn't been reported since the last delta.
+    fn check_stale(&mut self, state: &mut FollowState) {
+        let Some(threshold) = self.stale_after else { return };
+        let book = state.sync.book();
+        if state.stale || !book.is_stale(threshold) {
+            return;
+        }
+        state.stale = true;
+        warn!(last_update_ts = book.last_update_ts(), ?threshold, "Book is stale");
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.record_stale(&self.symbol, threshold, book) {
+                warn!(sink = sink.name(), error = %e, "Sink failed");
+            }
+        }
+    }
+
+    // apply applies a batch of events to the book, writing each to the sinks, and then
+    // passes the book on to the callbacks, registry and checkpoint once for the batch. Both
+    // the blocking and async loops are built on seed and apply.
+    fn apply<F>(
+        &mut self,
+        state: &mut FollowState,
+        events: &[MarketEvent],
+        arbitration: Option<&ArbitrationMetrics>,
+        on_update: &mut F,
+    ) -> Result<(), ClientError>
+    where
+        F: FnMut(&BookUpdate),
+    {
+        let (mut last, mut synced, mut coalesced) = (None, false, 0);
+        for event in events {
+            if let Some(event_synced) = self.apply_event(state, event)? {
+                last = Some(event);
+                synced |= event_synced;
+                coalesced += 1;
+            }
+        }
+        let Some(event) = last else { return Ok(()) };
+
+        let recovered = std::mem::take(&mut state.stale);
+        if recovered {
+            info!(ts = event.ts, "Book is updating again");
+        }
+
+        let book = state.sync.book();
+        if let Some(shared) = &self.shared {
+            shared.publish(event.ts, book);
+        }
+        let update = BookUpdate {
+            symbol: &self.symbol,
+            event,
+            book,
+            synced,
+            recovered,
+            arbitration,
+            coalesced,
+        };
+        if let Some(callback) = self.on_update.as_mut() {
+            callback(&update);
+        }
+        on_update(&update);
+
+        if let Some(registry) = &self.registry {
+            registry.publish(&self.symbol, event.ts, event.seq, book);
+        }
+
+        state.last_event = Some((event.ts, event.seq));
+        self.save_checkpoint(state, false);
+        Ok(())
+    }
+
+    // save_checkpoint checkpoints the synced book once the checkpoint interval has passed
+    // since the last, or regardless if forced.
+    fn save_checkpoint(&self, state: &mut FollowState, force: bool) {
+        let Some((path, interval)) = &self.checkpoint else { return };
+        let Some((ts, seq)) = state.last_event.filter(|_| state.sync.is_synced()) else { return };
+        if !force && state.last_checkpoint.elapsed() < *interval {
+            return;
+        }
+        if let Err(e) = snapshot::save_checkpoint(path, state.sync.book(), ts, seq) {
+            warn!(path = %path.display(), error = %e, "Failed to save checkpoint");
+        }
+        state.last_checkpoint = Instant::now();
+    }
+
+    // apply_event applies event to the book and writes it to the sinks, returning whether
+    // it synced the book, or None if the stream is still behind the snapshot.
+    fn apply_event(&mut self, state: &mut FollowState, event: &MarketEvent) -> Result<Option<bool>, ClientError> {
+        let sync = &mut state.sync;
+        let synced = match sync.on_event(event) {
+            SyncOutcome::Behind(diff) => {
+                trace!(behind_ms = diff, "Stream is behind the snapshot");
+                return Ok(None);
+            }
+            SyncOutcome::Synced => {
+                info!(ts = event.ts, "Local book is now synced");
+                true
+            }
+            SyncOutcome::Applied => false,
+            SyncOutcome::OutOfSync => {
+                if let Some(registry) = &self.registry {
+                    registry.remove(&self.symbol);
+                }
+                return Err(ClientError::OutOfSync);
+            }
+            SyncOutcome::ChecksumMismatch { expected, actual } => {
+                warn!(ts = event.ts, expected, actual, "Book failed the exchange checksum");
+                if let Some(registry) = &self.registry {
+                    registry.remove(&self.symbol);
+                }
+                return Err(ClientError::ChecksumMismatch { expected, actual });
+            }
+        };
+
+        trace!(ts = event.ts, prev_ts = event.prev_ts, "Applied delta");
+        if let Some(checker) = self.invariants.as_mut() {
+            let violations = checker.check(&self.symbol, event, sync.book());
+            if !violations.is_empty() {
+                if let Some(registry) = &self.registry {
+                    registry.remove(&self.symbol);
+                }
+                return Err(ClientError::InvariantViolated(violations));
+            }
+        }
+
+        for sink in &mut self.sinks {
+            if let Err(e) = sink.record_delta(&self.symbol, event, sync.book()) {
+                warn!(sink = sink.name(), error = %e, "Sink failed");
+            }
+        }
+        Ok(Some(synced))
+    }
+
+    // check_rejected returns the fatal error the exchange sent the client's connections, if
+    // one has.
+    fn check_rejected(&self) -> Result<(), ClientError> {
+        self.rejections.as_ref().map_or(Ok(()), Rejections::check)
+    }
+
+    // watch_rejections has the connections the client opens report the errors the exchange
+    // sends to the client.
+    fn watch_rejections(&mut self) {
+        let (sender, errors) = mpsc::channel();
+        let forward = self.feed.errors.replace(sender);
+        self.rejections = Some(Rejections { errors, forward });
+    }
+
+    fn connect(&mut self) -> (queue::Receiver<MarketEvent>, Option<Arc<ArbitrationMetrics>>) {
+        if let Some(events) = self.events.take() {
+            return (events, None);
+        }
+        self.watch_rejections();
+        if self.redundant {
+            let (receiver, metrics) = feed::connect_redundant_stream(&self.feed, &self.symbol, self.max_level);
+            (receiver, Some(metrics))
+        } else {
+            (feed::connect_stream(&self.feed, &self.symbol, self.max_level), None)
+        }
+    }
+}
+
+// continues_checkpoint offers event to probe, synced from checkpoint, and returns whether
+// the stream continues the checkpoint once the event tells, or None while it is behind it.
+fn continues_checkpoint(probe: &mut BookSync, checkpoint: &RestSnapshot, event: &MarketEvent) -> Option<bool> {
+    match probe.on_event(event) {
+        SyncOutcome::Behind(_) => None,
+        SyncOutcome::Synced | SyncOutcome::Applied => {
+            info!(ts = checkpoint.timestamp, "Warm starting from the checkpoint");
+            Some(true)
+        }
+        SyncOutcome::OutOfSync | SyncOutcome::ChecksumMismatch { .. } => {
+            info!(ts = checkpoint.timestamp, "Stream has moved past the checkpoint, fetching a snapshot");
+            Some(false)
+        }
+    }
+}
+
+// fetch_snapshot fetches the snapshot the book for symbol is seeded from.
+fn fetch_snapshot(source: &dyn SnapshotSource, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+    let _span = info_span!("snapshot_fetch", %symbol, source = source.name()).entered();
+    info!("Fetching snapshot");
+    let snapshot = source.fetch(symbol, max_level)?;
+    info!(ts = snapshot.timestamp, "Snapshot received");
+    Ok(snapshot)
+}
+
+// The async API drives the same seed and apply steps as the blocking one, awaiting events
+// and the snapshot fetch instead of blocking on them.
+#[cfg(feature = "async")]
+impl WooxClient {
+    // run_async is run for use on a tokio runtime.
+    pub async fn run_async(self) -> Result<(), ClientError> {
+        let span = info_span!("book", symbol = %self.symbol);
+        self.follow_async(|_| {}).instrument(span).await
+    }
+
+    // run_async_with is run_with for use on a tokio runtime.
+    pub async fn run_async_with<F>(self, mut on_update: F) -> Result<(), ClientError>
+    where
+        F: FnMut(&LocalOrderBook, &MarketEvent),
+    {
+        let span = info_span!("book", symbol = %self.symbol);
+        self.follow_async(|update| on_update(update.book, update.event)).instrument(span).await
+    }
+
+    async fn follow_async<F>(mut self, mut on_update: F) -> Result<(), ClientError>
+    where
+        F: FnMut(&BookUpdate),
+    {
+        let (mut receiver, arbitration) = if let Some(events) = self.events.take() {
+            // The blocking receiver is bridged onto a tokio channel by a thread of its own.
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            thread::spawn(move || {
+                for event in events {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            });
+            (rx, None)
+        } else if self.redundant {
+            self.watch_rejections();
+            let (receiver, metrics) = feed::connect_redundant_stream_async(&self.feed, &self.symbol, self.max_level);
+            (receiver, Some(metrics))
+        } else {
+            self.watch_rejections();
+            (feed::connect_stream_async(&self.feed, &self.symbol, self.max_level), None)
+        };
+
+        let mut batch = Vec::with_capacity(self.max_batch);
+        let snapshot = match self.warm_start_async(&mut receiver, &mut batch).await {
+            Some(checkpoint) => checkpoint,
+            None => match self.sync_snapshot_async(&mut receiver, &mut batch).await? {
+                Some(snapshot) => snapshot,
+                None => return self.check_rejected(),
+            },
+        };
+        let mut state = self.seed(snapshot);
+        let applied = self.apply(&mut state, &batch, arbitration.as_deref(), &mut on_update);
+        batch.clear();
+        applied?;
+
+        loop {
+            self.check_rejected()?;
+            let event = match self.idle_timeout() {
+                None => receiver.recv().await,
+                Some(timeout) => match tokio::time::timeout(timeout, receiver.recv()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        self.on_idle(&mut state, arbitration.as_deref());
+                        continue;
+                    }
+                },
+            };
+            match event {
+                Some(event) => {
+                    batch.push(event);
+                    while batch.len() < self.max_batch {
+                        match receiver.try_recv() {
+                            Ok(event) => batch.push(event),
+                            Err(_) => break,
+                        }
+                    }
+                    let applied = self.apply(&mut state, &batch, arbitration.as_deref(), &mut on_update);
+                    batch.clear();
+                    applied?
+                }
+                None => {
+                    self.save_checkpoint(&mut state, true);
+                    return self.check_rejected();
+                }
+            }
+        }
+    }
+
+    // sync_snapshot_async is sync_snapshot, awaiting the events read and the snapshot fetch.
+    async fn sync_snapshot_async(&self, receiver: &mut UnboundedReceiver<MarketEvent>, buffered: &mut Vec<MarketEvent>) -> Result<Option<RestSnapshot>, ClientError> {
+        let mut attempts = SnapshotAttempts::new(self.feed.exchange.sync_rule());
+        loop {
+            self.check_rejected()?;
+            if !attempts.ready(buffered) {
+                if buffered.is_empty() {
+                    info!("Waiting for the stream to start before fetching the snapshot");
+                }
+                match receiver.recv().await {
+                    Some(event) => buffered.push(event),
+                    None if buffered.is_empty() => return Ok(None),
+                    None => break,
+                }
+                continue;
+            }
+            attempts.fetching(buffered);
+            let source = Arc::clone(&self.source);
+            let (symbol, max_level) = (self.symbol.clone(), self.max_level);
+            let snapshot = tokio::task::spawn_blocking(move || fetch_snapshot(source.as_ref(), &symbol, max_level))
+                .await
+                .expect("Snapshot fetch panicked")?;
+            while let Ok(event) = receiver.try_recv() {
+                buffered.push(event);
+            }
+            match attempts.check(snapshot, buffered) {
+                SnapshotCheck::Sync(snapshot) => return Ok(Some(snapshot)),
+                SnapshotCheck::Refetch => {}
+                SnapshotCheck::OutOfSync => break,
+            }
+        }
+        self.check_rejected()?;
+        Err(ClientError::OutOfSync)
+    }
+
+    // warm_start_async is warm_start, awaiting the events read.
+    async fn warm_start_async(&self, receiver: &mut UnboundedReceiver<MarketEvent>, buffered: &mut Vec<MarketEvent>) -> Option<RestSnapshot> {
+        let (checkpoint, mut probe) = self.warm_start_probe()?;
+        let deadline = tokio::time::Instant::now() + WARM_START_TIMEOUT;
+        loop {
+            let Ok(Some(event)) = tokio::time::timeout_at(deadline, receiver.recv()).await else { return None };
+            let continues = continues_checkpoint(&mut probe, &checkpoint, &event);
+            buffered.push(event);
+            match continues {
+                Some(true) => return Some(checkpoint),
+                Some(false) => return None,
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+
+    use super::*;
+    use crate::exchange_api_types::{OrderBookDelta, RestQuote, SnapshotData, WsQuote};
+    use crate::feed::SocketBackend;
+    use crate::sink::SinkResult;
+    use crate::sync::MAX_SNAPSHOT_ATTEMPTS;
+    use crate::transport::{ScriptedConnector, ScriptedSource};
+
+    const SYMBOL: &str = "SPOT_BTC_USDT";
+
+    fn delta(ts: u64, prev_ts: u64, bid: f64) -> String {
+        format!(r#"{{"topic":"orderbookupdate@{}@50","ts":{},"data":{{"prevTs":{},"bids":[["{}","1"]],"asks":[]}}}}"#, SYMBOL, ts, prev_ts, bid)
+    }
+
+    fn snapshot(ts: u64) -> RestSnapshot {
+        let data = SnapshotData {
+            bids: vec![RestQuote { price: 99.0, quantity: 1.0 }],
+            asks: vec![RestQuote { price: 101.0, quantity: 1.0 }],
+        };
+        RestSnapshot::new(ts, ts, data)
+    }
+
+    fn scripted_client(frames: Vec<String>, snapshots: Vec<RestSnapshot>) -> (WooxClientBuilder<Symbol>, Arc<ScriptedConnector>) {
+        let connector = Arc::new(ScriptedConnector::default().with_script(frames));
+        let feed = FeedConfig { backend: SocketBackend::Transport(connector.clone()), ..FeedConfig::default() };
+        let builder = WooxClient::builder().symbol(SYMBOL).feed_config(feed).snapshot_source(Box::new(ScriptedSource::new(snapshots)));
+        (builder, connector)
+    }
+
+    // Followed is how following a book ended: the client's result, how many deltas were
+    // applied, and the ts and best bid of the book after the last.
+    struct Followed {
+        result: Result<(), ClientError>,
+        applied: usize,
+        last_ts: Option<u64>,
+        best_bid: Option<f64>,
+    }
+
+    fn run(builder: WooxClientBuilder<Symbol>) -> Followed {
+        let applied = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&applied);
+        let client = builder.on_update(move |update| { counted.fetch_add(update.coalesced, Ordering::SeqCst); }).build();
+        let (mut last_ts, mut best_bid) = (None, None);
+        let result = client.run_with(|book, event| {
+            last_ts = Some(event.ts);
+            best_bid = book.best_bid().map(|(price, _)| price.value());
+        });
+        Followed { result, applied: applied.load(Ordering::SeqCst), last_ts, best_bid }
+    }
+
+    #[test]
+    fn syncs_on_the_first_delta_continuing_the_snapshot() {
+        let frames = vec![
+            r#"{"id":"client_id_x","event":"subscribe","success":true,"ts":1}"#.to_string(),
+            r#"{"cmd":"PING","ts":5}"#.to_string(),
+            delta(900, 800, 98.0),
+            delta(1100, 1000, 100.0),
+            delta(1200, 1100, 100.5),
+        ];
+        let (builder, connector) = scripted_client(frames, vec![snapshot(1000)]);
+        let followed = run(builder);
+        assert!(followed.result.is_ok(), "{:?}", followed.result);
+        assert_eq!(followed.applied, 2);
+        assert_eq!(followed.last_ts, Some(1200));
+        assert_eq!(followed.best_bid, Some(100.5));
+
+        let sent = connector.sent();
+        assert!(sent[0].contains("SUBSCRIBE"), "{:?}", sent);
+        assert!(sent.iter().any(|frame| frame.contains("PONG")), "{:?}", sent);
+    }
+
+    #[test]
+    fn refetches_a_snapshot_the_stream_has_moved_past() {
+        let frames = vec![delta(1100, 1050, 98.0), delta(1200, 1100, 100.0), delta(1300, 1200, 100.5)];
+        let (builder, _) = scripted_client(frames, vec![snapshot(1000), snapshot(1100)]);
+        let followed = run(builder);
+        assert!(followed.result.is_ok(), "{:?}", followed.result);
+        assert_eq!(followed.applied, 2);
+        assert_eq!(followed.last_ts, Some(1300));
+        assert_eq!(followed.best_bid, Some(100.5));
+    }
+
+    #[test]
+    fn reports_out_of_sync_once_a_delta_is_dropped_after_sync() {
+        // The delta at 1300 is lost, so the one at 1400 doesn't continue the book. The
+        // deltas before it may all be buffered while the snapshot is fetched and applied as
+        // one batch, whose update the error cuts short, so they may never be reported.
+        let frames = vec![delta(1100, 1000, 99.5), delta(1200, 1100, 100.0), delta(1400, 1300, 100.5)];
+        let (builder, _) = scripted_client(frames, vec![snapshot(1000)]);
+        let followed = run(builder);
+        assert!(matches!(followed.result, Err(ClientError::OutOfSync)), "{:?}", followed.result);
+        assert!(followed.applied <= 2, "{}", followed.applied);
+        assert_ne!(followed.best_bid, Some(100.5));
+    }
+
+    // LaggingSource serves snapshots at ts 1000 and, as each is fetched, streams a delta that
+    // has moved past it, so every attempt is behind the stream.
+    struct LaggingSource {
+        fetched: Arc<AtomicUsize>,
+        events: Mutex<queue::Sender<MarketEvent>>,
+    }
+
+    impl SnapshotSource for LaggingSource {
+        fn name(&self) -> &str {
+            "lagging"
+        }
+
+        fn fetch(&self, _symbol: &str, _max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+            let fetched = self.fetched.fetch_add(1, Ordering::SeqCst) as u64 + 1;
+            let _ = self.events.lock().unwrap().send(event(1100 + fetched * 100, 1050 + fetched * 100));
+            Ok(snapshot(1000))
+        }
+    }
+
+    fn event(ts: u64, prev_ts: u64) -> MarketEvent {
+        MarketEvent {
+            ts,
+            prev_ts,
+            seq: ts,
+            prev_seq: prev_ts,
+            delta: OrderBookDelta { prev_ts, bids: vec![WsQuote { price: 98.0, quantity: 1.0 }], asks: Vec::new() },
+            snapshot: false,
+            checksum: None,
+            received_at: Instant::now(),
+            raw: None,
+            text: None,
+        }
+    }
+
+    #[test]
+    fn gives_up_once_the_snapshot_attempts_run_out() {
+        let (sender, events) = queue::bounded(queue::QueueConfig::default(), None);
+        assert!(sender.send(event(1100, 1050)).is_ok());
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let source = LaggingSource { fetched: Arc::clone(&fetched), events: Mutex::new(sender) };
+        let followed = run(WooxClient::builder().symbol(SYMBOL).events(events).snapshot_source(Box::new(source)));
+        assert!(matches!(followed.result, Err(ClientError::OutOfSync)), "{:?}", followed.result);
+        assert_eq!(followed.applied, 0);
+        assert_eq!(fetched.load(Ordering::SeqCst), MAX_SNAPSHOT_ATTEMPTS);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn gives_up_async_once_the_snapshot_attempts_run_out() {
+        let (sender, events) = queue::bounded(queue::QueueConfig::default(), None);
+        assert!(sender.send(event(1100, 1050)).is_ok());
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let source = LaggingSource { fetched: Arc::clone(&fetched), events: Mutex::new(sender) };
+        let client = WooxClient::builder().symbol(SYMBOL).events(events).snapshot_source(Box::new(source)).build();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let result = runtime.block_on(client.run_async());
+        assert!(matches!(result, Err(ClientError::OutOfSync)), "{:?}", result);
+        assert_eq!(fetched.load(Ordering::SeqCst), MAX_SNAPSHOT_ATTEMPTS);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn warm_starts_the_async_client_from_a_fresh_checkpoint() {
+        let path = std::env::temp_dir().join(format!("woox-warm-start-{}.json", std::process::id()));
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64;
+        let mut book = LocalOrderBook::new();
+        book.apply_snapshot(snapshot(now).data);
+        snapshot::save_checkpoint(&path, &book, now, now).unwrap();
+
+        let (sender, events) = queue::bounded(queue::QueueConfig::default(), None);
+        assert!(sender.send(event(now + 100, now)).is_ok());
+        assert!(sender.send(event(now + 200, now + 100)).is_ok());
+        drop(sender);
+        let applied = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&applied);
+        // No snapshot is scripted, so the book can only be seeded from the checkpoint.
+        let client = WooxClient::builder()
+            .symbol(SYMBOL)
+            .events(events)
+            .snapshot_source(Box::new(ScriptedSource::new(Vec::new())))
+            .warm_start(&path, Duration::from_secs(60))
+            .on_update(move |update| { counted.fetch_add(update.coalesced, Ordering::SeqCst); })
+            .build();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+        let result = runtime.block_on(client.run_async());
+        let _ = std::fs::remove_file(&path);
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(applied.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn calls_the_idle_callback_while_the_stream_is_quiet() {
+        let (sender, events) = queue::bounded(queue::QueueConfig::default(), None);
+        assert!(sender.send(event(1100, 1000)).is_ok());
+        let quiet = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            drop(sender);
+        });
+        let idle = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&idle);
+        let client = WooxClient::builder()
+            .symbol(SYMBOL)
+            .events(events)
+            .snapshot_source(Box::new(ScriptedSource::new(vec![snapshot(1000)])))
+            .on_idle(Duration::from_millis(10), move |book, _| seen.lock().unwrap().push(book.last_update_ts()))
+            .build();
+        let result = client.run();
+        quiet.join().unwrap();
+        assert!(result.is_ok(), "{:?}", result);
+        let idle = idle.lock().unwrap();
+        assert!(!idle.is_empty());
+        assert!(idle.iter().all(|&ts| ts == Some(1100)), "{:?}", idle);
+    }
+
+    #[test]
+    fn keeps_raw_messages_for_the_invariant_checker_whatever_feed_config_is_set() {
+        let client = WooxClient::builder().symbol(SYMBOL).check_invariants(50).feed_config(FeedConfig::default()).build();
+        assert!(client.feed_config().keep_raw);
+        let client = WooxClient::builder().symbol(SYMBOL).feed_config(FeedConfig::default()).build();
+        assert!(!client.feed_config().keep_raw);
+    }
+
+    #[test]
+    fn stops_on_a_rejected_subscription() {
+        let frames = vec![r#"{"id":"client_id_x","event":"subscribe","success":false,"ts":1,"errorMsg":"Invalid topic SPOT_BTC_USTD"}"#.to_string()];
+        let (builder, _) = scripted_client(frames, vec![snapshot(1000)]);
+        let followed = run(builder);
+        let error = followed.result.unwrap_err();
+        assert!(matches!(error, ClientError::Rejected(_)), "{:?}", error);
+        assert!(error.to_string().starts_with("stream rejected: "), "{}", error);
+        assert_eq!(followed.applied, 0);
+    }
+
+    // Applied is the ts and best bid of the book after each delta applied.
+    type Applied = Vec<(u64, Option<f64>)>;
+
+    // RecordingSink records the ts and best bid of the book after every delta applied, which
+    // it is written before the batch holding it is cut short by an error.
+    struct RecordingSink(Arc<Mutex<Applied>>);
+
+    impl Sink for RecordingSink {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        fn record_delta(&mut self, _symbol: &str, event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+            self.0.lock().unwrap().push((event.ts, book.best_bid().map(|(price, _)| price.value())));
+            Ok(())
+        }
+    }
+
+    fn bid_event(ts: u64, prev_ts: u64, bid: f64) -> MarketEvent {
+        let delta = OrderBookDelta { prev_ts, bids: vec![WsQuote { price: bid, quantity: 1.0 }], asks: Vec::new() };
+        MarketEvent { delta, ..event(ts, prev_ts) }
+    }
+
+    // follow_events follows events from a snapshot at 1000, returning the client's result,
+    // the ts and best bid after every delta applied, and the registry it published to.
+    fn follow_events(events: Vec<MarketEvent>) -> (Result<(), ClientError>, Applied, SnapshotRegistry) {
+        let (sender, receiver) = queue::bounded(queue::QueueConfig::default(), None);
+        for event in events {
+            assert!(sender.send(event).is_ok());
+        }
+        drop(sender);
+        let applied = Arc::new(Mutex::new(Vec::new()));
+        let registry = SnapshotRegistry::new();
+        let client = WooxClient::builder()
+            .symbol(SYMBOL)
+            .events(receiver)
+            .snapshot_source(Box::new(ScriptedSource::new(vec![snapshot(1000)])))
+            .sink(Box::new(RecordingSink(Arc::clone(&applied))))
+            .registry(registry.clone())
+            .build();
+        let result = client.run();
+        let applied = applied.lock().unwrap().clone();
+        (result, applied, registry)
+    }
+
+    #[test]
+    fn reports_out_of_sync_on_a_gap_after_sync() {
+        let events = vec![bid_event(1100, 1000, 99.5), bid_event(1200, 1100, 100.0), bid_event(1400, 1300, 100.5), bid_event(1500, 1400, 100.2)];
+        let (result, applied, _) = follow_events(events);
+        assert!(matches!(result, Err(ClientError::OutOfSync)), "{:?}", result);
+        // Nothing after the gap is applied, not even the delta continuing the one after it.
+        assert_eq!(applied, vec![(1100, Some(99.5)), (1200, Some(100.0))]);
+    }
+
+    #[test]
+    fn skips_a_duplicate_delta() {
+        let events = vec![bid_event(1100, 1000, 99.5), bid_event(1200, 1100, 100.0), bid_event(1200, 1100, 100.0), bid_event(1300, 1200, 100.5)];
+        let (result, applied, registry) = follow_events(events);
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(applied, vec![(1100, Some(99.5)), (1200, Some(100.0)), (1300, Some(100.5))]);
+        assert!(registry.snapshot_json(SYMBOL, 10).is_some());
+    }
+
+    #[test]
+    fn reports_out_of_sync_on_a_delta_out_of_order() {
+        // 1300 arrives ahead of the 1200 it continues, which is then behind the book.
+        let events = vec![bid_event(1100, 1000, 99.5), bid_event(1300, 1200, 100.5), bid_event(1200, 1100, 100.0)];
+        let (result, applied, _) = follow_events(events);
+        assert!(matches!(result, Err(ClientError::OutOfSync)), "{:?}", result);
+        assert_eq!(applied, vec![(1100, Some(99.5))]);
+    }
+
+    #[test]
+    fn withdraws_the_published_book_on_a_desync_mid_stream() {
+        // After a run of deltas the stream jumps ahead, as a feed resumed elsewhere would.
+        let mut events: Vec<_> = (1..=5).map(|n| bid_event(1000 + n * 100, 900 + n * 100, 99.0 + n as f64 / 10.0)).collect();
+        events.push(bid_event(2100, 2000, 101.0));
+        events.push(bid_event(2200, 2100, 101.5));
+        let (result, applied, registry) = follow_events(events);
+        assert!(matches!(result, Err(ClientError::OutOfSync)), "{:?}", result);
+        assert_eq!(applied.len(), 5);
+        assert_eq!(applied.last(), Some(&(1500, Some(99.5))));
+        // Peers must not be served a book that fell out of sync.
+        assert!(registry.snapshot_json(SYMBOL, 10).is_none());
+    }
+}
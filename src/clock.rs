@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::{debug, warn};
+
+use crate::rest::{RestClient, RestError};
+
+// WINDOW is how many round trip samples the estimate is taken from.
+const WINDOW: usize = 8;
+
+// RoundTrip is a server time sample taken between sent_ms and received_ms on the local
+// clock.
+#[derive(Debug, Clone, Copy)]
+struct RoundTrip {
+    server_ms: u64,
+    sent_ms: u64,
+    received_ms: u64,
+}
+
+impl RoundTrip {
+    fn rtt(&self) -> u64 {
+        self.received_ms.saturating_sub(self.sent_ms)
+    }
+
+    // skew_ms assumes the server read its clock halfway through the round trip.
+    fn skew_ms(&self) -> i64 {
+        let midpoint = self.sent_ms + self.rtt() / 2;
+        self.server_ms as i64 - midpoint as i64
+    }
+}
+
+// ClockSkew estimates how far the exchange's clock is ahead of the local one, so latencies
+// measured from exchange timestamps aren't skewed by the offset between the clocks.
+//
+// The estimate comes from the server time sample with the shortest round trip among the
+// last WINDOW, whose error is at most half its round trip. Exchange pings give a lower
+// bound as well, since a ping can't arrive before it was sent: an estimate below a ping's
+// bound is raised to it.
+#[derive(Debug, Default)]
+pub struct ClockSkew {
+    skew_ms: AtomicI64,
+    estimated: AtomicBool,
+    samples: Mutex<VecDeque<RoundTrip>>,
+}
+
+impl ClockSkew {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    // skew_ms returns the exchange's clock minus the local clock in ms, or None before the
+    // first sample.
+    pub fn skew_ms(&self) -> Option<i64> {
+        self.estimated.load(Ordering::Acquire).then(|| self.skew_ms.load(Ordering::Relaxed))
+    }
+
+    // to_exchange_ms converts local_ms, in ms since the epoch, to the exchange's clock.
+    pub fn to_exchange_ms(&self, local_ms: u64) -> u64 {
+        local_ms.saturating_add_signed(self.skew_ms().unwrap_or(0))
+    }
+
+    // exchange_now_ms returns the current time on the exchange's clock.
+    pub fn exchange_now_ms(&self) -> u64 {
+        self.to_exchange_ms(now_ms())
+    }
+
+    // record_round_trip records the server time server_ms of a request sent at sent_ms and
+    // answered at received_ms, both on the local clock.
+    pub fn record_round_trip(&self, server_ms: u64, sent_ms: u64, received_ms: u64) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(RoundTrip { server_ms, sent_ms, received_ms });
+        let best = samples.iter().min_by_key(|sample| sample.rtt()).unwrap();
+        debug!(skew_ms = best.skew_ms(), rtt_ms = best.rtt(), "Estimated clock skew");
+        self.set(best.skew_ms());
+    }
+
+    // record_ping records a ping timestamped server_ms by the exchange that was received at
+    // received_ms on the local clock.
+    pub fn record_ping(&self, server_ms: u64, received_ms: u64) {
+        let bound = server_ms as i64 - received_ms as i64;
+        if self.skew_ms().is_none_or(|skew| skew < bound) {
+            self.set(bound);
+        }
+    }
+
+    fn set(&self, skew_ms: i64) {
+        self.skew_ms.store(skew_ms, Ordering::Relaxed);
+        self.estimated.store(true, Ordering::Release);
+    }
+
+    // sample takes a server time sample from client.
+    pub fn sample(&self, client: &RestClient) -> Result<(), RestError> {
+        let sent_ms = now_ms();
+        let server_ms = client.server_time()?;
+        self.record_round_trip(server_ms, sent_ms, now_ms());
+        Ok(())
+    }
+
+    // spawn_refresh samples the server time from client on a new thread every interval.
+    pub fn spawn_refresh(self: &Arc<Self>, client: RestClient, interval: Duration) -> thread::JoinHandle<()> {
+        let clock = Arc::clone(self);
+        thread::spawn(move || loop {
+            if let Err(e) = clock.sample(&client) {
+                warn!(error = %e, "Failed to fetch the server time");
+            }
+            thread::sleep(interval);
+        })
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
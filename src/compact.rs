@@ -0,0 +1,361 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::exchange_api_types::{OrderBookDelta, WsQuote};
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::render::Precision;
+use crate::sink::{Sink, SinkResult};
+use crate::units::{Price, Qty};
+
+// MAGIC starts every compact recording, followed by the format version.
+const MAGIC: &[u8; 4] = b"WXCR";
+const VERSION: u8 = 1;
+
+// A record starts with a tag byte: the kind of record, with HAS_CHECKSUM set if an
+// exchange checksum follows the stream position.
+const SNAPSHOT: u8 = 0;
+const DELTA: u8 = 1;
+const HAS_CHECKSUM: u8 = 0x80;
+
+// FLUSH_INTERVAL bounds how much of a recording can be lost if the process dies.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// is_compact returns true if path starts like a compact recording.
+pub fn is_compact(path: &Path) -> bool {
+    let mut magic = [0; 4];
+    File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == MAGIC
+}
+
+// Header is what a compact recording starts with: the symbol recorded and the decimals its
+// prices and quantities are stored with.
+#[derive(Debug, Clone, PartialEq)]
+struct Header {
+    symbol: String,
+    precision: Precision,
+}
+
+impl Header {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(self.precision.price as u8);
+        out.push(self.precision.quantity as u8);
+        put_varint(out, self.symbol.len() as u64);
+        out.extend_from_slice(self.symbol.as_bytes());
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut start = [0; 7];
+        reader.read_exact(&mut start)?;
+        if &start[..4] != MAGIC {
+            return Err(invalid("not a compact recording"));
+        }
+        if start[4] != VERSION {
+            return Err(invalid(format!("unsupported compact recording version {}", start[4])));
+        }
+        let precision = Precision { price: start[5] as usize, quantity: start[6] as usize };
+        let mut symbol = vec![0; get_varint(reader)? as usize];
+        reader.read_exact(&mut symbol)?;
+        let symbol = String::from_utf8(symbol).map_err(|_| invalid("symbol is not UTF-8"))?;
+        Ok(Self { symbol, precision })
+    }
+}
+
+// Coding is the state records are encoded relative to, kept alike by the recorder and the
+// reader. A snapshot resets it, so it never spans a restart of the recorder.
+#[derive(Debug, Default)]
+struct Coding {
+    ts: u64,
+    seq: u64,
+    price: i64,
+    price_scale: f64,
+    quantity_scale: f64,
+}
+
+impl Coding {
+    fn new(precision: Precision) -> Self {
+        Self {
+            price_scale: 10f64.powi(precision.price as i32),
+            quantity_scale: 10f64.powi(precision.quantity as i32),
+            ..Self::default()
+        }
+    }
+
+    fn reset(&mut self) {
+        self.ts = 0;
+        self.seq = 0;
+        self.price = 0;
+    }
+
+    // put_levels encodes levels of (price, quantity), each price relative to the one before.
+    fn put_levels(&mut self, out: &mut Vec<u8>, levels: impl ExactSizeIterator<Item = (f64, f64)>) {
+        put_varint(out, levels.len() as u64);
+        for (price, quantity) in levels {
+            let price = (price * self.price_scale).round() as i64;
+            put_varint(out, zigzag(price.wrapping_sub(self.price)));
+            put_varint(out, (quantity * self.quantity_scale).round() as u64);
+            self.price = price;
+        }
+    }
+
+    fn get_levels(&mut self, reader: &mut impl Read) -> io::Result<Vec<WsQuote>> {
+        let len = get_varint(reader)? as usize;
+        let mut levels = Vec::with_capacity(len.min(1 << 16));
+        for _ in 0..len {
+            self.price = self.price.wrapping_add(unzigzag(get_varint(reader)?));
+            let quantity = get_varint(reader)?;
+            levels.push(WsQuote { price: self.price as f64 / self.price_scale, quantity: quantity as f64 / self.quantity_scale });
+        }
+        Ok(levels)
+    }
+}
+
+// CompactRecorder is a sink recording the synced book for a symbol in a compact binary
+// format, for captures too long to keep as JSON frames. A recording is a header followed
+// by records: a snapshot of the book each time it is (re)synced, written with the first
+// delta applied to it, then every delta applied. Prices and quantities are stored as whole
+// units of the symbol's precision, timestamps and stream positions as varints relative to
+// the record before, and each price as a varint relative to the one before, so a single
+// level delta typically takes under a dozen bytes. Opening an existing recording of the
+// same symbol appends to it.
+pub struct CompactRecorder {
+    path: PathBuf,
+    file: BufWriter<File>,
+    coding: Coding,
+    // pending is the snapshot the book was last synced from, until the first delta applied
+    // to it places it in the stream.
+    pending: Option<(u64, LocalOrderBook)>,
+    buf: Vec<u8>,
+    last_flush: Instant,
+}
+
+impl CompactRecorder {
+    // create opens the recording at path for symbol, with prices and quantities stored to
+    // the decimals of precision, creating it and its directory if needed.
+    pub fn create(path: impl Into<PathBuf>, symbol: &str, precision: Precision) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let header = Header { symbol: symbol.to_string(), precision };
+        let mut file = File::options().create(true).read(true).append(true).open(&path)?;
+        let mut buf = Vec::new();
+        if file.metadata()?.len() == 0 {
+            header.write(&mut buf);
+        } else {
+            let existing = Header::read(&mut BufReader::new(&mut file))?;
+            if existing != header {
+                return Err(invalid(format!("{} records {} at another precision or symbol", path.display(), existing.symbol)));
+            }
+        }
+        let mut recorder = Self {
+            path,
+            file: BufWriter::new(file),
+            coding: Coding::new(precision),
+            pending: None,
+            buf,
+            last_flush: Instant::now(),
+        };
+        recorder.write_buf()?;
+        Ok(recorder)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // record_event writes event, after the snapshot it is the first delta applied to.
+    fn record_event(&mut self, event: &MarketEvent) -> io::Result<()> {
+        if let Some((ts, book)) = self.pending.take() {
+            self.put_snapshot(ts, event.prev_seq, &book);
+        }
+        if event.snapshot {
+            self.coding.reset();
+        }
+        let coding = &mut self.coding;
+        let buf = &mut self.buf;
+        let kind = if event.snapshot { SNAPSHOT } else { DELTA };
+        buf.push(kind | if event.checksum.is_some() { HAS_CHECKSUM } else { 0 });
+        put_varint(buf, zigzag(event.ts.wrapping_sub(coding.ts) as i64));
+        put_varint(buf, zigzag(event.ts.wrapping_sub(event.prev_ts) as i64));
+        put_varint(buf, zigzag(event.seq.wrapping_sub(coding.seq) as i64));
+        put_varint(buf, zigzag(event.seq.wrapping_sub(event.prev_seq) as i64));
+        if let Some(checksum) = event.checksum {
+            put_varint(buf, checksum as u64);
+        }
+        coding.put_levels(buf, event.delta.bids.iter().map(|quote| (quote.price, quote.quantity)));
+        coding.put_levels(buf, event.delta.asks.iter().map(|quote| (quote.price, quote.quantity)));
+        coding.ts = event.ts;
+        coding.seq = event.seq;
+        self.write_buf()
+    }
+
+    // put_snapshot encodes the book synced from a snapshot at ts, placed at seq in the
+    // stream, resetting the coding state.
+    fn put_snapshot(&mut self, ts: u64, seq: u64, book: &LocalOrderBook) {
+        let coding = &mut self.coding;
+        coding.reset();
+        let buf = &mut self.buf;
+        buf.push(SNAPSHOT);
+        put_varint(buf, zigzag(ts as i64));
+        put_varint(buf, 0);
+        put_varint(buf, zigzag(seq as i64));
+        put_varint(buf, 0);
+        let levels = |level: (Price, Qty)| (level.0.value(), level.1.value());
+        coding.put_levels(buf, book.bids().map(levels).collect::<Vec<_>>().into_iter());
+        coding.put_levels(buf, book.asks().map(levels).collect::<Vec<_>>().into_iter());
+        coding.ts = ts;
+        coding.seq = seq;
+    }
+
+    fn write_buf(&mut self) -> io::Result<()> {
+        self.file.write_all(&self.buf)?;
+        self.buf.clear();
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.file.flush()?;
+            self.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+impl Sink for CompactRecorder {
+    fn name(&self) -> &str {
+        "compact"
+    }
+
+    fn record_snapshot(&mut self, _symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        let mut copy = LocalOrderBook::new();
+        copy.apply_snapshot(book.to_snapshot());
+        self.pending = Some((ts, copy));
+        Ok(())
+    }
+
+    fn record_delta(&mut self, _symbol: &str, event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        Ok(self.record_event(event)?)
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.last_flush = Instant::now();
+        Ok(self.file.flush()?)
+    }
+}
+
+// CompactReader reads the records of a compact recording back as market events, snapshots
+// as snapshot events carrying the whole book. A truncated last record, as left by a process
+// that died mid-write, ends the iteration with an error.
+pub struct CompactReader {
+    reader: BufReader<File>,
+    header: Header,
+    coding: Coding,
+    done: bool,
+}
+
+impl CompactReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header = Header::read(&mut reader)?;
+        let coding = Coding::new(header.precision);
+        Ok(Self { reader, header, coding, done: false })
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.header.symbol
+    }
+
+    pub fn precision(&self) -> Precision {
+        self.header.precision
+    }
+
+    // read_event reads the next record, or None at the end of the recording.
+    fn read_event(&mut self) -> io::Result<Option<MarketEvent>> {
+        let mut tag = [0];
+        if self.reader.read(&mut tag)? == 0 {
+            return Ok(None);
+        }
+        let reader = &mut self.reader;
+        let coding = &mut self.coding;
+        let snapshot = match tag[0] & !HAS_CHECKSUM {
+            SNAPSHOT => true,
+            DELTA => false,
+            kind => return Err(invalid(format!("unknown record kind {}", kind))),
+        };
+        if snapshot {
+            coding.reset();
+        }
+        let ts = coding.ts.wrapping_add(unzigzag(get_varint(reader)?) as u64);
+        let prev_ts = ts.wrapping_sub(unzigzag(get_varint(reader)?) as u64);
+        let seq = coding.seq.wrapping_add(unzigzag(get_varint(reader)?) as u64);
+        let prev_seq = seq.wrapping_sub(unzigzag(get_varint(reader)?) as u64);
+        let checksum = match tag[0] & HAS_CHECKSUM {
+            0 => None,
+            _ => Some(get_varint(reader)? as u32),
+        };
+        let bids = coding.get_levels(reader)?;
+        let asks = coding.get_levels(reader)?;
+        coding.ts = ts;
+        coding.seq = seq;
+        Ok(Some(MarketEvent {
+            ts,
+            prev_ts,
+            seq,
+            prev_seq,
+            delta: OrderBookDelta { prev_ts, bids, asks },
+            snapshot,
+            checksum,
+            received_at: Instant::now(),
+            raw: None,
+            text: None,
+        }))
+    }
+}
+
+impl Iterator for CompactReader {
+    type Item = io::Result<MarketEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let event = self.read_event().transpose();
+        self.done = !matches!(event, Some(Ok(_)));
+        event
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+// put_varint appends value as an LEB128 varint, 7 bits a byte, low bits first.
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub(crate) fn get_varint(reader: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint is too long"))
+}
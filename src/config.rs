@@ -0,0 +1,175 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use crate::render::RenderStyle;
+
+// OutputConfig throttles and shapes how the book is printed, leaving what isn't given at
+// the process's default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub max_fps: Option<u32>,
+    pub depth: Option<usize>,
+    pub style: Option<RenderStyle>,
+}
+
+// RuntimeConfig is the JSON config file of the settings a running process can change
+// without a restart, e.g.
+// {"symbols": ["PERP_BTC_USDT"], "max_level": 50, "output": {"max_fps": 5, "style": "ladder"}}
+// symbols are followed in addition to those the process was started with.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    pub symbols: Vec<String>,
+    pub max_level: Option<usize>,
+    pub output: OutputConfig,
+}
+
+impl RuntimeConfig {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Config(serde_json::Error),
+    // Rejected means the change can't be applied live, with why, and the process has kept
+    // its current config.
+    Rejected(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Config(e) => write!(f, "invalid config: {}", e),
+            ConfigError::Rejected(reason) => write!(f, "change rejected, restart to apply it: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(e: serde_json::Error) -> Self {
+        ConfigError::Config(e)
+    }
+}
+
+// ConfigChange is a reloaded RuntimeConfig and the one it replaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigChange {
+    pub previous: RuntimeConfig,
+    pub config: RuntimeConfig,
+}
+
+impl ConfigChange {
+    // added_symbols returns the symbols in the new config that weren't in the previous one.
+    pub fn added_symbols(&self) -> Vec<&str> {
+        let previous: BTreeSet<&String> = self.previous.symbols.iter().collect();
+        self.config.symbols.iter().filter(|symbol| !previous.contains(symbol)).map(String::as_str).collect()
+    }
+
+    // removed_symbols returns the symbols in the previous config that the new one dropped.
+    pub fn removed_symbols(&self) -> Vec<&str> {
+        let config: BTreeSet<&String> = self.config.symbols.iter().collect();
+        self.previous.symbols.iter().filter(|symbol| !config.contains(symbol)).map(String::as_str).collect()
+    }
+
+    pub fn symbols_changed(&self) -> bool {
+        self.previous.symbols != self.config.symbols || self.previous.max_level != self.config.max_level
+    }
+}
+
+// FileWatcher reports when a file's modification time changes, polled by the caller.
+#[derive(Debug, Clone)]
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    // new watches path for changes from its current state.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // changed returns true once each time the file is modified, created or removed.
+    pub fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+// ConfigWatcher watches a RuntimeConfig file for changes, passing each to the caller to
+// apply. A change that can't be parsed or that the caller rejects leaves the current config
+// in place, so the next change is compared against what is actually running.
+#[derive(Debug, Clone)]
+pub struct ConfigWatcher {
+    file: FileWatcher,
+    current: RuntimeConfig,
+}
+
+impl ConfigWatcher {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, ConfigError> {
+        let file = FileWatcher::new(path);
+        let current = RuntimeConfig::load(file.path())?;
+        Ok(Self { file, current })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    pub fn current(&self) -> &RuntimeConfig {
+        &self.current
+    }
+
+    // poll reloads the config if the file changed and passes the change to apply, which
+    // either applies all of it or rejects it with why without applying any. It returns
+    // whether a change was applied.
+    pub fn poll<F>(&mut self, apply: F) -> Result<bool, ConfigError>
+    where
+        F: FnOnce(&ConfigChange) -> Result<(), String>,
+    {
+        if !self.file.changed() {
+            return Ok(false);
+        }
+        let config = RuntimeConfig::load(self.file.path())?;
+        if config == self.current {
+            return Ok(false);
+        }
+        let change = ConfigChange { previous: self.current.clone(), config };
+        apply(&change).map_err(ConfigError::Rejected)?;
+        self.current = change.config;
+        Ok(true)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
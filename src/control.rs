@@ -0,0 +1,192 @@
+use std::io;
+use std::net::ToSocketAddrs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::exchange_api_types::WsTrade;
+use crate::feed::MarketEvent;
+use crate::http::{self, Request, Response};
+use crate::orderbook::LocalOrderBook;
+use crate::render::RenderStyle;
+use crate::sink::{Sink, SinkResult};
+
+// REPLY_TIMEOUT is how long a control request waits for the process to handle its command.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// ControlCommand is a change to a running process, sent through the control API. Each mode
+// handles the commands that apply to it and rejects the rest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    // Subscribe starts following symbol, at max_level or the process's default depth.
+    Subscribe { symbol: String, max_level: Option<usize> },
+    Unsubscribe { symbol: String },
+    // Resync restarts the book of symbol, or of every symbol if None, from a new snapshot.
+    Resync { symbol: Option<String> },
+    // Symbols lists the symbols followed.
+    Symbols,
+    // SetPaused pauses or resumes printing the book.
+    SetPaused(bool),
+    // SetOutput changes how the book is printed, leaving what is None unchanged.
+    SetOutput { style: Option<RenderStyle>, depth: Option<usize> },
+    // SetRecording stops or restarts writing the books to the sinks.
+    SetRecording(bool),
+}
+
+// ControlReply is the outcome of a command, a message for the caller either way.
+pub type ControlReply = Result<String, String>;
+
+// ControlRequest is a command received by the control API, waiting to be replied to.
+#[derive(Debug)]
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    reply: Sender<ControlReply>,
+}
+
+impl ControlRequest {
+    pub fn reply(self, reply: ControlReply) {
+        let _ = self.reply.send(reply);
+    }
+}
+
+// serve serves the control API on addr, returning the requests for the caller to handle,
+// typically between the updates of its main loop. Commands are POSTs, e.g.
+//   POST /subscribe?symbol=PERP_BTC_USDT&depth=50
+//   POST /unsubscribe?symbol=PERP_BTC_USDT
+//   POST /resync[?symbol=PERP_BTC_USDT]
+//   GET  /symbols
+//   POST /pause, POST /resume
+//   POST /output?style=ladder&depth=10
+//   POST /recording?enabled=false
+// The listener is bound before serve returns.
+pub fn serve<A: ToSocketAddrs>(addr: A) -> io::Result<(thread::JoinHandle<()>, Receiver<ControlRequest>)> {
+    let (requests, rx) = mpsc::channel();
+    let handle = http::serve(addr, move |request| {
+        let command = match parse(request) {
+            Ok(command) => command,
+            Err(response) => return response,
+        };
+        let (reply, replied) = mpsc::channel();
+        if requests.send(ControlRequest { command, reply }).is_err() {
+            return Response::new(503, "text/plain; charset=utf-8", "control requests are no longer handled");
+        }
+        match replied.recv_timeout(REPLY_TIMEOUT) {
+            Ok(Ok(message)) => Response::text(message),
+            Ok(Err(message)) => Response::bad_request(&message),
+            Err(_) => Response::new(503, "text/plain; charset=utf-8", "command not handled in time"),
+        }
+    })?;
+    Ok((handle, rx))
+}
+
+// parse returns the command of request, or the response rejecting it.
+fn parse(request: &Request) -> Result<ControlCommand, Response> {
+    let method = if request.path == "/symbols" { "GET" } else { "POST" };
+    if request.method != method {
+        return Err(Response::new(405, "text/plain; charset=utf-8", format!("use {}", method)));
+    }
+    let param = |name: &str| request.query.get(name).map(String::as_str);
+    let symbol = || param("symbol").map(str::to_string).ok_or_else(|| Response::bad_request("missing symbol"));
+    let depth = || match param("depth") {
+        Some(depth) => depth.parse().map(Some).map_err(|_| Response::bad_request("invalid depth")),
+        None => Ok(None),
+    };
+    let command = match request.path.as_str() {
+        "/subscribe" => ControlCommand::Subscribe { symbol: symbol()?, max_level: depth()? },
+        "/unsubscribe" => ControlCommand::Unsubscribe { symbol: symbol()? },
+        "/resync" => ControlCommand::Resync { symbol: param("symbol").map(str::to_string) },
+        "/symbols" => ControlCommand::Symbols,
+        "/pause" => ControlCommand::SetPaused(true),
+        "/resume" => ControlCommand::SetPaused(false),
+        "/output" => {
+            let style = match param("style") {
+                Some("plain") => Some(RenderStyle::Plain),
+                Some("ladder") => Some(RenderStyle::Ladder),
+                Some(_) => return Err(Response::bad_request("style must be plain or ladder")),
+                None => None,
+            };
+            ControlCommand::SetOutput { style, depth: depth()? }
+        }
+        "/recording" => match param("enabled") {
+            Some("true") => ControlCommand::SetRecording(true),
+            Some("false") => ControlCommand::SetRecording(false),
+            _ => return Err(Response::bad_request("enabled must be true or false")),
+        },
+        _ => return Err(Response::not_found()),
+    };
+    Ok(command)
+}
+
+// RecordingSwitch turns the sinks it gates on and off together, so recording can be paused
+// without closing them.
+#[derive(Debug, Clone)]
+pub struct RecordingSwitch {
+    enabled: Arc<AtomicBool>,
+}
+
+impl Default for RecordingSwitch {
+    fn default() -> Self {
+        Self { enabled: Arc::new(AtomicBool::new(true)) }
+    }
+}
+
+impl RecordingSwitch {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    // gate returns sink, dropping the records written to it while the switch is off.
+    pub fn gate(&self, sink: Box<dyn Sink>) -> GatedSink {
+        GatedSink { inner: sink, switch: self.clone() }
+    }
+}
+
+// GatedSink passes records on to its sink while its RecordingSwitch is on.
+pub struct GatedSink {
+    inner: Box<dyn Sink>,
+    switch: RecordingSwitch,
+}
+
+impl Sink for GatedSink {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        match self.switch.is_enabled() {
+            true => self.inner.record_snapshot(symbol, ts, book),
+            false => Ok(()),
+        }
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        match self.switch.is_enabled() {
+            true => self.inner.record_delta(symbol, event, book),
+            false => Ok(()),
+        }
+    }
+
+    fn record_stale(&mut self, symbol: &str, age: Duration, book: &LocalOrderBook) -> SinkResult {
+        match self.switch.is_enabled() {
+            true => self.inner.record_stale(symbol, age, book),
+            false => Ok(()),
+        }
+    }
+
+    fn record_trade(&mut self, trade: &WsTrade) -> SinkResult {
+        match self.switch.is_enabled() {
+            true => self.inner.record_trade(trade),
+            false => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,190 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::render::Precision;
+use crate::sink::{Sink, SinkResult};
+use crate::units::{Price, Qty};
+
+const MS_PER_HOUR: u64 = 3_600_000;
+
+const DELTA_HEADER: &str = "recv_ts,ts,prev_ts,side,price,quantity";
+const TOP_HEADER: &str = "recv_ts,ts,level,bid_price,bid_quantity,ask_price,ask_quantity";
+
+// HourlyCsvFile is a CSV file that is rotated to a new file every UTC hour, named
+// <dir>/<prefix>_<YYYYMMDD>_<HH>.csv based on the exchange timestamp of the rows.
+pub(crate) struct HourlyCsvFile {
+    dir: PathBuf,
+    prefix: String,
+    header: &'static str,
+    hour: Option<u64>,
+    writer: Option<BufWriter<File>>,
+}
+
+impl HourlyCsvFile {
+    pub(crate) fn new(dir: &Path, prefix: String, header: &'static str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self { dir: dir.to_path_buf(), prefix, header, hour: None, writer: None })
+    }
+
+    // writer returns the writer for the hour containing ts, rotating files if needed.
+    pub(crate) fn writer(&mut self, ts: u64) -> io::Result<&mut BufWriter<File>> {
+        let hour = ts / MS_PER_HOUR;
+        if self.hour != Some(hour) || self.writer.is_none() {
+            if let Some(mut writer) = self.writer.take() {
+                writer.flush()?;
+            }
+
+            let path = self.dir.join(format!("{}_{}.csv", self.prefix, utc_hour_stamp(ts)));
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let is_new = file.metadata()?.len() == 0;
+            let mut writer = BufWriter::new(file);
+            if is_new {
+                writeln!(writer, "{}", self.header)?;
+            }
+            self.writer = Some(writer);
+            self.hour = Some(hour);
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// CsvRecorder appends applied deltas and periodic top of book snapshots for a symbol to
+// hourly rotated CSV files, for offline analysis.
+pub struct CsvRecorder {
+    deltas: Option<HourlyCsvFile>,
+    top: Option<HourlyCsvFile>,
+    top_depth: usize,
+    top_interval: Duration,
+    last_top: Option<u64>,
+    // precision formats prices and quantities, None writes them in full.
+    precision: Option<Precision>,
+}
+
+impl CsvRecorder {
+    // new creates a recorder writing into dir. Deltas are recorded if record_deltas is set,
+    // and the top top_depth levels are recorded every top_interval if top_depth is non-zero.
+    pub fn new(dir: &Path, symbol: &str, record_deltas: bool, top_depth: usize, top_interval: Duration) -> io::Result<Self> {
+        let deltas = match record_deltas {
+            true => Some(HourlyCsvFile::new(dir, format!("{}_deltas", symbol), DELTA_HEADER)?),
+            false => None,
+        };
+        let top = match top_depth {
+            0 => None,
+            _ => Some(HourlyCsvFile::new(dir, format!("{}_top{}", symbol, top_depth), TOP_HEADER)?),
+        };
+        Ok(Self { deltas, top, top_depth, top_interval, last_top: None, precision: None })
+    }
+
+    // with_precision writes prices and quantities with the decimals of precision, e.g. the
+    // precision of the symbol's tick and lot sizes.
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    // record writes event, which has just been applied to book, and a top of book snapshot
+    // if one is due.
+    pub fn record(&mut self, event: &MarketEvent, book: &LocalOrderBook) -> io::Result<()> {
+        let recv_ts = now_ms();
+        if let Some(file) = self.deltas.as_mut() {
+            let writer = file.writer(event.ts)?;
+            let sides = [("BID", &event.delta.bids), ("ASK", &event.delta.asks)];
+            for (side, quotes) in sides {
+                for quote in quotes {
+                    let (price, quantity) = field_pair(Some(&(Price::new(quote.price), Qty::new(quote.quantity))), self.precision);
+                    writeln!(writer, "{},{},{},{},{},{}", recv_ts, event.ts, event.prev_ts, side, price, quantity)?;
+                }
+            }
+        }
+
+        let interval = self.top_interval.as_millis() as u64;
+        let due = self.last_top.is_none_or(|last| event.ts >= last + interval);
+        if let (Some(file), true) = (self.top.as_mut(), due) {
+            self.last_top = Some(event.ts);
+            let writer = file.writer(event.ts)?;
+            let bids: Vec<_> = book.bids().take(self.top_depth).collect();
+            let asks: Vec<_> = book.asks().take(self.top_depth).collect();
+            for level in 0..self.top_depth {
+                let (bid_price, bid_quantity) = field_pair(bids.get(level), self.precision);
+                let (ask_price, ask_quantity) = field_pair(asks.get(level), self.precision);
+                writeln!(
+                    writer,
+                    "{},{},{},{},{},{},{}",
+                    recv_ts, event.ts, level + 1, bid_price, bid_quantity, ask_price, ask_quantity
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for file in [self.deltas.as_mut(), self.top.as_mut()].into_iter().flatten() {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for CsvRecorder {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    fn record_delta(&mut self, _symbol: &str, event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        Ok(self.record(event, book)?)
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        Ok(CsvRecorder::flush(self)?)
+    }
+}
+
+impl Drop for CsvRecorder {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn field_pair(level: Option<&(Price, Qty)>, precision: Option<Precision>) -> (String, String) {
+    match (level, precision) {
+        (Some(&(price, quantity)), Some(precision)) => (precision.format_price(price), precision.format_quantity(quantity)),
+        (Some((price, quantity)), None) => (price.to_string(), quantity.to_string()),
+        (None, _) => (String::new(), String::new()),
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+// utc_hour_stamp formats a ms timestamp as YYYYMMDD_HH in UTC.
+pub fn utc_hour_stamp(ts: u64) -> String {
+    let (year, month, day) = civil_from_days((ts / 86_400_000) as i64);
+    let hour = ts / MS_PER_HOUR % 24;
+    format!("{:04}{:02}{:02}_{:02}", year, month, day, hour)
+}
+
+// civil_from_days converts days since the unix epoch into a (year, month, day) date.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
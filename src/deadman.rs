@@ -0,0 +1,92 @@
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::rest::RestError;
+use crate::risk::KillSwitch;
+use crate::trading::{ExecutionReport, TradingClient};
+
+// DeadManConfig is what a DeadManSwitch does when it fires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadManConfig {
+    // symbol is the symbol whose orders are cancelled, or None for every symbol.
+    pub symbol: Option<String>,
+    // retries is how many more times cancel_all is tried after it first fails, retry_delay
+    // apart.
+    pub retries: u32,
+    pub retry_delay: Duration,
+    // halt trips the kill switch, halting every strategy and order path sharing it. Without
+    // it only the open orders are cancelled and trading may resume.
+    pub halt: bool,
+}
+
+impl Default for DeadManConfig {
+    fn default() -> Self {
+        Self { symbol: None, retries: 5, retry_delay: Duration::from_millis(500), halt: true }
+    }
+}
+
+// DeadManSwitch cancels every open order when the feed an order flow depends on drops, so
+// orders are never left resting unattended. It fires once the private stream it watches
+// disconnects, or whenever fire is called, e.g. by a strategy loop that lost market data.
+#[derive(Clone)]
+pub struct DeadManSwitch {
+    client: Arc<dyn TradingClient>,
+    config: DeadManConfig,
+    kill_switch: KillSwitch,
+}
+
+impl DeadManSwitch {
+    pub fn new(client: Arc<dyn TradingClient>, config: DeadManConfig, kill_switch: KillSwitch) -> Self {
+        Self { client, config, kill_switch }
+    }
+
+    pub fn kill_switch(&self) -> &KillSwitch {
+        &self.kill_switch
+    }
+
+    // fire halts trading if configured to and cancels the open orders, retrying cancel_all
+    // until it succeeds or the retries run out. It returns the last error if it never did.
+    pub fn fire(&self, reason: &str) -> Result<(), RestError> {
+        warn!(reason, symbol = self.config.symbol, "Dead man's switch fired");
+        if self.config.halt {
+            self.kill_switch.trip(reason);
+        }
+        let mut attempt = 0;
+        loop {
+            match self.client.cancel_all(self.config.symbol.as_deref()) {
+                Ok(()) => {
+                    info!(attempt, "Cancelled all open orders");
+                    return Ok(());
+                }
+                Err(e) if attempt < self.config.retries => {
+                    warn!(attempt, error = %e, "Failed to cancel all open orders, retrying");
+                    attempt += 1;
+                    thread::sleep(self.config.retry_delay);
+                }
+                Err(e) => {
+                    error!(attempt, error = %e, "Failed to cancel all open orders, orders may be left open");
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    // watch forwards the execution reports of reports, firing the switch on a new thread
+    // once it disconnects. The returned receiver disconnects after the switch has fired.
+    pub fn watch(&self, reports: Receiver<ExecutionReport>) -> Receiver<ExecutionReport> {
+        let (tx, rx) = mpsc::channel();
+        let switch = self.clone();
+        thread::spawn(move || {
+            for report in reports {
+                // Keep watching if the consumer went away, the feed is what matters.
+                let _ = tx.send(report);
+            }
+            let _ = switch.fire("private stream disconnected");
+        });
+        rx
+    }
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+
+use crate::exchange_api_types::{OrderBookDelta, Side, SnapshotData};
+use crate::orderbook::OrderBook;
+use crate::units::{Price, Qty};
+
+// DenseBook is an OrderBook keeping the levels within a band of ticks around the mid in flat
+// arrays indexed by ticks from the band's base, so setting a level is O(1) and the touch is
+// tracked without a search. Prices are converted to whole ticks of the tick size, and must
+// lie on its grid. Levels outside the band, deep in the book, are kept in BTreeMaps instead,
+// and once the mid drifts out of the middle half of the band it is re-centred on the mid,
+// moving the levels between the two.
+#[derive(Debug, Clone)]
+pub struct DenseBook {
+    tick_size: f64,
+    // ticks_per_unit is 1 / tick_size for tick sizes below 1, which divide into prices more
+    // exactly than tick_size multiplies into them.
+    ticks_per_unit: f64,
+    // base is the tick of index 0 of the band.
+    base: i64,
+    bids: Vec<Qty>,
+    asks: Vec<Qty>,
+    best_bid: Option<usize>,
+    best_ask: Option<usize>,
+    outside_bids: BTreeMap<i64, Qty>,
+    outside_asks: BTreeMap<i64, Qty>,
+    recenters: u64,
+}
+
+impl DenseBook {
+    // new returns a book for prices on a grid of tick_size, with a band of band ticks.
+    pub fn new(tick_size: f64, band: usize) -> Self {
+        let band = band.max(4);
+        Self {
+            tick_size,
+            ticks_per_unit: if tick_size < 1.0 { (1.0 / tick_size).round() } else { 0.0 },
+            base: 0,
+            bids: vec![Qty::ZERO; band],
+            asks: vec![Qty::ZERO; band],
+            best_bid: None,
+            best_ask: None,
+            outside_bids: BTreeMap::new(),
+            outside_asks: BTreeMap::new(),
+            recenters: 0,
+        }
+    }
+
+    // recenters returns how many times the band has been re-centred on the mid.
+    pub fn recenters(&self) -> u64 {
+        self.recenters
+    }
+
+    fn tick(&self, price: f64) -> i64 {
+        match self.ticks_per_unit > 0.0 {
+            true => (price * self.ticks_per_unit).round() as i64,
+            false => (price / self.tick_size).round() as i64,
+        }
+    }
+
+    fn price(&self, tick: i64) -> Price {
+        match self.ticks_per_unit > 0.0 {
+            true => Price::new(tick as f64 / self.ticks_per_unit),
+            false => Price::new(tick as f64 * self.tick_size),
+        }
+    }
+
+    // index returns the band index of tick, if it is within the band.
+    fn index(&self, tick: i64) -> Option<usize> {
+        let index = tick.checked_sub(self.base)?;
+        (0..self.bids.len() as i64).contains(&index).then_some(index as usize)
+    }
+
+    fn set(&mut self, side: Side, tick: i64, quantity: Qty) {
+        let Some(index) = self.index(tick) else {
+            let outside = match side {
+                Side::Buy => &mut self.outside_bids,
+                Side::Sell => &mut self.outside_asks,
+            };
+            match quantity.is_zero() {
+                true => outside.remove(&tick),
+                false => outside.insert(tick, quantity),
+            };
+            return;
+        };
+        match side {
+            Side::Buy => {
+                self.bids[index] = quantity;
+                if !quantity.is_zero() && self.best_bid.is_none_or(|best| index > best) {
+                    self.best_bid = Some(index);
+                } else if quantity.is_zero() && self.best_bid == Some(index) {
+                    self.best_bid = self.bids[..index].iter().rposition(|quantity| !quantity.is_zero());
+                }
+            }
+            Side::Sell => {
+                self.asks[index] = quantity;
+                if !quantity.is_zero() && self.best_ask.is_none_or(|best| index < best) {
+                    self.best_ask = Some(index);
+                } else if quantity.is_zero() && self.best_ask == Some(index) {
+                    self.best_ask = self.asks[index + 1..]
+                        .iter()
+                        .position(|quantity| !quantity.is_zero())
+                        .map(|offset| index + 1 + offset);
+                }
+            }
+        }
+    }
+
+    // best_bid_tick and best_ask_tick are the ticks of the touch, inside the band or out.
+    fn best_bid_tick(&self) -> Option<i64> {
+        let above = self.outside_bids.keys().next_back().copied().filter(|&tick| tick >= self.base);
+        above
+            .or_else(|| self.best_bid.map(|index| self.base + index as i64))
+            .or_else(|| self.outside_bids.keys().next_back().copied())
+    }
+
+    fn best_ask_tick(&self) -> Option<i64> {
+        let below = self.outside_asks.keys().next().copied().filter(|&tick| tick < self.base + self.asks.len() as i64);
+        below
+            .or_else(|| self.best_ask.map(|index| self.base + index as i64))
+            .or_else(|| self.outside_asks.keys().next().copied())
+    }
+
+    // recenter moves the band onto the mid, or the one side there is, once it has drifted
+    // out of the middle half of the band.
+    fn recenter(&mut self) {
+        let len = self.bids.len() as i64;
+        let center = match (self.best_bid_tick(), self.best_ask_tick()) {
+            (Some(bid), Some(ask)) => bid + (ask - bid) / 2,
+            (Some(tick), None) | (None, Some(tick)) => tick,
+            (None, None) => return,
+        };
+        let offset = center - self.base;
+        if (len / 4..len - len / 4).contains(&offset) {
+            return;
+        }
+
+        let mut bids: Vec<(i64, Qty)> = std::mem::take(&mut self.outside_bids).into_iter().collect();
+        let mut asks: Vec<(i64, Qty)> = std::mem::take(&mut self.outside_asks).into_iter().collect();
+        bids.extend(band_levels(self.base, &self.bids));
+        asks.extend(band_levels(self.base, &self.asks));
+        self.bids.fill(Qty::ZERO);
+        self.asks.fill(Qty::ZERO);
+        self.best_bid = None;
+        self.best_ask = None;
+        self.base = center - len / 2;
+        for (tick, quantity) in bids {
+            self.set(Side::Buy, tick, quantity);
+        }
+        for (tick, quantity) in asks {
+            self.set(Side::Sell, tick, quantity);
+        }
+        self.recenters += 1;
+    }
+
+    fn set_delta(&mut self, delta: &OrderBookDelta) {
+        for quote in &delta.bids {
+            self.set(Side::Buy, self.tick(quote.price), Qty::new(quote.quantity));
+        }
+        for quote in &delta.asks {
+            self.set(Side::Sell, self.tick(quote.price), Qty::new(quote.quantity));
+        }
+    }
+
+    fn quantity_at_tick(&self, side: Side, tick: i64) -> Qty {
+        let (levels, outside) = match side {
+            Side::Buy => (&self.bids, &self.outside_bids),
+            Side::Sell => (&self.asks, &self.outside_asks),
+        };
+        match self.index(tick) {
+            Some(index) => levels[index],
+            None => outside.get(&tick).copied().unwrap_or_default(),
+        }
+    }
+}
+
+// band_levels returns the (tick, quantity) levels set in a band starting at tick base.
+fn band_levels(base: i64, levels: &[Qty]) -> impl DoubleEndedIterator<Item = (i64, Qty)> + '_ {
+    levels
+        .iter()
+        .enumerate()
+        .filter(|(_, quantity)| !quantity.is_zero())
+        .map(move |(index, &quantity)| (base + index as i64, quantity))
+}
+
+impl OrderBook for DenseBook {
+    fn apply_snapshot(&mut self, data: SnapshotData) {
+        self.bids.fill(Qty::ZERO);
+        self.asks.fill(Qty::ZERO);
+        self.best_bid = None;
+        self.best_ask = None;
+        self.outside_bids.clear();
+        self.outside_asks.clear();
+        // The band is placed on the snapshot before its levels are, so they land in it.
+        let best_bid = data.bids.iter().map(|quote| self.tick(quote.price)).max();
+        let best_ask = data.asks.iter().map(|quote| self.tick(quote.price)).min();
+        if let Some(center) = match (best_bid, best_ask) {
+            (Some(bid), Some(ask)) => Some(bid + (ask - bid) / 2),
+            (bid, ask) => bid.or(ask),
+        } {
+            self.base = center - self.bids.len() as i64 / 2;
+        }
+        for quote in data.bids {
+            let tick = self.tick(quote.price);
+            self.set(Side::Buy, tick, Qty::new(quote.quantity));
+        }
+        for quote in data.asks {
+            let tick = self.tick(quote.price);
+            self.set(Side::Sell, tick, Qty::new(quote.quantity));
+        }
+    }
+
+    fn apply_delta(&mut self, delta: &OrderBookDelta) {
+        self.set_delta(delta);
+        self.recenter();
+    }
+
+    // apply_deltas only checks whether the band needs re-centring once the batch is applied.
+    fn apply_deltas<'a>(&mut self, deltas: impl IntoIterator<Item = &'a OrderBookDelta>) {
+        for delta in deltas {
+            self.set_delta(delta);
+        }
+        self.recenter();
+    }
+
+    fn bids(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        let top = self.base + self.bids.len() as i64;
+        let above = self.outside_bids.range(top..).rev();
+        // The band is only walked from the touch, past which it is empty.
+        let band = band_levels(self.base, &self.bids[..self.best_bid.map_or(0, |best| best + 1)]).rev();
+        let below = self.outside_bids.range(..self.base).rev();
+        above
+            .map(|(&tick, &quantity)| (tick, quantity))
+            .chain(band)
+            .chain(below.map(|(&tick, &quantity)| (tick, quantity)))
+            .map(|(tick, quantity)| (self.price(tick), quantity))
+    }
+
+    fn asks(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        let top = self.base + self.asks.len() as i64;
+        let below = self.outside_asks.range(..self.base);
+        let start = self.best_ask.unwrap_or(self.asks.len());
+        let band = band_levels(self.base + start as i64, &self.asks[start..]);
+        let above = self.outside_asks.range(top..);
+        below
+            .map(|(&tick, &quantity)| (tick, quantity))
+            .chain(band)
+            .chain(above.map(|(&tick, &quantity)| (tick, quantity)))
+            .map(|(tick, quantity)| (self.price(tick), quantity))
+    }
+
+    fn best_bid(&self) -> Option<(Price, Qty)> {
+        let tick = self.best_bid_tick()?;
+        Some((self.price(tick), self.quantity_at_tick(Side::Buy, tick)))
+    }
+
+    fn best_ask(&self) -> Option<(Price, Qty)> {
+        let tick = self.best_ask_tick()?;
+        Some((self.price(tick), self.quantity_at_tick(Side::Sell, tick)))
+    }
+
+    fn quantity_at(&self, side: Side, price: Price) -> Qty {
+        self.quantity_at_tick(side, self.tick(price.value()))
+    }
+}
@@ -0,0 +1,417 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::exchange::{ExchangeFeed, Frame, WsError, WsErrorKind};
+use crate::exchange_api_types::{FundingRate, MarkPrice, OrderBookDelta, RestQuote, RestSnapshot, Side, SnapshotData, WsQuote, WsTrade};
+use crate::feed::MarketEvent;
+use crate::http_client;
+use crate::retry::RetryPolicy;
+use crate::snapshot::SnapshotError;
+use crate::sync::SyncRule;
+
+pub const BINANCE_SPOT_WS_URL: &str = "wss://stream.binance.com:9443/ws";
+pub const BINANCE_SPOT_REST_URL: &str = "https://api.binance.com";
+pub const BINANCE_FUTURES_WS_URL: &str = "wss://fstream.binance.com/ws";
+pub const BINANCE_FUTURES_REST_URL: &str = "https://fapi.binance.com";
+
+// FUTURES_DEPTH_LIMITS are the snapshot depths the futures depth endpoint accepts.
+const FUTURES_DEPTH_LIMITS: [usize; 7] = [5, 10, 20, 50, 100, 500, 1000];
+// SPOT_MAX_DEPTH is the deepest snapshot the spot depth endpoint returns.
+const SPOT_MAX_DEPTH: usize = 5000;
+
+// BinanceMarket selects Binance's spot or USD-M futures API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinanceMarket {
+    Spot,
+    UsdFutures,
+}
+
+// BinanceExchange is the Binance public API for one market. Its diff depth stream is
+// sequenced by update id: each event covers the ids U to u, and on futures also carries
+// the u of the event before it as pu. A spot event's predecessor ends at U - 1, so events
+// are normalized to seq u and prev_seq pu or U - 1, and snapshots to their lastUpdateId,
+// which the stream is joined to with SyncRule::Overlap.
+//
+// Symbols are given as Binance names them, e.g. BTCUSDT. The diff depth stream covers the
+// whole book, so levels beyond the snapshot depth are only known once they change.
+pub struct BinanceExchange {
+    market: BinanceMarket,
+    ws_url: String,
+    rest_url: String,
+    http: reqwest::blocking::Client,
+    retry: RetryPolicy,
+}
+
+impl BinanceExchange {
+    pub fn new(market: BinanceMarket, ws_url: &str, rest_url: &str) -> Self {
+        Self {
+            market,
+            ws_url: ws_url.to_string(),
+            rest_url: rest_url.trim_end_matches('/').to_string(),
+            http: http_client::shared(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+
+    pub fn spot() -> Self {
+        Self::new(BinanceMarket::Spot, BINANCE_SPOT_WS_URL, BINANCE_SPOT_REST_URL)
+    }
+
+    pub fn usd_futures() -> Self {
+        Self::new(BinanceMarket::UsdFutures, BINANCE_FUTURES_WS_URL, BINANCE_FUTURES_REST_URL)
+    }
+
+    // depth_limit returns the snapshot depth to request for max_level levels, the smallest
+    // the endpoint accepts that is at least max_level.
+    fn depth_limit(&self, max_level: usize) -> usize {
+        match self.market {
+            BinanceMarket::Spot => max_level.clamp(1, SPOT_MAX_DEPTH),
+            BinanceMarket::UsdFutures => {
+                let deepest = FUTURES_DEPTH_LIMITS[FUTURES_DEPTH_LIMITS.len() - 1];
+                FUTURES_DEPTH_LIMITS.into_iter().find(|&limit| limit >= max_level).unwrap_or(deepest)
+            }
+        }
+    }
+}
+
+// BinanceResponse is a struct representation of the response to a request, which stream
+// events don't have an id for.
+#[derive(Debug, Deserialize)]
+struct BinanceResponse {
+    id: Option<u64>,
+    error: Option<BinanceError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BinanceError {
+    code: i64,
+    msg: String,
+}
+
+// BinanceDepthUpdate is a struct representation of a diff depth stream event.
+#[derive(Debug, Deserialize)]
+struct BinanceDepthUpdate {
+    #[serde(rename = "e")]
+    event_type: String,
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "U")]
+    first_update_id: u64,
+    #[serde(rename = "u")]
+    final_update_id: u64,
+    // prev_final_update_id is only sent on futures.
+    #[serde(rename = "pu")]
+    prev_final_update_id: Option<u64>,
+    #[serde(rename = "b")]
+    bids: Vec<WsQuote>,
+    #[serde(rename = "a")]
+    asks: Vec<WsQuote>,
+}
+
+// BinanceAggTrade is a struct representation of an aggregate trade stream event.
+#[derive(Debug, Deserialize)]
+struct BinanceAggTrade {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "p")]
+    price: String,
+    #[serde(rename = "q")]
+    quantity: String,
+    #[serde(rename = "T")]
+    trade_time: u64,
+    // buyer_is_maker means the seller was the aggressor.
+    #[serde(rename = "m")]
+    buyer_is_maker: bool,
+}
+
+// BinanceMarkPrice is a struct representation of a futures mark price stream event, which
+// carries the funding rate too.
+#[derive(Debug, Deserialize)]
+struct BinanceMarkPrice {
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "p")]
+    mark_price: String,
+    #[serde(rename = "r")]
+    funding_rate: String,
+    #[serde(rename = "T")]
+    next_funding_time: u64,
+}
+
+// BinanceDepth is a struct representation of the depth endpoint response. Only futures
+// send the event time.
+#[derive(Debug, Deserialize)]
+struct BinanceDepth {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    #[serde(rename = "E")]
+    event_time: Option<u64>,
+    bids: Vec<WsQuote>,
+    asks: Vec<WsQuote>,
+}
+
+impl ExchangeFeed for BinanceExchange {
+    fn name(&self) -> &str {
+        match self.market {
+            BinanceMarket::Spot => "binance-spot",
+            BinanceMarket::UsdFutures => "binance-futures",
+        }
+    }
+
+    fn ws_url(&self) -> &str {
+        &self.ws_url
+    }
+
+    fn book_topic(&self, symbol: &str, _max_level: usize) -> String {
+        format!("{}@depth@100ms", symbol.to_lowercase())
+    }
+
+    fn trade_topic(&self, symbol: &str) -> String {
+        format!("{}@aggTrade", symbol.to_lowercase())
+    }
+
+    fn subscribe(&self, topic: &str) -> String {
+        json!({
+            "method": "SUBSCRIBE",
+            "params": [topic],
+            "id": 1
+        })
+        .to_string()
+    }
+
+    // frame tells request responses from stream events. Binance pings with websocket ping
+    // frames, which are answered by the websocket itself.
+    fn frame(&self, text: &str) -> Frame {
+        match serde_json::from_str::<BinanceResponse>(text) {
+            // The only request sent is the subscription.
+            Ok(BinanceResponse { id: Some(_), error: Some(error) }) => {
+                Frame::Error(WsError::new(WsErrorKind::Subscription, format!("{}: {}", error.code, error.msg)))
+            }
+            Ok(BinanceResponse { id: Some(id), error: None }) => Frame::Ack { command: format!("request {}", id) },
+            _ => Frame::Data,
+        }
+    }
+
+    fn parse_book(&self, text: &str, received_at: Instant) -> Result<Option<MarketEvent>, serde_json::Error> {
+        let update: BinanceDepthUpdate = serde_json::from_str(text)?;
+        if update.event_type != "depthUpdate" {
+            return Ok(None);
+        }
+        let prev_seq = update.prev_final_update_id.unwrap_or(update.first_update_id.saturating_sub(1));
+        Ok(Some(MarketEvent {
+            ts: update.event_time,
+            prev_ts: 0,
+            seq: update.final_update_id,
+            prev_seq,
+            delta: OrderBookDelta { prev_ts: 0, bids: update.bids, asks: update.asks },
+            snapshot: false,
+            checksum: None,
+            received_at,
+            raw: None,
+            text: None,
+        }))
+    }
+
+    fn parse_trades(&self, text: &str) -> Result<Vec<WsTrade>, serde_json::Error> {
+        let trade: BinanceAggTrade = serde_json::from_str(text)?;
+        let parse = |value: &str| value.parse::<f64>().map_err(serde::de::Error::custom);
+        Ok(vec![WsTrade {
+            symbol: trade.symbol,
+            price: parse(&trade.price)?,
+            quantity: parse(&trade.quantity)?,
+            side: if trade.buyer_is_maker { Side::Sell } else { Side::Buy },
+            ts: trade.trade_time,
+            backfilled: false,
+        }])
+    }
+
+    // funding_topic is the mark price stream, on futures only.
+    fn funding_topic(&self, symbol: &str) -> Option<String> {
+        (self.market == BinanceMarket::UsdFutures).then(|| format!("{}@markPrice", symbol.to_lowercase()))
+    }
+
+    fn parse_funding(&self, text: &str) -> Result<Option<FundingRate>, serde_json::Error> {
+        let mark: BinanceMarkPrice = serde_json::from_str(text)?;
+        Ok(Some(FundingRate {
+            symbol: mark.symbol,
+            rate: mark.funding_rate.parse().map_err(serde::de::Error::custom)?,
+            next_funding_ts: mark.next_funding_time,
+            ts: mark.event_time,
+        }))
+    }
+
+    // mark_price_topic is the mark price stream, on futures only.
+    fn mark_price_topic(&self, symbol: &str) -> Option<String> {
+        self.funding_topic(symbol)
+    }
+
+    fn parse_mark_price(&self, text: &str) -> Result<Option<MarkPrice>, serde_json::Error> {
+        let mark: BinanceMarkPrice = serde_json::from_str(text)?;
+        Ok(Some(MarkPrice {
+            symbol: mark.symbol,
+            price: mark.mark_price.parse().map_err(serde::de::Error::custom)?,
+            ts: mark.event_time,
+        }))
+    }
+
+    fn snapshot(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        let path = match self.market {
+            BinanceMarket::Spot => "/api/v3/depth",
+            BinanceMarket::UsdFutures => "/fapi/v1/depth",
+        };
+        let depth: BinanceDepth = self.retry.run("binance snapshot", || -> Result<_, SnapshotError> {
+            Ok(self
+                .http
+                .get(format!("{}{}", self.rest_url, path))
+                .query(&[("symbol", symbol.to_uppercase()), ("limit", self.depth_limit(max_level).to_string())])
+                .timeout(self.retry.timeout)
+                .send()?
+                .error_for_status()?
+                .json()?)
+        })?;
+
+        let quotes = |quotes: Vec<WsQuote>| -> Vec<RestQuote> {
+            quotes.into_iter().take(max_level).map(|quote| RestQuote { price: quote.price, quantity: quote.quantity }).collect()
+        };
+        let timestamp = depth.event_time.unwrap_or_else(now_ms);
+        let data = SnapshotData { bids: quotes(depth.bids), asks: quotes(depth.asks) };
+        Ok(RestSnapshot::new(timestamp, depth.last_update_id, data))
+    }
+
+    fn sync_rule(&self) -> SyncRule {
+        SyncRule::Overlap
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::http::{self, Response};
+    use crate::sync::{BookSync, SyncOutcome};
+    use crate::units::{Price, Qty};
+
+    // depth_update returns a diff depth event covering first to last, with pu set to prev
+    // as futures send it, bidding quantity at price.
+    fn depth_update(first: u64, last: u64, prev: Option<u64>, price: &str, quantity: &str) -> String {
+        let pu = prev.map(|prev| format!(r#","pu":{}"#, prev)).unwrap_or_default();
+        format!(
+            r#"{{"e":"depthUpdate","E":{},"s":"BTCUSDT","U":{},"u":{}{},"b":[["{}","{}"]],"a":[]}}"#,
+            last, first, last, pu, price, quantity
+        )
+    }
+
+    fn parse(exchange: &BinanceExchange, text: &str) -> MarketEvent {
+        exchange.parse_book(text, Instant::now()).unwrap().unwrap()
+    }
+
+    // Requests are the (path, limit) of each snapshot request served.
+    type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+    // serve_depth serves body at every path, returning the url to fetch snapshots from and
+    // the requests served.
+    fn serve_depth(body: &'static str) -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        http::serve_listener(listener, move |request| {
+            let limit = request.query.get("limit").cloned().unwrap_or_default();
+            seen.lock().unwrap().push((request.path.clone(), limit));
+            Response::json(body)
+        });
+        (url, requests)
+    }
+
+    #[test]
+    fn places_spot_events_after_the_update_before_their_first() {
+        let spot = BinanceExchange::spot();
+        let event = parse(&spot, &depth_update(101, 105, None, "100.0", "1"));
+        assert_eq!((event.prev_seq, event.seq, event.ts), (100, 105, 105));
+        assert!(spot.parse_book(r#"{"e":"aggTrade","E":1,"U":1,"u":1,"b":[],"a":[]}"#, Instant::now()).unwrap().is_none());
+    }
+
+    #[test]
+    fn places_futures_events_after_their_pu() {
+        let futures = BinanceExchange::usd_futures();
+        // pu, not U - 1, is the previous event's u on futures.
+        let event = parse(&futures, &depth_update(101, 105, Some(97), "100.0", "1"));
+        assert_eq!((event.prev_seq, event.seq), (97, 105));
+    }
+
+    #[test]
+    fn syncs_spot_on_the_first_event_spanning_the_snapshot() {
+        let spot = BinanceExchange::spot();
+        let snapshot = RestSnapshot::new(0, 100, SnapshotData { bids: vec![RestQuote { price: 99.0, quantity: 1.0 }], asks: Vec::new() });
+        let mut sync = BookSync::for_exchange(snapshot, &spot);
+
+        assert_eq!(sync.on_event(&parse(&spot, &depth_update(95, 99, None, "98.0", "1"))), SyncOutcome::Behind(6));
+        // 98 to 103 spans the snapshot's 100, repeating changes it already has.
+        assert_eq!(sync.on_event(&parse(&spot, &depth_update(98, 103, None, "100.0", "2"))), SyncOutcome::Synced);
+        assert_eq!(sync.on_event(&parse(&spot, &depth_update(104, 106, None, "99.0", "0"))), SyncOutcome::Applied);
+        assert_eq!(sync.book().best_bid(), Some((Price::new(100.0), Qty::new(2.0))));
+        assert_eq!(sync.book().quantity_at(Side::Buy, Price::new(99.0)), Qty::new(0.0));
+        // 107 was lost.
+        assert_eq!(sync.on_event(&parse(&spot, &depth_update(108, 110, None, "100.0", "3"))), SyncOutcome::OutOfSync);
+    }
+
+    #[test]
+    fn syncs_futures_on_the_first_event_spanning_the_snapshot() {
+        let futures = BinanceExchange::usd_futures();
+        let snapshot = RestSnapshot::new(0, 100, SnapshotData { bids: Vec::new(), asks: Vec::new() });
+        let mut sync = BookSync::for_exchange(snapshot, &futures);
+
+        assert_eq!(sync.on_event(&parse(&futures, &depth_update(91, 99, Some(90), "98.0", "1"))), SyncOutcome::Behind(10));
+        assert_eq!(sync.on_event(&parse(&futures, &depth_update(100, 104, Some(99), "100.0", "2"))), SyncOutcome::Synced);
+        // A futures event's U may be past the last u, continuity is only by pu.
+        assert_eq!(sync.on_event(&parse(&futures, &depth_update(107, 110, Some(104), "100.5", "1"))), SyncOutcome::Applied);
+        assert_eq!(sync.book().best_bid(), Some((Price::new(100.5), Qty::new(1.0))));
+        assert_eq!(sync.on_event(&parse(&futures, &depth_update(112, 115, Some(111), "101.0", "1"))), SyncOutcome::OutOfSync);
+    }
+
+    #[test]
+    fn refetches_a_snapshot_the_stream_moved_past() {
+        let futures = BinanceExchange::usd_futures();
+        let snapshot = RestSnapshot::new(0, 100, SnapshotData { bids: Vec::new(), asks: Vec::new() });
+        let events = [parse(&futures, &depth_update(102, 105, Some(101), "100.0", "1"))];
+        assert!(BookSync::moved_past(&snapshot, &events, SyncRule::Overlap));
+    }
+
+    #[test]
+    fn fetches_futures_snapshots_at_an_accepted_depth() {
+        let (url, requests) = serve_depth(r#"{"lastUpdateId":100,"E":1700,"T":1699,"bids":[["100.0","1"],["99.5","2"],["99.0","3"]],"asks":[["100.5","4"]]}"#);
+        let futures = BinanceExchange::new(BinanceMarket::UsdFutures, "ws://unused", &url);
+
+        let snapshot = futures.snapshot("btcusdt", 2).unwrap();
+        assert_eq!((snapshot.timestamp, snapshot.seq()), (1700, 100));
+        let bids: Vec<_> = snapshot.data.bids.iter().map(|quote| (quote.price, quote.quantity)).collect();
+        assert_eq!(bids, [(100.0, 1.0), (99.5, 2.0)]);
+        assert_eq!(snapshot.data.asks.len(), 1);
+        assert_eq!(*requests.lock().unwrap(), [("/fapi/v1/depth".to_string(), "5".to_string())]);
+    }
+
+    #[test]
+    fn fetches_spot_snapshots_at_the_requested_depth() {
+        let (url, requests) = serve_depth(r#"{"lastUpdateId":100,"bids":[["100.0","1"]],"asks":[]}"#);
+        let spot = BinanceExchange::new(BinanceMarket::Spot, "ws://unused", &url);
+
+        let snapshot = spot.snapshot("BTCUSDT", 7).unwrap();
+        assert_eq!(snapshot.seq(), 100);
+        assert!(snapshot.timestamp > 0);
+        assert_eq!(*requests.lock().unwrap(), [("/api/v3/depth".to_string(), "7".to_string())]);
+    }
+}
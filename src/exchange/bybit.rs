@@ -0,0 +1,307 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::exchange::{ExchangeFeed, Frame, WsError};
+use crate::exchange_api_types::{FundingRate, MarkPrice, OrderBookDelta, RestQuote, RestSnapshot, Side, SnapshotData, WsQuote, WsTrade};
+use crate::feed::MarketEvent;
+use crate::http_client;
+use crate::retry::RetryPolicy;
+use crate::snapshot::SnapshotError;
+use crate::sync::SyncRule;
+
+pub const BYBIT_LINEAR_WS_URL: &str = "wss://stream.bybit.com/v5/public/linear";
+pub const BYBIT_REST_URL: &str = "https://api.bybit.com";
+
+// BYBIT_PING_INTERVAL is how often connections are pinged, as Bybit recommends.
+const BYBIT_PING_INTERVAL: Duration = Duration::from_secs(20);
+
+// BYBIT_DEPTHS are the orderbook stream depths offered for linear contracts.
+const BYBIT_DEPTHS: [usize; 4] = [1, 50, 200, 500];
+
+// BybitExchange is the Bybit v5 public API for linear perpetuals. Its orderbook stream
+// pushes a snapshot on subscribing, then deltas numbered by an update id u that goes up by
+// one per message, so events are sequenced by u with prev_seq u - 1. Update ids are kept per
+// stream depth and REST snapshots don't carry the stream's, so the REST snapshot only seeds
+// the book until the stream's snapshot replaces it.
+//
+// Symbols are given as Bybit names them, e.g. BTCUSDT.
+pub struct BybitExchange {
+    ws_url: String,
+    rest_url: String,
+    http: reqwest::blocking::Client,
+    retry: RetryPolicy,
+}
+
+impl Default for BybitExchange {
+    fn default() -> Self {
+        Self::new(BYBIT_LINEAR_WS_URL, BYBIT_REST_URL)
+    }
+}
+
+impl BybitExchange {
+    pub fn new(ws_url: &str, rest_url: &str) -> Self {
+        Self {
+            ws_url: ws_url.to_string(),
+            rest_url: rest_url.trim_end_matches('/').to_string(),
+            http: http_client::shared(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+}
+
+// depth returns the shallowest stream depth with at least max_level levels, or the deepest.
+fn depth(max_level: usize) -> usize {
+    let deepest = BYBIT_DEPTHS[BYBIT_DEPTHS.len() - 1];
+    BYBIT_DEPTHS.into_iter().find(|&depth| depth >= max_level).unwrap_or(deepest)
+}
+
+// BybitResponse is a struct representation of the response to an operation, which stream
+// pushes don't have an op for.
+#[derive(Debug, Deserialize)]
+struct BybitResponse {
+    op: Option<String>,
+    #[serde(default)]
+    success: bool,
+    ret_msg: Option<String>,
+}
+
+// BybitMessage is a struct representation of a public stream push.
+#[derive(Debug, Deserialize)]
+struct BybitMessage<T> {
+    // kind is snapshot or delta.
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    ts: u64,
+    data: T,
+}
+
+// BybitBook is a struct representation of orderbook data, pushed by the stream and returned
+// by the orderbook endpoint.
+#[derive(Debug, Deserialize)]
+struct BybitBook {
+    #[serde(rename = "b")]
+    bids: Vec<WsQuote>,
+    #[serde(rename = "a")]
+    asks: Vec<WsQuote>,
+    #[serde(rename = "u")]
+    update_id: u64,
+}
+
+// BybitTrade is a struct representation of a trade pushed by the publicTrade stream. side
+// is the taker's.
+#[derive(Debug, Deserialize)]
+struct BybitTrade {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "p")]
+    price: String,
+    #[serde(rename = "v")]
+    quantity: String,
+    #[serde(rename = "S")]
+    side: String,
+    #[serde(rename = "T")]
+    ts: u64,
+}
+
+// BybitTicker is a struct representation of ticker data. Deltas only carry the fields that
+// changed.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitTicker {
+    symbol: String,
+    funding_rate: Option<String>,
+    next_funding_time: Option<String>,
+    mark_price: Option<String>,
+}
+
+// BybitOrderbookResponse is a struct representation of the orderbook endpoint response.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BybitOrderbookResponse {
+    ret_code: i64,
+    ret_msg: String,
+    result: Option<BybitRestBook>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BybitRestBook {
+    #[serde(flatten)]
+    book: BybitBook,
+    ts: u64,
+}
+
+impl ExchangeFeed for BybitExchange {
+    fn name(&self) -> &str {
+        "bybit"
+    }
+
+    fn ws_url(&self) -> &str {
+        &self.ws_url
+    }
+
+    fn book_topic(&self, symbol: &str, max_level: usize) -> String {
+        format!("orderbook.{}.{}", depth(max_level), symbol)
+    }
+
+    fn trade_topic(&self, symbol: &str) -> String {
+        format!("publicTrade.{}", symbol)
+    }
+
+    fn subscribe(&self, topic: &str) -> String {
+        json!({
+            "op": "subscribe",
+            "args": [topic]
+        })
+        .to_string()
+    }
+
+    // frame tells the responses to operations such as subscribe from stream pushes. The
+    // replies to heartbeats, op ping on public streams and pong on private ones, are skipped.
+    fn frame(&self, text: &str) -> Frame {
+        match serde_json::from_str::<BybitResponse>(text) {
+            Ok(BybitResponse { op: Some(op), .. }) if op == "ping" || op == "pong" => Frame::Control,
+            Ok(BybitResponse { op: Some(op), success: true, .. }) => Frame::Ack { command: op },
+            Ok(BybitResponse { op: Some(op), ret_msg, .. }) => Frame::Error(WsError::from_reply(&op, ret_msg.unwrap_or_else(|| text.to_string()))),
+            _ => Frame::Data,
+        }
+    }
+
+    // heartbeat is the ping Bybit expects from clients, which it drops connections without
+    // after about 10 minutes. A connection that drops regardless ends the stream, as on any
+    // venue, leaving it to the caller to follow the book again.
+    fn heartbeat(&self) -> Option<(Duration, String)> {
+        Some((BYBIT_PING_INTERVAL, json!({ "op": "ping" }).to_string()))
+    }
+
+    fn parse_book(&self, text: &str, received_at: Instant) -> Result<Option<MarketEvent>, serde_json::Error> {
+        let message: BybitMessage<BybitBook> = serde_json::from_str(text)?;
+        // An update id of 1 means the stream restarted, and the message is a snapshot.
+        let snapshot = message.kind.as_deref() == Some("snapshot") || message.data.update_id == 1;
+        Ok(Some(MarketEvent {
+            ts: message.ts,
+            prev_ts: 0,
+            seq: message.data.update_id,
+            prev_seq: message.data.update_id.saturating_sub(1),
+            delta: OrderBookDelta { prev_ts: 0, bids: message.data.bids, asks: message.data.asks },
+            snapshot,
+            checksum: None,
+            received_at,
+            raw: None,
+            text: None,
+        }))
+    }
+
+    fn parse_trades(&self, text: &str) -> Result<Vec<WsTrade>, serde_json::Error> {
+        let message: BybitMessage<Vec<BybitTrade>> = serde_json::from_str(text)?;
+        message
+            .data
+            .into_iter()
+            .map(|trade| {
+                Ok(WsTrade {
+                    price: trade.price.parse().map_err(serde::de::Error::custom)?,
+                    quantity: trade.quantity.parse().map_err(serde::de::Error::custom)?,
+                    side: if trade.side == "Sell" { Side::Sell } else { Side::Buy },
+                    ts: trade.ts,
+                    symbol: trade.symbol,
+                    backfilled: false,
+                })
+            })
+            .collect()
+    }
+
+    // funding_topic is the ticker stream, which carries the funding rate.
+    fn funding_topic(&self, symbol: &str) -> Option<String> {
+        Some(format!("tickers.{}", symbol))
+    }
+
+    fn parse_funding(&self, text: &str) -> Result<Option<FundingRate>, serde_json::Error> {
+        let message: BybitMessage<BybitTicker> = serde_json::from_str(text)?;
+        let Some(rate) = message.data.funding_rate else { return Ok(None) };
+        let next_funding_ts = match message.data.next_funding_time {
+            Some(ts) => ts.parse().map_err(serde::de::Error::custom)?,
+            None => 0,
+        };
+        Ok(Some(FundingRate {
+            symbol: message.data.symbol,
+            rate: rate.parse().map_err(serde::de::Error::custom)?,
+            next_funding_ts,
+            ts: message.ts,
+        }))
+    }
+
+    // mark_price_topic is the ticker stream, which carries the mark price too.
+    fn mark_price_topic(&self, symbol: &str) -> Option<String> {
+        self.funding_topic(symbol)
+    }
+
+    fn parse_mark_price(&self, text: &str) -> Result<Option<MarkPrice>, serde_json::Error> {
+        let message: BybitMessage<BybitTicker> = serde_json::from_str(text)?;
+        let Some(price) = message.data.mark_price else { return Ok(None) };
+        Ok(Some(MarkPrice {
+            symbol: message.data.symbol,
+            price: price.parse().map_err(serde::de::Error::custom)?,
+            ts: message.ts,
+        }))
+    }
+
+    fn snapshot(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        let response: BybitOrderbookResponse = self.retry.run("bybit snapshot", || -> Result<_, SnapshotError> {
+            Ok(self
+                .http
+                .get(format!("{}/v5/market/orderbook", self.rest_url))
+                .query(&[
+                    ("category", "linear".to_string()),
+                    ("symbol", symbol.to_string()),
+                    ("limit", depth(max_level).to_string()),
+                ])
+                .timeout(self.retry.timeout)
+                .send()?
+                .error_for_status()?
+                .json()?)
+        })?;
+        let Some(rest) = response.result.filter(|_| response.ret_code == 0) else {
+            let message = format!("bybit orderbook error {}: {}", response.ret_code, response.ret_msg);
+            return Err(SnapshotError::Parse(serde::de::Error::custom(message)));
+        };
+
+        let quotes = |quotes: Vec<WsQuote>| -> Vec<RestQuote> {
+            quotes.into_iter().take(max_level).map(|quote| RestQuote { price: quote.price, quantity: quote.quantity }).collect()
+        };
+        let data = SnapshotData { bids: quotes(rest.book.bids), asks: quotes(rest.book.asks) };
+        // Seq 0 comes before every update, so the book syncs on the stream's snapshot.
+        Ok(RestSnapshot::new(rest.ts, 0, data))
+    }
+
+    fn sync_rule(&self) -> SyncRule {
+        SyncRule::Overlap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_heartbeat_replies() {
+        let exchange = BybitExchange::default();
+        let pong = r#"{"success":true,"ret_msg":"pong","conn_id":"0970e817","op":"ping"}"#;
+        assert_eq!(exchange.frame(pong), Frame::Control);
+        let ack = r#"{"success":true,"ret_msg":"","conn_id":"0970e817","op":"subscribe"}"#;
+        assert_eq!(exchange.frame(ack), Frame::Ack { command: "subscribe".to_string() });
+        let push = r#"{"topic":"orderbook.50.BTCUSDT","type":"delta","ts":1,"data":{"s":"BTCUSDT","b":[],"a":[],"u":2}}"#;
+        assert_eq!(exchange.frame(push), Frame::Data);
+    }
+
+    #[test]
+    fn pings_every_interval() {
+        let (interval, message) = BybitExchange::default().heartbeat().unwrap();
+        assert_eq!(interval, BYBIT_PING_INTERVAL);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&message).unwrap(), json!({ "op": "ping" }));
+    }
+}
@@ -0,0 +1,215 @@
+// exchange abstracts the venue a book is followed on, so the feed, sync and manager
+// machinery works the same for any exchange with an adapter.
+pub mod binance;
+pub mod bybit;
+pub mod okx;
+pub mod woox;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::exchange_api_types::{FundingRate, MarkPrice, RestSnapshot, WsTrade};
+use crate::feed::MarketEvent;
+use crate::snapshot::{SnapshotError, SnapshotSource};
+use crate::sync::{BookChecksum, SyncRule};
+
+// Frame is what a text frame read from a venue's websocket is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    // Data is a stream message, passed on to be parsed.
+    Data,
+    // Ping is a keepalive to be answered with reply. ts is the exchange's clock when it was
+    // sent, if the ping carries it.
+    Ping { reply: String, ts: Option<u64> },
+    // Ack acknowledges a command sent, such as a subscription, named by command.
+    Ack { command: String },
+    // Error is the venue rejecting a command sent, such as a subscription to an unknown
+    // symbol.
+    Error(WsError),
+    // Control is any other message carrying no data, such as a pong, which is skipped.
+    Control,
+}
+
+// WsErrorKind is what a venue rejected a command for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsErrorKind {
+    // Subscription is a subscription rejected, usually for a misspelled symbol or topic,
+    // which nothing will ever be streamed for.
+    Subscription,
+    // RateLimited is a command refused for being sent too often.
+    RateLimited,
+    // Auth is a login rejected, for a bad key or signature.
+    Auth,
+    Other,
+}
+
+// WsError is a command a venue rejected and its reason, as the venue put it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WsError {
+    pub kind: WsErrorKind,
+    pub message: String,
+}
+
+// RATE_LIMIT_HINTS are the phrases in a reply without an error code that mark it a rate limit.
+const RATE_LIMIT_HINTS: [&str; 4] = ["rate limit", "ratelimit", "too many", "too frequent"];
+
+impl WsError {
+    pub fn new(kind: WsErrorKind, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into() }
+    }
+
+    // from_reply is the error a venue replied to command with, of the kind the command and
+    // message tell, for venues whose replies have no error code. Only whole phrases mark a
+    // rate limit, as words like limit also turn up in rejections, e.g. of a depth over one.
+    pub fn from_reply(command: &str, message: impl Into<String>) -> Self {
+        let message = message.into();
+        let lower = message.to_lowercase();
+        let kind = if RATE_LIMIT_HINTS.iter().any(|hint| lower.contains(hint)) {
+            WsErrorKind::RateLimited
+        } else if ["auth", "login"].iter().any(|auth| command.eq_ignore_ascii_case(auth)) {
+            WsErrorKind::Auth
+        } else if command.eq_ignore_ascii_case("subscribe") {
+            WsErrorKind::Subscription
+        } else {
+            WsErrorKind::Other
+        };
+        Self { kind, message }
+    }
+
+    // is_fatal returns true if the connection will never stream anything after the error,
+    // so it is better closed.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self.kind, WsErrorKind::Subscription | WsErrorKind::Auth)
+    }
+}
+
+impl fmt::Display for WsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            WsErrorKind::Subscription => "subscription rejected",
+            WsErrorKind::RateLimited => "rate limited",
+            WsErrorKind::Auth => "authentication failed",
+            WsErrorKind::Other => "command rejected",
+        };
+        write!(f, "{}: {}", kind, self.message)
+    }
+}
+
+impl std::error::Error for WsError {}
+
+// ExchangeFeed is a venue's public market data API: where its websocket is, how its depth
+// and trade streams are subscribed to and parsed into the normalized MarketEvent and
+// WsTrade, and how depth snapshots are fetched. Snapshots and events are sequenced by the
+// venue's seq, see MarketEvent.
+pub trait ExchangeFeed: Send + Sync {
+    // name identifies the venue in logs.
+    fn name(&self) -> &str;
+
+    // ws_url is the public websocket the streams are read from.
+    fn ws_url(&self) -> &str;
+
+    // book_topic names the depth stream of symbol at max_level levels.
+    fn book_topic(&self, symbol: &str, max_level: usize) -> String;
+
+    fn trade_topic(&self, symbol: &str) -> String;
+
+    // subscribe returns the message subscribing to topic once connected.
+    fn subscribe(&self, topic: &str) -> String;
+
+    // frame classifies a text frame read from the websocket, from its envelope rather than
+    // its text, which data can contain anything in.
+    fn frame(&self, text: &str) -> Frame;
+
+    // heartbeat is the message a venue that expects its clients to ping wants sent, and how
+    // often, for it to keep the connection open. None if the venue pings its clients instead.
+    fn heartbeat(&self) -> Option<(Duration, String)> {
+        None
+    }
+
+    // parse_book parses a depth stream message received at received_at, returning None if
+    // it carries no delta.
+    fn parse_book(&self, text: &str, received_at: Instant) -> Result<Option<MarketEvent>, serde_json::Error>;
+
+    fn parse_trades(&self, text: &str) -> Result<Vec<WsTrade>, serde_json::Error>;
+
+    // funding_topic names the funding rate stream of a perpetual symbol, or None if the
+    // venue has none for it.
+    fn funding_topic(&self, _symbol: &str) -> Option<String> {
+        None
+    }
+
+    // parse_funding parses a funding rate stream message, returning None if it carries no
+    // rate.
+    fn parse_funding(&self, _text: &str) -> Result<Option<FundingRate>, serde_json::Error> {
+        Ok(None)
+    }
+
+    // mark_price_topic names the mark price stream of a derivative symbol, or None if the
+    // venue has none for it.
+    fn mark_price_topic(&self, _symbol: &str) -> Option<String> {
+        None
+    }
+
+    // parse_mark_price parses a mark price stream message, returning None if it carries no
+    // price.
+    fn parse_mark_price(&self, _text: &str) -> Result<Option<MarkPrice>, serde_json::Error> {
+        Ok(None)
+    }
+
+    // snapshot fetches a depth snapshot of symbol with up to max_level levels per side.
+    fn snapshot(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError>;
+
+    // sync_rule is how the depth stream is joined to a snapshot.
+    fn sync_rule(&self) -> SyncRule {
+        SyncRule::Exact
+    }
+
+    // checksum is how the venue checksums its book, for verifying the local book against
+    // the checksums sent with its events. None if it doesn't send any.
+    fn checksum(&self) -> Option<BookChecksum> {
+        None
+    }
+}
+
+impl fmt::Debug for dyn ExchangeFeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ExchangeFeed({})", self.name())
+    }
+}
+
+// ExchangeSource is a snapshot source fetching from an exchange's snapshot endpoint.
+pub struct ExchangeSource {
+    exchange: Arc<dyn ExchangeFeed>,
+}
+
+impl ExchangeSource {
+    pub fn new(exchange: Arc<dyn ExchangeFeed>) -> Self {
+        Self { exchange }
+    }
+}
+
+impl SnapshotSource for ExchangeSource {
+    fn name(&self) -> &str {
+        self.exchange.name()
+    }
+
+    fn fetch(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        self.exchange.snapshot(symbol, max_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_rate_limits_from_replies_mentioning_limits() {
+        assert_eq!(WsError::from_reply("subscribe", "Rate limit exceeded").kind, WsErrorKind::RateLimited);
+        assert_eq!(WsError::from_reply("subscribe", "Too many requests").kind, WsErrorKind::RateLimited);
+        assert_eq!(WsError::from_reply("subscribe", "depth over the limit of 50").kind, WsErrorKind::Subscription);
+        assert_eq!(WsError::from_reply("subscribe", "topics must be separated by commas").kind, WsErrorKind::Subscription);
+        assert_eq!(WsError::from_reply("auth", "signature expired").kind, WsErrorKind::Auth);
+        assert_eq!(WsError::from_reply("unsubscribe", "unknown topic").kind, WsErrorKind::Other);
+    }
+}
@@ -0,0 +1,420 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::exchange::{ExchangeFeed, Frame, WsError, WsErrorKind};
+use crate::exchange_api_types::{FundingRate, LevelText, MarkPrice, OrderBookDelta, QuoteText, RestQuote, RestSnapshot, Side, SnapshotData, WsQuote, WsTrade};
+use crate::feed::MarketEvent;
+use crate::http_client;
+use crate::orderbook::LocalOrderBook;
+use crate::retry::RetryPolicy;
+use crate::snapshot::SnapshotError;
+use crate::sync::{BookChecksum, BookText, SyncRule};
+use crate::units::{Price, Qty};
+
+pub const OKX_WS_URL: &str = "wss://ws.okx.com:8443/ws/v5/public";
+pub const OKX_REST_URL: &str = "https://www.okx.com";
+
+// OKX_MAX_DEPTH is the deepest snapshot the books endpoint returns.
+const OKX_MAX_DEPTH: usize = 400;
+// CHECKSUM_LEVELS is how many levels per side OKX checksums.
+const CHECKSUM_LEVELS: usize = 25;
+
+// OkxExchange is the OKX v5 public API. Its books channel pushes a snapshot of the book on
+// subscribing, then updates sequenced by seqId, each carrying the seqId of the one before it
+// as prevSeqId and a CRC32 checksum of the book after it. The REST books endpoint has no
+// seqId, so the REST snapshot only seeds the book until the stream's snapshot replaces it.
+//
+// Symbols are OKX instrument ids, e.g. BTC-USDT or BTC-USDT-SWAP.
+pub struct OkxExchange {
+    ws_url: String,
+    rest_url: String,
+    http: reqwest::blocking::Client,
+    retry: RetryPolicy,
+}
+
+impl Default for OkxExchange {
+    fn default() -> Self {
+        Self::new(OKX_WS_URL, OKX_REST_URL)
+    }
+}
+
+impl OkxExchange {
+    pub fn new(ws_url: &str, rest_url: &str) -> Self {
+        Self {
+            ws_url: ws_url.to_string(),
+            rest_url: rest_url.trim_end_matches('/').to_string(),
+            http: http_client::shared(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+}
+
+// OkxEvent is a struct representation of the event OKX replies to an operation with, which
+// channel pushes don't have.
+#[derive(Debug, Deserialize)]
+struct OkxEvent {
+    // event is the operation replied to, such as subscribe, or error.
+    event: Option<String>,
+    code: Option<String>,
+    msg: Option<String>,
+}
+
+// OkxMessage is a struct representation of a channel push.
+#[derive(Debug, Deserialize)]
+struct OkxMessage<T> {
+    // action is snapshot or update on the books channel.
+    action: Option<String>,
+    #[serde(default = "Vec::new")]
+    data: Vec<T>,
+}
+
+// OkxBook is a struct representation of the book data pushed by the books channel.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxBook {
+    // Levels are [price, size, deprecated, orders], kept as the text OKX checksums.
+    asks: Vec<Vec<String>>,
+    bids: Vec<Vec<String>>,
+    ts: String,
+    checksum: i64,
+    // prev_seq_id is -1 on snapshots.
+    prev_seq_id: i64,
+    seq_id: i64,
+}
+
+// OkxTrade is a struct representation of a trade pushed by the trades channel. side is the
+// taker's.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxTrade {
+    inst_id: String,
+    px: String,
+    sz: String,
+    side: String,
+    ts: String,
+}
+
+// OkxFunding is a struct representation of the funding-rate channel data. funding_time is
+// when the rate is next charged.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxFunding {
+    inst_id: String,
+    funding_rate: String,
+    funding_time: String,
+    ts: String,
+}
+
+// OkxMarkPrice is a struct representation of the mark-price channel data.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OkxMarkPrice {
+    inst_id: String,
+    mark_px: String,
+    ts: String,
+}
+
+// OkxBooksResponse is a struct representation of the books endpoint response.
+#[derive(Debug, Deserialize)]
+struct OkxBooksResponse {
+    code: String,
+    msg: String,
+    data: Vec<OkxRestBook>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OkxRestBook {
+    asks: Vec<WsQuote>,
+    bids: Vec<WsQuote>,
+    ts: String,
+}
+
+impl ExchangeFeed for OkxExchange {
+    fn name(&self) -> &str {
+        "okx"
+    }
+
+    fn ws_url(&self) -> &str {
+        &self.ws_url
+    }
+
+    // book_topic is the 400 level books channel whatever max_level is, as the shallower
+    // channels carry no sequence or checksum.
+    fn book_topic(&self, symbol: &str, _max_level: usize) -> String {
+        format!("books:{}", symbol)
+    }
+
+    fn trade_topic(&self, symbol: &str) -> String {
+        format!("trades:{}", symbol)
+    }
+
+    // subscribe subscribes to a topic of the form channel:instId.
+    fn subscribe(&self, topic: &str) -> String {
+        let (channel, inst_id) = topic.split_once(':').unwrap_or((topic, ""));
+        json!({
+            "op": "subscribe",
+            "args": [{ "channel": channel, "instId": inst_id }]
+        })
+        .to_string()
+    }
+
+    // frame tells operation events and pongs from channel pushes. OKX doesn't ping, it drops
+    // connections that are quiet for 30s, which a books channel never is.
+    fn frame(&self, text: &str) -> Frame {
+        if text == "pong" {
+            return Frame::Control;
+        }
+        match serde_json::from_str::<OkxEvent>(text) {
+            Ok(OkxEvent { event: Some(event), code, msg }) if event == "error" => {
+                let code = code.unwrap_or_default();
+                let message = format!("{}: {}", code, msg.unwrap_or_default());
+                Frame::Error(WsError::new(okx_error_kind(&code), message))
+            }
+            Ok(OkxEvent { event: Some(event), .. }) => Frame::Ack { command: event },
+            _ => Frame::Data,
+        }
+    }
+
+    fn parse_book(&self, text: &str, received_at: Instant) -> Result<Option<MarketEvent>, serde_json::Error> {
+        let message: OkxMessage<OkxBook> = serde_json::from_str(text)?;
+        let snapshot = message.action.as_deref() == Some("snapshot");
+        let Some(book) = message.data.into_iter().next() else { return Ok(None) };
+        let ts = book.ts.parse::<u64>().map_err(serde::de::Error::custom)?;
+        let (bids, bid_text) = okx_levels(book.bids)?;
+        let (asks, ask_text) = okx_levels(book.asks)?;
+        Ok(Some(MarketEvent {
+            ts,
+            prev_ts: 0,
+            seq: book.seq_id.max(0) as u64,
+            prev_seq: book.prev_seq_id.max(0) as u64,
+            delta: OrderBookDelta { prev_ts: 0, bids, asks },
+            snapshot,
+            // The checksum is a signed 32 bit integer.
+            checksum: Some(book.checksum as i32 as u32),
+            received_at,
+            raw: None,
+            text: Some(Arc::new(QuoteText { bids: bid_text, asks: ask_text })),
+        }))
+    }
+
+    fn parse_trades(&self, text: &str) -> Result<Vec<WsTrade>, serde_json::Error> {
+        let message: OkxMessage<OkxTrade> = serde_json::from_str(text)?;
+        message
+            .data
+            .into_iter()
+            .map(|trade| {
+                Ok(WsTrade {
+                    price: trade.px.parse().map_err(serde::de::Error::custom)?,
+                    quantity: trade.sz.parse().map_err(serde::de::Error::custom)?,
+                    side: if trade.side == "sell" { Side::Sell } else { Side::Buy },
+                    ts: trade.ts.parse().map_err(serde::de::Error::custom)?,
+                    symbol: trade.inst_id,
+                    backfilled: false,
+                })
+            })
+            .collect()
+    }
+
+    // funding_topic is the funding-rate channel, which only swaps have.
+    fn funding_topic(&self, symbol: &str) -> Option<String> {
+        symbol.ends_with("-SWAP").then(|| format!("funding-rate:{}", symbol))
+    }
+
+    fn parse_funding(&self, text: &str) -> Result<Option<FundingRate>, serde_json::Error> {
+        let message: OkxMessage<OkxFunding> = serde_json::from_str(text)?;
+        let Some(funding) = message.data.into_iter().next() else { return Ok(None) };
+        Ok(Some(FundingRate {
+            symbol: funding.inst_id,
+            rate: funding.funding_rate.parse().map_err(serde::de::Error::custom)?,
+            next_funding_ts: funding.funding_time.parse().map_err(serde::de::Error::custom)?,
+            ts: funding.ts.parse().map_err(serde::de::Error::custom)?,
+        }))
+    }
+
+    // mark_price_topic is the mark-price channel, which derivatives have. Their instrument
+    // ids have a suffix after the currency pair, e.g. BTC-USDT-SWAP.
+    fn mark_price_topic(&self, symbol: &str) -> Option<String> {
+        (symbol.matches('-').count() >= 2).then(|| format!("mark-price:{}", symbol))
+    }
+
+    fn parse_mark_price(&self, text: &str) -> Result<Option<MarkPrice>, serde_json::Error> {
+        let message: OkxMessage<OkxMarkPrice> = serde_json::from_str(text)?;
+        let Some(mark) = message.data.into_iter().next() else { return Ok(None) };
+        Ok(Some(MarkPrice {
+            symbol: mark.inst_id,
+            price: mark.mark_px.parse().map_err(serde::de::Error::custom)?,
+            ts: mark.ts.parse().map_err(serde::de::Error::custom)?,
+        }))
+    }
+
+    fn snapshot(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        let response: OkxBooksResponse = self.retry.run("okx snapshot", || -> Result<_, SnapshotError> {
+            Ok(self
+                .http
+                .get(format!("{}/api/v5/market/books", self.rest_url))
+                .query(&[("instId", symbol.to_string()), ("sz", max_level.clamp(1, OKX_MAX_DEPTH).to_string())])
+                .timeout(self.retry.timeout)
+                .send()?
+                .error_for_status()?
+                .json()?)
+        })?;
+        let Some(book) = response.data.into_iter().next() else {
+            let message = format!("okx books error {}: {}", response.code, response.msg);
+            return Err(SnapshotError::Parse(serde::de::Error::custom(message)));
+        };
+
+        let quotes = |quotes: Vec<WsQuote>| -> Vec<RestQuote> {
+            quotes.into_iter().map(|quote| RestQuote { price: quote.price, quantity: quote.quantity }).collect()
+        };
+        let timestamp = book.ts.parse::<u64>().map_err(|e| SnapshotError::Parse(serde::de::Error::custom(e)))?;
+        let data = SnapshotData { bids: quotes(book.bids), asks: quotes(book.asks) };
+        // Seq 0 comes before every update, so the book syncs on the stream's snapshot.
+        Ok(RestSnapshot::new(timestamp, 0, data))
+    }
+
+    fn sync_rule(&self) -> SyncRule {
+        SyncRule::Overlap
+    }
+
+    fn checksum(&self) -> Option<BookChecksum> {
+        Some(okx_checksum)
+    }
+}
+
+// okx_error_kind is the kind of error an OKX error event's code is. OKX doesn't say which
+// operation failed, only why.
+fn okx_error_kind(code: &str) -> WsErrorKind {
+    match code {
+        // Login failures: a bad key, passphrase, timestamp or signature.
+        "60005" | "60006" | "60007" | "60008" | "60009" | "60024" => WsErrorKind::Auth,
+        // Requests too frequent.
+        "60014" => WsErrorKind::RateLimited,
+        // An invalid request or an unknown channel or instrument.
+        "60012" | "60013" | "60018" => WsErrorKind::Subscription,
+        _ => WsErrorKind::Other,
+    }
+}
+
+// okx_levels parses the levels of a books push into quotes, and the text of their prices
+// and sizes.
+fn okx_levels(levels: Vec<Vec<String>>) -> Result<(Vec<WsQuote>, Vec<LevelText>), serde_json::Error> {
+    let mut quotes = Vec::with_capacity(levels.len());
+    let mut text = Vec::with_capacity(levels.len());
+    for level in levels {
+        let [price, quantity, ..] = level.as_slice() else {
+            return Err(serde::de::Error::custom("okx level too short"));
+        };
+        quotes.push(WsQuote {
+            price: price.parse().map_err(serde::de::Error::custom)?,
+            quantity: quantity.parse().map_err(serde::de::Error::custom)?,
+        });
+        text.push((price.as_str().into(), quantity.as_str().into()));
+    }
+    Ok((quotes, text))
+}
+
+// okx_checksum is the CRC32 of the top 25 levels of each side, interleaved best first as
+// bid:ask pairs of price:size, with a side's levels left out once it runs out.
+//
+// OKX checksums the prices and sizes as it sent them, so those are used as sent. Only levels
+// seeded from the REST snapshot have no text, and are formatted from their values.
+pub fn okx_checksum(book: &LocalOrderBook, text: &BookText) -> u32 {
+    let field = |sent: Option<(&str, &str)>, price: Price, quantity: Qty| match sent {
+        Some((price, quantity)) => [price.to_string(), quantity.to_string()],
+        None => [price.value().to_string(), quantity.value().to_string()],
+    };
+    let mut bids = book.bids().take(CHECKSUM_LEVELS).map(|(price, quantity)| field(text.bid(price), price, quantity));
+    let mut asks = book.asks().take(CHECKSUM_LEVELS).map(|(price, quantity)| field(text.ask(price), price, quantity));
+    let mut fields = Vec::with_capacity(CHECKSUM_LEVELS * 4);
+    for _ in 0..CHECKSUM_LEVELS {
+        for level in [bids.next(), asks.next()].into_iter().flatten() {
+            fields.extend(level);
+        }
+    }
+    crc32fast::hash(fields.join(":").as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::Coalesce;
+    use crate::sync::{BookSync, SyncOutcome};
+
+    fn push(action: &str, prev_seq_id: i64, seq_id: i64, bids: &str, asks: &str, checksum: u32) -> String {
+        format!(
+            r#"{{"arg":{{"channel":"books","instId":"BTC-USDT"}},"action":"{}","data":[{{"asks":{},"bids":{},"ts":"1000","checksum":{},"prevSeqId":{},"seqId":{}}}]}}"#,
+            action, asks, bids, checksum as i32, prev_seq_id, seq_id
+        )
+    }
+
+    fn sync() -> BookSync {
+        let snapshot = RestSnapshot::new(0, 0, SnapshotData { bids: Vec::new(), asks: Vec::new() });
+        BookSync::with_rule(snapshot, SyncRule::Overlap).with_checksum(Some(okx_checksum))
+    }
+
+    #[test]
+    fn checksums_the_levels_as_sent() {
+        let okx = OkxExchange::default();
+        // Formatting the parsed values would give 3366.1:7:3366.8:9:3366:6:3368:8.
+        let expected = crc32fast::hash(b"3366.10:7.0:3366.80:9:3366.00:6:3368:8.00");
+        let text = push(
+            "snapshot",
+            -1,
+            10,
+            r#"[["3366.10","7.0","0","3"],["3366.00","6","0","4"]]"#,
+            r#"[["3366.80","9","0","3"],["3368","8.00","0","4"]]"#,
+            expected,
+        );
+        let event = okx.parse_book(&text, Instant::now()).unwrap().unwrap();
+
+        let mut sync = sync();
+        assert_eq!(sync.on_event(&event), SyncOutcome::Synced);
+    }
+
+    #[test]
+    fn keeps_the_text_of_levels_across_updates() {
+        let okx = OkxExchange::default();
+        let snapshot = push(
+            "snapshot",
+            -1,
+            10,
+            r#"[["100.50","1.0","0","1"],["100.00","2","0","1"]]"#,
+            r#"[["101.0","3","0","1"]]"#,
+            crc32fast::hash(b"100.50:1.0:101.0:3:100.00:2"),
+        );
+        // The update removes the best bid and changes the ask, leaving 100.00 as sent before.
+        let update = push(
+            "update",
+            10,
+            11,
+            r#"[["100.50","0","0","0"]]"#,
+            r#"[["101.0","4.50","0","1"]]"#,
+            crc32fast::hash(b"100.00:2:101.0:4.50"),
+        );
+
+        let mut sync = sync();
+        assert_eq!(sync.on_event(&okx.parse_book(&snapshot, Instant::now()).unwrap().unwrap()), SyncOutcome::Synced);
+        assert_eq!(sync.on_event(&okx.parse_book(&update, Instant::now()).unwrap().unwrap()), SyncOutcome::Applied);
+    }
+
+    #[test]
+    fn keeps_the_text_of_coalesced_updates() {
+        let okx = OkxExchange::default();
+        let snapshot = push("snapshot", -1, 10, r#"[["100.0","1","0","1"]]"#, r#"[["101.0","1","0","1"]]"#, crc32fast::hash(b"100.0:1:101.0:1"));
+        let first = push("update", 10, 11, r#"[["100.0","2.0","0","1"],["99.50","1","0","1"]]"#, "[]", 0);
+        let second = push("update", 11, 12, r#"[["100.0","3.00","0","1"]]"#, r#"[["101.50","2","0","1"]]"#, crc32fast::hash(b"100.0:3.00:101.0:1:99.50:1:101.50:2"));
+
+        let mut merged = okx.parse_book(&first, Instant::now()).unwrap().unwrap();
+        merged.coalesce(okx.parse_book(&second, Instant::now()).unwrap().unwrap());
+        let mut sync = sync();
+        assert_eq!(sync.on_event(&okx.parse_book(&snapshot, Instant::now()).unwrap().unwrap()), SyncOutcome::Synced);
+        assert_eq!(sync.on_event(&merged), SyncOutcome::Applied);
+    }
+}
@@ -0,0 +1,350 @@
+use std::borrow::Cow;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+use crate::exchange::{ExchangeFeed, Frame, WsError};
+use crate::exchange_api_types::{
+    FundingRate, MarkPrice, OrderBookDelta, RestSnapshot, V2WsMessage, V2WsTradeMessage, WsEnvelope, WsFundingMessage, WsMarkPriceMessage,
+    WsMessage, WsTrade, WsTradeMessage,
+};
+use crate::feed::MarketEvent;
+use crate::rest::WOOX_REST_URL;
+use crate::retry::RetryPolicy;
+use crate::snapshot::{SnapshotError, SnapshotSource, WooxRestSource, WOOX_REST_ORDERBOOK_URL};
+use crate::trading::WOOX_PRIVATE_WS_URL;
+
+pub const WOOX_WS_URL: &str = "wss://wss.woox.io/v3/public";
+const STAGING_WS_URL: &str = "wss://wss.staging.woox.io/v3/public";
+const STAGING_PRIVATE_WS_URL: &str = "wss://wss.staging.woox.io/v3/private";
+const STAGING_REST_URL: &str = "https://api.staging.woox.io";
+const ORDERBOOK_PATH: &str = "/v3/public/orderbook";
+const CLIENT_ID: &str = "client_id_x";
+
+const WOOX_SUBSCRIBE_CMD: &str = "SUBSCRIBE";
+const WOOX_PING_CMD: &str = "PING";
+const WOOX_PONG_CMD: &str = "PONG";
+
+// The legacy API's endpoints, its websocket streamed per application at
+// <url>/<application id>, and its events.
+const V2_WS_URL: &str = "wss://wss.woo.org/ws/stream";
+const V2_STAGING_WS_URL: &str = "wss://wss.staging.woo.org/ws/stream";
+const V1_REST_URL: &str = "https://api.woo.org";
+const V1_STAGING_REST_URL: &str = "https://api.staging.woo.org";
+const V1_ORDERBOOK_PATH: &str = "/v1/public/orderbook";
+const V2_SUBSCRIBE_EVENT: &str = "subscribe";
+const V2_PING_EVENT: &str = "ping";
+const V2_PONG_EVENT: &str = "pong";
+
+// ApiVersion is the generation of the Woo X public API followed. V3 is the current API. V2
+// is the legacy one, the v2 websocket and the v1 REST endpoints served with it, for
+// accounts still on it: its streams are per application, named symbol first, and send
+// numbers as numbers. Trading is always on v3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApiVersion {
+    V2,
+    #[default]
+    V3,
+}
+
+impl ApiVersion {
+    // from_name returns the version called name, v3, or v2 or v1 for the legacy API.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "v1" | "v2" => Some(ApiVersion::V2),
+            "v3" => Some(ApiVersion::V3),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ApiVersion::V2 => "v2",
+            ApiVersion::V3 => "v3",
+        }
+    }
+}
+
+// WooxEnvironment is the Woo X deployment connected to: production, or staging for testing
+// against with staging API keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WooxEnvironment {
+    #[default]
+    Prod,
+    Staging,
+}
+
+impl WooxEnvironment {
+    // from_name returns the environment called name, prod or staging.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "prod" | "production" => Some(WooxEnvironment::Prod),
+            "staging" => Some(WooxEnvironment::Staging),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WooxEnvironment::Prod => "prod",
+            WooxEnvironment::Staging => "staging",
+        }
+    }
+
+    pub fn ws_url(self) -> &'static str {
+        match self {
+            WooxEnvironment::Prod => WOOX_WS_URL,
+            WooxEnvironment::Staging => STAGING_WS_URL,
+        }
+    }
+
+    pub fn private_ws_url(self) -> &'static str {
+        match self {
+            WooxEnvironment::Prod => WOOX_PRIVATE_WS_URL,
+            WooxEnvironment::Staging => STAGING_PRIVATE_WS_URL,
+        }
+    }
+
+    // rest_url is the base url of the public REST API and the host private requests are
+    // signed for and sent to.
+    pub fn rest_url(self) -> &'static str {
+        match self {
+            WooxEnvironment::Prod => WOOX_REST_URL,
+            WooxEnvironment::Staging => STAGING_REST_URL,
+        }
+    }
+
+    pub fn orderbook_url(self) -> String {
+        format!("{}{}", self.rest_url(), ORDERBOOK_PATH)
+    }
+
+    // exchange returns the public API of the environment.
+    pub fn exchange(self) -> WooxExchange {
+        WooxExchange::new(self.ws_url(), &self.orderbook_url())
+    }
+
+    // ws_url_for is the public websocket of version, streaming for the application with
+    // application_id on the legacy API.
+    pub fn ws_url_for(self, version: ApiVersion, application_id: &str) -> String {
+        match (version, self) {
+            (ApiVersion::V3, _) => self.ws_url().to_string(),
+            (ApiVersion::V2, WooxEnvironment::Prod) => format!("{}/{}", V2_WS_URL, application_id),
+            (ApiVersion::V2, WooxEnvironment::Staging) => format!("{}/{}", V2_STAGING_WS_URL, application_id),
+        }
+    }
+
+    // orderbook_url_for is the REST orderbook endpoint of version.
+    pub fn orderbook_url_for(self, version: ApiVersion) -> String {
+        match (version, self) {
+            (ApiVersion::V3, _) => self.orderbook_url(),
+            (ApiVersion::V2, WooxEnvironment::Prod) => format!("{}{}", V1_REST_URL, V1_ORDERBOOK_PATH),
+            (ApiVersion::V2, WooxEnvironment::Staging) => format!("{}{}", V1_STAGING_REST_URL, V1_ORDERBOOK_PATH),
+        }
+    }
+
+    // exchange_for returns the public API of version, streaming for the application with
+    // application_id on the legacy API.
+    pub fn exchange_for(self, version: ApiVersion, application_id: &str) -> WooxExchange {
+        WooxExchange::new(&self.ws_url_for(version, application_id), &self.orderbook_url_for(version)).with_version(version)
+    }
+}
+
+// WooxExchange is the Woo X v3 public API, or the legacy one with_version selects. Woo X
+// sequences its depth stream by timestamp: each delta carries the timestamp of the one
+// before it, and snapshots the timestamp of the last delta they contain.
+pub struct WooxExchange {
+    ws_url: String,
+    rest: WooxRestSource,
+    version: ApiVersion,
+}
+
+impl Default for WooxExchange {
+    fn default() -> Self {
+        Self::new(WOOX_WS_URL, WOOX_REST_ORDERBOOK_URL)
+    }
+}
+
+impl WooxExchange {
+    // new returns the API served at ws_url, with snapshots from the orderbook endpoint at
+    // orderbook_url.
+    pub fn new(ws_url: &str, orderbook_url: &str) -> Self {
+        Self { ws_url: ws_url.to_string(), rest: WooxRestSource::new(orderbook_url), version: ApiVersion::default() }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { rest: self.rest.with_retry(retry), ..self }
+    }
+
+    // with_version speaks version's topics and messages, to the endpoints of that version
+    // given to new.
+    pub fn with_version(self, version: ApiVersion) -> Self {
+        Self { rest: self.rest.with_version(version), version, ..self }
+    }
+
+    pub fn version(&self) -> ApiVersion {
+        self.version
+    }
+
+    // pong is the reply to a ping.
+    fn pong(&self) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        match self.version {
+            ApiVersion::V3 => json!(
+                {
+                    "cmd": WOOX_PONG_CMD,
+                    "ts": now
+                }).to_string(),
+            ApiVersion::V2 => json!({ "event": V2_PONG_EVENT, "ts": now }).to_string(),
+        }
+    }
+}
+
+impl ExchangeFeed for WooxExchange {
+    fn name(&self) -> &str {
+        "woox"
+    }
+
+    fn ws_url(&self) -> &str {
+        &self.ws_url
+    }
+
+    // book_topic is the depth stream at max_level levels, which the legacy API doesn't
+    // limit: its stream updates the whole book.
+    fn book_topic(&self, symbol: &str, max_level: usize) -> String {
+        match self.version {
+            ApiVersion::V3 => format!("orderbookupdate@{}@{}", symbol, max_level),
+            ApiVersion::V2 => format!("{}@orderbookupdate", symbol),
+        }
+    }
+
+    fn trade_topic(&self, symbol: &str) -> String {
+        match self.version {
+            ApiVersion::V3 => format!("trade@{}", symbol),
+            ApiVersion::V2 => format!("{}@trade", symbol),
+        }
+    }
+
+    fn subscribe(&self, topic: &str) -> String {
+        match self.version {
+            ApiVersion::V3 => json!({
+                "id": CLIENT_ID,
+                "cmd": WOOX_SUBSCRIBE_CMD,
+                "params": [topic]
+            }),
+            ApiVersion::V2 => json!({
+                "id": CLIENT_ID,
+                "event": V2_SUBSCRIBE_EVENT,
+                "topic": topic
+            }),
+        }
+        .to_string()
+    }
+
+    fn frame(&self, text: &str) -> Frame {
+        // Text that isn't a message is passed on as data, for parsing to report.
+        let Ok(envelope) = serde_json::from_str::<WsEnvelope>(text) else { return Frame::Data };
+        if envelope.topic.is_some() {
+            return Frame::Data;
+        }
+        let ping = match self.version {
+            ApiVersion::V3 => envelope.cmd.as_deref() == Some(WOOX_PING_CMD),
+            ApiVersion::V2 => envelope.event.as_deref() == Some(V2_PING_EVENT),
+        };
+        if ping {
+            return Frame::Ping { reply: self.pong(), ts: envelope.ts };
+        }
+        let command = envelope.cmd.or(envelope.event).unwrap_or_default().into_owned();
+        match envelope.success {
+            Some(true) => Frame::Ack { command },
+            Some(false) => Frame::Error(WsError::from_reply(&command, envelope.message.map_or_else(|| text.to_string(), Cow::into_owned))),
+            None => Frame::Control,
+        }
+    }
+
+    fn parse_book(&self, text: &str, received_at: Instant) -> Result<Option<MarketEvent>, serde_json::Error> {
+        let parsed = match self.version {
+            ApiVersion::V3 => serde_json::from_str::<WsMessage>(text)?,
+            ApiVersion::V2 => {
+                let parsed: V2WsMessage = serde_json::from_str(text)?;
+                WsMessage { ts: parsed.ts, data: parsed.data.map(OrderBookDelta::from) }
+            }
+        };
+        Ok(parsed.data.map(|data| MarketEvent {
+            ts: parsed.ts,
+            prev_ts: data.prev_ts,
+            seq: parsed.ts,
+            prev_seq: data.prev_ts,
+            delta: data,
+            snapshot: false,
+            checksum: None,
+            received_at,
+            raw: None,
+            text: None,
+        }))
+    }
+
+    fn parse_trades(&self, text: &str) -> Result<Vec<WsTrade>, serde_json::Error> {
+        if self.version == ApiVersion::V2 {
+            return Ok(serde_json::from_str::<V2WsTradeMessage>(text)?.into_trades());
+        }
+        let parsed: WsTradeMessage = serde_json::from_str(text)?;
+        Ok(parsed.data.map(|data| data.into_vec()).unwrap_or_default())
+    }
+
+    // funding_topic is the estimated funding rate stream, which only perpetuals have.
+    fn funding_topic(&self, symbol: &str) -> Option<String> {
+        symbol.starts_with("PERP_").then(|| match self.version {
+            ApiVersion::V3 => format!("fundingrate@{}", symbol),
+            ApiVersion::V2 => format!("{}@estfundingrate", symbol),
+        })
+    }
+
+    fn parse_funding(&self, text: &str) -> Result<Option<FundingRate>, serde_json::Error> {
+        let parsed: WsFundingMessage = serde_json::from_str(text)?;
+        Ok(parsed.data.map(|data| FundingRate {
+            symbol: data.symbol,
+            rate: data.rate,
+            next_funding_ts: data.next_funding_ts,
+            ts: parsed.ts,
+        }))
+    }
+
+    // mark_price_topic is the mark price stream, which only perpetuals have.
+    fn mark_price_topic(&self, symbol: &str) -> Option<String> {
+        symbol.starts_with("PERP_").then(|| match self.version {
+            ApiVersion::V3 => format!("markprice@{}", symbol),
+            ApiVersion::V2 => format!("{}@markprice", symbol),
+        })
+    }
+
+    fn parse_mark_price(&self, text: &str) -> Result<Option<MarkPrice>, serde_json::Error> {
+        let parsed: WsMarkPriceMessage = serde_json::from_str(text)?;
+        Ok(parsed.data.map(|data| MarkPrice { symbol: data.symbol, price: data.price, ts: parsed.ts }))
+    }
+
+    fn snapshot(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        self.rest.fetch(symbol, max_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::WsErrorKind;
+
+    #[test]
+    fn classifies_frames_from_the_envelope() {
+        let exchange = WooxExchange::default();
+        let delta = r#"{"topic":"orderbookupdate@SPOT_BTC_USDT@50","ts":1100,"data":{"prevTs":1000,"bids":[["100.0","1"]],"asks":[]}}"#;
+        assert_eq!(exchange.frame(delta), Frame::Data);
+        assert!(matches!(exchange.frame(r#"{"cmd":"PING","ts":5}"#), Frame::Ping { ts: Some(5), .. }));
+        let ack = r#"{"id":"client_id_x","cmd":"SUBSCRIBE","success":true,"ts":1}"#;
+        assert_eq!(exchange.frame(ack), Frame::Ack { command: "SUBSCRIBE".to_string() });
+        // Escaped text can't be borrowed, and is unescaped into the error.
+        let rejected = r#"{"id":"client_id_x","cmd":"SUBSCRIBE","success":false,"ts":1,"errorMsg":"Invalid topic \"SPOT_BTC_USTD\""}"#;
+        let Frame::Error(error) = exchange.frame(rejected) else { panic!("{:?}", exchange.frame(rejected)) };
+        assert_eq!(error.kind, WsErrorKind::Subscription);
+        assert_eq!(error.message, r#"Invalid topic "SPOT_BTC_USTD""#);
+        assert_eq!(exchange.frame("not json"), Frame::Data);
+    }
+}
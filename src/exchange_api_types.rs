@@ -1,4 +1,7 @@
-use serde::{Deserialize, Deserializer};
+use std::borrow::Cow;
+
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 // WsQuote is a struct representation of the quote response apart of the WsQuote
 #[derive(Debug, Clone, Copy)]
@@ -7,6 +10,7 @@ pub struct WsQuote {
     pub quantity: f64,
 }
 
+#[cfg(not(feature = "fast-parse"))]
 impl<'de> Deserialize<'de> for WsQuote {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -22,6 +26,78 @@ impl<'de> Deserialize<'de> for WsQuote {
     }
 }
 
+// With fast-parse, a quote's price and quantity are parsed from the strings in the message,
+// borrowed where they aren't escaped, rather than each copied into a Vec<String> first.
+#[cfg(feature = "fast-parse")]
+impl<'de> Deserialize<'de> for WsQuote {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(fast::QuoteVisitor)
+    }
+}
+
+#[cfg(feature = "fast-parse")]
+mod fast {
+    use std::fmt;
+
+    use serde::de::{self, Deserialize, Deserializer, IgnoredAny, SeqAccess, Visitor};
+
+    use super::WsQuote;
+
+    pub(super) struct QuoteVisitor;
+
+    impl<'de> Visitor<'de> for QuoteVisitor {
+        type Value = WsQuote;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a [price, quantity] array of strings")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<WsQuote, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let too_short = || de::Error::custom("WsQuote array too short");
+            let StrF64(price) = seq.next_element()?.ok_or_else(too_short)?;
+            let StrF64(quantity) = seq.next_element()?.ok_or_else(too_short)?;
+            // Anything after the quantity is ignored, as the Vec<String> path does.
+            while seq.next_element::<IgnoredAny>()?.is_some() {}
+            Ok(WsQuote { price, quantity })
+        }
+    }
+
+    // StrF64 is a number sent as a string, parsed without being copied out of the message.
+    struct StrF64(f64);
+
+    impl<'de> Deserialize<'de> for StrF64 {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_str(StrF64Visitor)
+        }
+    }
+
+    struct StrF64Visitor;
+
+    impl<'de> Visitor<'de> for StrF64Visitor {
+        type Value = StrF64;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a number as a string")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<StrF64, E>
+        where
+            E: de::Error,
+        {
+            value.parse().map(StrF64).map_err(E::custom)
+        }
+    }
+}
+
 fn f64_from_string<'de, D>(deserializer: D) -> Result<f64, D::Error>
 where
     D: Deserializer<'de>,
@@ -30,38 +106,82 @@ where
     s.parse::<f64>().map_err(serde::de::Error::custom)
 }
 
+fn f64_to_string<S>(value: &f64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&value.to_string())
+}
+
 // RestQuote is a struct representation of the quore response apart of the REST endpoint
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct RestQuote {
-    #[serde(deserialize_with = "f64_from_string")]
+    #[serde(deserialize_with = "f64_from_string", serialize_with = "f64_to_string")]
     pub price: f64,
-    #[serde(deserialize_with = "f64_from_string")]
+    #[serde(deserialize_with = "f64_from_string", serialize_with = "f64_to_string")]
     pub quantity: f64,
 }
 
 // SnapshotData is a struct represntation of a snapshot provided from Woo X
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SnapshotData {
     pub bids: Vec<RestQuote>,
     pub asks: Vec<RestQuote>,
 }
 
-// RestSnapshot is a struct representation of the snapshot response from Woo X.
-#[derive(Debug, Deserialize)]
+// RestSnapshot is a struct representation of the snapshot response from Woo X, which is
+// also the normalized snapshot every exchange's is converted to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RestSnapshot {
     pub timestamp: u64,
     pub data: SnapshotData,
+    // seq places the snapshot in the exchange's stream, like MarketEvent::seq. It is only
+    // set by venues that don't sequence their stream by timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+}
+
+impl RestSnapshot {
+    // new returns a snapshot at timestamp with stream position seq, set only if it differs
+    // from the timestamp.
+    pub fn new(timestamp: u64, seq: u64, data: SnapshotData) -> Self {
+        Self { timestamp, data, seq: (seq != timestamp).then_some(seq) }
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq.unwrap_or(self.timestamp)
+    }
+}
+
+// WsEnvelope is the fields any Woo X websocket message may have, read first to tell pings,
+// command acks and rejections from stream data, which has a topic, without parsing the
+// data. Its text is borrowed from the message where it isn't escaped, and the topic and
+// data are skipped over, so reading it allocates nothing for stream data.
+#[derive(Debug, Default, Deserialize)]
+pub struct WsEnvelope<'a> {
+    #[serde(borrow)]
+    pub cmd: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub event: Option<Cow<'a, str>>,
+    pub topic: Option<IgnoredAny>,
+    // success is set on the reply to a command, false if it was rejected.
+    pub success: Option<bool>,
+    #[serde(borrow, alias = "errorMsg", alias = "msg")]
+    pub message: Option<Cow<'a, str>>,
+    pub ts: Option<u64>,
 }
 
 // WsMessage is a struct representation of the delta response from the Woo X websocket.
 #[derive(Debug, Deserialize)]
 pub struct WsMessage {
+    #[serde(default)]
+    pub ts: u64,
     pub data: Option<OrderBookDelta>
 }
 
 
 // WsQuote is a struct representation of the quote response apart of the websocket
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct OrderBookDelta {
     #[serde(rename = "prevTs")]
     pub prev_ts: u64,
@@ -69,3 +189,238 @@ pub struct OrderBookDelta {
     pub asks: Vec<WsQuote>,
 }
 
+// LevelText is the text a level's price and quantity were sent as.
+pub type LevelText = (Box<str>, Box<str>);
+
+// QuoteText is the text a delta's quotes were sent as, in the order of the delta's bids and
+// asks, kept for exchanges checksumming the text of their book.
+#[derive(Debug, Clone, Default)]
+pub struct QuoteText {
+    pub bids: Vec<LevelText>,
+    pub asks: Vec<LevelText>,
+}
+
+
+// Side is the side of the book a quote rests on, or the aggressor side of a trade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+// WsTrade is a struct representation of a public trade from the Woo X websocket.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WsTrade {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "px", deserialize_with = "f64_from_string")]
+    pub price: f64,
+    #[serde(rename = "sx", deserialize_with = "f64_from_string")]
+    pub quantity: f64,
+    #[serde(rename = "sd")]
+    pub side: Side,
+    pub ts: u64,
+    // backfilled marks trades recovered from the REST API after a websocket gap, rather
+    // than received live.
+    #[serde(skip)]
+    pub backfilled: bool,
+}
+
+// WsTrades is the data of a trade message, which may carry a single trade or a batch.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum WsTrades {
+    One(WsTrade),
+    Many(Vec<WsTrade>),
+}
+
+impl WsTrades {
+    pub fn into_vec(self) -> Vec<WsTrade> {
+        match self {
+            WsTrades::One(trade) => vec![trade],
+            WsTrades::Many(trades) => trades,
+        }
+    }
+}
+
+// WsTradeMessage is a struct representation of the trade response from the Woo X websocket.
+#[derive(Debug, Deserialize)]
+pub struct WsTradeMessage {
+    pub data: Option<WsTrades>,
+}
+
+// FundingRate is a perpetual's current funding rate, normalized by its ExchangeFeed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FundingRate {
+    pub symbol: String,
+    // rate is paid by longs to shorts each funding interval, as a fraction of notional.
+    pub rate: f64,
+    // next_funding_ts is when the rate is next charged, in ms since the epoch, or 0 if the
+    // exchange doesn't say.
+    pub next_funding_ts: u64,
+    pub ts: u64,
+}
+
+// WsFunding is a struct representation of the funding rate data from the Woo X websocket.
+#[derive(Debug, Deserialize)]
+pub struct WsFunding {
+    #[serde(rename = "s", alias = "symbol")]
+    pub symbol: String,
+    #[serde(rename = "r", alias = "fundingRate", deserialize_with = "f64_from_string_or_number")]
+    pub rate: f64,
+    #[serde(rename = "ft", alias = "fundingTs", default)]
+    pub next_funding_ts: u64,
+}
+
+// WsFundingMessage is a struct representation of the funding rate response from the Woo X
+// websocket.
+#[derive(Debug, Deserialize)]
+pub struct WsFundingMessage {
+    #[serde(default)]
+    pub ts: u64,
+    pub data: Option<WsFunding>,
+}
+
+// MarkPrice is the mark price of a derivative, the price its positions are valued and
+// liquidated at, as normalized from a venue's mark price stream.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MarkPrice {
+    pub symbol: String,
+    pub price: f64,
+    pub ts: u64,
+}
+
+// WsMarkPrice is a struct representation of the mark price data from the Woo X websocket.
+#[derive(Debug, Deserialize)]
+pub struct WsMarkPrice {
+    #[serde(rename = "s", alias = "symbol")]
+    pub symbol: String,
+    #[serde(rename = "p", alias = "price", deserialize_with = "f64_from_string_or_number")]
+    pub price: f64,
+}
+
+// WsMarkPriceMessage is a struct representation of the mark price response from the Woo X
+// websocket.
+#[derive(Debug, Deserialize)]
+pub struct WsMarkPriceMessage {
+    #[serde(default)]
+    pub ts: u64,
+    pub data: Option<WsMarkPrice>,
+}
+
+// The legacy Woo X API, its v2 websocket and v1 REST endpoints, sends numbers as numbers
+// and levels as pairs rather than the v3 API's strings. Its messages are converted to the
+// v3 ones above as they are parsed.
+
+// V2Quote is a [price, quantity] level from the v2 websocket.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct V2Quote(
+    #[serde(deserialize_with = "f64_from_string_or_number")] pub f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")] pub f64,
+);
+
+impl From<V2Quote> for WsQuote {
+    fn from(V2Quote(price, quantity): V2Quote) -> Self {
+        WsQuote { price, quantity }
+    }
+}
+
+// V2OrderBookDelta is a struct representation of the delta data from the v2 websocket.
+#[derive(Debug, Deserialize)]
+pub struct V2OrderBookDelta {
+    #[serde(rename = "prevTs")]
+    pub prev_ts: u64,
+    pub bids: Vec<V2Quote>,
+    pub asks: Vec<V2Quote>,
+}
+
+impl From<V2OrderBookDelta> for OrderBookDelta {
+    fn from(delta: V2OrderBookDelta) -> Self {
+        OrderBookDelta {
+            prev_ts: delta.prev_ts,
+            bids: delta.bids.into_iter().map(WsQuote::from).collect(),
+            asks: delta.asks.into_iter().map(WsQuote::from).collect(),
+        }
+    }
+}
+
+// V2WsMessage is a struct representation of the delta response from the v2 websocket.
+#[derive(Debug, Deserialize)]
+pub struct V2WsMessage {
+    #[serde(default)]
+    pub ts: u64,
+    pub data: Option<V2OrderBookDelta>,
+}
+
+// V2WsTrade is a struct representation of a public trade from the v2 websocket, which
+// carries the time of the trade on its message.
+#[derive(Debug, Deserialize)]
+pub struct V2WsTrade {
+    pub symbol: String,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub price: f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub size: f64,
+    pub side: Side,
+}
+
+// V2WsTradeMessage is a struct representation of the trade response from the v2 websocket.
+#[derive(Debug, Deserialize)]
+pub struct V2WsTradeMessage {
+    #[serde(default)]
+    pub ts: u64,
+    pub data: Option<V2WsTrade>,
+}
+
+impl V2WsTradeMessage {
+    pub fn into_trades(self) -> Vec<WsTrade> {
+        let ts = self.ts;
+        self.data
+            .map(|trade| WsTrade { symbol: trade.symbol, price: trade.price, quantity: trade.size, side: trade.side, ts, backfilled: false })
+            .into_iter()
+            .collect()
+    }
+}
+
+// V1RestQuote is a level of a v1 REST orderbook.
+#[derive(Debug, Deserialize)]
+pub struct V1RestQuote {
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub price: f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub quantity: f64,
+}
+
+// V1RestSnapshot is a struct representation of the v1 REST orderbook response, whose levels
+// aren't nested under data.
+#[derive(Debug, Deserialize)]
+pub struct V1RestSnapshot {
+    pub timestamp: u64,
+    pub bids: Vec<V1RestQuote>,
+    pub asks: Vec<V1RestQuote>,
+}
+
+impl From<V1RestSnapshot> for RestSnapshot {
+    fn from(snapshot: V1RestSnapshot) -> Self {
+        let quotes = |quotes: Vec<V1RestQuote>| quotes.into_iter().map(|quote| RestQuote { price: quote.price, quantity: quote.quantity }).collect();
+        let data = SnapshotData { bids: quotes(snapshot.bids), asks: quotes(snapshot.asks) };
+        RestSnapshot::new(snapshot.timestamp, snapshot.timestamp, data)
+    }
+}
+
+pub(crate) fn f64_from_string_or_number<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(f64),
+        String(String),
+    }
+    match Raw::deserialize(deserializer)? {
+        Raw::Number(value) => Ok(value),
+        Raw::String(s) => s.parse::<f64>().map_err(serde::de::Error::custom),
+    }
+}
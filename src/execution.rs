@@ -0,0 +1,285 @@
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::exchange_api_types::Side;
+use crate::oms::OrderManager;
+use crate::order::{OrderRequest, OrderStatus, OrderUpdate};
+use crate::orderbook::LocalOrderBook;
+use crate::trading::TradingClient;
+use crate::units::{Notional, Price, Qty};
+
+// AlgoKind is how an ExecutionAlgo works its parent order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlgoKind {
+    // Twap releases the quantity in equal slices over duration, the first at the start. A
+    // slice that doesn't fill rolls over into the next, and whatever is left at the end keeps
+    // working until it fills or the algo is cancelled.
+    Twap { duration: Duration, slices: u32 },
+    // Iceberg shows at most display of the quantity at a time, placing the next clip as each
+    // one fills.
+    Iceberg { display: Qty },
+}
+
+// AlgoConfig is a parent order for an ExecutionAlgo to work. Child orders rest at the touch
+// on the order's side, repriced as it moves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlgoConfig {
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: Qty,
+    pub kind: AlgoKind,
+    // limit is the worst price a child order is placed at, if any.
+    pub limit: Option<Price>,
+    // max_spread_bps pauses the algo while the book's spread is wider, in bps of the mid.
+    pub max_spread_bps: Option<f64>,
+    // lot_size is what child quantities are rounded down to.
+    pub lot_size: f64,
+}
+
+// AlgoEventKind is what happened to an ExecutionAlgo.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlgoEventKind {
+    // Placed is a child order placed, by its client order id.
+    Placed { client_order_id: u64, price: Price, quantity: Qty },
+    // Repricing means the child order is being cancelled to replace it, at the touch or with
+    // a newly released slice.
+    Repricing { client_order_id: u64 },
+    Filled { price: Price, quantity: Qty },
+    // Paused means the spread blew out past max_spread_bps and the child order is being
+    // pulled, until Resumed.
+    Paused { spread_bps: f64 },
+    Resumed,
+    Completed,
+    Cancelled,
+    // Failed means a child order was rejected, with the reason, and the algo stopped.
+    Failed(String),
+}
+
+// AlgoEvent reports progress on an ExecutionAlgo: what happened, and how much of the parent
+// order has filled once it did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlgoEvent {
+    pub kind: AlgoEventKind,
+    pub filled: Qty,
+    pub remaining: Qty,
+    pub average_price: Option<Price>,
+}
+
+// ChildOrder is the order an ExecutionAlgo has working.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChildOrder {
+    client_order_id: u64,
+    price: Price,
+    quantity: Qty,
+    filled: Qty,
+    // cancelling is set once a cancel has been requested, until the order is final.
+    cancelling: bool,
+}
+
+// ExecutionAlgo works a parent order through an OrderManager, one child order at a time.
+// It does no IO of its own: step is called with each book update, or on a timer for a TWAP's
+// slices, and on_order_update with the manager's updates for the child orders. A child is
+// only replaced once its cancel is confirmed, so the parent can never overfill.
+#[derive(Debug, Clone)]
+pub struct ExecutionAlgo {
+    config: AlgoConfig,
+    started: Instant,
+    filled: Qty,
+    notional: Notional,
+    child: Option<ChildOrder>,
+    paused: bool,
+    done: bool,
+}
+
+impl ExecutionAlgo {
+    // new starts working config, with a TWAP's schedule starting at now.
+    pub fn new(config: AlgoConfig, now: Instant) -> Self {
+        Self {
+            config,
+            started: now,
+            filled: Qty::ZERO,
+            notional: Notional::ZERO,
+            child: None,
+            paused: false,
+            done: false,
+        }
+    }
+
+    pub fn config(&self) -> &AlgoConfig {
+        &self.config
+    }
+
+    pub fn filled(&self) -> Qty {
+        self.filled
+    }
+
+    pub fn remaining(&self) -> Qty {
+        (self.config.quantity - self.filled).max(Qty::ZERO)
+    }
+
+    pub fn average_price(&self) -> Option<Price> {
+        self.notional.checked_price(self.filled)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // is_done returns true once the algo has completed, been cancelled or failed.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // released returns how much of the parent order may have been filled by now.
+    pub fn released(&self, now: Instant) -> Qty {
+        match self.config.kind {
+            AlgoKind::Twap { duration, slices } => {
+                let slices = slices.max(1);
+                let slice = duration / slices;
+                let elapsed = now.saturating_duration_since(self.started);
+                let released = match slice.is_zero() {
+                    true => slices,
+                    false => ((elapsed.as_secs_f64() / slice.as_secs_f64()) as u32 + 1).min(slices),
+                };
+                let released = self.config.quantity.checked_scale(released as f64 / slices as f64);
+                released.unwrap_or(self.config.quantity)
+            }
+            AlgoKind::Iceberg { .. } => self.config.quantity,
+        }
+    }
+
+    // step works the order against book at now: pausing while the spread is too wide,
+    // repricing the child order to the touch and placing the next one once the last is done.
+    pub fn step<C: TradingClient>(&mut self, orders: &mut OrderManager<C>, book: &LocalOrderBook, now: Instant) -> Vec<AlgoEvent> {
+        let mut events = Vec::new();
+        if self.done || self.child.is_some_and(|child| child.cancelling) {
+            return events;
+        }
+        let (Some((best_bid, _)), Some((best_ask, _))) = (book.best_bid(), book.best_ask()) else {
+            return events;
+        };
+
+        let spread_bps = (best_ask - best_bid).bps_of(best_bid.midpoint(best_ask));
+        if self.config.max_spread_bps.is_some_and(|max| spread_bps > max) {
+            if !self.paused {
+                self.paused = true;
+                info!(symbol = %self.config.symbol, spread_bps, "Execution paused on a wide spread");
+                events.push(self.event(AlgoEventKind::Paused { spread_bps }));
+            }
+            self.cancel_child(orders);
+            return events;
+        }
+        if self.paused {
+            self.paused = false;
+            events.push(self.event(AlgoEventKind::Resumed));
+        }
+
+        let price = match (self.config.side, self.config.limit) {
+            (Side::Buy, Some(limit)) => best_bid.min(limit),
+            (Side::Buy, None) => best_bid,
+            (Side::Sell, Some(limit)) => best_ask.max(limit),
+            (Side::Sell, None) => best_ask,
+        };
+        let mut target = (self.released(now) - self.filled).max(Qty::ZERO);
+        if let AlgoKind::Iceberg { display } = self.config.kind {
+            target = target.min(display);
+        }
+        let target = lots(target, self.config.lot_size);
+
+        match self.child {
+            Some(child) => {
+                // A newly released TWAP slice is added to the child by replacing it.
+                let stale = child.price != price || child.quantity - child.filled < target;
+                if stale && self.cancel_child(orders) {
+                    events.push(self.event(AlgoEventKind::Repricing { client_order_id: child.client_order_id }));
+                }
+            }
+            None if target > Qty::ZERO => {
+                let request = OrderRequest::limit(&self.config.symbol, self.config.side, target, price);
+                let update = orders.submit(request, Some(book));
+                self.child = Some(ChildOrder {
+                    client_order_id: update.order_id,
+                    price,
+                    quantity: target,
+                    filled: Qty::ZERO,
+                    cancelling: false,
+                });
+                events.push(self.event(AlgoEventKind::Placed { client_order_id: update.order_id, price, quantity: target }));
+                events.extend(self.on_order_update(&update));
+            }
+            None => {}
+        }
+        events
+    }
+
+    // on_order_update applies an update from the OrderManager, ignoring those for orders
+    // other than the child order.
+    pub fn on_order_update(&mut self, update: &OrderUpdate) -> Vec<AlgoEvent> {
+        let mut events = Vec::new();
+        let Some(child) = self.child.as_mut().filter(|child| child.client_order_id == update.order_id) else {
+            return events;
+        };
+        child.filled = update.filled;
+        if let Some(fill) = &update.fill {
+            self.filled += fill.quantity;
+            self.notional += fill.price * fill.quantity;
+            events.push(self.event(AlgoEventKind::Filled { price: fill.price, quantity: fill.quantity }));
+        }
+        if !update.status.is_final() {
+            return events;
+        }
+
+        self.child = None;
+        if self.done {
+            return events;
+        }
+        if let OrderStatus::Rejected(reason) = &update.status {
+            warn!(symbol = %self.config.symbol, %reason, "Execution child order rejected");
+            self.done = true;
+            events.push(self.event(AlgoEventKind::Failed(reason.clone())));
+        } else if lots(self.remaining(), self.config.lot_size).is_zero() {
+            self.done = true;
+            events.push(self.event(AlgoEventKind::Completed));
+        }
+        events
+    }
+
+    // cancel stops the algo, cancelling the child order. Fills of the child before the cancel
+    // is confirmed are still applied.
+    pub fn cancel<C: TradingClient>(&mut self, orders: &OrderManager<C>) -> Vec<AlgoEvent> {
+        if self.done {
+            return Vec::new();
+        }
+        self.cancel_child(orders);
+        self.done = true;
+        vec![self.event(AlgoEventKind::Cancelled)]
+    }
+
+    // cancel_child requests that the child order is cancelled, returning whether a cancel was
+    // requested. A cancel that fails is retried on the next step.
+    fn cancel_child<C: TradingClient>(&mut self, orders: &OrderManager<C>) -> bool {
+        let Some(child) = self.child.as_mut().filter(|child| !child.cancelling) else {
+            return false;
+        };
+        match orders.cancel(child.client_order_id) {
+            Ok(()) => {
+                child.cancelling = true;
+                true
+            }
+            Err(e) => {
+                warn!(client_order_id = child.client_order_id, error = %e, "Failed to cancel execution child order");
+                false
+            }
+        }
+    }
+
+    fn event(&self, kind: AlgoEventKind) -> AlgoEvent {
+        AlgoEvent { kind, filled: self.filled, remaining: self.remaining(), average_price: self.average_price() }
+    }
+}
+
+// lots rounds quantity down to a multiple of lot_size.
+fn lots(quantity: Qty, lot_size: f64) -> Qty {
+    quantity.floor_to(lot_size)
+}
@@ -0,0 +1,62 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::metrics::FeedMetrics;
+use crate::queue::{self, Coalesce, QueueConfig};
+
+// Fanout dispatches every item of a stream to each of its subscribers, so the book, a
+// recorder, a publisher and strategies can all consume the same market events
+// independently. Each subscriber has its own bounded queue with its own backpressure, so one
+// that falls behind drops or coalesces only its own items, unless it blocks, which holds up
+// the dispatcher and with it every subscriber. Subscribers only receive the items dispatched
+// after they subscribe.
+pub struct Fanout<T: Coalesce> {
+    subscribers: Arc<Mutex<Vec<queue::Sender<T>>>>,
+    metrics: Option<Arc<FeedMetrics>>,
+}
+
+impl<T: Coalesce + Clone + Send + 'static> Fanout<T> {
+    // spawn starts a thread dispatching the items of source to the subscribers until it
+    // ends, which ends every subscription. Queue depths and the items dropped or coalesced
+    // are counted in metrics.
+    pub fn spawn<I>(source: I, metrics: Option<Arc<FeedMetrics>>) -> Self
+    where
+        I: IntoIterator<Item = T> + Send + 'static,
+    {
+        let subscribers: Arc<Mutex<Vec<queue::Sender<T>>>> = Arc::default();
+        let dispatched = Arc::clone(&subscribers);
+        thread::spawn(move || {
+            for item in source {
+                let mut subscribers = dispatched.lock().unwrap();
+                // Every subscriber but the last is sent a clone, the last the item itself.
+                // Subscriptions whose receiver has gone are dropped.
+                let mut item = Some(item);
+                let last = subscribers.len().saturating_sub(1);
+                let mut index = 0;
+                subscribers.retain(|subscriber| {
+                    let copy = match index == last {
+                        true => item.take(),
+                        false => item.clone(),
+                    };
+                    index += 1;
+                    copy.is_some_and(|copy| subscriber.send(copy).is_ok())
+                });
+            }
+            dispatched.lock().unwrap().clear();
+        });
+        Self { subscribers, metrics }
+    }
+
+    // subscribe returns a receiver of every item dispatched from now on, queued as config
+    // sets.
+    pub fn subscribe(&self, config: QueueConfig) -> queue::Receiver<T> {
+        let (sender, receiver) = queue::bounded(config, self.metrics.clone());
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    // subscribers returns the number of subscriptions still being dispatched to.
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+}
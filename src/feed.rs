@@ -0,0 +1,794 @@
+use std::io;
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "async")]
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::{debug, info, info_span, warn};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Connector, Message, WebSocket};
+use url::Url;
+
+use crate::arbitrator::{ArbitrationMetrics, Arbitrator, ArbiterInput};
+use crate::chaos::Chaos;
+use crate::clock::ClockSkew;
+use crate::exchange::woox::WooxExchange;
+use crate::exchange::{ExchangeFeed, Frame, WsError};
+use crate::exchange_api_types::{FundingRate, LevelText, MarkPrice, OrderBookDelta, QuoteText, WsQuote, WsTrade};
+use crate::metrics::FeedMetrics;
+use crate::poll::{self, PollMode};
+use crate::proxy::ProxyConfig;
+use crate::queue::{self, Coalesce, QueueConfig};
+use crate::recorder::{self, FrameRecorder, ReplayConfig};
+use crate::transport::{WsConnector, WsTransport};
+
+// SocketBackend selects the TCP layer the websocket runs over.
+#[derive(Debug, Clone, Default)]
+pub enum SocketBackend {
+    // Portable uses blocking std sockets and works everywhere.
+    #[default]
+    Portable,
+    // IoUring submits socket reads and writes through io_uring, with an optional kernel
+    // submission polling thread idling after the given number of ms.
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    IoUring { sqpoll_idle_ms: Option<u32> },
+    // Replay reads the frames of a recording instead of connecting, passing them through
+    // the same parsing path as live frames.
+    Replay(ReplayConfig),
+    // Transport opens every connection with the connector rather than a socket, e.g. a
+    // ScriptedConnector feeding the same reading and parsing path a fixed sequence of frames.
+    Transport(Arc<dyn WsConnector>),
+}
+
+// FeedConfig configures how feed connections are made.
+#[derive(Debug, Clone)]
+pub struct FeedConfig {
+    // exchange is the venue connected to, Woo X by default.
+    pub exchange: Arc<dyn ExchangeFeed>,
+    pub backend: SocketBackend,
+    // poll_mode selects whether the reader thread blocks on or busy polls the socket.
+    pub poll_mode: PollMode,
+    // reader_core pins every reader thread to a core, for busy polling without the thread
+    // being moved between cores.
+    pub reader_core: Option<usize>,
+    // frame_recorder records every raw frame read, for replaying later.
+    pub frame_recorder: Option<FrameRecorder>,
+    // metrics counts the messages, parse errors and connections of every connection made.
+    pub metrics: Option<Arc<FeedMetrics>>,
+    // clock is given the timestamp of every ping from the exchange, bounding its skew.
+    pub clock: Option<Arc<ClockSkew>>,
+    // proxy is the proxy websockets are opened through, unless it bypasses the exchange.
+    pub proxy: Option<ProxyConfig>,
+    // tls is the rustls config wss connections are made with, the system's roots if None.
+    pub tls: Option<Arc<rustls::ClientConfig>>,
+    // chaos injects faults into the data messages of every connection, for testing that the
+    // book recovers from them.
+    pub chaos: Option<Arc<Chaos>>,
+    // keep_raw keeps the message every book event was parsed from on the event, for dumping
+    // it when the book breaks an invariant.
+    pub keep_raw: bool,
+    // queue bounds the queue of book events between each reader thread and its consumer, and
+    // sets what the reader does once it is full.
+    pub queue: QueueConfig,
+    // errors is sent every command the exchange rejects on any connection. A connection
+    // whose subscription is rejected is closed, which ends its stream.
+    pub errors: Option<Sender<WsError>>,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            exchange: Arc::new(WooxExchange::default()),
+            backend: SocketBackend::default(),
+            poll_mode: PollMode::default(),
+            reader_core: None,
+            frame_recorder: None,
+            metrics: None,
+            clock: None,
+            proxy: None,
+            tls: None,
+            chaos: None,
+            keep_raw: false,
+            queue: QueueConfig::default(),
+            errors: None,
+        }
+    }
+}
+
+// MarketEvent represents an order book delta provided by an exchange, normalized by its
+// ExchangeFeed.
+#[derive(Clone)]
+pub struct MarketEvent {
+    // ts is the exchange timestamp of the event, in ms since the epoch, and prev_ts that
+    // of the event before it where the exchange provides it, or 0.
+    pub ts: u64,
+    pub prev_ts: u64,
+    // seq and prev_seq place the event in the exchange's stream: an event continues the
+    // stream when its prev_seq is the seq of the event before it, and a snapshot when it is
+    // the snapshot's seq. Venues sequencing by timestamp, like Woo X, use ts and prev_ts.
+    pub seq: u64,
+    pub prev_seq: u64,
+    pub delta: OrderBookDelta,
+    // snapshot is set on events carrying the whole book rather than changes to it, which
+    // venues pushing snapshots on the stream send when subscribed to.
+    pub snapshot: bool,
+    // checksum is the exchange's checksum of the book after the event, if it sends one.
+    pub checksum: Option<u32>,
+    // received_at is when the reader thread parsed the event off the socket.
+    pub received_at: Instant,
+    // raw is the message the event was parsed from, only kept if FeedConfig::keep_raw is set.
+    pub raw: Option<Arc<str>>,
+    // text is the text the delta's quotes were sent as, kept by exchanges checksumming it.
+    pub text: Option<Arc<QuoteText>>,
+}
+
+// A later event is coalesced into an earlier one by taking its levels over the earlier's,
+// and its position in the stream as the end of the merged event's, so applying the merged
+// event leaves the book as applying both would. A later snapshot replaces the event.
+impl Coalesce for MarketEvent {
+    fn coalesce(&mut self, later: Self) {
+        if later.snapshot {
+            *self = later;
+            return;
+        }
+        self.text = match (self.text.take(), later.text) {
+            (Some(mut text), Some(later_text)) => {
+                let text_mut = Arc::make_mut(&mut text);
+                merge_text(&mut text_mut.bids, &self.delta.bids, &later_text.bids, &later.delta.bids);
+                merge_text(&mut text_mut.asks, &self.delta.asks, &later_text.asks, &later.delta.asks);
+                Some(text)
+            }
+            _ => None,
+        };
+        merge_quotes(&mut self.delta.bids, later.delta.bids);
+        merge_quotes(&mut self.delta.asks, later.delta.asks);
+        self.ts = later.ts;
+        self.seq = later.seq;
+        self.checksum = later.checksum;
+        self.raw = None;
+    }
+}
+
+// merge_quotes sets the levels of later in quotes, replacing those at the same price.
+fn merge_quotes(quotes: &mut Vec<WsQuote>, later: Vec<WsQuote>) {
+    for quote in later {
+        match quotes.iter_mut().find(|level| level.price == quote.price) {
+            Some(level) => level.quantity = quote.quantity,
+            None => quotes.push(quote),
+        }
+    }
+}
+
+// merge_text sets the text of later's quotes in text, which is that of quotes, in the
+// same places merge_quotes then sets the quotes.
+fn merge_text(text: &mut Vec<LevelText>, quotes: &[WsQuote], later_text: &[LevelText], later: &[WsQuote]) {
+    for (quote, later_text) in later.iter().zip(later_text) {
+        match quotes.iter().position(|level| level.price == quote.price) {
+            Some(index) => text[index] = later_text.clone(),
+            None => text.push(later_text.clone()),
+        }
+    }
+}
+
+// read_exchange_events reads messages from the websocket, answering pings and skipping
+// control messages as exchange classifies them, and passes the rest to on_message until
+// on_message returns false or the socket fails. In busy poll mode the socket is switched
+// to non-blocking and spun on.
+// Every frame read is recorded under topic if a recorder is given, and every ping timestamp
+// is given to clock. Rejected commands are sent to errors, and stop reading if fatal. The
+// exchange's heartbeat, if it wants one, is sent every interval, with reads timing out for
+// it while the connection is quiet.
+fn read_exchange_events<T, F>(
+    socket: &mut T,
+    exchange: &dyn ExchangeFeed,
+    poll_mode: PollMode,
+    recorder: Option<(&FrameRecorder, &str)>,
+    clock: Option<&ClockSkew>,
+    errors: Option<&Sender<WsError>>,
+    mut on_message: F,
+) where
+    T: WsTransport + ?Sized,
+    F: FnMut(&str) -> bool,
+{
+    let spin_limit = match poll_mode {
+        PollMode::Blocking => None,
+        PollMode::BusyPoll { spin_limit } => match socket.set_nonblocking(true) {
+            Ok(()) => Some(spin_limit),
+            Err(e) => {
+                warn!(error = %e, "Busy polling unavailable, falling back to blocking reads");
+                None
+            }
+        },
+    };
+    let heartbeat = exchange.heartbeat();
+    if let Some((interval, _)) = &heartbeat {
+        if let Err(e) = socket.set_read_timeout(Some(*interval)) {
+            warn!(error = %e, "Read timeouts unavailable, heartbeats only follow frames");
+        }
+    }
+    let mut last_heartbeat = Instant::now();
+    let mut spins = 0u32;
+    let mut parked = false;
+
+    loop {
+        if let Some((interval, message)) = &heartbeat {
+            if last_heartbeat.elapsed() >= *interval {
+                last_heartbeat = Instant::now();
+                match socket.send(Message::Text(message.clone())) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => {
+                        warn!(error = %e, "Failed to send heartbeat");
+                        return;
+                    }
+                }
+            }
+        }
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(_) => continue,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                spins += 1;
+                if spin_limit.flatten().is_some_and(|limit| spins >= limit) {
+                    // Park in a blocking read until the next frame arrives.
+                    parked = socket.set_nonblocking(false).is_ok();
+                    spins = 0;
+                }
+                std::hint::spin_loop();
+                continue;
+            }
+            Err(e) => {
+                warn!(error = %e, "Websocket read error");
+                return;
+            }
+        };
+        spins = 0;
+        if parked {
+            parked = false;
+            let _ = socket.set_nonblocking(true);
+        }
+
+        if let Some((recorder, topic)) = recorder {
+            if let Err(e) = recorder.record(topic, &text) {
+                warn!(path = %recorder.path().display(), error = %e, "Failed to record frame");
+            }
+        }
+
+        match exchange.frame(&text) {
+            Frame::Data => {}
+            Frame::Ping { reply, ts } => {
+                if let (Some(clock), Some(ts)) = (clock, ts) {
+                    clock.record_ping(ts, now_ms());
+                }
+                match socket.send(Message::Text(reply)) {
+                    // A non-blocking socket queues the frame and flushes it on the next read.
+                    Ok(()) => {}
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => {
+                        warn!(error = %e, "Failed to send pong");
+                        return;
+                    }
+                }
+                continue;
+            }
+            Frame::Ack { command } => {
+                debug!(command = %command, "Command acknowledged");
+                continue;
+            }
+            Frame::Error(error) => {
+                warn!(kind = ?error.kind, error = %error.message, "Exchange rejected a command");
+                let fatal = error.is_fatal();
+                if let Some(errors) = errors {
+                    let _ = errors.send(error);
+                }
+                if fatal {
+                    return;
+                }
+                continue;
+            }
+            Frame::Control => continue,
+        }
+
+        if !on_message(&text) { return; }
+    }
+}
+
+// connect_websocket opens a websocket to url, negotiating TLS for wss urls with tls or the
+// system's roots, through proxy unless it bypasses url's host.
+pub fn connect_websocket(
+    url: &str,
+    proxy: Option<&ProxyConfig>,
+    tls: Option<&Arc<rustls::ClientConfig>>,
+) -> io::Result<WebSocket<MaybeTlsStream<TcpStream>>> {
+    let (host, port) = host_port(url)?;
+    let stream = match proxy.filter(|proxy| !proxy.bypasses(&host)) {
+        Some(proxy) => proxy.tunnel(&host, port)?,
+        None => TcpStream::connect((host.as_str(), port))?,
+    };
+    stream.set_nodelay(true)?;
+    let connector = tls.map(|tls| Connector::Rustls(Arc::clone(tls)));
+    let (socket, _) = tungstenite::client_tls_with_config(url, stream, None, connector).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(socket)
+}
+
+// host_port returns the host and port a websocket url connects to.
+pub(crate) fn host_port(url: &str) -> io::Result<(String, u16)> {
+    let parsed = Url::parse(url).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "websocket url has no host"))?;
+    let port = parsed
+        .port_or_known_default()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "websocket url has no port"))?;
+    Ok((host.trim_start_matches('[').trim_end_matches(']').to_string(), port))
+}
+
+// run_connection subscribes to topic on an open websocket and passes every data message
+// to on_message until the connection ends.
+fn run_connection<T, F>(socket: &mut T, topic: &str, config: &FeedConfig, on_message: F)
+where
+    T: WsTransport + ?Sized,
+    F: FnMut(&str) -> bool,
+{
+    info!("Connected to websocket");
+
+    let exchange = config.exchange.as_ref();
+    if let Err(e) = socket.send(Message::Text(exchange.subscribe(topic))) {
+        warn!(error = %e, "Failed to subscribe");
+        return;
+    }
+    debug!("Subscribed");
+    let recorder = config.frame_recorder.as_ref().map(|recorder| (recorder, topic));
+    read_exchange_events(socket, exchange, config.poll_mode, recorder, config.clock.as_deref(), config.errors.as_ref(), on_message);
+}
+
+// spawn_connection connects to the exchange's websocket on a new thread, subscribes to topic
+// and passes every data message to on_message. on_close is called once the connection
+// has ended, for whatever reason.
+fn spawn_connection<F, C>(config: &FeedConfig, topic: String, mut on_message: F, on_close: C)
+where
+    F: FnMut(&str) -> bool + Send + 'static,
+    C: FnOnce() + Send + 'static,
+{
+    let config = config.clone();
+    let metrics = config.metrics.clone();
+    let mut injector = config.chaos.as_ref().map(Chaos::injector);
+    let mut on_message = move |text: &str| {
+        if let Some(metrics) = &metrics {
+            metrics.messages.fetch_add(1, Ordering::Relaxed);
+        }
+        match injector.as_mut() {
+            Some(injector) => injector.inject(text, &mut on_message),
+            None => on_message(text),
+        }
+    };
+
+    thread::spawn(move || {
+        let _span = info_span!("connection", exchange = config.exchange.name(), %topic).entered();
+        if let Some(core) = config.reader_core {
+            poll::pin_current_thread(core);
+        }
+        if let Some(metrics) = &config.metrics {
+            metrics.record_connect(&topic);
+        }
+        run_backend(&config, &topic, &mut on_message);
+        if let Some(metrics) = &config.metrics {
+            metrics.record_disconnect();
+        }
+        on_close();
+        // Dropping on_message ends the connection's stream, once its end has been recorded.
+        drop(on_message);
+    });
+}
+
+// run_backend connects to the exchange's websocket with config's backend and runs the
+// connection, passing every data message to on_message. A connection that can't be made is
+// logged, ending the connection as one that drops does.
+fn run_backend<F>(config: &FeedConfig, topic: &str, on_message: &mut F)
+where
+    F: FnMut(&str) -> bool,
+{
+    let parsed_url = match Url::parse(config.exchange.ws_url()) {
+        Ok(url) => url,
+        Err(e) => {
+            warn!(url = config.exchange.ws_url(), error = %e, "Invalid websocket url");
+            return;
+        }
+    };
+    match &config.backend {
+        SocketBackend::Portable => match connect_websocket(parsed_url.as_str(), config.proxy.as_ref(), config.tls.as_ref()) {
+            Ok(mut socket) => run_connection(&mut socket, topic, config, on_message),
+            Err(e) => warn!(error = %e, "Failed to connect to websocket"),
+        },
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        SocketBackend::IoUring { sqpoll_idle_ms } => {
+            match crate::uring::connect_websocket(parsed_url.as_str(), config.proxy.as_ref(), config.tls.as_ref(), *sqpoll_idle_ms) {
+                Ok(mut socket) => run_connection(&mut socket, topic, config, on_message),
+                Err(e) => warn!(error = %e, "Failed to connect to websocket"),
+            }
+        }
+        SocketBackend::Replay(replay) => {
+            info!(path = %replay.path.display(), "Replaying recording");
+            let result = recorder::replay(replay, topic, |text| match config.exchange.frame(text) {
+                Frame::Data => on_message(text),
+                Frame::Ping { .. } | Frame::Ack { .. } | Frame::Error(_) | Frame::Control => true,
+            });
+            if let Err(e) = result {
+                warn!(path = %replay.path.display(), error = %e, "Replay failed");
+            }
+        }
+        SocketBackend::Transport(connector) => match connector.connect(parsed_url.as_str(), topic) {
+            Ok(mut transport) => run_connection(transport.as_mut(), topic, config, on_message),
+            Err(e) => warn!(error = %e, "Failed to connect to websocket"),
+        },
+    }
+}
+
+// spawn_book_connection subscribes to the order book updates for symbol and passes every
+// delta to on_event until on_event returns false.
+fn spawn_book_connection<F, C>(config: &FeedConfig, symbol: &str, max_level: usize, mut on_event: F, on_close: C)
+where
+    F: FnMut(MarketEvent) -> bool + Send + 'static,
+    C: FnOnce() + Send + 'static,
+{
+    let exchange = Arc::clone(&config.exchange);
+    let topic = exchange.book_topic(symbol, max_level);
+    let metrics = config.metrics.clone();
+    let keep_raw = config.keep_raw;
+    let on_message = move |text: &str| {
+        match exchange.parse_book(text, Instant::now()) {
+            Ok(Some(event)) if keep_raw => return on_event(MarketEvent { raw: Some(text.into()), ..event }),
+            Ok(Some(event)) => return on_event(event),
+            Ok(None) => {}
+            Err(e) => {
+                if let Some(metrics) = &metrics {
+                    metrics.parse_errors.fetch_add(1, Ordering::Relaxed);
+                }
+                warn!(error = %e, data = text, "Failed to parse message");
+            }
+        }
+        true
+    };
+    spawn_connection(config, topic, on_message, on_close);
+}
+
+// spawn_trades_connection subscribes to the public trades for symbol and passes every
+// trade to on_trade until on_trade returns false.
+fn spawn_trades_connection<F>(config: &FeedConfig, symbol: &str, mut on_trade: F)
+where
+    F: FnMut(WsTrade) -> bool + Send + 'static,
+{
+    let exchange = Arc::clone(&config.exchange);
+    let topic = exchange.trade_topic(symbol);
+    let metrics = config.metrics.clone();
+    let on_message = move |text: &str| {
+        match exchange.parse_trades(text) {
+            Ok(trades) => {
+                for trade in trades {
+                    if !on_trade(trade) { return false; }
+                }
+            }
+            Err(e) => {
+                if let Some(metrics) = &metrics {
+                    metrics.parse_errors.fetch_add(1, Ordering::Relaxed);
+                }
+                warn!(error = %e, data = text, "Failed to parse message");
+            }
+        }
+        true
+    };
+    spawn_connection(config, topic, on_message, || {});
+}
+
+// spawn_funding_connection subscribes to the funding rate of symbol and passes every rate to
+// on_rate until on_rate returns false. It returns false if the exchange has no funding rate
+// stream for symbol.
+fn spawn_funding_connection<F>(config: &FeedConfig, symbol: &str, mut on_rate: F) -> bool
+where
+    F: FnMut(FundingRate) -> bool + Send + 'static,
+{
+    let exchange = Arc::clone(&config.exchange);
+    let Some(topic) = exchange.funding_topic(symbol) else { return false };
+    let metrics = config.metrics.clone();
+    let on_message = move |text: &str| {
+        match exchange.parse_funding(text) {
+            Ok(Some(rate)) => return on_rate(rate),
+            Ok(None) => {}
+            Err(e) => {
+                if let Some(metrics) = &metrics {
+                    metrics.parse_errors.fetch_add(1, Ordering::Relaxed);
+                }
+                warn!(error = %e, data = text, "Failed to parse message");
+            }
+        }
+        true
+    };
+    spawn_connection(config, topic, on_message, || {});
+    true
+}
+
+// spawn_mark_price_connection subscribes to the mark price of symbol and passes every price
+// to on_price until on_price returns false. It returns false if the exchange has no mark
+// price stream for symbol.
+fn spawn_mark_price_connection<F>(config: &FeedConfig, symbol: &str, mut on_price: F) -> bool
+where
+    F: FnMut(MarkPrice) -> bool + Send + 'static,
+{
+    let exchange = Arc::clone(&config.exchange);
+    let Some(topic) = exchange.mark_price_topic(symbol) else { return false };
+    let metrics = config.metrics.clone();
+    let on_message = move |text: &str| {
+        match exchange.parse_mark_price(text) {
+            Ok(Some(price)) => return on_price(price),
+            Ok(None) => {}
+            Err(e) => {
+                if let Some(metrics) = &metrics {
+                    metrics.parse_errors.fetch_add(1, Ordering::Relaxed);
+                }
+                warn!(error = %e, data = text, "Failed to parse message");
+            }
+        }
+        true
+    };
+    spawn_connection(config, topic, on_message, || {});
+    true
+}
+
+// spawn_redundant_connections opens two book connections for symbol and passes the
+// arbitrated events to on_event until on_event returns false.
+fn spawn_redundant_connections<F>(config: &FeedConfig, symbol: &str, max_level: usize, on_event: F) -> Arc<ArbitrationMetrics>
+where
+    F: FnMut(MarketEvent) -> bool + Send + 'static,
+{
+    let (input_tx, input_rx) = mpsc::channel();
+
+    for connection in 0..Arbitrator::CONNECTIONS {
+        let event_tx: Sender<ArbiterInput> = input_tx.clone();
+        let close_tx = input_tx.clone();
+        spawn_book_connection(
+            config,
+            symbol,
+            max_level,
+            move |event| event_tx.send(ArbiterInput::Event(connection, event)).is_ok(),
+            move || { let _ = close_tx.send(ArbiterInput::Closed(connection)); },
+        );
+    }
+    drop(input_tx);
+
+    let arbitrator = Arbitrator::new();
+    let metrics = arbitrator.metrics();
+    thread::spawn(move || arbitrator.run(input_rx, on_event));
+    metrics
+}
+
+// connect_stream attempts to connect to the exchange's websocket and returns a receiver
+// to consume the stream of market events for the specified symbol, queued as config.queue
+// sets.
+pub fn connect_stream(config: &FeedConfig, symbol: &str, max_level: usize) -> queue::Receiver<MarketEvent> {
+    let (tx, rx) = queue::bounded(config.queue, config.metrics.clone());
+    spawn_book_connection(config, symbol, max_level, move |event| tx.send(event).is_ok(), || {});
+    rx
+}
+
+// connect_trades connects to the exchange's websocket and returns a receiver to consume the
+// public trades for the specified symbol.
+pub fn connect_trades(config: &FeedConfig, symbol: &str) -> Receiver<WsTrade> {
+    let (tx, rx) = mpsc::channel();
+    spawn_trades_connection(config, symbol, move |trade| tx.send(trade).is_ok());
+    rx
+}
+
+// connect_funding connects to the exchange's websocket and returns a receiver to consume the
+// funding rate of the specified perpetual, or None if the exchange doesn't stream it.
+pub fn connect_funding(config: &FeedConfig, symbol: &str) -> Option<Receiver<FundingRate>> {
+    let (tx, rx) = mpsc::channel();
+    spawn_funding_connection(config, symbol, move |rate| tx.send(rate).is_ok()).then_some(rx)
+}
+
+// connect_mark_price connects to the exchange's websocket and returns a receiver to consume
+// the mark price of the specified derivative, or None if the exchange doesn't stream it.
+pub fn connect_mark_price(config: &FeedConfig, symbol: &str) -> Option<Receiver<MarkPrice>> {
+    let (tx, rx) = mpsc::channel();
+    spawn_mark_price_connection(config, symbol, move |price| tx.send(price).is_ok()).then_some(rx)
+}
+
+// connect_redundant_stream opens two websocket connections for the same symbol and
+// arbitrates between them, forwarding each event from whichever connection delivers it
+// first. The returned metrics track how often the secondary connection filled in for the primary.
+pub fn connect_redundant_stream(
+    config: &FeedConfig,
+    symbol: &str,
+    max_level: usize,
+) -> (queue::Receiver<MarketEvent>, Arc<ArbitrationMetrics>) {
+    let (tx, rx) = queue::bounded(config.queue, config.metrics.clone());
+    let metrics = spawn_redundant_connections(config, symbol, max_level, move |event| tx.send(event).is_ok());
+    (rx, metrics)
+}
+
+// The async variants below run the same connection threads as the blocking ones and only
+// differ in handing events over on tokio channels, which can be awaited without blocking
+// the runtime. Sending on an unbounded tokio channel never blocks the reader thread.
+
+// connect_stream_async is connect_stream for async consumers.
+#[cfg(feature = "async")]
+pub fn connect_stream_async(config: &FeedConfig, symbol: &str, max_level: usize) -> UnboundedReceiver<MarketEvent> {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    spawn_book_connection(config, symbol, max_level, move |event| tx.send(event).is_ok(), || {});
+    rx
+}
+
+// connect_trades_async is connect_trades for async consumers.
+#[cfg(feature = "async")]
+pub fn connect_trades_async(config: &FeedConfig, symbol: &str) -> UnboundedReceiver<WsTrade> {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    spawn_trades_connection(config, symbol, move |trade| tx.send(trade).is_ok());
+    rx
+}
+
+// connect_redundant_stream_async is connect_redundant_stream for async consumers.
+#[cfg(feature = "async")]
+pub fn connect_redundant_stream_async(
+    config: &FeedConfig,
+    symbol: &str,
+    max_level: usize,
+) -> (UnboundedReceiver<MarketEvent>, Arc<ArbitrationMetrics>) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let metrics = spawn_redundant_connections(config, symbol, max_level, move |event| tx.send(event).is_ok());
+    (rx, metrics)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::exchange_api_types::RestSnapshot;
+    use crate::snapshot::SnapshotError;
+
+    const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(10);
+
+    // Heartbeating is Woo X, as a venue wanting a heartbeat every HEARTBEAT_INTERVAL.
+    struct Heartbeating(WooxExchange);
+
+    impl ExchangeFeed for Heartbeating {
+        fn name(&self) -> &str {
+            "heartbeating"
+        }
+
+        fn ws_url(&self) -> &str {
+            self.0.ws_url()
+        }
+
+        fn book_topic(&self, symbol: &str, max_level: usize) -> String {
+            self.0.book_topic(symbol, max_level)
+        }
+
+        fn trade_topic(&self, symbol: &str) -> String {
+            self.0.trade_topic(symbol)
+        }
+
+        fn subscribe(&self, topic: &str) -> String {
+            self.0.subscribe(topic)
+        }
+
+        fn frame(&self, text: &str) -> Frame {
+            self.0.frame(text)
+        }
+
+        fn heartbeat(&self) -> Option<(Duration, String)> {
+            Some((HEARTBEAT_INTERVAL, "heartbeat".to_string()))
+        }
+
+        fn parse_book(&self, text: &str, received_at: Instant) -> Result<Option<MarketEvent>, serde_json::Error> {
+            self.0.parse_book(text, received_at)
+        }
+
+        fn parse_trades(&self, text: &str) -> Result<Vec<WsTrade>, serde_json::Error> {
+            self.0.parse_trades(text)
+        }
+
+        fn snapshot(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+            self.0.snapshot(symbol, max_level)
+        }
+    }
+
+    // QuietTransport reads its frames in order, a None being a read timing out after longer
+    // than HEARTBEAT_INTERVAL, and then fails as a closed connection would. Sends fail if
+    // fail_sends is set, as on a connection reset.
+    #[derive(Default)]
+    struct QuietTransport {
+        reads: VecDeque<Option<String>>,
+        read_timeout: Option<Duration>,
+        sent: Vec<String>,
+        fail_sends: bool,
+    }
+
+    impl WsTransport for QuietTransport {
+        fn read(&mut self) -> io::Result<Message> {
+            match self.reads.pop_front() {
+                Some(Some(text)) => Ok(Message::Text(text)),
+                Some(None) => {
+                    thread::sleep(HEARTBEAT_INTERVAL * 2);
+                    Err(io::ErrorKind::WouldBlock.into())
+                }
+                None => Err(io::Error::new(io::ErrorKind::ConnectionAborted, "end of script")),
+            }
+        }
+
+        fn send(&mut self, message: Message) -> io::Result<()> {
+            if self.fail_sends {
+                return Err(io::ErrorKind::ConnectionReset.into());
+            }
+            if let Message::Text(text) = message {
+                self.sent.push(text);
+            }
+            Ok(())
+        }
+
+        fn set_nonblocking(&mut self, _nonblocking: bool) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+            self.read_timeout = timeout;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sends_heartbeats_while_the_connection_is_quiet() {
+        let delta = r#"{"topic":"orderbookupdate@SPOT_BTC_USDT@50","ts":1100,"data":{"prevTs":1000,"bids":[],"asks":[]}}"#;
+        let mut transport = QuietTransport { reads: VecDeque::from([None, None, Some(delta.to_string())]), ..QuietTransport::default() };
+        let mut messages = 0;
+        let exchange = Heartbeating(WooxExchange::default());
+        read_exchange_events(&mut transport, &exchange, PollMode::Blocking, None, None, None, |_| {
+            messages += 1;
+            true
+        });
+        assert_eq!(transport.read_timeout, Some(HEARTBEAT_INTERVAL));
+        assert_eq!(transport.sent, vec!["heartbeat", "heartbeat"]);
+        assert_eq!(messages, 1);
+    }
+
+    #[test]
+    fn sends_no_heartbeats_to_venues_that_ping() {
+        let mut transport = QuietTransport { reads: VecDeque::from([None]), ..QuietTransport::default() };
+        read_exchange_events(&mut transport, &WooxExchange::default(), PollMode::Blocking, None, None, None, |_| true);
+        assert_eq!(transport.read_timeout, None);
+        assert!(transport.sent.is_empty());
+    }
+
+    #[test]
+    fn stops_reading_once_a_pong_cant_be_sent() {
+        let delta = r#"{"topic":"orderbookupdate@SPOT_BTC_USDT@50","ts":1100,"data":{"prevTs":1000,"bids":[],"asks":[]}}"#;
+        let reads = VecDeque::from([Some(r#"{"cmd":"PING","ts":5}"#.to_string()), Some(delta.to_string())]);
+        let mut transport = QuietTransport { reads, fail_sends: true, ..QuietTransport::default() };
+        let mut messages = 0;
+        read_exchange_events(&mut transport, &WooxExchange::default(), PollMode::Blocking, None, None, None, |_| {
+            messages += 1;
+            true
+        });
+        assert_eq!(messages, 0);
+    }
+
+    #[test]
+    fn ends_the_stream_when_the_websocket_cant_be_connected() {
+        // Nothing listens on port 1, so connecting is refused.
+        let exchange = WooxExchange::new("ws://127.0.0.1:1/ws", "http://127.0.0.1:1");
+        let metrics = Arc::new(FeedMetrics::default());
+        let config = FeedConfig { exchange: Arc::new(exchange), metrics: Some(Arc::clone(&metrics)), ..FeedConfig::default() };
+        let events = connect_stream(&config, "SPOT_BTC_USDT", 50);
+        assert!(events.recv().is_err());
+        assert_eq!(metrics.connects.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.open.load(Ordering::Relaxed), 0);
+    }
+}
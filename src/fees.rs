@@ -0,0 +1,47 @@
+use crate::order::Liquidity;
+use crate::units::{Notional, Price, Qty};
+
+// FeeSchedule is the fee rates an account pays on a venue, in bps of the notional filled,
+// either taken from config or fetched for the account's fee tier with
+// TradingClient::fee_schedule. Negative rates are rebates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeSchedule {
+    pub maker_bps: f64,
+    pub taker_bps: f64,
+}
+
+impl Default for FeeSchedule {
+    // default is the Woo X base tier.
+    fn default() -> Self {
+        Self::new(2.0, 5.0)
+    }
+}
+
+impl FeeSchedule {
+    pub const fn new(maker_bps: f64, taker_bps: f64) -> Self {
+        Self { maker_bps, taker_bps }
+    }
+
+    // from_rates returns the schedule for rates given as fractions of the notional, as
+    // venues report them, e.g. 0.0005 for 5 bps.
+    pub fn from_rates(maker_rate: f64, taker_rate: f64) -> Self {
+        Self::new(maker_rate * 10_000.0, taker_rate * 10_000.0)
+    }
+
+    pub fn rate_bps(&self, liquidity: Liquidity) -> f64 {
+        match liquidity {
+            Liquidity::Maker => self.maker_bps,
+            Liquidity::Taker => self.taker_bps,
+        }
+    }
+
+    // fee returns the fee for filling notional with liquidity.
+    pub fn fee(&self, notional: Notional, liquidity: Liquidity) -> Notional {
+        Notional::new(notional.value() * self.rate_bps(liquidity) / 10_000.0)
+    }
+
+    // fill_fee returns the fee for filling quantity at price with liquidity.
+    pub fn fill_fee(&self, price: Price, quantity: Qty, liquidity: Liquidity) -> Notional {
+        self.fee(price * quantity, liquidity)
+    }
+}
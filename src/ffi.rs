@@ -0,0 +1,337 @@
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use tracing::error;
+
+use crate::client::WooxClient;
+use crate::exchange::woox::{WooxEnvironment, WooxExchange};
+use crate::exchange::ExchangeFeed;
+use crate::exchange_api_types::WsQuote;
+use crate::feed::{self, FeedConfig};
+use crate::queue;
+use crate::shared_book::{self, SharedBookReader, SharedBookWriter};
+use crate::units::{Price, Qty};
+
+// STOP_POLL bounds how long a stopped feed takes to notice, while no events arrive.
+const STOP_POLL: Duration = Duration::from_millis(100);
+
+// WooxLevel is a price level as C sees it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WooxLevel {
+    pub price: f64,
+    pub quantity: f64,
+}
+
+impl From<(Price, Qty)> for WooxLevel {
+    fn from((price, quantity): (Price, Qty)) -> Self {
+        Self { price: price.value(), quantity: quantity.value() }
+    }
+}
+
+impl From<&WsQuote> for WooxLevel {
+    fn from(quote: &WsQuote) -> Self {
+        Self { price: quote.price, quantity: quote.quantity }
+    }
+}
+
+// WooxBookUpdate is what an update callback is given after each update is applied. The
+// pointers are only valid for the duration of the callback.
+#[repr(C)]
+pub struct WooxBookUpdate {
+    pub symbol: *const c_char,
+    pub ts: u64,
+    pub prev_ts: u64,
+    // synced is true for the first update after the book synced from its snapshot.
+    pub synced: bool,
+    // bids and asks are the levels the update changed, a zero quantity removing the level.
+    pub bids: *const WooxLevel,
+    pub bids_len: usize,
+    pub asks: *const WooxLevel,
+    pub asks_len: usize,
+}
+
+pub type WooxUpdateCallback = extern "C" fn(update: *const WooxBookUpdate, user_data: *mut c_void);
+
+// Callback is a registered update callback and the pointer it is given back. The pointer
+// is only handed back to the callback, on the book thread, which C registers it for.
+struct Callback {
+    callback: WooxUpdateCallback,
+    user_data: *mut c_void,
+}
+
+unsafe impl Send for Callback {}
+
+impl Callback {
+    fn call(&self, update: &WooxBookUpdate) {
+        (self.callback)(update, self.user_data);
+    }
+}
+
+// WooxFeed is the opaque handle C embeds a Woo X book feed through, declared with the rest
+// of the interface in include/woox.h: woox_feed_new makes one for a symbol,
+// woox_feed_on_update registers a callback for its updates, and woox_feed_start follows
+// the book on background threads until woox_feed_free stops it. The top of the book is
+// readable from any thread while it runs, lock free through a shared book.
+pub struct WooxFeed {
+    symbol: String,
+    depth: usize,
+    exchange: Arc<dyn ExchangeFeed>,
+    reader: SharedBookReader,
+    writer: Option<SharedBookWriter>,
+    callback: Option<Callback>,
+    stopped: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl WooxFeed {
+    // start follows the book, forwarding the stream to the client until the feed is stopped,
+    // which disconnects the client's stream and so ends it.
+    fn start(&mut self) -> bool {
+        let Some(writer) = self.writer.take() else { return false };
+        let config = FeedConfig { exchange: Arc::clone(&self.exchange), ..FeedConfig::default() };
+        let stream = feed::connect_stream(&config, &self.symbol, self.depth);
+        let (sender, events) = queue::bounded(config.queue, None);
+        let stopped = Arc::clone(&self.stopped);
+        self.threads.push(thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                let event = match stream.recv_timeout(STOP_POLL) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+        }));
+
+        let mut builder = WooxClient::builder().symbol(&self.symbol).depth(self.depth).feed_config(config).events(events).shared_book(writer);
+        if let Some(callback) = self.callback.take() {
+            let symbol = CString::new(self.symbol.as_str()).unwrap_or_default();
+            let (mut bids, mut asks) = (Vec::new(), Vec::new());
+            builder = builder.on_update(move |update| {
+                bids.clear();
+                bids.extend(update.event.delta.bids.iter().map(WooxLevel::from));
+                asks.clear();
+                asks.extend(update.event.delta.asks.iter().map(WooxLevel::from));
+                let update = WooxBookUpdate {
+                    symbol: symbol.as_ptr(),
+                    ts: update.event.ts,
+                    prev_ts: update.event.prev_ts,
+                    synced: update.synced,
+                    bids: bids.as_ptr(),
+                    bids_len: bids.len(),
+                    asks: asks.as_ptr(),
+                    asks_len: asks.len(),
+                };
+                callback.call(&update);
+            });
+        }
+        let client = builder.build();
+        self.threads.push(thread::spawn(move || {
+            if let Err(e) = client.run() {
+                error!(error = %e, "Feed stopped");
+            }
+        }));
+        true
+    }
+
+    // is_running returns true while the feed is following the book.
+    fn is_running(&self) -> bool {
+        self.threads.last().is_some_and(|client| !client.is_finished())
+    }
+}
+
+impl Drop for WooxFeed {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// woox_feed_new returns a feed of the book for symbol, subscribed to and sharing depth
+/// levels a side, or null if symbol is null or isn't valid UTF-8. It isn't started.
+///
+/// # Safety
+///
+/// symbol must be null or point to a nul terminated string, readable up to and including
+/// the nul, for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn woox_feed_new(symbol: Option<NonNull<c_char>>, depth: usize) -> Option<Box<WooxFeed>> {
+    let symbol = unsafe { c_str(symbol) }?;
+    let depth = depth.max(1);
+    let (writer, reader) = shared_book::shared_book(depth);
+    Some(Box::new(WooxFeed {
+        symbol: symbol.to_string(),
+        depth,
+        exchange: Arc::new(WooxExchange::default()),
+        reader,
+        writer: Some(writer),
+        callback: None,
+        stopped: Arc::default(),
+        threads: Vec::new(),
+    }))
+}
+
+/// woox_feed_set_environment connects the feed to the Woo X environment named, "prod" or
+/// "staging", rather than production. It returns 0, or -1 if the name isn't known or the
+/// feed has already started.
+///
+/// # Safety
+///
+/// feed must be null or a feed from woox_feed_new not yet freed, and name null or a nul
+/// terminated string readable up to and including the nul for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn woox_feed_set_environment(feed: Option<&mut WooxFeed>, name: Option<NonNull<c_char>>) -> c_int {
+    let environment = unsafe { c_str(name) }.and_then(WooxEnvironment::from_name);
+    match (feed, environment) {
+        (Some(feed), Some(environment)) if feed.writer.is_some() => {
+            feed.exchange = Arc::new(environment.exchange());
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// woox_feed_set_endpoints connects the feed to the websocket at ws_url, fetching snapshots
+/// from the orderbook endpoint at orderbook_url, such as a proxy or a test server. It
+/// returns 0, or -1 if either isn't valid UTF-8 or the feed has already started.
+///
+/// # Safety
+///
+/// feed must be null or a feed from woox_feed_new not yet freed, and ws_url and
+/// orderbook_url each null or a nul terminated string readable up to and including the nul
+/// for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn woox_feed_set_endpoints(feed: Option<&mut WooxFeed>, ws_url: Option<NonNull<c_char>>, orderbook_url: Option<NonNull<c_char>>) -> c_int {
+    match (feed, unsafe { c_str(ws_url) }, unsafe { c_str(orderbook_url) }) {
+        (Some(feed), Some(ws_url), Some(orderbook_url)) if feed.writer.is_some() => {
+            feed.exchange = Arc::new(WooxExchange::new(ws_url, orderbook_url));
+            0
+        }
+        _ => -1,
+    }
+}
+
+// woox_feed_on_update registers callback to be called with user_data after every update
+// is applied, on the feed's book thread, replacing any callback registered before. It
+// returns 0, or -1 if the feed has already started.
+#[no_mangle]
+pub extern "C" fn woox_feed_on_update(feed: Option<&mut WooxFeed>, callback: WooxUpdateCallback, user_data: *mut c_void) -> c_int {
+    match feed {
+        Some(feed) if feed.writer.is_some() => {
+            feed.callback = Some(Callback { callback, user_data });
+            0
+        }
+        _ => -1,
+    }
+}
+
+// woox_feed_start connects and follows the book on background threads. It returns 0, or -1
+// if the feed has already started.
+#[no_mangle]
+pub extern "C" fn woox_feed_start(feed: Option<&mut WooxFeed>) -> c_int {
+    match feed.is_some_and(WooxFeed::start) {
+        true => 0,
+        false => -1,
+    }
+}
+
+// woox_feed_is_running returns true while the feed is following the book, false before it
+// is started and once it has stopped, such as when the book couldn't be synced.
+#[no_mangle]
+pub extern "C" fn woox_feed_is_running(feed: Option<&WooxFeed>) -> bool {
+    feed.is_some_and(WooxFeed::is_running)
+}
+
+// woox_feed_ts returns the exchange timestamp the book was last updated at, 0 before it
+// has synced.
+#[no_mangle]
+pub extern "C" fn woox_feed_ts(feed: Option<&WooxFeed>) -> u64 {
+    feed.map_or(0, |feed| feed.reader.ts())
+}
+
+// woox_feed_best_bid sets out to the best bid, returning false, leaving out alone, while
+// there is none.
+#[no_mangle]
+pub extern "C" fn woox_feed_best_bid(feed: Option<&WooxFeed>, out: Option<&mut WooxLevel>) -> bool {
+    best(feed.and_then(|feed| feed.reader.best_bid()), out)
+}
+
+// woox_feed_best_ask is woox_feed_best_bid for the best ask.
+#[no_mangle]
+pub extern "C" fn woox_feed_best_ask(feed: Option<&WooxFeed>, out: Option<&mut WooxLevel>) -> bool {
+    best(feed.and_then(|feed| feed.reader.best_ask()), out)
+}
+
+// c_str returns the nul terminated string C passes at s, if it is valid UTF-8.
+//
+// # Safety
+//
+// s must be None or point to a nul terminated string that stays readable, and unchanged,
+// for as long as the returned str is used.
+unsafe fn c_str<'a>(s: Option<NonNull<c_char>>) -> Option<&'a str> {
+    unsafe { CStr::from_ptr(s?.as_ptr()) }.to_str().ok()
+}
+
+fn best(level: Option<(Price, Qty)>, out: Option<&mut WooxLevel>) -> bool {
+    match (level, out) {
+        (Some(level), Some(out)) => {
+            *out = WooxLevel::from(level);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// woox_feed_top copies up to n of the top levels a side, read together, into bids and
+/// asks, each room for n levels, and sets bids_len and asks_len to how many were copied.
+/// It returns the exchange timestamp the levels are as of, 0 before the book has synced.
+///
+/// # Safety
+///
+/// feed must be null or a feed from woox_feed_new not yet freed. bids and asks must each be
+/// null or point to writable room for at least n WooxLevels, not overlapping each other,
+/// and bids_len and asks_len each null or point to a writable size_t.
+#[no_mangle]
+pub unsafe extern "C" fn woox_feed_top(
+    feed: Option<&WooxFeed>,
+    n: usize,
+    bids: Option<NonNull<WooxLevel>>,
+    bids_len: Option<&mut usize>,
+    asks: Option<NonNull<WooxLevel>>,
+    asks_len: Option<&mut usize>,
+) -> u64 {
+    let (Some(feed), Some(bids), Some(bids_len), Some(asks), Some(asks_len)) = (feed, bids, bids_len, asks, asks_len) else { return 0 };
+    let top = feed.reader.top(n);
+    // C promises room for n levels at each of bids and asks, and top has no more than n.
+    let copy = |levels: &[(Price, Qty)], out: NonNull<WooxLevel>| {
+        for (index, &level) in levels.iter().enumerate() {
+            unsafe { out.add(index).write(WooxLevel::from(level)) };
+        }
+        levels.len()
+    };
+    *bids_len = copy(&top.bids, bids);
+    *asks_len = copy(&top.asks, asks);
+    top.ts
+}
+
+/// woox_feed_free stops the feed, waiting for its threads to finish, and frees it. A feed
+/// still fetching its first snapshot finishes that first.
+///
+/// # Safety
+///
+/// feed must be null or a feed from woox_feed_new, not already freed. It mustn't be used
+/// again once freed, from any thread.
+#[no_mangle]
+pub unsafe extern "C" fn woox_feed_free(feed: Option<Box<WooxFeed>>) {
+    drop(feed);
+}
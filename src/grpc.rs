@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{TcpListener, ToSocketAddrs};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use futures_util::stream::{self, Stream, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::exchange_api_types::WsTrade;
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::proto;
+use crate::sink::{Sink, SinkResult};
+
+use proto::market_data_server::{MarketData, MarketDataServer};
+
+// BROADCAST_CAPACITY is how many book updates or trades a stream can fall behind by before
+// it is ended with a data loss status.
+const BROADCAST_CAPACITY: usize = 4096;
+
+type GrpcStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+// PublishedBook is the service's copy of a book and the timestamp it was last updated at.
+struct PublishedBook {
+    ts: u64,
+    book: LocalOrderBook,
+}
+
+impl PublishedBook {
+    fn update(&self, symbol: &str) -> proto::BookUpdate {
+        proto::BookUpdate::from_book(symbol, self.ts, &self.book)
+    }
+}
+
+// GrpcPublisher is a sink that serves the synced books and trades it is given over the
+// MarketData gRPC service defined in proto/woox.proto.
+#[derive(Clone)]
+pub struct GrpcPublisher {
+    books: Arc<Mutex<HashMap<String, PublishedBook>>>,
+    updates: broadcast::Sender<proto::BookUpdate>,
+    trades: broadcast::Sender<proto::Trade>,
+}
+
+impl Default for GrpcPublisher {
+    fn default() -> Self {
+        Self {
+            books: Arc::default(),
+            updates: broadcast::channel(BROADCAST_CAPACITY).0,
+            trades: broadcast::channel(BROADCAST_CAPACITY).0,
+        }
+    }
+}
+
+impl GrpcPublisher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // serve serves the MarketData service on addr from a runtime on a background thread.
+    // It returns once the listener is bound.
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> io::Result<thread::JoinHandle<()>> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+        let service = MarketDataServer::new(self.clone());
+
+        Ok(thread::spawn(move || {
+            runtime.block_on(async move {
+                let incoming = tokio::net::TcpListener::from_std(listener)
+                    .map_err(|e| e.to_string())
+                    .and_then(|listener| TcpIncoming::from_listener(listener, true, None).map_err(|e| e.to_string()));
+                let result = match incoming {
+                    Ok(incoming) => Server::builder().add_service(service).serve_with_incoming(incoming).await.map_err(|e| e.to_string()),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    error!(error = %e, "gRPC server stopped");
+                }
+            });
+        }))
+    }
+}
+
+// wants returns true if symbol was requested, an empty request being for every symbol.
+fn wants(symbols: &[String], symbol: &str) -> bool {
+    symbols.is_empty() || symbols.iter().any(|wanted| wanted == symbol)
+}
+
+// broadcast_stream streams the items of rx matching symbols, ending with a data loss status
+// if the stream falls too far behind.
+fn broadcast_stream<T>(rx: broadcast::Receiver<T>, symbols: Vec<String>, symbol_of: fn(&T) -> &str) -> impl Stream<Item = Result<T, Status>>
+where
+    T: Clone + Send + 'static,
+{
+    stream::unfold(Some((rx, symbols)), move |state| async move {
+        let (mut rx, symbols) = state?;
+        loop {
+            match rx.recv().await {
+                Ok(item) if wants(&symbols, symbol_of(&item)) => return Some((Ok(item), Some((rx, symbols)))),
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    return Some((Err(Status::data_loss(format!("stream fell {} messages behind", missed))), None));
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+#[tonic::async_trait]
+impl MarketData for GrpcPublisher {
+    async fn get_snapshot(&self, request: Request<proto::SnapshotRequest>) -> Result<Response<proto::BookSnapshot>, Status> {
+        let request = request.into_inner();
+        let max_level = match request.max_level {
+            0 => usize::MAX,
+            max_level => max_level as usize,
+        };
+        let books = self.books.lock().unwrap();
+        let Some(published) = books.get(&request.symbol) else {
+            return Err(Status::not_found(format!("no synced book for {}", request.symbol)));
+        };
+        Ok(Response::new(proto::BookSnapshot::from_book(&request.symbol, published.ts, &published.book, max_level)))
+    }
+
+    type StreamBookUpdatesStream = GrpcStream<proto::BookUpdate>;
+
+    async fn stream_book_updates(&self, request: Request<proto::StreamRequest>) -> Result<Response<Self::StreamBookUpdatesStream>, Status> {
+        let symbols = request.into_inner().symbols;
+        // Subscribing under the books lock, which updates are sent under, makes the stream
+        // pick up exactly where the snapshots leave off.
+        let (snapshots, rx) = {
+            let books = self.books.lock().unwrap();
+            let snapshots: Vec<_> = books
+                .iter()
+                .filter(|(symbol, _)| wants(&symbols, symbol))
+                .map(|(symbol, published)| published.update(symbol))
+                .collect();
+            (snapshots, self.updates.subscribe())
+        };
+        let updates = broadcast_stream(rx, symbols, |update: &proto::BookUpdate| update.symbol.as_str());
+        Ok(Response::new(Box::pin(stream::iter(snapshots.into_iter().map(Ok)).chain(updates))))
+    }
+
+    type StreamTradesStream = GrpcStream<proto::Trade>;
+
+    async fn stream_trades(&self, request: Request<proto::StreamRequest>) -> Result<Response<Self::StreamTradesStream>, Status> {
+        let symbols = request.into_inner().symbols;
+        let trades = broadcast_stream(self.trades.subscribe(), symbols, |trade: &proto::Trade| trade.symbol.as_str());
+        Ok(Response::new(Box::pin(trades)))
+    }
+}
+
+impl Sink for GrpcPublisher {
+    fn name(&self) -> &str {
+        "grpc"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        let mut copy = LocalOrderBook::new();
+        copy.apply_snapshot(book.to_snapshot());
+        let published = PublishedBook { ts, book: copy };
+        let mut books = self.books.lock().unwrap();
+        // Sending fails only when nothing is streaming, which is fine.
+        let _ = self.updates.send(published.update(symbol));
+        books.insert(symbol.to_string(), published);
+        Ok(())
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        let mut books = self.books.lock().unwrap();
+        let Some(published) = books.get_mut(symbol) else {
+            return Ok(());
+        };
+        published.book.apply_delta(&event.delta);
+        published.ts = event.ts;
+        let _ = self.updates.send(proto::BookUpdate::from_event(symbol, event));
+        Ok(())
+    }
+
+    fn record_trade(&mut self, trade: &WsTrade) -> SinkResult {
+        let _ = self.trades.send(proto::Trade::from(trade));
+        Ok(())
+    }
+}
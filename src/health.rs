@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::feed::MarketEvent;
+use crate::http::{self, Response};
+use crate::metrics::FeedMetrics;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+
+// BookHealth is what Health knows of one symbol's book.
+#[derive(Debug, Default)]
+struct BookHealth {
+    synced: bool,
+    // stale is set when the book is reported stale, until the next delta.
+    stale: bool,
+    last_update: Option<Instant>,
+    last_update_ts: Option<u64>,
+    resyncs: u64,
+}
+
+// SymbolStatus is the sync state of one symbol's book.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SymbolStatus {
+    pub symbol: String,
+    pub synced: bool,
+    pub stale: bool,
+    // last_update_age_ms is the time since the book last changed, locally, and
+    // last_update_ts the exchange timestamp of that change.
+    pub last_update_age_ms: Option<u64>,
+    pub last_update_ts: Option<u64>,
+    pub resyncs: u64,
+}
+
+// Status is the state of the feed served at /status.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Status {
+    pub healthy: bool,
+    // problems are why the feed isn't healthy, if it isn't.
+    pub problems: Vec<String>,
+    pub uptime_secs: f64,
+    pub open_connections: u64,
+    pub connects: u64,
+    pub reconnects: u64,
+    pub messages: u64,
+    pub symbols: Vec<SymbolStatus>,
+}
+
+// Health tracks the liveness of the feed and the sync state of each book written to its
+// sink, served over HTTP for orchestrators and load balancers to probe. The feed is healthy
+// while a connection is open and every book is synced and has changed within max_update_age.
+#[derive(Debug)]
+pub struct Health {
+    feed: Arc<FeedMetrics>,
+    max_update_age: Duration,
+    started: Instant,
+    books: Mutex<BTreeMap<String, BookHealth>>,
+}
+
+impl Health {
+    // new tracks the connections counted by feed, which has to be the FeedConfig's metrics.
+    pub fn new(feed: Arc<FeedMetrics>, max_update_age: Duration) -> Arc<Self> {
+        Arc::new(Self { feed, max_update_age, started: Instant::now(), books: Mutex::new(BTreeMap::new()) })
+    }
+
+    // sink returns a sink recording the sync state of the books written to it.
+    pub fn sink(self: &Arc<Self>) -> HealthSink {
+        HealthSink { health: Arc::clone(self) }
+    }
+
+    // serve serves /healthz, 200 while the feed is healthy and 503 with the problems if not,
+    // and the full Status as JSON at /status. It returns once the listener is bound.
+    pub fn serve<A: ToSocketAddrs>(self: &Arc<Self>, addr: A) -> io::Result<thread::JoinHandle<()>> {
+        let health = Arc::clone(self);
+        http::serve(addr, move |request| match request.path.as_str() {
+            "/healthz" => {
+                let status = health.status();
+                match status.healthy {
+                    true => Response::text("ok"),
+                    false => Response::new(503, "text/plain; charset=utf-8", status.problems.join("\n")),
+                }
+            }
+            "/status" => Response::json(serde_json::to_vec(&health.status()).unwrap_or_default()),
+            _ => Response::not_found(),
+        })
+    }
+
+    pub fn status(&self) -> Status {
+        let books = self.books.lock().unwrap();
+        let symbols: Vec<SymbolStatus> = books
+            .iter()
+            .map(|(symbol, book)| SymbolStatus {
+                symbol: symbol.clone(),
+                synced: book.synced,
+                stale: book.stale,
+                last_update_age_ms: book.last_update.map(|at| at.elapsed().as_millis() as u64),
+                last_update_ts: book.last_update_ts,
+                resyncs: book.resyncs,
+            })
+            .collect();
+
+        let open_connections = self.feed.open.load(Ordering::Relaxed);
+        let mut problems = Vec::new();
+        if open_connections == 0 {
+            problems.push("no connection open".to_string());
+        }
+        if symbols.is_empty() {
+            problems.push("no book synced yet".to_string());
+        }
+        let max_age_ms = self.max_update_age.as_millis() as u64;
+        for symbol in &symbols {
+            if !symbol.synced {
+                problems.push(format!("{} not synced", symbol.symbol));
+            } else if symbol.stale || symbol.last_update_age_ms.is_some_and(|age| age > max_age_ms) {
+                problems.push(format!("{} last updated {}ms ago", symbol.symbol, symbol.last_update_age_ms.unwrap_or_default()));
+            }
+        }
+        Status {
+            healthy: problems.is_empty(),
+            problems,
+            uptime_secs: self.started.elapsed().as_secs_f64(),
+            open_connections,
+            connects: self.feed.connects.load(Ordering::Relaxed),
+            reconnects: self.feed.reconnects.load(Ordering::Relaxed),
+            messages: self.feed.messages.load(Ordering::Relaxed),
+            symbols,
+        }
+    }
+
+    fn with_book(&self, symbol: &str, f: impl FnOnce(&mut BookHealth)) {
+        let mut books = self.books.lock().unwrap();
+        f(books.entry(symbol.to_string()).or_default());
+    }
+}
+
+// HealthSink records the snapshots, deltas and staleness of the books written to it into
+// Health.
+pub struct HealthSink {
+    health: Arc<Health>,
+}
+
+impl Sink for HealthSink {
+    fn name(&self) -> &str {
+        "health"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, _book: &LocalOrderBook) -> SinkResult {
+        self.health.with_book(symbol, |book| {
+            book.synced = true;
+            book.stale = false;
+            book.last_update = Some(Instant::now());
+            book.last_update_ts = Some(ts);
+            book.resyncs += 1;
+        });
+        Ok(())
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        self.health.with_book(symbol, |book| {
+            book.synced = true;
+            book.stale = false;
+            book.last_update = Some(Instant::now());
+            book.last_update_ts = Some(event.ts);
+        });
+        Ok(())
+    }
+
+    fn record_stale(&mut self, symbol: &str, _age: Duration, _book: &LocalOrderBook) -> SinkResult {
+        self.health.with_book(symbol, |book| book.stale = true);
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::csv_export::HourlyCsvFile;
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+use crate::units::{Price, Qty};
+use crate::volume_profile::{bucket_index, bucket_price};
+
+const CSV_HEADER: &str = "ts,price,bid_quantity,ask_quantity";
+
+// HeatmapRange is the range of prices a HeatmapSampler keeps of each sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeatmapRange {
+    // Fixed keeps the prices from low to high, inclusive.
+    Fixed { low: Price, high: Price },
+    // AroundMid keeps the prices within bps of the book's mid at each sample.
+    AroundMid { bps: f64 },
+}
+
+// HeatmapConfig is how often a HeatmapSampler samples the book, by its update time, and how
+// it buckets the levels into the grid of a heatmap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeatmapConfig {
+    pub interval: Duration,
+    // bucket_size is the height of a row of the grid, e.g. the tick size or a multiple of it.
+    pub bucket_size: f64,
+    pub range: HeatmapRange,
+}
+
+// HeatmapCell is the quantity resting in one price bucket of a sample, starting at price.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeatmapCell {
+    pub price: Price,
+    pub bid: Qty,
+    pub ask: Qty,
+}
+
+// HeatmapColumn is one sample of the book, a column of the heatmap at ts. Only the buckets
+// with quantity resting in them are kept, lowest price first, so a sparse book stays small.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeatmapColumn {
+    pub ts: u64,
+    pub mid: Option<Price>,
+    pub cells: Vec<HeatmapCell>,
+}
+
+// HeatmapSampler samples the full book at an interval of its update time into the columns
+// of a liquidity heatmap, a price by size grid over time.
+#[derive(Debug, Clone)]
+pub struct HeatmapSampler {
+    config: HeatmapConfig,
+    last: Option<u64>,
+}
+
+impl HeatmapSampler {
+    pub fn new(config: HeatmapConfig) -> Self {
+        Self { config, last: None }
+    }
+
+    pub fn config(&self) -> &HeatmapConfig {
+        &self.config
+    }
+
+    // sample returns a column of book if a sample is due at its last update.
+    pub fn sample(&mut self, book: &LocalOrderBook) -> Option<HeatmapColumn> {
+        let ts = book.last_update_ts()?;
+        let interval = self.config.interval.as_millis() as u64;
+        if self.last.is_some_and(|last| ts < last + interval) {
+            return None;
+        }
+        self.last = Some(ts);
+        Some(self.column(book, ts))
+    }
+
+    // column returns the column of book at ts, whether or not a sample is due.
+    pub fn column(&self, book: &LocalOrderBook, ts: u64) -> HeatmapColumn {
+        let mid = book.mid_price();
+        let (low, high) = match (self.config.range, mid) {
+            (HeatmapRange::Fixed { low, high }, _) => (low, high),
+            (HeatmapRange::AroundMid { bps }, Some(mid)) => {
+                let offset = mid.value() * bps / 10_000.0;
+                (Price::new(mid.value() - offset), Price::new(mid.value() + offset))
+            }
+            (HeatmapRange::AroundMid { .. }, None) => return HeatmapColumn { ts, mid, cells: Vec::new() },
+        };
+
+        let size = self.config.bucket_size;
+        let mut buckets: BTreeMap<i64, (Qty, Qty)> = BTreeMap::new();
+        let in_range = |&(price, _): &(Price, Qty)| price >= low && price <= high;
+        for (price, quantity) in book.bids().take_while(|&(price, _)| price >= low).filter(in_range) {
+            buckets.entry(bucket_index(price, size)).or_default().0 += quantity;
+        }
+        for (price, quantity) in book.asks().take_while(|&(price, _)| price <= high).filter(in_range) {
+            buckets.entry(bucket_index(price, size)).or_default().1 += quantity;
+        }
+        let cells = buckets
+            .into_iter()
+            .map(|(bucket, (bid, ask))| HeatmapCell { price: bucket_price(bucket, size), bid, ask })
+            .collect();
+        HeatmapColumn { ts, mid, cells }
+    }
+}
+
+// HeatmapRecorder samples a symbol's book into heatmap columns, appending a row per cell to
+// hourly rotated CSV files named <dir>/<symbol>_heatmap_<YYYYMMDD>_<HH>.csv.
+pub struct HeatmapRecorder {
+    sampler: HeatmapSampler,
+    file: HourlyCsvFile,
+}
+
+impl HeatmapRecorder {
+    pub fn new(dir: &Path, symbol: &str, config: HeatmapConfig) -> io::Result<Self> {
+        let file = HourlyCsvFile::new(dir, format!("{}_heatmap", symbol), CSV_HEADER)?;
+        Ok(Self { sampler: HeatmapSampler::new(config), file })
+    }
+
+    // record writes a column of book if a sample is due.
+    pub fn record(&mut self, book: &LocalOrderBook) -> io::Result<()> {
+        let Some(column) = self.sampler.sample(book) else { return Ok(()) };
+        let writer = self.file.writer(column.ts)?;
+        for cell in &column.cells {
+            writeln!(writer, "{},{},{},{}", column.ts, cell.price, cell.bid, cell.ask)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Sink for HeatmapRecorder {
+    fn name(&self) -> &str {
+        "heatmap"
+    }
+
+    fn record_snapshot(&mut self, _symbol: &str, _ts: u64, book: &LocalOrderBook) -> SinkResult {
+        Ok(self.record(book)?)
+    }
+
+    fn record_delta(&mut self, _symbol: &str, _event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        Ok(self.record(book)?)
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        Ok(HeatmapRecorder::flush(self)?)
+    }
+}
+
+impl Drop for HeatmapRecorder {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
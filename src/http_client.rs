@@ -0,0 +1,95 @@
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+
+use crate::proxy::ProxyConfig;
+
+// USER_AGENT identifies this crate and its version to the exchanges.
+pub const USER_AGENT: &str = concat!("woox/", env!("CARGO_PKG_VERSION"));
+
+static SHARED: OnceLock<Client> = OnceLock::new();
+
+// HttpClientConfig is how the HTTP client every REST request goes through is built. timeout
+// bounds a whole request unless the request sets its own, as retried requests do.
+#[derive(Debug, Clone)]
+pub struct HttpClientConfig {
+    pub user_agent: String,
+    pub connect_timeout: Duration,
+    pub timeout: Duration,
+    // pool_idle_timeout is how long an idle connection is kept open for reuse, and
+    // pool_max_idle_per_host how many are kept per host.
+    pub pool_idle_timeout: Duration,
+    pub pool_max_idle_per_host: usize,
+    pub tcp_keepalive: Duration,
+    // proxy is the proxy requests are sent through. Without one, the proxies set by the
+    // environment are used.
+    pub proxy: Option<ProxyConfig>,
+    // tls is the rustls config connections are made with, the system's roots if None.
+    pub tls: Option<Arc<rustls::ClientConfig>>,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: USER_AGENT.to_string(),
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(30),
+            pool_idle_timeout: Duration::from_secs(90),
+            pool_max_idle_per_host: 8,
+            tcp_keepalive: Duration::from_secs(30),
+            proxy: None,
+            tls: None,
+        }
+    }
+}
+
+impl HttpClientConfig {
+    pub fn with_user_agent(self, user_agent: &str) -> Self {
+        Self { user_agent: user_agent.to_string(), ..self }
+    }
+
+    pub fn with_timeouts(self, connect_timeout: Duration, timeout: Duration) -> Self {
+        Self { connect_timeout, timeout, ..self }
+    }
+
+    pub fn with_proxy(self, proxy: Option<ProxyConfig>) -> Self {
+        Self { proxy, ..self }
+    }
+
+    pub fn with_tls(self, tls: Option<Arc<rustls::ClientConfig>>) -> Self {
+        Self { tls, ..self }
+    }
+
+    pub fn build(&self) -> reqwest::Result<Client> {
+        let mut builder = Client::builder();
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.reqwest_proxy()?);
+        }
+        if let Some(tls) = &self.tls {
+            builder = builder.use_preconfigured_tls(rustls::ClientConfig::clone(tls));
+        }
+        builder
+            .user_agent(&self.user_agent)
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.timeout)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .tcp_keepalive(self.tcp_keepalive)
+            .build()
+    }
+}
+
+// configure builds the shared client from config. It has to be called before the first
+// request, and returns false if the shared client was already built.
+pub fn configure(config: &HttpClientConfig) -> reqwest::Result<bool> {
+    let client = config.build()?;
+    Ok(SHARED.set(client).is_ok())
+}
+
+// shared returns the client every REST request goes through, built from the default config
+// if configure wasn't called. Clones share one connection pool, so the connections opened by
+// one request are kept alive for the next, e.g. a resync's snapshot.
+pub fn shared() -> Client {
+    SHARED.get_or_init(|| HttpClientConfig::default().build().unwrap_or_default()).clone()
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+use tracing::error;
+
+use crate::exchange_api_types::Side;
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::units::{Price, Qty};
+
+// Violation is a book invariant a delta broke.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    // Crossed means the best bid is at or above the best ask.
+    Crossed { bid: Price, ask: Price },
+    // NonPositiveQuantity means a level rests with a quantity of zero or less.
+    NonPositiveQuantity { side: Side, price: Price, quantity: Qty },
+    // TooManyLevels means a side has more levels than the checker allows.
+    TooManyLevels { side: Side, levels: usize, max_levels: usize },
+    // TimestampRegressed means the delta is older than the one applied before it.
+    TimestampRegressed { ts: u64, prev_ts: u64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Crossed { bid, ask } => write!(f, "book crossed, bid {} at or above ask {}", bid.value(), ask.value()),
+            Violation::NonPositiveQuantity { side, price, quantity } => {
+                write!(f, "{:?} level {} has quantity {}", side, price.value(), quantity.value())
+            }
+            Violation::TooManyLevels { side, levels, max_levels } => {
+                write!(f, "{:?} side has {} levels, more than {}", side, levels, max_levels)
+            }
+            Violation::TimestampRegressed { ts, prev_ts } => write!(f, "delta at {} is older than the last at {}", ts, prev_ts),
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+// InvariantChecker checks the book after every delta applied to it: it isn't crossed, every
+// level has a positive quantity, neither side has more than max_levels levels, and deltas
+// are applied in timestamp order. It is a debug mode, walking every level on every delta.
+#[derive(Debug, Clone)]
+pub struct InvariantChecker {
+    max_levels: usize,
+    last_ts: Option<u64>,
+}
+
+impl InvariantChecker {
+    pub fn new(max_levels: usize) -> Self {
+        Self { max_levels, last_ts: None }
+    }
+
+    // check returns the invariants book broke once event was applied to it, logging the
+    // event's raw message, where the feed kept it, and the whole book if there are any.
+    pub fn check(&mut self, symbol: &str, event: &MarketEvent, book: &LocalOrderBook) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if let Some(prev_ts) = self.last_ts.filter(|&prev_ts| event.ts < prev_ts) {
+            violations.push(Violation::TimestampRegressed { ts: event.ts, prev_ts });
+        }
+        self.last_ts = Some(self.last_ts.map_or(event.ts, |prev_ts| prev_ts.max(event.ts)));
+
+        if let (Some((bid, _)), Some((ask, _))) = (book.best_bid(), book.best_ask()) {
+            if bid >= ask {
+                violations.push(Violation::Crossed { bid, ask });
+            }
+        }
+        let sides: [(Side, Vec<(Price, Qty)>); 2] = [(Side::Buy, book.bids().collect()), (Side::Sell, book.asks().collect())];
+        for (side, levels) in &sides {
+            if levels.len() > self.max_levels {
+                violations.push(Violation::TooManyLevels { side: *side, levels: levels.len(), max_levels: self.max_levels });
+            }
+            for &(price, quantity) in levels {
+                if quantity.value() <= 0.0 {
+                    violations.push(Violation::NonPositiveQuantity { side: *side, price, quantity });
+                }
+            }
+        }
+
+        if !violations.is_empty() {
+            let messages: Vec<String> = violations.iter().map(Violation::to_string).collect();
+            let book = serde_json::to_string(&book.to_snapshot()).unwrap_or_default();
+            error!(
+                %symbol,
+                ts = event.ts,
+                prev_ts = event.prev_ts,
+                violations = %messages.join("; "),
+                raw = event.raw.as_deref().unwrap_or("(not kept)"),
+                %book,
+                "Book invariant violated"
+            );
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::exchange_api_types::{OrderBookDelta, RestQuote, SnapshotData, WsQuote};
+
+    fn book(bids: &[(f64, f64)], asks: &[(f64, f64)]) -> LocalOrderBook {
+        let quotes = |levels: &[(f64, f64)]| levels.iter().map(|&(price, quantity)| RestQuote { price, quantity }).collect();
+        let mut book = LocalOrderBook::new();
+        book.apply_snapshot(SnapshotData { bids: quotes(bids), asks: quotes(asks) });
+        book
+    }
+
+    fn event(ts: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> MarketEvent {
+        let quotes = |levels: &[(f64, f64)]| levels.iter().map(|&(price, quantity)| WsQuote { price, quantity }).collect();
+        MarketEvent {
+            ts,
+            prev_ts: ts - 100,
+            seq: ts,
+            prev_seq: ts - 100,
+            delta: OrderBookDelta { prev_ts: ts - 100, bids: quotes(bids), asks: quotes(asks) },
+            snapshot: false,
+            checksum: None,
+            received_at: Instant::now(),
+            raw: None,
+            text: None,
+        }
+    }
+
+    #[test]
+    fn passes_a_sound_book() {
+        let mut book = book(&[(99.0, 1.0), (98.0, 2.0)], &[(101.0, 1.0)]);
+        let event = event(1100, &[(100.0, 1.0)], &[]);
+        book.apply_delta(&event.delta);
+        assert!(InvariantChecker::new(5).check("SPOT_BTC_USDT", &event, &book).is_empty());
+    }
+
+    #[test]
+    fn reports_a_crossed_book() {
+        let mut book = book(&[(99.0, 1.0)], &[(101.0, 1.0)]);
+        let event = event(1100, &[(101.5, 1.0)], &[]);
+        book.apply_delta(&event.delta);
+        let violations = InvariantChecker::new(5).check("SPOT_BTC_USDT", &event, &book);
+        assert_eq!(violations, vec![Violation::Crossed { bid: Price::new(101.5), ask: Price::new(101.0) }]);
+    }
+
+    #[test]
+    fn reports_a_side_deeper_than_max_levels() {
+        let mut book = book(&[(99.0, 1.0), (98.0, 1.0)], &[(101.0, 1.0)]);
+        let event = event(1100, &[(97.0, 1.0)], &[]);
+        book.apply_delta(&event.delta);
+        let violations = InvariantChecker::new(2).check("SPOT_BTC_USDT", &event, &book);
+        assert_eq!(violations, vec![Violation::TooManyLevels { side: Side::Buy, levels: 3, max_levels: 2 }]);
+    }
+
+    #[test]
+    fn reports_a_delta_older_than_the_last() {
+        let book = book(&[(99.0, 1.0)], &[(101.0, 1.0)]);
+        let mut checker = InvariantChecker::new(5);
+        assert!(checker.check("SPOT_BTC_USDT", &event(1200, &[], &[]), &book).is_empty());
+        let violations = checker.check("SPOT_BTC_USDT", &event(1100, &[], &[]), &book);
+        assert_eq!(violations, vec![Violation::TimestampRegressed { ts: 1100, prev_ts: 1200 }]);
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, KafkaResult, RDKafkaErrorCode};
+use rdkafka::producer::{BaseProducer, BaseRecord, Producer};
+#[cfg(feature = "proto")]
+use prost::Message;
+
+use crate::exchange_api_types::WsTrade;
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+#[cfg(feature = "proto")]
+use crate::proto;
+use crate::publish::{BookMessage, TradeMessage};
+use crate::sink::{Sink, SinkResult};
+
+// FLUSH_TIMEOUT bounds how long flush waits for queued messages to be delivered.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+// QUEUE_FULL_WAIT is how long to serve deliveries before retrying a send rejected because
+// the producer queue is full.
+const QUEUE_FULL_WAIT: Duration = Duration::from_millis(100);
+
+// Encoding is how a KafkaSink encodes the messages it produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    // Json produces the JSON publish protocol messages of the publish module.
+    Json,
+    // Protobuf produces the messages of proto/woox.proto: a BookSnapshot on the snapshot
+    // topic, a BookUpdate on the delta topic and a Trade on the trade topic.
+    #[cfg(feature = "proto")]
+    Protobuf,
+}
+
+// KafkaConfig configures the brokers and topics a KafkaSink produces to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KafkaConfig {
+    // brokers is the bootstrap broker list, e.g. localhost:9092.
+    pub brokers: String,
+    pub snapshot_topic: String,
+    pub delta_topic: String,
+    pub trade_topic: String,
+    pub encoding: Encoding,
+}
+
+impl KafkaConfig {
+    pub fn new(brokers: &str) -> Self {
+        Self {
+            brokers: brokers.to_string(),
+            snapshot_topic: "woox.snapshots".to_string(),
+            delta_topic: "woox.deltas".to_string(),
+            trade_topic: "woox.trades".to_string(),
+            encoding: Encoding::Json,
+        }
+    }
+}
+
+// KafkaSink produces snapshots, deltas and trades to Kafka, encoded as its config sets.
+// Messages are keyed by symbol, so each symbol's messages land on one partition
+// and stay in order. Clones share the producer.
+#[derive(Clone)]
+pub struct KafkaSink {
+    producer: Arc<BaseProducer>,
+    config: KafkaConfig,
+}
+
+impl KafkaSink {
+    pub fn new(config: KafkaConfig) -> KafkaResult<Self> {
+        let producer = ClientConfig::new().set("bootstrap.servers", &config.brokers).create()?;
+        Ok(Self { producer: Arc::new(producer), config })
+    }
+
+    // send queues payload to topic keyed by symbol, waiting for deliveries while the
+    // producer queue is full.
+    fn send(&self, topic: &str, symbol: &str, payload: &[u8]) -> SinkResult {
+        let mut record = BaseRecord::to(topic).key(symbol).payload(payload);
+        loop {
+            match self.producer.send(record) {
+                Ok(()) => break,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), rejected)) => {
+                    self.producer.poll(QUEUE_FULL_WAIT);
+                    record = rejected;
+                }
+                Err((e, _)) => return Err(e.into()),
+            }
+        }
+        // Serve delivery reports without blocking.
+        self.producer.poll(Duration::ZERO);
+        Ok(())
+    }
+}
+
+impl Sink for KafkaSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        let payload = match self.config.encoding {
+            Encoding::Json => serde_json::to_vec(&BookMessage::snapshot(symbol, ts, book))?,
+            #[cfg(feature = "proto")]
+            Encoding::Protobuf => proto::BookSnapshot::from_book(symbol, ts, book, usize::MAX).encode_to_vec(),
+        };
+        self.send(&self.config.snapshot_topic, symbol, &payload)
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        let payload = match self.config.encoding {
+            Encoding::Json => serde_json::to_vec(&BookMessage::update(symbol, event))?,
+            #[cfg(feature = "proto")]
+            Encoding::Protobuf => proto::BookUpdate::from_event(symbol, event).encode_to_vec(),
+        };
+        self.send(&self.config.delta_topic, symbol, &payload)
+    }
+
+    fn record_trade(&mut self, trade: &WsTrade) -> SinkResult {
+        let payload = match self.config.encoding {
+            Encoding::Json => serde_json::to_vec(&TradeMessage::from(trade))?,
+            #[cfg(feature = "proto")]
+            Encoding::Protobuf => proto::Trade::from(trade).encode_to_vec(),
+        };
+        self.send(&self.config.trade_topic, &trade.symbol, &payload)
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.producer.flush(FLUSH_TIMEOUT)?;
+        Ok(())
+    }
+}
+
+impl Drop for KafkaSink {
+    fn drop(&mut self) {
+        let _ = self.producer.flush(FLUSH_TIMEOUT);
+    }
+}
@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use crate::rest::{KlineHistoryRequest, RestClient, RestError, RestKline};
+use crate::units::{Notional, Price, Qty};
+
+// PAGE_LIMIT is the most candles requested per page.
+const PAGE_LIMIT: u64 = 1000;
+
+// KlineInterval is the period a candle covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KlineInterval {
+    Minute1,
+    Minute5,
+    Minute15,
+    Minute30,
+    Hour1,
+    Hour4,
+    Hour12,
+    Day1,
+    Week1,
+}
+
+impl KlineInterval {
+    // as_str returns the interval as the exchange names it.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KlineInterval::Minute1 => "1m",
+            KlineInterval::Minute5 => "5m",
+            KlineInterval::Minute15 => "15m",
+            KlineInterval::Minute30 => "30m",
+            KlineInterval::Hour1 => "1h",
+            KlineInterval::Hour4 => "4h",
+            KlineInterval::Hour12 => "12h",
+            KlineInterval::Day1 => "1d",
+            KlineInterval::Week1 => "1w",
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        let minutes = match self {
+            KlineInterval::Minute1 => 1,
+            KlineInterval::Minute5 => 5,
+            KlineInterval::Minute15 => 15,
+            KlineInterval::Minute30 => 30,
+            KlineInterval::Hour1 => 60,
+            KlineInterval::Hour4 => 4 * 60,
+            KlineInterval::Hour12 => 12 * 60,
+            KlineInterval::Day1 => 24 * 60,
+            KlineInterval::Week1 => 7 * 24 * 60,
+        };
+        Duration::from_secs(minutes * 60)
+    }
+}
+
+// Kline is a historical candle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kline {
+    pub symbol: String,
+    pub interval: KlineInterval,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    // volume is the base quantity traded, and amount the quote notional traded.
+    pub volume: Qty,
+    pub amount: Notional,
+    pub start_ts: u64,
+    pub end_ts: u64,
+}
+
+impl Kline {
+    fn from_rest(rest: RestKline, interval: KlineInterval) -> Self {
+        Self {
+            symbol: rest.symbol,
+            interval,
+            open: Price::new(rest.open),
+            high: Price::new(rest.high),
+            low: Price::new(rest.low),
+            close: Price::new(rest.close),
+            volume: Qty::new(rest.volume),
+            amount: Notional::new(rest.amount),
+            start_ts: rest.start_timestamp,
+            end_ts: rest.end_timestamp,
+        }
+    }
+}
+
+// KlineClient fetches historical candles from the Woo X REST API.
+#[derive(Default)]
+pub struct KlineClient {
+    rest: RestClient,
+}
+
+impl KlineClient {
+    pub fn new(rest: RestClient) -> Self {
+        Self { rest }
+    }
+
+    // fetch returns the candles for symbol starting in [start_ts, end_ts), oldest first.
+    // The range is requested a page of candles at a time, so it can be any length.
+    pub fn fetch(&self, symbol: &str, interval: KlineInterval, start_ts: u64, end_ts: u64) -> Result<Vec<Kline>, RestError> {
+        let page_span = interval.duration().as_millis() as u64 * PAGE_LIMIT;
+        let mut klines: Vec<Kline> = Vec::new();
+        let mut cursor = start_ts;
+
+        while cursor < end_ts {
+            let page_end = end_ts.min(cursor + page_span);
+            let request = KlineHistoryRequest {
+                symbol: symbol.to_string(),
+                interval: interval.as_str().to_string(),
+                start_time: cursor,
+                end_time: page_end,
+                limit: PAGE_LIMIT,
+            };
+            for rest in self.rest.send(&request)?.rows {
+                if rest.start_timestamp >= cursor && rest.start_timestamp < page_end {
+                    klines.push(Kline::from_rest(rest, interval));
+                }
+            }
+            cursor = page_end;
+        }
+
+        klines.sort_by_key(|kline| kline.start_ts);
+        klines.dedup_by_key(|kline| kline.start_ts);
+        Ok(klines)
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::exchange_api_types::{Side, WsQuote, WsTrade};
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::units::{Price, Qty};
+
+// Experimental: the exchange only publishes aggregated levels (L2), so the individual
+// order events here are inferred and will often be wrong. They are meant for queue
+// modeling research, not for anything that needs the real order flow.
+
+// TRADE_MATCH_WINDOW_MS is how far apart a trade and a level decrease can be and still be
+// matched as an execution.
+const TRADE_MATCH_WINDOW_MS: u64 = 250;
+
+// QTY_EPSILON absorbs float noise when comparing quantities.
+const QTY_EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum L3EventKind {
+    // Add is a new order joining the back of its level's queue.
+    Add,
+    // Cancel is an order, or part of one, leaving the queue without trading.
+    Cancel,
+    // Execution is an order, or part of one, filled by a trade from the front of the queue.
+    Execution,
+}
+
+// L3Event is a single inferred order event. side is the side of the book the order rests
+// on, Buy for bids and Sell for asks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct L3Event {
+    pub ts: u64,
+    pub kind: L3EventKind,
+    pub side: Side,
+    pub price: Price,
+    pub quantity: Qty,
+    // order_id identifies the synthetic order the event applies to. Ids are only
+    // meaningful within one L3Reconstructor.
+    pub order_id: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SyntheticOrder {
+    id: u64,
+    quantity: Qty,
+}
+
+// L3Reconstructor infers order events from the level changes of a book and the public
+// trades for it. Each level is modeled as a FIFO queue of synthetic orders:
+//  - a level increase is a new order at the back of the queue,
+//  - a decrease matched by a recent trade at that price is an execution from the front,
+//  - any other decrease is a cancel, of an order with exactly that quantity if there is
+//    one, and otherwise from the back of the queue, as the newest orders cancel most often.
+// Levels present when the reconstructor is seeded start out as a single order each. Trades
+// arrive on their own connection, so one that lands after the delta it caused is missed
+// and the decrease is taken as a cancel.
+#[derive(Debug, Default)]
+pub struct L3Reconstructor {
+    bids: BTreeMap<Price, VecDeque<SyntheticOrder>>,
+    asks: BTreeMap<Price, VecDeque<SyntheticOrder>>,
+    // trades holds recent trades not yet matched to a level decrease, with the quantity
+    // left to match.
+    trades: VecDeque<(WsTrade, Qty)>,
+    next_id: u64,
+}
+
+impl L3Reconstructor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // seed resets the queues to the levels of book, each as a single order, and returns
+    // the adds for them.
+    pub fn seed(&mut self, ts: u64, book: &LocalOrderBook) -> Vec<L3Event> {
+        self.bids.clear();
+        self.asks.clear();
+        self.trades.clear();
+
+        let mut events = Vec::new();
+        for (side, levels) in [(Side::Buy, book.bids().collect::<Vec<_>>()), (Side::Sell, book.asks().collect())] {
+            for (price, quantity) in levels {
+                events.push(self.add(ts, side, price, quantity));
+            }
+        }
+        events
+    }
+
+    // on_trade queues trade to be matched against the level decreases that follow it.
+    pub fn on_trade(&mut self, trade: &WsTrade) {
+        self.trades.push_back((trade.clone(), Qty::new(trade.quantity)));
+    }
+
+    // on_delta returns the order events inferred from the level changes in event.
+    pub fn on_delta(&mut self, event: &MarketEvent) -> Vec<L3Event> {
+        self.trades.retain(|(trade, _)| trade.ts + TRADE_MATCH_WINDOW_MS >= event.ts);
+
+        let mut events = Vec::new();
+        for (side, quotes) in [(Side::Buy, &event.delta.bids), (Side::Sell, &event.delta.asks)] {
+            for quote in quotes {
+                self.on_level(event.ts, side, quote, &mut events);
+            }
+        }
+        events
+    }
+
+    fn on_level(&mut self, ts: u64, side: Side, quote: &WsQuote, events: &mut Vec<L3Event>) {
+        let price = Price::new(quote.price);
+        let new_quantity = Qty::new(quote.quantity);
+        let old_quantity: Qty = self.queue(side, price).map_or(Qty::ZERO, |queue| queue.iter().map(|order| order.quantity).sum());
+
+        if new_quantity.value() > old_quantity.value() + QTY_EPSILON {
+            events.push(self.add(ts, side, price, new_quantity - old_quantity));
+        } else if new_quantity.value() < old_quantity.value() - QTY_EPSILON {
+            let mut decrease = old_quantity - new_quantity;
+            let executed = self.match_trades(side, price, decrease);
+            if executed.value() > QTY_EPSILON {
+                self.execute(ts, side, price, executed, events);
+                decrease -= executed;
+            }
+            if decrease.value() > QTY_EPSILON {
+                self.cancel(ts, side, price, decrease, events);
+            }
+        }
+
+        if new_quantity.value() <= QTY_EPSILON {
+            self.levels(side).remove(&price);
+        }
+    }
+
+    // match_trades consumes up to quantity of the recent trades that could have traded
+    // against the resting side at price, returning the quantity matched. Buy aggressors
+    // trade against asks and sell aggressors against bids.
+    fn match_trades(&mut self, side: Side, price: Price, quantity: Qty) -> Qty {
+        let aggressor = match side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        let mut matched = Qty::ZERO;
+        for (trade, remaining) in self.trades.iter_mut() {
+            if trade.side != aggressor || Price::new(trade.price) != price || remaining.value() <= QTY_EPSILON {
+                continue;
+            }
+            let fill = (*remaining).min(quantity - matched);
+            *remaining -= fill;
+            matched += fill;
+            if (quantity - matched).value() <= QTY_EPSILON {
+                break;
+            }
+        }
+        self.trades.retain(|(_, remaining)| remaining.value() > QTY_EPSILON);
+        matched
+    }
+
+    fn add(&mut self, ts: u64, side: Side, price: Price, quantity: Qty) -> L3Event {
+        self.next_id += 1;
+        let order = SyntheticOrder { id: self.next_id, quantity };
+        self.levels(side).entry(price).or_default().push_back(order);
+        L3Event { ts, kind: L3EventKind::Add, side, price, quantity, order_id: order.id }
+    }
+
+    // execute fills quantity from the front of the queue at price.
+    fn execute(&mut self, ts: u64, side: Side, price: Price, mut quantity: Qty, events: &mut Vec<L3Event>) {
+        let Some(queue) = self.levels(side).get_mut(&price) else { return };
+        while quantity.value() > QTY_EPSILON {
+            let Some(front) = queue.front_mut() else { break };
+            let fill = front.quantity.min(quantity);
+            events.push(L3Event { ts, kind: L3EventKind::Execution, side, price, quantity: fill, order_id: front.id });
+            front.quantity -= fill;
+            quantity -= fill;
+            if front.quantity.value() <= QTY_EPSILON {
+                queue.pop_front();
+            }
+        }
+    }
+
+    // cancel removes quantity from the queue at price, preferring a single order of exactly
+    // that quantity and otherwise taking from the back.
+    fn cancel(&mut self, ts: u64, side: Side, price: Price, mut quantity: Qty, events: &mut Vec<L3Event>) {
+        let Some(queue) = self.levels(side).get_mut(&price) else { return };
+
+        let exact = queue.iter().position(|order| (order.quantity - quantity).value().abs() <= QTY_EPSILON);
+        if let Some(index) = exact {
+            let order = queue.remove(index).unwrap();
+            events.push(L3Event { ts, kind: L3EventKind::Cancel, side, price, quantity: order.quantity, order_id: order.id });
+            return;
+        }
+
+        while quantity.value() > QTY_EPSILON {
+            let Some(back) = queue.back_mut() else { break };
+            let cancelled = back.quantity.min(quantity);
+            events.push(L3Event { ts, kind: L3EventKind::Cancel, side, price, quantity: cancelled, order_id: back.id });
+            back.quantity -= cancelled;
+            quantity -= cancelled;
+            if back.quantity.value() <= QTY_EPSILON {
+                queue.pop_back();
+            }
+        }
+    }
+
+    // queue_ahead returns the quantity ahead of order_id in its queue, if it is still resting.
+    pub fn queue_ahead(&self, side: Side, price: Price, order_id: u64) -> Option<Qty> {
+        let queue = self.queue(side, price)?;
+        let position = queue.iter().position(|order| order.id == order_id)?;
+        Some(queue.iter().take(position).map(|order| order.quantity).sum())
+    }
+
+    // orders returns the number of synthetic orders resting at price.
+    pub fn orders(&self, side: Side, price: Price) -> usize {
+        self.queue(side, price).map_or(0, VecDeque::len)
+    }
+
+    fn queue(&self, side: Side, price: Price) -> Option<&VecDeque<SyntheticOrder>> {
+        match side {
+            Side::Buy => self.bids.get(&price),
+            Side::Sell => self.asks.get(&price),
+        }
+    }
+
+    fn levels(&mut self, side: Side) -> &mut BTreeMap<Price, VecDeque<SyntheticOrder>> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+}
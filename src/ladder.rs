@@ -0,0 +1,109 @@
+use std::cmp::{Ordering, Reverse};
+
+use crate::exchange_api_types::{OrderBookDelta, Side, SnapshotData, WsQuote};
+use crate::orderbook::OrderBook;
+use crate::units::{Price, Qty};
+
+// LadderBook is an OrderBook keeping each side in a contiguous vec sorted by price, found by
+// binary search. For the tens of levels a depth stream covers, a vec fits in a few cache
+// lines and beats the pointer chasing of a BTreeMap on both updates and reads. Each side is
+// sorted with its best price last, so the updates near the touch, the bulk of them, shift
+// the fewest levels.
+#[derive(Debug, Clone, Default)]
+pub struct LadderBook {
+    // bids are sorted by ascending price and asks by descending price.
+    bids: Vec<(Price, Qty)>,
+    asks: Vec<(Price, Qty)>,
+}
+
+impl LadderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // with_capacity returns a book with room for levels levels a side before it reallocates.
+    pub fn with_capacity(levels: usize) -> Self {
+        Self { bids: Vec::with_capacity(levels), asks: Vec::with_capacity(levels) }
+    }
+
+    // len returns the number of levels on side.
+    pub fn len(&self, side: Side) -> usize {
+        match side {
+            Side::Buy => self.bids.len(),
+            Side::Sell => self.asks.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+}
+
+impl OrderBook for LadderBook {
+    fn apply_snapshot(&mut self, data: SnapshotData) {
+        self.bids.clear();
+        self.asks.clear();
+        self.bids.extend(data.bids.iter().map(|quote| (Price::new(quote.price), Qty::new(quote.quantity))));
+        self.asks.extend(data.asks.iter().map(|quote| (Price::new(quote.price), Qty::new(quote.quantity))));
+        self.bids.sort_by_key(|&(price, _)| price);
+        self.asks.sort_by_key(|&(price, _)| Reverse(price));
+        // A price repeated in the snapshot takes its last quantity, as in LocalOrderBook.
+        self.bids.dedup_by(keep_later);
+        self.asks.dedup_by(keep_later);
+    }
+
+    fn apply_delta(&mut self, delta: &OrderBookDelta) {
+        for quote in &delta.bids {
+            update(&mut self.bids, quote, |level, price| level.cmp(&price));
+        }
+        for quote in &delta.asks {
+            update(&mut self.asks, quote, |level, price| price.cmp(&level));
+        }
+    }
+
+    fn bids(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        self.bids.iter().rev().copied()
+    }
+
+    fn asks(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        self.asks.iter().rev().copied()
+    }
+
+    fn best_bid(&self) -> Option<(Price, Qty)> {
+        self.bids.last().copied()
+    }
+
+    fn best_ask(&self) -> Option<(Price, Qty)> {
+        self.asks.last().copied()
+    }
+
+    fn quantity_at(&self, side: Side, price: Price) -> Qty {
+        let found = match side {
+            Side::Buy => self.bids.binary_search_by(|&(level, _)| level.cmp(&price)).map(|i| self.bids[i].1),
+            Side::Sell => self.asks.binary_search_by(|&(level, _)| price.cmp(&level)).map(|i| self.asks[i].1),
+        };
+        found.unwrap_or_default()
+    }
+}
+
+// keep_later merges level into the equal priced earlier one before it, keeping its quantity.
+fn keep_later(level: &mut (Price, Qty), earlier: &mut (Price, Qty)) -> bool {
+    let same = level.0 == earlier.0;
+    if same {
+        earlier.1 = level.1;
+    }
+    same
+}
+
+// update sets the level of quote in levels, sorted by order, removing it if the quantity is 0.
+fn update(levels: &mut Vec<(Price, Qty)>, quote: &WsQuote, order: fn(Price, Price) -> Ordering) {
+    let (price, quantity) = (Price::new(quote.price), Qty::new(quote.quantity));
+    match (levels.binary_search_by(|&(level, _)| order(level, price)), quote.quantity == 0.0) {
+        (Ok(i), true) => {
+            levels.remove(i);
+        }
+        (Ok(i), false) => levels[i].1 = quantity,
+        (Err(_), true) => {}
+        (Err(i), false) => levels.insert(i, (price, quantity)),
+    }
+}
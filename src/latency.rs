@@ -0,0 +1,165 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use hdrhistogram::Histogram;
+use tracing::info;
+
+use crate::clock::ClockSkew;
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+
+// MAX_LATENCY_US is the largest latency tracked, in microseconds. Larger ones are recorded
+// as this.
+const MAX_LATENCY_US: u64 = 60_000_000;
+// SIGNIFICANT_DIGITS is the precision the histograms keep their values to.
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+// QUANTILES are the quantiles reported by summaries and metrics.
+pub const QUANTILES: [f64; 3] = [0.5, 0.99, 0.999];
+
+// Stage is a leg of an event's path from the exchange to the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    // Feed is from the exchange timestamp to the event being received locally.
+    Feed,
+    // Processing is from the event being received to it being applied to the book.
+    Processing,
+    // EndToEnd is from the exchange timestamp to the event being applied.
+    EndToEnd,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 3] = [Stage::Feed, Stage::Processing, Stage::EndToEnd];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Stage::Feed => "feed",
+            Stage::Processing => "processing",
+            Stage::EndToEnd => "end_to_end",
+        }
+    }
+}
+
+// LatencyHistograms keeps an HDR histogram of each stage's latency, in microseconds.
+#[derive(Debug, Clone)]
+pub struct LatencyHistograms {
+    feed: Histogram<u64>,
+    processing: Histogram<u64>,
+    end_to_end: Histogram<u64>,
+}
+
+impl Default for LatencyHistograms {
+    fn default() -> Self {
+        let histogram = || Histogram::new_with_bounds(1, MAX_LATENCY_US, SIGNIFICANT_DIGITS).unwrap();
+        Self { feed: histogram(), processing: histogram(), end_to_end: histogram() }
+    }
+}
+
+impl LatencyHistograms {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // record records the latencies of event, which has just been applied to the book,
+    // correcting for the skew between the clocks if clock is given.
+    pub fn record(&mut self, event: &MarketEvent, clock: Option<&ClockSkew>) {
+        let applied_ms = clock.map_or_else(now_ms, ClockSkew::exchange_now_ms);
+        self.record_at(event.ts, event.received_at.elapsed(), applied_ms);
+    }
+
+    // record_at records the latencies of an event with exchange timestamp ts that took
+    // processing to apply after it was received, finishing at applied_ms on the exchange's
+    // clock. A feed latency below zero, from an uncorrected local clock being behind the
+    // exchange's, is recorded as zero.
+    pub fn record_at(&mut self, ts: u64, processing: Duration, applied_ms: u64) {
+        let end_to_end = Duration::from_millis(applied_ms.saturating_sub(ts));
+        let feed = end_to_end.saturating_sub(processing);
+        self.feed.saturating_record(micros(feed));
+        self.processing.saturating_record(micros(processing));
+        self.end_to_end.saturating_record(micros(end_to_end));
+    }
+
+    pub fn histogram(&self, stage: Stage) -> &Histogram<u64> {
+        match stage {
+            Stage::Feed => &self.feed,
+            Stage::Processing => &self.processing,
+            Stage::EndToEnd => &self.end_to_end,
+        }
+    }
+
+    // quantile returns the latency of stage at quantile, e.g. 0.99.
+    pub fn quantile(&self, stage: Stage, quantile: f64) -> Duration {
+        Duration::from_micros(self.histogram(stage).value_at_quantile(quantile))
+    }
+
+    pub fn count(&self) -> u64 {
+        self.processing.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.feed.reset();
+        self.processing.reset();
+        self.end_to_end.reset();
+    }
+
+    // summary returns a single line description of each stage's quantiles.
+    pub fn summary(&self) -> String {
+        let stages: Vec<String> = Stage::ALL
+            .iter()
+            .map(|&stage| {
+                let quantiles: Vec<String> = QUANTILES
+                    .iter()
+                    .map(|&quantile| format!("p{} {:?}", quantile * 100.0, self.quantile(stage, quantile)))
+                    .collect();
+                format!("{} {}", stage.label(), quantiles.join(" "))
+            })
+            .collect();
+        format!("Latency over {} events: {}", self.count(), stages.join(" | "))
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().min(MAX_LATENCY_US as u128) as u64
+}
+
+// LatencyReporter is a sink that logs a summary of the latencies of the deltas written to
+// it every interval, then starts over.
+pub struct LatencyReporter {
+    histograms: LatencyHistograms,
+    interval: Duration,
+    last_report: Instant,
+    clock: Option<Arc<ClockSkew>>,
+}
+
+impl LatencyReporter {
+    pub fn new(interval: Duration) -> Self {
+        Self { histograms: LatencyHistograms::new(), interval, last_report: Instant::now(), clock: None }
+    }
+
+    // with_clock corrects the latencies measured from exchange timestamps by clock's skew.
+    pub fn with_clock(mut self, clock: Arc<ClockSkew>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+}
+
+impl Sink for LatencyReporter {
+    fn name(&self) -> &str {
+        "latency"
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        self.histograms.record(event, self.clock.as_deref());
+        if self.last_report.elapsed() >= self.interval {
+            self.last_report = Instant::now();
+            info!(%symbol, "{}", self.histograms.summary());
+            self.histograms.reset();
+        }
+        Ok(())
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
@@ -0,0 +1,161 @@
+#[cfg(feature = "native")]
+pub mod adaptive;
+#[cfg(feature = "native")]
+pub mod aggregated;
+#[cfg(feature = "native")]
+pub mod alerts;
+#[cfg(feature = "native")]
+pub mod analytics;
+#[cfg(feature = "native")]
+pub mod arbitrage;
+#[cfg(feature = "native")]
+pub mod arbitrator;
+#[cfg(feature = "native")]
+pub mod backfill;
+#[cfg(feature = "native")]
+pub mod backtest;
+#[cfg(feature = "native")]
+pub mod basis;
+#[cfg(feature = "native")]
+pub mod candle;
+#[cfg(feature = "native")]
+pub mod chaos;
+#[cfg(feature = "native")]
+pub mod client;
+#[cfg(feature = "native")]
+pub mod clock;
+#[cfg(feature = "native")]
+pub mod compact;
+#[cfg(feature = "native")]
+pub mod config;
+#[cfg(feature = "native")]
+pub mod control;
+#[cfg(feature = "native")]
+pub mod csv_export;
+#[cfg(feature = "native")]
+pub mod deadman;
+pub mod dense;
+#[cfg(feature = "native")]
+pub mod exchange;
+pub mod exchange_api_types;
+#[cfg(feature = "native")]
+pub mod execution;
+#[cfg(feature = "native")]
+pub mod fanout;
+#[cfg(feature = "native")]
+pub mod feed;
+#[cfg(feature = "native")]
+pub mod fees;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "native")]
+pub mod health;
+#[cfg(feature = "native")]
+pub mod heatmap;
+#[cfg(feature = "native")]
+pub mod http;
+#[cfg(feature = "native")]
+pub mod http_client;
+#[cfg(feature = "native")]
+pub mod invariants;
+#[cfg(feature = "kafka")]
+pub mod kafka_sink;
+#[cfg(feature = "native")]
+pub mod kline;
+pub mod ladder;
+#[cfg(feature = "native")]
+pub mod latency;
+#[cfg(feature = "native")]
+pub mod manager;
+#[cfg(feature = "native")]
+pub mod market_maker;
+#[cfg(feature = "native")]
+pub mod metrics;
+#[cfg(feature = "nats")]
+pub mod nats_sink;
+#[cfg(feature = "native")]
+pub mod notifiers;
+#[cfg(feature = "native")]
+pub mod l3;
+#[cfg(feature = "native")]
+pub mod oms;
+#[cfg(feature = "native")]
+pub mod order;
+pub mod orderbook;
+#[cfg(feature = "parquet")]
+pub mod parquet_recorder;
+#[cfg(feature = "native")]
+pub mod peer;
+#[cfg(feature = "native")]
+pub mod pnl;
+#[cfg(feature = "native")]
+pub mod poll;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "native")]
+pub mod proxy;
+#[cfg(feature = "native")]
+pub mod publish;
+#[cfg(feature = "native")]
+pub mod queue;
+#[cfg(feature = "native")]
+pub mod reconstruct;
+#[cfg(feature = "native")]
+pub mod recorder;
+#[cfg(feature = "redis")]
+pub mod redis_sink;
+#[cfg(feature = "native")]
+pub mod render;
+#[cfg(feature = "native")]
+pub mod rest;
+#[cfg(feature = "native")]
+pub mod retry;
+#[cfg(feature = "native")]
+pub mod risk;
+#[cfg(feature = "native")]
+pub mod scheduler;
+#[cfg(feature = "native")]
+pub mod session;
+#[cfg(feature = "native")]
+pub mod shared_book;
+#[cfg(feature = "shm")]
+pub mod shm;
+#[cfg(feature = "native")]
+pub mod simulator;
+#[cfg(feature = "native")]
+pub mod sink;
+#[cfg(feature = "native")]
+pub mod slo;
+#[cfg(feature = "native")]
+pub mod snapshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_sink;
+#[cfg(feature = "native")]
+pub mod standby;
+#[cfg(feature = "native")]
+pub mod strategy;
+#[cfg(feature = "native")]
+pub mod supervisor;
+#[cfg(feature = "native")]
+pub mod symbol;
+#[cfg(feature = "native")]
+pub mod sync;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "native")]
+pub mod tls;
+#[cfg(feature = "native")]
+pub mod trading;
+#[cfg(feature = "native")]
+pub mod transport;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod units;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
+#[cfg(feature = "native")]
+pub mod volume_profile;
+#[cfg(feature = "wasm")]
+pub mod wasm;
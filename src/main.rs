@@ -1,149 +1,1565 @@
-mod orderbook;
-mod exchange_api_types;
-
+use std::path::Path;
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use std::net::TcpStream;
-
-use serde_json::json;
-use tungstenite::{connect, Message, WebSocket};
-use tungstenite::stream::MaybeTlsStream;
-use url::Url;
+use std::time::{Duration, Instant};
 
-use orderbook::LocalOrderBook;
-use exchange_api_types::{OrderBookDelta, WsMessage, RestSnapshot};
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+use woox::alerts::{AlertConfig, AlertRule, AlertSink};
+use woox::analytics::{BookAnalytics, BookAnalyzer, RealizedVolatility};
+use woox::arbitrage::{ArbAlertKind, ArbConfig, ArbLeg, ArbitrageMonitor};
+use woox::arbitrator::ArbitrationMetrics;
+use woox::backfill::{self, TradeBackfill};
+use woox::backtest::{self, BacktestStats};
+use woox::basis::{BasisConfig, BasisMonitor};
+use woox::chaos::{Chaos, ChaosConfig};
+use woox::client::WooxClient;
+use woox::clock::ClockSkew;
+use woox::compact::{self, CompactRecorder};
+use woox::control::{self, ControlCommand, ControlReply, ControlRequest, RecordingSwitch};
+use woox::config::{ConfigError, ConfigWatcher, FileWatcher, OutputConfig};
+use woox::csv_export::CsvRecorder;
+use woox::exchange::woox::{ApiVersion, WooxEnvironment};
+use woox::exchange_api_types::WsTrade;
+use woox::feed::{FeedConfig, SocketBackend};
+use woox::fees::FeeSchedule;
+use woox::health::Health;
+use woox::heatmap::{HeatmapConfig, HeatmapRange, HeatmapRecorder};
+use woox::http_client::{self, HttpClientConfig};
+#[cfg(feature = "grpc")]
+use woox::grpc::GrpcPublisher;
+#[cfg(feature = "kafka")]
+use woox::kafka_sink::{self, KafkaConfig, KafkaSink};
+use woox::latency::LatencyReporter;
+use woox::manager::{self, BookManager, SymbolFilter};
+use woox::market_maker::{MarketMaker, MarketMakerConfig};
+use woox::metrics::{FeedMetrics, Metrics};
+#[cfg(feature = "nats")]
+use woox::nats_sink::{NatsConfig, NatsSink};
+#[cfg(feature = "parquet")]
+use woox::parquet_recorder::{self, ParquetHeatmapRecorder, ParquetRecorder};
+use woox::peer::{self, PeerSource, SnapshotRegistry};
+use woox::poll::{LatencyStats, PollMode};
+#[cfg(feature = "proto")]
+use woox::proto::ProtoRecorder;
+use woox::proxy::ProxyConfig;
+use woox::queue::{Backpressure, QueueConfig};
+use woox::publish::{BookMessage, PeriodicSnapshots};
+use woox::publish::ws_server::WsPublisher;
+use woox::reconstruct::Reconstructor;
+use woox::recorder::{FrameRecorder, RecordingSource, ReplayConfig, ReplaySource, ReplaySpeed};
+#[cfg(feature = "redis")]
+use woox::redis_sink::{RedisConfig, RedisSink};
+use woox::render::{Precision, RenderConfig, RenderStyle, Renderer};
+use woox::rest::RestClient;
+use woox::risk::{KillSwitch, RiskCheck, RiskLimits};
+use woox::snapshot::{FallbackSource, SnapshotSource, WooxRestSource};
+#[cfg(feature = "shm")]
+use woox::shm::ShmPublisher;
+use woox::session::SessionStats;
+use woox::simulator::SimConfig;
+use woox::sink::{Sink, SinkResult};
+use woox::slo::{LagSlo, MonitoredSink};
+#[cfg(feature = "tui")]
+use woox::symbol::SymbolMapper;
+use woox::strategy::{StrategyLoop, StrategyRunner};
+use woox::supervisor::Supervisor;
+use woox::tls::TlsConfig;
+use woox::trading::{ApiCredentials, TradingClient, WooxTradingClient};
+use woox::units::{Notional, Price, Qty};
+#[cfg(feature = "sqlite")]
+use woox::sqlite_sink::{self, SqliteSink};
 
-const WOOX_WS_URL: &str = "wss://wss.woox.io/v3/public";
-const REST_URL: &str = "https://api.woox.io/v3/public/orderbook";
-const CLIENT_ID: &str = "client_id_x";
 const SYMBOL: &str = "PERP_ETH_USDT";
 const MAX_LEVEL: usize = 50;
 
-const WOOX_SUBSCRIBE_CMD: &str = "SUBSCRIBE";
-const WOOX_PING_CMD: &str = "PING";
-const WOOX_PONG_CMD: &str = "PONG";
+// REDUNDANT_FEED opens a second websocket connection for the same symbol and arbitrates
+// between the two, so a hiccup on one connection doesn't leave a gap in the book.
+const REDUNDANT_FEED: bool = false;
+
+// DISPLAY_DEPTH is the number of levels printed on each side of the book.
+const DISPLAY_DEPTH: usize = 5;
+// MAX_RENDER_FPS caps how often the book is redrawn. None redraws on every delta.
+const MAX_RENDER_FPS: Option<u32> = Some(10);
+// RENDER_CATCH_UP_INTERVAL is how soon a quiet stream redraws the book if MAX_RENDER_FPS
+// held back the redraw for the last delta.
+const RENDER_CATCH_UP_INTERVAL: Duration = Duration::from_millis(50);
+// COALESCE_UPDATES is the most queued deltas applied before the book is rendered once for
+// them all, so a burst is caught up on rather than drawn delta by delta. 1 renders each.
+const COALESCE_UPDATES: usize = 64;
+// RENDER_ON_CHANGE_ONLY skips redraws when the displayed levels are unchanged.
+const RENDER_ON_CHANGE_ONLY: bool = false;
+// RENDER_STYLE selects between the plain level list and the colored price ladder.
+const RENDER_STYLE: RenderStyle = RenderStyle::Ladder;
+
+// CONFIG_PATH is a JSON file of the settings changed live as it is edited: the symbols
+// followed with --all and their depth, and the book output, see RuntimeConfig. It and
+// ALERTS_PATH are checked for changes every CONFIG_POLL_INTERVAL.
+const CONFIG_PATH: Option<&str> = None;
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// TAPE_ROWS is the number of recent trades printed below the book, 0 for none, and
+// LARGE_TRADE_SIZE the size from which they're flagged, on the tape and in the TUI.
+const TAPE_ROWS: usize = 10;
+const LARGE_TRADE_SIZE: Option<Qty> = Some(Qty::new(10.0));
+
+// CHECKPOINT_PATH is where the synced book is checkpointed, periodically and on shutdown.
+// When set, a checkpoint younger than CHECKPOINT_MAX_AGE is warm started from if the stream
+// continues from it, before falling back to a snapshot.
+const CHECKPOINT_PATH: Option<&str> = None;
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+const CHECKPOINT_MAX_AGE: Duration = Duration::from_secs(5);
+
+// FRAME_RECORD_PATH is where every raw websocket frame and fetched snapshot is recorded,
+// for replaying later with --replay <file> [speed].
+const FRAME_RECORD_PATH: Option<&str> = None;
+
+// COMPACT_RECORD_DIR is where the synced book is recorded in the compact binary format, a
+// <symbol>.wxcr file per symbol, for backtesting later with --backtest <file>. It isn't
+// paused with the other recorders, as a recording with a gap can't be replayed past it.
+const COMPACT_RECORD_DIR: Option<&str> = None;
+
+// PROTO_RECORD_DIR is where the synced book, its top and the public trades are recorded as
+// length delimited MarketEvent messages of proto/woox.proto, in a <symbol>.pb and a
+// <symbol>.trades.pb file per symbol.
+#[cfg(feature = "proto")]
+const PROTO_RECORD_DIR: Option<&str> = None;
+
+// RECONSTRUCT_DEPTH is how many levels a side --reconstruct samples over a time range.
+const RECONSTRUCT_DEPTH: usize = 5;
+
+// SNAPSHOT_SERVER_ADDR is where this instance serves its synced book to peer instances.
+const SNAPSHOT_SERVER_ADDR: Option<&str> = None;
+// PEER_SNAPSHOT_URL is a peer instance's snapshot server, tried before the Woo X REST API
+// so a fleet tracking the same symbol only needs one REST snapshot per resync.
+const PEER_SNAPSHOT_URL: Option<&str> = None;
+
+// POLL_MODE selects whether the reader and book threads block or busy poll for events.
+// Busy polling burns a core per thread for lower tail latency; the handoff latency shown
+// under the book can be compared between modes.
+const POLL_MODE: PollMode = PollMode::Blocking;
+// READER_CORE and BOOK_CORE pin the websocket reader threads and the book thread to those
+// cores, so busy polling threads don't share or move between cores. None leaves them to the
+// scheduler.
+const READER_CORE: Option<usize> = None;
+const BOOK_CORE: Option<usize> = None;
+// EVENT_QUEUE bounds the events queued for each book thread. Once a book falls that far
+// behind, queued deltas are coalesced into its latest state rather than the queue growing.
+const EVENT_QUEUE: QueueConfig = QueueConfig { capacity: 10_000, backpressure: Backpressure::Coalesce };
+
+// CSV_DIR is where applied deltas and top of book history are recorded as hourly CSV files.
+const CSV_DIR: Option<&str> = None;
+const CSV_RECORD_DELTAS: bool = true;
+// CSV_TOP_DEPTH is the number of levels in each top of book row group, 0 disables them.
+const CSV_TOP_DEPTH: usize = 5;
+const CSV_TOP_INTERVAL: Duration = Duration::from_secs(1);
+
+// PARQUET_DIR is where deltas, trades and snapshots are recorded as day partitioned
+// parquet files.
+#[cfg(feature = "parquet")]
+const PARQUET_DIR: Option<&str> = None;
+
+// HEATMAP_DIR is where samples of the full book are recorded as hourly CSV files, a row per
+// price bucket with resting quantity, for rendering liquidity heatmaps. With the parquet
+// feature, HEATMAP_PARQUET_DIR records them as day partitioned parquet files.
+const HEATMAP_DIR: Option<&str> = None;
+#[cfg(feature = "parquet")]
+const HEATMAP_PARQUET_DIR: Option<&str> = None;
+// HEATMAP is how often the book is sampled for the heatmap, and the prices kept of each sample.
+const HEATMAP: HeatmapConfig = HeatmapConfig {
+    interval: Duration::from_secs(1),
+    bucket_size: 0.1,
+    range: HeatmapRange::AroundMid { bps: 100.0 },
+};
+
+// TRADE_BACKFILL fetches the trades missed while the trades websocket reconnects from the
+// REST API, recording them flagged as backfilled.
+const TRADE_BACKFILL: bool = true;
+
+// BOOK_PUBLISH_ADDR is where the synced book is republished over a websocket, as a
+// snapshot on connect followed by incremental updates.
+const BOOK_PUBLISH_ADDR: Option<&str> = None;
+
+// GRPC_ADDR is where the synced book and trades are served over the MarketData gRPC service.
+#[cfg(feature = "grpc")]
+const GRPC_ADDR: Option<&str> = None;
+
+// REDIS_URL is the Redis server the top of book and periodic snapshots are published to.
+#[cfg(feature = "redis")]
+const REDIS_URL: Option<&str> = None;
+
+// KAFKA_BROKERS are the Kafka brokers snapshots, deltas and trades are produced to, on the
+// topics of KafkaConfig::new.
+#[cfg(feature = "kafka")]
+const KAFKA_BROKERS: Option<&str> = None;
+#[cfg(feature = "kafka")]
+const KAFKA_ENCODING: kafka_sink::Encoding = kafka_sink::Encoding::Json;
+
+// NATS_URL is the NATS server snapshots, deltas and trades are published to, on the
+// subjects of NatsConfig::new.
+#[cfg(feature = "nats")]
+const NATS_URL: Option<&str> = None;
+
+// PUBLISH_SNAPSHOT_INTERVAL is how often the book publishers above are also given the full
+// book, as a baseline for consumers that join mid-stream.
+const PUBLISH_SNAPSHOT_INTERVAL: Option<Duration> = Some(Duration::from_secs(30));
+
+// SHM_DIR is where a shared memory ring of top of book updates is created for each
+// symbol, as woox-<symbol>.ring, for same-host consumers to read with shm::ShmReader.
+#[cfg(feature = "shm")]
+const SHM_DIR: Option<&str> = None;
+#[cfg(feature = "shm")]
+const SHM_CAPACITY: usize = 4096;
+
+// METRICS_ADDR is where Prometheus metrics of the feed and books are served, at /metrics.
+const METRICS_ADDR: Option<&str> = None;
+
+// CONTROL_ADDR is where the control API is served, to change what is followed, recorded and
+// printed without a restart, see control::serve.
+const CONTROL_ADDR: Option<&str> = None;
+
+// HEALTH_ADDR is where /healthz and /status are served for liveness probes. The feed is
+// unhealthy once a book goes HEALTH_MAX_UPDATE_AGE without changing.
+const HEALTH_ADDR: Option<&str> = None;
+const HEALTH_MAX_UPDATE_AGE: Duration = Duration::from_secs(30);
+
+// HTTP_USER_AGENT, HTTP_CONNECT_TIMEOUT and HTTP_TIMEOUT configure the client shared by every
+// REST request, which keeps its connections alive so resyncs don't pay a new TLS handshake.
+// Retried requests are bounded by their RetryPolicy's timeout instead of HTTP_TIMEOUT.
+const HTTP_USER_AGENT: &str = http_client::USER_AGENT;
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+// PROXY_PATH is a JSON file of the proxy REST requests and websockets go through, see
+// ProxyConfig. Without it, the HTTPS_PROXY, ALL_PROXY and NO_PROXY environment variables are
+// used.
+const PROXY_PATH: Option<&str> = None;
+
+// TLS_CA_PATH is a PEM bundle of root certificates trusted in addition to the system's, and
+// TLS_PINNED_SHA256 the SHA-256 fingerprints of the only certificates the exchange may
+// present, for websockets and REST requests alike, see TlsConfig.
+const TLS_CA_PATH: Option<&str> = None;
+const TLS_PINNED_SHA256: &[&str] = &[];
+
+// CHAOS are the faults injected into the feed with --chaos <seed>, for checking the books
+// recover from dropped, delayed, duplicated and reordered messages and killed connections.
+// The same seed injects the same faults into the same messages.
+const CHAOS: ChaosConfig = ChaosConfig {
+    seed: 0,
+    drop: 0.001,
+    delay: 0.01,
+    max_delay: Duration::from_millis(200),
+    duplicate: 0.001,
+    reorder: 0.001,
+    kill: 0.0001,
+};
+
+// LOG_LEVEL is the default log filter, overridden by the RUST_LOG environment variable,
+// e.g. RUST_LOG=woox=debug. Delta applies are logged at trace.
+const LOG_LEVEL: &str = "info";
+// LOG_JSON writes logs as JSON lines instead of text, for running non-interactively.
+const LOG_JSON: bool = false;
+
+// LATENCY_SUMMARY_INTERVAL is how often a summary of the feed, processing and end to end
+// latency quantiles is logged.
+const LATENCY_SUMMARY_INTERVAL: Option<Duration> = Some(Duration::from_secs(60));
+
+// CLOCK_SKEW_INTERVAL is how often the exchange's server time is sampled to estimate the
+// clock skew the latency metrics are corrected by.
+const CLOCK_SKEW_INTERVAL: Option<Duration> = Some(Duration::from_secs(30));
+
+// STALE_AFTER is how long the book may go without a delta before it's reported stale.
+const STALE_AFTER: Option<Duration> = Some(Duration::from_secs(5));
+
+// CHECK_INVARIANTS checks the book after every delta, stopping and logging the delta's raw
+// message and the book if it is crossed, has an empty level, has more than this many levels
+// a side or goes back in time. It walks the whole book on every delta, so is for debugging.
+const CHECK_INVARIANTS: Option<usize> = None;
+
+// ALERTS_PATH is a JSON file of alert rules evaluated against every book followed and the
+// webhook, Slack and Telegram notifiers they page, see AlertConfig. Stale rules trigger after
+// STALE_AFTER. Changes to the rules are applied live.
+const ALERTS_PATH: Option<&str> = None;
+
+// ANALYTICS_INTERVAL is how often the book analytics of SYMBOL are logged while following
+// it, None for never, with the mid's realized volatility over VOLATILITY_WINDOWS sampled
+// every VOLATILITY_SAMPLE_INTERVAL.
+const ANALYTICS_INTERVAL: Option<Duration> = Some(Duration::from_secs(60));
+const VOLATILITY_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const VOLATILITY_WINDOWS: [Duration; 3] = [Duration::from_secs(60), Duration::from_secs(300), Duration::from_secs(3600)];
+
+// SESSION_REPORT_INTERVAL is how often the session statistics are logged while following
+// a book. A summary is printed on shutdown regardless, and written as JSON to
+// SESSION_SUMMARY_PATH if set.
+const SESSION_REPORT_INTERVAL: Option<Duration> = Some(Duration::from_secs(300));
+const SESSION_SUMMARY_PATH: Option<&str> = None;
+
+// SINK_LAG_SLO is the delivery lag objective every sink is monitored against, alerts are
+// printed when a sink persistently misses it.
+const SINK_LAG_SLO: Option<LagSlo> = Some(LagSlo {
+    threshold: Duration::from_millis(500),
+    max_violation_ratio: 0.01,
+    window: Duration::from_secs(10),
+    windows_to_alert: 3,
+});
+
+// MANAGER_POLL_INTERVAL is how often the books followed with --all are polled, and
+// MANAGER_STATUS_INTERVAL how often their sync status is printed.
+const MANAGER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const MANAGER_STATUS_INTERVAL: Duration = Duration::from_secs(5);
+// LIFECYCLE_REFRESH_INTERVAL is how often the instruments list is checked for delistings,
+// suspensions and new listings matching --all, or LIFECYCLE_RETRY_INTERVAL while a feed
+// has failed.
+const LIFECYCLE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const LIFECYCLE_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+// ARB_CONFIG is the size the --arb spread is executable for and the net spread it alerts
+// at.
+const ARB_CONFIG: ArbConfig = ArbConfig { size: Qty::new(1.0), threshold_bps: 5.0 };
+
+// FEES are the fee rates assumed for --arb and --mm when the account's own can't be fetched
+// with the WOOX_API_KEY and WOOX_API_SECRET credentials.
+const FEES: FeeSchedule = FeeSchedule::new(2.0, 5.0);
+
+// BASIS_FUNDING_INTERVAL is how often the perpetual followed with --basis charges funding,
+// and BASIS_HORIZON how long carry is computed over.
+const BASIS_FUNDING_INTERVAL: Duration = Duration::from_secs(8 * 60 * 60);
+const BASIS_HORIZON: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+// MM_CONFIG is how the --mm reference market maker quotes, paper trading on the simulator.
+const MM_CONFIG: MarketMakerConfig = MarketMakerConfig {
+    quote_size: Qty::new(0.1),
+    half_spread_bps: 2.0,
+    skew_bps: 4.0,
+    max_position: Qty::new(1.0),
+    requote_bps: 0.5,
+    tick_size: 0.01,
+};
+// MM_RISK_LIMITS are the pre-trade limits the --mm market maker's orders are checked against.
+const MM_RISK_LIMITS: RiskLimits = RiskLimits {
+    max_order_size: Some(Qty::new(0.5)),
+    max_order_notional: Some(Notional::new(5_000.0)),
+    max_position: Some(Qty::new(1.5)),
+    price_band_bps: Some(50.0),
+};
+
+// SQLITE_PATH is the SQLite database snapshots, deltas and trades are stored in.
+#[cfg(feature = "sqlite")]
+const SQLITE_PATH: Option<&str> = None;
+
+// symbol_precision returns the precision to format symbol's prices and quantities with,
+// from its instrument tick and lot sizes, or the default if they can't be fetched.
+fn symbol_precision(symbol: &str) -> Precision {
+    match Precision::fetch(&rest_client(), symbol) {
+        Ok(precision) => precision,
+        Err(e) => {
+            warn!(%symbol, error = %e, "Failed to fetch instrument metadata, using the default precision");
+            Precision::default()
+        }
+    }
+}
+
+// sinks returns the enabled sinks the applied stream for symbol is written to.
+fn sinks(symbol: &str, precision: Precision) -> Vec<Box<dyn Sink>> {
+    let mut sinks = recorders(symbol, precision);
+    sinks.extend(stream_sinks(symbol));
+    monitored(sinks)
+}
+
+// recorders returns the enabled recorders the applied stream for symbol is written to.
+fn recorders(symbol: &str, precision: Precision) -> Vec<Box<dyn Sink>> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    if let Some(dir) = COMPACT_RECORD_DIR {
+        let path = Path::new(dir).join(format!("{}.wxcr", symbol));
+        sinks.push(Box::new(CompactRecorder::create(path, symbol, precision).expect("Failed to create compact recording")));
+    }
+    if let Some(dir) = CSV_DIR {
+        let csv = CsvRecorder::new(Path::new(dir), symbol, CSV_RECORD_DELTAS, CSV_TOP_DEPTH, CSV_TOP_INTERVAL)
+            .expect("Failed to create CSV recorder")
+            .with_precision(precision);
+        sinks.push(Box::new(recording().gate(Box::new(csv))));
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(dir) = PARQUET_DIR {
+        let parquet = ParquetRecorder::new(Path::new(dir), symbol, parquet_recorder::DEFAULT_BATCH_SIZE);
+        sinks.push(Box::new(recording().gate(Box::new(parquet))));
+    }
+    #[cfg(feature = "proto")]
+    if let Some(dir) = PROTO_RECORD_DIR {
+        let path = Path::new(dir).join(format!("{}.pb", symbol));
+        let proto = ProtoRecorder::create(path).expect("Failed to create protobuf recording").with_tickers();
+        sinks.push(Box::new(recording().gate(Box::new(proto))));
+    }
+    if let Some(dir) = HEATMAP_DIR {
+        let heatmap = HeatmapRecorder::new(Path::new(dir), symbol, HEATMAP).expect("Failed to create heatmap recorder");
+        sinks.push(Box::new(recording().gate(Box::new(heatmap))));
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(dir) = HEATMAP_PARQUET_DIR {
+        let heatmap = ParquetHeatmapRecorder::new(Path::new(dir), symbol, HEATMAP, parquet_recorder::DEFAULT_BATCH_SIZE);
+        sinks.push(Box::new(recording().gate(Box::new(heatmap))));
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = SQLITE_PATH {
+        let sqlite = SqliteSink::open(Path::new(path), sqlite_sink::DEFAULT_BATCH_SIZE)
+            .expect("Failed to open SQLite database");
+        sinks.push(Box::new(recording().gate(Box::new(sqlite))));
+    }
+    sinks
+}
+
+// stream_sinks returns the enabled sinks other than the recorders the applied stream for
+// symbol is written to.
+#[cfg_attr(not(feature = "shm"), allow(unused_variables))]
+fn stream_sinks(symbol: &str) -> Vec<Box<dyn Sink>> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    if let Some(path) = ALERTS_PATH {
+        let config = AlertConfig::load(Path::new(path)).expect("Failed to load alert rules");
+        let (notifiers, updates) = (config.notifiers(), alert_rule_updates(path, &config));
+        sinks.push(Box::new(AlertSink::new(config.rules, notifiers).with_updates(updates)));
+    }
+    #[cfg(feature = "shm")]
+    if let Some(dir) = SHM_DIR {
+        let path = Path::new(dir).join(format!("woox-{}.ring", symbol));
+        sinks.push(Box::new(ShmPublisher::create(&path, symbol, SHM_CAPACITY).expect("Failed to create shared memory ring")));
+    }
+    #[cfg(feature = "redis")]
+    if let Some(url) = REDIS_URL {
+        let redis = RedisSink::connect(url, RedisConfig::default()).expect("Failed to connect to Redis");
+        sinks.push(Box::new(redis));
+    }
+    #[cfg(feature = "kafka")]
+    if let Some(brokers) = KAFKA_BROKERS {
+        sinks.push(published(Box::new(kafka_sink(brokers))));
+    }
+    #[cfg(feature = "nats")]
+    if let Some(url) = NATS_URL {
+        sinks.push(published(Box::new(nats_sink(url))));
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(publisher) = grpc_publisher() {
+        sinks.push(published(Box::new(publisher)));
+    }
+    if let Some(metrics) = metrics() {
+        sinks.push(Box::new(metrics.sink()));
+    }
+    if let Some(health) = health() {
+        sinks.push(Box::new(health.sink()));
+    }
+    if let Some(interval) = LATENCY_SUMMARY_INTERVAL {
+        let reporter = LatencyReporter::new(interval);
+        sinks.push(Box::new(match clock() {
+            Some(clock) => reporter.with_clock(clock),
+            None => reporter,
+        }));
+    }
+    if let Some(addr) = BOOK_PUBLISH_ADDR {
+        let publisher = WsPublisher::new();
+        publisher.serve(addr).expect("Failed to start the book publish server");
+        sinks.push(published(Box::new(publisher)));
+    }
+    sinks
+}
+
+// published returns publisher, given the full book every PUBLISH_SNAPSHOT_INTERVAL if set.
+fn published(publisher: Box<dyn Sink>) -> Box<dyn Sink> {
+    match PUBLISH_SNAPSHOT_INTERVAL {
+        Some(interval) => Box::new(PeriodicSnapshots::new(publisher, interval)),
+        None => publisher,
+    }
+}
+
+// metrics returns the metrics served on METRICS_ADDR, starting the server the first time.
+fn metrics() -> Option<Arc<Metrics>> {
+    static METRICS: std::sync::OnceLock<Option<Arc<Metrics>>> = std::sync::OnceLock::new();
+    METRICS
+        .get_or_init(|| {
+            let addr = METRICS_ADDR?;
+            let metrics = match clock() {
+                Some(clock) => Metrics::with_clock(clock),
+                None => Metrics::new(),
+            };
+            metrics.serve(addr).expect("Failed to start the metrics server");
+            info!(%addr, "Serving metrics");
+            Some(metrics)
+        })
+        .clone()
+}
+
+// alert_rule_updates returns the alert rules reloaded from path as it changes, starting to
+// watch it the first time. config is the alert config the process started with; a reload
+// that changes its notifiers is rejected.
+fn alert_rule_updates(path: &'static str, config: &AlertConfig) -> Receiver<Vec<AlertRule>> {
+    type Subscribers = Arc<Mutex<Vec<Sender<Vec<AlertRule>>>>>;
+    static SUBSCRIBERS: std::sync::OnceLock<Subscribers> = std::sync::OnceLock::new();
+    let subscribers = SUBSCRIBERS.get_or_init(|| {
+        let subscribers: Subscribers = Arc::default();
+        let (watching, mut current) = (Arc::clone(&subscribers), config.clone());
+        thread::spawn(move || {
+            let mut file = FileWatcher::new(path);
+            loop {
+                thread::sleep(CONFIG_POLL_INTERVAL);
+                if !file.changed() {
+                    continue;
+                }
+                let reloaded = AlertConfig::load(Path::new(path)).map_err(|e| e.to_string()).and_then(|config| {
+                    current.check_reload(&config).map_err(|reason| format!("{}, restart to apply it", reason))?;
+                    Ok(config)
+                });
+                match reloaded {
+                    Ok(config) => {
+                        info!(%path, rules = config.rules.len(), "Reloaded alert rules");
+                        watching.lock().unwrap().retain(|subscriber| subscriber.send(config.rules.clone()).is_ok());
+                        current = config;
+                    }
+                    Err(e) => warn!(%path, error = %e, "Keeping the current alert rules, rejected the change"),
+                }
+            }
+        });
+        subscribers
+    });
+    let (updates, rx) = mpsc::channel();
+    subscribers.lock().unwrap().push(updates);
+    rx
+}
+
+// runtime_config loads CONFIG_PATH if one is configured, to be polled for changes.
+fn runtime_config() -> Option<ConfigWatcher> {
+    let path = CONFIG_PATH?;
+    let config = ConfigWatcher::load(path).expect("Failed to load the runtime config");
+    info!(%path, "Watching the runtime config");
+    Some(config)
+}
+
+// apply_output prints the book as output configures, with the defaults for what it doesn't.
+fn apply_output(renderer: &mut Renderer, output: &OutputConfig) {
+    renderer.set_max_fps(output.max_fps.or(MAX_RENDER_FPS));
+    renderer.set_depth(output.depth.unwrap_or(DISPLAY_DEPTH));
+    renderer.set_style(output.style.unwrap_or(RENDER_STYLE));
+}
+
+// log_config_change logs the outcome of polling a runtime config for changes.
+fn log_config_change(config: &ConfigWatcher, result: Result<bool, ConfigError>) {
+    match result {
+        Ok(true) => info!(path = %config.path().display(), "Applied runtime config change"),
+        Ok(false) => {}
+        Err(e) => warn!(path = %config.path().display(), error = %e, "Keeping the current runtime config"),
+    }
+}
+
+// recording returns the switch the recorders are gated by, turned on and off through the
+// control API.
+fn recording() -> &'static RecordingSwitch {
+    static RECORDING: std::sync::OnceLock<RecordingSwitch> = std::sync::OnceLock::new();
+    RECORDING.get_or_init(RecordingSwitch::default)
+}
+
+// control_requests starts the control API if one is configured, returning its requests.
+fn control_requests() -> Option<Receiver<ControlRequest>> {
+    let addr = CONTROL_ADDR?;
+    let (_, requests) = control::serve(addr).expect("Failed to start the control server");
+    info!(%addr, "Serving the control API");
+    Some(requests)
+}
+
+// set_recording turns the recorders on or off, for the control API.
+fn set_recording(enabled: bool) -> ControlReply {
+    recording().set(enabled);
+    info!(enabled, "Recording toggled");
+    Ok(format!("recording {}", if enabled { "on" } else { "off" }))
+}
+
+// health returns the health served on HEALTH_ADDR, starting the server the first time.
+fn health() -> Option<Arc<Health>> {
+    static HEALTH: std::sync::OnceLock<Option<Arc<Health>>> = std::sync::OnceLock::new();
+    HEALTH
+        .get_or_init(|| {
+            let addr = HEALTH_ADDR?;
+            let health = Health::new(feed_metrics(), HEALTH_MAX_UPDATE_AGE);
+            health.serve(addr).expect("Failed to start the health server");
+            info!(%addr, "Serving health checks");
+            Some(health)
+        })
+        .clone()
+}
+
+// feed_metrics returns the feed counters, shared by the metrics server and the session
+// statistics.
+fn feed_metrics() -> Arc<FeedMetrics> {
+    static FEED: std::sync::OnceLock<Arc<FeedMetrics>> = std::sync::OnceLock::new();
+    FEED.get_or_init(|| metrics().map_or_else(Default::default, |metrics| Arc::clone(&metrics.feed))).clone()
+}
+
+// fees returns the account's fee rates, fetched once with the credentials in the environment,
+// or FEES without them or if the fetch fails.
+fn fees() -> FeeSchedule {
+    static FEE_SCHEDULE: std::sync::OnceLock<FeeSchedule> = std::sync::OnceLock::new();
+    *FEE_SCHEDULE.get_or_init(|| {
+        let Some(credentials) = ApiCredentials::from_env() else { return FEES };
+        match WooxTradingClient::new(environment().rest_url(), credentials).fee_schedule() {
+            Ok(fees) => {
+                info!(maker_bps = fees.maker_bps, taker_bps = fees.taker_bps, "Fetched account fee rates");
+                fees
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to fetch account fee rates, using defaults");
+                FEES
+            }
+        }
+    })
+}
+
+// clock returns the clock skew estimate, refreshed every CLOCK_SKEW_INTERVAL from the
+// first call.
+fn clock() -> Option<Arc<ClockSkew>> {
+    static CLOCK: std::sync::OnceLock<Option<Arc<ClockSkew>>> = std::sync::OnceLock::new();
+    CLOCK
+        .get_or_init(|| {
+            let interval = CLOCK_SKEW_INTERVAL?;
+            let clock = ClockSkew::new();
+            clock.spawn_refresh(rest_client(), interval);
+            Some(clock)
+        })
+        .clone()
+}
+
+// grpc_publisher returns the gRPC publisher serving on GRPC_ADDR, starting it the first
+// time, so the book and trade sinks share one server.
+#[cfg(feature = "grpc")]
+fn grpc_publisher() -> Option<GrpcPublisher> {
+    static PUBLISHER: std::sync::OnceLock<Option<GrpcPublisher>> = std::sync::OnceLock::new();
+    PUBLISHER
+        .get_or_init(|| {
+            let addr = GRPC_ADDR?;
+            let publisher = GrpcPublisher::new();
+            publisher.serve(addr).expect("Failed to start the gRPC server");
+            Some(publisher)
+        })
+        .clone()
+}
+
+// kafka_sink returns a sink on the producer to the Kafka brokers, creating it the first
+// time, so the book and trade sinks of every symbol share one producer.
+#[cfg(feature = "kafka")]
+fn kafka_sink(brokers: &str) -> KafkaSink {
+    static SINK: std::sync::OnceLock<KafkaSink> = std::sync::OnceLock::new();
+    SINK.get_or_init(|| {
+        let config = KafkaConfig { encoding: KAFKA_ENCODING, ..KafkaConfig::new(brokers) };
+        KafkaSink::new(config).expect("Failed to create Kafka producer")
+    })
+    .clone()
+}
+
+// nats_sink returns a sink on the connection to the NATS server at url, connecting the
+// first time, so the book and trade sinks of every symbol share one connection.
+#[cfg(feature = "nats")]
+fn nats_sink(url: &str) -> NatsSink {
+    static SINK: std::sync::OnceLock<NatsSink> = std::sync::OnceLock::new();
+    SINK.get_or_init(|| NatsSink::connect(NatsConfig::new(url)).expect("Failed to connect to NATS")).clone()
+}
+
+// monitored wraps every sink to be monitored against SINK_LAG_SLO, if set.
+fn monitored(sinks: Vec<Box<dyn Sink>>) -> Vec<Box<dyn Sink>> {
+    let Some(slo) = SINK_LAG_SLO else { return sinks };
+    sinks
+        .into_iter()
+        .map(|sink| Box::new(MonitoredSink::new(sink, slo, |alert| warn!("{}", alert.summary()))) as Box<dyn Sink>)
+        .collect()
+}
+
+// write_sinks passes a record to every sink, logging the ones that fail.
+fn write_sinks<F>(sinks: &mut [Box<dyn Sink>], mut write: F)
+where
+    F: FnMut(&mut dyn Sink) -> SinkResult,
+{
+    for sink in sinks {
+        if let Err(e) = write(sink.as_mut()) {
+            warn!(sink = sink.name(), error = %e, "Sink failed");
+        }
+    }
+}
+
+// follow_book follows the order book for SYMBOL, printing it after updates, throttled by
+// the render config, with the trades from tape below it.
+fn follow_book(config: FeedConfig, source: Box<dyn SnapshotSource>, session: &SessionStats, tape: Option<Receiver<WsTrade>>) {
+    let precision = symbol_precision(SYMBOL);
+    let mut renderer = Renderer::new(RenderConfig {
+        depth: DISPLAY_DEPTH,
+        max_fps: MAX_RENDER_FPS,
+        on_change_only: RENDER_ON_CHANGE_ONLY,
+        style: RENDER_STYLE,
+        precision,
+        tape_rows: if tape.is_some() { TAPE_ROWS } else { 0 },
+        large_trade_size: LARGE_TRADE_SIZE,
+    });
+    let mut analyzer = ANALYTICS_INTERVAL.map(|interval| {
+        let volatility = RealizedVolatility::new(VOLATILITY_SAMPLE_INTERVAL, &VOLATILITY_WINDOWS);
+        BookAnalyzer::new(SYMBOL, DISPLAY_DEPTH, interval, volatility)
+    });
+    let control = control_requests();
+    let mut runtime = runtime_config();
+    if let Some(runtime) = &runtime {
+        apply_output(&mut renderer, &runtime.current().output);
+        if !runtime.current().symbols.is_empty() || runtime.current().max_level.is_some() {
+            warn!("The symbols and max_level of the runtime config only apply with --all");
+        }
+    }
+    let mut last_config_check = Instant::now();
+    let display = Arc::new(Mutex::new(BookDisplay { renderer, handoff: LatencyStats::default(), paused: false }));
+    let idle_display = Arc::clone(&display);
+
+    let mut builder = WooxClient::builder()
+        .symbol(SYMBOL)
+        .depth(MAX_LEVEL)
+        .feed_config(config)
+        .redundant(REDUNDANT_FEED)
+        .coalesce(COALESCE_UPDATES)
+        .pin(READER_CORE, BOOK_CORE)
+        .snapshot_source(source)
+        .on_update(move |update| {
+            let mut display = display.lock().unwrap();
+            let BookDisplay { renderer, handoff, paused } = &mut *display;
+            handoff.record(update.event.received_at.elapsed());
+            for trade in tape.iter().flat_map(Receiver::try_iter) {
+                renderer.on_trade(trade);
+            }
+            if let Some(analytics) = analyzer.as_mut().and_then(|analyzer| analyzer.on_book(update.book)) {
+                log_analytics(&analytics);
+            }
+            for request in control.iter().flat_map(Receiver::try_iter) {
+                let reply = match request.command {
+                    ControlCommand::SetPaused(pause) => {
+                        *paused = pause;
+                        Ok(format!("output {}", if pause { "paused" } else { "resumed" }))
+                    }
+                    ControlCommand::SetOutput { style, depth } => {
+                        style.into_iter().for_each(|style| renderer.set_style(style));
+                        depth.into_iter().for_each(|depth| renderer.set_depth(depth));
+                        Ok("output changed".to_string())
+                    }
+                    ControlCommand::SetRecording(enabled) => set_recording(enabled),
+                    ControlCommand::Symbols => Ok(SYMBOL.to_string()),
+                    _ => Err("only supported when following several books with --all".to_string()),
+                };
+                request.reply(reply);
+            }
+            if let Some(runtime) = runtime.as_mut().filter(|_| last_config_check.elapsed() >= CONFIG_POLL_INTERVAL) {
+                last_config_check = Instant::now();
+                let result = runtime.poll(|change| {
+                    if change.symbols_changed() {
+                        return Err("symbols and max_level only change live with --all".to_string());
+                    }
+                    apply_output(renderer, &change.config.output);
+                    Ok(())
+                });
+                log_config_change(runtime, result);
+            }
+            if !*paused && renderer.render(update.book) {
+                print_diagnostics(handoff, update.arbitration);
+            }
+        })
+        .on_idle(RENDER_CATCH_UP_INTERVAL, move |book, arbitration| {
+            let mut display = idle_display.lock().unwrap();
+            if !display.paused && display.renderer.render_pending(book) {
+                print_diagnostics(&display.handoff, arbitration);
+            }
+        })
+        .sink(Box::new(session.sink(SESSION_REPORT_INTERVAL)));
+    for sink in sinks(SYMBOL, precision) {
+        builder = builder.sink(sink);
+    }
+    if let Some(registry) = snapshot_registry() {
+        builder = builder.registry(registry);
+    }
+    if let Some(path) = CHECKPOINT_PATH {
+        builder = builder.checkpoint(path, CHECKPOINT_INTERVAL).warm_start(path, CHECKPOINT_MAX_AGE);
+    }
+    if let Some(threshold) = STALE_AFTER {
+        builder = builder.stale_after(threshold);
+    }
+    if let Some(max_levels) = CHECK_INVARIANTS {
+        builder = builder.check_invariants(max_levels);
+    }
 
+    if let Err(e) = builder.build().run() {
+        error!(symbol = SYMBOL, error = %e, "Stopped following the book");
+    }
+    finish_session(session);
+}
 
-// MarketEvent represents an order book delta provided by the Woo X exchange.
-struct MarketEvent {
-    prev_ts: u64,
-    delta: OrderBookDelta,
+// log_analytics logs book analytics, with the annualized volatility over each window.
+fn log_analytics(analytics: &BookAnalytics) {
+    let volatility: Vec<String> = analytics
+        .volatility
+        .iter()
+        .map(|vol| format!("{}s: {:.1}%", vol.window_ms / 1000, vol.annualized * 100.0))
+        .collect();
+    info!(
+        symbol = %analytics.symbol,
+        mid = ?analytics.mid.map(|mid| mid.value()),
+        spread_bps = ?analytics.spread_bps,
+        imbalance = ?analytics.imbalance,
+        volatility = %volatility.join(", "),
+        "Book analytics"
+    );
 }
 
-// read_exchange_events reads delta updates from the WebSocket and sends evnts over the Sender
-fn read_exchange_events(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, tx: Sender<MarketEvent>) {
+// start_session starts the session statistics for SYMBOL, finishing the session and
+// checkpointing the book if the process is interrupted.
+fn start_session() -> SessionStats {
+    let session = SessionStats::new(SYMBOL).with_feed(feed_metrics());
+    let interrupted = session.clone();
+    let handler = ctrlc::set_handler(move || {
+        finish_session(&interrupted);
+        checkpoint_on_exit();
+        std::process::exit(130);
+    });
+    if let Err(e) = handler {
+        warn!(error = %e, "Failed to install the interrupt handler");
+    }
+    session
+}
+
+// finish_session prints the session summary, writing it to SESSION_SUMMARY_PATH if set.
+fn finish_session(session: &SessionStats) {
+    let summary = session.summary();
+    println!("{}", summary);
+    if let Some(path) = SESSION_SUMMARY_PATH {
+        let written = serde_json::to_vec_pretty(&summary).map_err(std::io::Error::from).and_then(|json| std::fs::write(path, json));
+        if let Err(e) = written {
+            warn!(%path, error = %e, "Failed to write the session summary");
+        }
+    }
+}
+
+// checkpoint_on_exit saves the last synced book to CHECKPOINT_PATH, if it is set and the
+// book has synced, for the next run to warm start from.
+fn checkpoint_on_exit() {
+    let (Some(path), Some(registry)) = (CHECKPOINT_PATH, snapshot_registry()) else { return };
+    match registry.save_checkpoint(SYMBOL, Path::new(path)) {
+        Ok(true) => info!(%path, "Checkpointed the book"),
+        Ok(false) => {}
+        Err(e) => warn!(%path, error = %e, "Failed to checkpoint the book"),
+    }
+}
+
+// reconstruct_book prints the book for SYMBOL in a recording as of ts, in ms since the
+// epoch, as a publish protocol snapshot.
+fn reconstruct_book(path: &str, ts: u64) {
+    let reconstructed = Reconstructor::open(Path::new(path), SYMBOL, MAX_LEVEL, FeedConfig::default().exchange)
+        .and_then(|mut reconstructor| Ok(reconstructor.book_at(ts)?.map(|book| BookMessage::snapshot(SYMBOL, ts, book))));
+    match reconstructed {
+        Ok(Some(message)) => println!("{}", serde_json::to_string(&message).expect("Failed to serialize the book")),
+        Ok(None) => println!("The recording has no synced {} book at {}", SYMBOL, ts),
+        Err(e) => error!(%path, error = %e, "Failed to reconstruct the book"),
+    }
+}
+
+// reconstruct_series prints the top RECONSTRUCT_DEPTH levels of the book for SYMBOL in a
+// recording every interval from from to to as CSV, a row per sample.
+fn reconstruct_series(path: &str, from: u64, to: u64, interval: Duration) {
+    let samples = Reconstructor::open(Path::new(path), SYMBOL, MAX_LEVEL, FeedConfig::default().exchange)
+        .and_then(|mut reconstructor| reconstructor.series(from, to, interval, RECONSTRUCT_DEPTH));
+    let samples = match samples {
+        Ok(samples) => samples,
+        Err(e) => return error!(%path, error = %e, "Failed to reconstruct the book"),
+    };
+    let precision = symbol_precision(SYMBOL);
+    let columns = |side: &str| (1..=RECONSTRUCT_DEPTH).map(|level| format!("{side}_price_{level},{side}_qty_{level}")).collect::<Vec<_>>();
+    println!("ts,{},{}", columns("bid").join(","), columns("ask").join(","));
+    let levels = |levels: &[(Price, Qty)]| {
+        (0..RECONSTRUCT_DEPTH)
+            .map(|level| match levels.get(level) {
+                Some(&(price, quantity)) => format!("{},{}", precision.format_price(price), precision.format_quantity(quantity)),
+                None => ",".to_string(),
+            })
+            .collect::<Vec<_>>()
+    };
+    for sample in samples {
+        println!("{},{},{}", sample.ts, levels(&sample.bids).join(","), levels(&sample.asks).join(","));
+    }
+}
+
+// replay_config parses the --replay arguments: a recording and an optional speed, either
+// a factor such as 10x or max.
+fn replay_config(args: &[String]) -> Option<ReplayConfig> {
+    let path = args.first()?;
+    let speed = match args.get(1).map(String::as_str) {
+        None => ReplaySpeed::Original,
+        Some("max") => ReplaySpeed::Max,
+        Some(factor) => ReplaySpeed::Accelerated(factor.trim_end_matches('x').parse().ok()?),
+    };
+    Some(ReplayConfig { path: path.into(), speed })
+}
+
+// replay_book follows SYMBOL from a frame recording instead of the live feed, resyncing from
+// the recorded snapshots.
+fn replay_book(replay: ReplayConfig) {
+    let source = Box::new(ReplaySource::new(replay.path.clone()));
+    let config = FeedConfig {
+        backend: SocketBackend::Replay(replay),
+        poll_mode: POLL_MODE,
+        metrics: Some(feed_metrics()),
+        ..FeedConfig::default()
+    };
+    follow_book(config, source, &start_session(), None);
+}
+
+// symbol_filter parses the --symbols and --all arguments: an optional symbol pattern such
+// as PERP_* and an optional quote currency.
+fn symbol_filter(args: &[String]) -> SymbolFilter {
+    SymbolFilter {
+        pattern: args.first().cloned(),
+        quote: args.get(1).cloned(),
+        ..SymbolFilter::default()
+    }
+}
+
+// list_symbols prints the trading symbols matching filter.
+fn list_symbols(filter: &SymbolFilter) {
+    match manager::discover_symbols(&rest_client(), filter) {
+        Ok(symbols) => symbols.iter().for_each(|symbol| println!("{}", symbol)),
+        Err(e) => error!(error = %e, "Failed to list symbols"),
+    }
+}
+
+// follow_matching follows a book for every trading symbol matching filter, printing how
+// many are synced, and follows listings and delistings as they happen.
+fn follow_matching(filter: &SymbolFilter) {
+    let rest = rest_client();
+    let mut manager = BookManager::new(feed_config(None), Arc::from(snapshot_source(None)));
+    match manager.subscribe_matching(&rest, filter, MAX_LEVEL) {
+        Ok(symbols) if symbols.is_empty() => info!("No symbols match yet"),
+        Ok(symbols) => info!(symbols = symbols.len(), "Following symbols"),
+        Err(e) => return error!(error = %e, "Failed to list symbols"),
+    }
+    manager.auto_subscribe(filter.clone(), MAX_LEVEL);
+    let control = control_requests();
+    let mut runtime = runtime_config();
+    if let Some(runtime) = &runtime {
+        let max_level = runtime.current().max_level.unwrap_or(MAX_LEVEL);
+        runtime.current().symbols.iter().for_each(|symbol| manager.subscribe(symbol, max_level));
+    }
+    let mut last_config_check = Instant::now();
+
+    let mut last_status = Instant::now();
+    let mut last_refresh: Option<Instant> = None;
+    loop {
+        let refresh_interval = if manager.has_failed_feeds() { LIFECYCLE_RETRY_INTERVAL } else { LIFECYCLE_REFRESH_INTERVAL };
+        if last_refresh.is_none_or(|last| last.elapsed() >= refresh_interval) {
+            last_refresh = Some(Instant::now());
+            match manager.refresh(&rest) {
+                Ok(events) => events.iter().for_each(|event| info!(?event, "Instrument lifecycle")),
+                Err(e) => warn!(error = %e, "Failed to refresh instruments"),
+            }
+        }
+        for request in control.iter().flat_map(Receiver::try_iter) {
+            let reply = control_manager(&mut manager, &request.command);
+            request.reply(reply);
+        }
+        if let Some(runtime) = runtime.as_mut().filter(|_| last_config_check.elapsed() >= CONFIG_POLL_INTERVAL) {
+            last_config_check = Instant::now();
+            let result = runtime.poll(|change| {
+                for symbol in change.removed_symbols() {
+                    info!(%symbol, "Unsubscribed by the runtime config");
+                    manager.unsubscribe(symbol);
+                }
+                // Subscribing to a followed symbol changes its depth, so every symbol of the
+                // config is subscribed again in case max_level changed.
+                let max_level = change.config.max_level.unwrap_or(MAX_LEVEL);
+                for symbol in &change.config.symbols {
+                    manager.subscribe(symbol, max_level);
+                }
+                Ok(())
+            });
+            log_config_change(runtime, result);
+        }
+        for (symbol, result) in manager.poll() {
+            if let Err(e) = result {
+                warn!(%symbol, error = %e, "Book poll failed");
+            }
+        }
+        if last_status.elapsed() >= MANAGER_STATUS_INTERVAL {
+            last_status = Instant::now();
+            let synced = manager.symbols().filter(|&symbol| manager.warm_book(symbol).is_some_and(|book| book.active().is_synced())).count();
+            info!(synced, books = manager.len(), "Books synced");
+        }
+        thread::sleep(MANAGER_POLL_INTERVAL);
+    }
+}
+
+// control_manager applies a control API command to the books followed by manager.
+fn control_manager(manager: &mut BookManager, command: &ControlCommand) -> ControlReply {
+    match command {
+        ControlCommand::Subscribe { symbol, max_level } => {
+            manager.subscribe(symbol, max_level.unwrap_or(MAX_LEVEL));
+            info!(%symbol, "Subscribed through the control API");
+            Ok(format!("subscribed to {}", symbol))
+        }
+        ControlCommand::Unsubscribe { symbol } => match manager.unsubscribe(symbol) {
+            true => {
+                info!(%symbol, "Unsubscribed through the control API");
+                Ok(format!("unsubscribed from {}", symbol))
+            }
+            false => Err(format!("{} is not followed", symbol)),
+        },
+        ControlCommand::Resync { symbol: Some(symbol) } => match manager.resync(symbol) {
+            true => Ok(format!("resyncing {}", symbol)),
+            false => Err(format!("{} is not followed", symbol)),
+        },
+        ControlCommand::Resync { symbol: None } => {
+            let symbols: Vec<String> = manager.symbols().map(str::to_string).collect();
+            for symbol in &symbols {
+                manager.resync(symbol);
+            }
+            Ok(format!("resyncing {} books", symbols.len()))
+        }
+        ControlCommand::Symbols => Ok(manager.symbols().collect::<Vec<_>>().join("\n")),
+        ControlCommand::SetPaused(_) | ControlCommand::SetOutput { .. } | ControlCommand::SetRecording(_) => {
+            Err("the books aren't printed or recorded when following several books".to_string())
+        }
+    }
+}
+
+// follow_arbitrage follows the books of two symbols and logs when the executable spread
+// between them, net of fees, opens or closes past the threshold.
+fn follow_arbitrage(symbol_a: &str, symbol_b: &str) {
+    let leg = |symbol: &str| ArbLeg {
+        name: symbol.to_string(),
+        config: feed_config(None),
+        source: Arc::from(snapshot_source(None)),
+        symbol: symbol.to_string(),
+        fees: fees(),
+    };
+    let mut arbitrage = ArbitrageMonitor::start(leg(symbol_a), leg(symbol_b), ARB_CONFIG, MAX_LEVEL);
+    info!(a = symbol_a, b = symbol_b, size = ARB_CONFIG.size.value(), threshold_bps = ARB_CONFIG.threshold_bps, "Monitoring arbitrage");
+
+    let mut last_status = Instant::now();
     loop {
-        if let Ok(Message::Text(text)) = socket.read() {
-            if text.contains(WOOX_PING_CMD) {
-                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-                let pong = json!(
-                    {
-                        "cmd": WOOX_PONG_CMD,
-                        "ts": now
-                    }).to_string();
-
-                socket.send(Message::Text(pong)).unwrap();
-                continue;
-            }
-
-            if text.contains("success") { continue; }
-
-            match serde_json::from_str::<WsMessage>(&text) {
-                Ok(parsed) => {
-                    if let Some(data) = parsed.data {
-                        let event = MarketEvent {
-                            prev_ts: data.prev_ts,
-                            delta: data,
-                        };
-                        
-                        if tx.send(event).is_err() { break; }
+        match arbitrage.poll() {
+            Ok(Some(alert)) => {
+                let spread = alert.spread;
+                let direction = spread.direction;
+                match alert.kind {
+                    ArbAlertKind::Opened => {
+                        let (buy, sell) = (spread.buy_price.value(), spread.sell_price.value());
+                        warn!(?direction, net_bps = spread.net_bps, buy, sell, "Arbitrage opened")
                     }
+                    ArbAlertKind::Closed => info!(?direction, net_bps = spread.net_bps, "Arbitrage closed"),
                 }
-                Err(e) => println!("Parse err: {} , data: {}", e, text),
             }
+            Ok(None) => {}
+            Err((leg, e)) => return error!(%leg, error = %e, "Arbitrage feed failed"),
         }
+        if last_status.elapsed() >= MANAGER_STATUS_INTERVAL {
+            last_status = Instant::now();
+            if let Some(spread) = arbitrage.monitor().latest() {
+                info!(direction = ?spread.direction, gross_bps = spread.gross_bps, net_bps = spread.net_bps, "Arbitrage spread");
+            }
+        }
+        thread::sleep(MANAGER_POLL_INTERVAL);
     }
 }
 
+// paper_market_make runs the reference market maker on symbol against the live book,
+// paper trading on the simulator, and logs its position and PnL.
+fn paper_market_make(symbol: &str) {
+    let risk = RiskCheck::new(MM_RISK_LIMITS, KillSwitch::new());
+    let mut runner = StrategyRunner::new(symbol, SimConfig { fees: fees(), ..SimConfig::default() }).with_risk(risk);
+    runner.register(Box::new(MarketMaker::new(symbol, MM_CONFIG)));
+    let source = Arc::from(snapshot_source(None));
+    let mut strategies = StrategyLoop::start(&feed_config(None), source, runner, MAX_LEVEL);
+    info!(symbol, quote_size = MM_CONFIG.quote_size.value(), half_spread_bps = MM_CONFIG.half_spread_bps, "Paper market making");
 
-// connect_stream attempts to connect to the Woo X websocket and returns a receiver
-// to consume the stream of market events for the specified symbol. 
-fn connect_stream(symbol: &str) -> Receiver<MarketEvent> {
-    let (tx, rx) = mpsc::channel();
-    let symbol = symbol.to_string();
+    let mut last_status = Instant::now();
+    loop {
+        if let Err(e) = strategies.poll() {
+            return error!(error = %e, "Strategy feed failed");
+        }
+        if last_status.elapsed() >= MANAGER_STATUS_INTERVAL {
+            last_status = Instant::now();
+            let execution = strategies.runner().execution();
+            let position = execution.position();
+            info!(
+                position = position.quantity.value(),
+                realized = position.realized.value(),
+                fees = position.fees.value(),
+                fills = execution.fills().len(),
+                open_orders = execution.open_orders().count(),
+                "Paper trading"
+            );
+        }
+        thread::sleep(MANAGER_POLL_INTERVAL);
+    }
+}
 
-    thread::spawn(move || {
-        let parsed_url = Url::parse(WOOX_WS_URL).unwrap();
-        let (mut socket, _) = connect(parsed_url.as_str())
-            .expect("Failed to connect to websocker");
+// follow_basis follows a spot and a perpetual book and the perpetual's funding rate, logging
+// the basis and carry between them.
+fn follow_basis(spot: &str, perp: &str) {
+    let config = BasisConfig { funding_interval: BASIS_FUNDING_INTERVAL, horizon: BASIS_HORIZON };
+    let source = Arc::from(snapshot_source(None));
+    let mut basis = BasisMonitor::start(&feed_config(None), source, spot, perp, MAX_LEVEL, config);
+    info!(spot, perp, "Monitoring basis");
+
+    let mut latest = None;
+    let mut last_status = Instant::now();
+    loop {
+        match basis.poll() {
+            Ok(Some(event)) => latest = Some(event),
+            Ok(None) => {}
+            Err(e) => return error!(error = %e, "Basis feed failed"),
+        }
+        if last_status.elapsed() >= MANAGER_STATUS_INTERVAL {
+            last_status = Instant::now();
+            if let Some(event) = &latest {
+                info!(
+                    basis_bps = event.basis_bps,
+                    annualized_basis = event.annualized_basis,
+                    funding_rate = event.funding_rate,
+                    annualized_carry = event.annualized_carry,
+                    "Basis"
+                );
+            }
+        }
+        thread::sleep(MANAGER_POLL_INTERVAL);
+    }
+}
 
-        println!("Connected to websocket");
+// run_backtest rebuilds the book for SYMBOL from a frame or compact recording as fast as
+// possible, writing it to the configured sinks, and prints a summary of the books seen.
+fn run_backtest(path: &str) {
+    let precision = symbol_precision(SYMBOL);
+    let mut builder = match compact::is_compact(Path::new(path)) {
+        true => match backtest::backtest_compact(Path::new(path), SYMBOL, MAX_LEVEL) {
+            Ok(builder) => builder,
+            Err(e) => return error!(%path, error = %e, "Failed to open the compact recording"),
+        },
+        false => backtest::backtest(path, SYMBOL, MAX_LEVEL),
+    };
+    // The recorders are left out, as the recording being replayed may be where they write.
+    for sink in monitored(stream_sinks(SYMBOL)) {
+        builder = builder.sink(sink);
+    }
 
-        let topic = format!("orderbookupdate@{}@{}", symbol, MAX_LEVEL);
-        let sub_msg = json!({
-            "id": CLIENT_ID,
-            "cmd": WOOX_SUBSCRIBE_CMD,
-            "params": [topic]
-        });
+    let mut stats = BacktestStats::default();
+    if let Err(e) = builder.build().run_with(|book, event| stats.record(book, event)) {
+        error!(error = %e, "Backtest stopped");
+    }
+    println!("{}", stats.summary(precision));
+}
 
-        socket.send(Message::Text(sub_msg.to_string())).unwrap();
-        read_exchange_events(&mut socket, tx);
+// trade_sinks returns the enabled sinks public trades for symbol are written to.
+#[cfg_attr(not(any(feature = "parquet", feature = "proto")), allow(unused_variables))]
+#[cfg_attr(not(any(feature = "parquet", feature = "proto", feature = "sqlite", feature = "kafka", feature = "nats", feature = "grpc")), allow(unused_mut))]
+fn trade_sinks(symbol: &str) -> Vec<Box<dyn Sink>> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    #[cfg(feature = "parquet")]
+    if let Some(dir) = PARQUET_DIR {
+        let parquet = ParquetRecorder::new(Path::new(dir), symbol, parquet_recorder::DEFAULT_BATCH_SIZE);
+        sinks.push(Box::new(recording().gate(Box::new(parquet))));
+    }
+    #[cfg(feature = "proto")]
+    if let Some(dir) = PROTO_RECORD_DIR {
+        let path = Path::new(dir).join(format!("{}.trades.pb", symbol));
+        let proto = ProtoRecorder::create(path).expect("Failed to create protobuf recording");
+        sinks.push(Box::new(recording().gate(Box::new(proto))));
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(path) = SQLITE_PATH {
+        let sqlite = SqliteSink::open(Path::new(path), sqlite_sink::DEFAULT_BATCH_SIZE)
+            .expect("Failed to open SQLite database");
+        sinks.push(Box::new(recording().gate(Box::new(sqlite))));
+    }
+    #[cfg(feature = "kafka")]
+    if let Some(brokers) = KAFKA_BROKERS {
+        sinks.push(Box::new(kafka_sink(brokers)));
+    }
+    #[cfg(feature = "nats")]
+    if let Some(url) = NATS_URL {
+        sinks.push(Box::new(nats_sink(url)));
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(publisher) = grpc_publisher() {
+        sinks.push(Box::new(publisher));
+    }
+    monitored(sinks)
+}
+
+// spawn_trade_recorder writes the public trades for symbol to the trade sinks and session
+// on a background thread, returning the trades for the tape.
+fn spawn_trade_recorder(config: &FeedConfig, symbol: &str, session: &SessionStats) -> Receiver<WsTrade> {
+    let mut sinks = trade_sinks(symbol);
+    sinks.push(Box::new(session.sink(None)));
+    let backfill = TRADE_BACKFILL.then(TradeBackfill::default);
+    let trades = backfill::connect_trades_backfilled(config, symbol, backfill);
+    let (tape_tx, tape) = mpsc::channel();
+    thread::spawn(move || {
+        for trade in trades {
+            write_sinks(&mut sinks, |sink| sink.record_trade(&trade));
+            // The tape is dropped with the book, and the trades keep being recorded.
+            let _ = tape_tx.send(trade);
+        }
     });
+    tape
+}
 
-    rx
+// feed_config returns the websocket feed configuration for the selected environment, using
+// io_uring sockets when built with the io-uring feature on Linux.
+fn feed_config(recorder: Option<FrameRecorder>) -> FeedConfig {
+    let config = FeedConfig {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        backend: SocketBackend::IoUring { sqpoll_idle_ms: None },
+        poll_mode: POLL_MODE,
+        frame_recorder: recorder,
+        metrics: Some(feed_metrics()),
+        clock: clock(),
+        proxy: proxy(),
+        tls: tls(),
+        chaos: chaos(),
+        queue: EVENT_QUEUE,
+        ..FeedConfig::default()
+    };
+    FeedConfig { exchange: Arc::new(environment().exchange_for(api_version(), application_id())), ..config }
 }
 
-// process_orderbook reads events from the receiver and updates the local order book
-// with the websocket delta events. It takes a snapshot of the remote order book and 
-// repeatedly adds deltas to update the local order book. It prints the order book after every update.
-fn process_orderbook(symbol: &str, receiver: Receiver<MarketEvent>) { 
-    println!("Buffering for 4 seconds");
-    thread::sleep(Duration::from_millis(4000));
-    
-    println!("Fetching snapshot");
-    let url = format!("{}?symbol={}&maxLevel={}", REST_URL, symbol, MAX_LEVEL);
-    
-    let snapshot: RestSnapshot = reqwest::blocking::get(url)
-        .expect("HTTP request failed")
-        .json()
-        .expect("Failed to parse snapshot json");
+// ENVIRONMENT is the Woo X environment selected with --env.
+static ENVIRONMENT: std::sync::OnceLock<WooxEnvironment> = std::sync::OnceLock::new();
 
-    println!("Snapshot received at ts: {}", snapshot.timestamp);
+// environment returns the Woo X environment selected with --env, production by default.
+fn environment() -> WooxEnvironment {
+    ENVIRONMENT.get().copied().unwrap_or_default()
+}
 
-    let mut book = LocalOrderBook::new();
-    book.apply_snapshot(snapshot.data);
-    
-    println!("Attempting to sync book with ws");
+// API_VERSION is the Woo X public API version selected with --api-version.
+static API_VERSION: std::sync::OnceLock<ApiVersion> = std::sync::OnceLock::new();
 
-    let mut synced = false;
+// api_version returns the Woo X public API version selected with --api-version, v3 by
+// default.
+fn api_version() -> ApiVersion {
+    API_VERSION.get().copied().unwrap_or_default()
+}
 
-    for event in receiver {
-        if !synced {
-            if event.prev_ts < snapshot.timestamp {
-                let diff = snapshot.timestamp - event.prev_ts;
-                println!("Stream is {}ms behind snapshot", diff);
-                continue; 
+// APPLICATION_ID is the application whose streams the legacy API is followed for, read
+// from WOOX_APPLICATION_ID when --api-version v2 is given.
+static APPLICATION_ID: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+fn application_id() -> &'static str {
+    APPLICATION_ID.get().map_or("", String::as_str)
+}
+
+// CHAOS_SEED is the seed given with --chaos, if faults are injected.
+static CHAOS_SEED: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+
+// chaos returns the injector of the CHAOS faults shared by every feed connection, if --chaos
+// was given.
+fn chaos() -> Option<Arc<Chaos>> {
+    static INJECTOR: std::sync::OnceLock<Option<Arc<Chaos>>> = std::sync::OnceLock::new();
+    INJECTOR.get_or_init(|| CHAOS_SEED.get().map(|&seed| Arc::new(Chaos::new(CHAOS.with_seed(seed))))).clone()
+}
+
+// rest_client returns a client for the REST API of the selected environment.
+fn rest_client() -> RestClient {
+    RestClient::new(environment().rest_url())
+}
+
+// tls returns the TLS config built from TLS_CA_PATH and TLS_PINNED_SHA256, or None to use
+// the system's roots.
+fn tls() -> Option<Arc<rustls::ClientConfig>> {
+    static TLS: std::sync::OnceLock<Option<Arc<rustls::ClientConfig>>> = std::sync::OnceLock::new();
+    TLS.get_or_init(|| {
+        if TLS_CA_PATH.is_none() && TLS_PINNED_SHA256.is_empty() {
+            return None;
+        }
+        let mut config = TlsConfig::default().with_pinned(TLS_PINNED_SHA256);
+        if let Some(path) = TLS_CA_PATH {
+            config = config.with_ca_file(path);
+        }
+        Some(config.client_config().expect("Failed to load the TLS config"))
+    })
+    .clone()
+}
+
+// proxy returns the proxy from PROXY_PATH, or the environment if it isn't set.
+fn proxy() -> Option<ProxyConfig> {
+    static PROXY: std::sync::OnceLock<Option<ProxyConfig>> = std::sync::OnceLock::new();
+    PROXY
+        .get_or_init(|| {
+            let proxy = match PROXY_PATH {
+                Some(path) => Some(ProxyConfig::load(Path::new(path))),
+                None => ProxyConfig::from_env(),
+            };
+            let proxy = proxy.transpose().expect("Failed to load the proxy config");
+            if let Some(proxy) = &proxy {
+                info!(no_proxy = ?proxy.no_proxy, "Connecting through a proxy");
             }
+            proxy
+        })
+        .clone()
+}
+
+// frame_recorder opens the frame recording if one is configured.
+fn frame_recorder() -> Option<FrameRecorder> {
+    let path = FRAME_RECORD_PATH?;
+    info!(%path, "Recording frames");
+    Some(FrameRecorder::create(path).expect("Failed to open frame recording"))
+}
+
+// snapshot_source returns the live snapshot source, recorded to recorder if given.
+fn snapshot_source(recorder: Option<FrameRecorder>) -> Box<dyn SnapshotSource> {
+    let source = fallback_source();
+    match recorder {
+        Some(recorder) => Box::new(RecordingSource::new(source, recorder)),
+        None => source,
+    }
+}
+
+// fallback_source returns the source used to seed the local book: a peer instance, then
+// the Woo X REST API, as configured. A checkpoint is only warm started from, as a snapshot
+// the stream has moved past can't be synced from.
+fn fallback_source() -> Box<dyn SnapshotSource> {
+    let mut sources: Vec<Box<dyn SnapshotSource>> = Vec::new();
+    if let Some(url) = PEER_SNAPSHOT_URL {
+        sources.push(Box::new(PeerSource::new(url)));
+    }
 
-            if event.prev_ts == snapshot.timestamp {
-                println!("Local book is now synced");
-                synced = true;
-                book.apply_delta(event.delta);
-                book.print_top_5();
-            } 
-            else if event.prev_ts > snapshot.timestamp {
-                 println!("Local book out of sync, probably rerun with a bigger buffer time");
-                 return; 
+    if sources.is_empty() {
+        return Box::new(WooxRestSource::new(&environment().orderbook_url_for(api_version())).with_version(api_version()));
+    }
+    sources.push(Box::new(WooxRestSource::new(&environment().orderbook_url_for(api_version())).with_version(api_version())));
+    Box::new(FallbackSource::new(sources))
+}
+
+// snapshot_registry returns the registry of synced books, kept when they are served to
+// peers or checkpointed on shutdown, starting the peer snapshot server if one is configured.
+fn snapshot_registry() -> Option<SnapshotRegistry> {
+    static REGISTRY: std::sync::OnceLock<Option<SnapshotRegistry>> = std::sync::OnceLock::new();
+    REGISTRY
+        .get_or_init(|| {
+            if SNAPSHOT_SERVER_ADDR.is_none() && CHECKPOINT_PATH.is_none() {
+                return None;
+            }
+            let registry = SnapshotRegistry::new();
+            if let Some(addr) = SNAPSHOT_SERVER_ADDR {
+                peer::serve_snapshots(addr, registry.clone()).expect("Failed to start snapshot server");
+                info!(%addr, "Serving snapshots");
             }
-        } else {
-            book.apply_delta(event.delta);
-            book.print_top_5();
+            Some(registry)
+        })
+        .clone()
+}
+
+// BookDisplay is how follow_book prints the book: the renderer, the handoff latency between
+// the reader thread receiving an event and the book thread dequeuing it, printed below the
+// book, and whether printing is paused. The update and idle callbacks share it.
+struct BookDisplay {
+    renderer: Renderer,
+    handoff: LatencyStats,
+    paused: bool,
+}
+
+// print_diagnostics prints the feed diagnostics below a rendered book.
+fn print_diagnostics(handoff: &LatencyStats, arbitration: Option<&ArbitrationMetrics>) {
+    println!();
+    println!(
+        "Handoff latency ({}): mean {:?} | max {:?} | events {}",
+        POLL_MODE.label(),
+        handoff.mean(),
+        handoff.max(),
+        handoff.count()
+    );
+    if let Some(metrics) = arbitration {
+        println!("{}", metrics.summary());
+    }
+}
+
+// normalize_symbols maps market names given on the command line, e.g. eth-usdt-perp, to
+// listed Woo X symbols, printing why and returning None if one can't be mapped. The names
+// are used as given if the instruments can't be listed.
+#[cfg(feature = "tui")]
+fn normalize_symbols(inputs: Vec<String>) -> Option<Vec<String>> {
+    if inputs.is_empty() {
+        return Some(inputs);
+    }
+    let mapper = match SymbolMapper::fetch(&rest_client()) {
+        Ok(mapper) => mapper,
+        Err(e) => {
+            println!("Failed to list instruments, using symbols as given: {}", e);
+            return Some(inputs);
         }
+    };
+    inputs
+        .iter()
+        .map(|input| mapper.normalize(input).inspect_err(|e| println!("{}", e)).ok())
+        .collect()
+}
+
+// run_tui runs the full-screen terminal UI for the given symbols, or SYMBOL if none are given.
+#[cfg(feature = "tui")]
+fn run_tui(symbols: Vec<String>) {
+    let Some(symbols) = normalize_symbols(symbols) else { return };
+    let symbols = if symbols.is_empty() { vec![SYMBOL.to_string()] } else { symbols };
+    let precisions = symbols.iter().map(|symbol| (symbol.clone(), symbol_precision(symbol))).collect();
+    let config = woox::tui::TuiConfig {
+        feed: feed_config(None),
+        symbols,
+        max_level: MAX_LEVEL,
+        depth: DISPLAY_DEPTH,
+        precisions,
+        large_trade_size: LARGE_TRADE_SIZE,
+    };
+    woox::tui::run(config, Arc::from(snapshot_source(None))).expect("Terminal UI failed");
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_symbols: Vec<String>) {
+    println!("The terminal UI requires building with --features tui");
+}
+
+// init_logging writes logs to stderr, keeping stdout for the book and command output.
+fn init_logging() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(LOG_LEVEL));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr);
+    if LOG_JSON {
+        builder.json().init();
+    } else {
+        builder.init();
     }
 }
 
 fn main() {
-    let data_stream = connect_stream(SYMBOL);
-    process_orderbook(SYMBOL, data_stream);
-}
\ No newline at end of file
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // --env selects the Woo X environment, --api-version its public API and --chaos injects
+    // faults for every mode, so they may come before or after the mode's arguments.
+    if let Some(i) = args.iter().position(|arg| arg == "--env") {
+        match args.get(i + 1).and_then(|name| WooxEnvironment::from_name(name)) {
+            Some(environment) => {
+                let _ = ENVIRONMENT.set(environment);
+                args.drain(i..i + 2);
+            }
+            None => return println!("Usage: --env <prod|staging>"),
+        }
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--api-version") {
+        match args.get(i + 1).and_then(|name| ApiVersion::from_name(name)) {
+            Some(version) => {
+                let _ = API_VERSION.set(version);
+                args.drain(i..i + 2);
+            }
+            None => return println!("Usage: --api-version <v3|v2>"),
+        }
+    }
+    // The legacy API only streams to an application, named in its websocket url.
+    if api_version() == ApiVersion::V2 {
+        match std::env::var("WOOX_APPLICATION_ID").ok().filter(|id| !id.trim().is_empty()) {
+            Some(id) => {
+                let _ = APPLICATION_ID.set(id.trim().to_string());
+            }
+            None => return println!("--api-version v2 needs the application id to stream for in WOOX_APPLICATION_ID"),
+        }
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--chaos") {
+        match args.get(i + 1).and_then(|seed| seed.parse().ok()) {
+            Some(seed) => {
+                let _ = CHAOS_SEED.set(seed);
+                args.drain(i..i + 2);
+            }
+            None => return println!("Usage: --chaos <seed>"),
+        }
+    }
+    // The terminal UI owns the screen, so it runs without logging.
+    if args.first().map(String::as_str) == Some("--tui") {
+        run_tui(args[1..].to_vec());
+        return;
+    }
+    init_logging();
+    if environment() != WooxEnvironment::Prod {
+        info!(environment = environment().name(), rest_url = environment().rest_url(), "Using a non-production environment");
+    }
+    if api_version() != ApiVersion::default() {
+        info!(
+            version = api_version().name(),
+            ws_url = environment().ws_url_for(api_version(), application_id()),
+            orderbook_url = environment().orderbook_url_for(api_version()),
+            "Using the legacy public API"
+        );
+    }
+    if let Some(seed) = CHAOS_SEED.get() {
+        warn!(seed, "Injecting faults into the feed");
+    }
+    let http = HttpClientConfig::default()
+        .with_user_agent(HTTP_USER_AGENT)
+        .with_timeouts(HTTP_CONNECT_TIMEOUT, HTTP_TIMEOUT)
+        .with_proxy(proxy())
+        .with_tls(tls());
+    if let Err(e) = http_client::configure(&http) {
+        warn!(error = %e, "Failed to build the HTTP client, using the default");
+    }
+
+    if args.first().map(String::as_str) == Some("--backtest") {
+        match args.get(1) {
+            Some(path) => run_backtest(path),
+            None => println!("Usage: --backtest <file>"),
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("--reconstruct") {
+        let times: Option<Vec<u64>> = args[1..].iter().skip(1).map(|arg| arg.parse().ok()).collect();
+        match (args.get(1), times.as_deref()) {
+            (Some(path), Some(&[ts])) => reconstruct_book(path, ts),
+            (Some(path), Some(&[from, to, interval_ms])) => reconstruct_series(path, from, to, Duration::from_millis(interval_ms)),
+            _ => println!("Usage: --reconstruct <file> <ts> | --reconstruct <file> <from ts> <to ts> <interval ms>"),
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("--replay") {
+        match replay_config(&args[1..]) {
+            Some(replay) => replay_book(replay),
+            None => println!("Usage: --replay <file> [speed, e.g. 10x or max]"),
+        }
+        return;
+    }
+
+    if args.first().map(String::as_str) == Some("--supervise") {
+        match args.get(1).map(Supervisor::start) {
+            Some(Ok(supervisor)) => supervisor.run(),
+            Some(Err(e)) => error!(error = %e, "Failed to start supervisor"),
+            None => println!("Usage: --supervise <config.json>"),
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("--symbols") {
+        list_symbols(&symbol_filter(&args[1..]));
+        return;
+    }
+    if args.first().map(String::as_str) == Some("--all") {
+        follow_matching(&symbol_filter(&args[1..]));
+        return;
+    }
+    if args.first().map(String::as_str) == Some("--arb") {
+        match (args.get(1), args.get(2)) {
+            (Some(a), Some(b)) => follow_arbitrage(a, b),
+            _ => println!("Usage: --arb <symbol> <symbol>, e.g. --arb SPOT_ETH_USDT PERP_ETH_USDT"),
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("--mm") {
+        match args.get(1) {
+            Some(symbol) => paper_market_make(symbol),
+            None => println!("Usage: --mm <symbol>, e.g. --mm PERP_ETH_USDT"),
+        }
+        return;
+    }
+    if args.first().map(String::as_str) == Some("--basis") {
+        match (args.get(1), args.get(2)) {
+            (Some(spot), Some(perp)) => follow_basis(spot, perp),
+            _ => println!("Usage: --basis <spot symbol> <perp symbol>, e.g. --basis SPOT_ETH_USDT PERP_ETH_USDT"),
+        }
+        return;
+    }
+
+    let recorder = frame_recorder();
+    let config = feed_config(recorder.clone());
+    let session = start_session();
+    let tape = spawn_trade_recorder(&config, SYMBOL, &session);
+    follow_book(config, snapshot_source(recorder), &session, Some(tape));
+}
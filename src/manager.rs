@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::feed::FeedConfig;
+use crate::orderbook::LocalOrderBook;
+use crate::rest::{RestClient, RestError, RestInstrument};
+use crate::snapshot::SnapshotSource;
+use crate::standby::{PollError, WarmBook, WarmPoll};
+
+// SymbolFilter selects instruments by symbol pattern and quote currency.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolFilter {
+    // pattern matches the symbol, with * matching any run of characters, e.g. PERP_*.
+    pub pattern: Option<String>,
+    // quote matches the quote asset, e.g. USDT.
+    pub quote: Option<String>,
+    // include_suspended also matches instruments that aren't currently trading.
+    pub include_suspended: bool,
+}
+
+impl SymbolFilter {
+    // pattern returns a filter on the symbol pattern alone.
+    pub fn pattern(pattern: &str) -> Self {
+        Self { pattern: Some(pattern.to_string()), ..Self::default() }
+    }
+
+    pub fn matches(&self, instrument: &RestInstrument) -> bool {
+        (self.include_suspended || instrument.is_trading())
+            && self.pattern.as_deref().is_none_or(|pattern| glob_match(pattern, &instrument.symbol))
+            && self.quote.as_deref().is_none_or(|quote| instrument.quote_asset.eq_ignore_ascii_case(quote))
+    }
+}
+
+// glob_match returns true if text matches pattern, where * matches any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No * in the pattern, so it has to match exactly.
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+// discover_symbols returns the symbols of every listed instrument matching filter, sorted.
+pub fn discover_symbols(rest: &RestClient, filter: &SymbolFilter) -> Result<Vec<String>, RestError> {
+    let mut symbols: Vec<String> = rest
+        .instruments(None)?
+        .into_iter()
+        .filter(|instrument| filter.matches(instrument))
+        .map(|instrument| instrument.symbol)
+        .collect();
+    symbols.sort();
+    Ok(symbols)
+}
+
+// LifecycleEvent is a change in an instrument's listing seen by BookManager::refresh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    // Listed is a new trading instrument. subscribed is set if it matched an auto subscribe
+    // filter and its book was started.
+    Listed { symbol: String, subscribed: bool },
+    // Delisted is a followed instrument that is no longer listed, its book was closed.
+    Delisted { symbol: String },
+    // Suspended is a followed instrument that stopped trading, its book was closed until
+    // it resumes.
+    Suspended { symbol: String },
+    // Resumed is a suspended instrument trading again, its book was restarted.
+    Resumed { symbol: String },
+}
+
+// AutoSubscribe is a filter newly listed instruments are subscribed to at max_level.
+#[derive(Debug, Clone)]
+struct AutoSubscribe {
+    filter: SymbolFilter,
+    max_level: usize,
+}
+
+// BookManager maintains a warm book for each of a set of symbols, restarting books that
+// fall out of sync. refresh follows the instruments list: books of delisted or suspended
+// instruments are closed, suspended ones restarted when they resume, and new listings
+// matching an auto subscribe filter subscribed to.
+pub struct BookManager {
+    config: FeedConfig,
+    source: Arc<dyn SnapshotSource>,
+    books: BTreeMap<String, WarmBook>,
+    auto_subscribe: Vec<AutoSubscribe>,
+    // listed is the trading status of every instrument at the last refresh.
+    listed: Option<HashMap<String, bool>>,
+    // suspended are the symbols closed by a suspension and the depth to resume them at.
+    suspended: HashMap<String, usize>,
+    // failed are the symbols whose feed failed, which aren't polled until the next refresh
+    // restarts or closes them.
+    failed: HashSet<String>,
+}
+
+impl BookManager {
+    pub fn new(config: FeedConfig, source: Arc<dyn SnapshotSource>) -> Self {
+        Self {
+            config,
+            source,
+            books: BTreeMap::new(),
+            auto_subscribe: Vec::new(),
+            listed: None,
+            suspended: HashMap::new(),
+            failed: HashSet::new(),
+        }
+    }
+
+    // subscribe starts following symbol at max_level, changing its depth if it is already
+    // followed.
+    pub fn subscribe(&mut self, symbol: &str, max_level: usize) {
+        match self.books.get_mut(symbol) {
+            Some(book) => book.change_depth(max_level),
+            None => {
+                let book = WarmBook::start(&self.config, symbol, max_level, Arc::clone(&self.source));
+                self.books.insert(symbol.to_string(), book);
+            }
+        }
+    }
+
+    // subscribe_matching subscribes to every listed symbol matching filter and returns them.
+    pub fn subscribe_matching(&mut self, rest: &RestClient, filter: &SymbolFilter, max_level: usize) -> Result<Vec<String>, RestError> {
+        let symbols = discover_symbols(rest, filter)?;
+        for symbol in &symbols {
+            self.subscribe(symbol, max_level);
+        }
+        Ok(symbols)
+    }
+
+    // auto_subscribe subscribes to instruments matching filter at max_level as they are
+    // listed, from the next refresh on.
+    pub fn auto_subscribe(&mut self, filter: SymbolFilter, max_level: usize) {
+        self.auto_subscribe.push(AutoSubscribe { filter, max_level });
+    }
+
+    // unsubscribe stops following symbol, returning whether it was followed.
+    pub fn unsubscribe(&mut self, symbol: &str) -> bool {
+        self.failed.remove(symbol);
+        self.suspended.remove(symbol);
+        self.books.remove(symbol).is_some()
+    }
+
+    // resync restarts the book of symbol from a new snapshot, returning whether it is followed.
+    pub fn resync(&mut self, symbol: &str) -> bool {
+        self.failed.remove(symbol);
+        self.books.get_mut(symbol).map(WarmBook::restart).is_some()
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.books.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.books.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.books.is_empty()
+    }
+
+    pub fn warm_book(&self, symbol: &str) -> Option<&WarmBook> {
+        self.books.get(symbol)
+    }
+
+    // book returns the active book for symbol, once its snapshot has arrived.
+    pub fn book(&self, symbol: &str) -> Option<&LocalOrderBook> {
+        self.books.get(symbol)?.active().book()
+    }
+
+    // has_failed_feeds returns true if a feed failed since the last refresh, which may mean
+    // its instrument was delisted or suspended.
+    pub fn has_failed_feeds(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    // poll polls every book, restarting the ones that fell out of sync, and returns the
+    // result for each symbol. Books whose feed failed are left for refresh to handle.
+    pub fn poll(&mut self) -> Vec<(&str, Result<WarmPoll, PollError>)> {
+        let mut results = Vec::new();
+        for (symbol, book) in self.books.iter_mut() {
+            if self.failed.contains(symbol) {
+                continue;
+            }
+            let result = book.poll();
+            match result {
+                Err(PollError::OutOfSync) => book.restart(),
+                Err(_) => {
+                    self.failed.insert(symbol.clone());
+                }
+                Ok(_) => {}
+            }
+            results.push((symbol.as_str(), result));
+        }
+        results
+    }
+
+    // refresh fetches the instruments list and applies the changes since the last refresh
+    // to the followed books, returning them. Failed feeds of instruments still trading are
+    // restarted. The first refresh only closes books; later ones also report new listings.
+    pub fn refresh(&mut self, rest: &RestClient) -> Result<Vec<LifecycleEvent>, RestError> {
+        let instruments = rest.instruments(None)?;
+        let listed: HashMap<String, bool> =
+            instruments.iter().map(|instrument| (instrument.symbol.clone(), instrument.is_trading())).collect();
+        let mut events = Vec::new();
+
+        let followed: Vec<String> = self.books.keys().cloned().collect();
+        for symbol in followed {
+            match listed.get(&symbol) {
+                None => {
+                    self.unsubscribe(&symbol);
+                    events.push(LifecycleEvent::Delisted { symbol });
+                }
+                Some(false) => {
+                    let max_level = self.books[&symbol].active().max_level();
+                    self.unsubscribe(&symbol);
+                    self.suspended.insert(symbol.clone(), max_level);
+                    events.push(LifecycleEvent::Suspended { symbol });
+                }
+                Some(true) if self.failed.remove(&symbol) => self.books.get_mut(&symbol).unwrap().restart(),
+                Some(true) => {}
+            }
+        }
+
+        let resumed: Vec<(String, usize)> = self
+            .suspended
+            .iter()
+            .filter(|(symbol, _)| listed.get(*symbol) == Some(&true))
+            .map(|(symbol, &max_level)| (symbol.clone(), max_level))
+            .collect();
+        for (symbol, max_level) in resumed {
+            self.suspended.remove(&symbol);
+            self.subscribe(&symbol, max_level);
+            events.push(LifecycleEvent::Resumed { symbol });
+        }
+        self.suspended.retain(|symbol, _| listed.contains_key(symbol));
+
+        if let Some(previous) = &self.listed {
+            let mut new: Vec<&RestInstrument> = instruments
+                .iter()
+                .filter(|instrument| instrument.is_trading() && !previous.contains_key(&instrument.symbol))
+                .collect();
+            new.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+            for instrument in new {
+                let auto = self.auto_subscribe.iter().find(|auto| auto.filter.matches(instrument)).map(|auto| auto.max_level);
+                if let Some(max_level) = auto {
+                    self.subscribe(&instrument.symbol, max_level);
+                }
+                events.push(LifecycleEvent::Listed { symbol: instrument.symbol.clone(), subscribed: auto.is_some() });
+            }
+        }
+        self.listed = Some(listed);
+        Ok(events)
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use crate::exchange_api_types::Side;
+use crate::order::{OrderId, OrderRequest, OrderUpdate};
+use crate::orderbook::LocalOrderBook;
+use crate::strategy::{Strategy, StrategyContext};
+use crate::units::{Price, Qty};
+
+// MarketMakerConfig is how a MarketMaker quotes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarketMakerConfig {
+    // quote_size is the quantity quoted on each side.
+    pub quote_size: Qty,
+    // half_spread_bps is how far each quote sits from the fair price.
+    pub half_spread_bps: f64,
+    // skew_bps is how far the fair price is shifted against the position when it is at
+    // max_position, and in proportion below it.
+    pub skew_bps: f64,
+    // max_position is the largest position held either way. A side that would add to a
+    // position at the limit isn't quoted.
+    pub max_position: Qty,
+    // requote_bps is how far a quote may drift from where it would be quoted now before it
+    // is replaced.
+    pub requote_bps: f64,
+    pub tick_size: f64,
+}
+
+// MarketMaker is a reference quoting strategy. It keeps a bid and an ask quote_size deep
+// around the book's microprice, skewed against its position so that it leans towards
+// trading back to flat, and stops adding to a position at max_position.
+pub struct MarketMaker {
+    symbol: String,
+    config: MarketMakerConfig,
+    // quotes are the strategy's open orders and the price they were placed at.
+    quotes: HashMap<Side, (OrderId, Price)>,
+}
+
+impl MarketMaker {
+    pub fn new(symbol: &str, config: MarketMakerConfig) -> Self {
+        Self { symbol: symbol.to_string(), config, quotes: HashMap::new() }
+    }
+
+    // quote returns the bid and ask for book with position held, each None if that side
+    // shouldn't be quoted.
+    pub fn quote(&self, book: &LocalOrderBook, position: Qty) -> (Option<Price>, Option<Price>) {
+        let (Some(fair), Some((best_bid, _)), Some((best_ask, _))) = (book.microprice(), book.best_bid(), book.best_ask()) else {
+            return (None, None);
+        };
+        let config = &self.config;
+        let inventory = position.ratio(config.max_position).clamp(-1.0, 1.0);
+        let reservation = fair.value() * (1.0 - inventory * config.skew_bps / 10_000.0);
+        let half_spread = reservation * config.half_spread_bps / 10_000.0;
+
+        // Quotes skewed through the touch would take, so they're held at the touch instead.
+        let mut bid = Price::new(reservation - half_spread).floor_to(config.tick_size);
+        if bid >= best_ask {
+            bid = best_bid;
+        }
+        let mut ask = Price::new(reservation + half_spread).ceil_to(config.tick_size);
+        if ask <= best_bid {
+            ask = best_ask;
+        }
+        let bid = (position < config.max_position).then_some(bid);
+        let ask = (position > Qty::ZERO - config.max_position).then_some(ask);
+        (bid, ask)
+    }
+
+    // requote replaces the quote on side if it has drifted from price, or cancels it if
+    // price is None.
+    fn requote(&mut self, ctx: &mut StrategyContext, side: Side, price: Option<Price>) {
+        let tolerance = |price: Price| price.value() * self.config.requote_bps / 10_000.0;
+        match (self.quotes.get(&side).copied(), price) {
+            (Some((_, placed)), Some(price)) if (placed - price).value().abs() < tolerance(price) => {}
+            (Some((id, _)), _) => {
+                ctx.cancel(id);
+                self.quotes.remove(&side);
+                if let Some(price) = price {
+                    ctx.submit(OrderRequest::limit(&self.symbol, side, self.config.quote_size, price));
+                }
+            }
+            (None, Some(price)) => ctx.submit(OrderRequest::limit(&self.symbol, side, self.config.quote_size, price)),
+            (None, None) => {}
+        }
+    }
+}
+
+impl Strategy for MarketMaker {
+    fn name(&self) -> &str {
+        "market maker"
+    }
+
+    fn on_book_update(&mut self, ctx: &mut StrategyContext, book: &LocalOrderBook) {
+        let (bid, ask) = self.quote(book, ctx.execution().position().quantity);
+        self.requote(ctx, Side::Buy, bid);
+        self.requote(ctx, Side::Sell, ask);
+    }
+
+    // on_order_update tracks the quotes as they are placed and fill. A fill changes the
+    // position, so the quotes are refreshed at once rather than on the next book update.
+    fn on_order_update(&mut self, ctx: &mut StrategyContext, update: &OrderUpdate) {
+        if update.status.is_final() {
+            self.quotes.retain(|_, (id, _)| *id != update.order_id);
+        } else if let Some((_, request)) = ctx.execution().open_orders().find(|(id, _)| *id == update.order_id) {
+            if let Some(price) = request.price() {
+                self.quotes.insert(request.side, (update.order_id, price));
+            }
+        }
+        if update.fill.is_some() {
+            let (bid, ask) = self.quote(ctx.book(), ctx.execution().position().quantity);
+            self.requote(ctx, Side::Buy, bid);
+            self.requote(ctx, Side::Sell, ask);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange_api_types::{RestQuote, SnapshotData, WsTrade};
+    use crate::order::Liquidity;
+    use crate::simulator::{QueueModel, SimConfig};
+    use crate::strategy::StrategyRunner;
+
+    const SYMBOL: &str = "SPOT_BTC_USDT";
+    const CONFIG: MarketMakerConfig = MarketMakerConfig {
+        quote_size: Qty::new(1.0),
+        half_spread_bps: 25.0,
+        skew_bps: 100.0,
+        max_position: Qty::new(2.0),
+        requote_bps: 1.0,
+        tick_size: 0.5,
+    };
+
+    // book returns a book of one level a side, 5 deep, so its microprice is the mid.
+    fn book(bid: f64, ask: f64) -> LocalOrderBook {
+        let mut book = LocalOrderBook::new();
+        book.apply_snapshot(SnapshotData { bids: vec![RestQuote { price: bid, quantity: 5.0 }], asks: vec![RestQuote { price: ask, quantity: 5.0 }] });
+        book
+    }
+
+    fn trade(side: Side, price: f64) -> WsTrade {
+        WsTrade { symbol: SYMBOL.to_string(), price, quantity: 1.0, side, ts: 1, backfilled: false }
+    }
+
+    // quotes returns the side and price of the runner's resting orders, oldest first.
+    fn quotes(runner: &StrategyRunner) -> Vec<(Side, f64)> {
+        runner.execution().open_orders().map(|(_, request)| (request.side, request.price().unwrap().value())).collect()
+    }
+
+    fn position(runner: &StrategyRunner) -> f64 {
+        runner.execution().position().quantity.value()
+    }
+
+    #[test]
+    fn quotes_around_the_microprice_and_skews_against_the_inventory() {
+        let mut runner = StrategyRunner::new(SYMBOL, SimConfig { queue: QueueModel::FillOnTouch, ..SimConfig::default() });
+        runner.register(Box::new(MarketMaker::new(SYMBOL, CONFIG)));
+
+        // Flat, the quotes sit 25 bps either side of 100, rounded out to the tick.
+        let quiet = book(99.0, 101.0);
+        runner.on_book_update(&quiet);
+        assert_eq!(quotes(&runner), vec![(Side::Buy, 99.5), (Side::Sell, 100.5)]);
+        // An unchanged book leaves them alone.
+        runner.on_book_update(&quiet);
+        assert_eq!(quotes(&runner), vec![(Side::Buy, 99.5), (Side::Sell, 100.5)]);
+
+        // Long 1, half the limit, both quotes lean 50 bps lower, to sell the position off.
+        runner.on_trade(&trade(Side::Sell, 99.5), &quiet);
+        assert_eq!(position(&runner), 1.0);
+        assert_eq!(quotes(&runner), vec![(Side::Buy, 99.0), (Side::Sell, 100.0)]);
+
+        // At the limit no more is bought, and the ask leans further.
+        runner.on_trade(&trade(Side::Sell, 99.0), &quiet);
+        assert_eq!(position(&runner), 2.0);
+        assert_eq!(quotes(&runner), vec![(Side::Sell, 99.5)]);
+
+        runner.on_trade(&trade(Side::Buy, 99.5), &quiet);
+        assert_eq!(position(&runner), 1.0);
+        assert_eq!(quotes(&runner), vec![(Side::Buy, 99.0), (Side::Sell, 100.0)]);
+
+        // The book moving up to the ask fills it, and the quotes follow the book, flat again.
+        runner.on_book_update(&book(100.0, 102.0));
+        assert_eq!(position(&runner), 0.0);
+        assert_eq!(quotes(&runner), vec![(Side::Buy, 100.5), (Side::Sell, 101.5)]);
+
+        let fills: Vec<_> = runner.execution().fills().iter().map(|fill| (fill.side, fill.price.value(), fill.liquidity)).collect();
+        assert_eq!(
+            fills,
+            vec![(Side::Buy, 99.5, Liquidity::Maker), (Side::Buy, 99.0, Liquidity::Maker), (Side::Sell, 99.5, Liquidity::Maker), (Side::Sell, 100.0, Liquidity::Maker)]
+        );
+    }
+}
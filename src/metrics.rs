@@ -0,0 +1,323 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::analytics::{self, LiquidityStats, LiquiditySummary};
+use crate::clock::ClockSkew;
+use crate::feed::MarketEvent;
+use crate::http::{self, Response};
+use crate::latency::{LatencyHistograms, Stage, QUANTILES};
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+
+// LATENCY_BUCKETS are the upper bounds in seconds of the processing latency histogram
+// buckets.
+const LATENCY_BUCKETS: [f64; 12] = [0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25];
+
+// FeedMetrics counts what the feed connections read. It is shared with the connections
+// through FeedConfig::metrics.
+#[derive(Debug, Default)]
+pub struct FeedMetrics {
+    // messages is the number of data messages received, excluding pings and acks.
+    pub messages: AtomicU64,
+    pub parse_errors: AtomicU64,
+    pub connects: AtomicU64,
+    // reconnects is the number of connections made to a topic that had been connected before.
+    pub reconnects: AtomicU64,
+    // open is the number of connections currently open.
+    pub open: AtomicU64,
+    // queued is the number of events waiting in the queues of the book threads, and
+    // queue_dropped and queue_coalesced count those dropped or merged into another once a
+    // queue was full.
+    pub queued: AtomicU64,
+    pub queue_dropped: AtomicU64,
+    pub queue_coalesced: AtomicU64,
+    topics: Mutex<HashSet<String>>,
+}
+
+impl FeedMetrics {
+    // record_connect counts a connection to topic.
+    pub fn record_connect(&self, topic: &str) {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+        self.open.fetch_add(1, Ordering::Relaxed);
+        if !self.topics.lock().unwrap().insert(topic.to_string()) {
+            self.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // record_disconnect counts a connection ending.
+    pub fn record_disconnect(&self) {
+        let _ = self.open.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |open| open.checked_sub(1));
+    }
+}
+
+// LatencyHistogram is a cumulative histogram over LATENCY_BUCKETS.
+#[derive(Debug, Default)]
+struct LatencyHistogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: Duration,
+}
+
+impl LatencyHistogram {
+    fn observe(&mut self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += latency;
+    }
+}
+
+// LIQUIDITY_WINDOWS are the windows the liquidity of each book is averaged over, and the
+// label of each. The last window to end is exported.
+const LIQUIDITY_WINDOWS: [(&str, Duration); 2] = [("1m", Duration::from_secs(60)), ("1h", Duration::from_secs(3600))];
+
+// BookLiquidity is the liquidity of a book over one of LIQUIDITY_WINDOWS.
+#[derive(Debug)]
+struct BookLiquidity {
+    stats: LiquidityStats,
+    last: Option<LiquiditySummary>,
+}
+
+// BookMetrics are the metrics of one symbol's book.
+#[derive(Debug)]
+struct BookMetrics {
+    deltas: u64,
+    resyncs: u64,
+    bid_depth: usize,
+    ask_depth: usize,
+    best_bid: Option<f64>,
+    best_ask: Option<f64>,
+    latency: LatencyHistogram,
+    stages: LatencyHistograms,
+    liquidity: Vec<BookLiquidity>,
+}
+
+impl Default for BookMetrics {
+    fn default() -> Self {
+        let liquidity = LIQUIDITY_WINDOWS.iter().map(|&(_, window)| BookLiquidity {
+            stats: LiquidityStats::new(analytics::DEFAULT_LIQUIDITY_DEPTH, &analytics::DEFAULT_SPREAD_THRESHOLDS_BPS, Some(window)),
+            last: None,
+        });
+        Self {
+            deltas: 0,
+            resyncs: 0,
+            bid_depth: 0,
+            ask_depth: 0,
+            best_bid: None,
+            best_ask: None,
+            latency: LatencyHistogram::default(),
+            stages: LatencyHistograms::default(),
+            liquidity: liquidity.collect(),
+        }
+    }
+}
+
+impl BookMetrics {
+    fn observe_book(&mut self, book: &LocalOrderBook) {
+        self.bid_depth = book.bids().count();
+        self.ask_depth = book.asks().count();
+        self.best_bid = book.best_bid().map(|(price, _)| price.value());
+        self.best_ask = book.best_ask().map(|(price, _)| price.value());
+        for liquidity in &mut self.liquidity {
+            if let Some(summary) = liquidity.stats.on_book(book).pop() {
+                liquidity.last = Some(summary);
+            }
+        }
+    }
+}
+
+// Metrics collects the feed and book metrics and renders them in the Prometheus text
+// exposition format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub feed: Arc<FeedMetrics>,
+    books: Mutex<BTreeMap<String, BookMetrics>>,
+    clock: Option<Arc<ClockSkew>>,
+}
+
+impl Metrics {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    // with_clock returns metrics whose latencies measured from exchange timestamps are
+    // corrected by clock's skew, which is exported as well.
+    pub fn with_clock(clock: Arc<ClockSkew>) -> Arc<Self> {
+        Arc::new(Self { clock: Some(clock), ..Self::default() })
+    }
+
+    // sink returns a sink recording the book metrics of the books written to it.
+    pub fn sink(self: &Arc<Self>) -> MetricsSink {
+        MetricsSink { metrics: Arc::clone(self) }
+    }
+
+    // serve serves the metrics on addr at /metrics. It returns once the listener is bound.
+    pub fn serve<A: ToSocketAddrs>(self: &Arc<Self>, addr: A) -> io::Result<thread::JoinHandle<()>> {
+        let metrics = Arc::clone(self);
+        http::serve(addr, move |request| match request.path.as_str() {
+            "/metrics" => Response::new(200, "text/plain; version=0.0.4", metrics.render()),
+            _ => Response::not_found(),
+        })
+    }
+
+    fn with_book(&self, symbol: &str, f: impl FnOnce(&mut BookMetrics)) {
+        let mut books = self.books.lock().unwrap();
+        f(books.entry(symbol.to_string()).or_default());
+    }
+
+    // render returns the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let feed = &self.feed;
+        let counters = [
+            ("woox_messages_received_total", "Data messages received from the websocket.", &feed.messages),
+            ("woox_parse_errors_total", "Websocket messages that failed to parse.", &feed.parse_errors),
+            ("woox_connects_total", "Websocket connections made.", &feed.connects),
+            ("woox_reconnects_total", "Websocket connections made to an already connected topic.", &feed.reconnects),
+            ("woox_queue_dropped_total", "Events dropped from a full book queue.", &feed.queue_dropped),
+            ("woox_queue_coalesced_total", "Events merged into another in a full book queue.", &feed.queue_coalesced),
+        ];
+        for (name, help, counter) in counters {
+            header(&mut out, name, "counter", help);
+            let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+        header(&mut out, "woox_open_connections", "gauge", "Websocket connections currently open.");
+        let _ = writeln!(out, "woox_open_connections {}", feed.open.load(Ordering::Relaxed));
+        header(&mut out, "woox_queue_depth", "gauge", "Events waiting in the book queues.");
+        let _ = writeln!(out, "woox_queue_depth {}", feed.queued.load(Ordering::Relaxed));
+        if let Some(skew) = self.clock.as_ref().and_then(|clock| clock.skew_ms()) {
+            header(&mut out, "woox_clock_skew_seconds", "gauge", "Estimated exchange clock minus local clock.");
+            let _ = writeln!(out, "woox_clock_skew_seconds {}", skew as f64 / 1000.0);
+        }
+
+        let books = self.books.lock().unwrap();
+        header(&mut out, "woox_deltas_applied_total", "counter", "Deltas applied to the book.");
+        for (symbol, book) in books.iter() {
+            let _ = writeln!(out, "woox_deltas_applied_total{{symbol=\"{}\"}} {}", symbol, book.deltas);
+        }
+        header(&mut out, "woox_resyncs_total", "counter", "Times the book was synced from a snapshot.");
+        for (symbol, book) in books.iter() {
+            let _ = writeln!(out, "woox_resyncs_total{{symbol=\"{}\"}} {}", symbol, book.resyncs);
+        }
+        header(&mut out, "woox_book_depth", "gauge", "Price levels on each side of the book.");
+        for (symbol, book) in books.iter() {
+            let _ = writeln!(out, "woox_book_depth{{symbol=\"{}\",side=\"bid\"}} {}", symbol, book.bid_depth);
+            let _ = writeln!(out, "woox_book_depth{{symbol=\"{}\",side=\"ask\"}} {}", symbol, book.ask_depth);
+        }
+        header(&mut out, "woox_best_bid", "gauge", "Best bid price.");
+        for (symbol, book) in books.iter() {
+            if let Some(price) = book.best_bid {
+                let _ = writeln!(out, "woox_best_bid{{symbol=\"{}\"}} {}", symbol, price);
+            }
+        }
+        header(&mut out, "woox_best_ask", "gauge", "Best ask price.");
+        for (symbol, book) in books.iter() {
+            if let Some(price) = book.best_ask {
+                let _ = writeln!(out, "woox_best_ask{{symbol=\"{}\"}} {}", symbol, price);
+            }
+        }
+
+        let windows = || {
+            books.iter().flat_map(|(symbol, book)| {
+                let summaries = book.liquidity.iter().map(|liquidity| liquidity.last.as_ref());
+                LIQUIDITY_WINDOWS.iter().zip(summaries).filter_map(move |(&(window, _), summary)| Some((symbol, window, summary?)))
+            })
+        };
+        header(&mut out, "woox_spread_avg_bps", "gauge", "Time weighted average spread over the last window.");
+        for (symbol, window, summary) in windows() {
+            let _ = writeln!(out, "woox_spread_avg_bps{{symbol=\"{}\",window=\"{}\"}} {}", symbol, window, summary.avg_spread_bps);
+        }
+        header(&mut out, "woox_top_depth_avg", "gauge", "Time weighted average quantity in the top levels of each side over the last window.");
+        for (symbol, window, summary) in windows() {
+            let labels = format!("symbol=\"{}\",window=\"{}\"", symbol, window);
+            let _ = writeln!(out, "woox_top_depth_avg{{{},side=\"bid\"}} {}", labels, summary.avg_bid_depth);
+            let _ = writeln!(out, "woox_top_depth_avg{{{},side=\"ask\"}} {}", labels, summary.avg_ask_depth);
+        }
+        header(&mut out, "woox_spread_above_ratio", "gauge", "Share of the last window the spread was wider than the threshold.");
+        for (symbol, window, summary) in windows() {
+            for above in &summary.spread_above {
+                let labels = format!("symbol=\"{}\",window=\"{}\",threshold_bps=\"{}\"", symbol, window, above.threshold_bps);
+                let _ = writeln!(out, "woox_spread_above_ratio{{{}}} {}", labels, above.ratio);
+            }
+        }
+
+        let name = "woox_processing_latency_seconds";
+        header(&mut out, name, "histogram", "Time from a delta being received to it being applied.");
+        for (symbol, book) in books.iter() {
+            let latency = &book.latency;
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(latency.buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "{}_bucket{{symbol=\"{}\",le=\"{}\"}} {}", name, symbol, bound, cumulative);
+            }
+            let _ = writeln!(out, "{}_bucket{{symbol=\"{}\",le=\"+Inf\"}} {}", name, symbol, latency.count);
+            let _ = writeln!(out, "{}_sum{{symbol=\"{}\"}} {}", name, symbol, latency.sum.as_secs_f64());
+            let _ = writeln!(out, "{}_count{{symbol=\"{}\"}} {}", name, symbol, latency.count);
+        }
+
+        let name = "woox_event_latency_seconds";
+        header(&mut out, name, "summary", "Latency of each stage from the exchange timestamp to a delta being applied.");
+        for (symbol, book) in books.iter() {
+            for stage in Stage::ALL {
+                let histogram = book.stages.histogram(stage);
+                let labels = format!("symbol=\"{}\",stage=\"{}\"", symbol, stage.label());
+                for quantile in QUANTILES {
+                    let seconds = book.stages.quantile(stage, quantile).as_secs_f64();
+                    let _ = writeln!(out, "{}{{{},quantile=\"{}\"}} {}", name, labels, quantile, seconds);
+                }
+                let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, histogram.mean() * histogram.len() as f64 / 1e6);
+                let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.len());
+            }
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// MetricsSink records the resyncs, deltas, depth, best prices and processing latency of
+// the books written to it into Metrics.
+pub struct MetricsSink {
+    metrics: Arc<Metrics>,
+}
+
+impl Sink for MetricsSink {
+    fn name(&self) -> &str {
+        "metrics"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, _ts: u64, book: &LocalOrderBook) -> SinkResult {
+        self.metrics.with_book(symbol, |metrics| {
+            metrics.resyncs += 1;
+            metrics.observe_book(book);
+        });
+        Ok(())
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        let latency = event.received_at.elapsed();
+        let applied_ms = self.metrics.clock.as_ref().map_or_else(now_ms, |clock| clock.exchange_now_ms());
+        self.metrics.with_book(symbol, |metrics| {
+            metrics.deltas += 1;
+            metrics.latency.observe(latency);
+            metrics.stages.record_at(event.ts, latency, applied_ms);
+            metrics.observe_book(book);
+        });
+        Ok(())
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
@@ -0,0 +1,130 @@
+use std::error::Error;
+use std::future::IntoFuture;
+use std::sync::Arc;
+
+use async_nats::jetstream::{self, context::PublishAckFuture};
+use serde::Serialize;
+use tokio::runtime::Runtime;
+
+use crate::exchange_api_types::WsTrade;
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::publish::{BookMessage, TradeMessage};
+use crate::sink::{Sink, SinkResult};
+
+// MAX_PENDING_ACKS is how many JetStream publishes may await their ack before the sink
+// waits for them.
+const MAX_PENDING_ACKS: usize = 1000;
+
+// NatsConfig configures the server and subjects a NatsSink publishes to. Book messages are
+// published to <prefix>.<symbol>.book and trades to <prefix>.<symbol>.trades.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NatsConfig {
+    // url is the NATS server, e.g. nats://127.0.0.1:4222.
+    pub url: String,
+    pub prefix: String,
+    // stream, if set, persists the published subjects in the JetStream stream of that name,
+    // created if it doesn't exist. Its acks are checked every MAX_PENDING_ACKS publishes
+    // and on flush.
+    pub stream: Option<String>,
+}
+
+impl NatsConfig {
+    pub fn new(url: &str) -> Self {
+        Self { url: url.to_string(), prefix: "md.woox".to_string(), stream: None }
+    }
+}
+
+// NatsSink publishes snapshots, deltas and trades to NATS subjects as JSON publish protocol
+// messages, optionally persisted in a JetStream stream. Clones share the connection and
+// its runtime, each awaiting the acks of only its own publishes.
+pub struct NatsSink {
+    runtime: Arc<Runtime>,
+    client: async_nats::Client,
+    jetstream: Option<jetstream::Context>,
+    pending_acks: Vec<PublishAckFuture>,
+    prefix: String,
+}
+
+impl NatsSink {
+    pub fn connect(config: NatsConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        // The client's connection is driven by a background task, so the runtime needs a
+        // worker of its own rather than running only inside block_on.
+        let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build()?;
+        let client = runtime.block_on(async_nats::connect(&config.url))?;
+        let jetstream = match &config.stream {
+            Some(stream) => {
+                let context = jetstream::new(client.clone());
+                runtime.block_on(context.get_or_create_stream(jetstream::stream::Config {
+                    name: stream.clone(),
+                    subjects: vec![format!("{}.>", config.prefix)],
+                    ..Default::default()
+                }))?;
+                Some(context)
+            }
+            None => None,
+        };
+        Ok(Self { runtime: Arc::new(runtime), client, jetstream, pending_acks: Vec::new(), prefix: config.prefix })
+    }
+
+    fn publish<T: Serialize>(&mut self, symbol: &str, kind: &str, message: &T) -> SinkResult {
+        let subject = format!("{}.{}.{}", self.prefix, symbol, kind);
+        let payload = serde_json::to_vec(message)?.into();
+        match &self.jetstream {
+            Some(jetstream) => {
+                let ack = self.runtime.block_on(jetstream.publish(subject, payload))?;
+                self.pending_acks.push(ack);
+                if self.pending_acks.len() >= MAX_PENDING_ACKS {
+                    self.wait_acks()?;
+                }
+            }
+            None => self.runtime.block_on(self.client.publish(subject, payload))?,
+        }
+        Ok(())
+    }
+
+    // wait_acks waits for the acks of the pending JetStream publishes, failing on the first
+    // one the stream rejected.
+    fn wait_acks(&mut self) -> SinkResult {
+        for ack in self.pending_acks.drain(..) {
+            self.runtime.block_on(ack.into_future())?;
+        }
+        Ok(())
+    }
+}
+
+impl Clone for NatsSink {
+    fn clone(&self) -> Self {
+        Self {
+            runtime: self.runtime.clone(),
+            client: self.client.clone(),
+            jetstream: self.jetstream.clone(),
+            pending_acks: Vec::new(),
+            prefix: self.prefix.clone(),
+        }
+    }
+}
+
+impl Sink for NatsSink {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        self.publish(symbol, "book", &BookMessage::snapshot(symbol, ts, book))
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        self.publish(symbol, "book", &BookMessage::update(symbol, event))
+    }
+
+    fn record_trade(&mut self, trade: &WsTrade) -> SinkResult {
+        self.publish(&trade.symbol, "trades", &TradeMessage::from(trade))
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.wait_acks()?;
+        self.runtime.block_on(self.client.flush())?;
+        Ok(())
+    }
+}
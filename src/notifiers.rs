@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::{AlertEvent, Notifier, NotifyResult};
+use crate::http_client;
+
+// TIMEOUT bounds each request to a notifier's endpoint, so a hung endpoint doesn't hold up
+// the alerts queued behind it.
+const TIMEOUT: Duration = Duration::from_secs(10);
+const TELEGRAM_API: &str = "https://api.telegram.org";
+
+// NotifierConfig is a notifier in the alert config, e.g.
+// {"kind": "slack", "webhook_url": "https://hooks.slack.com/services/..."}
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotifierConfig {
+    // Webhook posts each event as JSON to url.
+    Webhook { url: String },
+    // Slack posts each event to a Slack incoming webhook.
+    Slack { webhook_url: String },
+    // Telegram sends each event to chat_id through the bot API as bot_token's bot.
+    Telegram { bot_token: String, chat_id: String },
+}
+
+impl NotifierConfig {
+    pub fn build(&self) -> Box<dyn Notifier> {
+        match self {
+            NotifierConfig::Webhook { url } => Box::new(WebhookNotifier::new(url)),
+            NotifierConfig::Slack { webhook_url } => Box::new(SlackNotifier::new(webhook_url)),
+            NotifierConfig::Telegram { bot_token, chat_id } => Box::new(TelegramNotifier::new(bot_token, chat_id)),
+        }
+    }
+}
+
+// WebhookNotifier posts each alert event as JSON to a generic HTTP webhook, e.g.
+// {"rule": "wide", "symbol": "PERP_ETH_USDT", "kind": "triggered", "message": "...", "ts": 0}
+pub struct WebhookNotifier {
+    url: String,
+    http: Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> Self {
+        Self { url: url.to_string(), http: http_client::shared() }
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn notify(&mut self, event: &AlertEvent) -> NotifyResult {
+        post(&self.http, &self.url, event)
+    }
+}
+
+#[derive(Serialize)]
+struct SlackMessage<'a> {
+    text: &'a str,
+}
+
+// SlackNotifier posts each alert event to a Slack incoming webhook, as a line of text.
+pub struct SlackNotifier {
+    webhook_url: String,
+    http: Client,
+}
+
+impl SlackNotifier {
+    pub fn new(webhook_url: &str) -> Self {
+        Self { webhook_url: webhook_url.to_string(), http: http_client::shared() }
+    }
+}
+
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    fn notify(&mut self, event: &AlertEvent) -> NotifyResult {
+        post(&self.http, &self.webhook_url, &SlackMessage { text: &event.to_string() })
+    }
+}
+
+#[derive(Serialize)]
+struct TelegramMessage<'a> {
+    chat_id: &'a str,
+    text: &'a str,
+}
+
+// TelegramNotifier sends each alert event to a chat through the Telegram bot API.
+pub struct TelegramNotifier {
+    url: String,
+    chat_id: String,
+    http: Client,
+}
+
+impl TelegramNotifier {
+    pub fn new(bot_token: &str, chat_id: &str) -> Self {
+        let url = format!("{}/bot{}/sendMessage", TELEGRAM_API, bot_token);
+        Self { url, chat_id: chat_id.to_string(), http: http_client::shared() }
+    }
+}
+
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    fn notify(&mut self, event: &AlertEvent) -> NotifyResult {
+        post(&self.http, &self.url, &TelegramMessage { chat_id: &self.chat_id, text: &event.to_string() })
+    }
+}
+
+// post posts body as JSON to url, failing on an error status. The url is left out of the
+// error, since the bot API's carries the bot token.
+fn post<T: Serialize + ?Sized>(http: &Client, url: &str, body: &T) -> NotifyResult {
+    http.post(url)
+        .timeout(TIMEOUT)
+        .json(body)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.without_url())?;
+    Ok(())
+}
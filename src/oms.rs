@@ -0,0 +1,474 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{info, warn};
+
+use crate::exchange_api_types::Side;
+use crate::order::{Fill, Liquidity, OrderId, OrderRequest, OrderStatus, OrderUpdate};
+use crate::orderbook::LocalOrderBook;
+use crate::pnl::Position;
+use crate::rest::RestError;
+use crate::risk::RiskCheck;
+use crate::trading::{ExchangeOrder, ExecutionReport, TradingClient};
+use crate::units::{Notional, Price, Qty};
+
+// ManagedOrder is an order the OrderManager placed, as it currently believes it to be.
+// client_order_id is the manager's id for it, and order_id the venue's once acked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManagedOrder {
+    pub client_order_id: u64,
+    pub order_id: Option<OrderId>,
+    pub request: OrderRequest,
+    pub status: OrderStatus,
+    pub filled: Qty,
+    pub average_price: Option<Price>,
+    pub updated_ts: u64,
+}
+
+impl ManagedOrder {
+    fn remaining(&self) -> Qty {
+        (self.request.quantity - self.filled).max(Qty::ZERO)
+    }
+
+    // update returns an OrderUpdate for the order, identified by its client order id.
+    fn update(&self, fill: Option<Fill>) -> OrderUpdate {
+        OrderUpdate {
+            order_id: self.client_order_id,
+            symbol: self.request.symbol.clone(),
+            status: self.status.clone(),
+            filled: self.filled,
+            remaining: self.remaining(),
+            average_price: self.average_price,
+            fill,
+        }
+    }
+
+    // advance moves the order to the state the venue reported, returning false if the report
+    // is older than what is already known: the order has already reached a final status, or
+    // more of it was already known to be filled. Reports can arrive out of order between the
+    // private stream and REST queries, so the state only ever moves forward.
+    fn advance(&mut self, order: &ExchangeOrder) -> bool {
+        if self.status.is_final() || order.filled < self.filled {
+            return false;
+        }
+        if order.filled == self.filled && order.status == self.status {
+            return false;
+        }
+        self.order_id = Some(order.order_id);
+        self.status = order.status.clone();
+        self.filled = order.filled;
+        self.average_price = order.average_price.or(self.average_price);
+        self.updated_ts = order.ts;
+        true
+    }
+}
+
+// OrderManager tracks every order placed through a TradingClient through its lifecycle,
+// from the execution reports of the private stream, and the positions their fills add up
+// to. reconcile checks its view against the venue's REST API, catching up on reports the
+// stream missed.
+pub struct OrderManager<C: TradingClient> {
+    client: C,
+    next_client_order_id: u64,
+    orders: BTreeMap<u64, ManagedOrder>,
+    // by_order_id maps the venue's order ids to client order ids.
+    by_order_id: HashMap<OrderId, u64>,
+    positions: HashMap<String, Position>,
+    risk: Option<RiskCheck>,
+}
+
+impl<C: TradingClient> OrderManager<C> {
+    // new manages the orders placed through client. Client order ids start at the current
+    // time in ms, so they don't repeat those of a previous run.
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            next_client_order_id: now_ms(),
+            orders: BTreeMap::new(),
+            by_order_id: HashMap::new(),
+            positions: HashMap::new(),
+            risk: None,
+        }
+    }
+
+    // with_risk checks every order submitted against risk before it is placed.
+    pub fn with_risk(mut self, risk: RiskCheck) -> Self {
+        self.risk = Some(risk);
+        self
+    }
+
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    // order returns the order with client order id, if the manager placed it.
+    pub fn order(&self, client_order_id: u64) -> Option<&ManagedOrder> {
+        self.orders.get(&client_order_id)
+    }
+
+    // open_orders returns the orders not yet in a final status, oldest first.
+    pub fn open_orders(&self) -> impl Iterator<Item = &ManagedOrder> {
+        self.orders.values().filter(|order| !order.status.is_final())
+    }
+
+    pub fn position(&self, symbol: &str) -> Option<&Position> {
+        self.positions.get(symbol)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = (&str, &Position)> {
+        self.positions.iter().map(|(symbol, position)| (symbol.as_str(), position))
+    }
+
+    // submit places request, returning the update for the new order. book is the symbol's
+    // local book, whose mid the risk check's price band is checked against. An order failing
+    // the risk check or that the client fails to place is kept as rejected.
+    pub fn submit(&mut self, request: OrderRequest, book: Option<&LocalOrderBook>) -> OrderUpdate {
+        let client_order_id = self.next_client_order_id;
+        self.next_client_order_id += 1;
+        let mut order = ManagedOrder {
+            client_order_id,
+            order_id: None,
+            request,
+            status: OrderStatus::New,
+            filled: Qty::ZERO,
+            average_price: None,
+            updated_ts: now_ms(),
+        };
+        let reference = book.and_then(LocalOrderBook::mid_price);
+        let checked = match &self.risk {
+            Some(risk) => risk.check(&order.request, self.exposure(&order.request.symbol, order.request.side), reference),
+            None => Ok(()),
+        };
+        if let Err(violation) = checked {
+            warn!(client_order_id, %violation, "Order failed the risk check");
+            order.status = OrderStatus::Rejected(violation.to_string());
+            let update = order.update(None);
+            self.orders.insert(client_order_id, order);
+            return update;
+        }
+
+        match self.client.place_order(&order.request, client_order_id) {
+            Ok(order_id) => {
+                order.order_id = Some(order_id);
+                self.by_order_id.insert(order_id, client_order_id);
+            }
+            Err(e) => {
+                warn!(client_order_id, error = %e, "Order rejected");
+                order.status = OrderStatus::Rejected(e.to_string());
+            }
+        }
+        let update = order.update(None);
+        self.orders.insert(client_order_id, order);
+        update
+    }
+
+    // cancel requests that the order with client order id is cancelled. The order stays open
+    // until the venue reports it cancelled.
+    pub fn cancel(&self, client_order_id: u64) -> Result<(), RestError> {
+        let order = self.orders.get(&client_order_id).filter(|order| !order.status.is_final());
+        match order.and_then(|order| Some((order.order_id?, &order.request.symbol))) {
+            Some((order_id, symbol)) => self.client.cancel_order(symbol, order_id),
+            None => Err(RestError::Api(format!("no open order {}", client_order_id))),
+        }
+    }
+
+    // cancel_all requests that every open order in symbol, or in every symbol if None, is
+    // cancelled, including any the manager didn't place.
+    pub fn cancel_all(&self, symbol: Option<&str>) -> Result<(), RestError> {
+        self.client.cancel_all(symbol)
+    }
+
+    // on_execution_report applies a report from the private stream, returning the update it
+    // made to the order, or None if it was stale or for an order the manager didn't place.
+    // A fill only counts if it takes the order's filled quantity past what is known, so a
+    // fill already caught up on by reconcile isn't added to the position twice.
+    pub fn on_execution_report(&mut self, report: &ExecutionReport) -> Option<OrderUpdate> {
+        let client_order_id = self.client_order_id(&report.order)?;
+        let order = self.orders.get_mut(&client_order_id)?;
+        let filled_more = report.order.filled > order.filled;
+        if !order.advance(&report.order) {
+            return None;
+        }
+        let fill = report.fill.as_ref().filter(|_| filled_more).map(|fill| Fill {
+            order_id: client_order_id,
+            symbol: report.order.symbol.clone(),
+            side: report.order.side,
+            price: fill.price,
+            quantity: fill.quantity,
+            fee: fill.fee,
+            liquidity: fill.liquidity,
+            ts: report.order.ts,
+        });
+        let update = order.update(fill.clone());
+        if let Some(fill) = &fill {
+            self.apply_fill(fill);
+        }
+        Some(update)
+    }
+
+    // reconcile queries the venue's open orders and the orders the manager believes open that
+    // the venue no longer lists, bringing each up to date. Fills the stream missed are added to
+    // the positions at the price they imply, without fees. It returns the updates it made.
+    pub fn reconcile(&mut self) -> Result<Vec<OrderUpdate>, RestError> {
+        let venue: Vec<ExchangeOrder> = self.client.open_orders(None)?;
+        let listed: HashSet<OrderId> = venue.iter().map(|order| order.order_id).collect();
+        let mut reports = venue;
+        let missing: Vec<OrderId> = self
+            .open_orders()
+            .filter_map(|order| order.order_id)
+            .filter(|order_id| !listed.contains(order_id))
+            .collect();
+        for order_id in missing {
+            reports.push(self.client.order(order_id)?);
+        }
+
+        let mut updates = Vec::new();
+        for report in reports {
+            let Some(client_order_id) = self.client_order_id(&report) else {
+                info!(order_id = report.order_id, symbol = %report.symbol, "Open order not placed by this manager");
+                continue;
+            };
+            let Some(order) = self.orders.get_mut(&client_order_id) else { continue };
+            let (filled, notional) = (order.filled, order.average_price.map_or(Notional::ZERO, |price| price * order.filled));
+            if !order.advance(&report) {
+                continue;
+            }
+            warn!(client_order_id, order_id = report.order_id, status = %report.status, "Reconciled order");
+
+            let missed = order.filled - filled;
+            let missed_notional = order.average_price.map_or(Notional::ZERO, |price| price * order.filled) - notional;
+            // The venue doesn't say whether a missed fill made or took liquidity.
+            let fill = missed_notional.checked_price(missed).filter(|_| missed > Qty::ZERO).map(|price| Fill {
+                order_id: client_order_id,
+                symbol: report.symbol.clone(),
+                side: report.side,
+                price,
+                quantity: missed,
+                fee: Notional::ZERO,
+                liquidity: Liquidity::Taker,
+                ts: report.ts,
+            });
+            let update = order.update(fill.clone());
+            if let Some(fill) = &fill {
+                self.apply_fill(fill);
+            }
+            updates.push(update);
+        }
+        Ok(updates)
+    }
+
+    // exposure returns the position in symbol plus the unfilled quantity of the open orders
+    // on side, signed.
+    fn exposure(&self, symbol: &str, side: Side) -> Qty {
+        let position = self.positions.get(symbol).map_or(Qty::ZERO, |position| position.quantity);
+        let open: Qty = self
+            .open_orders()
+            .filter(|order| order.request.symbol == symbol && order.request.side == side)
+            .map(ManagedOrder::remaining)
+            .sum();
+        match side {
+            Side::Buy => position + open,
+            Side::Sell => position - open,
+        }
+    }
+
+    // client_order_id returns the client order id of a venue order, if the manager placed it.
+    fn client_order_id(&mut self, order: &ExchangeOrder) -> Option<u64> {
+        if let Some(&client_order_id) = self.by_order_id.get(&order.order_id) {
+            return Some(client_order_id);
+        }
+        // A report can beat the place order response, so match on the client order id too.
+        self.orders.contains_key(&order.client_order_id).then(|| {
+            self.by_order_id.insert(order.order_id, order.client_order_id);
+            order.client_order_id
+        })
+    }
+
+    fn apply_fill(&mut self, fill: &Fill) {
+        let position = self.positions.entry(fill.symbol.clone()).or_default();
+        position.apply(fill.side, fill.price, fill.quantity, fill.fee);
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::trading::ExecutionFill;
+
+    const SYMBOL: &str = "SPOT_BTC_USDT";
+
+    // Venue acks every order placed with the next order id from 100, unless refusing, and
+    // answers REST queries with the orders set on it.
+    #[derive(Default)]
+    struct Venue {
+        placed: Mutex<Vec<u64>>,
+        refusing: bool,
+        open: Mutex<Vec<ExchangeOrder>>,
+        orders: Mutex<HashMap<OrderId, ExchangeOrder>>,
+    }
+
+    impl TradingClient for Venue {
+        fn place_order(&self, _request: &OrderRequest, client_order_id: u64) -> Result<OrderId, RestError> {
+            if self.refusing {
+                return Err(RestError::Api("insufficient balance".to_string()));
+            }
+            let mut placed = self.placed.lock().unwrap();
+            placed.push(client_order_id);
+            Ok(99 + placed.len() as OrderId)
+        }
+
+        fn cancel_order(&self, _symbol: &str, _order_id: OrderId) -> Result<(), RestError> {
+            Ok(())
+        }
+
+        fn cancel_all(&self, _symbol: Option<&str>) -> Result<(), RestError> {
+            Ok(())
+        }
+
+        fn order(&self, order_id: OrderId) -> Result<ExchangeOrder, RestError> {
+            self.orders.lock().unwrap().get(&order_id).cloned().ok_or_else(|| RestError::Api(format!("no order {}", order_id)))
+        }
+
+        fn open_orders(&self, _symbol: Option<&str>) -> Result<Vec<ExchangeOrder>, RestError> {
+            Ok(self.open.lock().unwrap().clone())
+        }
+    }
+
+    // buy places a limit buy of 2 at 100, returning its client order id.
+    fn buy(manager: &mut OrderManager<Venue>) -> u64 {
+        manager.submit(OrderRequest::limit(SYMBOL, Side::Buy, Qty::new(2.0), Price::new(100.0)), None).order_id
+    }
+
+    fn venue_order(order_id: OrderId, client_order_id: u64, status: OrderStatus, filled: f64, average_price: Option<f64>) -> ExchangeOrder {
+        ExchangeOrder {
+            order_id,
+            client_order_id,
+            symbol: SYMBOL.to_string(),
+            side: Side::Buy,
+            quantity: Qty::new(2.0),
+            price: Some(Price::new(100.0)),
+            status,
+            filled: Qty::new(filled),
+            average_price: average_price.map(Price::new),
+            ts: 1,
+        }
+    }
+
+    // report reports order, with a fill of quantity at price if quantity isn't zero.
+    fn report(order: ExchangeOrder, price: f64, quantity: f64) -> ExecutionReport {
+        let fill = (quantity > 0.0).then(|| ExecutionFill {
+            trade_id: 1,
+            price: Price::new(price),
+            quantity: Qty::new(quantity),
+            fee: Notional::new(0.01),
+            liquidity: Liquidity::Maker,
+        });
+        ExecutionReport { order, fill }
+    }
+
+    fn position(manager: &OrderManager<Venue>) -> f64 {
+        manager.position(SYMBOL).map_or(0.0, |position| position.quantity.value())
+    }
+
+    #[test]
+    fn follows_an_order_from_new_through_fills_to_filled() {
+        let mut manager = OrderManager::new(Venue::default());
+        let id = buy(&mut manager);
+        let order = manager.order(id).unwrap();
+        assert_eq!((order.status.clone(), order.order_id), (OrderStatus::New, Some(100)));
+
+        let update = manager.on_execution_report(&report(venue_order(100, id, OrderStatus::PartiallyFilled, 1.5, Some(100.0)), 100.0, 1.5)).unwrap();
+        assert_eq!(update.status, OrderStatus::PartiallyFilled);
+        assert_eq!((update.filled, update.remaining), (Qty::new(1.5), Qty::new(0.5)));
+        assert_eq!(update.fill.map(|fill| (fill.quantity, fill.liquidity)), Some((Qty::new(1.5), Liquidity::Maker)));
+        assert_eq!(position(&manager), 1.5);
+
+        let update = manager.on_execution_report(&report(venue_order(100, id, OrderStatus::Filled, 2.0, Some(99.9)), 99.6, 0.5)).unwrap();
+        assert_eq!((update.status, update.remaining), (OrderStatus::Filled, Qty::ZERO));
+        assert_eq!(position(&manager), 2.0);
+        assert_eq!(manager.open_orders().count(), 0);
+        assert!(manager.cancel(id).is_err());
+    }
+
+    #[test]
+    fn ignores_reports_behind_what_is_known() {
+        let mut manager = OrderManager::new(Venue::default());
+        let id = buy(&mut manager);
+        let partial = report(venue_order(100, id, OrderStatus::PartiallyFilled, 1.0, Some(100.0)), 100.0, 1.0);
+        assert!(manager.on_execution_report(&partial).is_some());
+        // A repeat, and a report from before the fill, change nothing.
+        assert!(manager.on_execution_report(&partial).is_none());
+        assert!(manager.on_execution_report(&report(venue_order(100, id, OrderStatus::New, 0.0, None), 0.0, 0.0)).is_none());
+        assert_eq!(position(&manager), 1.0);
+
+        assert!(manager.on_execution_report(&report(venue_order(100, id, OrderStatus::Cancelled, 1.0, Some(100.0)), 0.0, 0.0)).is_some());
+        // Nothing moves an order on from a final status.
+        assert!(manager.on_execution_report(&report(venue_order(100, id, OrderStatus::Filled, 2.0, Some(100.0)), 100.0, 1.0)).is_none());
+        assert_eq!(manager.order(id).unwrap().status, OrderStatus::Cancelled);
+        assert_eq!(position(&manager), 1.0);
+    }
+
+    #[test]
+    fn keeps_an_order_the_venue_refused_as_rejected() {
+        let mut manager = OrderManager::new(Venue { refusing: true, ..Venue::default() });
+        let id = buy(&mut manager);
+        let order = manager.order(id).unwrap();
+        assert!(matches!(&order.status, OrderStatus::Rejected(reason) if reason.contains("insufficient balance")), "{:?}", order.status);
+        assert_eq!(order.order_id, None);
+        assert_eq!(manager.open_orders().count(), 0);
+    }
+
+    #[test]
+    fn matches_reports_by_client_order_id_and_ignores_other_orders() {
+        let mut manager = OrderManager::new(Venue::default());
+        let id = buy(&mut manager);
+        // A report can carry an order id the manager hasn't seen, if it beat the response.
+        assert!(manager.on_execution_report(&report(venue_order(7, id, OrderStatus::PartiallyFilled, 1.0, Some(100.0)), 100.0, 1.0)).is_some());
+        assert!(manager.on_execution_report(&report(venue_order(8, 1, OrderStatus::Filled, 2.0, Some(100.0)), 100.0, 2.0)).is_none());
+        assert_eq!(position(&manager), 1.0);
+    }
+
+    #[test]
+    fn reconciles_fills_the_stream_missed_from_rest() {
+        let mut manager = OrderManager::new(Venue::default());
+        let (resting, gone) = (buy(&mut manager), buy(&mut manager));
+        let venue = manager.client();
+        // The first order is still listed, part filled. The second has filled and left the
+        // open orders, so is queried on its own. 555 wasn't placed by the manager.
+        venue.open.lock().unwrap().extend([
+            venue_order(100, resting, OrderStatus::PartiallyFilled, 1.0, Some(100.0)),
+            venue_order(555, 1, OrderStatus::New, 0.0, None),
+        ]);
+        venue.orders.lock().unwrap().insert(101, venue_order(101, gone, OrderStatus::Filled, 2.0, Some(99.0)));
+
+        let updates = manager.reconcile().unwrap();
+        assert_eq!(updates.iter().map(|update| (update.order_id, update.status.clone())).collect::<Vec<_>>(), vec![(resting, OrderStatus::PartiallyFilled), (gone, OrderStatus::Filled)]);
+        let fill = updates[1].fill.as_ref().unwrap();
+        assert_eq!((fill.price, fill.quantity, fill.fee, fill.liquidity), (Price::new(99.0), Qty::new(2.0), Notional::ZERO, Liquidity::Taker));
+        assert_eq!(position(&manager), 3.0);
+        // Nothing has changed since, so reconciling again makes no updates.
+        assert!(manager.reconcile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn counts_a_fill_once_between_rest_and_the_stream() {
+        let mut manager = OrderManager::new(Venue::default());
+        let id = buy(&mut manager);
+        manager.client().open.lock().unwrap().push(venue_order(100, id, OrderStatus::PartiallyFilled, 1.0, Some(100.0)));
+        assert_eq!(manager.reconcile().unwrap().len(), 1);
+        assert_eq!(position(&manager), 1.0);
+
+        // The stream's late report of the fill reconcile caught up on isn't counted again,
+        // and the next fill is.
+        assert!(manager.on_execution_report(&report(venue_order(100, id, OrderStatus::PartiallyFilled, 1.0, Some(100.0)), 100.0, 1.0)).is_none());
+        assert_eq!(position(&manager), 1.0);
+        assert!(manager.on_execution_report(&report(venue_order(100, id, OrderStatus::Filled, 2.0, Some(100.0)), 100.0, 1.0)).is_some());
+        assert_eq!(position(&manager), 2.0);
+    }
+}
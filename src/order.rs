@@ -0,0 +1,111 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::exchange_api_types::Side;
+use crate::units::{Notional, Price, Qty};
+
+// OrderId identifies an order to whatever executes it.
+pub type OrderId = u64;
+
+// OrderKind is how an order is priced.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum OrderKind {
+    // Market fills immediately against the book at whatever prices it takes.
+    Market,
+    // Limit fills at price or better. Whatever doesn't fill on arrival rests in the book.
+    Limit { price: Price },
+}
+
+// OrderRequest is an order to be placed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderRequest {
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: Qty,
+    pub kind: OrderKind,
+}
+
+impl OrderRequest {
+    pub fn market(symbol: &str, side: Side, quantity: Qty) -> Self {
+        Self { symbol: symbol.to_string(), side, quantity, kind: OrderKind::Market }
+    }
+
+    pub fn limit(symbol: &str, side: Side, quantity: Qty, price: Price) -> Self {
+        Self { symbol: symbol.to_string(), side, quantity, kind: OrderKind::Limit { price } }
+    }
+
+    // price returns the limit price, if the order has one.
+    pub fn price(&self) -> Option<Price> {
+        match self.kind {
+            OrderKind::Market => None,
+            OrderKind::Limit { price } => Some(price),
+        }
+    }
+}
+
+// OrderStatus is where an order is in its lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum OrderStatus {
+    // New is an accepted order that hasn't filled.
+    New,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    // Rejected is an order that wasn't accepted, with why.
+    Rejected(String),
+}
+
+impl OrderStatus {
+    // is_final returns true once the order can no longer change.
+    pub fn is_final(&self) -> bool {
+        matches!(self, OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Rejected(_))
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderStatus::New => write!(f, "new"),
+            OrderStatus::PartiallyFilled => write!(f, "partially filled"),
+            OrderStatus::Filled => write!(f, "filled"),
+            OrderStatus::Cancelled => write!(f, "cancelled"),
+            OrderStatus::Rejected(reason) => write!(f, "rejected: {}", reason),
+        }
+    }
+}
+
+// Liquidity is whether a fill added liquidity to the book or took it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+// Fill is part or all of an order executing at one price.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fill {
+    pub order_id: OrderId,
+    pub symbol: String,
+    pub side: Side,
+    pub price: Price,
+    pub quantity: Qty,
+    // fee is what the fill cost in quote currency, negative for a rebate.
+    pub fee: Notional,
+    pub liquidity: Liquidity,
+    pub ts: u64,
+}
+
+// OrderUpdate reports a change to an order: its status and fills so far, and the fill that
+// caused the change, if one did.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderUpdate {
+    pub order_id: OrderId,
+    pub symbol: String,
+    pub status: OrderStatus,
+    pub filled: Qty,
+    pub remaining: Qty,
+    // average_price is the average price of the fills so far, if any.
+    pub average_price: Option<Price>,
+    pub fill: Option<Fill>,
+}
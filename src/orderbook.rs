@@ -1,82 +1,332 @@
 use std::collections::BTreeMap;
-use ordered_float::OrderedFloat;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
-use crate::exchange_api_types::SnapshotData;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use crate::exchange_api_types::{OrderBookDelta, RestQuote, Side, SnapshotData};
+#[cfg(feature = "native")]
+use crate::render::Precision;
+use crate::units::{Notional, Price, Qty};
+
+// FillEstimate is what an order of requested quantity taking the book would fill at: the
+// quantity the book could fill, the average and worst prices it fills at, and the slippage
+// of the average price from the touch in bps, positive when worse. worst_price is how far
+// the order walks the book, its market impact.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FillEstimate {
+    pub side: Side,
+    pub requested: Qty,
+    pub filled: Qty,
+    pub average_price: Price,
+    pub worst_price: Price,
+    pub slippage_bps: f64,
+    // levels is how many levels the order takes from.
+    pub levels: usize,
+}
+
+impl FillEstimate {
+    // is_complete returns true if the book could fill the whole requested quantity.
+    pub fn is_complete(&self) -> bool {
+        self.filled >= self.requested
+    }
+
+    // unfilled returns the requested quantity the book couldn't fill.
+    pub fn unfilled(&self) -> Qty {
+        (self.requested - self.filled).max(Qty::ZERO)
+    }
+}
+
+// OrderBook is a book of price levels that snapshots and deltas are applied to, implemented
+// by LocalOrderBook over BTreeMaps and LadderBook over sorted vecs.
+pub trait OrderBook {
+    // apply_snapshot replaces the book with the levels of a snapshot.
+    fn apply_snapshot(&mut self, data: SnapshotData);
+
+    // apply_delta sets the levels of a delta, removing those with a quantity of 0.
+    fn apply_delta(&mut self, delta: &OrderBookDelta);
+
+    // apply_deltas applies deltas in order, for a book that can apply a batch at once.
+    fn apply_deltas<'a>(&mut self, deltas: impl IntoIterator<Item = &'a OrderBookDelta>) {
+        for delta in deltas {
+            self.apply_delta(delta);
+        }
+    }
+
+    // bids returns the bid levels, best (highest) price first.
+    fn bids(&self) -> impl Iterator<Item = (Price, Qty)> + '_;
+
+    // asks returns the ask levels, best (lowest) price first.
+    fn asks(&self) -> impl Iterator<Item = (Price, Qty)> + '_;
+
+    fn best_bid(&self) -> Option<(Price, Qty)>;
+
+    fn best_ask(&self) -> Option<(Price, Qty)>;
+
+    // quantity_at returns the quantity resting at price on side, zero if there's no level.
+    fn quantity_at(&self, side: Side, price: Price) -> Qty;
+
+    // mid_price returns the midpoint between the best bid and ask, or None if either side is empty.
+    fn mid_price(&self) -> Option<Price> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) => Some(bid.midpoint(ask)),
+            _ => None,
+        }
+    }
+}
 
 // LocalOrderBook contains the current bids and asks for a symbol.
 // OrderBookDeltas can be applied to update the order book in real time.
+#[derive(Default)]
 pub struct LocalOrderBook {
-    bids: BTreeMap<OrderedFloat<f64>, f64>,
-    asks: BTreeMap<OrderedFloat<f64>, f64>,
+    bids: BTreeMap<Price, Qty>,
+    asks: BTreeMap<Price, Qty>,
+    // last_update is the exchange timestamp of the last update and when it was recorded.
+    last_update: Option<(u64, Instant)>,
 }
 
 impl LocalOrderBook {
     pub fn new() -> Self {
         Self {
             bids: BTreeMap::new(),
-            asks: BTreeMap::new()
+            asks: BTreeMap::new(),
+            last_update: None,
         }
     }
 
+    // mark_updated records that the book was last updated by a snapshot or delta with
+    // exchange timestamp ts.
+    pub fn mark_updated(&mut self, ts: u64) {
+        self.last_update = Some((ts, Instant::now()));
+    }
+
+    // last_update_ts returns the exchange timestamp of the last update, if any.
+    pub fn last_update_ts(&self) -> Option<u64> {
+        self.last_update.map(|(ts, _)| ts)
+    }
+
+    // is_stale returns true if the book hasn't been updated within max_age, measured on
+    // the local clock, or has never been updated.
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        self.last_update.is_none_or(|(_, at)| at.elapsed() > max_age)
+    }
+
     // apply_snapshot clears the orderbook and syncs the state to the given snapshot
     pub fn apply_snapshot(&mut self, data: SnapshotData) {
         self.bids.clear();
         self.asks.clear();
 
         for quote in data.bids {
-            self.bids.insert(OrderedFloat(quote.price), quote.quantity);
+            self.bids.insert(Price::new(quote.price), Qty::new(quote.quantity));
         }
 
         for quote in data.asks {
-            self.asks.insert(OrderedFloat(quote.price), quote.quantity);
+            self.asks.insert(Price::new(quote.price), Qty::new(quote.quantity));
+        }
+    }
+
+    // to_snapshot returns the current state of the order book as snapshot data.
+    pub fn to_snapshot(&self) -> SnapshotData {
+        let quote = |(price, quantity): (Price, Qty)| RestQuote { price: price.value(), quantity: quantity.value() };
+        SnapshotData {
+            bids: self.bids().map(quote).collect(),
+            asks: self.asks().map(quote).collect(),
         }
     }
 
     // apply_delta applies the order book delta to the local order book.
     // It will remove bids and asks with quantities set to 0.
-    pub fn apply_delta(&mut self, delta: crate::OrderBookDelta) {
-        for quote in delta.bids {
+    pub fn apply_delta(&mut self, delta: &OrderBookDelta) {
+        for quote in &delta.bids {
             if quote.quantity == 0.0 {
-                self.bids.remove(&OrderedFloat(quote.price));
+                self.bids.remove(&Price::new(quote.price));
             } else {
-                self.bids.insert(OrderedFloat(quote.price), quote.quantity);
+                self.bids.insert(Price::new(quote.price), Qty::new(quote.quantity));
             }
         }
         
-        for quote in delta.asks {
+        for quote in &delta.asks {
             if quote.quantity == 0.0 {
-                self.asks.remove(&OrderedFloat(quote.price));
+                self.asks.remove(&Price::new(quote.price));
             } else {
-                self.asks.insert(OrderedFloat(quote.price), quote.quantity);
+                self.asks.insert(Price::new(quote.price), Qty::new(quote.quantity));
             }
         }
     }
 
-    // print_top_5 will print the top 5 bids and asks in the order book.
-    pub fn print_top_5(&self) {
+    // apply_deltas applies the deltas to the local order book in order.
+    pub fn apply_deltas<'a>(&mut self, deltas: impl IntoIterator<Item = &'a OrderBookDelta>) {
+        for delta in deltas {
+            self.apply_delta(delta);
+        }
+    }
+
+    // bids returns the (price, quantity) bid levels, best (highest) price first.
+    pub fn bids(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        self.bids.iter().rev().map(|(&price, &quantity)| (price, quantity))
+    }
+
+    // asks returns the (price, quantity) ask levels, best (lowest) price first.
+    pub fn asks(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        self.asks.iter().map(|(&price, &quantity)| (price, quantity))
+    }
+
+    // best_bid returns the highest bid level, if any.
+    pub fn best_bid(&self) -> Option<(Price, Qty)> {
+        self.bids().next()
+    }
+
+    // best_ask returns the lowest ask level, if any.
+    pub fn best_ask(&self) -> Option<(Price, Qty)> {
+        self.asks().next()
+    }
+
+    // quantity_at returns the quantity resting at price on side, zero if there's no level.
+    pub fn quantity_at(&self, side: Side, price: Price) -> Qty {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        levels.get(&price).copied().unwrap_or_default()
+    }
+
+    // estimate_fill walks the book for an order on side of quantity, a buy taking the asks
+    // and a sell the bids, returning what it would fill at, or None if the side it takes is
+    // empty. An order larger than the side fills what there is.
+    pub fn estimate_fill(&self, side: Side, quantity: Qty) -> Option<FillEstimate> {
+        self.estimate_fill_with_participation(side, quantity, 1.0)
+    }
+
+    // estimate_fill_with_participation is estimate_fill taking at most max_participation, a
+    // fraction in (0, 1], of each level's quantity, as an order that doesn't want to clear
+    // the levels it hits would. The order walks deeper, and what the levels can't fill within
+    // the limit is left unfilled.
+    pub fn estimate_fill_with_participation(&self, side: Side, quantity: Qty, max_participation: f64) -> Option<FillEstimate> {
+        let participation = max_participation.clamp(0.0, 1.0);
+        let levels: Box<dyn Iterator<Item = (Price, Qty)>> = match side {
+            Side::Buy => Box::new(self.asks()),
+            Side::Sell => Box::new(self.bids()),
+        };
+        let mut levels = levels.peekable();
+        let touch = levels.peek()?.0;
+
+        let mut remaining = quantity;
+        let mut notional = Notional::ZERO;
+        let mut worst_price = touch;
+        let mut taken = 0;
+        for (price, available) in levels {
+            if remaining <= Qty::ZERO {
+                break;
+            }
+            let fill = available.checked_scale(participation).unwrap_or(Qty::ZERO).min(remaining);
+            if fill.is_zero() {
+                continue;
+            }
+            notional += price * fill;
+            remaining -= fill;
+            worst_price = price;
+            taken += 1;
+        }
+
+        let filled = quantity - remaining;
+        let average_price = notional.checked_price(filled).unwrap_or(touch);
+        let slippage = match side {
+            Side::Buy => average_price - touch,
+            Side::Sell => touch - average_price,
+        };
+        Some(FillEstimate {
+            side,
+            requested: quantity,
+            filled,
+            average_price,
+            worst_price,
+            slippage_bps: slippage.bps_of(touch),
+            levels: taken,
+        })
+    }
+
+    // mid_price returns the midpoint between the best bid and ask, or None if either side is empty.
+    pub fn mid_price(&self) -> Option<Price> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) => Some(bid.midpoint(ask)),
+            _ => None,
+        }
+    }
+
+    // microprice returns the best bid and ask weighted by the size on the opposite side,
+    // which leans towards the side more likely to be taken next, or None if either side is
+    // empty.
+    pub fn microprice(&self) -> Option<Price> {
+        let ((bid, bid_qty), (ask, ask_qty)) = (self.best_bid()?, self.best_ask()?);
+        let total = bid_qty + ask_qty;
+        if total.is_zero() {
+            return Some(bid.midpoint(ask));
+        }
+        (bid * ask_qty + ask * bid_qty).checked_price(total)
+    }
+
+    // print_top will print the top depth bids and asks in the order book.
+    #[cfg(feature = "native")]
+    pub fn print_top(&self, depth: usize, precision: Precision) {
         // Clear console
         print!("{}[2J{}", 27 as char, 27 as char);
         print!("{}[1;1H", 27 as char);
         
 
-        let bids: Vec<_> = self.bids.iter().rev().take(5).collect();
-        let asks: Vec<_> = self.asks.iter().take(5).collect();
+        let bids: Vec<_> = self.bids.iter().rev().take(depth).collect();
+        let asks: Vec<_> = self.asks.iter().take(depth).collect();
 
-        for i in 0..5 {
+        for i in 0..depth {
             if i < bids.len() {
-                println!("BID Price: {:.2} \t BID Size: {:.4}", bids[i].0, bids[i].1);
+                println!("BID Price: {} \t BID Size: {}", precision.format_price(*bids[i].0), precision.format_quantity(*bids[i].1));
             } else {
                 println!("BID Price: - \t BID Size: -");
             }
         }
         println!();
 
-        for i in 0..5 {
+        for i in 0..depth {
             if i < asks.len() {
-                println!("ASK Price: {:.2} \t ASK Size: {:.4}", asks[i].0, asks[i].1);
+                println!("ASK Price: {} \t ASK Size: {}", precision.format_price(*asks[i].0), precision.format_quantity(*asks[i].1));
             } else {
                 println!("ASK Price: - \t ASK Size: -");
             }
         }
     }
-}
\ No newline at end of file
+}
+
+impl OrderBook for LocalOrderBook {
+    fn apply_snapshot(&mut self, data: SnapshotData) {
+        LocalOrderBook::apply_snapshot(self, data)
+    }
+
+    fn apply_delta(&mut self, delta: &OrderBookDelta) {
+        LocalOrderBook::apply_delta(self, delta)
+    }
+
+    fn bids(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        LocalOrderBook::bids(self)
+    }
+
+    fn asks(&self) -> impl Iterator<Item = (Price, Qty)> + '_ {
+        LocalOrderBook::asks(self)
+    }
+
+    fn best_bid(&self) -> Option<(Price, Qty)> {
+        LocalOrderBook::best_bid(self)
+    }
+
+    fn best_ask(&self) -> Option<(Price, Qty)> {
+        LocalOrderBook::best_ask(self)
+    }
+
+    fn quantity_at(&self, side: Side, price: Price) -> Qty {
+        LocalOrderBook::quantity_at(self, side, price)
+    }
+
+    fn mid_price(&self) -> Option<Price> {
+        LocalOrderBook::mid_price(self)
+    }
+}
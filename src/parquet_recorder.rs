@@ -0,0 +1,352 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, UInt32Array, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::Result;
+use parquet::file::properties::WriterProperties;
+use tracing::error;
+
+use crate::csv_export::civil_from_days;
+use crate::exchange_api_types::{Side, WsTrade};
+use crate::feed::MarketEvent;
+use crate::heatmap::{HeatmapConfig, HeatmapSampler};
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+
+const MS_PER_DAY: u64 = 86_400_000;
+
+// DEFAULT_BATCH_SIZE is the number of rows buffered before a record batch is written.
+pub const DEFAULT_BATCH_SIZE: usize = 8192;
+
+enum Value<'a> {
+    U64(u64),
+    U32(u32),
+    F64(f64),
+    Bool(bool),
+    Str(&'a str),
+}
+
+enum Column {
+    U64(Vec<u64>),
+    U32(Vec<u32>),
+    F64(Vec<f64>),
+    Bool(Vec<bool>),
+    Utf8(Vec<String>),
+}
+
+impl Column {
+    fn for_type(data_type: &DataType) -> Self {
+        match data_type {
+            DataType::UInt64 => Column::U64(Vec::new()),
+            DataType::UInt32 => Column::U32(Vec::new()),
+            DataType::Float64 => Column::F64(Vec::new()),
+            DataType::Boolean => Column::Bool(Vec::new()),
+            _ => Column::Utf8(Vec::new()),
+        }
+    }
+
+    fn push(&mut self, value: &Value) {
+        match (self, value) {
+            (Column::U64(values), Value::U64(v)) => values.push(*v),
+            (Column::U32(values), Value::U32(v)) => values.push(*v),
+            (Column::F64(values), Value::F64(v)) => values.push(*v),
+            (Column::Bool(values), Value::Bool(v)) => values.push(*v),
+            (Column::Utf8(values), Value::Str(v)) => values.push(v.to_string()),
+            _ => panic!("parquet value does not match its column type"),
+        }
+    }
+
+    fn take(&mut self) -> ArrayRef {
+        match self {
+            Column::U64(values) => Arc::new(UInt64Array::from(std::mem::take(values))),
+            Column::U32(values) => Arc::new(UInt32Array::from(std::mem::take(values))),
+            Column::F64(values) => Arc::new(Float64Array::from(std::mem::take(values))),
+            Column::Bool(values) => Arc::new(BooleanArray::from(std::mem::take(values))),
+            Column::Utf8(values) => Arc::new(StringArray::from(std::mem::take(values))),
+        }
+    }
+}
+
+// Table buffers rows of one kind of record and writes them in batches to a parquet file in
+// the partition directory of the row's UTC day: <dir>/date=YYYY-MM-DD/<prefix>_<start>.parquet.
+struct Table {
+    dir: PathBuf,
+    prefix: String,
+    schema: SchemaRef,
+    columns: Vec<Column>,
+    rows: usize,
+    batch_size: usize,
+    day: Option<u64>,
+    writer: Option<ArrowWriter<File>>,
+}
+
+impl Table {
+    fn new(dir: &Path, prefix: String, fields: Vec<Field>, batch_size: usize) -> Self {
+        let columns = fields.iter().map(|field| Column::for_type(field.data_type())).collect();
+        Self {
+            dir: dir.to_path_buf(),
+            prefix,
+            schema: Arc::new(Schema::new(fields)),
+            columns,
+            rows: 0,
+            batch_size: batch_size.max(1),
+            day: None,
+            writer: None,
+        }
+    }
+
+    // push buffers a row with exchange timestamp ts, writing out the buffered batch when it
+    // is full or the row starts a new day.
+    fn push(&mut self, ts: u64, row: &[Value]) -> Result<()> {
+        let day = ts / MS_PER_DAY;
+        if self.day != Some(day) {
+            self.close()?;
+            self.day = Some(day);
+        }
+
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push(value);
+        }
+        self.rows += 1;
+
+        if self.rows >= self.batch_size {
+            self.write_batch(ts)?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self, ts: u64) -> Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+
+        if self.writer.is_none() {
+            let (year, month, day) = civil_from_days((ts / MS_PER_DAY) as i64);
+            let partition = self.dir.join(format!("date={:04}-{:02}-{:02}", year, month, day));
+            fs::create_dir_all(&partition)?;
+
+            let file = File::create(partition.join(format!("{}_{}.parquet", self.prefix, ts)))?;
+            let props = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+            self.writer = Some(ArrowWriter::try_new(file, Arc::clone(&self.schema), Some(props))?);
+        }
+
+        let columns = self.columns.iter_mut().map(Column::take).collect();
+        let batch = RecordBatch::try_new(Arc::clone(&self.schema), columns)?;
+        self.rows = 0;
+        self.writer.as_mut().unwrap().write(&batch)
+    }
+
+    // close writes any buffered rows and finishes the current file.
+    fn close(&mut self) -> Result<()> {
+        if let Some(day) = self.day {
+            self.write_batch(day * MS_PER_DAY)?;
+        }
+        if let Some(writer) = self.writer.take() {
+            writer.close()?;
+        }
+        Ok(())
+    }
+}
+
+// ParquetRecorder records book deltas, trades, and snapshots for a symbol into columnar
+// parquet files partitioned by UTC day, one file per kind of record.
+pub struct ParquetRecorder {
+    deltas: Table,
+    trades: Table,
+    snapshots: Table,
+}
+
+impl ParquetRecorder {
+    pub fn new(dir: &Path, symbol: &str, batch_size: usize) -> Self {
+        let field = |name: &str, data_type: DataType| Field::new(name, data_type, false);
+        let deltas = Table::new(
+            dir,
+            format!("{}_deltas", symbol),
+            vec![
+                field("ts", DataType::UInt64),
+                field("prev_ts", DataType::UInt64),
+                field("side", DataType::Utf8),
+                field("price", DataType::Float64),
+                field("quantity", DataType::Float64),
+            ],
+            batch_size,
+        );
+        let trades = Table::new(
+            dir,
+            format!("{}_trades", symbol),
+            vec![
+                field("ts", DataType::UInt64),
+                field("side", DataType::Utf8),
+                field("price", DataType::Float64),
+                field("quantity", DataType::Float64),
+                field("backfilled", DataType::Boolean),
+            ],
+            batch_size,
+        );
+        let snapshots = Table::new(
+            dir,
+            format!("{}_snapshots", symbol),
+            vec![
+                field("ts", DataType::UInt64),
+                field("side", DataType::Utf8),
+                field("level", DataType::UInt32),
+                field("price", DataType::Float64),
+                field("quantity", DataType::Float64),
+            ],
+            batch_size,
+        );
+        Self { deltas, trades, snapshots }
+    }
+
+    // record_delta records every level change in event.
+    pub fn record_delta(&mut self, event: &MarketEvent) -> Result<()> {
+        let sides = [("BID", &event.delta.bids), ("ASK", &event.delta.asks)];
+        for (side, quotes) in sides {
+            for quote in quotes {
+                self.deltas.push(event.ts, &[
+                    Value::U64(event.ts),
+                    Value::U64(event.prev_ts),
+                    Value::Str(side),
+                    Value::F64(quote.price),
+                    Value::F64(quote.quantity),
+                ])?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn record_trade(&mut self, trade: &WsTrade) -> Result<()> {
+        let side = match trade.side {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL",
+        };
+        self.trades.push(trade.ts, &[
+            Value::U64(trade.ts),
+            Value::Str(side),
+            Value::F64(trade.price),
+            Value::F64(trade.quantity),
+            Value::Bool(trade.backfilled),
+        ])
+    }
+
+    // record_snapshot records every level of book as of ts.
+    pub fn record_snapshot(&mut self, ts: u64, book: &LocalOrderBook) -> Result<()> {
+        let bids: Vec<_> = book.bids().collect();
+        let asks: Vec<_> = book.asks().collect();
+        for (side, levels) in [("BID", bids), ("ASK", asks)] {
+            for (level, (price, quantity)) in levels.into_iter().enumerate() {
+                self.snapshots.push(ts, &[
+                    Value::U64(ts),
+                    Value::Str(side),
+                    Value::U32(level as u32 + 1),
+                    Value::F64(price.value()),
+                    Value::F64(quantity.value()),
+                ])?;
+            }
+        }
+        Ok(())
+    }
+
+    // close writes all buffered rows and finishes the open files.
+    pub fn close(&mut self) -> Result<()> {
+        self.deltas.close()?;
+        self.trades.close()?;
+        self.snapshots.close()
+    }
+}
+
+impl Sink for ParquetRecorder {
+    fn name(&self) -> &str {
+        "parquet"
+    }
+
+    fn record_snapshot(&mut self, _symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        Ok(ParquetRecorder::record_snapshot(self, ts, book)?)
+    }
+
+    fn record_delta(&mut self, _symbol: &str, event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        Ok(ParquetRecorder::record_delta(self, event)?)
+    }
+
+    fn record_trade(&mut self, trade: &WsTrade) -> SinkResult {
+        Ok(ParquetRecorder::record_trade(self, trade)?)
+    }
+}
+
+impl Drop for ParquetRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            error!(error = %e, "Failed to close parquet recorder");
+        }
+    }
+}
+
+// ParquetHeatmapRecorder samples a symbol's book into heatmap columns, writing a row per cell
+// to parquet files partitioned by UTC day like ParquetRecorder's.
+pub struct ParquetHeatmapRecorder {
+    sampler: HeatmapSampler,
+    cells: Table,
+}
+
+impl ParquetHeatmapRecorder {
+    pub fn new(dir: &Path, symbol: &str, config: HeatmapConfig, batch_size: usize) -> Self {
+        let field = |name: &str, data_type: DataType| Field::new(name, data_type, false);
+        let cells = Table::new(
+            dir,
+            format!("{}_heatmap", symbol),
+            vec![
+                field("ts", DataType::UInt64),
+                field("price", DataType::Float64),
+                field("bid_quantity", DataType::Float64),
+                field("ask_quantity", DataType::Float64),
+            ],
+            batch_size,
+        );
+        Self { sampler: HeatmapSampler::new(config), cells }
+    }
+
+    // record writes a column of book if a sample is due.
+    pub fn record(&mut self, book: &LocalOrderBook) -> Result<()> {
+        let Some(column) = self.sampler.sample(book) else { return Ok(()) };
+        for cell in &column.cells {
+            self.cells.push(column.ts, &[
+                Value::U64(column.ts),
+                Value::F64(cell.price.value()),
+                Value::F64(cell.bid.value()),
+                Value::F64(cell.ask.value()),
+            ])?;
+        }
+        Ok(())
+    }
+
+    // close writes all buffered rows and finishes the open file.
+    pub fn close(&mut self) -> Result<()> {
+        self.cells.close()
+    }
+}
+
+impl Sink for ParquetHeatmapRecorder {
+    fn name(&self) -> &str {
+        "parquet_heatmap"
+    }
+
+    fn record_snapshot(&mut self, _symbol: &str, _ts: u64, book: &LocalOrderBook) -> SinkResult {
+        Ok(self.record(book)?)
+    }
+
+    fn record_delta(&mut self, _symbol: &str, _event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        Ok(self.record(book)?)
+    }
+}
+
+impl Drop for ParquetHeatmapRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            error!(error = %e, "Failed to close parquet heatmap recorder");
+        }
+    }
+}
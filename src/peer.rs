@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use crate::exchange_api_types::{RestSnapshot, SnapshotData};
+use crate::http::{self, Response};
+use crate::http_client;
+use crate::orderbook::LocalOrderBook;
+use crate::retry::RetryPolicy;
+use crate::snapshot::{SnapshotError, SnapshotSource};
+
+// SnapshotRegistry holds the latest synced snapshot of every book this instance maintains,
+// so they can be served to peer instances.
+#[derive(Clone, Default)]
+pub struct SnapshotRegistry {
+    books: Arc<RwLock<HashMap<String, RestSnapshot>>>,
+}
+
+impl SnapshotRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // publish records book, last updated at timestamp by the event numbered seq, as the
+    // current snapshot for symbol.
+    pub fn publish(&self, symbol: &str, timestamp: u64, seq: u64, book: &LocalOrderBook) {
+        let snapshot = RestSnapshot::new(timestamp, seq, book.to_snapshot());
+        self.books.write().unwrap().insert(symbol.to_string(), snapshot);
+    }
+
+    // remove drops the snapshot for symbol, e.g. when its book falls out of sync.
+    pub fn remove(&self, symbol: &str) {
+        self.books.write().unwrap().remove(symbol);
+    }
+
+    // snapshot_json returns the current snapshot for symbol serialized in the Woo X REST
+    // format, limited to max_level levels per side.
+    pub fn snapshot_json(&self, symbol: &str, max_level: usize) -> Option<String> {
+        let books = self.books.read().unwrap();
+        let snapshot = books.get(symbol)?;
+        let limited = RestSnapshot {
+            timestamp: snapshot.timestamp,
+            data: SnapshotData {
+                bids: snapshot.data.bids.iter().take(max_level).copied().collect(),
+                asks: snapshot.data.asks.iter().take(max_level).copied().collect(),
+            },
+            seq: snapshot.seq,
+        };
+        serde_json::to_string(&limited).ok()
+    }
+
+    // save_checkpoint writes the current snapshot for symbol to path as a checkpoint,
+    // returning whether there was one to write.
+    pub fn save_checkpoint(&self, symbol: &str, path: &Path) -> Result<bool, SnapshotError> {
+        let books = self.books.read().unwrap();
+        let Some(snapshot) = books.get(symbol) else { return Ok(false) };
+        fs::write(path, serde_json::to_string(snapshot)?)?;
+        Ok(true)
+    }
+}
+
+// serve_snapshots serves the registry's snapshots over HTTP at
+// GET /snapshot?symbol=<symbol>&maxLevel=<levels>, in the same format as the Woo X REST API.
+pub fn serve_snapshots<A: ToSocketAddrs>(addr: A, registry: SnapshotRegistry) -> io::Result<thread::JoinHandle<()>> {
+    http::serve(addr, move |request| {
+        if request.path != "/snapshot" {
+            return Response::not_found();
+        }
+        let Some(symbol) = request.query.get("symbol") else {
+            return Response::bad_request("missing symbol");
+        };
+        let max_level = request
+            .query
+            .get("maxLevel")
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(usize::MAX);
+
+        match registry.snapshot_json(symbol, max_level) {
+            Some(json) => Response::json(json),
+            None => Response::not_found(),
+        }
+    })
+}
+
+// PeerSource fetches snapshots from another instance's snapshot server.
+pub struct PeerSource {
+    base_url: String,
+    http: reqwest::blocking::Client,
+    retry: RetryPolicy,
+}
+
+impl PeerSource {
+    // new creates a source for the peer serving snapshots at base_url, e.g. http://10.0.0.2:9101.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: http_client::shared(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+}
+
+impl SnapshotSource for PeerSource {
+    fn name(&self) -> &str {
+        "peer"
+    }
+
+    fn fetch(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        let url = format!("{}/snapshot?symbol={}&maxLevel={}", self.base_url, symbol, max_level);
+        self.retry.run("peer snapshot", || Ok(self.http.get(&url).timeout(self.retry.timeout).send()?.error_for_status()?.json()?))
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::exchange_api_types::{MarkPrice, Side};
+use crate::feed::{self, FeedConfig};
+use crate::fees::FeeSchedule;
+use crate::order::Fill;
+use crate::standby::PollError;
+use crate::trading::ExecutionReport;
+use crate::units::{Notional, Price, Qty};
+
+// Position is a net position in a symbol and the PnL it has realized, accounted at its
+// average entry price. quantity is negative for a short.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Position {
+    pub quantity: Qty,
+    // average_entry is the average price the open position was entered at, None while flat.
+    pub average_entry: Option<Price>,
+    pub realized: Notional,
+    pub fees: Notional,
+}
+
+impl Position {
+    // apply adds a fill to the position. Fills against the position realize the difference
+    // to the average entry, and a fill flipping it opens the rest at the fill price.
+    pub fn apply(&mut self, side: Side, price: Price, quantity: Qty, fee: Notional) {
+        self.fees += fee;
+        let signed = match side {
+            Side::Buy => quantity.value(),
+            Side::Sell => -quantity.value(),
+        };
+        let position = self.quantity.value();
+        let entry = self.average_entry.map_or(price.value(), Price::value);
+
+        if position == 0.0 || position.signum() == signed.signum() {
+            let size = position.abs() + signed.abs();
+            self.average_entry = Some(Price::new((entry * position.abs() + price.value() * signed.abs()) / size));
+        } else {
+            let closed = signed.abs().min(position.abs());
+            self.realized += Notional::new(closed * (price.value() - entry) * position.signum());
+            if signed.abs() > position.abs() {
+                self.average_entry = Some(price);
+            } else if signed.abs() == position.abs() {
+                self.average_entry = None;
+            }
+        }
+        self.quantity = Qty::new(position + signed);
+    }
+
+    // unrealized returns the PnL of the open position marked at mark.
+    pub fn unrealized(&self, mark: Price) -> Notional {
+        match self.average_entry {
+            Some(entry) => Notional::new(self.quantity.value() * (mark - entry).value()),
+            None => Notional::ZERO,
+        }
+    }
+
+    // exposure returns the value of the open position at mark, negative for a short.
+    pub fn exposure(&self, mark: Price) -> Notional {
+        mark * self.quantity
+    }
+
+    // total returns the realized and unrealized PnL at mark, net of fees.
+    pub fn total(&self, mark: Price) -> Notional {
+        self.realized + self.unrealized(mark) - self.fees
+    }
+}
+
+// PositionPnl is the PnL of the position in one symbol, marked at its last mark price.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionPnl {
+    pub symbol: String,
+    pub quantity: Qty,
+    pub average_entry: Option<Price>,
+    // mark is the last mark price, None before the first, in which case the position is
+    // marked at its average entry.
+    pub mark: Option<Price>,
+    pub realized: Notional,
+    pub unrealized: Notional,
+    pub fees: Notional,
+    // total is the realized and unrealized PnL net of fees.
+    pub total: Notional,
+    // exposure is the value of the open position at the mark, negative for a short.
+    pub exposure: Notional,
+}
+
+// PnlEvent is the PnL of every position at a point in time, and their sums.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PnlEvent {
+    pub ts: u64,
+    pub positions: Vec<PositionPnl>,
+    pub realized: Notional,
+    pub unrealized: Notional,
+    pub fees: Notional,
+    pub total: Notional,
+    // gross_exposure is the sum of the absolute exposures.
+    pub gross_exposure: Notional,
+}
+
+// PnlTracker keeps the position in each symbol traded from its fills, and the PnL of each
+// marked at the latest mark price.
+#[derive(Debug, Clone, Default)]
+pub struct PnlTracker {
+    positions: BTreeMap<String, Position>,
+    marks: BTreeMap<String, Price>,
+    fees: Option<FeeSchedule>,
+}
+
+impl PnlTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // with_fees charges fills reported without a fee, like those reconciled from an order's
+    // filled quantity, at the rate of fees for their liquidity.
+    pub fn with_fees(self, fees: FeeSchedule) -> Self {
+        Self { fees: Some(fees), ..self }
+    }
+
+    pub fn on_fill(&mut self, fill: &Fill) {
+        let fee = match self.fees {
+            Some(fees) if fill.fee.is_zero() => fees.fill_fee(fill.price, fill.quantity, fill.liquidity),
+            _ => fill.fee,
+        };
+        let position = self.positions.entry(fill.symbol.clone()).or_default();
+        position.apply(fill.side, fill.price, fill.quantity, fee);
+    }
+
+    // on_mark records the mark price of symbol, e.g. from a mark price stream or a book's mid.
+    pub fn on_mark(&mut self, symbol: &str, price: Price) {
+        self.marks.insert(symbol.to_string(), price);
+    }
+
+    pub fn position(&self, symbol: &str) -> Option<&Position> {
+        self.positions.get(symbol)
+    }
+
+    pub fn mark(&self, symbol: &str) -> Option<Price> {
+        self.marks.get(symbol).copied()
+    }
+
+    // pnl returns the PnL of the position in symbol, or None if it has never traded.
+    pub fn pnl(&self, symbol: &str) -> Option<PositionPnl> {
+        let position = self.positions.get(symbol)?;
+        let mark = self.mark(symbol);
+        let marked_at = mark.or(position.average_entry).unwrap_or_default();
+        let unrealized = position.unrealized(marked_at);
+        Some(PositionPnl {
+            symbol: symbol.to_string(),
+            quantity: position.quantity,
+            average_entry: position.average_entry,
+            mark,
+            realized: position.realized,
+            unrealized,
+            fees: position.fees,
+            total: position.total(marked_at),
+            exposure: position.exposure(marked_at),
+        })
+    }
+
+    // event returns the PnL of every position traded, in symbol order.
+    pub fn event(&self) -> PnlEvent {
+        let positions: Vec<PositionPnl> = self.positions.keys().filter_map(|symbol| self.pnl(symbol)).collect();
+        PnlEvent {
+            ts: now_ms(),
+            realized: positions.iter().map(|pnl| pnl.realized).sum(),
+            unrealized: positions.iter().map(|pnl| pnl.unrealized).sum(),
+            fees: positions.iter().map(|pnl| pnl.fees).sum(),
+            total: positions.iter().map(|pnl| pnl.total).sum(),
+            gross_exposure: positions.iter().map(|pnl| Notional::new(pnl.exposure.value().abs())).sum(),
+            positions,
+        }
+    }
+}
+
+// PnlFeed tracks the PnL of an account live: fills from its execution report stream, and the
+// mark price streams of the symbols it trades, connected as they first fill. A PnlEvent is
+// emitted every interval.
+pub struct PnlFeed {
+    config: FeedConfig,
+    reports: Receiver<ExecutionReport>,
+    marks: BTreeMap<String, Receiver<MarkPrice>>,
+    tracker: PnlTracker,
+    interval: Duration,
+    last_event: Instant,
+}
+
+impl PnlFeed {
+    // start tracks the fills of reports, with mark prices from the venue of config.
+    pub fn start(config: &FeedConfig, reports: Receiver<ExecutionReport>, interval: Duration) -> Self {
+        Self {
+            config: config.clone(),
+            reports,
+            marks: BTreeMap::new(),
+            tracker: PnlTracker::new(),
+            interval,
+            last_event: Instant::now(),
+        }
+    }
+
+    // with_fees charges the fills reported without a fee at the rates of fees.
+    pub fn with_fees(self, fees: FeeSchedule) -> Self {
+        Self { tracker: self.tracker.with_fees(fees), ..self }
+    }
+
+    pub fn tracker(&self) -> &PnlTracker {
+        &self.tracker
+    }
+
+    // poll applies the fills and mark prices available, returning a PnlEvent if one is due.
+    // It fails once the execution report stream disconnects.
+    pub fn poll(&mut self) -> Result<Option<PnlEvent>, PollError> {
+        loop {
+            match self.reports.try_recv() {
+                Ok(report) => self.on_report(&report),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(PollError::Disconnected),
+            }
+        }
+        for marks in self.marks.values() {
+            // A mark stream that drops leaves the last mark in place.
+            while let Ok(mark) = marks.try_recv() {
+                self.tracker.on_mark(&mark.symbol, Price::new(mark.price));
+            }
+        }
+
+        if self.last_event.elapsed() < self.interval {
+            return Ok(None);
+        }
+        self.last_event = Instant::now();
+        Ok(Some(self.tracker.event()))
+    }
+
+    fn on_report(&mut self, report: &ExecutionReport) {
+        let Some(fill) = &report.fill else { return };
+        let order = &report.order;
+        if !self.marks.contains_key(&order.symbol) {
+            if let Some(marks) = feed::connect_mark_price(&self.config, &order.symbol) {
+                self.marks.insert(order.symbol.clone(), marks);
+            }
+        }
+        self.tracker.on_fill(&Fill {
+            order_id: order.order_id,
+            symbol: order.symbol.clone(),
+            side: order.side,
+            price: fill.price,
+            quantity: fill.quantity,
+            fee: fill.fee,
+            liquidity: fill.liquidity,
+            ts: order.ts,
+        });
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
@@ -0,0 +1,198 @@
+use std::io;
+use std::net::TcpStream;
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError, TryRecvError};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+use tungstenite::stream::MaybeTlsStream;
+
+use crate::queue::{self, Coalesce};
+
+// PollMode selects how the reader and book threads wait for work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PollMode {
+    // Blocking waits in the kernel until data arrives.
+    #[default]
+    Blocking,
+    // BusyPoll spins on the socket and channel instead of blocking, trading a full core per
+    // thread for lower tail latency. With a spin_limit, a thread parks in a blocking wait
+    // after that many empty polls and resumes spinning once woken.
+    BusyPoll { spin_limit: Option<u32> },
+}
+
+impl PollMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PollMode::Blocking => "blocking",
+            PollMode::BusyPoll { spin_limit: None } => "busy-poll",
+            PollMode::BusyPoll { spin_limit: Some(_) } => "spin-then-park",
+        }
+    }
+}
+
+// Channel is the receiving end of a channel recv and recv_timeout can wait on: an
+// mpsc::Receiver, or a bounded queue::Receiver.
+pub trait Channel<T> {
+    fn recv(&self) -> Result<T, RecvError>;
+    fn try_recv(&self) -> Result<T, TryRecvError>;
+    fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError>;
+}
+
+impl<T> Channel<T> for Receiver<T> {
+    fn recv(&self) -> Result<T, RecvError> {
+        Receiver::recv(self)
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        Receiver::try_recv(self)
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        Receiver::recv_timeout(self, timeout)
+    }
+}
+
+impl<T: Coalesce> Channel<T> for queue::Receiver<T> {
+    fn recv(&self) -> Result<T, RecvError> {
+        queue::Receiver::recv(self)
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        queue::Receiver::try_recv(self)
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        queue::Receiver::recv_timeout(self, timeout)
+    }
+}
+
+// recv receives the next item from receiver according to mode. It returns None once every
+// sender has been dropped.
+pub fn recv<T>(receiver: &impl Channel<T>, mode: PollMode) -> Option<T> {
+    let PollMode::BusyPoll { spin_limit } = mode else {
+        return receiver.recv().ok();
+    };
+
+    let mut spins = 0u32;
+    loop {
+        match receiver.try_recv() {
+            Ok(item) => return Some(item),
+            Err(TryRecvError::Disconnected) => return None,
+            Err(TryRecvError::Empty) => {}
+        }
+
+        spins += 1;
+        if spin_limit.is_some_and(|limit| spins >= limit) {
+            return receiver.recv().ok();
+        }
+        std::hint::spin_loop();
+    }
+}
+
+// recv_timeout is recv, giving up once timeout passes without an item.
+pub fn recv_timeout<T>(receiver: &impl Channel<T>, mode: PollMode, timeout: Duration) -> Result<T, RecvTimeoutError> {
+    let PollMode::BusyPoll { spin_limit } = mode else {
+        return receiver.recv_timeout(timeout);
+    };
+
+    let deadline = Instant::now() + timeout;
+    let mut spins = 0u32;
+    loop {
+        match receiver.try_recv() {
+            Ok(item) => return Ok(item),
+            Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        spins += 1;
+        if spin_limit.is_some_and(|limit| spins >= limit) {
+            return receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        }
+        if Instant::now() >= deadline {
+            return Err(RecvTimeoutError::Timeout);
+        }
+        std::hint::spin_loop();
+    }
+}
+
+// pin_current_thread pins the calling thread to core, so a busy polling thread keeps its
+// core and caches to itself, returning whether it could be pinned.
+pub fn pin_current_thread(core: usize) -> bool {
+    let pinned = core_affinity::set_for_current(core_affinity::CoreId { id: core });
+    match pinned {
+        true => info!(core, "Pinned thread to core"),
+        false => warn!(core, "Failed to pin thread to core"),
+    }
+    pinned
+}
+
+// NonBlocking is implemented by websocket streams that can be switched into non-blocking
+// mode for busy polling.
+pub trait NonBlocking {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+
+    // set_read_timeout bounds how long a blocking read waits for data before failing with
+    // WouldBlock or TimedOut.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl NonBlocking for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl<S: NonBlocking + io::Read + io::Write> NonBlocking for MaybeTlsStream<S> {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            MaybeTlsStream::Plain(stream) => stream.set_nonblocking(nonblocking),
+            MaybeTlsStream::NativeTls(stream) => stream.get_ref().set_nonblocking(nonblocking),
+            MaybeTlsStream::Rustls(stream) => stream.get_ref().set_nonblocking(nonblocking),
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, "unknown stream type")),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            MaybeTlsStream::Plain(stream) => stream.set_read_timeout(timeout),
+            MaybeTlsStream::NativeTls(stream) => stream.get_ref().set_read_timeout(timeout),
+            MaybeTlsStream::Rustls(stream) => stream.get_ref().set_read_timeout(timeout),
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, "unknown stream type")),
+        }
+    }
+}
+
+// LatencyStats accumulates a running count, mean and max of latency samples.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatencyStats {
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, sample: Duration) {
+        self.count += 1;
+        self.total += sample;
+        self.max = self.max.max(sample);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+}
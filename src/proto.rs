@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use prost::Message;
+
+use crate::compact;
+use crate::exchange_api_types::{WsQuote, WsTrade};
+use crate::feed;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+use crate::units::{Price, Qty};
+
+// The messages of proto/woox.proto, and with the grpc feature the MarketData service.
+include!(concat!(env!("OUT_DIR"), "/woox.v1.rs"));
+
+// FLUSH_INTERVAL bounds how much of a recording can be lost if the process dies.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+// MAX_MESSAGE_LEN bounds the length a recorded message may claim, so a corrupt length
+// isn't allocated.
+const MAX_MESSAGE_LEN: u64 = 64 << 20;
+
+// levels returns up to max_level levels of a side of a book, best first.
+fn levels(levels: impl Iterator<Item = (Price, Qty)>, max_level: usize) -> Vec<Level> {
+    levels.take(max_level).map(Level::from).collect()
+}
+
+// quote_levels returns the levels of a delta side.
+fn quote_levels(quotes: &[WsQuote]) -> Vec<Level> {
+    quotes.iter().map(|quote| Level { price: quote.price, quantity: quote.quantity }).collect()
+}
+
+impl From<(Price, Qty)> for Level {
+    fn from((price, quantity): (Price, Qty)) -> Self {
+        Self { price: price.value(), quantity: quantity.value() }
+    }
+}
+
+impl BookSnapshot {
+    // from_book returns up to max_level levels a side of the book for symbol as of ts.
+    pub fn from_book(symbol: &str, ts: u64, book: &LocalOrderBook, max_level: usize) -> Self {
+        Self { symbol: symbol.to_string(), ts, bids: levels(book.bids(), max_level), asks: levels(book.asks(), max_level) }
+    }
+}
+
+impl BookUpdate {
+    // from_book returns the whole book for symbol as of ts, as a snapshot update.
+    pub fn from_book(symbol: &str, ts: u64, book: &LocalOrderBook) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            ts,
+            prev_ts: 0,
+            snapshot: true,
+            bids: levels(book.bids(), usize::MAX),
+            asks: levels(book.asks(), usize::MAX),
+        }
+    }
+
+    // from_event returns the levels event changed in the book for symbol.
+    pub fn from_event(symbol: &str, event: &feed::MarketEvent) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            ts: event.ts,
+            prev_ts: event.prev_ts,
+            snapshot: false,
+            bids: quote_levels(&event.delta.bids),
+            asks: quote_levels(&event.delta.asks),
+        }
+    }
+}
+
+impl From<&WsTrade> for Trade {
+    fn from(trade: &WsTrade) -> Self {
+        let side = match trade.side {
+            crate::exchange_api_types::Side::Buy => Side::Buy,
+            crate::exchange_api_types::Side::Sell => Side::Sell,
+        };
+        Self {
+            symbol: trade.symbol.clone(),
+            price: trade.price,
+            quantity: trade.quantity,
+            side: side as i32,
+            ts: trade.ts,
+            backfilled: trade.backfilled,
+        }
+    }
+}
+
+impl Ticker {
+    // from_book returns the top of the book for symbol as of ts.
+    pub fn from_book(symbol: &str, ts: u64, book: &LocalOrderBook) -> Self {
+        Self { symbol: symbol.to_string(), ts, bid: book.best_bid().map(Level::from), ask: book.best_ask().map(Level::from) }
+    }
+}
+
+impl From<BookSnapshot> for MarketEvent {
+    fn from(snapshot: BookSnapshot) -> Self {
+        Self { event: Some(market_event::Event::Snapshot(snapshot)) }
+    }
+}
+
+impl From<BookUpdate> for MarketEvent {
+    fn from(update: BookUpdate) -> Self {
+        Self { event: Some(market_event::Event::Update(update)) }
+    }
+}
+
+impl From<Trade> for MarketEvent {
+    fn from(trade: Trade) -> Self {
+        Self { event: Some(market_event::Event::Trade(trade)) }
+    }
+}
+
+impl From<Ticker> for MarketEvent {
+    fn from(ticker: Ticker) -> Self {
+        Self { event: Some(market_event::Event::Ticker(ticker)) }
+    }
+}
+
+// ProtoRecorder is a sink recording market events as length delimited MarketEvent
+// messages, each a varint length followed by the message, for consumers that would rather
+// decode the published schema than a format of this crate's own. Each (re)sync is recorded
+// as a whole book snapshot, followed by every delta applied and every trade, and with
+// tickers, by the top of the book after each delta that moved it. Opening an existing
+// recording appends to it.
+pub struct ProtoRecorder {
+    path: PathBuf,
+    file: BufWriter<File>,
+    tickers: bool,
+    // last_tickers are the tops of book last recorded, by symbol.
+    last_tickers: HashMap<String, Ticker>,
+    last_flush: Instant,
+}
+
+impl ProtoRecorder {
+    // create opens the recording at path, creating it and its directory if needed.
+    pub fn create(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = File::options().create(true).append(true).open(&path)?;
+        Ok(Self { path, file: BufWriter::new(file), tickers: false, last_tickers: HashMap::new(), last_flush: Instant::now() })
+    }
+
+    // with_tickers also records the top of the book each time a delta moves it.
+    pub fn with_tickers(mut self) -> Self {
+        self.tickers = true;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write(&mut self, event: impl Into<MarketEvent>) -> io::Result<()> {
+        self.file.write_all(&event.into().encode_length_delimited_to_vec())?;
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.file.flush()?;
+            self.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+impl Sink for ProtoRecorder {
+    fn name(&self) -> &str {
+        "proto"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        // The top of a resynced book is recorded afresh after its first delta.
+        self.last_tickers.remove(symbol);
+        Ok(self.write(BookSnapshot::from_book(symbol, ts, book, usize::MAX))?)
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &feed::MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        self.write(BookUpdate::from_event(symbol, event))?;
+        if !self.tickers {
+            return Ok(());
+        }
+        let ticker = Ticker::from_book(symbol, event.ts, book);
+        let moved = self.last_tickers.get(symbol).is_none_or(|last| last.bid != ticker.bid || last.ask != ticker.ask);
+        if moved {
+            self.last_tickers.insert(symbol.to_string(), ticker.clone());
+            self.write(ticker)?;
+        }
+        Ok(())
+    }
+
+    fn record_trade(&mut self, trade: &WsTrade) -> SinkResult {
+        Ok(self.write(Trade::from(trade))?)
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.last_flush = Instant::now();
+        Ok(self.file.flush()?)
+    }
+}
+
+// ProtoReader reads the MarketEvent messages of a protobuf recording back. A truncated
+// last message, as left by a process that died mid-write, ends the iteration with an
+// error.
+pub struct ProtoReader {
+    reader: BufReader<File>,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl ProtoReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self { reader: BufReader::new(File::open(path)?), buf: Vec::new(), done: false })
+    }
+
+    // read_event reads the next message, or None at the end of the recording.
+    fn read_event(&mut self) -> io::Result<Option<MarketEvent>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let len = compact::get_varint(&mut self.reader)?;
+        if len > MAX_MESSAGE_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("message of {} bytes is too long", len)));
+        }
+        self.buf.resize(len as usize, 0);
+        self.reader.read_exact(&mut self.buf)?;
+        MarketEvent::decode(self.buf.as_slice()).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl Iterator for ProtoReader {
+    type Item = io::Result<MarketEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let event = self.read_event().transpose();
+        self.done = !matches!(event, Some(Ok(_)));
+        event
+    }
+}
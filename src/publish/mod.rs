@@ -0,0 +1,138 @@
+// publish republishes the synced books to local consumers, so they don't need to talk to
+// Woo X themselves.
+pub mod ws_server;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::exchange_api_types::{Side, WsQuote, WsTrade};
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+
+// BookMessage is a book message of the publish protocol, encoded as JSON. Levels are
+// [price, quantity] pairs, bids best first and asks best first. A consumer receives a
+// snapshot of a book on every resync, from the websocket server on connect and
+// periodically from a publisher wrapped in PeriodicSnapshots, followed by an update for
+// every delta applied since; an update level with a zero quantity removes it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BookMessage {
+    Snapshot {
+        symbol: String,
+        ts: u64,
+        bids: Vec<[f64; 2]>,
+        asks: Vec<[f64; 2]>,
+    },
+    Update {
+        symbol: String,
+        ts: u64,
+        #[serde(rename = "prevTs")]
+        prev_ts: u64,
+        bids: Vec<[f64; 2]>,
+        asks: Vec<[f64; 2]>,
+    },
+}
+
+impl BookMessage {
+    pub fn snapshot(symbol: &str, ts: u64, book: &LocalOrderBook) -> Self {
+        BookMessage::Snapshot {
+            symbol: symbol.to_string(),
+            ts,
+            bids: book.bids().map(|(price, quantity)| [price.value(), quantity.value()]).collect(),
+            asks: book.asks().map(|(price, quantity)| [price.value(), quantity.value()]).collect(),
+        }
+    }
+
+    pub fn update(symbol: &str, event: &MarketEvent) -> Self {
+        let levels = |quotes: &[WsQuote]| quotes.iter().map(|quote| [quote.price, quote.quantity]).collect();
+        BookMessage::Update {
+            symbol: symbol.to_string(),
+            ts: event.ts,
+            prev_ts: event.prev_ts,
+            bids: levels(&event.delta.bids),
+            asks: levels(&event.delta.asks),
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            BookMessage::Snapshot { symbol, .. } | BookMessage::Update { symbol, .. } => symbol,
+        }
+    }
+}
+
+// TradeMessage is a trade message of the publish protocol, encoded as JSON.
+#[derive(Debug, Clone, Serialize)]
+pub struct TradeMessage {
+    pub symbol: String,
+    pub price: f64,
+    pub quantity: f64,
+    pub side: Side,
+    pub ts: u64,
+    pub backfilled: bool,
+}
+
+impl From<&WsTrade> for TradeMessage {
+    fn from(trade: &WsTrade) -> Self {
+        Self {
+            symbol: trade.symbol.clone(),
+            price: trade.price,
+            quantity: trade.quantity,
+            side: trade.side,
+            ts: trade.ts,
+            backfilled: trade.backfilled,
+        }
+    }
+}
+
+// PeriodicSnapshots passes records on to a publisher, also giving it the full book every
+// interval of exchange time, so consumers that join a bus or topic mid-stream have a
+// baseline to apply the updates to without waiting for a resync.
+pub struct PeriodicSnapshots {
+    inner: Box<dyn Sink>,
+    interval_ms: u64,
+    // last_snapshot is the exchange timestamp each book was last given in full at.
+    last_snapshot: HashMap<String, u64>,
+}
+
+impl PeriodicSnapshots {
+    pub fn new(sink: Box<dyn Sink>, interval: Duration) -> Self {
+        Self { inner: sink, interval_ms: interval.as_millis() as u64, last_snapshot: HashMap::new() }
+    }
+}
+
+impl Sink for PeriodicSnapshots {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        self.last_snapshot.insert(symbol.to_string(), ts);
+        self.inner.record_snapshot(symbol, ts, book)
+    }
+
+    // record_delta follows the delta with the book it leaves, once the interval has passed.
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        self.inner.record_delta(symbol, event, book)?;
+        let last = *self.last_snapshot.entry(symbol.to_string()).or_insert(event.ts);
+        if event.ts.saturating_sub(last) >= self.interval_ms {
+            self.record_snapshot(symbol, event.ts, book)?;
+        }
+        Ok(())
+    }
+
+    fn record_stale(&mut self, symbol: &str, age: Duration, book: &LocalOrderBook) -> SinkResult {
+        self.inner.record_stale(symbol, age, book)
+    }
+
+    fn record_trade(&mut self, trade: &WsTrade) -> SinkResult {
+        self.inner.record_trade(trade)
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.inner.flush()
+    }
+}
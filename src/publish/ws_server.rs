@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tracing::warn;
+use tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tungstenite::Message;
+
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+
+use super::BookMessage;
+
+// Subscriber is a connected client and the symbols it wants, None for all of them.
+struct Subscriber {
+    symbols: Option<Vec<String>>,
+    tx: Sender<Arc<String>>,
+}
+
+impl Subscriber {
+    fn wants(&self, symbol: &str) -> bool {
+        self.symbols.as_ref().is_none_or(|symbols| symbols.iter().any(|wanted| wanted == symbol))
+    }
+}
+
+// PublishedBook is the publisher's copy of a book and the timestamp it was last updated at.
+struct PublishedBook {
+    ts: u64,
+    book: LocalOrderBook,
+}
+
+#[derive(Default)]
+struct State {
+    books: HashMap<String, PublishedBook>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl State {
+    // broadcast sends message to every subscriber that wants it, dropping the ones that
+    // disconnected.
+    fn broadcast(&mut self, message: &BookMessage) {
+        let Ok(json) = serde_json::to_string(message) else { return };
+        let json = Arc::new(json);
+        self.subscribers
+            .retain(|subscriber| !subscriber.wants(message.symbol()) || subscriber.tx.send(Arc::clone(&json)).is_ok());
+    }
+}
+
+// WsPublisher is a sink that republishes the synced books it is given over a local
+// websocket server. Clients connect to ws://<addr>/ for every book, or
+// ws://<addr>/?symbols=A,B for just those.
+#[derive(Clone, Default)]
+pub struct WsPublisher {
+    state: Arc<Mutex<State>>,
+}
+
+impl WsPublisher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // serve accepts websocket clients on addr, handling each on its own thread. It returns
+    // once the listener is bound.
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> io::Result<thread::JoinHandle<()>> {
+        let listener = TcpListener::bind(addr)?;
+        let publisher = self.clone();
+        Ok(thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let publisher = publisher.clone();
+                thread::spawn(move || {
+                    if let Err(e) = publisher.handle_client(stream) {
+                        warn!(error = %e, "Websocket publish client error");
+                    }
+                });
+            }
+        }))
+    }
+
+    // handle_client completes the websocket handshake, then writes the client every message
+    // it subscribed to until it disconnects.
+    fn handle_client(&self, stream: TcpStream) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut symbols = None;
+        let mut socket = tungstenite::accept_hdr(stream, SymbolsCallback(&mut symbols)).map_err(|e| e.to_string())?;
+
+        let messages = self.subscribe(symbols);
+        for json in messages {
+            socket.send(Message::Text(json.as_str().to_owned()))?;
+        }
+        Ok(())
+    }
+
+    // subscribe registers a subscriber and queues it the current snapshot of every book it
+    // wants, under the same lock as the broadcasts so it sees every update after them.
+    fn subscribe(&self, symbols: Option<Vec<String>>) -> Receiver<Arc<String>> {
+        let (tx, rx) = mpsc::channel();
+        let mut state = self.state.lock().unwrap();
+        let subscriber = Subscriber { symbols, tx };
+        for (symbol, published) in &state.books {
+            if subscriber.wants(symbol) {
+                if let Ok(json) = serde_json::to_string(&BookMessage::snapshot(symbol, published.ts, &published.book)) {
+                    let _ = subscriber.tx.send(Arc::new(json));
+                }
+            }
+        }
+        state.subscribers.push(subscriber);
+        rx
+    }
+
+    // subscribers returns the number of connected clients.
+    pub fn subscribers(&self) -> usize {
+        self.state.lock().unwrap().subscribers.len()
+    }
+}
+
+// SymbolsCallback stores the symbols a client requested during the handshake.
+struct SymbolsCallback<'a>(&'a mut Option<Vec<String>>);
+
+impl Callback for SymbolsCallback<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        *self.0 = requested_symbols(request);
+        Ok(response)
+    }
+}
+
+// requested_symbols returns the symbols listed in the symbols query parameter of the
+// handshake request, or None for every symbol.
+fn requested_symbols(request: &Request) -> Option<Vec<String>> {
+    let query = request.uri().query()?;
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "symbols")
+        .map(|(_, symbols)| symbols.split(',').filter(|symbol| !symbol.is_empty()).map(str::to_string).collect())
+}
+
+impl Sink for WsPublisher {
+    fn name(&self) -> &str {
+        "ws-publish"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        let mut copy = LocalOrderBook::new();
+        copy.apply_snapshot(book.to_snapshot());
+        let mut state = self.state.lock().unwrap();
+        state.broadcast(&BookMessage::snapshot(symbol, ts, &copy));
+        state.books.insert(symbol.to_string(), PublishedBook { ts, book: copy });
+        Ok(())
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        let mut state = self.state.lock().unwrap();
+        let Some(published) = state.books.get_mut(symbol) else {
+            return Ok(());
+        };
+        published.book.apply_delta(&event.delta);
+        published.ts = event.ts;
+        state.broadcast(&BookMessage::update(symbol, event));
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::metrics::FeedMetrics;
+
+pub const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+
+// Backpressure is what a sender does when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    // Block waits for the receiver to make room, stalling the reader thread and, once the
+    // socket buffers fill, the exchange.
+    #[default]
+    Block,
+    // DropOldest drops the item at the front of the queue to make room. Dropping a book
+    // delta leaves a gap, which the book reports out of sync on and has to resync from.
+    DropOldest,
+    // Coalesce merges the item into the one at the back of the queue, so a queue of a
+    // single symbol's deltas folds into its latest state rather than growing. The queue
+    // should still hold the deltas buffered while the snapshot is fetched, as a merged delta
+    // spanning the snapshot can't be synced from.
+    Coalesce,
+}
+
+// QueueConfig is the capacity of the queues between the reader and book threads, and what
+// is done once one is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueConfig {
+    pub capacity: usize,
+    pub backpressure: Backpressure,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self { capacity: DEFAULT_QUEUE_CAPACITY, backpressure: Backpressure::default() }
+    }
+}
+
+// Coalesce is implemented by items a later item can be merged into, leaving one item that
+// leaves the consumer in the same state as both would.
+pub trait Coalesce {
+    fn coalesce(&mut self, later: Self);
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+    senders: usize,
+    receiver: bool,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    // ready is signalled when an item is queued or the last sender goes, and space when an
+    // item is taken or the receiver goes.
+    ready: Condvar,
+    space: Condvar,
+    config: QueueConfig,
+    metrics: Option<Arc<FeedMetrics>>,
+}
+
+// bounded returns a queue of config's capacity, counting its depth and the items it drops
+// and coalesces in metrics.
+pub fn bounded<T: Coalesce>(config: QueueConfig, metrics: Option<Arc<FeedMetrics>>) -> (Sender<T>, Receiver<T>) {
+    let config = QueueConfig { capacity: config.capacity.max(1), ..config };
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State { items: VecDeque::new(), senders: 1, receiver: true }),
+        ready: Condvar::new(),
+        space: Condvar::new(),
+        config,
+        metrics,
+    });
+    (Sender { shared: Arc::clone(&shared) }, Receiver { shared })
+}
+
+// Sender queues items for the Receiver, applying the queue's backpressure once it is full.
+pub struct Sender<T: Coalesce> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Coalesce> Sender<T> {
+    // send queues item, returning it if the receiver has gone.
+    pub fn send(&self, item: T) -> Result<(), T> {
+        let shared = &self.shared;
+        let mut state = shared.state.lock().unwrap();
+        if shared.config.backpressure == Backpressure::Block {
+            while state.receiver && state.items.len() >= shared.config.capacity {
+                state = shared.space.wait(state).unwrap();
+            }
+        }
+        if !state.receiver {
+            return Err(item);
+        }
+        if state.items.len() < shared.config.capacity {
+            state.items.push_back(item);
+            shared.count(|metrics| metrics.queued.fetch_add(1, Ordering::Relaxed));
+        } else if shared.config.backpressure == Backpressure::DropOldest {
+            state.items.pop_front();
+            state.items.push_back(item);
+            shared.count(|metrics| metrics.queue_dropped.fetch_add(1, Ordering::Relaxed));
+        } else {
+            // The queue has at least one item, its capacity being at least 1.
+            state.items.back_mut().unwrap().coalesce(item);
+            shared.count(|metrics| metrics.queue_coalesced.fetch_add(1, Ordering::Relaxed));
+        }
+        shared.ready.notify_one();
+        Ok(())
+    }
+}
+
+impl<T: Coalesce> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        Self { shared: Arc::clone(&self.shared) }
+    }
+}
+
+impl<T: Coalesce> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            self.shared.ready.notify_all();
+        }
+    }
+}
+
+// Receiver takes items off the queue, with the same methods as an mpsc::Receiver.
+pub struct Receiver<T: Coalesce> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Coalesce> Receiver<T> {
+    // len returns the number of items queued.
+    pub fn len(&self) -> usize {
+        self.shared.state.lock().unwrap().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(item) = self.take(&mut state) {
+                return Ok(item);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self.shared.ready.wait(state).unwrap();
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.state.lock().unwrap();
+        match self.take(&mut state) {
+            Some(item) => Ok(item),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(item) = self.take(&mut state) {
+                return Ok(item);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self.shared.ready.wait_timeout(state, remaining).unwrap().0;
+        }
+    }
+
+    // try_iter returns an iterator over the items queued, without waiting for more.
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(|| self.try_recv().ok())
+    }
+
+    fn take(&self, state: &mut State<T>) -> Option<T> {
+        let item = state.items.pop_front()?;
+        self.shared.count(|metrics| metrics.queued.fetch_sub(1, Ordering::Relaxed));
+        self.shared.space.notify_one();
+        Some(item)
+    }
+}
+
+impl<T: Coalesce> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.receiver = false;
+        let dropped = state.items.len() as u64;
+        state.items.clear();
+        self.shared.count(|metrics| metrics.queued.fetch_sub(dropped, Ordering::Relaxed));
+        self.shared.space.notify_all();
+    }
+}
+
+// Iterating a Receiver blocks for each item, ending once every sender has gone.
+impl<T: Coalesce> Iterator for Receiver<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.recv().ok()
+    }
+}
+
+impl<T> Shared<T> {
+    fn count(&self, f: impl FnOnce(&FeedMetrics) -> u64) {
+        if let Some(metrics) = &self.metrics {
+            f(metrics);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+
+    // Batch is a queued item coalescing by appending the later item's values.
+    #[derive(Debug, PartialEq)]
+    struct Batch(Vec<u32>);
+
+    impl Coalesce for Batch {
+        fn coalesce(&mut self, later: Self) {
+            self.0.extend(later.0);
+        }
+    }
+
+    // queue returns a queue of capacity 2 with backpressure, and the metrics it counts in.
+    fn queue(backpressure: Backpressure) -> (Sender<Batch>, Receiver<Batch>, Arc<FeedMetrics>) {
+        let metrics = Arc::new(FeedMetrics::default());
+        let (sender, receiver) = bounded(QueueConfig { capacity: 2, backpressure }, Some(Arc::clone(&metrics)));
+        (sender, receiver, metrics)
+    }
+
+    fn count(metric: &AtomicU64) -> u64 {
+        metric.load(Ordering::Relaxed)
+    }
+
+    fn values(receiver: &Receiver<Batch>) -> Vec<Vec<u32>> {
+        receiver.try_iter().map(|batch| batch.0).collect()
+    }
+
+    #[test]
+    fn blocks_a_sender_until_there_is_room() {
+        let (sender, receiver, metrics) = queue(Backpressure::Block);
+        assert!(sender.send(Batch(vec![1])).is_ok());
+        assert!(sender.send(Batch(vec![2])).is_ok());
+        assert_eq!(count(&metrics.queued), 2);
+
+        let (sent_tx, sent) = mpsc::channel();
+        let blocked = thread::spawn(move || {
+            let result = sender.send(Batch(vec![3]));
+            sent_tx.send(()).unwrap();
+            result
+        });
+        assert_eq!(sent.recv_timeout(Duration::from_millis(50)), Err(RecvTimeoutError::Timeout));
+        assert_eq!(receiver.recv(), Ok(Batch(vec![1])));
+        assert!(sent.recv_timeout(Duration::from_secs(5)).is_ok());
+        assert!(blocked.join().unwrap().is_ok());
+        assert_eq!(values(&receiver), vec![vec![2], vec![3]]);
+        assert_eq!((count(&metrics.queued), count(&metrics.queue_dropped), count(&metrics.queue_coalesced)), (0, 0, 0));
+    }
+
+    #[test]
+    fn drops_the_oldest_item_once_full() {
+        let (sender, receiver, metrics) = queue(Backpressure::DropOldest);
+        for value in 1..=4 {
+            assert!(sender.send(Batch(vec![value])).is_ok());
+        }
+        assert_eq!(receiver.len(), 2);
+        assert_eq!(count(&metrics.queued), 2);
+        assert_eq!(count(&metrics.queue_dropped), 2);
+        assert_eq!(values(&receiver), vec![vec![3], vec![4]]);
+        assert_eq!(count(&metrics.queued), 0);
+    }
+
+    #[test]
+    fn coalesces_into_the_newest_item_once_full() {
+        let (sender, receiver, metrics) = queue(Backpressure::Coalesce);
+        for value in 1..=4 {
+            assert!(sender.send(Batch(vec![value])).is_ok());
+        }
+        assert_eq!(count(&metrics.queued), 2);
+        assert_eq!(count(&metrics.queue_coalesced), 2);
+        assert_eq!(values(&receiver), vec![vec![1], vec![2, 3, 4]]);
+        assert_eq!(count(&metrics.queued), 0);
+    }
+
+    #[test]
+    fn disconnects_the_receiver_once_every_sender_has_gone() {
+        let (sender, receiver, _) = queue(Backpressure::Block);
+        let clone = sender.clone();
+        assert!(sender.send(Batch(vec![1])).is_ok());
+        drop(sender);
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(10)), Ok(Batch(vec![1])));
+        // A sender is left, so the queue is only empty.
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(10)), Err(RecvTimeoutError::Timeout));
+
+        assert!(clone.send(Batch(vec![2])).is_ok());
+        drop(clone);
+        // What was queued is still received, and only then is the queue disconnected.
+        assert_eq!(receiver.recv(), Ok(Batch(vec![2])));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(receiver.recv(), Err(RecvError));
+        assert_eq!(receiver.recv_timeout(Duration::from_millis(10)), Err(RecvTimeoutError::Disconnected));
+    }
+
+    #[test]
+    fn fails_sends_once_the_receiver_has_gone() {
+        let (sender, receiver, metrics) = queue(Backpressure::Block);
+        assert!(sender.send(Batch(vec![1])).is_ok());
+        assert!(sender.send(Batch(vec![2])).is_ok());
+        // A sender blocked on the full queue is woken to fail.
+        let blocked = thread::spawn(move || {
+            let result = sender.send(Batch(vec![3]));
+            (sender, result)
+        });
+        thread::sleep(Duration::from_millis(20));
+        drop(receiver);
+        let (sender, result) = blocked.join().unwrap();
+        assert_eq!(result, Err(Batch(vec![3])));
+        assert_eq!(sender.send(Batch(vec![4])), Err(Batch(vec![4])));
+        // The items dropped with the receiver are no longer counted as queued.
+        assert_eq!(count(&metrics.queued), 0);
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::compact::{self, CompactReader};
+use crate::exchange::ExchangeFeed;
+use crate::exchange_api_types::{RestQuote, RestSnapshot, SnapshotData, WsQuote};
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::recorder::{self, RecordedFrame};
+use crate::sync::{BookSync, SyncOutcome};
+use crate::units::{Price, Qty};
+
+// MAX_AHEAD bounds the events kept while no book is synced, waiting for a snapshot to
+// apply them to.
+const MAX_AHEAD: usize = 100_000;
+
+// Record is a snapshot or a delta read from a recording.
+enum Record {
+    Snapshot(RestSnapshot),
+    Event(MarketEvent),
+}
+
+type Records = Box<dyn Iterator<Item = io::Result<Record>>>;
+
+// frame_records reads the snapshots and book frames recorded for symbol in a frame
+// recording, parsing the frames as exchange does.
+fn frame_records(path: &Path, symbol: &str, max_level: usize, exchange: Arc<dyn ExchangeFeed>) -> io::Result<Records> {
+    let snapshot_topic = recorder::snapshot_topic(symbol);
+    let book_topic = exchange.book_topic(symbol, max_level);
+    let records = recorder::read_frames(path)?.filter_map(move |frame| {
+        let RecordedFrame { topic, text, .. } = match frame {
+            Ok(frame) => frame,
+            Err(e) => return Some(Err(e)),
+        };
+        if topic == snapshot_topic {
+            Some(serde_json::from_str(&text).map(Record::Snapshot).map_err(io::Error::from))
+        } else if topic == book_topic {
+            exchange.parse_book(&text, Instant::now()).map_err(io::Error::from).transpose().map(|event| event.map(Record::Event))
+        } else {
+            None
+        }
+    });
+    Ok(Box::new(records))
+}
+
+// compact_records reads the records of a compact recording of symbol, its snapshot events
+// as snapshots.
+fn compact_records(path: &Path, symbol: &str) -> io::Result<Records> {
+    let reader = CompactReader::open(path)?;
+    if reader.symbol() != symbol {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is a recording of {}", path.display(), reader.symbol())));
+    }
+    let records = reader.map(|event| {
+        let event = event?;
+        if !event.snapshot {
+            return Ok(Record::Event(event));
+        }
+        let quotes = |quotes: Vec<WsQuote>| quotes.into_iter().map(|quote| RestQuote { price: quote.price, quantity: quote.quantity }).collect();
+        let data = SnapshotData { bids: quotes(event.delta.bids), asks: quotes(event.delta.asks) };
+        Ok(Record::Snapshot(RestSnapshot::new(event.ts, event.seq, data)))
+    });
+    Ok(Box::new(records))
+}
+
+// BookSample is the top levels of a reconstructed book at ts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookSample {
+    pub ts: u64,
+    pub bids: Vec<(Price, Qty)>,
+    pub asks: Vec<(Price, Qty)>,
+}
+
+// Reconstructor rebuilds the book recorded for a symbol as it stood at given exchange
+// timestamps, for looking at the liquidity there was at the time of a fill. It reads a
+// frame or compact recording forward, syncing the book from each recorded snapshot the
+// way the client did and applying the deltas up to the timestamp asked for, so the times
+// asked for must not go backwards.
+pub struct Reconstructor {
+    records: Records,
+    exchange: Arc<dyn ExchangeFeed>,
+    // next is a record read past the time last asked for, kept for the next.
+    next: Option<Record>,
+    // ahead are the events read but not yet applied: ones past the time last asked for, or
+    // waiting for a snapshot while no book is synced.
+    ahead: VecDeque<MarketEvent>,
+    sync: Option<BookSync>,
+    // last_seq is the seq of the last event applied to the synced book.
+    last_seq: u64,
+    done: bool,
+}
+
+impl Reconstructor {
+    // open reads the recording at path, a compact recording or a frame recording of the
+    // book for symbol subscribed to with max_level levels on exchange.
+    pub fn open(path: &Path, symbol: &str, max_level: usize, exchange: Arc<dyn ExchangeFeed>) -> io::Result<Self> {
+        let records = match compact::is_compact(path) {
+            true => compact_records(path, symbol)?,
+            false => frame_records(path, symbol, max_level, Arc::clone(&exchange))?,
+        };
+        Ok(Self { records, exchange, next: None, ahead: VecDeque::new(), sync: None, last_seq: 0, done: false })
+    }
+
+    // book_at returns the book as of ts, having applied every delta recorded up to it, or
+    // None if the recording had no synced book at ts.
+    pub fn book_at(&mut self, ts: u64) -> io::Result<Option<&LocalOrderBook>> {
+        loop {
+            self.apply_ahead(ts);
+            // Once the next event is past ts and continues the book, the book is as of ts. If
+            // it starts a new stream instead, as after a restart of the recorder, the
+            // snapshot that stream was synced from is recorded after its first events, and
+            // is read on for in case it was taken by ts.
+            if self.done || self.front_continues() {
+                break;
+            }
+            let Some(record) = self.next_record()? else {
+                self.done = true;
+                continue;
+            };
+            match record {
+                Record::Snapshot(snapshot) if snapshot.timestamp > ts => {
+                    self.next = Some(Record::Snapshot(snapshot));
+                    break;
+                }
+                Record::Snapshot(snapshot) => {
+                    self.last_seq = snapshot.seq();
+                    self.sync = Some(BookSync::for_exchange(snapshot, self.exchange.as_ref()));
+                }
+                Record::Event(event) => {
+                    if self.ahead.len() >= MAX_AHEAD {
+                        self.ahead.pop_front();
+                    }
+                    self.ahead.push_back(event);
+                }
+            }
+        }
+        Ok(self.sync.as_ref().map(BookSync::book))
+    }
+
+    // sample returns the top depth levels a side of the book at ts.
+    pub fn sample(&mut self, ts: u64, depth: usize) -> io::Result<Option<BookSample>> {
+        let book = self.book_at(ts)?;
+        Ok(book.map(|book| BookSample { ts, bids: book.bids().take(depth).collect(), asks: book.asks().take(depth).collect() }))
+    }
+
+    // series samples the top depth levels of the book every interval from from to to, both
+    // included, skipping the times there was no synced book.
+    pub fn series(&mut self, from: u64, to: u64, interval: Duration, depth: usize) -> io::Result<Vec<BookSample>> {
+        let step = (interval.as_millis() as u64).max(1);
+        let mut samples = Vec::new();
+        let mut ts = from;
+        while ts <= to {
+            samples.extend(self.sample(ts, depth)?);
+            ts += step;
+        }
+        Ok(samples)
+    }
+
+    // apply_ahead applies the events ahead up to ts that continue the synced book, dropping
+    // the book at the first that doesn't until the next snapshot.
+    fn apply_ahead(&mut self, ts: u64) {
+        while self.ahead.front().is_some_and(|event| event.ts <= ts) {
+            if !self.front_continues() {
+                self.sync = None;
+                return;
+            }
+            let event = self.ahead.pop_front().unwrap();
+            let Some(sync) = self.sync.as_mut() else { return };
+            match sync.on_event(&event) {
+                SyncOutcome::Behind(_) => {}
+                SyncOutcome::Synced | SyncOutcome::Applied => self.last_seq = event.seq,
+                SyncOutcome::OutOfSync | SyncOutcome::ChecksumMismatch { .. } => self.sync = None,
+            }
+        }
+    }
+
+    // front_continues returns true if the next event ahead continues the synced book, or
+    // is left to the sync to place while it hasn't synced yet.
+    fn front_continues(&self) -> bool {
+        match (&self.sync, self.ahead.front()) {
+            (Some(sync), Some(event)) => !sync.is_synced() || event.snapshot || event.prev_seq == self.last_seq,
+            _ => false,
+        }
+    }
+
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        match self.next.take() {
+            Some(record) => Ok(Some(record)),
+            None => self.records.next().transpose(),
+        }
+    }
+}
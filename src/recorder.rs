@@ -0,0 +1,211 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::exchange_api_types::RestSnapshot;
+use crate::snapshot::{SnapshotError, SnapshotSource};
+
+// RecordedFrame is one line of a frame recording: a raw websocket frame, or a snapshot
+// fetched from a SnapshotSource, with the time it was received.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    // received_ms is the wall clock time the frame was read, in ms since the epoch.
+    pub received_ms: u64,
+    // topic is the websocket topic the frame arrived on, or snapshot@SYMBOL for snapshots.
+    pub topic: String,
+    pub text: String,
+}
+
+// snapshot_topic is the topic snapshots for symbol are recorded under.
+pub fn snapshot_topic(symbol: &str) -> String {
+    format!("snapshot@{}", symbol)
+}
+
+// FLUSH_INTERVAL bounds how many recorded frames can be lost if the process dies.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+struct FrameWriter {
+    file: BufWriter<File>,
+    last_flush: Instant,
+}
+
+// FrameRecorder appends frames to a newline delimited JSON file. It is a cheap handle
+// that can be cloned into every connection, all writing to the same file.
+#[derive(Clone)]
+pub struct FrameRecorder {
+    path: PathBuf,
+    writer: Arc<Mutex<FrameWriter>>,
+}
+
+impl fmt::Debug for FrameRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameRecorder").field("path", &self.path).finish()
+    }
+}
+
+impl FrameRecorder {
+    // create opens path for appending, creating it and its directory if needed.
+    pub fn create(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = File::options().create(true).append(true).open(&path)?;
+        let writer = FrameWriter { file: BufWriter::new(file), last_flush: Instant::now() };
+        Ok(Self { path, writer: Arc::new(Mutex::new(writer)) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // record appends text, received on topic now.
+    pub fn record(&self, topic: &str, text: &str) -> io::Result<()> {
+        let frame = RecordedFrame { received_ms: now_ms(), topic: topic.to_string(), text: text.to_string() };
+        let line = serde_json::to_string(&frame)?;
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer.file, "{}", line)?;
+        if writer.last_flush.elapsed() >= FLUSH_INTERVAL {
+            writer.file.flush()?;
+            writer.last_flush = Instant::now();
+        }
+        Ok(())
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.last_flush = Instant::now();
+        writer.file.flush()
+    }
+}
+
+// read_frames returns an iterator over the frames recorded in path, in recorded order.
+// Lines that can't be parsed, such as a truncated last line, end the iteration with an error.
+pub fn read_frames(path: &Path) -> io::Result<impl Iterator<Item = io::Result<RecordedFrame>>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(reader.lines().map(|line| Ok(serde_json::from_str(&line?)?)))
+}
+
+// ReplaySpeed is how fast recorded frames are replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplaySpeed {
+    // Original replays with the recorded gaps between frames.
+    #[default]
+    Original,
+    // Accelerated replays with the recorded gaps divided by the given factor.
+    Accelerated(f64),
+    // Max replays every frame as fast as it can be consumed.
+    Max,
+}
+
+// ReplayConfig selects a frame recording to replay in place of a live websocket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayConfig {
+    pub path: PathBuf,
+    pub speed: ReplaySpeed,
+}
+
+// replay passes the text of every frame recorded on topic to on_message, paced by speed,
+// until the recording ends or on_message returns false.
+pub fn replay<F>(config: &ReplayConfig, topic: &str, mut on_message: F) -> io::Result<()>
+where
+    F: FnMut(&str) -> bool,
+{
+    let start = Instant::now();
+    let mut first_ms = None;
+
+    for frame in read_frames(&config.path)? {
+        let frame = frame?;
+        if frame.topic != topic {
+            continue;
+        }
+
+        let first = *first_ms.get_or_insert(frame.received_ms);
+        let offset = Duration::from_millis(frame.received_ms.saturating_sub(first));
+        let due = match config.speed {
+            ReplaySpeed::Original => Some(offset),
+            ReplaySpeed::Accelerated(factor) if factor > 0.0 => Some(offset.div_f64(factor)),
+            ReplaySpeed::Accelerated(_) | ReplaySpeed::Max => None,
+        };
+        if let Some(wait) = due.and_then(|due| due.checked_sub(start.elapsed())) {
+            thread::sleep(wait);
+        }
+
+        if !on_message(&frame.text) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// RecordingSource wraps a SnapshotSource and records every snapshot it returns, so a
+// recording holds everything needed to sync the book offline.
+pub struct RecordingSource {
+    inner: Box<dyn SnapshotSource>,
+    recorder: FrameRecorder,
+}
+
+impl RecordingSource {
+    pub fn new(inner: Box<dyn SnapshotSource>, recorder: FrameRecorder) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl SnapshotSource for RecordingSource {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn fetch(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        let snapshot = self.inner.fetch(symbol, max_level)?;
+        self.recorder.record(&snapshot_topic(symbol), &serde_json::to_string(&snapshot)?)?;
+        Ok(snapshot)
+    }
+}
+
+// ReplaySource returns the snapshots stored in a frame recording, in order, one per fetch,
+// so a replayed feed resyncs from the same snapshots the live feed did.
+pub struct ReplaySource {
+    path: PathBuf,
+    fetched: Mutex<usize>,
+}
+
+impl ReplaySource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), fetched: Mutex::new(0) }
+    }
+}
+
+impl SnapshotSource for ReplaySource {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    fn fetch(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        let topic = snapshot_topic(symbol);
+        let mut fetched = self.fetched.lock().unwrap();
+
+        let mut snapshots = read_frames(&self.path)?.filter(|frame| {
+            frame.as_ref().map_or(true, |frame| frame.topic == topic)
+        });
+        let frame = snapshots.nth(*fetched).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no more {} snapshots recorded", symbol))
+        })??;
+        *fetched += 1;
+
+        let mut snapshot: RestSnapshot = serde_json::from_str(&frame.text)?;
+        snapshot.data.bids.truncate(max_level);
+        snapshot.data.asks.truncate(max_level);
+        Ok(snapshot)
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use redis::{Client, Connection, Pipeline, RedisResult};
+use serde::Serialize;
+
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+use crate::units::{Price, Qty};
+
+// RedisConfig configures what a RedisSink publishes. Channels and keys are named
+// <prefix><symbol>:top and <prefix><symbol>:snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedisConfig {
+    pub prefix: String,
+    // snapshot_depth is the number of levels per side in published snapshots.
+    pub snapshot_depth: usize,
+    // snapshot_interval is how often a snapshot is published between resyncs.
+    pub snapshot_interval: Duration,
+    // write_keys also stores the latest top of book and snapshot under their channel name,
+    // for consumers that poll rather than subscribe.
+    pub write_keys: bool,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self { prefix: "woox:".to_string(), snapshot_depth: 20, snapshot_interval: Duration::from_secs(1), write_keys: false }
+    }
+}
+
+// TopOfBook is the message published to the top channel, levels being [price, quantity].
+#[derive(Debug, Serialize)]
+struct TopOfBook<'a> {
+    symbol: &'a str,
+    ts: u64,
+    bid: Option<[f64; 2]>,
+    ask: Option<[f64; 2]>,
+}
+
+// Snapshot is the message published to the snapshot channel, bids and asks best first.
+#[derive(Debug, Serialize)]
+struct Snapshot<'a> {
+    symbol: &'a str,
+    ts: u64,
+    bids: Vec<[f64; 2]>,
+    asks: Vec<[f64; 2]>,
+}
+
+fn level((price, quantity): (Price, Qty)) -> [f64; 2] {
+    [price.value(), quantity.value()]
+}
+
+// RedisSink publishes the top of book to Redis whenever it changes, and a snapshot on every
+// resync and every snapshot_interval, as JSON on pub/sub channels.
+pub struct RedisSink {
+    conn: Connection,
+    config: RedisConfig,
+    // published is the last top of book and snapshot time published for each symbol.
+    published: HashMap<String, Published>,
+}
+
+type Top = (Option<[f64; 2]>, Option<[f64; 2]>);
+
+#[derive(Debug, Default)]
+struct Published {
+    top: Option<Top>,
+    snapshot_at: Option<Instant>,
+}
+
+impl RedisSink {
+    // connect connects to the Redis server at url, e.g. redis://127.0.0.1:6379.
+    pub fn connect(url: &str, config: RedisConfig) -> RedisResult<Self> {
+        let conn = Client::open(url)?.get_connection()?;
+        Ok(Self { conn, config, published: HashMap::new() })
+    }
+
+    fn channel(&self, symbol: &str, kind: &str) -> String {
+        format!("{}{}:{}", self.config.prefix, symbol, kind)
+    }
+
+    // publish adds a command publishing message to channel, and storing it under channel
+    // if keys are written, to pipe.
+    fn publish(&self, pipe: &mut Pipeline, channel: &str, message: &str) {
+        pipe.cmd("PUBLISH").arg(channel).arg(message).ignore();
+        if self.config.write_keys {
+            pipe.cmd("SET").arg(channel).arg(message).ignore();
+        }
+    }
+
+    // publish_top publishes the top of book if it changed since it was last published.
+    fn publish_top(&mut self, pipe: &mut Pipeline, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        let top = (book.best_bid().map(level), book.best_ask().map(level));
+        let published = self.published.entry(symbol.to_string()).or_default();
+        if published.top == Some(top) {
+            return Ok(());
+        }
+        published.top = Some(top);
+        let message = serde_json::to_string(&TopOfBook { symbol, ts, bid: top.0, ask: top.1 })?;
+        self.publish(pipe, &self.channel(symbol, "top"), &message);
+        Ok(())
+    }
+
+    fn publish_snapshot(&mut self, pipe: &mut Pipeline, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        self.published.entry(symbol.to_string()).or_default().snapshot_at = Some(Instant::now());
+        let depth = self.config.snapshot_depth;
+        let message = serde_json::to_string(&Snapshot {
+            symbol,
+            ts,
+            bids: book.bids().take(depth).map(level).collect(),
+            asks: book.asks().take(depth).map(level).collect(),
+        })?;
+        self.publish(pipe, &self.channel(symbol, "snapshot"), &message);
+        Ok(())
+    }
+}
+
+impl Sink for RedisSink {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        let mut pipe = redis::pipe();
+        self.publish_snapshot(&mut pipe, symbol, ts, book)?;
+        self.publish_top(&mut pipe, symbol, ts, book)?;
+        pipe.query::<()>(&mut self.conn)?;
+        Ok(())
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        let mut pipe = redis::pipe();
+        self.publish_top(&mut pipe, symbol, event.ts, book)?;
+        let snapshot_at = self.published.get(symbol).and_then(|published| published.snapshot_at);
+        if snapshot_at.is_none_or(|at| at.elapsed() >= self.config.snapshot_interval) {
+            self.publish_snapshot(&mut pipe, symbol, event.ts, book)?;
+        }
+        if pipe.cmd_iter().next().is_some() {
+            pipe.query::<()>(&mut self.conn)?;
+        }
+        Ok(())
+    }
+}
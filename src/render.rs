@@ -0,0 +1,356 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::exchange_api_types::{Side, WsTrade};
+use crate::orderbook::LocalOrderBook;
+use crate::rest::{RestClient, RestError, RestInstrument};
+use crate::units::{Price, Qty};
+
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_GREEN: &str = "\x1b[32m";
+const ANSI_YELLOW: &str = "\x1b[33m";
+const ANSI_BOLD: &str = "\x1b[1m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+// MAX_DECIMALS caps the decimals derived from a tick size.
+const MAX_DECIMALS: usize = 12;
+
+// BAR_WIDTH is the width in characters of the size bar of the largest displayed level.
+const BAR_WIDTH: usize = 30;
+
+// RenderStyle selects how the book is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderStyle {
+    // Plain prints the bids then the asks, one level per line.
+    Plain,
+    // Ladder prints a colored price ladder with the asks above the bids, cumulative sizes
+    // and bars proportional to each level's size.
+    Ladder,
+}
+
+// Precision is the number of decimals prices and quantities are formatted with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precision {
+    pub price: usize,
+    pub quantity: usize,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Self { price: 2, quantity: 4 }
+    }
+}
+
+impl Precision {
+    // from_ticks returns the precision that shows every digit of a price tick and quantity
+    // lot, e.g. 0.01 and 0.0001 give 2 and 4 decimals.
+    pub fn from_ticks(price_tick: f64, quantity_tick: f64) -> Self {
+        Self { price: decimals(price_tick), quantity: decimals(quantity_tick) }
+    }
+
+    pub fn from_instrument(instrument: &RestInstrument) -> Self {
+        Self::from_ticks(instrument.quote_tick, instrument.base_tick)
+    }
+
+    // fetch returns the precision of symbol from its instrument metadata.
+    pub fn fetch(rest: &RestClient, symbol: &str) -> Result<Self, RestError> {
+        let instruments = rest.instruments(Some(symbol))?;
+        match instruments.iter().find(|instrument| instrument.symbol == symbol) {
+            Some(instrument) => Ok(Self::from_instrument(instrument)),
+            None => Err(RestError::Api(format!("unknown symbol {}", symbol))),
+        }
+    }
+
+    pub fn format_price(&self, price: Price) -> String {
+        format!("{:.*}", self.price, price)
+    }
+
+    pub fn format_quantity(&self, quantity: Qty) -> String {
+        format!("{:.*}", self.quantity, quantity)
+    }
+}
+
+// decimals returns the number of decimals needed to show multiples of tick exactly.
+fn decimals(tick: f64) -> usize {
+    if !tick.is_finite() || tick <= 0.0 {
+        return 0;
+    }
+    (0..MAX_DECIMALS)
+        .find(|&decimals| {
+            let scaled = tick * 10f64.powi(decimals as i32);
+            scaled.round() >= 1.0 && (scaled - scaled.round()).abs() <= scaled * 1e-6
+        })
+        .unwrap_or(MAX_DECIMALS)
+}
+
+// RenderConfig controls how much of the book is displayed and how often.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderConfig {
+    // depth is the number of levels printed on each side of the book.
+    pub depth: usize,
+    // max_fps caps the number of renders per second, None renders on every update.
+    pub max_fps: Option<u32>,
+    // on_change_only skips renders when the displayed levels haven't changed.
+    pub on_change_only: bool,
+    pub style: RenderStyle,
+    pub precision: Precision,
+    // tape_rows is the number of recent trades printed below the book, 0 for none.
+    pub tape_rows: usize,
+    // large_trade_size flags the trades of at least this size on the tape.
+    pub large_trade_size: Option<Qty>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            depth: 5,
+            max_fps: Some(10),
+            on_change_only: false,
+            style: RenderStyle::Plain,
+            precision: Precision::default(),
+            tape_rows: 0,
+            large_trade_size: None,
+        }
+    }
+}
+
+// TradeTape is a rolling time and sales of the latest public trades, newest first, that
+// flags the prints of at least large_size.
+#[derive(Debug, Clone)]
+pub struct TradeTape {
+    trades: VecDeque<WsTrade>,
+    capacity: usize,
+    large_size: Option<Qty>,
+}
+
+impl TradeTape {
+    pub fn new(capacity: usize, large_size: Option<Qty>) -> Self {
+        Self { trades: VecDeque::with_capacity(capacity), capacity, large_size }
+    }
+
+    // push adds trade to the top of the tape, dropping the oldest once it is full.
+    pub fn push(&mut self, trade: WsTrade) {
+        self.trades.push_front(trade);
+        self.trades.truncate(self.capacity);
+    }
+
+    // trades returns the trades on the tape, newest first.
+    pub fn trades(&self) -> impl Iterator<Item = &WsTrade> {
+        self.trades.iter()
+    }
+
+    pub fn is_large(&self, trade: &WsTrade) -> bool {
+        self.large_size.is_some_and(|size| Qty::new(trade.quantity) >= size)
+    }
+}
+
+// Renderer decides when the book should be redrawn according to its RenderConfig.
+pub struct Renderer {
+    config: RenderConfig,
+    last_render: Option<Instant>,
+    last_levels: Vec<(Price, Qty)>,
+    tape: TradeTape,
+    // tape_changed is set when a trade is added to the tape since the last render.
+    tape_changed: bool,
+    // pending is set when a render is held back by the frame rate limit, for render_pending
+    // to catch up on once the frame interval has passed.
+    pending: bool,
+}
+
+impl Renderer {
+    pub fn new(config: RenderConfig) -> Self {
+        Self {
+            config,
+            last_render: None,
+            last_levels: Vec::new(),
+            tape: TradeTape::new(config.tape_rows, config.large_trade_size),
+            tape_changed: false,
+            pending: false,
+        }
+    }
+
+    pub fn config(&self) -> &RenderConfig {
+        &self.config
+    }
+
+    pub fn set_style(&mut self, style: RenderStyle) {
+        self.config.style = style;
+    }
+
+    pub fn set_depth(&mut self, depth: usize) {
+        self.config.depth = depth;
+    }
+
+    pub fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        self.config.max_fps = max_fps;
+    }
+
+    // on_trade adds trade to the tape printed below the book, if there is one.
+    pub fn on_trade(&mut self, trade: WsTrade) {
+        if self.config.tape_rows > 0 {
+            self.tape.push(trade);
+            self.tape_changed = true;
+        }
+    }
+
+    // should_render returns true if the book should be printed now, and records the render if so.
+    pub fn should_render(&mut self, book: &LocalOrderBook) -> bool {
+        let now = Instant::now();
+        if let (Some(interval), Some(last)) = (self.frame_interval(), self.last_render) {
+            if now.duration_since(last) < interval {
+                self.pending = true;
+                return false;
+            }
+        }
+        self.pending = false;
+
+        if self.config.on_change_only {
+            let depth = self.config.depth;
+            let levels: Vec<_> = book.bids().take(depth).chain(book.asks().take(depth)).collect();
+            if self.last_render.is_some() && levels == self.last_levels && !self.tape_changed {
+                return false;
+            }
+            self.last_levels = levels;
+        }
+
+        self.last_render = Some(now);
+        self.tape_changed = false;
+        true
+    }
+
+    // frame_interval is the least time between renders, if the frame rate is limited.
+    pub fn frame_interval(&self) -> Option<Duration> {
+        self.config.max_fps.map(|fps| Duration::from_secs(1) / fps.max(1))
+    }
+
+    // render_pending renders book if a render was held back by the frame rate limit and
+    // the frame interval has since passed, so the last update is printed even if no other
+    // follows it. It returns whether it printed.
+    pub fn render_pending(&mut self, book: &LocalOrderBook) -> bool {
+        self.pending && self.render(book)
+    }
+
+    // render prints the book if should_render allows it, returning whether it printed.
+    pub fn render(&mut self, book: &LocalOrderBook) -> bool {
+        if !self.should_render(book) {
+            return false;
+        }
+        match self.config.style {
+            RenderStyle::Plain => book.print_top(self.config.depth, self.config.precision),
+            RenderStyle::Ladder => print_ladder(book, self.config.depth, self.config.precision),
+        }
+        if self.config.tape_rows > 0 {
+            println!();
+            print_tape(&self.tape, self.config.precision);
+        }
+        true
+    }
+}
+
+// print_ladder prints the top depth levels of book as a price ladder: asks descending above
+// the spread, bids descending below it, each with its cumulative size and a size bar.
+pub fn print_ladder(book: &LocalOrderBook, depth: usize, precision: Precision) {
+    let asks: Vec<_> = book.asks().take(depth).collect();
+    let bids: Vec<_> = book.bids().take(depth).collect();
+    let max_quantity = asks.iter().chain(&bids).map(|&(_, quantity)| quantity).fold(Qty::ZERO, Qty::max);
+
+    let cumulative = |levels: &[(Price, Qty)]| -> Vec<Qty> {
+        levels.iter().scan(Qty::ZERO, |total, &(_, quantity)| { *total += quantity; Some(*total) }).collect()
+    };
+    let ask_totals = cumulative(&asks);
+    let bid_totals = cumulative(&bids);
+
+    // Clear console
+    print!("{}[2J{}", 27 as char, 27 as char);
+    print!("{}[1;1H", 27 as char);
+
+    println!("{:>5} {:>14} {:>14} {:>14}", "", "PRICE", "SIZE", "TOTAL");
+    for (i, &(price, quantity)) in asks.iter().enumerate().rev() {
+        print_level("ASK", ANSI_RED, price, quantity, ask_totals[i], max_quantity, precision);
+    }
+
+    match (bids.first(), asks.first()) {
+        (Some(&(bid, _)), Some(&(ask, _))) => {
+            let spread = ask - bid;
+            let bps = spread.bps_of(bid.midpoint(ask));
+            println!("{}{:>5} {:>14.*} ({:.2} bps){}", ANSI_YELLOW, "SPRD", precision.price, spread, bps, ANSI_RESET);
+        }
+        _ => println!("{}{:>5} {:>14}{}", ANSI_YELLOW, "SPRD", "-", ANSI_RESET),
+    }
+
+    for (i, &(price, quantity)) in bids.iter().enumerate() {
+        print_level("BID", ANSI_GREEN, price, quantity, bid_totals[i], max_quantity, precision);
+    }
+}
+
+fn print_level(label: &str, color: &str, price: Price, quantity: Qty, total: Qty, max_quantity: Qty, precision: Precision) {
+    let bar = (quantity.ratio(max_quantity) * BAR_WIDTH as f64).round() as usize;
+    println!(
+        "{}{:>5} {:>14.*} {:>14.*} {:>14.*} {}{}",
+        color,
+        label,
+        precision.price,
+        price,
+        precision.quantity,
+        quantity,
+        precision.quantity,
+        total,
+        "\u{2588}".repeat(bar.max(1)),
+        ANSI_RESET
+    );
+}
+
+// print_tape prints the trades on tape, newest first, aggressive buys in green and sells in
+// red. Large prints are bold and marked with a *.
+pub fn print_tape(tape: &TradeTape, precision: Precision) {
+    println!("{:>12} {:>5} {:>14} {:>14}", "TIME", "SIDE", "PRICE", "SIZE");
+    for trade in tape.trades() {
+        let (label, color) = match trade.side {
+            Side::Buy => ("BUY", ANSI_GREEN),
+            Side::Sell => ("SELL", ANSI_RED),
+        };
+        let (weight, flag) = if tape.is_large(trade) { (ANSI_BOLD, " *") } else { ("", "") };
+        println!(
+            "{}{}{:>12} {:>5} {:>14} {:>14}{}{}",
+            color,
+            weight,
+            format_time(trade.ts),
+            label,
+            precision.format_price(Price::new(trade.price)),
+            precision.format_quantity(Qty::new(trade.quantity)),
+            flag,
+            ANSI_RESET
+        );
+    }
+}
+
+// format_time formats a ms timestamp as the UTC time of day.
+pub fn format_time(ts: u64) -> String {
+    let ms = ts % 1000;
+    let secs = ts / 1000 % 86_400;
+    format!("{:02}:{:02}:{:02}.{:03}", secs / 3600, secs / 60 % 60, secs % 60, ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn catches_up_on_a_render_held_back_by_the_frame_rate() {
+        let mut renderer = Renderer::new(RenderConfig { max_fps: Some(50), ..RenderConfig::default() });
+        let book = LocalOrderBook::new();
+        assert!(!renderer.render_pending(&book));
+        assert!(renderer.should_render(&book));
+        assert!(!renderer.should_render(&book));
+        // The frame interval hasn't passed yet, so the held back render stays pending.
+        assert!(!renderer.render_pending(&book));
+        thread::sleep(renderer.frame_interval().unwrap());
+        assert!(renderer.render_pending(&book));
+        assert!(!renderer.render_pending(&book));
+    }
+}
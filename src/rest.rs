@@ -0,0 +1,370 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::exchange_api_types::{f64_from_string_or_number, Side, WsTrade};
+use crate::http_client;
+use crate::retry::{fail_transient, is_transient, RetryPolicy, Retryable};
+
+pub const WOOX_REST_URL: &str = "https://api.woox.io";
+
+// RestError is returned when a Woo X REST request fails.
+#[derive(Debug)]
+pub enum RestError {
+    Http(reqwest::Error),
+    // Api means the exchange rejected the request, with its message if it gave one.
+    Api(String),
+    // RetriesExhausted means every attempt of a retried request failed, with the error of the last.
+    RetriesExhausted { attempts: u32, last: Box<RestError> },
+}
+
+impl fmt::Display for RestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestError::Http(e) => write!(f, "http error: {}", e),
+            RestError::Api(message) => write!(f, "api error: {}", message),
+            RestError::RetriesExhausted { attempts, last } => write!(f, "{} (after {} attempts)", last, attempts),
+        }
+    }
+}
+
+impl std::error::Error for RestError {}
+
+impl Retryable for RestError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            RestError::Http(e) => is_transient(e),
+            _ => false,
+        }
+    }
+
+    fn exhausted(attempts: u32, last: Self) -> Self {
+        RestError::RetriesExhausted { attempts, last: Box::new(last) }
+    }
+}
+
+impl From<reqwest::Error> for RestError {
+    fn from(e: reqwest::Error) -> Self {
+        RestError::Http(e)
+    }
+}
+
+// RestRequest is a typed request to a public Woo X v3 REST endpoint.
+pub trait RestRequest {
+    // Response is the data of the response envelope.
+    type Response: DeserializeOwned;
+
+    // PATH is the endpoint path, relative to the REST base url.
+    const PATH: &'static str;
+
+    fn query(&self) -> Vec<(&'static str, String)>;
+}
+
+// RestClient sends RestRequests to the Woo X REST API, unwrapping the response envelope.
+// Failed requests are retried with its RetryPolicy.
+pub struct RestClient {
+    base_url: String,
+    http: reqwest::blocking::Client,
+    retry: RetryPolicy,
+}
+
+impl Default for RestClient {
+    fn default() -> Self {
+        Self::new(WOOX_REST_URL)
+    }
+}
+
+impl RestClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: http_client::shared(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+
+    pub fn send<R: RestRequest>(&self, request: &R) -> Result<R::Response, RestError> {
+        let url = format!("{}{}", self.base_url, R::PATH);
+        let query = request.query();
+        self.retry.run(R::PATH, || {
+            let response = self.http.get(&url).query(&query).timeout(self.retry.timeout).send()?;
+            fail_transient(response)?.json::<RestResponse<R::Response>>()?.into_data()
+        })
+    }
+
+    // instruments returns the tradable instruments, or just symbol if given.
+    pub fn instruments(&self, symbol: Option<&str>) -> Result<Vec<RestInstrument>, RestError> {
+        let request = InstrumentsRequest { symbol: symbol.map(str::to_string) };
+        Ok(self.send(&request)?.rows)
+    }
+
+    // tokens returns the listed tokens, or just token if given.
+    pub fn tokens(&self, token: Option<&str>) -> Result<Vec<RestToken>, RestError> {
+        let request = TokensRequest { token: token.map(str::to_string) };
+        Ok(self.send(&request)?.rows)
+    }
+
+    pub fn system_status(&self) -> Result<SystemStatus, RestError> {
+        self.send(&SystemStatusRequest)
+    }
+
+    // server_time returns the exchange's clock, in ms since the epoch.
+    pub fn server_time(&self) -> Result<u64, RestError> {
+        Ok(self.send(&ServerTimeRequest)?.timestamp)
+    }
+
+    // market_trades returns up to limit of the most recent public trades for symbol.
+    pub fn market_trades(&self, symbol: &str, limit: usize) -> Result<Vec<RestTrade>, RestError> {
+        let request = MarketTradesRequest { symbol: symbol.to_string(), limit };
+        Ok(self.send(&request)?.rows)
+    }
+}
+
+// RestResponse is the envelope every Woo X v3 REST response is wrapped in.
+#[derive(Debug, Deserialize)]
+pub struct RestResponse<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub message: Option<String>,
+}
+
+impl<T> RestResponse<T> {
+    // into_data returns the data of a successful response, or the exchange's error.
+    pub fn into_data(self) -> Result<T, RestError> {
+        match self {
+            RestResponse { success: true, data: Some(data), .. } => Ok(data),
+            RestResponse { success: true, data: None, .. } => Err(RestError::Api("response has no data".to_string())),
+            RestResponse { message, .. } => Err(RestError::Api(message.unwrap_or_else(|| "request failed".to_string()))),
+        }
+    }
+}
+
+// RestRows is the data of a paged list response.
+#[derive(Debug, Deserialize)]
+pub struct RestRows<T> {
+    pub rows: Vec<T>,
+}
+
+// RestKline is a struct representation of a candle from the Woo X kline history endpoint.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestKline {
+    pub symbol: String,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub open: f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub high: f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub low: f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub close: f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub volume: f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub amount: f64,
+    pub start_timestamp: u64,
+    pub end_timestamp: u64,
+}
+
+// RestTrade is a struct representation of a trade from the Woo X market trades endpoint.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RestTrade {
+    pub symbol: String,
+    pub side: Side,
+    #[serde(alias = "executedPrice", deserialize_with = "f64_from_string_or_number")]
+    pub price: f64,
+    #[serde(alias = "executedQuantity", deserialize_with = "f64_from_string_or_number")]
+    pub quantity: f64,
+    #[serde(alias = "executedTimestamp")]
+    pub timestamp: u64,
+}
+
+impl RestTrade {
+    // into_backfilled converts the trade into the websocket representation, flagged as backfilled.
+    pub fn into_backfilled(self) -> WsTrade {
+        WsTrade {
+            symbol: self.symbol,
+            price: self.price,
+            quantity: self.quantity,
+            side: self.side,
+            ts: self.timestamp,
+            backfilled: true,
+        }
+    }
+}
+
+
+// InstrumentsRequest requests the tradable instruments.
+#[derive(Debug, Clone, Default)]
+pub struct InstrumentsRequest {
+    pub symbol: Option<String>,
+}
+
+impl RestRequest for InstrumentsRequest {
+    type Response = RestRows<RestInstrument>;
+    const PATH: &'static str = "/v3/public/instruments";
+
+    fn query(&self) -> Vec<(&'static str, String)> {
+        self.symbol.iter().map(|symbol| ("symbol", symbol.clone())).collect()
+    }
+}
+
+// RestInstrument is a struct representation of an instrument from the Woo X instruments
+// endpoint: its assets, trading status and order increments and limits.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestInstrument {
+    pub symbol: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub base_asset: String,
+    #[serde(default)]
+    pub quote_asset: String,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub quote_tick: f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    pub base_tick: f64,
+    #[serde(default, deserialize_with = "f64_from_string_or_number")]
+    pub base_min: f64,
+    #[serde(default, deserialize_with = "f64_from_string_or_number")]
+    pub base_max: f64,
+    #[serde(default, deserialize_with = "f64_from_string_or_number")]
+    pub quote_min: f64,
+    #[serde(default, deserialize_with = "f64_from_string_or_number")]
+    pub quote_max: f64,
+    #[serde(default, deserialize_with = "f64_from_string_or_number")]
+    pub min_notional: f64,
+}
+
+impl RestInstrument {
+    pub fn is_trading(&self) -> bool {
+        self.status == "TRADING"
+    }
+}
+
+// TokensRequest requests the listed tokens.
+#[derive(Debug, Clone, Default)]
+pub struct TokensRequest {
+    pub token: Option<String>,
+}
+
+impl RestRequest for TokensRequest {
+    type Response = RestRows<RestToken>;
+    const PATH: &'static str = "/v3/public/token";
+
+    fn query(&self) -> Vec<(&'static str, String)> {
+        self.token.iter().map(|token| ("token", token.clone())).collect()
+    }
+}
+
+// RestToken is a struct representation of a token from the Woo X token endpoint.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestToken {
+    pub token: String,
+    #[serde(default)]
+    pub fullname: Option<String>,
+    #[serde(default)]
+    pub decimals: Option<u32>,
+    #[serde(default)]
+    pub delisted: bool,
+}
+
+// SystemStatusRequest requests the exchange's system status.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemStatusRequest;
+
+impl RestRequest for SystemStatusRequest {
+    type Response = SystemStatus;
+    const PATH: &'static str = "/v3/public/systemInfo";
+
+    fn query(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+}
+
+// SystemStatus is a struct representation of the Woo X system status: 0 while the exchange
+// is running normally and 2 during maintenance.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemStatus {
+    pub status: i32,
+    #[serde(default)]
+    pub msg: String,
+    // estimated_end_time is when maintenance is expected to end, in ms since the epoch.
+    #[serde(default)]
+    pub estimated_end_time: Option<u64>,
+}
+
+impl SystemStatus {
+    pub fn is_maintenance(&self) -> bool {
+        self.status == 2
+    }
+}
+
+// ServerTimeRequest requests the exchange's current time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServerTimeRequest;
+
+impl RestRequest for ServerTimeRequest {
+    type Response = ServerTime;
+    const PATH: &'static str = "/v3/public/systemTime";
+
+    fn query(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+}
+
+// ServerTime is the Woo X server time, in ms since the epoch.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ServerTime {
+    pub timestamp: u64,
+}
+
+// MarketTradesRequest requests the most recent public trades for a symbol.
+#[derive(Debug, Clone)]
+pub struct MarketTradesRequest {
+    pub symbol: String,
+    pub limit: usize,
+}
+
+impl RestRequest for MarketTradesRequest {
+    type Response = RestRows<RestTrade>;
+    const PATH: &'static str = "/v3/public/marketTrades";
+
+    fn query(&self) -> Vec<(&'static str, String)> {
+        vec![("symbol", self.symbol.clone()), ("limit", self.limit.to_string())]
+    }
+}
+
+// KlineHistoryRequest requests the candles of an interval starting in [start_time, end_time).
+#[derive(Debug, Clone)]
+pub struct KlineHistoryRequest {
+    pub symbol: String,
+    // interval is the candle period as the exchange names it, e.g. 1m.
+    pub interval: String,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub limit: u64,
+}
+
+impl RestRequest for KlineHistoryRequest {
+    type Response = RestRows<RestKline>;
+    const PATH: &'static str = "/v3/public/klineHistory";
+
+    fn query(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("symbol", self.symbol.clone()),
+            ("type", self.interval.clone()),
+            ("startTime", self.start_time.to_string()),
+            ("endTime", self.end_time.to_string()),
+            ("limit", self.limit.to_string()),
+        ]
+    }
+}
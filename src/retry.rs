@@ -0,0 +1,121 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::thread;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use tracing::warn;
+
+// RetryPolicy is how a REST request is retried: each attempt is bounded by timeout, and a
+// failed attempt is retried after a backoff that doubles from min_backoff up to max_backoff,
+// with full jitter so clients resyncing at once don't retry in step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // attempts is the total number of attempts, including the first.
+    pub attempts: u32,
+    pub timeout: Duration,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            timeout: Duration::from_secs(10),
+            min_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // once is a policy of a single attempt, for requests that aren't safe to repeat.
+    pub fn once(timeout: Duration) -> Self {
+        Self { attempts: 1, timeout, ..Self::default() }
+    }
+
+    pub fn with_attempts(self, attempts: u32) -> Self {
+        Self { attempts: attempts.max(1), ..self }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    pub fn with_backoff(self, min_backoff: Duration, max_backoff: Duration) -> Self {
+        Self { min_backoff, max_backoff: max_backoff.max(min_backoff), ..self }
+    }
+
+    // backoff returns the delay before the attempt after attempt, counted from 1: a random
+    // duration up to min_backoff doubled attempt - 1 times, capped at max_backoff.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self.min_backoff.saturating_mul(1 << (attempt.max(1) - 1).min(16)).min(self.max_backoff);
+        ceiling.mul_f64(jitter())
+    }
+
+    // run calls request until it succeeds, fails with an error that isn't retryable, or has
+    // been attempted attempts times, in which case the last error is returned as exhausted.
+    // what names the request in logs.
+    pub fn run<T, E, F>(&self, what: &str, mut request: F) -> Result<T, E>
+    where
+        E: Retryable + std::fmt::Display,
+        F: FnMut() -> Result<T, E>,
+    {
+        let attempts = self.attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match request() {
+                Ok(value) => return Ok(value),
+                Err(e) if !e.is_retryable() => return Err(e),
+                Err(e) if attempt >= attempts => {
+                    return Err(match attempts {
+                        1 => e,
+                        _ => E::exhausted(attempts, e),
+                    })
+                }
+                Err(e) => {
+                    let backoff = self.backoff(attempt);
+                    warn!(request = what, attempt, error = %e, ?backoff, "Request failed, retrying");
+                    thread::sleep(backoff);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+// Retryable is an error a RetryPolicy can retry.
+pub trait Retryable: Sized {
+    // is_retryable reports whether the request may succeed if tried again.
+    fn is_retryable(&self) -> bool;
+
+    // exhausted wraps the error of the last of attempts failed attempts.
+    fn exhausted(attempts: u32, last: Self) -> Self;
+}
+
+// is_transient reports whether a failed HTTP request may succeed if tried again: it timed
+// out, couldn't connect or was cut off, or the server was overloaded or failing.
+pub fn is_transient(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        None => e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+    }
+}
+
+// fail_transient turns a response with a status is_transient would retry into an error,
+// leaving other responses to be parsed, since error responses carry the exchange's message.
+pub fn fail_transient(response: reqwest::blocking::Response) -> reqwest::Result<reqwest::blocking::Response> {
+    let status = response.status();
+    match status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        true => response.error_for_status(),
+        false => Ok(response),
+    }
+}
+
+// jitter returns a random factor in [0, 1), from the random keys std seeds its hash maps with.
+fn jitter() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use tracing::error;
+
+use crate::exchange_api_types::Side;
+use crate::order::OrderRequest;
+use crate::units::{Notional, Price, Qty};
+
+// RiskLimits are the pre-trade limits an order has to be within. Limits left None aren't
+// checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RiskLimits {
+    pub max_order_size: Option<Qty>,
+    // max_order_notional is checked at the order's price, or at the reference price for a
+    // market order.
+    pub max_order_notional: Option<Notional>,
+    // max_position is the largest position either way the order could leave, were it and
+    // every open order on its side to fill.
+    pub max_position: Option<Qty>,
+    // price_band_bps is how far from the reference price, the book's mid, a limit order may
+    // be priced.
+    pub price_band_bps: Option<f64>,
+}
+
+// RiskViolation is why an order was rejected before it was placed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskViolation {
+    // KillSwitch means trading has been halted, with the reason it was.
+    KillSwitch(String),
+    OrderSize { quantity: Qty, limit: Qty },
+    OrderNotional { notional: Notional, limit: Notional },
+    Position { position: Qty, limit: Qty },
+    PriceBand { price: Price, reference: Price, deviation_bps: f64, limit_bps: f64 },
+    // NoReferencePrice means a limit needing the book's mid couldn't be checked without one.
+    NoReferencePrice,
+}
+
+impl fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskViolation::KillSwitch(reason) => write!(f, "kill switch tripped: {}", reason),
+            RiskViolation::OrderSize { quantity, limit } => write!(f, "order size {} over limit {}", quantity, limit),
+            RiskViolation::OrderNotional { notional, limit } => write!(f, "order notional {} over limit {}", notional, limit),
+            RiskViolation::Position { position, limit } => write!(f, "position {} would be over limit {}", position, limit),
+            RiskViolation::PriceBand { price, reference, deviation_bps, limit_bps } => {
+                write!(f, "price {} is {:.1} bps from {}, over the {} bps band", price, deviation_bps, reference, limit_bps)
+            }
+            RiskViolation::NoReferencePrice => write!(f, "no reference price to check the order against"),
+        }
+    }
+}
+
+impl std::error::Error for RiskViolation {}
+
+// KillSwitch halts trading when tripped: every RiskCheck sharing it rejects every order until
+// it is reset. Clones share the same switch, so one switch can halt every strategy and
+// symbol in the process.
+#[derive(Debug, Clone, Default)]
+pub struct KillSwitch {
+    reason: Arc<Mutex<Option<String>>>,
+}
+
+impl KillSwitch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // trip halts trading for reason. Tripping a tripped switch keeps the first reason.
+    pub fn trip(&self, reason: &str) {
+        let mut current = self.reason.lock().unwrap();
+        if current.is_none() {
+            error!(reason, "Kill switch tripped");
+            *current = Some(reason.to_string());
+        }
+    }
+
+    pub fn reset(&self) {
+        *self.reason.lock().unwrap() = None;
+    }
+
+    // tripped returns why the switch was tripped, or None if it hasn't been.
+    pub fn tripped(&self) -> Option<String> {
+        self.reason.lock().unwrap().clone()
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.reason.lock().unwrap().is_some()
+    }
+}
+
+// RiskCheck checks orders against RiskLimits and a KillSwitch before they are placed.
+#[derive(Debug, Clone, Default)]
+pub struct RiskCheck {
+    limits: RiskLimits,
+    kill_switch: KillSwitch,
+}
+
+impl RiskCheck {
+    pub fn new(limits: RiskLimits, kill_switch: KillSwitch) -> Self {
+        Self { limits, kill_switch }
+    }
+
+    pub fn limits(&self) -> &RiskLimits {
+        &self.limits
+    }
+
+    pub fn kill_switch(&self) -> &KillSwitch {
+        &self.kill_switch
+    }
+
+    // check returns the first limit request breaks. exposure is the signed position request
+    // would add to were it to fill: the current position plus the open orders on its side.
+    // reference is the book's mid, if there is one.
+    pub fn check(&self, request: &OrderRequest, exposure: Qty, reference: Option<Price>) -> Result<(), RiskViolation> {
+        if let Some(reason) = self.kill_switch.tripped() {
+            return Err(RiskViolation::KillSwitch(reason));
+        }
+        let limits = &self.limits;
+
+        if let Some(limit) = limits.max_order_size.filter(|&limit| request.quantity > limit) {
+            return Err(RiskViolation::OrderSize { quantity: request.quantity, limit });
+        }
+
+        if let Some(limit) = limits.max_order_notional {
+            let price = request.price().or(reference).ok_or(RiskViolation::NoReferencePrice)?;
+            let notional = price * request.quantity;
+            if notional > limit {
+                return Err(RiskViolation::OrderNotional { notional, limit });
+            }
+        }
+
+        if let Some(limit) = limits.max_position {
+            let position = match request.side {
+                Side::Buy => exposure + request.quantity,
+                Side::Sell => exposure - request.quantity,
+            };
+            if position.value().abs() > limit.value() {
+                return Err(RiskViolation::Position { position, limit });
+            }
+        }
+
+        if let (Some(limit_bps), Some(price)) = (limits.price_band_bps, request.price()) {
+            let reference = reference.ok_or(RiskViolation::NoReferencePrice)?;
+            let deviation_bps = (price - reference).bps_of(reference).abs();
+            if deviation_bps > limit_bps {
+                return Err(RiskViolation::PriceBand { price, reference, deviation_bps, limit_bps });
+            }
+        }
+        Ok(())
+    }
+}
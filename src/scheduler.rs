@@ -0,0 +1,152 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::feed::{self, FeedConfig, MarketEvent};
+
+// DEFAULT_WEIGHT is the weight of symbols that haven't been given one.
+pub const DEFAULT_WEIGHT: u32 = 1;
+
+struct Queue<T> {
+    weight: u32,
+    // last_finish is the virtual finish time of the newest item in the queue.
+    last_finish: f64,
+    items: VecDeque<(f64, T)>,
+}
+
+struct State<T> {
+    queues: HashMap<String, Queue<T>>,
+    // virtual_time is the finish time of the last item popped.
+    virtual_time: f64,
+    closed: bool,
+}
+
+// SymbolScheduler is a multi-producer queue of per-symbol items, popped in weighted fair
+// order. When items for several symbols are waiting, a symbol with weight 4 is served four
+// times as often as one with weight 1, so high priority symbols stay fresh under load while
+// low priority ones are never starved.
+pub struct SymbolScheduler<T> {
+    inner: Arc<(Mutex<State<T>>, Condvar)>,
+}
+
+impl<T> Clone for SymbolScheduler<T> {
+    fn clone(&self) -> Self {
+        Self { inner: Arc::clone(&self.inner) }
+    }
+}
+
+impl<T> Default for SymbolScheduler<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SymbolScheduler<T> {
+    pub fn new() -> Self {
+        let state = State {
+            queues: HashMap::new(),
+            virtual_time: 0.0,
+            closed: false,
+        };
+        Self { inner: Arc::new((Mutex::new(state), Condvar::new())) }
+    }
+
+    // set_weight sets the scheduling weight of symbol. Weights below 1 are treated as 1.
+    pub fn set_weight(&self, symbol: &str, weight: u32) {
+        let mut state = self.inner.0.lock().unwrap();
+        state.queue(symbol).weight = weight.max(1);
+    }
+
+    // push queues item for symbol and wakes a waiting consumer.
+    pub fn push(&self, symbol: &str, item: T) {
+        let (lock, ready) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        let virtual_time = state.virtual_time;
+        let queue = state.queue(symbol);
+        let finish = queue.last_finish.max(virtual_time) + 1.0 / queue.weight as f64;
+        queue.last_finish = finish;
+        queue.items.push_back((finish, item));
+        ready.notify_one();
+    }
+
+    // pop blocks until an item is available and returns the item with the earliest virtual
+    // finish time along with its symbol. It returns None once the scheduler is closed and empty.
+    pub fn pop(&self) -> Option<(String, T)> {
+        let (lock, ready) = &*self.inner;
+        let mut state = lock.lock().unwrap();
+        loop {
+            if let Some(next) = state.pop_next() {
+                return Some(next);
+            }
+            if state.closed {
+                return None;
+            }
+            state = ready.wait(state).unwrap();
+        }
+    }
+
+    // try_pop returns the next item without blocking.
+    pub fn try_pop(&self) -> Option<(String, T)> {
+        self.inner.0.lock().unwrap().pop_next()
+    }
+
+    // len returns the number of queued items for symbol.
+    pub fn len(&self, symbol: &str) -> usize {
+        let state = self.inner.0.lock().unwrap();
+        state.queues.get(symbol).map_or(0, |queue| queue.items.len())
+    }
+
+    // is_empty returns true if no items are queued for any symbol.
+    pub fn is_empty(&self) -> bool {
+        let state = self.inner.0.lock().unwrap();
+        state.queues.values().all(|queue| queue.items.is_empty())
+    }
+
+    // close wakes every consumer. Queued items can still be popped, after which pop returns None.
+    pub fn close(&self) {
+        let (lock, ready) = &*self.inner;
+        lock.lock().unwrap().closed = true;
+        ready.notify_all();
+    }
+}
+
+impl<T> State<T> {
+    fn queue(&mut self, symbol: &str) -> &mut Queue<T> {
+        self.queues.entry(symbol.to_string()).or_insert_with(|| Queue {
+            weight: DEFAULT_WEIGHT,
+            last_finish: 0.0,
+            items: VecDeque::new(),
+        })
+    }
+
+    fn pop_next(&mut self) -> Option<(String, T)> {
+        let symbol = self
+            .queues
+            .iter()
+            .filter_map(|(symbol, queue)| queue.items.front().map(|(finish, _)| (symbol, *finish)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(symbol, _)| symbol.clone())?;
+
+        let (finish, item) = self.queues.get_mut(&symbol)?.items.pop_front()?;
+        self.virtual_time = finish;
+        Some((symbol, item))
+    }
+}
+
+// connect_scheduled connects a book stream for every (symbol, weight) pair and returns a
+// scheduler the market events of all of them are pushed into.
+pub fn connect_scheduled(config: &FeedConfig, symbols: &[(String, u32)], max_level: usize) -> SymbolScheduler<MarketEvent> {
+    let scheduler = SymbolScheduler::new();
+    for (symbol, weight) in symbols {
+        scheduler.set_weight(symbol, *weight);
+
+        let events = feed::connect_stream(config, symbol, max_level);
+        let scheduler = scheduler.clone();
+        let symbol = symbol.clone();
+        std::thread::spawn(move || {
+            for event in events {
+                scheduler.push(&symbol, event);
+            }
+        });
+    }
+    scheduler
+}
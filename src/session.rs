@@ -0,0 +1,246 @@
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::analytics::{self, LiquidityStats, LiquiditySummary};
+use crate::exchange_api_types::WsTrade;
+use crate::feed::MarketEvent;
+use crate::metrics::FeedMetrics;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+use crate::units::Price;
+
+// Stats are the running totals of a session.
+struct Stats {
+    started: Instant,
+    updates: u64,
+    trades: u64,
+    resyncs: u64,
+    min_spread: Option<Price>,
+    max_spread: Option<Price>,
+    // synced is whether the book is in sync with the stream, since since.
+    synced: bool,
+    since: Instant,
+    in_sync: Duration,
+    desynced: Duration,
+    liquidity: LiquidityStats,
+}
+
+impl Stats {
+    // transition moves the book into the synced state at at, crediting the time since the
+    // last transition to the state it was in.
+    fn transition(&mut self, synced: bool, at: Instant) {
+        let elapsed = at.saturating_duration_since(self.since);
+        if self.synced {
+            self.in_sync += elapsed;
+        } else {
+            self.desynced += elapsed;
+        }
+        self.synced = synced;
+        self.since = at;
+    }
+}
+
+// SessionStats tracks what happened to a symbol's book over a run: the updates applied,
+// trades seen, spread range, resyncs and reconnects, and how long the book was in sync
+// with the stream. It is a handle, cloned into the sinks that record it.
+#[derive(Clone)]
+pub struct SessionStats {
+    symbol: String,
+    stats: Arc<Mutex<Stats>>,
+    feed: Option<Arc<FeedMetrics>>,
+}
+
+impl SessionStats {
+    pub fn new(symbol: &str) -> Self {
+        let now = Instant::now();
+        let stats = Stats {
+            started: now,
+            updates: 0,
+            trades: 0,
+            resyncs: 0,
+            min_spread: None,
+            max_spread: None,
+            synced: false,
+            since: now,
+            in_sync: Duration::ZERO,
+            desynced: Duration::ZERO,
+            liquidity: LiquidityStats::new(analytics::DEFAULT_LIQUIDITY_DEPTH, &analytics::DEFAULT_SPREAD_THRESHOLDS_BPS, None),
+        };
+        Self { symbol: symbol.to_string(), stats: Arc::new(Mutex::new(stats)), feed: None }
+    }
+
+    // with_feed reports the reconnects counted by feed.
+    pub fn with_feed(mut self, feed: Arc<FeedMetrics>) -> Self {
+        self.feed = Some(feed);
+        self
+    }
+
+    // sink returns a sink recording the books and trades written to it, logging a summary
+    // every report_interval if given.
+    pub fn sink(&self, report_interval: Option<Duration>) -> SessionSink {
+        SessionSink { stats: self.clone(), report_interval, last_report: Instant::now() }
+    }
+
+    // record_snapshot records the book being synced from a snapshot. The book was out of
+    // sync since the last delta applied, if it had been in sync.
+    pub fn record_snapshot(&self) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.resyncs += 1;
+        if stats.synced {
+            let since = stats.since;
+            stats.transition(false, since);
+        }
+    }
+
+    // record_delta records book after a delta was applied to it.
+    pub fn record_delta(&self, book: &LocalOrderBook) {
+        let mut stats = self.stats.lock().unwrap();
+        let now = Instant::now();
+        if stats.synced {
+            // The time in sync is credited up to each delta as it's applied, so a desync is
+            // counted from the last delta applied.
+            let in_sync = now.saturating_duration_since(stats.since);
+            stats.in_sync += in_sync;
+            stats.since = now;
+        } else {
+            stats.transition(true, now);
+        }
+        stats.updates += 1;
+        stats.liquidity.on_book(book);
+        if let (Some((bid, _)), Some((ask, _))) = (book.best_bid(), book.best_ask()) {
+            if bid < ask {
+                let spread = ask - bid;
+                stats.min_spread = Some(stats.min_spread.map_or(spread, |min| min.min(spread)));
+                stats.max_spread = Some(stats.max_spread.map_or(spread, |max| max.max(spread)));
+            }
+        }
+    }
+
+    pub fn record_trade(&self) {
+        self.stats.lock().unwrap().trades += 1;
+    }
+
+    // summary returns the stats so far.
+    pub fn summary(&self) -> SessionSummary {
+        let stats = self.stats.lock().unwrap();
+        let current = stats.since.elapsed();
+        let (in_sync, desynced) = match stats.synced {
+            true => (stats.in_sync + current, stats.desynced),
+            false => (stats.in_sync, stats.desynced + current),
+        };
+        SessionSummary {
+            symbol: self.symbol.clone(),
+            duration_secs: stats.started.elapsed().as_secs_f64(),
+            updates: stats.updates,
+            trades: stats.trades,
+            resyncs: stats.resyncs,
+            reconnects: self.feed.as_ref().map_or(0, |feed| feed.reconnects.load(Ordering::Relaxed)),
+            min_spread: stats.min_spread.map(Price::value),
+            max_spread: stats.max_spread.map(Price::value),
+            in_sync_secs: in_sync.as_secs_f64(),
+            desynced_secs: desynced.as_secs_f64(),
+            liquidity: stats.liquidity.current(),
+        }
+    }
+}
+
+// SessionSummary is a point in time summary of SessionStats, serializable for writing out
+// at the end of a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub symbol: String,
+    pub duration_secs: f64,
+    pub updates: u64,
+    pub trades: u64,
+    pub resyncs: u64,
+    pub reconnects: u64,
+    // min_spread and max_spread are over the uncrossed books, in price units.
+    pub min_spread: Option<f64>,
+    pub max_spread: Option<f64>,
+    pub in_sync_secs: f64,
+    pub desynced_secs: f64,
+    // liquidity is the time weighted spread and top of book depth over the session.
+    pub liquidity: Option<LiquiditySummary>,
+}
+
+impl SessionSummary {
+    // in_sync_ratio returns the fraction of the session the book was in sync.
+    pub fn in_sync_ratio(&self) -> f64 {
+        let total = self.in_sync_secs + self.desynced_secs;
+        if total > 0.0 { self.in_sync_secs / total } else { 0.0 }
+    }
+}
+
+impl fmt::Display for SessionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let or_dash = |value: Option<f64>| value.map_or_else(|| "-".to_string(), |value| value.to_string());
+        write!(
+            f,
+            "Session {} over {:.0}s: {} updates | {} trades | spread min {} max {}{} | in sync {:.1}% ({:.0}s, desynced {:.0}s) | {} resyncs | {} reconnects",
+            self.symbol,
+            self.duration_secs,
+            self.updates,
+            self.trades,
+            or_dash(self.min_spread),
+            or_dash(self.max_spread),
+            self.liquidity.as_ref().map_or_else(String::new, |liquidity| {
+                format!(
+                    " avg {:.2} bps | top depth bid {:.4} ask {:.4}",
+                    liquidity.avg_spread_bps,
+                    liquidity.avg_bid_depth.value(),
+                    liquidity.avg_ask_depth.value()
+                )
+            }),
+            self.in_sync_ratio() * 100.0,
+            self.in_sync_secs,
+            self.desynced_secs,
+            self.resyncs,
+            self.reconnects,
+        )
+    }
+}
+
+// SessionSink records the snapshots, deltas and trades written to it into SessionStats.
+pub struct SessionSink {
+    stats: SessionStats,
+    report_interval: Option<Duration>,
+    last_report: Instant,
+}
+
+impl SessionSink {
+    fn maybe_report(&mut self) {
+        let Some(interval) = self.report_interval else { return };
+        if self.last_report.elapsed() >= interval {
+            self.last_report = Instant::now();
+            info!("{}", self.stats.summary());
+        }
+    }
+}
+
+impl Sink for SessionSink {
+    fn name(&self) -> &str {
+        "session"
+    }
+
+    fn record_snapshot(&mut self, _symbol: &str, _ts: u64, _book: &LocalOrderBook) -> SinkResult {
+        self.stats.record_snapshot();
+        Ok(())
+    }
+
+    fn record_delta(&mut self, _symbol: &str, _event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        self.stats.record_delta(book);
+        self.maybe_report();
+        Ok(())
+    }
+
+    fn record_trade(&mut self, _trade: &WsTrade) -> SinkResult {
+        self.stats.record_trade();
+        self.maybe_report();
+        Ok(())
+    }
+}
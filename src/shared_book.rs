@@ -0,0 +1,261 @@
+use std::hint;
+use std::sync::atomic::{fence, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::orderbook::OrderBook;
+use crate::units::{Price, Qty};
+
+// SharedBook shares the top levels of a book between the thread applying its deltas and
+// any number of reader threads without a lock, through a seqlock: the writer bumps the
+// sequence to odd, overwrites the levels and bumps it back to even, and a reader retries
+// if the sequence was odd or moved while it read. The writer never waits on readers, and
+// readers only ever wait out a write in progress. Every field is an atomic, so a torn read
+// is retried rather than undefined.
+#[derive(Debug)]
+struct SharedBook {
+    seq: AtomicU64,
+    ts: AtomicU64,
+    bids: SharedSide,
+    asks: SharedSide,
+}
+
+#[derive(Debug)]
+struct SharedSide {
+    len: AtomicUsize,
+    // levels holds the price and quantity bits of each level, best first.
+    levels: Box<[(AtomicU64, AtomicU64)]>,
+}
+
+impl SharedSide {
+    fn new(depth: usize) -> Self {
+        Self { len: AtomicUsize::new(0), levels: (0..depth).map(|_| (AtomicU64::new(0), AtomicU64::new(0))).collect() }
+    }
+
+    fn store(&self, levels: impl Iterator<Item = (Price, Qty)>) {
+        let mut len = 0;
+        for ((price, quantity), slot) in levels.zip(self.levels.iter()) {
+            slot.0.store(price.value().to_bits(), Ordering::Relaxed);
+            slot.1.store(quantity.value().to_bits(), Ordering::Relaxed);
+            len += 1;
+        }
+        self.len.store(len, Ordering::Relaxed);
+    }
+
+    fn level(&self, index: usize) -> (Price, Qty) {
+        let (price, quantity) = &self.levels[index];
+        (
+            Price::new(f64::from_bits(price.load(Ordering::Relaxed))),
+            Qty::new(f64::from_bits(quantity.load(Ordering::Relaxed))),
+        )
+    }
+
+    fn load_into(&self, n: usize, out: &mut Vec<(Price, Qty)>) {
+        out.clear();
+        let len = self.len.load(Ordering::Relaxed).min(self.levels.len()).min(n);
+        out.extend((0..len).map(|index| self.level(index)));
+    }
+
+    fn best(&self) -> Option<(Price, Qty)> {
+        (self.len.load(Ordering::Relaxed) > 0).then(|| self.level(0))
+    }
+}
+
+// shared_book returns the writer and a reader of a book sharing depth levels a side.
+pub fn shared_book(depth: usize) -> (SharedBookWriter, SharedBookReader) {
+    let book = Arc::new(SharedBook {
+        seq: AtomicU64::new(0),
+        ts: AtomicU64::new(0),
+        bids: SharedSide::new(depth),
+        asks: SharedSide::new(depth),
+    });
+    (SharedBookWriter { book: Arc::clone(&book) }, SharedBookReader { book })
+}
+
+// SharedBookWriter publishes a book to its readers. There is only the one writer, so it
+// isn't Clone.
+#[derive(Debug)]
+pub struct SharedBookWriter {
+    book: Arc<SharedBook>,
+}
+
+impl SharedBookWriter {
+    // publish shares the top levels of book, last updated at ts, with the readers.
+    pub fn publish(&self, ts: u64, book: &impl OrderBook) {
+        let shared = &self.book;
+        let seq = shared.seq.load(Ordering::Relaxed);
+        shared.seq.store(seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        shared.ts.store(ts, Ordering::Relaxed);
+        shared.bids.store(book.bids());
+        shared.asks.store(book.asks());
+        shared.seq.store(seq + 2, Ordering::Release);
+    }
+
+    pub fn reader(&self) -> SharedBookReader {
+        SharedBookReader { book: Arc::clone(&self.book) }
+    }
+}
+
+// BookTop is a consistent copy of the top levels of a shared book.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookTop {
+    // ts is the exchange timestamp the book was last updated at, 0 before it is published.
+    pub ts: u64,
+    pub bids: Vec<(Price, Qty)>,
+    pub asks: Vec<(Price, Qty)>,
+}
+
+// SharedBookReader reads the book its writer publishes, from any thread.
+#[derive(Debug, Clone)]
+pub struct SharedBookReader {
+    book: Arc<SharedBook>,
+}
+
+impl SharedBookReader {
+    // depth returns the most levels a side shared.
+    pub fn depth(&self) -> usize {
+        self.book.bids.levels.len()
+    }
+
+    // ts returns the exchange timestamp the book was last updated at.
+    pub fn ts(&self) -> u64 {
+        self.read(|book| book.ts.load(Ordering::Relaxed))
+    }
+
+    pub fn best_bid(&self) -> Option<(Price, Qty)> {
+        self.read(|book| book.bids.best())
+    }
+
+    pub fn best_ask(&self) -> Option<(Price, Qty)> {
+        self.read(|book| book.asks.best())
+    }
+
+    // mid_price returns the midpoint of the best bid and ask, read together.
+    pub fn mid_price(&self) -> Option<Price> {
+        match self.read(|book| (book.bids.best(), book.asks.best())) {
+            (Some((bid, _)), Some((ask, _))) => Some(bid.midpoint(ask)),
+            _ => None,
+        }
+    }
+
+    // top returns the top n levels a side.
+    pub fn top(&self, n: usize) -> BookTop {
+        let mut top = BookTop::default();
+        self.top_into(n, &mut top);
+        top
+    }
+
+    // top_into is top, reusing the vecs of top rather than allocating.
+    pub fn top_into(&self, n: usize, top: &mut BookTop) {
+        top.ts = self.read(|book| {
+            book.bids.load_into(n, &mut top.bids);
+            book.asks.load_into(n, &mut top.asks);
+            book.ts.load(Ordering::Relaxed)
+        });
+    }
+
+    // read runs f until it has read the book without a write overlapping it.
+    fn read<R>(&self, mut f: impl FnMut(&SharedBook) -> R) -> R {
+        let book = &self.book;
+        loop {
+            let seq = book.seq.load(Ordering::Acquire);
+            if seq % 2 == 1 {
+                hint::spin_loop();
+                continue;
+            }
+            let value = f(book);
+            fence(Ordering::Acquire);
+            if book.seq.load(Ordering::Relaxed) == seq {
+                return value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    use super::*;
+    use crate::exchange_api_types::{RestQuote, SnapshotData};
+    use crate::orderbook::LocalOrderBook;
+
+    // book returns a book with levels levels a side around 100, every level quantity.
+    fn book(levels: usize, quantity: f64) -> LocalOrderBook {
+        let side = |sign: f64| (1..=levels).map(|level| RestQuote { price: 100.0 + sign * level as f64, quantity }).collect();
+        let mut book = LocalOrderBook::new();
+        OrderBook::apply_snapshot(&mut book, SnapshotData { bids: side(-1.0), asks: side(1.0) });
+        book
+    }
+
+    fn level(price: f64, quantity: f64) -> (Price, Qty) {
+        (Price::new(price), Qty::new(quantity))
+    }
+
+    #[test]
+    fn reads_back_the_top_levels_published() {
+        let (writer, reader) = shared_book(3);
+        assert_eq!(reader.top(3), BookTop::default());
+        assert_eq!((reader.best_bid(), reader.mid_price()), (None, None));
+
+        writer.publish(1000, &book(5, 2.0));
+        assert_eq!(reader.depth(), 3);
+        assert_eq!(reader.ts(), 1000);
+        assert_eq!(reader.best_bid(), Some(level(99.0, 2.0)));
+        assert_eq!(reader.best_ask(), Some(level(101.0, 2.0)));
+        assert_eq!(reader.mid_price(), Some(Price::new(100.0)));
+        // Only depth levels are shared, however many are asked for.
+        let top = reader.top(10);
+        assert_eq!(top.bids, vec![level(99.0, 2.0), level(98.0, 2.0), level(97.0, 2.0)]);
+        assert_eq!(top.asks, vec![level(101.0, 2.0), level(102.0, 2.0), level(103.0, 2.0)]);
+        assert_eq!(reader.top(1).bids, vec![level(99.0, 2.0)]);
+
+        // A book with fewer levels than before shares only those.
+        let mut top = top;
+        writer.publish(1100, &book(1, 3.0));
+        writer.reader().top_into(3, &mut top);
+        assert_eq!(top, BookTop { ts: 1100, bids: vec![level(99.0, 3.0)], asks: vec![level(101.0, 3.0)] });
+    }
+
+    #[test]
+    fn never_reads_a_torn_top_while_the_writer_publishes() {
+        const DEPTH: usize = 8;
+        const PUBLISHES: u64 = 20_000;
+        // Every book is published with its number as the ts and as the quantity of every
+        // level, and with that number of levels a side, so a read mixing two is seen.
+        let (writer, reader) = shared_book(DEPTH);
+        let done = Arc::new(AtomicBool::new(false));
+
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let (reader, done) = (reader.clone(), Arc::clone(&done));
+                thread::spawn(move || {
+                    let (mut top, mut last_ts, mut reads) = (BookTop::default(), 0, 0u64);
+                    while !done.load(Ordering::Relaxed) {
+                        reader.top_into(DEPTH, &mut top);
+                        if top.ts == 0 {
+                            continue;
+                        }
+                        let levels = (top.ts - 1) as usize % DEPTH + 1;
+                        assert_eq!((top.bids.len(), top.asks.len()), (levels, levels), "torn at {}", top.ts);
+                        assert!(top.bids.iter().chain(&top.asks).all(|&(_, quantity)| quantity == Qty::new(top.ts as f64)), "torn at {}: {:?}", top.ts, top);
+                        assert!(top.ts >= last_ts, "read {} after {}", top.ts, last_ts);
+                        last_ts = top.ts;
+                        reads += 1;
+                    }
+                    reads
+                })
+            })
+            .collect();
+
+        for n in 1..=PUBLISHES {
+            writer.publish(n, &book((n - 1) as usize % DEPTH + 1, n as f64));
+        }
+        done.store(true, Ordering::Relaxed);
+        for reader in readers {
+            assert!(reader.join().unwrap() > 0);
+        }
+        assert_eq!(reader.ts(), PUBLISHES);
+    }
+}
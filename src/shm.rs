@@ -0,0 +1,405 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+use memmap2::{MmapOptions, MmapRaw};
+
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+
+// The ring is a file of 64 bit words: a header of HEADER_WORDS words followed by capacity
+// slots of SLOT_WORDS words, so every field can be accessed atomically from any process
+// mapping it.
+const MAGIC: u64 = 0x574f_4f58_5348_4d32; // "WOOXSHM2"
+const HEADER_WORDS: usize = 16;
+const SLOT_WORDS: usize = 8;
+const MAGIC_WORD: usize = 0;
+const CAPACITY_WORD: usize = 1;
+const WRITE_SEQ_WORD: usize = 2;
+// GENERATION_WORD counts the rings created at the ring's path, from 1 for the first.
+const GENERATION_WORD: usize = 3;
+// REPLACED_WORD is set once a newer ring has replaced the ring at its path, so readers
+// still mapping it move on to the new one.
+const REPLACED_WORD: usize = 4;
+// SYMBOL_WORD is where the symbol starts, as up to MAX_SYMBOL_LEN NUL padded bytes.
+const SYMBOL_WORD: usize = 8;
+const MAX_SYMBOL_LEN: usize = 64;
+
+const HAS_BID: u64 = 1;
+const HAS_ASK: u64 = 2;
+
+// Level is a (price, quantity) pair.
+pub type Level = (f64, f64);
+
+// TopOfBook is a top of book update written to the ring.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TopOfBook {
+    // seq numbers the updates written to the ring from 1.
+    pub seq: u64,
+    pub ts: u64,
+    pub bid: Option<Level>,
+    pub ask: Option<Level>,
+}
+
+// Ring is a mapped ring file viewed as words.
+struct Ring {
+    map: MmapRaw,
+    capacity: u64,
+}
+
+impl Ring {
+    fn words(&self) -> &[AtomicU64] {
+        let len = self.map.len() / 8;
+        // SAFETY: the map is page aligned and lives as long as the returned slice, AtomicU64
+        // has the layout of a u64, and every process only accesses the ring through atomics.
+        // Readers map the file read only and never store to it.
+        unsafe { std::slice::from_raw_parts(self.map.as_ptr() as *const AtomicU64, len) }
+    }
+
+    fn word(&self, index: usize) -> &AtomicU64 {
+        &self.words()[index]
+    }
+
+    fn slot(&self, seq: u64) -> &[AtomicU64] {
+        let start = HEADER_WORDS + (seq % self.capacity) as usize * SLOT_WORDS;
+        &self.words()[start..start + SLOT_WORDS]
+    }
+
+    fn symbol(&self) -> String {
+        let bytes: Vec<u8> = self.words()[SYMBOL_WORD..SYMBOL_WORD + MAX_SYMBOL_LEN / 8]
+            .iter()
+            .flat_map(|word| word.load(Ordering::Relaxed).to_le_bytes())
+            .take_while(|&byte| byte != 0)
+            .collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+// map_ring maps the ring file at path, read only unless writable, checking it is a whole
+// ring.
+fn map_ring(path: &Path, writable: bool) -> io::Result<Ring> {
+    let map = match writable {
+        true => MmapRaw::map_raw(&OpenOptions::new().read(true).write(true).open(path)?)?,
+        false => MmapOptions::new().map_raw_read_only(&File::open(path)?)?,
+    };
+    if map.len() < HEADER_WORDS * 8 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a woox shared memory ring"));
+    }
+    let mut ring = Ring { map, capacity: 1 };
+    if ring.word(MAGIC_WORD).load(Ordering::Acquire) != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a woox shared memory ring"));
+    }
+    ring.capacity = ring.word(CAPACITY_WORD).load(Ordering::Relaxed);
+    if ring.capacity == 0 || ring.map.len() < (HEADER_WORDS + ring.capacity as usize * SLOT_WORDS) * 8 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated woox shared memory ring"));
+    }
+    Ok(ring)
+}
+
+fn level_bits(level: Option<Level>) -> (u64, u64) {
+    level.map_or((0, 0), |(price, quantity)| (price.to_bits(), quantity.to_bits()))
+}
+
+// ShmPublisher is a sink that writes the top of book of a symbol into a shared memory ring
+// each time it changes, for latency sensitive consumers on the same host to read with a
+// ShmReader. There is a single writer and any number of readers, which never block it: a
+// reader that falls a whole ring behind skips ahead, counting the updates it missed.
+pub struct ShmPublisher {
+    ring: Ring,
+    seq: u64,
+    last: Option<(Option<Level>, Option<Level>)>,
+}
+
+impl ShmPublisher {
+    // create creates the ring file at path, typically under /dev/shm, with room for
+    // capacity updates, replacing any existing one. The ring is built under a temporary name
+    // and renamed over the old one, which readers may still have mapped: truncating it in
+    // place would restart its seq under them, or fault them reading past its end. The old
+    // ring is marked replaced instead, for them to move on to the new one.
+    pub fn create(path: &Path, symbol: &str, capacity: usize) -> io::Result<Self> {
+        if symbol.len() > MAX_SYMBOL_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "symbol too long for the ring header"));
+        }
+        let capacity = capacity.max(1);
+        let previous = map_ring(path, true).ok();
+        let generation = previous.as_ref().map_or(0, |ring| ring.word(GENERATION_WORD).load(Ordering::Relaxed)) + 1;
+
+        let name = path.file_name().map_or_else(|| "ring".into(), |name| name.to_string_lossy());
+        let temp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&temp)?;
+        file.set_len(((HEADER_WORDS + capacity * SLOT_WORDS) * 8) as u64)?;
+        let ring = Ring { map: MmapRaw::map_raw(&file)?, capacity: capacity as u64 };
+
+        let mut symbol_bytes = [0u8; MAX_SYMBOL_LEN];
+        symbol_bytes[..symbol.len()].copy_from_slice(symbol.as_bytes());
+        for (i, chunk) in symbol_bytes.chunks(8).enumerate() {
+            ring.word(SYMBOL_WORD + i).store(u64::from_le_bytes(chunk.try_into().unwrap()), Ordering::Relaxed);
+        }
+        ring.word(CAPACITY_WORD).store(capacity as u64, Ordering::Relaxed);
+        ring.word(GENERATION_WORD).store(generation, Ordering::Relaxed);
+        // Readers check the magic last, so they never see a half initialized header.
+        ring.word(MAGIC_WORD).store(MAGIC, Ordering::Release);
+        if let Err(e) = fs::rename(&temp, path) {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+        // Only once the new ring is at path, so a reader moving on finds it there.
+        if let Some(previous) = previous {
+            previous.word(REPLACED_WORD).store(1, Ordering::Release);
+        }
+        Ok(Self { ring, seq: 0, last: None })
+    }
+
+    // publish writes the top of book of book at ts if it changed since the last write.
+    pub fn publish(&mut self, ts: u64, book: &LocalOrderBook) {
+        let bid = book.best_bid().map(|(price, quantity)| (price.value(), quantity.value()));
+        let ask = book.best_ask().map(|(price, quantity)| (price.value(), quantity.value()));
+        if self.last == Some((bid, ask)) {
+            return;
+        }
+        self.last = Some((bid, ask));
+        self.seq += 1;
+
+        // Each slot is a seqlock: its sequence word is odd while the slot is written and
+        // 2 * seq once the update numbered seq is complete.
+        let slot = self.ring.slot(self.seq);
+        slot[0].store(2 * self.seq - 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        let (bid_price, bid_quantity) = level_bits(bid);
+        let (ask_price, ask_quantity) = level_bits(ask);
+        let flags = if bid.is_some() { HAS_BID } else { 0 } | if ask.is_some() { HAS_ASK } else { 0 };
+        for (word, value) in slot[1..7].iter().zip([ts, bid_price, bid_quantity, ask_price, ask_quantity, flags]) {
+            word.store(value, Ordering::Relaxed);
+        }
+        slot[0].store(2 * self.seq, Ordering::Release);
+        self.ring.word(WRITE_SEQ_WORD).store(self.seq, Ordering::Release);
+    }
+}
+
+impl Sink for ShmPublisher {
+    fn name(&self) -> &str {
+        "shm"
+    }
+
+    fn record_snapshot(&mut self, _symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        self.publish(ts, book);
+        Ok(())
+    }
+
+    fn record_delta(&mut self, _symbol: &str, event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        self.publish(event.ts, book);
+        Ok(())
+    }
+}
+
+// ShmReader reads the top of book updates a ShmPublisher writes to a ring. Once the ring
+// is replaced by a publisher creating a new one at its path, the reader moves on to the new
+// ring after the last update of the old, reading it from its first.
+pub struct ShmReader {
+    path: PathBuf,
+    ring: Ring,
+    next: u64,
+    missed: u64,
+}
+
+impl ShmReader {
+    // open maps the ring file at path, starting from the next update written.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let ring = map_ring(path, false)?;
+        let next = ring.word(WRITE_SEQ_WORD).load(Ordering::Acquire) + 1;
+        Ok(Self { path: path.to_path_buf(), ring, next, missed: 0 })
+    }
+
+    pub fn symbol(&self) -> String {
+        self.ring.symbol()
+    }
+
+    // generation returns which of the rings created at the path the reader is reading, from
+    // 1 for the first.
+    pub fn generation(&self) -> u64 {
+        self.ring.word(GENERATION_WORD).load(Ordering::Relaxed)
+    }
+
+    // missed returns the number of updates skipped because the reader fell a ring behind.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    // latest returns the most recent update, if any has been written, without moving the
+    // reader through the ring. A replaced ring is moved on from first.
+    pub fn latest(&mut self) -> Option<TopOfBook> {
+        while self.follow_replacement() {}
+        loop {
+            let written = self.ring.word(WRITE_SEQ_WORD).load(Ordering::Acquire);
+            if written == 0 {
+                return None;
+            }
+            if let Some(top) = self.read(written) {
+                return Some(top);
+            }
+        }
+    }
+
+    // try_next returns the next update, or None if the reader is caught up. It never blocks.
+    pub fn try_next(&mut self) -> Option<TopOfBook> {
+        loop {
+            let written = self.ring.word(WRITE_SEQ_WORD).load(Ordering::Acquire);
+            if self.next > written {
+                // Caught up on a replaced ring, whose updates its replacement follows.
+                if self.follow_replacement() {
+                    continue;
+                }
+                return None;
+            }
+            // Updates older than a ring have been overwritten.
+            let oldest = written.saturating_sub(self.ring.capacity - 1).max(1);
+            if self.next < oldest {
+                self.missed += oldest - self.next;
+                self.next = oldest;
+            }
+            match self.read(self.next) {
+                Some(top) => {
+                    self.next += 1;
+                    return Some(top);
+                }
+                // The slot was overwritten while it was read, so the reader is a ring behind
+                // and skips ahead on the next attempt.
+                None => continue,
+            }
+        }
+    }
+
+    // follow_replacement maps the ring that replaced the one read, if it has been, to be read
+    // from its first update, returning whether it did.
+    fn follow_replacement(&mut self) -> bool {
+        if self.ring.word(REPLACED_WORD).load(Ordering::Acquire) == 0 {
+            return false;
+        }
+        match map_ring(&self.path, false) {
+            Ok(ring) => {
+                self.ring = ring;
+                self.next = 1;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    // read reads the update numbered seq, or returns None if its slot no longer holds it.
+    fn read(&self, seq: u64) -> Option<TopOfBook> {
+        let slot = self.ring.slot(seq);
+        if slot[0].load(Ordering::Acquire) != 2 * seq {
+            return None;
+        }
+        let mut values = [0u64; 6];
+        for (value, word) in values.iter_mut().zip(&slot[1..7]) {
+            *value = word.load(Ordering::Relaxed);
+        }
+        fence(Ordering::Acquire);
+        if slot[0].load(Ordering::Relaxed) != 2 * seq {
+            return None;
+        }
+        let level = |flag: u64, price: u64, quantity: u64| {
+            (values[5] & flag != 0).then(|| (f64::from_bits(price), f64::from_bits(quantity)))
+        };
+        Some(TopOfBook {
+            seq,
+            ts: values[0],
+            bid: level(HAS_BID, values[1], values[2]),
+            ask: level(HAS_ASK, values[3], values[4]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange_api_types::{RestQuote, SnapshotData};
+
+    const SYMBOL: &str = "SPOT_BTC_USDT";
+
+    // RingPath is a ring file in the temp dir, removed once the test is done with it.
+    struct RingPath(PathBuf);
+
+    impl RingPath {
+        fn new(name: &str) -> Self {
+            Self(std::env::temp_dir().join(format!("woox-shm-{}-{}.ring", name, std::process::id())))
+        }
+    }
+
+    impl Drop for RingPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn book(bid: f64, ask: f64) -> LocalOrderBook {
+        let mut book = LocalOrderBook::new();
+        book.apply_snapshot(SnapshotData { bids: vec![RestQuote { price: bid, quantity: 1.0 }], asks: vec![RestQuote { price: ask, quantity: 2.0 }] });
+        book
+    }
+
+    #[test]
+    fn reads_back_every_update_published() {
+        let path = RingPath::new("round-trip");
+        let mut publisher = ShmPublisher::create(&path.0, SYMBOL, 8).unwrap();
+        let mut reader = ShmReader::open(&path.0).unwrap();
+        assert_eq!(reader.symbol(), SYMBOL);
+        assert_eq!(reader.generation(), 1);
+        assert_eq!(reader.try_next(), None);
+        assert_eq!(reader.latest(), None);
+
+        publisher.publish(1000, &book(99.0, 101.0));
+        // An unchanged top isn't written again.
+        publisher.publish(1100, &book(99.0, 101.0));
+        publisher.publish(1200, &book(99.5, 101.0));
+        publisher.publish(1300, &LocalOrderBook::new());
+
+        let top = |seq, ts, bid, ask| Some(TopOfBook { seq, ts, bid, ask });
+        assert_eq!(reader.try_next(), top(1, 1000, Some((99.0, 1.0)), Some((101.0, 2.0))));
+        assert_eq!(reader.try_next(), top(2, 1200, Some((99.5, 1.0)), Some((101.0, 2.0))));
+        assert_eq!(reader.try_next(), top(3, 1300, None, None));
+        assert_eq!(reader.try_next(), None);
+        assert_eq!(reader.latest(), top(3, 1300, None, None));
+        assert_eq!(reader.missed(), 0);
+    }
+
+    #[test]
+    fn skips_ahead_a_reader_a_whole_ring_behind() {
+        let path = RingPath::new("overrun");
+        let mut publisher = ShmPublisher::create(&path.0, SYMBOL, 4).unwrap();
+        let mut reader = ShmReader::open(&path.0).unwrap();
+        for n in 0..10 {
+            publisher.publish(1000 + n, &book(90.0 + n as f64, 101.0));
+        }
+        let seqs: Vec<u64> = std::iter::from_fn(|| reader.try_next()).map(|top| top.seq).collect();
+        assert_eq!(seqs, vec![7, 8, 9, 10]);
+        assert_eq!(reader.missed(), 6);
+    }
+
+    #[test]
+    fn moves_a_reader_on_to_a_ring_recreated_at_its_path() {
+        let path = RingPath::new("recreate");
+        let mut first = ShmPublisher::create(&path.0, SYMBOL, 4).unwrap();
+        let mut reader = ShmReader::open(&path.0).unwrap();
+        first.publish(1000, &book(99.0, 101.0));
+        first.publish(1100, &book(99.5, 101.0));
+
+        // A restarted publisher starts its seq again, in a new ring, not under the reader.
+        let mut second = ShmPublisher::create(&path.0, SYMBOL, 8).unwrap();
+        second.publish(2000, &book(98.0, 102.0));
+
+        let read: Vec<(u64, u64)> = std::iter::from_fn(|| reader.try_next()).map(|top| (top.seq, top.ts)).collect();
+        assert_eq!(read, vec![(1, 1000), (2, 1100), (1, 2000)]);
+        assert_eq!(reader.generation(), 2);
+        assert_eq!(reader.missed(), 0);
+
+        second.publish(2100, &book(98.5, 102.0));
+        assert_eq!(reader.try_next().map(|top| (top.seq, top.ts)), Some((2, 2100)));
+        // A reader opened after only ever sees the new ring.
+        let mut late = ShmReader::open(&path.0).unwrap();
+        assert_eq!(late.latest().map(|top| top.ts), Some(2100));
+    }
+}
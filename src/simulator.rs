@@ -0,0 +1,355 @@
+use std::collections::BTreeMap;
+
+use crate::exchange_api_types::{Side, WsTrade};
+use crate::fees::FeeSchedule;
+use crate::order::{Fill, Liquidity, OrderId, OrderKind, OrderRequest, OrderStatus, OrderUpdate};
+use crate::orderbook::LocalOrderBook;
+use crate::pnl::Position;
+use crate::risk::RiskCheck;
+use crate::units::{Notional, Price, Qty};
+
+// QueueModel is when a resting order in a SimulatedExecution fills.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueueModel {
+    // FillOnTouch fills an order as soon as the book touches its price, as if it were
+    // first in the queue at its level.
+    FillOnTouch,
+    // Estimated places an order at the back of the queue at its level when it rests, and
+    // only fills it once trades at its price have eaten through the quantity ahead of it.
+    // The queue ahead shrinks with the trades at the level and with a proportional share of
+    // the level's other decreases, the cancels, which can come from anywhere in the queue.
+    // Trades through the price and the book moving through it fill regardless.
+    #[default]
+    Estimated,
+}
+
+// SimConfig is the fees a SimulatedExecution charges and how it fills resting orders.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SimConfig {
+    pub fees: FeeSchedule,
+    pub queue: QueueModel,
+}
+
+// QueuePosition is the estimated place of a resting order in the queue at its level: the
+// quantity ahead of it, and the level's quantity when last seen, excluding the order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct QueuePosition {
+    ahead: Qty,
+    level: Qty,
+}
+
+impl QueuePosition {
+    // on_level updates the queue for the level's quantity now being level. A decrease not
+    // already taken by trades is a cancel, taken from ahead of the order in proportion to
+    // how much of the level is ahead of it.
+    fn on_level(&mut self, level: Qty) {
+        if level < self.level && self.level > Qty::ZERO {
+            let kept = level.ratio(self.level);
+            self.ahead = self.ahead.checked_scale(kept).unwrap_or(Qty::ZERO);
+        }
+        self.ahead = self.ahead.min(level);
+        self.level = level;
+    }
+
+    // on_trade takes a trade of quantity at the level from the front of the queue,
+    // returning what is left of it once the queue ahead is through.
+    fn on_trade(&mut self, quantity: Qty) -> Qty {
+        let taken = quantity.min(self.ahead);
+        self.ahead -= taken;
+        self.level = (self.level - taken).max(Qty::ZERO);
+        quantity - taken
+    }
+}
+
+// SimOrder is an order resting in a SimulatedExecution.
+#[derive(Debug, Clone)]
+struct SimOrder {
+    id: OrderId,
+    request: OrderRequest,
+    filled: Qty,
+    notional: Notional,
+    // queue is the order's queue position once resting, under QueueModel::Estimated.
+    queue: Option<QueuePosition>,
+}
+
+impl SimOrder {
+    fn remaining(&self) -> Qty {
+        self.request.quantity - self.filled
+    }
+
+    fn update(&self, status: OrderStatus, fill: Option<Fill>) -> OrderUpdate {
+        OrderUpdate {
+            order_id: self.id,
+            symbol: self.request.symbol.clone(),
+            status,
+            filled: self.filled,
+            remaining: self.remaining(),
+            average_price: self.notional.checked_price(self.filled),
+            fill,
+        }
+    }
+}
+
+// SimulatedExecution paper trades a symbol against its live local book. Market orders and
+// the marketable part of limit orders fill immediately by walking the book, as a taker.
+// The rest of a limit order rests, filling as a maker as its QueueModel has it. Liquidity
+// taken isn't removed from the book, and resting orders aren't added to it, so orders are
+// assumed small next to the levels they trade with.
+pub struct SimulatedExecution {
+    symbol: String,
+    config: SimConfig,
+    next_id: OrderId,
+    orders: BTreeMap<OrderId, SimOrder>,
+    fills: Vec<Fill>,
+    position: Position,
+    risk: Option<RiskCheck>,
+}
+
+impl SimulatedExecution {
+    pub fn new(symbol: &str, config: SimConfig) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            config,
+            next_id: 1,
+            orders: BTreeMap::new(),
+            fills: Vec::new(),
+            position: Position::default(),
+            risk: None,
+        }
+    }
+
+    // with_risk checks every order submitted against risk, rejecting those breaking its
+    // limits, with the book's mid as the reference price.
+    pub fn with_risk(mut self, risk: RiskCheck) -> Self {
+        self.risk = Some(risk);
+        self
+    }
+
+    pub fn risk(&self) -> Option<&RiskCheck> {
+        self.risk.as_ref()
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    // open_orders returns the resting orders' ids and requests.
+    pub fn open_orders(&self) -> impl Iterator<Item = (OrderId, &OrderRequest)> {
+        self.orders.values().map(|order| (order.id, &order.request))
+    }
+
+    // fills returns every fill so far, oldest first.
+    pub fn fills(&self) -> &[Fill] {
+        &self.fills
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    // pnl returns the total PnL net of fees, with the open position marked at the mid of
+    // book, or at its average entry if book has no mid.
+    pub fn pnl(&self, book: &LocalOrderBook) -> Notional {
+        let mark = book.mid_price().or(self.position.average_entry).unwrap_or_default();
+        self.position.total(mark)
+    }
+
+    // submit places request against book, returning the updates it caused: a rejection, or
+    // its fills on arrival and whether it rests.
+    pub fn submit(&mut self, request: OrderRequest, book: &LocalOrderBook) -> Vec<OrderUpdate> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut order = SimOrder { id, request, filled: Qty::ZERO, notional: Notional::ZERO, queue: None };
+        if let Some(reason) = self.validate(&order.request) {
+            return vec![order.update(OrderStatus::Rejected(reason), None)];
+        }
+        if let Some(risk) = &self.risk {
+            if let Err(violation) = risk.check(&order.request, self.exposure(order.request.side), book.mid_price()) {
+                return vec![order.update(OrderStatus::Rejected(violation.to_string()), None)];
+            }
+        }
+
+        let ts = book.last_update_ts().unwrap_or_default();
+        let limit = order.request.price();
+        let levels: Vec<(Price, Qty)> = match order.request.side {
+            Side::Buy => book.asks().take_while(|&(price, _)| limit.is_none_or(|limit| price <= limit)).collect(),
+            Side::Sell => book.bids().take_while(|&(price, _)| limit.is_none_or(|limit| price >= limit)).collect(),
+        };
+        let mut updates = Vec::new();
+        for (price, quantity) in levels {
+            let remaining = order.remaining();
+            if remaining.is_zero() {
+                break;
+            }
+            let fill = self.fill(&mut order, price, quantity.min(remaining), Liquidity::Taker, ts);
+            updates.push(order.update(status_of(&order), Some(fill)));
+        }
+
+        match order.request.kind {
+            // A market order doesn't rest, what the book couldn't fill is cancelled.
+            OrderKind::Market if !order.remaining().is_zero() => updates.push(order.update(OrderStatus::Cancelled, None)),
+            OrderKind::Market => {}
+            OrderKind::Limit { .. } if order.remaining().is_zero() => {}
+            OrderKind::Limit { .. } => {
+                if updates.is_empty() {
+                    updates.push(order.update(OrderStatus::New, None));
+                }
+                if self.config.queue == QueueModel::Estimated {
+                    let level = book.quantity_at(order.request.side, order.request.price().unwrap_or_default());
+                    order.queue = Some(QueuePosition { ahead: level, level });
+                }
+                self.orders.insert(id, order);
+            }
+        }
+        updates
+    }
+
+    // cancel cancels a resting order, returning its final update, or None if it isn't resting.
+    pub fn cancel(&mut self, id: OrderId) -> Option<OrderUpdate> {
+        let order = self.orders.remove(&id)?;
+        Some(order.update(OrderStatus::Cancelled, None))
+    }
+
+    // queue_ahead returns the estimated quantity ahead of resting order id in its queue,
+    // under QueueModel::Estimated.
+    pub fn queue_ahead(&self, id: OrderId) -> Option<Qty> {
+        Some(self.orders.get(&id)?.queue?.ahead)
+    }
+
+    // cancel_all cancels every resting order.
+    pub fn cancel_all(&mut self) -> Vec<OrderUpdate> {
+        let ids: Vec<OrderId> = self.orders.keys().copied().collect();
+        ids.into_iter().filter_map(|id| self.cancel(id)).collect()
+    }
+
+    // on_book fills the resting orders the book has reached. Under FillOnTouch bids at or
+    // above the best ask and asks at or below the best bid fill in full at their price;
+    // under Estimated only orders the book has moved through do, and the queues of the rest
+    // are updated from their levels.
+    pub fn on_book(&mut self, book: &LocalOrderBook) -> Vec<OrderUpdate> {
+        let ts = book.last_update_ts().unwrap_or_default();
+        let (best_bid, best_ask) = (book.best_bid().map(|(price, _)| price), book.best_ask().map(|(price, _)| price));
+        let touch = self.config.queue == QueueModel::FillOnTouch;
+        let mut reached = Vec::new();
+        for order in self.orders.values_mut() {
+            let price = order.request.price().unwrap_or_default();
+            let filled = match order.request.side {
+                Side::Buy => best_ask.is_some_and(|ask| ask < price || (touch && ask == price)),
+                Side::Sell => best_bid.is_some_and(|bid| bid > price || (touch && bid == price)),
+            };
+            if filled {
+                reached.push(order.id);
+            } else if let Some(queue) = order.queue.as_mut() {
+                queue.on_level(book.quantity_at(order.request.side, price));
+            }
+        }
+        reached.into_iter().filter_map(|id| self.fill_resting(id, None, ts)).collect()
+    }
+
+    // on_trade fills resting orders a trade printed at or through: bids at or above the
+    // price of a sell and asks at or below the price of a buy, oldest first, up to the
+    // trade's size. Under Estimated a trade at an order's price first goes to the queue
+    // ahead of it.
+    pub fn on_trade(&mut self, trade: &WsTrade) -> Vec<OrderUpdate> {
+        if trade.symbol != self.symbol {
+            return Vec::new();
+        }
+        let trade_price = Price::new(trade.price);
+        let mut reached = Vec::new();
+        for order in self.orders.values_mut() {
+            let price = order.request.price().unwrap_or_default();
+            let hit = match (order.request.side, trade.side) {
+                (Side::Buy, Side::Sell) => price >= trade_price,
+                (Side::Sell, Side::Buy) => price <= trade_price,
+                _ => false,
+            };
+            if !hit {
+                continue;
+            }
+            let reaching = match order.queue.as_mut() {
+                Some(queue) if price == trade_price => queue.on_trade(Qty::new(trade.quantity)),
+                _ => Qty::new(trade.quantity),
+            };
+            if reaching > Qty::ZERO {
+                reached.push((order.id, reaching));
+            }
+        }
+
+        let mut available = Qty::new(trade.quantity);
+        let mut updates = Vec::new();
+        for (id, reaching) in reached {
+            if available.is_zero() {
+                break;
+            }
+            if let Some(update) = self.fill_resting(id, Some(available.min(reaching)), trade.ts) {
+                available -= update.fill.as_ref().map_or(Qty::ZERO, |fill| fill.quantity);
+                updates.push(update);
+            }
+        }
+        updates
+    }
+
+    // fill_resting fills up to limit of resting order id at its price as a maker, or all of
+    // it without a limit.
+    fn fill_resting(&mut self, id: OrderId, limit: Option<Qty>, ts: u64) -> Option<OrderUpdate> {
+        let mut order = self.orders.remove(&id)?;
+        let price = order.request.price()?;
+        let quantity = limit.map_or(order.remaining(), |limit| limit.min(order.remaining()));
+        let fill = self.fill(&mut order, price, quantity, Liquidity::Maker, ts);
+        let update = order.update(status_of(&order), Some(fill));
+        if !order.remaining().is_zero() {
+            self.orders.insert(id, order);
+        }
+        Some(update)
+    }
+
+    // fill executes quantity of order at price, charging the fee for liquidity.
+    fn fill(&mut self, order: &mut SimOrder, price: Price, quantity: Qty, liquidity: Liquidity, ts: u64) -> Fill {
+        let notional = price * quantity;
+        let fee = self.config.fees.fee(notional, liquidity);
+        order.filled += quantity;
+        order.notional += notional;
+        self.position.apply(order.request.side, price, quantity, fee);
+
+        let fill = Fill {
+            order_id: order.id,
+            symbol: order.request.symbol.clone(),
+            side: order.request.side,
+            price,
+            quantity,
+            fee,
+            liquidity,
+            ts,
+        };
+        self.fills.push(fill.clone());
+        fill
+    }
+
+    // validate returns why request can't be placed, if it can't.
+    // exposure returns the position plus the unfilled quantity resting on side, signed.
+    fn exposure(&self, side: Side) -> Qty {
+        let resting: Qty = self.orders.values().filter(|order| order.request.side == side).map(SimOrder::remaining).sum();
+        match side {
+            Side::Buy => self.position.quantity + resting,
+            Side::Sell => self.position.quantity - resting,
+        }
+    }
+
+    fn validate(&self, request: &OrderRequest) -> Option<String> {
+        if request.symbol != self.symbol {
+            return Some(format!("symbol {} isn't simulated", request.symbol));
+        }
+        if !(request.quantity.is_finite() && request.quantity > Qty::ZERO) {
+            return Some("quantity must be positive".to_string());
+        }
+        if request.price().is_some_and(|price| !(price.is_finite() && price > Price::ZERO)) {
+            return Some("price must be positive".to_string());
+        }
+        None
+    }
+}
+
+// status_of returns the status of an order that has been filled on.
+fn status_of(order: &SimOrder) -> OrderStatus {
+    if order.remaining().is_zero() { OrderStatus::Filled } else { OrderStatus::PartiallyFilled }
+}
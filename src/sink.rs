@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::time::Duration;
+
+use crate::exchange_api_types::WsTrade;
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+
+pub type SinkResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+// Sink is a destination the applied market data stream is written to, such as a recorder
+// or a database. Every method defaults to ignoring the record, so sinks only implement the
+// kinds of records they store.
+pub trait Sink: Send {
+    // name identifies the sink in logs.
+    fn name(&self) -> &str;
+
+    // record_snapshot is called with the book for symbol each time it is (re)synced from a
+    // snapshot taken at ts.
+    fn record_snapshot(&mut self, _symbol: &str, _ts: u64, _book: &LocalOrderBook) -> SinkResult {
+        Ok(())
+    }
+
+    // record_delta is called after event has been applied to book.
+    fn record_delta(&mut self, _symbol: &str, _event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        Ok(())
+    }
+
+    // record_stale is called once when no delta has been applied to book for age, and again
+    // after every later delta that is followed by as long a gap.
+    fn record_stale(&mut self, _symbol: &str, _age: Duration, _book: &LocalOrderBook) -> SinkResult {
+        Ok(())
+    }
+
+    fn record_trade(&mut self, _trade: &WsTrade) -> SinkResult {
+        Ok(())
+    }
+
+    // flush writes out anything the sink has buffered.
+    fn flush(&mut self) -> SinkResult {
+        Ok(())
+    }
+}
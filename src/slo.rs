@@ -0,0 +1,216 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::exchange_api_types::WsTrade;
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+
+// LagSlo is a delivery lag objective for a sink: at most max_violation_ratio of the records
+// written over each window may take longer than threshold from the exchange timestamp to
+// being written. It is violated persistently once windows_to_alert windows in a row miss it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LagSlo {
+    pub threshold: Duration,
+    pub max_violation_ratio: f64,
+    pub window: Duration,
+    pub windows_to_alert: u32,
+}
+
+impl Default for LagSlo {
+    fn default() -> Self {
+        Self {
+            threshold: Duration::from_millis(500),
+            max_violation_ratio: 0.01,
+            window: Duration::from_secs(10),
+            windows_to_alert: 3,
+        }
+    }
+}
+
+// LagCause is which side of the local receive most of the lag was spent on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagCause {
+    // Upstream lag is between the exchange timestamp and the record being received
+    // locally: the exchange, the network or the local clock.
+    Upstream,
+    // Downstream lag is between the record being received and the sink writing it: the
+    // book thread or the sink itself.
+    Downstream,
+}
+
+// SloAlertKind is whether an alert opens or closes a violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SloAlertKind {
+    Violated,
+    Recovered,
+}
+
+// SloAlert reports a sink starting or stopping to persistently miss its LagSlo, with the
+// stats of the window that triggered it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SloAlert {
+    pub sink: String,
+    pub kind: SloAlertKind,
+    pub cause: LagCause,
+    pub violation_ratio: f64,
+    pub mean_upstream: Duration,
+    pub mean_downstream: Duration,
+    pub samples: u64,
+}
+
+impl SloAlert {
+    pub fn summary(&self) -> String {
+        let state = match self.kind {
+            SloAlertKind::Violated => "violating",
+            SloAlertKind::Recovered => "recovered from",
+        };
+        format!(
+            "{} sink {} its lag SLO: {:.1}% of {} records late, mean upstream {:?} downstream {:?}, mostly {:?}",
+            self.sink,
+            state,
+            self.violation_ratio * 100.0,
+            self.samples,
+            self.mean_upstream,
+            self.mean_downstream,
+            self.cause
+        )
+    }
+}
+
+// LagWindow accumulates the lags of the records written during one window.
+#[derive(Debug, Default)]
+struct LagWindow {
+    samples: u64,
+    late: u64,
+    upstream_total: Duration,
+    downstream_total: Duration,
+}
+
+// LagMonitor tracks the delivery lag of a sink against a LagSlo.
+#[derive(Debug)]
+pub struct LagMonitor {
+    slo: LagSlo,
+    window: LagWindow,
+    window_start: Instant,
+    missed_windows: u32,
+    violating: bool,
+}
+
+impl LagMonitor {
+    pub fn new(slo: LagSlo) -> Self {
+        Self { slo, window: LagWindow::default(), window_start: Instant::now(), missed_windows: 0, violating: false }
+    }
+
+    pub fn is_violating(&self) -> bool {
+        self.violating
+    }
+
+    // record records a record's upstream and downstream lag and returns an alert for sink
+    // when the record closes a window that opens or closes a violation.
+    pub fn record(&mut self, sink: &str, upstream: Duration, downstream: Duration) -> Option<SloAlert> {
+        self.window.samples += 1;
+        if upstream + downstream > self.slo.threshold {
+            self.window.late += 1;
+        }
+        self.window.upstream_total += upstream;
+        self.window.downstream_total += downstream;
+
+        if self.window_start.elapsed() < self.slo.window {
+            return None;
+        }
+        self.window_start = Instant::now();
+        let window = std::mem::take(&mut self.window);
+
+        let violation_ratio = window.late as f64 / window.samples as f64;
+        let missed = violation_ratio > self.slo.max_violation_ratio;
+        self.missed_windows = if missed { self.missed_windows + 1 } else { 0 };
+
+        let kind = match (self.violating, missed) {
+            (false, true) if self.missed_windows >= self.slo.windows_to_alert => SloAlertKind::Violated,
+            (true, false) => SloAlertKind::Recovered,
+            _ => return None,
+        };
+        self.violating = kind == SloAlertKind::Violated;
+
+        let mean_upstream = window.upstream_total / window.samples as u32;
+        let mean_downstream = window.downstream_total / window.samples as u32;
+        Some(SloAlert {
+            sink: sink.to_string(),
+            kind,
+            cause: if mean_downstream > mean_upstream { LagCause::Downstream } else { LagCause::Upstream },
+            violation_ratio,
+            mean_upstream,
+            mean_downstream,
+            samples: window.samples,
+        })
+    }
+}
+
+// MonitoredSink is a sink that measures the delivery lag of every delta and trade written
+// to the sink it wraps, and passes an alert to on_alert when the sink starts or stops
+// persistently missing its LagSlo.
+pub struct MonitoredSink {
+    sink: Box<dyn Sink>,
+    monitor: LagMonitor,
+    on_alert: Box<dyn FnMut(&SloAlert) + Send>,
+}
+
+impl MonitoredSink {
+    pub fn new<F>(sink: Box<dyn Sink>, slo: LagSlo, on_alert: F) -> Self
+    where
+        F: FnMut(&SloAlert) + Send + 'static,
+    {
+        Self { sink, monitor: LagMonitor::new(slo), on_alert: Box::new(on_alert) }
+    }
+
+    pub fn is_violating(&self) -> bool {
+        self.monitor.is_violating()
+    }
+
+    // observe records a record with exchange timestamp ts, received locally at received.
+    fn observe(&mut self, ts: u64, received: Instant) {
+        let downstream = received.elapsed();
+        let total = Duration::from_millis(now_ms().saturating_sub(ts));
+        let upstream = total.saturating_sub(downstream);
+        if let Some(alert) = self.monitor.record(self.sink.name(), upstream, downstream) {
+            (self.on_alert)(&alert);
+        }
+    }
+}
+
+impl Sink for MonitoredSink {
+    fn name(&self) -> &str {
+        self.sink.name()
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        self.sink.record_snapshot(symbol, ts, book)
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, book: &LocalOrderBook) -> SinkResult {
+        let result = self.sink.record_delta(symbol, event, book);
+        self.observe(event.ts, event.received_at);
+        result
+    }
+
+    fn record_stale(&mut self, symbol: &str, age: Duration, book: &LocalOrderBook) -> SinkResult {
+        self.sink.record_stale(symbol, age, book)
+    }
+
+    // record_trade counts only the write as downstream, as trades don't carry the time
+    // they were received.
+    fn record_trade(&mut self, trade: &WsTrade) -> SinkResult {
+        let received = Instant::now();
+        let result = self.sink.record_trade(trade);
+        self.observe(trade.ts, received);
+        result
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.sink.flush()
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
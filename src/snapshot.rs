@@ -0,0 +1,213 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::warn;
+
+use crate::exchange::woox::ApiVersion;
+use crate::exchange_api_types::{RestSnapshot, V1RestSnapshot};
+use crate::http_client;
+use crate::orderbook::LocalOrderBook;
+use crate::retry::{is_transient, RetryPolicy, Retryable};
+
+pub const WOOX_REST_ORDERBOOK_URL: &str = "https://api.woox.io/v3/public/orderbook";
+
+// SnapshotError is returned when a snapshot source cannot provide a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    Http(reqwest::Error),
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    // Stale is returned when the available snapshot is older than the source accepts.
+    Stale { age: Duration },
+    // Exhausted is returned by a FallbackSource when every source failed.
+    Exhausted(Vec<SnapshotError>),
+    // RetriesExhausted is returned when every attempt of a retried request failed, with the
+    // error of the last.
+    RetriesExhausted { attempts: u32, last: Box<SnapshotError> },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Http(e) => write!(f, "snapshot request failed: {}", e),
+            SnapshotError::Io(e) => write!(f, "snapshot read failed: {}", e),
+            SnapshotError::Parse(e) => write!(f, "failed to parse snapshot: {}", e),
+            SnapshotError::Stale { age } => write!(f, "snapshot is stale ({}ms old)", age.as_millis()),
+            SnapshotError::Exhausted(errors) => {
+                write!(f, "all snapshot sources failed")?;
+                for e in errors {
+                    write!(f, "; {}", e)?;
+                }
+                Ok(())
+            }
+            SnapshotError::RetriesExhausted { attempts, last } => write!(f, "{} (after {} attempts)", last, attempts),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Retryable for SnapshotError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            SnapshotError::Http(e) => is_transient(e),
+            _ => false,
+        }
+    }
+
+    fn exhausted(attempts: u32, last: Self) -> Self {
+        SnapshotError::RetriesExhausted { attempts, last: Box::new(last) }
+    }
+}
+
+impl From<reqwest::Error> for SnapshotError {
+    fn from(e: reqwest::Error) -> Self {
+        SnapshotError::Http(e)
+    }
+}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Parse(e)
+    }
+}
+
+// SnapshotSource provides full order book snapshots used to (re)sync a local book.
+pub trait SnapshotSource: Send + Sync {
+    // name identifies the source in logs.
+    fn name(&self) -> &str;
+
+    // fetch returns a snapshot of the order book for symbol with up to max_level levels per side.
+    fn fetch(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError>;
+}
+
+// WooxRestSource fetches snapshots from the Woo X REST orderbook endpoint, retrying failed
+// requests with its RetryPolicy.
+pub struct WooxRestSource {
+    url: String,
+    version: ApiVersion,
+    http: reqwest::blocking::Client,
+    retry: RetryPolicy,
+}
+
+impl WooxRestSource {
+    pub fn new(url: &str) -> Self {
+        Self { url: url.to_string(), version: ApiVersion::default(), http: http_client::shared(), retry: RetryPolicy::default() }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+
+    // with_version fetches from the orderbook endpoint of version at url, such as
+    // WooxEnvironment::orderbook_url_for's.
+    pub fn with_version(self, version: ApiVersion) -> Self {
+        Self { version, ..self }
+    }
+}
+
+impl Default for WooxRestSource {
+    fn default() -> Self {
+        Self::new(WOOX_REST_ORDERBOOK_URL)
+    }
+}
+
+impl SnapshotSource for WooxRestSource {
+    fn name(&self) -> &str {
+        "woox-rest"
+    }
+
+    fn fetch(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        let get = |url: &str| self.http.get(url).timeout(self.retry.timeout).send()?.error_for_status();
+        match self.version {
+            ApiVersion::V3 => {
+                let url = format!("{}?symbol={}&maxLevel={}", self.url, symbol, max_level);
+                self.retry.run("snapshot", || Ok(get(&url)?.json()?))
+            }
+            ApiVersion::V2 => {
+                let url = format!("{}/{}?max_level={}", self.url, symbol, max_level);
+                self.retry.run("snapshot", || Ok(get(&url)?.json::<V1RestSnapshot>()?.into()))
+            }
+        }
+    }
+}
+
+// CheckpointSource reads a snapshot previously written with save_checkpoint. Checkpoints
+// older than max_age are rejected so a stale file never seeds the book.
+pub struct CheckpointSource {
+    path: PathBuf,
+    max_age: Duration,
+}
+
+impl CheckpointSource {
+    pub fn new(path: impl Into<PathBuf>, max_age: Duration) -> Self {
+        Self { path: path.into(), max_age }
+    }
+}
+
+impl SnapshotSource for CheckpointSource {
+    fn name(&self) -> &str {
+        "checkpoint"
+    }
+
+    fn fetch(&self, _symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        let mut snapshot: RestSnapshot = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let age = Duration::from_millis(now.saturating_sub(snapshot.timestamp));
+        if age > self.max_age {
+            return Err(SnapshotError::Stale { age });
+        }
+
+        snapshot.data.bids.truncate(max_level);
+        snapshot.data.asks.truncate(max_level);
+        Ok(snapshot)
+    }
+}
+
+// save_checkpoint writes the current state of book, last updated at timestamp by the event
+// numbered seq, to path in the format read by CheckpointSource.
+pub fn save_checkpoint(path: &std::path::Path, book: &LocalOrderBook, timestamp: u64, seq: u64) -> Result<(), SnapshotError> {
+    let snapshot = RestSnapshot::new(timestamp, seq, book.to_snapshot());
+    fs::write(path, serde_json::to_string(&snapshot)?)?;
+    Ok(())
+}
+
+// FallbackSource tries each of its sources in order and returns the first snapshot fetched.
+pub struct FallbackSource {
+    sources: Vec<Box<dyn SnapshotSource>>,
+}
+
+impl FallbackSource {
+    pub fn new(sources: Vec<Box<dyn SnapshotSource>>) -> Self {
+        Self { sources }
+    }
+}
+
+impl SnapshotSource for FallbackSource {
+    fn name(&self) -> &str {
+        "fallback"
+    }
+
+    fn fetch(&self, symbol: &str, max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        let mut errors = Vec::new();
+        for source in &self.sources {
+            match source.fetch(symbol, max_level) {
+                Ok(snapshot) => return Ok(snapshot),
+                Err(e) => {
+                    warn!(source = source.name(), %symbol, error = %e, "Snapshot source failed");
+                    errors.push(e);
+                }
+            }
+        }
+        Err(SnapshotError::Exhausted(errors))
+    }
+}
@@ -0,0 +1,160 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::exchange_api_types::{Side, WsTrade};
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::sink::{Sink, SinkResult};
+
+// DEFAULT_BATCH_SIZE is the number of rows written per transaction.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS snapshots (
+        id INTEGER PRIMARY KEY,
+        symbol TEXT NOT NULL,
+        ts INTEGER NOT NULL,
+        side TEXT NOT NULL,
+        level INTEGER NOT NULL,
+        price REAL NOT NULL,
+        quantity REAL NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS snapshots_symbol_ts ON snapshots (symbol, ts);
+
+    CREATE TABLE IF NOT EXISTS deltas (
+        id INTEGER PRIMARY KEY,
+        symbol TEXT NOT NULL,
+        ts INTEGER NOT NULL,
+        prev_ts INTEGER NOT NULL,
+        side TEXT NOT NULL,
+        price REAL NOT NULL,
+        quantity REAL NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS deltas_symbol_ts ON deltas (symbol, ts);
+
+    CREATE TABLE IF NOT EXISTS trades (
+        id INTEGER PRIMARY KEY,
+        symbol TEXT NOT NULL,
+        ts INTEGER NOT NULL,
+        side TEXT NOT NULL,
+        price REAL NOT NULL,
+        quantity REAL NOT NULL,
+        backfilled INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX IF NOT EXISTS trades_symbol_ts ON trades (symbol, ts);
+";
+
+// SqliteSink stores snapshots, deltas and trades in a SQLite database, indexed on
+// (symbol, ts), for deployments that want queryable history without a data warehouse.
+// Rows are written in transactions of batch_size rows.
+pub struct SqliteSink {
+    conn: Connection,
+    batch_size: usize,
+    pending: usize,
+}
+
+impl SqliteSink {
+    pub fn open(path: &Path, batch_size: usize) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn, batch_size: batch_size.max(1), pending: 0 })
+    }
+
+    // begin opens a transaction if one isn't already open.
+    fn begin(&mut self) -> rusqlite::Result<()> {
+        if self.conn.is_autocommit() {
+            self.conn.execute_batch("BEGIN")?;
+        }
+        Ok(())
+    }
+
+    // written records that rows were written, committing once a batch is full.
+    fn written(&mut self, rows: usize) -> rusqlite::Result<()> {
+        self.pending += rows;
+        if self.pending >= self.batch_size {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self) -> rusqlite::Result<()> {
+        if !self.conn.is_autocommit() {
+            self.conn.execute_batch("COMMIT")?;
+        }
+        self.pending = 0;
+        Ok(())
+    }
+}
+
+impl Sink for SqliteSink {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    fn record_snapshot(&mut self, symbol: &str, ts: u64, book: &LocalOrderBook) -> SinkResult {
+        self.begin()?;
+        let mut rows = 0;
+        {
+            let mut insert = self.conn.prepare_cached(
+                "INSERT INTO snapshots (symbol, ts, side, level, price, quantity) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            let bids: Vec<_> = book.bids().collect();
+            let asks: Vec<_> = book.asks().collect();
+            for (side, levels) in [("BID", bids), ("ASK", asks)] {
+                for (level, (price, quantity)) in levels.into_iter().enumerate() {
+                    insert.execute(params![symbol, ts as i64, side, level as i64 + 1, price.value(), quantity.value()])?;
+                    rows += 1;
+                }
+            }
+        }
+        self.written(rows)?;
+        Ok(())
+    }
+
+    fn record_delta(&mut self, symbol: &str, event: &MarketEvent, _book: &LocalOrderBook) -> SinkResult {
+        self.begin()?;
+        let mut rows = 0;
+        {
+            let mut insert = self.conn.prepare_cached(
+                "INSERT INTO deltas (symbol, ts, prev_ts, side, price, quantity) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            let sides = [("BID", &event.delta.bids), ("ASK", &event.delta.asks)];
+            for (side, quotes) in sides {
+                for quote in quotes {
+                    insert.execute(params![symbol, event.ts as i64, event.prev_ts as i64, side, quote.price, quote.quantity])?;
+                    rows += 1;
+                }
+            }
+        }
+        self.written(rows)?;
+        Ok(())
+    }
+
+    fn record_trade(&mut self, trade: &WsTrade) -> SinkResult {
+        let side = match trade.side {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL",
+        };
+        self.begin()?;
+        self.conn
+            .prepare_cached(
+                "INSERT INTO trades (symbol, ts, side, price, quantity, backfilled) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?
+            .execute(params![trade.symbol, trade.ts as i64, side, trade.price, trade.quantity, trade.backfilled])?;
+        self.written(1)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> SinkResult {
+        self.commit()?;
+        Ok(())
+    }
+}
+
+impl Drop for SqliteSink {
+    fn drop(&mut self) {
+        let _ = self.commit();
+    }
+}
@@ -0,0 +1,265 @@
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use crate::exchange::ExchangeFeed;
+use crate::exchange_api_types::RestSnapshot;
+use crate::feed::{self, FeedConfig, MarketEvent};
+use crate::orderbook::LocalOrderBook;
+use crate::queue;
+use crate::snapshot::{SnapshotError, SnapshotSource};
+use crate::sync::{BookSync, SnapshotAttempts, SnapshotCheck, SyncOutcome};
+
+// PollError is returned when a BookFeed can no longer make progress.
+#[derive(Debug)]
+pub enum PollError {
+    Snapshot(SnapshotError),
+    // OutOfSync means the stream moved past the snapshot or the book failed a checksum, and
+    // the feed must be restarted.
+    OutOfSync,
+    // Disconnected means the snapshot fetch or websocket stream ended unexpectedly.
+    Disconnected,
+}
+
+impl fmt::Display for PollError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollError::Snapshot(e) => write!(f, "{}", e),
+            PollError::OutOfSync => write!(f, "book out of sync"),
+            PollError::Disconnected => write!(f, "feed disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for PollError {}
+
+// BookFeed is a websocket book stream for a symbol together with the book synced from it.
+// The snapshot is fetched on a background thread once the first delta arrives, and the
+// deltas polled meanwhile are buffered until it does, so polling never blocks. If the
+// stream has moved past the snapshot by then, a newer one is fetched after the next delta.
+pub struct BookFeed {
+    symbol: String,
+    max_level: usize,
+    events: queue::Receiver<MarketEvent>,
+    source: Arc<dyn SnapshotSource>,
+    // snapshot is the snapshot fetch in flight, if any.
+    snapshot: Option<Receiver<Result<RestSnapshot, SnapshotError>>>,
+    // buffered are the deltas polled before the book is seeded.
+    buffered: Vec<MarketEvent>,
+    attempts: SnapshotAttempts,
+    exchange: Arc<dyn ExchangeFeed>,
+    sync: Option<BookSync>,
+}
+
+impl BookFeed {
+    pub fn start(config: &FeedConfig, symbol: &str, max_level: usize, source: &Arc<dyn SnapshotSource>) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            max_level,
+            events: feed::connect_stream(config, symbol, max_level),
+            source: Arc::clone(source),
+            snapshot: None,
+            buffered: Vec::new(),
+            attempts: SnapshotAttempts::new(config.exchange.sync_rule()),
+            exchange: Arc::clone(&config.exchange),
+            sync: None,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn max_level(&self) -> usize {
+        self.max_level
+    }
+
+    // book returns the book once the snapshot has arrived.
+    pub fn book(&self) -> Option<&LocalOrderBook> {
+        self.sync.as_ref().map(BookSync::book)
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.sync.as_ref().is_some_and(BookSync::is_synced)
+    }
+
+    // poll applies every delta available without blocking and returns how many were applied.
+    pub fn poll(&mut self) -> Result<usize, PollError> {
+        if self.sync.is_none() && !self.try_seed()? {
+            return Ok(0);
+        }
+
+        let mut buffered = std::mem::take(&mut self.buffered).into_iter();
+        let sync = self.sync.as_mut().unwrap();
+        let mut applied = 0;
+        loop {
+            let event = match buffered.next() {
+                Some(event) => event,
+                None => match self.events.try_recv() {
+                    Ok(event) => event,
+                    Err(TryRecvError::Empty) => return Ok(applied),
+                    Err(TryRecvError::Disconnected) => return Err(PollError::Disconnected),
+                },
+            };
+            match sync.on_event(&event) {
+                SyncOutcome::Behind(_) => {}
+                SyncOutcome::Synced | SyncOutcome::Applied => applied += 1,
+                SyncOutcome::OutOfSync | SyncOutcome::ChecksumMismatch { .. } => return Err(PollError::OutOfSync),
+            }
+        }
+    }
+
+    // try_seed buffers the deltas available, fetching a snapshot once there are enough, and
+    // returns true once the book has been seeded from a snapshot the stream continues. A
+    // stream that has ended still seeds the book with the deltas it buffered.
+    fn try_seed(&mut self) -> Result<bool, PollError> {
+        let mut ended = false;
+        while !ended {
+            match self.events.try_recv() {
+                Ok(event) => self.buffered.push(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => ended = true,
+            }
+        }
+        let Some(fetch) = &self.snapshot else {
+            if self.attempts.ready(&self.buffered) {
+                self.fetch();
+            } else if ended {
+                return Err(PollError::Disconnected);
+            }
+            return Ok(false);
+        };
+        let snapshot = match fetch.try_recv() {
+            Ok(Ok(snapshot)) => snapshot,
+            Ok(Err(e)) => return Err(PollError::Snapshot(e)),
+            Err(TryRecvError::Empty) => return Ok(false),
+            Err(TryRecvError::Disconnected) => return Err(PollError::Disconnected),
+        };
+        self.snapshot = None;
+        match self.attempts.check(snapshot, &self.buffered) {
+            SnapshotCheck::Sync(snapshot) => {
+                self.sync = Some(BookSync::for_exchange(snapshot, self.exchange.as_ref()));
+                Ok(true)
+            }
+            SnapshotCheck::Refetch => Ok(false),
+            SnapshotCheck::OutOfSync => Err(PollError::OutOfSync),
+        }
+    }
+
+    // fetch fetches a snapshot on a background thread.
+    fn fetch(&mut self) {
+        let (snapshot_tx, snapshot_rx) = mpsc::channel();
+        let source = Arc::clone(&self.source);
+        let (symbol, max_level) = (self.symbol.clone(), self.max_level);
+        thread::spawn(move || {
+            let _ = snapshot_tx.send(source.fetch(&symbol, max_level));
+        });
+        self.snapshot = Some(snapshot_rx);
+        self.attempts.fetching(&self.buffered);
+    }
+}
+
+// WarmPoll is the result of polling a WarmBook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WarmPoll {
+    // applied is the number of deltas applied to the active book.
+    pub applied: usize,
+    // swapped is set when a standby book was synced and became the active book.
+    pub swapped: bool,
+}
+
+// WarmBook keeps a synced book for a symbol and changes its subscribed depth without a gap
+// in coverage: the book at the new depth is built on a standby feed while the active feed
+// keeps updating, and it is swapped in once synced.
+pub struct WarmBook {
+    config: FeedConfig,
+    source: Arc<dyn SnapshotSource>,
+    active: BookFeed,
+    standby: Option<BookFeed>,
+    standby_error: Option<PollError>,
+}
+
+impl WarmBook {
+    pub fn start(config: &FeedConfig, symbol: &str, max_level: usize, source: Arc<dyn SnapshotSource>) -> Self {
+        let active = BookFeed::start(config, symbol, max_level, &source);
+        Self {
+            config: config.clone(),
+            source,
+            active,
+            standby: None,
+            standby_error: None,
+        }
+    }
+
+    pub fn active(&self) -> &BookFeed {
+        &self.active
+    }
+
+    // pending_depth returns the depth being built on the standby feed, if any.
+    pub fn pending_depth(&self) -> Option<usize> {
+        self.standby.as_ref().map(BookFeed::max_level)
+    }
+
+    // take_standby_error returns why the last standby feed was abandoned, if it failed.
+    pub fn take_standby_error(&mut self) -> Option<PollError> {
+        self.standby_error.take()
+    }
+
+    // change_depth starts building the book at max_level on a standby feed. Requesting the
+    // active depth cancels any pending change.
+    pub fn change_depth(&mut self, max_level: usize) {
+        if max_level == self.active.max_level() {
+            self.standby = None;
+            return;
+        }
+        if self.pending_depth() == Some(max_level) {
+            return;
+        }
+        let symbol = self.active.symbol().to_string();
+        self.standby = Some(BookFeed::start(&self.config, &symbol, max_level, &self.source));
+    }
+
+    // restart replaces the active feed with a new one at the same depth, e.g. after it fell
+    // out of sync. A pending depth change is kept.
+    pub fn restart(&mut self) {
+        let (symbol, max_level) = (self.active.symbol().to_string(), self.active.max_level());
+        self.active = BookFeed::start(&self.config, &symbol, max_level, &self.source);
+    }
+
+    // poll updates the active book and the standby book, swapping the standby in once it
+    // has synced. Errors are those of the active feed; a failed standby is dropped and its
+    // error kept for take_standby_error.
+    pub fn poll(&mut self) -> Result<WarmPoll, PollError> {
+        let mut swapped = false;
+        if let Some(standby) = self.standby.as_mut() {
+            match standby.poll() {
+                Ok(_) if standby.is_synced() => {
+                    self.active = self.standby.take().unwrap();
+                    swapped = true;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.standby = None;
+                    self.standby_error = Some(e);
+                }
+            }
+        }
+
+        let applied = self.active.poll()?;
+        Ok(WarmPoll { applied, swapped })
+    }
+
+    // poll_restarting polls the book, restarting it if it fell out of sync, and returns how
+    // many deltas were applied to the active book.
+    pub fn poll_restarting(&mut self) -> Result<usize, PollError> {
+        match self.poll() {
+            Ok(poll) => Ok(poll.applied),
+            Err(PollError::OutOfSync) => {
+                self.restart();
+                Ok(0)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::candle::{Candle, CandleBuilder, CandleSource};
+use crate::exchange_api_types::WsTrade;
+use crate::feed::{self, FeedConfig};
+use crate::order::{OrderId, OrderRequest, OrderUpdate};
+use crate::orderbook::LocalOrderBook;
+use crate::risk::RiskCheck;
+use crate::simulator::{SimConfig, SimulatedExecution};
+use crate::snapshot::SnapshotSource;
+use crate::standby::{PollError, WarmBook};
+
+// Strategy is trading logic driven by a StrategyRunner. Every hook has a default that does
+// nothing, so a strategy only implements the events it trades on. Orders are placed and
+// cancelled through the StrategyContext each hook is given, and take effect once the hook
+// returns.
+pub trait Strategy: Send {
+    fn name(&self) -> &str;
+
+    // on_book_update is called after the book changes.
+    fn on_book_update(&mut self, _ctx: &mut StrategyContext, _book: &LocalOrderBook) {}
+
+    // on_trade is called for each public trade in the symbol.
+    fn on_trade(&mut self, _ctx: &mut StrategyContext, _trade: &WsTrade) {}
+
+    // on_candle is called for each candle closed, if the runner builds them.
+    fn on_candle(&mut self, _ctx: &mut StrategyContext, _candle: &Candle) {}
+
+    // on_order_update is called for each change to an order the strategy placed.
+    fn on_order_update(&mut self, _ctx: &mut StrategyContext, _update: &OrderUpdate) {}
+
+    // on_timer is called every timer_interval, at the first event on or after it's due.
+    fn on_timer(&mut self, _ctx: &mut StrategyContext, _now: Instant) {}
+
+    // timer_interval is how often on_timer is called, or None to never call it.
+    fn timer_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// StrategyCommand is an order action a strategy has asked for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrategyCommand {
+    Submit(OrderRequest),
+    Cancel(OrderId),
+    // CancelAll cancels every open order of the strategy, not those of other strategies.
+    CancelAll,
+}
+
+// StrategyContext is what a strategy hook sees of the runner: the execution it trades on
+// and the current book, read only, and the commands it has issued so far.
+pub struct StrategyContext<'a> {
+    execution: &'a SimulatedExecution,
+    book: &'a LocalOrderBook,
+    commands: Vec<StrategyCommand>,
+}
+
+impl<'a> StrategyContext<'a> {
+    fn new(execution: &'a SimulatedExecution, book: &'a LocalOrderBook) -> Self {
+        Self { execution, book, commands: Vec::new() }
+    }
+
+    // execution returns the execution the strategy trades on, for its position and fills.
+    pub fn execution(&self) -> &SimulatedExecution {
+        self.execution
+    }
+
+    pub fn book(&self) -> &LocalOrderBook {
+        self.book
+    }
+
+    pub fn submit(&mut self, request: OrderRequest) {
+        self.commands.push(StrategyCommand::Submit(request));
+    }
+
+    pub fn cancel(&mut self, id: OrderId) {
+        self.commands.push(StrategyCommand::Cancel(id));
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.commands.push(StrategyCommand::CancelAll);
+    }
+
+    // commands returns the commands issued so far in this hook.
+    pub fn commands(&self) -> &[StrategyCommand] {
+        &self.commands
+    }
+}
+
+// RegisteredStrategy is a strategy in a StrategyRunner and when its timer is next due.
+struct RegisteredStrategy {
+    strategy: Box<dyn Strategy>,
+    next_timer: Option<Instant>,
+}
+
+// StrategyRunner drives the strategies registered with it from a symbol's book updates and
+// trades, trading on a paper trading SimulatedExecution. It does no IO itself, so the same
+// strategies run against a live feed through a StrategyLoop and against a recording from a
+// backtest's update callback.
+//
+// Each event first updates the execution, whose order updates go to the strategies that
+// placed the orders, and is then passed to every strategy in the order registered. Every
+// event comes with the current book, which the commands a hook issues are executed against
+// once it returns.
+pub struct StrategyRunner {
+    execution: SimulatedExecution,
+    strategies: Vec<RegisteredStrategy>,
+    // owners maps the orders placed by strategies to the index of the strategy.
+    owners: HashMap<OrderId, usize>,
+    candles: Option<CandleBuilder>,
+    halted: bool,
+}
+
+impl StrategyRunner {
+    pub fn new(symbol: &str, config: SimConfig) -> Self {
+        Self {
+            execution: SimulatedExecution::new(symbol, config),
+            strategies: Vec::new(),
+            owners: HashMap::new(),
+            candles: None,
+            halted: false,
+        }
+    }
+
+    // with_risk checks the orders of every strategy against risk before they are placed.
+    // A rejected order is reported to its strategy like any other update. Once the kill
+    // switch of risk is tripped the runner halts: the open orders are cancelled and the
+    // strategies are passed no more events.
+    pub fn with_risk(self, risk: RiskCheck) -> Self {
+        Self { execution: self.execution.with_risk(risk), ..self }
+    }
+
+    // with_candles builds candles of each of intervals from source, passing each one closed
+    // to the strategies before the event that closed it.
+    pub fn with_candles(self, source: CandleSource, intervals: &[Duration]) -> Self {
+        let candles = CandleBuilder::new(self.execution.symbol(), source, intervals);
+        Self { candles: Some(candles), ..self }
+    }
+
+    pub fn register(&mut self, strategy: Box<dyn Strategy>) {
+        let next_timer = strategy.timer_interval().map(|interval| Instant::now() + interval);
+        self.strategies.push(RegisteredStrategy { strategy, next_timer });
+    }
+
+    // strategies returns the names of the registered strategies, in order.
+    pub fn strategies(&self) -> impl Iterator<Item = &str> {
+        self.strategies.iter().map(|registered| registered.strategy.name())
+    }
+
+    pub fn execution(&self) -> &SimulatedExecution {
+        &self.execution
+    }
+
+    // is_halted returns true once the kill switch has halted the runner.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // check_halted halts the runner if the kill switch has been tripped, cancelling the open
+    // orders, and returns whether it is halted. The cancels aren't passed to the strategies.
+    fn check_halted(&mut self) -> bool {
+        if !self.halted && self.execution.risk().is_some_and(|risk| risk.kill_switch().is_tripped()) {
+            self.halted = true;
+            let cancelled = self.execution.cancel_all().len();
+            self.owners.clear();
+            warn!(cancelled, "Kill switch tripped, strategies halted");
+        }
+        self.halted
+    }
+
+    // on_book_update fills the resting orders book has reached and passes it to the
+    // strategies.
+    pub fn on_book_update(&mut self, book: &LocalOrderBook) {
+        if self.check_halted() {
+            return;
+        }
+        let updates = self.execution.on_book(book);
+        self.dispatch_updates(updates, book);
+        let candles = self.candles.as_mut().map(|candles| candles.on_book(book)).unwrap_or_default();
+        self.dispatch_candles(candles, book);
+        for index in 0..self.strategies.len() {
+            self.run_hook(index, book, |strategy, ctx| strategy.on_book_update(ctx, book));
+        }
+    }
+
+    // on_trade fills the resting orders trade printed through and passes it to the
+    // strategies. Trades in other symbols are ignored.
+    pub fn on_trade(&mut self, trade: &WsTrade, book: &LocalOrderBook) {
+        if trade.symbol != self.execution.symbol() || self.check_halted() {
+            return;
+        }
+        let updates = self.execution.on_trade(trade);
+        self.dispatch_updates(updates, book);
+        let candles = self.candles.as_mut().map(|candles| candles.on_trade(trade)).unwrap_or_default();
+        self.dispatch_candles(candles, book);
+        for index in 0..self.strategies.len() {
+            self.run_hook(index, book, |strategy, ctx| strategy.on_trade(ctx, trade));
+        }
+    }
+
+    // on_timer calls the timers that are due at now. A timer that fell more than an interval
+    // behind is next due an interval from now, rather than firing for every interval missed.
+    pub fn on_timer(&mut self, now: Instant, book: &LocalOrderBook) {
+        if self.check_halted() {
+            return;
+        }
+        for index in 0..self.strategies.len() {
+            let registered = &mut self.strategies[index];
+            let Some(due) = registered.next_timer.filter(|&due| due <= now) else { continue };
+            registered.next_timer = registered.strategy.timer_interval().map(|interval| {
+                let next = due + interval;
+                if next <= now { now + interval } else { next }
+            });
+            self.run_hook(index, book, |strategy, ctx| strategy.on_timer(ctx, now));
+        }
+    }
+
+    // run_hook calls hook on strategy index and executes the commands it issued.
+    fn run_hook<F>(&mut self, index: usize, book: &LocalOrderBook, hook: F)
+    where
+        F: FnOnce(&mut dyn Strategy, &mut StrategyContext),
+    {
+        let mut ctx = StrategyContext::new(&self.execution, book);
+        hook(self.strategies[index].strategy.as_mut(), &mut ctx);
+        let commands = ctx.commands;
+        for command in commands {
+            self.execute(index, command, book);
+        }
+    }
+
+    // execute runs command for strategy index, passing the updates it causes to the owners
+    // of the orders.
+    fn execute(&mut self, index: usize, command: StrategyCommand, book: &LocalOrderBook) {
+        let updates = match command {
+            StrategyCommand::Submit(request) => {
+                let updates = self.execution.submit(request, book);
+                if let Some(update) = updates.first() {
+                    self.owners.insert(update.order_id, index);
+                }
+                updates
+            }
+            StrategyCommand::Cancel(id) if self.owners.get(&id) == Some(&index) => {
+                self.execution.cancel(id).into_iter().collect()
+            }
+            StrategyCommand::Cancel(_) => Vec::new(),
+            StrategyCommand::CancelAll => {
+                let owned: Vec<OrderId> = self
+                    .execution
+                    .open_orders()
+                    .map(|(id, _)| id)
+                    .filter(|id| self.owners.get(id) == Some(&index))
+                    .collect();
+                owned.into_iter().filter_map(|id| self.execution.cancel(id)).collect()
+            }
+        };
+        self.dispatch_updates(updates, book);
+    }
+
+    // dispatch_candles passes each of candles to every strategy.
+    fn dispatch_candles(&mut self, candles: Vec<Candle>, book: &LocalOrderBook) {
+        for candle in candles {
+            for index in 0..self.strategies.len() {
+                self.run_hook(index, book, |strategy, ctx| strategy.on_candle(ctx, &candle));
+            }
+        }
+    }
+
+    // dispatch_updates passes updates to the strategies that placed the orders. Commands
+    // issued in response are executed in turn.
+    fn dispatch_updates(&mut self, updates: Vec<OrderUpdate>, book: &LocalOrderBook) {
+        for update in updates {
+            let Some(&index) = self.owners.get(&update.order_id) else { continue };
+            if update.status.is_final() {
+                self.owners.remove(&update.order_id);
+            }
+            self.run_hook(index, book, |strategy, ctx| strategy.on_order_update(ctx, &update));
+        }
+    }
+}
+
+// StrategyLoop runs a StrategyRunner against a live book and trade feed for its symbol.
+pub struct StrategyLoop {
+    book: WarmBook,
+    trades: Receiver<WsTrade>,
+    runner: StrategyRunner,
+}
+
+impl StrategyLoop {
+    pub fn start(
+        config: &FeedConfig,
+        source: Arc<dyn SnapshotSource>,
+        runner: StrategyRunner,
+        max_level: usize,
+    ) -> Self {
+        let symbol = runner.execution().symbol().to_string();
+        Self {
+            book: WarmBook::start(config, &symbol, max_level, source),
+            trades: feed::connect_trades(config, &symbol),
+            runner,
+        }
+    }
+
+    pub fn runner(&self) -> &StrategyRunner {
+        &self.runner
+    }
+
+    // poll applies the deltas and trades available, passing them to the strategies while
+    // the book is synced, then calls the timers that are due. Trades while it isn't are
+    // dropped. It returns how many events
+    // were passed on. The book is restarted if it falls out of sync.
+    pub fn poll(&mut self) -> Result<usize, PollError> {
+        let applied = self.book.poll_restarting()?;
+        let active = self.book.active();
+        let book = active.book().filter(|_| active.is_synced());
+        let mut events = 0;
+        if let (true, Some(book)) = (applied > 0, book) {
+            self.runner.on_book_update(book);
+            events += 1;
+        }
+        loop {
+            match self.trades.try_recv() {
+                Ok(trade) => {
+                    if let Some(book) = book {
+                        self.runner.on_trade(&trade, book);
+                        events += 1;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(PollError::Disconnected),
+            }
+        }
+        if let Some(book) = book {
+            self.runner.on_timer(Instant::now(), book);
+        }
+        Ok(events)
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+// TICK is how often workers and the config file are checked.
+const TICK: Duration = Duration::from_millis(200);
+// MIN_BACKOFF and MAX_BACKOFF bound the delay before restarting a crashed worker, which
+// doubles with every crash until the worker stays up for STABLE_AFTER.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+// ROLLING_GRACE is how long a restarted worker must stay up during a config reload before
+// the next changed worker is restarted.
+const ROLLING_GRACE: Duration = Duration::from_secs(5);
+
+// WorkerSpec describes a child worker process, typically one per venue.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct WorkerSpec {
+    pub name: String,
+    // program is the executable to run, the supervisor's own executable if not given.
+    #[serde(default)]
+    pub program: Option<PathBuf>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// SupervisorConfig is the JSON config file of the supervisor, e.g.
+// {"workers": [{"name": "woox-perps", "args": ["--all", "PERP_*"]}]}
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SupervisorConfig {
+    pub workers: Vec<WorkerSpec>,
+}
+
+#[derive(Debug)]
+pub enum SupervisorError {
+    Io(io::Error),
+    Config(serde_json::Error),
+    // DuplicateWorker means two workers in the config share a name.
+    DuplicateWorker(String),
+}
+
+impl fmt::Display for SupervisorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SupervisorError::Io(e) => write!(f, "{}", e),
+            SupervisorError::Config(e) => write!(f, "invalid supervisor config: {}", e),
+            SupervisorError::DuplicateWorker(name) => write!(f, "duplicate worker {}", name),
+        }
+    }
+}
+
+impl std::error::Error for SupervisorError {}
+
+impl From<io::Error> for SupervisorError {
+    fn from(e: io::Error) -> Self {
+        SupervisorError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SupervisorError {
+    fn from(e: serde_json::Error) -> Self {
+        SupervisorError::Config(e)
+    }
+}
+
+impl SupervisorConfig {
+    pub fn load(path: &Path) -> Result<Self, SupervisorError> {
+        let config: Self = serde_json::from_slice(&fs::read(path)?)?;
+        let mut names: Vec<&str> = config.workers.iter().map(|worker| worker.name.as_str()).collect();
+        names.sort_unstable();
+        if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(SupervisorError::DuplicateWorker(pair[0].to_string()));
+        }
+        Ok(config)
+    }
+}
+
+// Worker is a running, or crashed and waiting to restart, child process. The child is
+// killed when the worker is dropped.
+struct Worker {
+    spec: WorkerSpec,
+    child: Option<Child>,
+    started: Instant,
+    backoff: Duration,
+    restart_at: Option<Instant>,
+}
+
+impl Worker {
+    fn start(spec: WorkerSpec) -> Self {
+        let mut worker = Self { spec, child: None, started: Instant::now(), backoff: MIN_BACKOFF, restart_at: None };
+        worker.spawn();
+        worker
+    }
+
+    // spawn starts the child process, scheduling a retry if it can't be started.
+    fn spawn(&mut self) {
+        let program = match &self.spec.program {
+            Some(program) => program.clone(),
+            None => std::env::current_exe().unwrap_or_else(|_| PathBuf::from("woox")),
+        };
+        self.started = Instant::now();
+        match Command::new(program).args(&self.spec.args).spawn() {
+            Ok(child) => {
+                info!(worker = %self.spec.name, pid = child.id(), "Started worker");
+                self.child = Some(child);
+                self.restart_at = None;
+            }
+            Err(e) => {
+                warn!(worker = %self.spec.name, error = %e, "Failed to start worker");
+                self.schedule_restart();
+            }
+        }
+    }
+
+    fn schedule_restart(&mut self) {
+        if self.started.elapsed() >= STABLE_AFTER {
+            self.backoff = MIN_BACKOFF;
+        }
+        info!(worker = %self.spec.name, backoff = ?self.backoff, "Restarting worker");
+        self.restart_at = Some(Instant::now() + self.backoff);
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+
+    // check reaps the child if it exited and restarts it once its backoff has passed.
+    fn check(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            match child.try_wait() {
+                Ok(None) => return,
+                Ok(Some(status)) => warn!(worker = %self.spec.name, %status, "Worker exited"),
+                Err(e) => warn!(worker = %self.spec.name, error = %e, "Failed to check worker"),
+            }
+            self.child = None;
+            self.schedule_restart();
+        }
+        if self.restart_at.is_some_and(|at| Instant::now() >= at) {
+            self.spawn();
+        }
+    }
+
+    // is_up returns true if the child has been running for at least grace.
+    fn is_up(&self, grace: Duration) -> bool {
+        self.child.is_some() && self.started.elapsed() >= grace
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Supervisor runs the workers of a config file as child processes, restarting each with
+// backoff when it crashes, so one venue failing doesn't take down the others. When the file
+// changes, removed workers are stopped, added ones started, and changed ones restarted one
+// at a time, each only after the previous one has stayed up for a grace period, so a bad
+// config stops at the first worker it breaks.
+pub struct Supervisor {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    workers: BTreeMap<String, Worker>,
+    // rolling are the changed workers still to be restarted by a reload, in order.
+    rolling: VecDeque<WorkerSpec>,
+    rolling_current: Option<String>,
+}
+
+impl Supervisor {
+    // start loads the config at path and starts its workers.
+    pub fn start(path: impl Into<PathBuf>) -> Result<Self, SupervisorError> {
+        let path = path.into();
+        let config = SupervisorConfig::load(&path)?;
+        let workers = config.workers.into_iter().map(|spec| (spec.name.clone(), Worker::start(spec))).collect();
+        Ok(Self {
+            modified: modified(&path),
+            path,
+            workers,
+            rolling: VecDeque::new(),
+            rolling_current: None,
+        })
+    }
+
+    // run supervises the workers until the process is stopped.
+    pub fn run(mut self) {
+        loop {
+            self.tick();
+            thread::sleep(TICK);
+        }
+    }
+
+    // tick checks every worker, reloads the config if it changed and advances a rolling
+    // restart.
+    pub fn tick(&mut self) {
+        for worker in self.workers.values_mut() {
+            worker.check();
+        }
+
+        let modified = modified(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            match SupervisorConfig::load(&self.path) {
+                Ok(config) => self.reload(config),
+                Err(e) => warn!(path = %self.path.display(), error = %e, "Keeping the current workers, failed to reload the config"),
+            }
+        }
+
+        self.roll();
+    }
+
+    fn reload(&mut self, config: SupervisorConfig) {
+        info!(path = %self.path.display(), "Reloading supervisor config");
+        self.workers.retain(|name, _| {
+            let keep = config.workers.iter().any(|spec| &spec.name == name);
+            if !keep {
+                info!(worker = %name, "Stopping removed worker");
+            }
+            keep
+        });
+
+        self.rolling.clear();
+        for spec in config.workers {
+            match self.workers.get(&spec.name) {
+                None => {
+                    self.workers.insert(spec.name.clone(), Worker::start(spec));
+                }
+                Some(worker) if worker.spec != spec => self.rolling.push_back(spec),
+                Some(_) => {}
+            }
+        }
+    }
+
+    // roll restarts the next changed worker once the previous one is up.
+    fn roll(&mut self) {
+        if let Some(current) = &self.rolling_current {
+            if self.workers.get(current).is_some_and(|worker| !worker.is_up(ROLLING_GRACE)) {
+                return;
+            }
+            self.rolling_current = None;
+        }
+        let Some(spec) = self.rolling.pop_front() else { return };
+        info!(worker = %spec.name, "Restarting worker with its new config");
+        self.rolling_current = Some(spec.name.clone());
+        // Dropping the old worker stops its child before the new one starts.
+        self.workers.remove(&spec.name);
+        self.workers.insert(spec.name.clone(), Worker::start(spec));
+    }
+
+    // worker_names returns the names of the supervised workers.
+    pub fn worker_names(&self) -> impl Iterator<Item = &str> {
+        self.workers.keys().map(String::as_str)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+
+use crate::rest::{RestClient, RestError};
+
+// MAX_SUGGESTIONS is the most similar symbols listed for an unknown market.
+const MAX_SUGGESTIONS: usize = 5;
+
+// Market is the kind of instrument a symbol trades, its first part in Woo X naming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Market {
+    Spot,
+    Perp,
+}
+
+impl Market {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Market::Spot => "SPOT",
+            Market::Perp => "PERP",
+        }
+    }
+
+    // parse returns the market a symbol part names, accepting the usual aliases for
+    // perpetual swaps.
+    fn parse(part: &str) -> Option<Self> {
+        match part {
+            "SPOT" => Some(Market::Spot),
+            "PERP" | "PERPS" | "PERPETUAL" | "SWAP" => Some(Market::Perp),
+            _ => None,
+        }
+    }
+}
+
+// SymbolError is returned when an input can't be mapped to a listed symbol.
+#[derive(Debug)]
+pub enum SymbolError {
+    // Unknown means no listed symbol matches the input. suggestions are listed symbols
+    // that look similar.
+    Unknown { input: String, suggestions: Vec<String> },
+    // Ambiguous means the input matches several listed symbols, e.g. both the spot and
+    // perpetual markets of a pair.
+    Ambiguous { input: String, candidates: Vec<String> },
+    Rest(RestError),
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolError::Unknown { input, suggestions } if suggestions.is_empty() => write!(f, "unknown market {}", input),
+            SymbolError::Unknown { input, suggestions } => {
+                write!(f, "unknown market {}, did you mean {}?", input, suggestions.join(", "))
+            }
+            SymbolError::Ambiguous { input, candidates } => {
+                write!(f, "{} could be any of {}, name the market", input, candidates.join(", "))
+            }
+            SymbolError::Rest(e) => write!(f, "failed to list instruments: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+impl From<RestError> for SymbolError {
+    fn from(e: RestError) -> Self {
+        SymbolError::Rest(e)
+    }
+}
+
+// ListedSymbol is a Woo X symbol split into its parts.
+#[derive(Debug, Clone)]
+struct ListedSymbol {
+    symbol: String,
+    market: Option<Market>,
+    base: String,
+    quote: String,
+}
+
+impl ListedSymbol {
+    // parse splits a MARKET_BASE_QUOTE symbol, returning None for other shapes.
+    fn parse(symbol: &str) -> Option<Self> {
+        let (market, pair) = symbol.split_once('_')?;
+        let (base, quote) = pair.rsplit_once('_')?;
+        Some(Self {
+            symbol: symbol.to_string(),
+            market: Market::parse(market),
+            base: base.to_string(),
+            quote: quote.to_string(),
+        })
+    }
+}
+
+// SymbolMapper maps human friendly market names such as ETH-USDT-PERP, eth/usdt or ethusdt
+// to the Woo X symbol, e.g. PERP_ETH_USDT, validating them against the listed instruments.
+// Inputs that don't name the market resolve to the default market when it is listed for
+// the pair.
+#[derive(Debug, Clone)]
+pub struct SymbolMapper {
+    listed: Vec<ListedSymbol>,
+    default_market: Market,
+}
+
+impl SymbolMapper {
+    // new returns a mapper over the given listed symbols, defaulting to perpetuals.
+    pub fn new<I, S>(symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let listed = symbols.into_iter().filter_map(|symbol| ListedSymbol::parse(symbol.as_ref())).collect();
+        Self { listed, default_market: Market::Perp }
+    }
+
+    // fetch returns a mapper over every instrument listed on the Woo X REST API.
+    pub fn fetch(rest: &RestClient) -> Result<Self, RestError> {
+        let instruments = rest.instruments(None)?;
+        Ok(Self::new(instruments.iter().map(|instrument| &instrument.symbol)))
+    }
+
+    pub fn with_default_market(mut self, market: Market) -> Self {
+        self.default_market = market;
+        self
+    }
+
+    // normalize returns the listed symbol input names.
+    pub fn normalize(&self, input: &str) -> Result<String, SymbolError> {
+        let upper = input.trim().to_ascii_uppercase();
+        if let Some(listed) = self.listed.iter().find(|listed| listed.symbol == upper) {
+            return Ok(listed.symbol.clone());
+        }
+
+        let mut market = None;
+        let mut pair = String::new();
+        for part in upper.split(|c: char| !c.is_ascii_alphanumeric()).filter(|part| !part.is_empty()) {
+            match Market::parse(part) {
+                Some(parsed) if market.is_none() => market = Some(parsed),
+                _ => pair.push_str(part),
+            }
+        }
+
+        let matches: Vec<&ListedSymbol> = self
+            .listed
+            .iter()
+            .filter(|listed| format!("{}{}", listed.base, listed.quote) == pair)
+            .filter(|listed| market.is_none() || listed.market == market)
+            .collect();
+
+        match matches.as_slice() {
+            [] => Err(SymbolError::Unknown { input: input.to_string(), suggestions: self.suggestions(&pair) }),
+            [only] => Ok(only.symbol.clone()),
+            several => match several.iter().find(|listed| listed.market == Some(self.default_market)) {
+                Some(listed) => Ok(listed.symbol.clone()),
+                None => Err(SymbolError::Ambiguous {
+                    input: input.to_string(),
+                    candidates: several.iter().map(|listed| listed.symbol.clone()).collect(),
+                }),
+            },
+        }
+    }
+
+    // suggestions returns listed symbols for the base asset pair starts with.
+    fn suggestions(&self, pair: &str) -> Vec<String> {
+        if pair.is_empty() {
+            return Vec::new();
+        }
+        let mut suggestions: Vec<String> = self
+            .listed
+            .iter()
+            .filter(|listed| pair.starts_with(&listed.base))
+            .map(|listed| listed.symbol.clone())
+            .collect();
+        suggestions.sort();
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+}
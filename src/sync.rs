@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+
+use tracing::info;
+
+use crate::exchange::ExchangeFeed;
+use crate::exchange_api_types::{LevelText, OrderBookDelta, QuoteText, RestSnapshot, WsQuote};
+use crate::feed::MarketEvent;
+use crate::orderbook::LocalOrderBook;
+use crate::units::Price;
+
+// SyncOutcome is the result of offering a market event to a BookSync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncOutcome {
+    // Behind means the event predates the snapshot, or once synced the last event applied,
+    // and was skipped. It holds how far behind the stream is, in seq, which is ms on Woo X.
+    Behind(u64),
+    // Synced means the event was the first to continue the snapshot and the book is now synced.
+    Synced,
+    // Applied means the event was applied to an already synced book.
+    Applied,
+    // OutOfSync means the stream has moved past the snapshot without an event continuing
+    // it, so the book can't be synced from this snapshot.
+    OutOfSync,
+    // ChecksumMismatch means the event was applied, but the book no longer matches the
+    // exchange's checksum of it, so it has diverged and must be resynced.
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+// BookChecksum computes an exchange's checksum of a book, which is verified against the
+// checksum sent with each event where the exchange sends one. It is given the text the
+// book's levels were sent as, for those of its events that carry it.
+pub type BookChecksum = fn(&LocalOrderBook, &BookText) -> u32;
+
+// BookText is the text each level of a book was last sent as, (price, quantity), kept from
+// the QuoteText of the events applied to it.
+#[derive(Debug, Clone, Default)]
+pub struct BookText {
+    bids: BTreeMap<Price, LevelText>,
+    asks: BTreeMap<Price, LevelText>,
+}
+
+impl BookText {
+    // bid returns the text the bid at price was sent as, if it was sent with any.
+    pub fn bid(&self, price: Price) -> Option<(&str, &str)> {
+        self.bids.get(&price).map(|(price, quantity)| (&**price, &**quantity))
+    }
+
+    pub fn ask(&self, price: Price) -> Option<(&str, &str)> {
+        self.asks.get(&price).map(|(price, quantity)| (&**price, &**quantity))
+    }
+
+    // apply sets the text of the levels delta sets, and drops that of the levels it removes.
+    fn apply(&mut self, delta: &OrderBookDelta, text: &QuoteText) {
+        set_text(&mut self.bids, &delta.bids, &text.bids);
+        set_text(&mut self.asks, &delta.asks, &text.asks);
+    }
+}
+
+fn set_text(levels: &mut BTreeMap<Price, LevelText>, quotes: &[WsQuote], text: &[LevelText]) {
+    for (quote, text) in quotes.iter().zip(text) {
+        if quote.quantity == 0.0 {
+            levels.remove(&Price::new(quote.price));
+        } else {
+            levels.insert(Price::new(quote.price), text.clone());
+        }
+    }
+}
+
+// SyncRule is how an exchange's stream is joined to a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncRule {
+    // Exact syncs on the event continuing the snapshot, whose prev_seq is the snapshot's
+    // seq, as on Woo X.
+    #[default]
+    Exact,
+    // Overlap syncs on the first event spanning the snapshot, prev_seq <= snapshot seq <=
+    // seq, as on Binance. The event may repeat changes the snapshot already has, which is
+    // harmless as deltas set levels rather than adjust them.
+    Overlap,
+}
+
+// BookSync seeds a LocalOrderBook from a snapshot and applies the websocket deltas that
+// follow it, skipping deltas the snapshot already contains.
+pub struct BookSync {
+    book: LocalOrderBook,
+    snapshot_ts: u64,
+    snapshot_seq: u64,
+    rule: SyncRule,
+    // last_seq is the seq of the last event applied, or the snapshot's before the first.
+    last_seq: u64,
+    checksum: Option<BookChecksum>,
+    text: BookText,
+    synced: bool,
+}
+
+impl BookSync {
+    pub fn new(snapshot: RestSnapshot) -> Self {
+        Self::with_rule(snapshot, SyncRule::Exact)
+    }
+
+    pub fn with_rule(snapshot: RestSnapshot, rule: SyncRule) -> Self {
+        let snapshot_seq = snapshot.seq();
+        let mut book = LocalOrderBook::new();
+        book.apply_snapshot(snapshot.data);
+        book.mark_updated(snapshot.timestamp);
+        Self {
+            book,
+            snapshot_ts: snapshot.timestamp,
+            snapshot_seq,
+            rule,
+            last_seq: snapshot_seq,
+            checksum: None,
+            text: BookText::default(),
+            synced: false,
+        }
+    }
+
+    // for_exchange syncs from snapshot by the rule and checksum of exchange.
+    pub fn for_exchange(snapshot: RestSnapshot, exchange: &dyn ExchangeFeed) -> Self {
+        Self::with_rule(snapshot, exchange.sync_rule()).with_checksum(exchange.checksum())
+    }
+
+    // with_checksum verifies the book against the checksums of the events applied to it.
+    pub fn with_checksum(mut self, checksum: Option<BookChecksum>) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    pub fn book(&self) -> &LocalOrderBook {
+        &self.book
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    pub fn snapshot_ts(&self) -> u64 {
+        self.snapshot_ts
+    }
+
+    // on_event applies event to the book if it continues the snapshot or the synced stream,
+    // under either rule. A snapshot event replaces the book whenever it arrives, syncing it.
+    pub fn on_event(&mut self, event: &MarketEvent) -> SyncOutcome {
+        if event.snapshot {
+            self.book = LocalOrderBook::new();
+            self.text = BookText::default();
+            self.synced = true;
+            return self.apply(event).unwrap_or(SyncOutcome::Synced);
+        }
+
+        // Once synced every event must continue the one before it. One the book already
+        // has, as the events applied so far cover the stream up to last_seq, is skipped, and
+        // any other means deltas were lost, such as dropped from a full queue.
+        if self.synced {
+            if event.seq <= self.last_seq {
+                return SyncOutcome::Behind(self.last_seq - event.seq);
+            }
+            if event.prev_seq != self.last_seq {
+                return SyncOutcome::OutOfSync;
+            }
+            return self.apply(event).unwrap_or(SyncOutcome::Applied);
+        }
+
+        let continues = match self.rule {
+            SyncRule::Exact => event.prev_seq == self.snapshot_seq,
+            SyncRule::Overlap => event.prev_seq <= self.snapshot_seq && self.snapshot_seq <= event.seq,
+        };
+        if continues {
+            self.synced = true;
+            return self.apply(event).unwrap_or(SyncOutcome::Synced);
+        }
+
+        let behind = match self.rule {
+            SyncRule::Exact => event.prev_seq < self.snapshot_seq,
+            SyncRule::Overlap => event.seq < self.snapshot_seq,
+        };
+        if behind {
+            return SyncOutcome::Behind(self.snapshot_seq - event.prev_seq);
+        }
+
+        SyncOutcome::OutOfSync
+    }
+
+    // moved_past returns true if events, the first read from the stream, moved past
+    // snapshot before one continued it, so the book can't be synced from it and a newer
+    // snapshot is needed. It is false while every event is behind the snapshot.
+    pub fn moved_past(snapshot: &RestSnapshot, events: &[MarketEvent], rule: SyncRule) -> bool {
+        let mut probe = Self::with_rule(snapshot.clone(), rule);
+        let placed = events.iter().map(|event| probe.on_event(event)).find(|outcome| !matches!(outcome, SyncOutcome::Behind(_)));
+        placed == Some(SyncOutcome::OutOfSync)
+    }
+
+    // apply applies event to the book, returning a mismatch if the book then fails the
+    // event's checksum.
+    fn apply(&mut self, event: &MarketEvent) -> Option<SyncOutcome> {
+        self.book.apply_delta(&event.delta);
+        self.book.mark_updated(event.ts);
+        self.last_seq = event.seq;
+        if let Some(text) = &event.text {
+            self.text.apply(&event.delta, text);
+        }
+
+        let (checksum, expected) = (self.checksum?, event.checksum?);
+        let actual = checksum(&self.book, &self.text);
+        (actual != expected).then_some(SyncOutcome::ChecksumMismatch { expected, actual })
+    }
+}
+
+// MAX_SNAPSHOT_ATTEMPTS bounds the snapshots fetched for the stream to have moved past
+// before giving up syncing it.
+pub const MAX_SNAPSHOT_ATTEMPTS: usize = 5;
+
+// SnapshotCheck is whether a stream can be synced from a snapshot fetched for it.
+#[derive(Debug)]
+pub enum SnapshotCheck {
+    // Sync means the buffered events haven't moved past the snapshot, so it seeds the book.
+    Sync(RestSnapshot),
+    // Refetch means the stream has moved past the snapshot and a newer one is needed,
+    // fetched once another event has been read.
+    Refetch,
+    // OutOfSync means the stream moved past every snapshot fetched.
+    OutOfSync,
+}
+
+// SnapshotAttempts counts the snapshots fetched to sync a stream from, whichever way its
+// events are read and its snapshots fetched. A snapshot is fetched once the stream has
+// started, and if the stream has moved past it, as when the exchange's snapshots lag its
+// stream, the next once an event has been read since, up to MAX_SNAPSHOT_ATTEMPTS.
+#[derive(Debug, Clone)]
+pub struct SnapshotAttempts {
+    rule: SyncRule,
+    attempts: usize,
+    // fetch_after is how many events were buffered when the last snapshot was fetched,
+    // which must be exceeded for the next.
+    fetch_after: usize,
+}
+
+impl SnapshotAttempts {
+    pub fn new(rule: SyncRule) -> Self {
+        Self { rule, attempts: 0, fetch_after: 0 }
+    }
+
+    // ready returns true once buffered, the events read since the stream started, has one
+    // read since the last snapshot was fetched, so the next can be.
+    pub fn ready(&self, buffered: &[MarketEvent]) -> bool {
+        buffered.len() > self.fetch_after
+    }
+
+    // fetching records a snapshot being fetched with buffered read so far.
+    pub fn fetching(&mut self, buffered: &[MarketEvent]) {
+        self.fetch_after = buffered.len();
+        self.attempts += 1;
+    }
+
+    // check checks snapshot against buffered, which includes the events read while it was
+    // being fetched.
+    pub fn check(&self, snapshot: RestSnapshot, buffered: &[MarketEvent]) -> SnapshotCheck {
+        if !BookSync::moved_past(&snapshot, buffered, self.rule) {
+            return SnapshotCheck::Sync(snapshot);
+        }
+        info!(ts = snapshot.timestamp, attempt = self.attempts, buffered = buffered.len(), "Stream has moved past the snapshot");
+        if self.attempts >= MAX_SNAPSHOT_ATTEMPTS {
+            SnapshotCheck::OutOfSync
+        } else {
+            SnapshotCheck::Refetch
+        }
+    }
+}
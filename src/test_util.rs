@@ -0,0 +1,223 @@
+use std::error::Error;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+use tracing::warn;
+use tungstenite::Message;
+
+use crate::exchange::woox::WooxExchange;
+use crate::exchange_api_types::{RestQuote, RestSnapshot, SnapshotData};
+use crate::http::{self, Response};
+
+const ORDERBOOK_PATH: &str = "/v3/public/orderbook";
+
+// MockFrame is a step of the script the mock server plays to every subscribed connection.
+#[derive(Debug, Clone)]
+pub enum MockFrame {
+    // Delta is an order book update at ts continuing the one at prev_ts, with the levels as
+    // (price, quantity), a quantity of 0 removing the level.
+    Delta { ts: u64, prev_ts: u64, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)> },
+    // Ping is a ping the client is expected to answer with a pong.
+    Ping,
+    // Raw is sent as is, e.g. a malformed frame.
+    Raw(String),
+    // Pause waits before the next step.
+    Pause(Duration),
+}
+
+// MockWoox is an in-process stand-in for the Woo X public API, for exercising the whole
+// connect, snapshot and sync path without the exchange. Its websocket acks every
+// subscription, plays the script to it and then closes the connection; its REST API serves
+// the fixture snapshot at the orderbook endpoint.
+pub struct MockWoox {
+    snapshot: RestSnapshot,
+    script: Vec<MockFrame>,
+    // snapshot_failures is how many snapshot requests fail with a 503 before one succeeds.
+    snapshot_failures: usize,
+}
+
+impl MockWoox {
+    pub fn new(snapshot: RestSnapshot) -> Self {
+        Self { snapshot, script: Vec::new(), snapshot_failures: 0 }
+    }
+
+    pub fn with_frame(mut self, frame: MockFrame) -> Self {
+        self.script.push(frame);
+        self
+    }
+
+    pub fn with_delta(self, ts: u64, prev_ts: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Self {
+        self.with_frame(MockFrame::Delta { ts, prev_ts, bids: bids.to_vec(), asks: asks.to_vec() })
+    }
+
+    pub fn with_snapshot_failures(self, snapshot_failures: usize) -> Self {
+        Self { snapshot_failures, ..self }
+    }
+
+    // start binds the websocket and REST servers to ephemeral local ports and serves them
+    // on background threads, which run until the process exits.
+    pub fn start(self) -> io::Result<MockWooxServer> {
+        let state = Arc::new(MockState {
+            snapshot: self.snapshot,
+            script: self.script,
+            snapshot_failures: self.snapshot_failures,
+            snapshot_requests: AtomicUsize::new(0),
+            received: Mutex::new(Vec::new()),
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let ws_addr = listener.local_addr()?;
+        let ws_state = Arc::clone(&state);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = Arc::clone(&ws_state);
+                thread::spawn(move || {
+                    if let Err(e) = state.handle_client(stream) {
+                        warn!(error = %e, "Mock websocket client error");
+                    }
+                });
+            }
+        });
+
+        let rest_listener = TcpListener::bind("127.0.0.1:0")?;
+        let rest_addr = rest_listener.local_addr()?;
+        let rest_state = Arc::clone(&state);
+        http::serve_listener(rest_listener, move |request| rest_state.handle_request(request));
+
+        Ok(MockWooxServer { ws_addr, rest_addr, state })
+    }
+}
+
+// MockWooxServer is a running MockWoox.
+pub struct MockWooxServer {
+    ws_addr: SocketAddr,
+    rest_addr: SocketAddr,
+    state: Arc<MockState>,
+}
+
+impl MockWooxServer {
+    pub fn ws_url(&self) -> String {
+        format!("ws://{}/v3/public", self.ws_addr)
+    }
+
+    pub fn rest_url(&self) -> String {
+        format!("http://{}", self.rest_addr)
+    }
+
+    pub fn orderbook_url(&self) -> String {
+        format!("{}{}", self.rest_url(), ORDERBOOK_PATH)
+    }
+
+    // exchange returns the Woo X API served by the mock, for a FeedConfig or WooxClient.
+    pub fn exchange(&self) -> WooxExchange {
+        WooxExchange::new(&self.ws_url(), &self.orderbook_url())
+    }
+
+    // received returns every frame clients sent the websocket, subscriptions and pongs, in
+    // the order they were read.
+    pub fn received(&self) -> Vec<String> {
+        self.state.received.lock().unwrap().clone()
+    }
+
+    // snapshot_requests returns the number of snapshot requests served, failed or not.
+    pub fn snapshot_requests(&self) -> usize {
+        self.state.snapshot_requests.load(Ordering::Relaxed)
+    }
+}
+
+struct MockState {
+    snapshot: RestSnapshot,
+    script: Vec<MockFrame>,
+    snapshot_failures: usize,
+    snapshot_requests: AtomicUsize,
+    received: Mutex<Vec<String>>,
+}
+
+impl MockState {
+    // handle_client acks the client's subscription, plays it the script and closes the
+    // connection, recording every frame the client sends until it has closed too.
+    fn handle_client(&self, stream: TcpStream) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut socket = tungstenite::accept(stream).map_err(|e| e.to_string())?;
+
+        let subscription = loop {
+            if let Message::Text(text) = socket.read()? {
+                self.received.lock().unwrap().push(text.clone());
+                break text;
+            }
+        };
+        let request: serde_json::Value = serde_json::from_str(&subscription).unwrap_or_default();
+        let topic = request["params"][0].as_str().unwrap_or_default().to_string();
+        let ack = json!({ "id": request["id"], "event": "subscribe", "success": true, "ts": now_ms() });
+        socket.send(Message::Text(ack.to_string()))?;
+
+        for frame in &self.script {
+            let text = match frame {
+                MockFrame::Delta { ts, prev_ts, bids, asks } => json!({
+                    "topic": topic,
+                    "ts": ts,
+                    "data": { "prevTs": prev_ts, "bids": levels(bids), "asks": levels(asks) },
+                })
+                .to_string(),
+                MockFrame::Ping => json!({ "cmd": "PING", "ts": now_ms() }).to_string(),
+                MockFrame::Raw(text) => text.clone(),
+                MockFrame::Pause(pause) => {
+                    thread::sleep(*pause);
+                    continue;
+                }
+            };
+            socket.send(Message::Text(text))?;
+        }
+
+        socket.close(None)?;
+        loop {
+            match socket.read() {
+                Ok(Message::Text(text)) => self.received.lock().unwrap().push(text),
+                Ok(_) => {}
+                Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn handle_request(&self, request: &http::Request) -> Response {
+        if request.path != ORDERBOOK_PATH {
+            return Response::not_found();
+        }
+        if self.snapshot_requests.fetch_add(1, Ordering::Relaxed) < self.snapshot_failures {
+            return Response::new(503, "text/plain; charset=utf-8", "unavailable");
+        }
+        let max_level = request.query.get("maxLevel").and_then(|level| level.parse().ok()).unwrap_or(usize::MAX);
+        let limited = RestSnapshot {
+            timestamp: self.snapshot.timestamp,
+            data: SnapshotData {
+                bids: self.snapshot.data.bids.iter().take(max_level).copied().collect(),
+                asks: self.snapshot.data.asks.iter().take(max_level).copied().collect(),
+            },
+            seq: self.snapshot.seq,
+        };
+        match serde_json::to_string(&limited) {
+            Ok(json) => Response::json(json),
+            Err(e) => Response::new(503, "text/plain; charset=utf-8", e.to_string()),
+        }
+    }
+}
+
+// snapshot returns a fixture snapshot at timestamp with the levels as (price, quantity).
+pub fn snapshot(timestamp: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> RestSnapshot {
+    let quotes = |levels: &[(f64, f64)]| levels.iter().map(|&(price, quantity)| RestQuote { price, quantity }).collect();
+    RestSnapshot { timestamp, data: SnapshotData { bids: quotes(bids), asks: quotes(asks) }, seq: None }
+}
+
+// levels returns levels in the websocket's format, pairs of decimal strings.
+fn levels(levels: &[(f64, f64)]) -> Vec<[String; 2]> {
+    levels.iter().map(|(price, quantity)| [price.to_string(), quantity.to_string()]).collect()
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
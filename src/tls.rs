@@ -0,0 +1,167 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+
+// TlsConfig customizes how the exchange's certificates are verified, for websockets and REST
+// requests alike. ca_file is a PEM bundle of root certificates trusted in addition to the
+// system's, e.g. the CA of a TLS-intercepting proxy. pinned_sha256 are the SHA-256
+// fingerprints of the server certificates accepted, hex with or without colons as printed
+// by openssl x509 -fingerprint -sha256; when given, a server whose certificate chains to a
+// trusted root is still rejected unless its certificate is one of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub ca_file: Option<PathBuf>,
+    pub pinned_sha256: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(io::Error),
+    // NoCertificates means the CA file has no PEM certificates in it.
+    NoCertificates(PathBuf),
+    // InvalidPin means a pinned fingerprint isn't 32 bytes of hex.
+    InvalidPin(String),
+    Rustls(rustls::Error),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::Io(e) => write!(f, "{}", e),
+            TlsError::NoCertificates(path) => write!(f, "no certificates in {}", path.display()),
+            TlsError::InvalidPin(pin) => write!(f, "invalid certificate fingerprint {}", pin),
+            TlsError::Rustls(e) => write!(f, "tls error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+impl From<io::Error> for TlsError {
+    fn from(e: io::Error) -> Self {
+        TlsError::Io(e)
+    }
+}
+
+impl From<rustls::Error> for TlsError {
+    fn from(e: rustls::Error) -> Self {
+        TlsError::Rustls(e)
+    }
+}
+
+impl TlsConfig {
+    pub fn with_ca_file(self, ca_file: impl Into<PathBuf>) -> Self {
+        Self { ca_file: Some(ca_file.into()), ..self }
+    }
+
+    pub fn with_pinned(self, fingerprints: &[&str]) -> Self {
+        Self { pinned_sha256: fingerprints.iter().map(|pin| pin.to_string()).collect(), ..self }
+    }
+
+    // client_config builds the rustls config connections are made with, trusting the system's
+    // roots and ca_file's.
+    pub fn client_config(&self) -> Result<Arc<ClientConfig>, TlsError> {
+        let mut roots = RootCertStore::empty();
+        // Unreadable system certificates are skipped, as tungstenite and reqwest do.
+        roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+        if let Some(path) = &self.ca_file {
+            let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?)).collect::<Result<Vec<_>, _>>()?;
+            if certs.is_empty() {
+                return Err(TlsError::NoCertificates(path.clone()));
+            }
+            for cert in certs {
+                roots.add(cert)?;
+            }
+        }
+
+        // The provider is chosen explicitly, since the features enabled by tungstenite and
+        // reqwest don't leave rustls a default.
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), Arc::clone(&provider))
+            .build()
+            .map_err(|e| TlsError::Rustls(rustls::Error::General(e.to_string())))?;
+        let builder = ClientConfig::builder_with_provider(provider).with_safe_default_protocol_versions()?;
+        let config = match self.pinned_sha256.is_empty() {
+            true => builder.with_webpki_verifier(verifier).with_no_client_auth(),
+            false => {
+                let pins = self.pinned_sha256.iter().map(|pin| parse_pin(pin)).collect::<Result<Vec<_>, _>>()?;
+                let verifier = Arc::new(PinnedVerifier { inner: verifier, pins });
+                builder.dangerous().with_custom_certificate_verifier(verifier).with_no_client_auth()
+            }
+        };
+        Ok(Arc::new(config))
+    }
+}
+
+// fingerprint returns the SHA-256 fingerprint of a DER certificate, as hex.
+pub fn fingerprint(cert: &[u8]) -> String {
+    Sha256::digest(cert).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_pin(pin: &str) -> Result<[u8; 32], TlsError> {
+    let hex: String = pin.chars().filter(|c| *c != ':').collect();
+    let mut bytes = [0u8; 32];
+    if hex.len() != 64 {
+        return Err(TlsError::InvalidPin(pin.to_string()));
+    }
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| TlsError::InvalidPin(pin.to_string()))?;
+    }
+    Ok(bytes)
+}
+
+// PinnedVerifier verifies the server's certificate chain as usual, then rejects it unless the
+// server's certificate is pinned.
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        let digest: [u8; 32] = Sha256::digest(end_entity.as_ref()).into();
+        match self.pins.contains(&digest) {
+            true => Ok(verified),
+            false => Err(rustls::Error::General(format!("certificate {} isn't pinned", fingerprint(end_entity)))),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
@@ -0,0 +1,621 @@
+use std::env;
+use std::fmt;
+use std::io;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tracing::{info, info_span, warn};
+use tungstenite::Message;
+
+use crate::exchange::woox::WooxExchange;
+use crate::exchange::{ExchangeFeed, Frame};
+use crate::exchange_api_types::{f64_from_string_or_number, Side};
+use crate::feed::{self, FeedConfig};
+use crate::fees::FeeSchedule;
+use crate::http_client;
+use crate::order::{Liquidity, OrderId, OrderKind, OrderRequest, OrderStatus};
+use crate::rest::{RestError, RestResponse, RestRows, WOOX_REST_URL};
+use crate::retry::{fail_transient, RetryPolicy};
+use crate::transport::WsTransport;
+use crate::units::{Notional, Price, Qty};
+
+pub const WOOX_PRIVATE_WS_URL: &str = "wss://wss.woox.io/v3/private";
+const CLIENT_ID: &str = "client_id_x";
+const EXECUTION_REPORT_TOPIC: &str = "executionreport";
+// WOOX_PING_INTERVAL is how often Woo X pings a websocket connection.
+const WOOX_PING_INTERVAL: Duration = Duration::from_secs(10);
+// PRIVATE_STREAM_TIMEOUT is how long the private stream may go without a frame before its
+// connection is taken to be dead. A few ping intervals, so one late ping doesn't end it.
+const PRIVATE_STREAM_TIMEOUT: Duration = Duration::from_secs(WOOX_PING_INTERVAL.as_secs() * 3);
+
+// ApiCredentials are the key and secret private requests are signed with.
+#[derive(Clone)]
+pub struct ApiCredentials {
+    pub api_key: String,
+    api_secret: String,
+}
+
+// The secret is kept out of Debug output, so credentials can be logged with the config
+// holding them.
+impl fmt::Debug for ApiCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiCredentials").field("api_key", &self.api_key).finish_non_exhaustive()
+    }
+}
+
+impl ApiCredentials {
+    pub fn new(api_key: &str, api_secret: &str) -> Self {
+        Self { api_key: api_key.to_string(), api_secret: api_secret.to_string() }
+    }
+
+    // from_env reads the credentials from WOOX_API_KEY and WOOX_API_SECRET, if both are set.
+    pub fn from_env() -> Option<Self> {
+        Some(Self::new(&env::var("WOOX_API_KEY").ok()?, &env::var("WOOX_API_SECRET").ok()?))
+    }
+
+    // sign returns the hex HMAC-SHA256 of payload under the secret.
+    pub fn sign(&self, payload: &str) -> String {
+        hmac_sha256(self.api_secret.as_bytes(), payload.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+// hmac_sha256 is HMAC (RFC 2104) over SHA-256.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new().chain_update(block.map(|byte| byte ^ 0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(block.map(|byte| byte ^ 0x5c)).chain_update(inner).finalize().into()
+}
+
+// ExchangeOrder is the state of an order as the exchange reports it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExchangeOrder {
+    pub order_id: OrderId,
+    pub client_order_id: u64,
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: Qty,
+    // price is None for market orders.
+    pub price: Option<Price>,
+    pub status: OrderStatus,
+    pub filled: Qty,
+    pub average_price: Option<Price>,
+    pub ts: u64,
+}
+
+// ExecutionFill is the fill an execution report announces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionFill {
+    pub trade_id: u64,
+    pub price: Price,
+    pub quantity: Qty,
+    // fee is the fee charged, assumed to be in quote currency.
+    pub fee: Notional,
+    pub liquidity: Liquidity,
+}
+
+// ExecutionReport is a change to one of the account's orders, pushed on the private stream:
+// the order's state after it, and the fill that caused it, if one did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionReport {
+    pub order: ExchangeOrder,
+    pub fill: Option<ExecutionFill>,
+}
+
+// TradingClient places and manages orders on a venue. client_order_id is the caller's own
+// id for an order, echoed back in its execution reports so they can be matched to it before
+// the venue's order id is known.
+pub trait TradingClient: Send + Sync {
+    // place_order places request, returning the venue's id for it.
+    fn place_order(&self, request: &OrderRequest, client_order_id: u64) -> Result<OrderId, RestError>;
+
+    fn cancel_order(&self, symbol: &str, order_id: OrderId) -> Result<(), RestError>;
+
+    // cancel_all cancels every open order in symbol, or in every symbol if None.
+    fn cancel_all(&self, symbol: Option<&str>) -> Result<(), RestError>;
+
+    fn order(&self, order_id: OrderId) -> Result<ExchangeOrder, RestError>;
+
+    // open_orders returns the orders resting in symbol, or in every symbol if None.
+    fn open_orders(&self, symbol: Option<&str>) -> Result<Vec<ExchangeOrder>, RestError>;
+
+    // fee_schedule returns the account's fee rates at its current fee tier. Clients of venues
+    // that don't report them return an error, and the rates have to come from config.
+    fn fee_schedule(&self) -> Result<FeeSchedule, RestError> {
+        Err(RestError::Api("fee rates not available from this client".to_string()))
+    }
+}
+
+// A shared client trades like the client it shares, e.g. between an OrderManager and a
+// DeadManSwitch.
+impl<T: TradingClient + ?Sized> TradingClient for Arc<T> {
+    fn place_order(&self, request: &OrderRequest, client_order_id: u64) -> Result<OrderId, RestError> {
+        (**self).place_order(request, client_order_id)
+    }
+
+    fn cancel_order(&self, symbol: &str, order_id: OrderId) -> Result<(), RestError> {
+        (**self).cancel_order(symbol, order_id)
+    }
+
+    fn cancel_all(&self, symbol: Option<&str>) -> Result<(), RestError> {
+        (**self).cancel_all(symbol)
+    }
+
+    fn order(&self, order_id: OrderId) -> Result<ExchangeOrder, RestError> {
+        (**self).order(order_id)
+    }
+
+    fn open_orders(&self, symbol: Option<&str>) -> Result<Vec<ExchangeOrder>, RestError> {
+        (**self).open_orders(symbol)
+    }
+
+    fn fee_schedule(&self) -> Result<FeeSchedule, RestError> {
+        (**self).fee_schedule()
+    }
+}
+
+// WooxTradingClient is a TradingClient for the Woo X v3 private REST API. Requests are signed
+// with the HMAC-SHA256 of their timestamp, method, path and body. GET requests are retried
+// with its RetryPolicy; orders are placed and cancelled in a single attempt, since a request
+// that timed out may still have reached the exchange.
+pub struct WooxTradingClient {
+    base_url: String,
+    credentials: ApiCredentials,
+    http: reqwest::blocking::Client,
+    retry: RetryPolicy,
+}
+
+impl WooxTradingClient {
+    pub fn new(base_url: &str, credentials: ApiCredentials) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            credentials,
+            http: http_client::shared(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    // live returns a client for the production API.
+    pub fn live(credentials: ApiCredentials) -> Self {
+        Self::new(WOOX_REST_URL, credentials)
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+
+    // send signs and sends a request to path, a path and query string, unwrapping the
+    // response envelope. Each attempt is signed afresh, so retries aren't rejected as stale.
+    fn send<T: DeserializeOwned>(&self, method: Method, path: &str, body: Option<serde_json::Value>) -> Result<T, RestError> {
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let retry = match method {
+            Method::GET => self.retry,
+            _ => RetryPolicy::once(self.retry.timeout),
+        };
+        retry.run(path, || {
+            let timestamp = now_ms().to_string();
+            let signature = self.credentials.sign(&format!("{}{}{}{}", timestamp, method, path, body));
+
+            let mut request = self
+                .http
+                .request(method.clone(), format!("{}{}", self.base_url, path))
+                .timeout(retry.timeout)
+                .header("x-api-key", &self.credentials.api_key)
+                .header("x-api-signature", signature)
+                .header("x-api-timestamp", timestamp);
+            if !body.is_empty() {
+                request = request.header("Content-Type", "application/json").body(body.clone());
+            }
+            fail_transient(request.send()?)?.json::<RestResponse<T>>()?.into_data()
+        })
+    }
+}
+
+// WooxOrderAck is a struct representation of the place order response data.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WooxOrderAck {
+    order_id: OrderId,
+}
+
+// WooxOrder is a struct representation of an order from the Woo X order endpoints.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WooxOrder {
+    order_id: OrderId,
+    #[serde(default)]
+    client_order_id: u64,
+    symbol: String,
+    side: Side,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    quantity: f64,
+    #[serde(default, deserialize_with = "f64_from_string_or_number")]
+    price: f64,
+    status: String,
+    #[serde(default, alias = "executed", deserialize_with = "f64_from_string_or_number")]
+    total_executed_quantity: f64,
+    #[serde(default, alias = "averageExecutedPrice", deserialize_with = "f64_from_string_or_number")]
+    avg_price: f64,
+    #[serde(default, alias = "updatedTime", alias = "timestamp")]
+    updated_time: u64,
+}
+
+impl WooxOrder {
+    fn into_order(self, reason: Option<String>) -> ExchangeOrder {
+        let filled = Qty::new(self.total_executed_quantity);
+        ExchangeOrder {
+            order_id: self.order_id,
+            client_order_id: self.client_order_id,
+            symbol: self.symbol,
+            side: self.side,
+            quantity: Qty::new(self.quantity),
+            price: (self.price > 0.0).then(|| Price::new(self.price)),
+            status: woox_status(&self.status, reason, filled),
+            filled,
+            average_price: (self.avg_price > 0.0).then(|| Price::new(self.avg_price)),
+            ts: self.updated_time,
+        }
+    }
+}
+
+// WooxAccountInfo is a struct representation of the account info response data, of which
+// only the fee rates of the account's tier are used.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WooxAccountInfo {
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    maker_fee_rate: f64,
+    #[serde(deserialize_with = "f64_from_string_or_number")]
+    taker_fee_rate: f64,
+}
+
+// woox_status maps a Woo X order status to an OrderStatus. Statuses of requests in flight,
+// like CANCEL_REQUESTED, leave the order open.
+fn woox_status(status: &str, reason: Option<String>, filled: Qty) -> OrderStatus {
+    match status {
+        "FILLED" => OrderStatus::Filled,
+        "CANCELLED" => OrderStatus::Cancelled,
+        "REJECTED" => OrderStatus::Rejected(reason.unwrap_or_else(|| "rejected by the exchange".to_string())),
+        "PARTIAL_FILLED" => OrderStatus::PartiallyFilled,
+        _ if filled > Qty::ZERO => OrderStatus::PartiallyFilled,
+        _ => OrderStatus::New,
+    }
+}
+
+impl TradingClient for WooxTradingClient {
+    fn place_order(&self, request: &OrderRequest, client_order_id: u64) -> Result<OrderId, RestError> {
+        let side = match request.side {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL",
+        };
+        let mut body = json!({
+            "symbol": request.symbol,
+            "side": side,
+            "quantity": request.quantity.value().to_string(),
+            "clientOrderId": client_order_id,
+        });
+        match request.kind {
+            OrderKind::Market => body["type"] = json!("MARKET"),
+            OrderKind::Limit { price } => {
+                body["type"] = json!("LIMIT");
+                body["price"] = json!(price.value().to_string());
+            }
+        }
+        let ack: WooxOrderAck = self.send(Method::POST, "/v3/trade/order", Some(body))?;
+        Ok(ack.order_id)
+    }
+
+    fn cancel_order(&self, symbol: &str, order_id: OrderId) -> Result<(), RestError> {
+        let path = format!("/v3/trade/order?orderId={}&symbol={}", order_id, symbol);
+        self.send::<serde_json::Value>(Method::DELETE, &path, None).map(|_| ())
+    }
+
+    fn cancel_all(&self, symbol: Option<&str>) -> Result<(), RestError> {
+        let path = match symbol {
+            Some(symbol) => format!("/v3/trade/orders?symbol={}", symbol),
+            None => "/v3/trade/orders".to_string(),
+        };
+        self.send::<serde_json::Value>(Method::DELETE, &path, None).map(|_| ())
+    }
+
+    fn order(&self, order_id: OrderId) -> Result<ExchangeOrder, RestError> {
+        let order: WooxOrder = self.send(Method::GET, &format!("/v3/trade/order?orderId={}", order_id), None)?;
+        Ok(order.into_order(None))
+    }
+
+    fn open_orders(&self, symbol: Option<&str>) -> Result<Vec<ExchangeOrder>, RestError> {
+        let path = match symbol {
+            Some(symbol) => format!("/v3/trade/orders?status=INCOMPLETE&symbol={}", symbol),
+            None => "/v3/trade/orders?status=INCOMPLETE".to_string(),
+        };
+        let orders: RestRows<WooxOrder> = self.send(Method::GET, &path, None)?;
+        Ok(orders.rows.into_iter().map(|order| order.into_order(None)).collect())
+    }
+
+    fn fee_schedule(&self) -> Result<FeeSchedule, RestError> {
+        let info: WooxAccountInfo = self.send(Method::GET, "/v3/account/info", None)?;
+        Ok(FeeSchedule::from_rates(info.maker_fee_rate, info.taker_fee_rate))
+    }
+}
+
+// WsExecutionReportMessage is a struct representation of an execution report pushed on the
+// Woo X private stream.
+#[derive(Debug, Deserialize)]
+struct WsExecutionReportMessage {
+    topic: Option<String>,
+    data: Option<WsExecutionReport>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WsExecutionReport {
+    #[serde(flatten)]
+    order: WooxOrder,
+    #[serde(default)]
+    trade_id: u64,
+    #[serde(default, deserialize_with = "f64_from_string_or_number")]
+    executed_price: f64,
+    #[serde(default, deserialize_with = "f64_from_string_or_number")]
+    executed_quantity: f64,
+    #[serde(default, deserialize_with = "f64_from_string_or_number")]
+    fee: f64,
+    #[serde(default)]
+    maker: bool,
+    reason: Option<String>,
+}
+
+impl WsExecutionReport {
+    fn into_report(self) -> ExecutionReport {
+        let fill = (self.trade_id != 0 && self.executed_quantity > 0.0).then(|| ExecutionFill {
+            trade_id: self.trade_id,
+            price: Price::new(self.executed_price),
+            quantity: Qty::new(self.executed_quantity),
+            fee: Notional::new(self.fee),
+            liquidity: if self.maker { Liquidity::Maker } else { Liquidity::Taker },
+        });
+        ExecutionReport { order: self.order.into_order(self.reason), fill }
+    }
+}
+
+// connect_execution_reports logs in to the Woo X private stream at ws_url on a new thread,
+// connecting as the FeedConfig's connections are, and returns a receiver of the account's
+// execution reports. The receiver disconnects when the stream does, or once it has been
+// silent for PRIVATE_STREAM_TIMEOUT, which is what a dead man's switch watches for.
+pub fn connect_execution_reports(ws_url: &str, credentials: ApiCredentials, config: &FeedConfig) -> Receiver<ExecutionReport> {
+    let (tx, rx) = mpsc::channel();
+    let ws_url = ws_url.to_string();
+    let (proxy, tls) = (config.proxy.clone(), config.tls.clone());
+    thread::spawn(move || {
+        let _span = info_span!("connection", exchange = "woox", topic = EXECUTION_REPORT_TOPIC).entered();
+        match feed::connect_websocket(ws_url.as_str(), proxy.as_ref(), tls.as_ref()) {
+            Ok(mut socket) => stream_execution_reports(&mut socket, &credentials, PRIVATE_STREAM_TIMEOUT, &tx),
+            Err(e) => warn!(error = %e, "Failed to connect to the private stream"),
+        }
+    });
+    rx
+}
+
+// stream_execution_reports logs in to the private stream on socket and sends the execution
+// reports it pushes on tx, until the stream ends, tx disconnects, or no frame, not even a
+// ping, arrives within timeout.
+fn stream_execution_reports<T: WsTransport + ?Sized>(socket: &mut T, credentials: &ApiCredentials, timeout: Duration, tx: &Sender<ExecutionReport>) {
+    // A connection that dropped without closing, e.g. behind a NAT that forgot it, would
+    // otherwise block the read forever and never end the stream.
+    if let Err(e) = socket.set_read_timeout(Some(timeout)) {
+        return warn!(error = %e, "Failed to set the private stream read timeout");
+    }
+    let timestamp = now_ms();
+    let login = json!({
+        "id": CLIENT_ID,
+        "cmd": "LOGIN",
+        "params": {
+            "apiKey": credentials.api_key,
+            "signature": credentials.sign(&timestamp.to_string()),
+            "timestamp": timestamp,
+        }
+    });
+    // The private stream pings and subscribes like the public one.
+    let exchange = WooxExchange::default();
+    let subscribe = exchange.subscribe(EXECUTION_REPORT_TOPIC);
+    for command in [login.to_string(), subscribe] {
+        if let Err(e) = socket.send(Message::Text(command)) {
+            return warn!(error = %e, "Failed to log in to the private stream");
+        }
+    }
+    info!("Connected to the private stream");
+
+    loop {
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(_) => continue,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                return warn!(timeout = ?timeout, "Private stream went silent");
+            }
+            Err(e) => return warn!(error = %e, "Private stream read error"),
+        };
+        match exchange.frame(&text) {
+            Frame::Ping { reply, .. } => {
+                if let Err(e) = socket.send(Message::Text(reply)) {
+                    return warn!(error = %e, "Failed to send pong");
+                }
+                continue;
+            }
+            // A rejected login leaves nothing to stream, and ends the stream.
+            Frame::Error(error) if error.is_fatal() => return warn!(error = %error, "Private stream command rejected"),
+            Frame::Error(error) => {
+                warn!(error = %error, "Private stream command rejected");
+                continue;
+            }
+            Frame::Ack { .. } | Frame::Control => continue,
+            Frame::Data => {}
+        }
+        match serde_json::from_str::<WsExecutionReportMessage>(&text) {
+            Ok(WsExecutionReportMessage { topic: Some(topic), data: Some(report) }) if topic == EXECUTION_REPORT_TOPIC => {
+                if tx.send(report.into_report()).is_err() {
+                    return;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!(error = %e, data = text, "Failed to parse message"),
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    use super::*;
+    use crate::deadman::{DeadManConfig, DeadManSwitch};
+    use crate::risk::KillSwitch;
+
+    // SilentTransport reads its frames in order and then goes silent, each read waiting out
+    // the read timeout as a connection dropped without closing would.
+    #[derive(Default)]
+    struct SilentTransport {
+        frames: VecDeque<String>,
+        read_timeout: Option<Duration>,
+        sent: Vec<String>,
+    }
+
+    impl WsTransport for SilentTransport {
+        fn read(&mut self) -> io::Result<Message> {
+            if let Some(text) = self.frames.pop_front() {
+                return Ok(Message::Text(text));
+            }
+            match self.read_timeout {
+                Some(timeout) => {
+                    thread::sleep(timeout);
+                    Err(io::ErrorKind::WouldBlock.into())
+                }
+                None => loop {
+                    thread::park();
+                },
+            }
+        }
+
+        fn send(&mut self, message: Message) -> io::Result<()> {
+            if let Message::Text(text) = message {
+                self.sent.push(text);
+            }
+            Ok(())
+        }
+
+        fn set_nonblocking(&mut self, _nonblocking: bool) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+            self.read_timeout = timeout;
+            Ok(())
+        }
+    }
+
+    // CancelCounter counts the cancel_all calls made on it.
+    #[derive(Default)]
+    struct CancelCounter {
+        cancels: AtomicUsize,
+    }
+
+    impl TradingClient for CancelCounter {
+        fn place_order(&self, _request: &OrderRequest, _client_order_id: u64) -> Result<OrderId, RestError> {
+            Err(RestError::Api("not supported".to_string()))
+        }
+
+        fn cancel_order(&self, _symbol: &str, _order_id: OrderId) -> Result<(), RestError> {
+            Err(RestError::Api("not supported".to_string()))
+        }
+
+        fn cancel_all(&self, _symbol: Option<&str>) -> Result<(), RestError> {
+            self.cancels.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn order(&self, _order_id: OrderId) -> Result<ExchangeOrder, RestError> {
+            Err(RestError::Api("not supported".to_string()))
+        }
+
+        fn open_orders(&self, _symbol: Option<&str>) -> Result<Vec<ExchangeOrder>, RestError> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    // The HMAC-SHA-256 test cases of RFC 4231, the last two keyed with more than a block.
+    #[test]
+    fn hmac_sha256_matches_rfc_4231() {
+        let key_4: Vec<u8> = (0x01..=0x19).collect();
+        let cases: [(&[u8], &[u8], &str); 6] = [
+            (&[0x0b; 20], b"Hi There", "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+            (b"Jefe", b"what do ya want for nothing?", "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+            (&[0xaa; 20], &[0xdd; 50], "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe"),
+            (&key_4, &[0xcd; 50], "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b"),
+            (&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First", "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+            (
+                &[0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.",
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+        for (case, (key, data, expected)) in cases.into_iter().enumerate() {
+            assert_eq!(hex(&hmac_sha256(key, data)), expected, "case {}", case + 1);
+        }
+        // Test case 5 checks only the first 128 bits.
+        assert_eq!(hex(&hmac_sha256(&[0x0c; 20], b"Test With Truncation")[..16]), "a3b6167473100ee06e0c796c2955552b");
+    }
+
+    #[test]
+    fn signs_with_the_hex_hmac_of_the_secret() {
+        let credentials = ApiCredentials::new("key", "Jefe");
+        assert_eq!(credentials.sign("what do ya want for nothing?"), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+        assert!(!format!("{:?}", credentials).contains("Jefe"));
+    }
+
+    #[test]
+    fn silent_private_stream_fires_the_dead_mans_switch() {
+        let client = Arc::new(CancelCounter::default());
+        let switch = DeadManSwitch::new(client.clone(), DeadManConfig::default(), KillSwitch::new());
+        let (tx, rx) = mpsc::channel();
+        let reports = switch.watch(rx);
+
+        let timeout = Duration::from_millis(50);
+        let started = Instant::now();
+        let stream = thread::spawn(move || {
+            let mut socket = SilentTransport { frames: [r#"{"cmd":"PING","ts":5}"#.to_string()].into(), ..Default::default() };
+            stream_execution_reports(&mut socket, &ApiCredentials::new("key", "secret"), timeout, &tx);
+            socket
+        });
+
+        // The watched receiver disconnects only after the switch has fired.
+        assert!(reports.recv_timeout(Duration::from_secs(5)).is_err());
+        let socket = stream.join().unwrap();
+        assert!(started.elapsed() >= timeout);
+        assert_eq!(socket.read_timeout, Some(timeout));
+        // The login and subscription, then the pong to the one ping before the silence.
+        assert_eq!(socket.sent.len(), 3);
+        assert!(socket.sent[0].contains("LOGIN"));
+        assert_eq!(client.cancels.load(Ordering::SeqCst), 1);
+        assert_eq!(switch.kill_switch().tripped().as_deref(), Some("private stream disconnected"));
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tungstenite::{Message, WebSocket};
+
+use crate::exchange_api_types::RestSnapshot;
+use crate::poll::NonBlocking;
+use crate::snapshot::{SnapshotError, SnapshotSource};
+
+// WsTransport is an open websocket the feed reads frames from and sends its subscription and
+// pongs on. It is implemented by tungstenite's websockets, and by ScriptedTransport to drive
+// the feed with a fixed sequence of frames. Reads and sends on a non-blocking transport fail
+// with WouldBlock rather than waiting.
+pub trait WsTransport {
+    fn read(&mut self) -> io::Result<Message>;
+
+    fn send(&mut self, message: Message) -> io::Result<()>;
+
+    // set_nonblocking switches reads to failing with WouldBlock rather than waiting for a
+    // frame, for busy polling.
+    fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()>;
+
+    // set_read_timeout has blocking reads fail with WouldBlock or TimedOut once they have
+    // waited timeout for a frame, for sending heartbeats on a quiet connection.
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl<S: Read + Write + NonBlocking> WsTransport for WebSocket<S> {
+    fn read(&mut self) -> io::Result<Message> {
+        WebSocket::read(self).map_err(into_io)
+    }
+
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        WebSocket::send(self, message).map_err(into_io)
+    }
+
+    fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        self.get_ref().set_nonblocking(nonblocking)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_read_timeout(timeout)
+    }
+}
+
+// into_io returns a websocket error as the io error it wraps, so WouldBlock can be told
+// apart, or as an other error.
+fn into_io(e: tungstenite::Error) -> io::Error {
+    match e {
+        tungstenite::Error::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+// WsConnector opens the transport of every feed connection, in place of connecting to the
+// exchange's websocket, for the Transport socket backend.
+pub trait WsConnector: fmt::Debug + Send + Sync {
+    // connect opens a transport to url, which will be subscribed to topic.
+    fn connect(&self, url: &str, topic: &str) -> io::Result<Box<dyn WsTransport + Send>>;
+}
+
+// ScriptedTransport replays a fixed sequence of text frames and then fails as a closed
+// connection would. Every frame sent on it is appended to sent.
+pub struct ScriptedTransport {
+    frames: VecDeque<String>,
+    sent: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScriptedTransport {
+    pub fn new(frames: Vec<String>) -> Self {
+        Self { frames: frames.into(), sent: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    // sent returns the frames sent on the transport so far, the subscription first.
+    pub fn sent(&self) -> Vec<String> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl WsTransport for ScriptedTransport {
+    fn read(&mut self) -> io::Result<Message> {
+        self.frames
+            .pop_front()
+            .map(Message::Text)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::ConnectionAborted, "end of script"))
+    }
+
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        if let Message::Text(text) = message {
+            self.sent.lock().unwrap().push(text);
+        }
+        Ok(())
+    }
+
+    fn set_nonblocking(&mut self, _nonblocking: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// ScriptedConnector hands out a ScriptedTransport per connection, each with the next of its
+// scripts, so a sequence of frames, a disconnect and a reconnect can be replayed exactly. Once
+// the scripts run out, connecting fails as if the exchange refused it.
+#[derive(Default)]
+pub struct ScriptedConnector {
+    scripts: Mutex<VecDeque<Vec<String>>>,
+    sent: Arc<Mutex<Vec<String>>>,
+}
+
+impl fmt::Debug for ScriptedConnector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptedConnector").field("scripts", &self.scripts.lock().unwrap().len()).finish()
+    }
+}
+
+impl ScriptedConnector {
+    pub fn new(scripts: Vec<Vec<String>>) -> Self {
+        Self { scripts: Mutex::new(scripts.into()), sent: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    // with_script adds a connection replaying frames after those already scripted.
+    pub fn with_script<T: Into<String>>(self, frames: impl IntoIterator<Item = T>) -> Self {
+        self.scripts.lock().unwrap().push_back(frames.into_iter().map(Into::into).collect());
+        self
+    }
+
+    // sent returns the frames sent on every connection so far, in order.
+    pub fn sent(&self) -> Vec<String> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+impl WsConnector for ScriptedConnector {
+    fn connect(&self, _url: &str, _topic: &str) -> io::Result<Box<dyn WsTransport + Send>> {
+        let frames = self
+            .scripts
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::ConnectionRefused, "no scripted connections left"))?;
+        Ok(Box::new(ScriptedTransport { frames: frames.into(), sent: Arc::clone(&self.sent) }))
+    }
+}
+
+// ScriptedSource is a snapshot source returning each of its snapshots once, in order, in
+// place of fetching them, then failing.
+pub struct ScriptedSource {
+    snapshots: Mutex<VecDeque<RestSnapshot>>,
+}
+
+impl ScriptedSource {
+    pub fn new(snapshots: Vec<RestSnapshot>) -> Self {
+        Self { snapshots: Mutex::new(snapshots.into()) }
+    }
+}
+
+impl SnapshotSource for ScriptedSource {
+    fn name(&self) -> &str {
+        "scripted"
+    }
+
+    fn fetch(&self, _symbol: &str, _max_level: usize) -> Result<RestSnapshot, SnapshotError> {
+        self.snapshots
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| SnapshotError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "no scripted snapshots left")))
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Cell, List, ListItem, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::exchange_api_types::{Side, WsTrade};
+use crate::feed::{self, FeedConfig};
+use crate::render::{self, Precision, TradeTape};
+use crate::snapshot::SnapshotSource;
+use crate::standby::{PollError, WarmBook};
+use crate::units::{Price, Qty};
+
+const MAX_TRADES: usize = 200;
+const MIN_DEPTH: usize = 1;
+const MAX_DEPTH: usize = 100;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// SUBSCRIPTION_DEPTHS are the order book depths that can be subscribed to.
+const SUBSCRIPTION_DEPTHS: [usize; 2] = [50, 100];
+
+// TuiConfig configures the full-screen terminal UI.
+pub struct TuiConfig {
+    pub feed: FeedConfig,
+    // symbols are the symbols that can be cycled through, starting with the first.
+    pub symbols: Vec<String>,
+    // max_level is the initial subscribed and snapshot depth.
+    pub max_level: usize,
+    // depth is the initial number of ladder levels shown on each side.
+    pub depth: usize,
+    // precisions are the per symbol formatting precisions, symbols without one use the
+    // default.
+    pub precisions: HashMap<String, Precision>,
+    // large_trade_size flags the trades of at least this size on the tape.
+    pub large_trade_size: Option<Qty>,
+}
+
+// Status is the connection and sync status of the current symbol.
+enum Status {
+    Buffering,
+    Synced,
+    Resyncing,
+    Error(String),
+}
+
+impl Status {
+    fn label(&self) -> (String, Color) {
+        match self {
+            Status::Buffering => ("BUFFERING".to_string(), Color::Yellow),
+            Status::Synced => ("SYNCED".to_string(), Color::Green),
+            Status::Resyncing => ("OUT OF SYNC, RESYNCING".to_string(), Color::Red),
+            Status::Error(e) => (format!("ERROR: {}", e), Color::Red),
+        }
+    }
+}
+
+// Session holds the feed connections and book for the symbol being displayed.
+struct Session {
+    symbol: String,
+    precision: Precision,
+    book: WarmBook,
+    trades: Receiver<WsTrade>,
+    status: Status,
+    tape: TradeTape,
+    updates: u64,
+}
+
+impl Session {
+    fn start(symbol: &str, max_level: usize, config: &TuiConfig, source: &Arc<dyn SnapshotSource>) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            precision: config.precisions.get(symbol).copied().unwrap_or_default(),
+            book: WarmBook::start(&config.feed, symbol, max_level, Arc::clone(source)),
+            trades: feed::connect_trades(&config.feed, symbol),
+            status: Status::Buffering,
+            tape: TradeTape::new(MAX_TRADES, config.large_trade_size),
+            updates: 0,
+        }
+    }
+
+    // poll consumes everything available on the session's channels, resyncing the book if
+    // it fell out of sync.
+    fn poll(&mut self) {
+        for trade in self.trades.try_iter() {
+            self.tape.push(trade);
+        }
+
+        match self.book.poll() {
+            Ok(poll) => {
+                self.updates += poll.applied as u64;
+                if self.book.active().is_synced() {
+                    self.status = Status::Synced;
+                }
+            }
+            Err(PollError::OutOfSync) => {
+                self.book.restart();
+                self.status = Status::Resyncing;
+            }
+            Err(e) => self.status = Status::Error(e.to_string()),
+        }
+        if let Some(e) = self.book.take_standby_error() {
+            self.status = Status::Error(format!("depth change failed: {}", e));
+        }
+    }
+}
+
+// App is the state of the terminal UI.
+struct App {
+    config: TuiConfig,
+    source: Arc<dyn SnapshotSource>,
+    symbol_index: usize,
+    depth: usize,
+    session: Session,
+}
+
+impl App {
+    fn new(config: TuiConfig, source: Arc<dyn SnapshotSource>) -> Self {
+        let session = Session::start(&config.symbols[0], config.max_level, &config, &source);
+        let depth = config.depth.clamp(MIN_DEPTH, MAX_DEPTH);
+        Self { config, source, symbol_index: 0, depth, session }
+    }
+
+    fn restart(&mut self) {
+        let symbol = self.config.symbols[self.symbol_index].clone();
+        let max_level = self.session.book.active().max_level();
+        self.session = Session::start(&symbol, max_level, &self.config, &self.source);
+    }
+
+    // step_subscription requests the next larger or smaller subscribed depth, which is
+    // built on a standby feed and swapped in once synced.
+    fn step_subscription(&mut self, deeper: bool) {
+        let current = self.session.book.pending_depth().unwrap_or(self.session.book.active().max_level());
+        let index = SUBSCRIPTION_DEPTHS.iter().position(|&depth| depth == current).unwrap_or(0);
+        let index = match deeper {
+            true => (index + 1).min(SUBSCRIPTION_DEPTHS.len() - 1),
+            false => index.saturating_sub(1),
+        };
+        self.session.book.change_depth(SUBSCRIPTION_DEPTHS[index]);
+    }
+
+    fn switch_symbol(&mut self, forward: bool) {
+        let count = self.config.symbols.len();
+        self.symbol_index = if forward {
+            (self.symbol_index + 1) % count
+        } else {
+            (self.symbol_index + count - 1) % count
+        };
+        self.restart();
+    }
+
+    // handle_key applies a key press and returns false if the UI should exit.
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Right | KeyCode::Tab => self.switch_symbol(true),
+            KeyCode::Left | KeyCode::BackTab => self.switch_symbol(false),
+            KeyCode::Up | KeyCode::Char('+') => self.depth = (self.depth + 1).min(MAX_DEPTH),
+            KeyCode::Down | KeyCode::Char('-') => self.depth = self.depth.saturating_sub(1).max(MIN_DEPTH),
+            KeyCode::Char(']') => self.step_subscription(true),
+            KeyCode::Char('[') => self.step_subscription(false),
+            KeyCode::Char('r') => self.restart(),
+            _ => {}
+        }
+        true
+    }
+}
+
+// run starts the full-screen terminal UI and blocks until the user quits.
+pub fn run(config: TuiConfig, source: Arc<dyn SnapshotSource>) -> io::Result<()> {
+    assert!(!config.symbols.is_empty(), "TuiConfig requires at least one symbol");
+
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, App::new(config, source));
+    ratatui::restore();
+    result
+}
+
+fn run_app(terminal: &mut DefaultTerminal, mut app: App) -> io::Result<()> {
+    loop {
+        app.session.poll();
+
+        terminal.draw(|frame| draw(frame, &app))?;
+
+        if event::poll(POLL_INTERVAL)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.handle_key(key.code) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [main, status] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [ladder, trades] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+
+    draw_ladder(frame, app, ladder);
+    draw_trades(frame, app, trades);
+    draw_status(frame, app, status);
+}
+
+// draw_ladder draws the asks above the bids with cumulative sizes and the spread between them.
+fn draw_ladder(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(format!(" {} ", app.session.symbol));
+    let Some(book) = app.session.book.active().book() else {
+        frame.render_widget(Paragraph::new("Waiting for snapshot...").block(block), area);
+        return;
+    };
+
+    let precision = app.session.precision;
+    let level_row = |price: Price, quantity: Qty, cumulative: Qty, color: Color| {
+        Row::new(vec![
+            Cell::from(precision.format_price(price)),
+            Cell::from(precision.format_quantity(quantity)),
+            Cell::from(precision.format_quantity(cumulative)),
+        ])
+        .style(Style::default().fg(color))
+    };
+
+    let mut cumulative = Qty::ZERO;
+    let mut asks: Vec<Row> = book
+        .asks()
+        .take(app.depth)
+        .map(|(price, quantity)| {
+            cumulative += quantity;
+            level_row(price, quantity, cumulative, Color::Red)
+        })
+        .collect();
+    asks.reverse();
+
+    let spread = match (book.best_bid(), book.best_ask()) {
+        (Some((bid, _)), Some((ask, _))) => format!("spread {}", precision.format_price(ask - bid)),
+        _ => "spread -".to_string(),
+    };
+    let spread_row = Row::new(vec![Cell::from(spread), Cell::from(""), Cell::from("")])
+        .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD | Modifier::REVERSED));
+
+    let mut cumulative = Qty::ZERO;
+    let bids = book.bids().take(app.depth).map(|(price, quantity)| {
+        cumulative += quantity;
+        level_row(price, quantity, cumulative, Color::Green)
+    });
+
+    let rows: Vec<Row> = asks.into_iter().chain(std::iter::once(spread_row)).chain(bids).collect();
+    let widths = [Constraint::Percentage(34), Constraint::Percentage(33), Constraint::Percentage(33)];
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["Price", "Size", "Total"]).style(Style::default().add_modifier(Modifier::BOLD)))
+        .block(block);
+    frame.render_widget(table, area);
+}
+
+// draw_trades draws the most recent public trades, newest first. Large prints are bold and
+// marked with a *.
+fn draw_trades(frame: &mut Frame, app: &App, area: Rect) {
+    let visible = area.height.saturating_sub(2) as usize;
+    let precision = app.session.precision;
+    let tape = &app.session.tape;
+    let items: Vec<ListItem> = tape
+        .trades()
+        .take(visible)
+        .map(|trade| {
+            let color = match trade.side {
+                Side::Buy => Color::Green,
+                Side::Sell => Color::Red,
+            };
+            let large = tape.is_large(trade);
+            let item = ListItem::new(Line::from(vec![
+                Span::raw(format!("{} ", render::format_time(trade.ts))),
+                Span::styled(format!("{:<4} ", format!("{:?}", trade.side).to_uppercase()), Style::default().fg(color)),
+                Span::raw(format!("{} ", precision.format_price(Price::new(trade.price)))),
+                Span::raw(precision.format_quantity(Qty::new(trade.quantity))),
+                Span::raw(if large { " *" } else { "" }),
+            ]));
+            match large {
+                true => item.style(Style::default().add_modifier(Modifier::BOLD)),
+                false => item,
+            }
+        })
+        .collect();
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(" Trades "));
+    frame.render_widget(list, area);
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let (label, color) = app.session.status.label();
+    let subscribed = match app.session.book.pending_depth() {
+        Some(pending) => format!("{} -> {}", app.session.book.active().max_level(), pending),
+        None => app.session.book.active().max_level().to_string(),
+    };
+    let line = Line::from(vec![
+        Span::styled(format!(" {} ", label), Style::default().fg(Color::Black).bg(color)),
+        Span::raw(format!(
+            " {} | depth {} | subscribed {} | updates {} | <-/-> symbol  +/- depth  [/] subscription  r resync  q quit",
+            app.session.symbol, app.depth, subscribed, app.session.updates
+        )),
+    ]);
+    frame.render_widget(Paragraph::new(line), area);
+}
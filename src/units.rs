@@ -0,0 +1,318 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+// unit defines a newtype over f64 for a single kind of value. Units are totally ordered
+// (so they can key a BTreeMap), only add to and subtract from the same unit, and format
+// like the f64 they wrap, so "{:.2}" works as it would on the raw value. They serialize
+// as the bare f64 too.
+macro_rules! unit {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(f64);
+
+        impl $name {
+            pub const ZERO: $name = $name(0.0);
+
+            pub const fn new(value: f64) -> Self {
+                Self(value)
+            }
+
+            // value returns the raw f64, for formats and math the unit types don't cover.
+            pub const fn value(self) -> f64 {
+                self.0
+            }
+
+            pub fn is_zero(self) -> bool {
+                self.0 == 0.0
+            }
+
+            pub fn is_finite(self) -> bool {
+                self.0.is_finite()
+            }
+
+            // checked_add returns self + other, or None if the result is not finite.
+            pub fn checked_add(self, other: Self) -> Option<Self> {
+                Self::finite(self.0 + other.0)
+            }
+
+            // checked_sub returns self - other, or None if the result is not finite.
+            pub fn checked_sub(self, other: Self) -> Option<Self> {
+                Self::finite(self.0 - other.0)
+            }
+
+            // checked_scale returns self * factor, or None if the result is not finite.
+            pub fn checked_scale(self, factor: f64) -> Option<Self> {
+                Self::finite(self.0 * factor)
+            }
+
+            pub fn max(self, other: Self) -> Self {
+                Self(self.0.max(other.0))
+            }
+
+            pub fn min(self, other: Self) -> Self {
+                Self(self.0.min(other.0))
+            }
+
+            fn finite(value: f64) -> Option<Self> {
+                value.is_finite().then_some(Self(value))
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.total_cmp(&other.0)
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                Self(self.0 + other.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) {
+                self.0 += other.0;
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                Self(self.0 - other.0)
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                Self(iter.map(|unit| unit.0).sum())
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+    };
+}
+
+unit!(
+    // Price is a price in quote currency per unit of base currency.
+    Price
+);
+
+unit!(
+    // Qty is an amount of base currency.
+    Qty
+);
+
+unit!(
+    // Notional is an amount of quote currency, the value of a quantity at a price.
+    Notional
+);
+
+impl Price {
+    // midpoint returns the price halfway between self and other.
+    pub fn midpoint(self, other: Price) -> Price {
+        Price((self.0 + other.0) / 2.0)
+    }
+
+    // bps_of returns self as basis points of reference, e.g. a spread as bps of the mid.
+    pub fn bps_of(self, reference: Price) -> f64 {
+        self.0 / reference.0 * 10_000.0
+    }
+
+    // checked_notional returns the value of quantity at this price, or None if it is not finite.
+    pub fn checked_notional(self, quantity: Qty) -> Option<Notional> {
+        Notional::finite(self.0 * quantity.0)
+    }
+
+    // from_ticks converts a number of ticks of tick_size into a price.
+    pub fn from_ticks(ticks: i64, tick_size: f64) -> Price {
+        Price(from_increments(ticks, tick_size))
+    }
+
+    // to_ticks returns the nearest number of ticks of tick_size, or None if tick_size is
+    // not a positive finite size.
+    pub fn to_ticks(self, tick_size: f64) -> Option<i64> {
+        to_increments(self.0, tick_size)
+    }
+
+    // floor_ticks returns the number of ticks of tick_size at or below the price, a price
+    // already on a tick being that tick, or None if tick_size is not a positive finite size.
+    pub fn floor_ticks(self, tick_size: f64) -> Option<i64> {
+        round_increments(self.0, tick_size, f64::floor)
+    }
+
+    // ceil_ticks returns the number of ticks of tick_size at or above the price.
+    pub fn ceil_ticks(self, tick_size: f64) -> Option<i64> {
+        round_increments(self.0, tick_size, f64::ceil)
+    }
+
+    // floor_to rounds the price down to a multiple of tick_size, as a bid would be. A price
+    // already on a tick is kept, and so is any price if tick_size isn't a positive finite size.
+    pub fn floor_to(self, tick_size: f64) -> Price {
+        self.floor_ticks(tick_size).map_or(self, |ticks| Price::from_ticks(ticks, tick_size))
+    }
+
+    // ceil_to rounds the price up to a multiple of tick_size, as an ask would be.
+    pub fn ceil_to(self, tick_size: f64) -> Price {
+        self.ceil_ticks(tick_size).map_or(self, |ticks| Price::from_ticks(ticks, tick_size))
+    }
+}
+
+impl Qty {
+    // from_lots converts a number of lots of lot_size into a quantity.
+    pub fn from_lots(lots: i64, lot_size: f64) -> Qty {
+        Qty(from_increments(lots, lot_size))
+    }
+
+    // to_lots returns the nearest number of lots of lot_size, or None if lot_size is not a
+    // positive finite size.
+    pub fn to_lots(self, lot_size: f64) -> Option<i64> {
+        to_increments(self.0, lot_size)
+    }
+
+    // floor_to rounds the quantity down to a multiple of lot_size, so it never exceeds the
+    // original amount. A quantity already a multiple is kept, and so is any quantity if
+    // lot_size isn't a positive finite size.
+    pub fn floor_to(self, lot_size: f64) -> Qty {
+        round_increments(self.0, lot_size, f64::floor).map_or(self, |lots| Qty::from_lots(lots, lot_size))
+    }
+
+    // ratio returns self as a fraction of total, or 0 if total is zero.
+    pub fn ratio(self, total: Qty) -> f64 {
+        if total.is_zero() { 0.0 } else { self.0 / total.0 }
+    }
+}
+
+impl Notional {
+    // checked_price returns the price quantity was traded at for this notional, or None if
+    // quantity is zero.
+    pub fn checked_price(self, quantity: Qty) -> Option<Price> {
+        Price::finite(self.0 / quantity.0)
+    }
+
+    // checked_qty returns the quantity this notional buys at price, or None if price is zero.
+    pub fn checked_qty(self, price: Price) -> Option<Qty> {
+        Qty::finite(self.0 / price.0)
+    }
+}
+
+impl Mul<Qty> for Price {
+    type Output = Notional;
+
+    fn mul(self, quantity: Qty) -> Notional {
+        Notional(self.0 * quantity.0)
+    }
+}
+
+impl Mul<Price> for Qty {
+    type Output = Notional;
+
+    fn mul(self, price: Price) -> Notional {
+        Notional(self.0 * price.0)
+    }
+}
+
+// INCREMENT_EPSILON is how far, in increments, a value may be off a multiple of its increment
+// and still be taken as on it, allowing for the rounding error of dividing by an increment
+// like 0.1 that f64 can't represent: 2.3 / 0.1 is 22.999999999999996.
+const INCREMENT_EPSILON: f64 = 1e-9;
+
+fn to_increments(value: f64, increment: f64) -> Option<i64> {
+    round_increments(value, increment, f64::round)
+}
+
+// round_increments returns the number of increments in value, rounded with round unless it
+// is within INCREMENT_EPSILON of a whole number, which it is taken as.
+fn round_increments(value: f64, increment: f64, round: fn(f64) -> f64) -> Option<i64> {
+    if !(increment > 0.0 && increment.is_finite()) {
+        return None;
+    }
+    let increments = value / increment;
+    let nearest = increments.round();
+    let increments = if (increments - nearest).abs() <= INCREMENT_EPSILON { nearest } else { round(increments) };
+    increments.is_finite().then_some(increments as i64)
+}
+
+// from_increments returns increments multiples of increment. Increments that divide a unit
+// are divided into it, so 24 ticks of 0.1 are 2.4 rather than 2.4000000000000004.
+fn from_increments(increments: i64, increment: f64) -> f64 {
+    let per_unit = 1.0 / increment;
+    if per_unit >= 1.0 && (per_unit - per_unit.round()).abs() <= INCREMENT_EPSILON * per_unit {
+        return increments as f64 / per_unit.round();
+    }
+    increments as f64 * increment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn floor_and_ceil_keep_values_on_a_tick() {
+        assert_eq!(Price::new(2.3).floor_to(0.1).value(), 2.3);
+        assert_eq!(Price::new(2.4).ceil_to(0.1).value(), 2.4);
+        assert_eq!(Price::new(100.1).floor_to(0.1).value(), 100.1);
+        assert_eq!(Qty::new(0.3).floor_to(0.1).value(), 0.3);
+        assert_eq!(Qty::new(0.1 + 0.2).floor_to(0.1).value(), 0.3);
+    }
+
+    #[test]
+    fn floor_and_ceil_round_values_between_ticks() {
+        assert_eq!(Price::new(2.35).floor_to(0.1).value(), 2.3);
+        assert_eq!(Price::new(2.35).ceil_to(0.1).value(), 2.4);
+        assert_eq!(Price::new(101.26).floor_to(0.25).value(), 101.25);
+        assert_eq!(Price::new(101.26).ceil_to(0.25).value(), 101.5);
+        assert_eq!(Price::new(12.0).ceil_to(5.0).value(), 15.0);
+        assert_eq!(Qty::new(0.0019).floor_to(0.001).value(), 0.001);
+        assert_eq!(Price::new(-2.35).floor_to(0.1).value(), -2.4);
+    }
+
+    #[test]
+    fn invalid_increments_leave_values_as_they_are() {
+        assert_eq!(Price::new(2.35).floor_to(0.0).value(), 2.35);
+        assert_eq!(Price::new(2.35).ceil_to(f64::NAN).value(), 2.35);
+        assert_eq!(Qty::new(1.5).floor_to(-1.0).value(), 1.5);
+        assert_eq!(Price::new(2.35).to_ticks(0.0), None);
+    }
+
+    #[test]
+    fn ticks_convert_both_ways() {
+        assert_eq!(Price::new(2.3).to_ticks(0.1), Some(23));
+        assert_eq!(Price::from_ticks(24, 0.1).value(), 2.4);
+        assert_eq!(Price::from_ticks(3, 5.0).value(), 15.0);
+        assert_eq!(Qty::from_lots(7, 0.001).value(), 0.007);
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::fd::AsRawFd;
+use std::sync::Arc;
+use std::time::Duration;
+
+use io_uring::{opcode, squeue, types, IoUring};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Connector, WebSocket};
+
+use crate::poll::NonBlocking;
+use crate::feed::host_port;
+use crate::proxy::ProxyConfig;
+
+const RING_ENTRIES: u32 = 8;
+
+// UringStream is a TCP stream whose reads and writes are submitted through io_uring instead
+// of read(2)/write(2). With sqpoll enabled the kernel polls the submission queue itself, so
+// submitting a receive doesn't need a syscall of its own at high message rates.
+pub struct UringStream {
+    stream: TcpStream,
+    ring: IoUring,
+}
+
+impl UringStream {
+    // connect opens a TCP connection to addr. sqpoll_idle_ms enables a kernel submission
+    // polling thread that sleeps after being idle for that long.
+    pub fn connect<A: ToSocketAddrs>(addr: A, sqpoll_idle_ms: Option<u32>) -> io::Result<Self> {
+        Self::new(TcpStream::connect(addr)?, sqpoll_idle_ms)
+    }
+
+    // new submits the reads and writes of an open connection through io_uring.
+    pub fn new(stream: TcpStream, sqpoll_idle_ms: Option<u32>) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+
+        let mut builder = IoUring::builder();
+        if let Some(idle) = sqpoll_idle_ms {
+            builder.setup_sqpoll(idle);
+        }
+        let ring = builder.build(RING_ENTRIES)?;
+
+        Ok(Self { stream, ring })
+    }
+
+    // complete submits entry and waits for its completion, returning the operation's result.
+    // Callers must keep any buffer referenced by entry alive until complete returns.
+    fn complete(&mut self, entry: squeue::Entry) -> io::Result<usize> {
+        // SAFETY: the entry only references buffers the caller keeps alive, and we wait for
+        // its completion below before returning.
+        unsafe {
+            self.ring
+                .submission()
+                .push(&entry)
+                .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+        }
+
+        loop {
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let cqe = self
+            .ring
+            .completion()
+            .next()
+            .ok_or_else(|| io::Error::other("io_uring completion queue is empty"))?;
+        match cqe.result() {
+            res if res < 0 => Err(io::Error::from_raw_os_error(-res)),
+            res => Ok(res as usize),
+        }
+    }
+}
+
+impl NonBlocking for UringStream {
+    // set_nonblocking makes receives complete with WouldBlock instead of waiting for data.
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.stream.set_nonblocking(nonblocking)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)
+    }
+}
+
+impl Read for UringStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let fd = types::Fd(self.stream.as_raw_fd());
+        let entry = opcode::Recv::new(fd, buf.as_mut_ptr(), buf.len() as u32).build();
+        self.complete(entry)
+    }
+}
+
+impl Write for UringStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let fd = types::Fd(self.stream.as_raw_fd());
+        let entry = opcode::Send::new(fd, buf.as_ptr(), buf.len() as u32).build();
+        self.complete(entry)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// connect_websocket opens a websocket to url over a UringStream, negotiating TLS for wss urls
+// with tls or the system's roots, through proxy unless it bypasses url's host.
+pub fn connect_websocket(
+    url: &str,
+    proxy: Option<&ProxyConfig>,
+    tls: Option<&Arc<rustls::ClientConfig>>,
+    sqpoll_idle_ms: Option<u32>,
+) -> io::Result<WebSocket<MaybeTlsStream<UringStream>>> {
+    let (host, port) = host_port(url)?;
+    let stream = match proxy.filter(|proxy| !proxy.bypasses(&host)) {
+        Some(proxy) => UringStream::new(proxy.tunnel(&host, port)?, sqpoll_idle_ms)?,
+        None => UringStream::connect((host.as_str(), port), sqpoll_idle_ms)?,
+    };
+    let connector = tls.map(|tls| Connector::Rustls(Arc::clone(tls)));
+    let (socket, _) = tungstenite::client_tls_with_config(url, stream, None, connector).map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(socket)
+}
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::exchange_api_types::{Side, WsTrade};
+use crate::units::{Notional, Price, Qty};
+
+// DEFAULT_VALUE_AREA is the share of the volume the value area usually covers.
+pub const DEFAULT_VALUE_AREA: f64 = 0.7;
+
+const CSV_HEADER: &str = "price,volume,buy_volume,sell_volume";
+
+// ProfileLevel is the volume traded in one price bucket, starting at price, split by the
+// aggressor's side.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ProfileLevel {
+    pub price: Price,
+    pub volume: Qty,
+    pub buy_volume: Qty,
+    pub sell_volume: Qty,
+}
+
+// ValueArea is the range of buckets around the point of control, the bucket with the most
+// volume, holding a share of the volume traded. low and high are the lowest and highest
+// buckets in it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ValueArea {
+    pub poc: Price,
+    pub low: Price,
+    pub high: Price,
+    pub volume: Qty,
+}
+
+// ProfileSnapshot is a VolumeProfile at a point in time, for export.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProfileSnapshot {
+    pub symbol: String,
+    pub bucket_size: f64,
+    pub volume: Qty,
+    pub trades: u64,
+    pub vwap: Option<Price>,
+    pub value_area: Option<ValueArea>,
+    pub levels: Vec<ProfileLevel>,
+}
+
+// VolumeProfile is the volume traded at each price over a session, from the trade stream,
+// in buckets of bucket_size. It is a benchmark for execution, through the session VWAP, and
+// what volume profile charts are drawn from.
+#[derive(Debug, Clone)]
+pub struct VolumeProfile {
+    symbol: String,
+    bucket_size: f64,
+    // buckets maps the index of each bucket traded in, its price over bucket_size rounded
+    // down, to its buy and sell volume.
+    buckets: BTreeMap<i64, (Qty, Qty)>,
+    volume: Qty,
+    notional: Notional,
+    trades: u64,
+}
+
+impl VolumeProfile {
+    // new profiles symbol in buckets of bucket_size, e.g. the tick size or a multiple of it.
+    pub fn new(symbol: &str, bucket_size: f64) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            bucket_size,
+            buckets: BTreeMap::new(),
+            volume: Qty::ZERO,
+            notional: Notional::ZERO,
+            trades: 0,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn bucket_size(&self) -> f64 {
+        self.bucket_size
+    }
+
+    // reset clears the profile for a new session.
+    pub fn reset(&mut self) {
+        *self = Self::new(&self.symbol, self.bucket_size);
+    }
+
+    // on_trade adds trade to the profile. Trades in other symbols are ignored.
+    pub fn on_trade(&mut self, trade: &WsTrade) {
+        if trade.symbol != self.symbol {
+            return;
+        }
+        let (price, quantity) = (Price::new(trade.price), Qty::new(trade.quantity));
+        let (buy, sell) = self.buckets.entry(self.bucket(price)).or_default();
+        match trade.side {
+            Side::Buy => *buy += quantity,
+            Side::Sell => *sell += quantity,
+        }
+        self.volume += quantity;
+        self.notional += price * quantity;
+        self.trades += 1;
+    }
+
+    pub fn volume(&self) -> Qty {
+        self.volume
+    }
+
+    pub fn trades(&self) -> u64 {
+        self.trades
+    }
+
+    // vwap returns the volume weighted average price of the session.
+    pub fn vwap(&self) -> Option<Price> {
+        self.notional.checked_price(self.volume)
+    }
+
+    // volume_at returns the volume traded in the bucket holding price.
+    pub fn volume_at(&self, price: Price) -> Qty {
+        self.buckets.get(&self.bucket(price)).map_or(Qty::ZERO, |&(buy, sell)| buy + sell)
+    }
+
+    // levels returns the buckets traded in, lowest price first.
+    pub fn levels(&self) -> Vec<ProfileLevel> {
+        self.buckets
+            .iter()
+            .map(|(&bucket, &(buy, sell))| ProfileLevel {
+                price: self.bucket_price(bucket),
+                volume: buy + sell,
+                buy_volume: buy,
+                sell_volume: sell,
+            })
+            .collect()
+    }
+
+    // poc returns the point of control, the bucket with the most volume, the lowest of those
+    // tied.
+    pub fn poc(&self) -> Option<Price> {
+        let levels = self.levels();
+        levels.get(poc_index(&levels)?).map(|level| level.price)
+    }
+
+    // value_area returns the buckets around the point of control holding share of the volume,
+    // e.g. DEFAULT_VALUE_AREA. It grows from the point of control a bucket at a time, towards
+    // whichever neighbour traded more.
+    pub fn value_area(&self, share: f64) -> Option<ValueArea> {
+        let levels = self.levels();
+        let poc = poc_index(&levels)?;
+        let target = self.volume.checked_scale(share.clamp(0.0, 1.0))?;
+        let (mut low, mut high, mut volume) = (poc, poc, levels[poc].volume);
+        while volume < target && (low > 0 || high + 1 < levels.len()) {
+            let below = low.checked_sub(1).map(|i| levels[i].volume);
+            let above = levels.get(high + 1).map(|level| level.volume);
+            match (below, above) {
+                (Some(below), Some(above)) if below > above => {
+                    low -= 1;
+                    volume += below;
+                }
+                (_, Some(above)) => {
+                    high += 1;
+                    volume += above;
+                }
+                (Some(below), None) => {
+                    low -= 1;
+                    volume += below;
+                }
+                (None, None) => break,
+            }
+        }
+        Some(ValueArea { poc: levels[poc].price, low: levels[low].price, high: levels[high].price, volume })
+    }
+
+    // snapshot returns the profile with its value area covering DEFAULT_VALUE_AREA.
+    pub fn snapshot(&self) -> ProfileSnapshot {
+        ProfileSnapshot {
+            symbol: self.symbol.clone(),
+            bucket_size: self.bucket_size,
+            volume: self.volume,
+            trades: self.trades,
+            vwap: self.vwap(),
+            value_area: self.value_area(DEFAULT_VALUE_AREA),
+            levels: self.levels(),
+        }
+    }
+
+    // write_csv writes a row per bucket traded in to writer, lowest price first.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{}", CSV_HEADER)?;
+        for level in self.levels() {
+            writeln!(writer, "{},{},{},{}", level.price, level.volume, level.buy_volume, level.sell_volume)?;
+        }
+        writer.flush()
+    }
+
+    fn bucket_price(&self, bucket: i64) -> Price {
+        bucket_price(bucket, self.bucket_size)
+    }
+
+    fn bucket(&self, price: Price) -> i64 {
+        bucket_index(price, self.bucket_size)
+    }
+}
+
+// bucket_price returns the lowest price in bucket, of bucket_size. Buckets that divide a unit
+// price are divided into it, so 1001 buckets of 0.1 are 100.1 rather than 100.10000000000001.
+pub(crate) fn bucket_price(bucket: i64, bucket_size: f64) -> Price {
+    let per_unit = 1.0 / bucket_size;
+    if per_unit >= 1.0 && (per_unit - per_unit.round()).abs() < 1e-9 {
+        return Price::new(bucket as f64 / per_unit.round());
+    }
+    Price::from_ticks(bucket, bucket_size)
+}
+
+// bucket_index returns the index of the bucket of bucket_size holding price, allowing for the
+// rounding error of a price on a bucket boundary, e.g. 100.1 in buckets of 0.1.
+pub(crate) fn bucket_index(price: Price, bucket_size: f64) -> i64 {
+    (price.value() / bucket_size + 1e-9).floor() as i64
+}
+
+// poc_index returns the index of the level with the most volume, the first of those tied.
+fn poc_index(levels: &[ProfileLevel]) -> Option<usize> {
+    levels
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by(|(_, a), (_, b)| a.volume.cmp(&b.volume))
+        .map(|(index, _)| index)
+}
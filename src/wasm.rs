@@ -0,0 +1,301 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use js_sys::{Function, JSON};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{MessageEvent, Response, WebSocket};
+
+use crate::exchange_api_types::{OrderBookDelta, RestSnapshot, WsEnvelope, WsMessage};
+use crate::orderbook::LocalOrderBook;
+use crate::units::{Price, Qty};
+
+// The Woo X endpoints and commands, as WooxExchange has them.
+const WOOX_WS_URL: &str = "wss://wss.woox.io/v3/public";
+const WOOX_REST_ORDERBOOK_URL: &str = "https://api.woox.io/v3/public/orderbook";
+const CLIENT_ID: &str = "client_id_x";
+const WOOX_SUBSCRIBE_CMD: &str = "SUBSCRIBE";
+const WOOX_PING_CMD: &str = "PING";
+const WOOX_PONG_CMD: &str = "PONG";
+
+// MAX_BUFFERED bounds the deltas buffered while the snapshot is fetched.
+const MAX_BUFFERED: usize = 10_000;
+
+// BookSample is the JSON a book snapshot is handed to JS as.
+#[derive(Serialize)]
+struct BookSample<'a> {
+    symbol: &'a str,
+    ts: u64,
+    synced: bool,
+    bids: Vec<(f64, f64)>,
+    asks: Vec<(f64, f64)>,
+}
+
+fn levels(levels: impl Iterator<Item = (Price, Qty)>, depth: usize) -> Vec<(f64, f64)> {
+    levels.take(depth).map(|(price, quantity)| (price.value(), quantity.value())).collect()
+}
+
+// FeedState is the book and its sync state, shared by the socket's callbacks and the
+// fetch of its snapshot.
+struct FeedState {
+    symbol: String,
+    max_level: usize,
+    orderbook_url: String,
+    book: LocalOrderBook,
+    // seeded is the timestamp of the last delta applied, or of the snapshot before the
+    // first, once a snapshot has been applied.
+    seeded: Option<u64>,
+    synced: bool,
+    // buffered are the deltas read while the snapshot is fetched, with their timestamps.
+    buffered: Vec<(u64, OrderBookDelta)>,
+    fetching: bool,
+    on_update: Option<Function>,
+    error: Option<String>,
+}
+
+impl FeedState {
+    // offer applies the delta at ts if it continues the book, or buffers it until the book
+    // has been seeded from a snapshot. It returns true if the book was updated, and false
+    // with the book dropped if the delta doesn't continue it, which a new snapshot fixes.
+    fn offer(&mut self, ts: u64, delta: OrderBookDelta) -> bool {
+        let Some(last) = self.seeded else {
+            if self.buffered.len() >= MAX_BUFFERED {
+                self.buffered.remove(0);
+            }
+            self.buffered.push((ts, delta));
+            return false;
+        };
+        // The deltas a snapshot already has are skipped, and the book syncs on the one
+        // continuing it, as BookSync does on Woo X.
+        if !self.synced && ts <= last {
+            return false;
+        }
+        if delta.prev_ts != last {
+            self.seeded = None;
+            self.synced = false;
+            self.buffered.push((ts, delta));
+            return false;
+        }
+        self.book.apply_delta(&delta);
+        self.book.mark_updated(ts);
+        self.seeded = Some(ts);
+        self.synced = true;
+        true
+    }
+
+    // seed resets the book to snapshot and offers it the deltas buffered meanwhile.
+    fn seed(&mut self, snapshot: RestSnapshot) -> bool {
+        self.book = LocalOrderBook::new();
+        self.book.apply_snapshot(snapshot.data);
+        self.book.mark_updated(snapshot.timestamp);
+        self.seeded = Some(snapshot.timestamp);
+        self.synced = false;
+        let mut updated = false;
+        for (ts, delta) in std::mem::take(&mut self.buffered) {
+            updated |= self.offer(ts, delta);
+        }
+        updated
+    }
+
+    fn ts(&self) -> u64 {
+        self.book.last_update_ts().unwrap_or_default()
+    }
+}
+
+// BookFeed follows the Woo X book for a symbol in the browser: it subscribes to the depth
+// stream over a WebSocket, fetches a snapshot to sync from with fetch, and applies the
+// deltas that continue it to a LocalOrderBook, the same book and parsing the native client
+// uses. A delta that doesn't continue the book resyncs it from a new snapshot. JS reads the
+// book with snapshot, optionally after each update through onUpdate.
+#[wasm_bindgen]
+pub struct BookFeed {
+    state: Rc<RefCell<FeedState>>,
+    socket: WebSocket,
+    _on_open: Closure<dyn FnMut()>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+#[wasm_bindgen]
+impl BookFeed {
+    // new connects to the ws_url websocket, fetching snapshots from the orderbook endpoint
+    // at orderbook_url, Woo X production's if left undefined, and follows max_level levels
+    // of the book for symbol.
+    #[wasm_bindgen(constructor)]
+    pub fn new(symbol: &str, max_level: usize, ws_url: Option<String>, orderbook_url: Option<String>) -> Result<BookFeed, JsValue> {
+        let socket = WebSocket::new(ws_url.as_deref().unwrap_or(WOOX_WS_URL))?;
+        let state = Rc::new(RefCell::new(FeedState {
+            symbol: symbol.to_string(),
+            max_level,
+            orderbook_url: orderbook_url.unwrap_or_else(|| WOOX_REST_ORDERBOOK_URL.to_string()),
+            book: LocalOrderBook::new(),
+            seeded: None,
+            synced: false,
+            buffered: Vec::new(),
+            fetching: false,
+            on_update: None,
+            error: None,
+        }));
+
+        let subscribe = serde_json::json!({
+            "id": CLIENT_ID,
+            "cmd": WOOX_SUBSCRIBE_CMD,
+            "params": [format!("orderbookupdate@{}@{}", symbol, max_level)]
+        })
+        .to_string();
+        let opened = socket.clone();
+        let on_open = Closure::<dyn FnMut()>::new(move || {
+            let _ = opened.send_with_str(&subscribe);
+        });
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+
+        let (shared, replies) = (Rc::clone(&state), socket.clone());
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |message: MessageEvent| {
+            if let Some(text) = message.data().as_string() {
+                on_text(&shared, &replies, &text);
+            }
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        Ok(BookFeed { state, socket, _on_open: on_open, _on_message: on_message })
+    }
+
+    // onUpdate sets callback to be called with the book's timestamp after every update.
+    #[wasm_bindgen(js_name = onUpdate)]
+    pub fn on_update(&self, callback: Function) {
+        self.state.borrow_mut().on_update = Some(callback);
+    }
+
+    // snapshot returns the top depth levels a side of the book as {symbol, ts, synced,
+    // bids, asks}, each level a [price, quantity] pair, best first.
+    pub fn snapshot(&self, depth: usize) -> Result<JsValue, JsValue> {
+        let state = self.state.borrow();
+        let sample = BookSample {
+            symbol: &state.symbol,
+            ts: state.ts(),
+            synced: state.synced,
+            bids: levels(state.book.bids(), depth),
+            asks: levels(state.book.asks(), depth),
+        };
+        let json = serde_json::to_string(&sample).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        JSON::parse(&json)
+    }
+
+    // bestBid returns the best bid as [price, quantity], or undefined while there is none.
+    #[wasm_bindgen(js_name = bestBid)]
+    pub fn best_bid(&self) -> Option<Vec<f64>> {
+        let (price, quantity) = self.state.borrow().book.best_bid()?;
+        Some(vec![price.value(), quantity.value()])
+    }
+
+    #[wasm_bindgen(js_name = bestAsk)]
+    pub fn best_ask(&self) -> Option<Vec<f64>> {
+        let (price, quantity) = self.state.borrow().book.best_ask()?;
+        Some(vec![price.value(), quantity.value()])
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn synced(&self) -> bool {
+        self.state.borrow().synced
+    }
+
+    // lastError is the last snapshot fetch error or command the exchange rejected, if any,
+    // cleared by the next sync.
+    #[wasm_bindgen(getter, js_name = lastError)]
+    pub fn last_error(&self) -> Option<String> {
+        self.state.borrow().error.clone()
+    }
+
+    // close closes the websocket, leaving the book as it last was.
+    pub fn close(&self) {
+        self.socket.set_onmessage(None);
+        let _ = self.socket.close();
+    }
+}
+
+impl Drop for BookFeed {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+// on_text handles a text frame from the websocket, answering pings and applying deltas.
+fn on_text(state: &Rc<RefCell<FeedState>>, socket: &WebSocket, text: &str) {
+    let Ok(envelope) = serde_json::from_str::<WsEnvelope>(text) else { return };
+    // Only stream data has a topic. Of the rest, pings are answered and a rejected command,
+    // such as a subscription to an unknown symbol, is kept as the error.
+    if envelope.topic.is_none() {
+        if envelope.cmd.as_deref() == Some(WOOX_PING_CMD) {
+            let pong = serde_json::json!({ "cmd": WOOX_PONG_CMD, "ts": js_sys::Date::now() as u64 }).to_string();
+            let _ = socket.send_with_str(&pong);
+        } else if envelope.success == Some(false) {
+            state.borrow_mut().error = Some(envelope.message.map_or_else(|| text.to_string(), |message| message.into_owned()));
+        }
+        return;
+    }
+    let Ok(WsMessage { ts, data: Some(delta) }) = serde_json::from_str::<WsMessage>(text) else { return };
+    let (updated, fetch) = {
+        let mut state = state.borrow_mut();
+        let updated = state.offer(ts, delta);
+        let fetch = state.seeded.is_none() && !state.fetching;
+        state.fetching |= fetch;
+        (updated, fetch)
+    };
+    if updated {
+        notify(state);
+    }
+    if fetch {
+        wasm_bindgen_futures::spawn_local(fetch_snapshot(Rc::clone(state)));
+    }
+}
+
+// fetch_snapshot fetches a snapshot and seeds the book from it. A failed fetch is retried
+// with the next delta.
+async fn fetch_snapshot(state: Rc<RefCell<FeedState>>) {
+    let url = {
+        let state = state.borrow();
+        format!("{}?symbol={}&maxLevel={}", state.orderbook_url, state.symbol, state.max_level)
+    };
+    let fetched = fetch_text(&url).await.and_then(|text| serde_json::from_str::<RestSnapshot>(&text).map_err(|e| e.to_string()));
+    let updated = {
+        let mut state = state.borrow_mut();
+        state.fetching = false;
+        match fetched {
+            Ok(snapshot) => {
+                state.error = None;
+                state.seed(snapshot)
+            }
+            Err(e) => {
+                state.error = Some(e);
+                false
+            }
+        }
+    };
+    if updated {
+        notify(&state);
+    }
+}
+
+async fn fetch_text(url: &str) -> Result<String, String> {
+    let window = web_sys::window().ok_or("no window to fetch from")?;
+    let describe = |e: JsValue| e.as_string().unwrap_or_else(|| format!("{:?}", e));
+    let response: Response = JsFuture::from(window.fetch_with_str(url)).await.map_err(describe)?.unchecked_into();
+    if !response.ok() {
+        return Err(format!("snapshot fetch failed with status {}", response.status()));
+    }
+    let text = JsFuture::from(response.text().map_err(describe)?).await.map_err(describe)?;
+    text.as_string().ok_or_else(|| "snapshot response isn't text".to_string())
+}
+
+// notify calls the update callback, outside the borrow of the state, so it can read the
+// book.
+fn notify(state: &Rc<RefCell<FeedState>>) {
+    let (callback, ts) = {
+        let state = state.borrow();
+        (state.on_update.clone(), state.ts())
+    };
+    if let Some(callback) = callback {
+        let _ = callback.call1(&JsValue::NULL, &JsValue::from_f64(ts as f64));
+    }
+}
@@ -0,0 +1,62 @@
+// Follows a book served by MockWoox through the C interface, as C would: new, set the
+// endpoints, start, read the top and free. Run it with
+// cargo test --features ffi,test-util --test ffi.
+use std::ffi::CString;
+use std::ptr::NonNull;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use woox::ffi::{woox_feed_free, woox_feed_is_running, woox_feed_new, woox_feed_set_endpoints, woox_feed_start, woox_feed_top, woox_feed_ts, WooxLevel};
+use woox::test_util::{snapshot, MockWoox};
+
+// SYNC_TIMEOUT bounds how long the feed is waited on to apply the last delta.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn follows_a_book_from_new_to_free() {
+    let server = MockWoox::new(snapshot(1000, &[(99.0, 1.0), (98.0, 2.0)], &[(101.0, 1.0)]))
+        .with_delta(1100, 1000, &[(100.0, 1.5)], &[])
+        .with_delta(1200, 1100, &[(99.0, 0.0)], &[(100.5, 3.0)])
+        .start()
+        .unwrap();
+    let symbol = CString::new("SPOT_BTC_USDT").unwrap();
+    let ws_url = CString::new(server.ws_url()).unwrap();
+    let orderbook_url = CString::new(server.orderbook_url()).unwrap();
+    let as_ptr = |s: &CString| NonNull::new(s.as_ptr().cast_mut());
+
+    let mut feed = unsafe { woox_feed_new(as_ptr(&symbol), 5) }.expect("no feed for a valid symbol");
+    assert_eq!(unsafe { woox_feed_set_endpoints(Some(&mut feed), as_ptr(&ws_url), as_ptr(&orderbook_url)) }, 0);
+    assert!(!woox_feed_is_running(Some(&feed)));
+    assert_eq!(woox_feed_start(Some(&mut feed)), 0);
+    // A started feed can't be started, or pointed elsewhere, again.
+    assert_eq!(woox_feed_start(Some(&mut feed)), -1);
+    assert_eq!(unsafe { woox_feed_set_endpoints(Some(&mut feed), as_ptr(&ws_url), as_ptr(&orderbook_url)) }, -1);
+
+    let deadline = Instant::now() + SYNC_TIMEOUT;
+    while woox_feed_ts(Some(&feed)) < 1200 && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let (mut bids, mut asks) = ([WooxLevel::default(); 5], [WooxLevel::default(); 5]);
+    let (mut bids_len, mut asks_len) = (usize::MAX, usize::MAX);
+    let ts = unsafe {
+        woox_feed_top(Some(&feed), 3, NonNull::new(bids.as_mut_ptr()), Some(&mut bids_len), NonNull::new(asks.as_mut_ptr()), Some(&mut asks_len))
+    };
+    assert_eq!(ts, 1200);
+    // The delta at 1200 removes 99, leaving 100 and 98.
+    assert_eq!(bids_len, 2);
+    assert_eq!((bids[0].price, bids[0].quantity), (100.0, 1.5));
+    assert_eq!((bids[1].price, bids[1].quantity), (98.0, 2.0));
+    assert_eq!(asks_len, 2);
+    assert_eq!((asks[0].price, asks[0].quantity), (100.5, 3.0));
+    assert_eq!((asks[1].price, asks[1].quantity), (101.0, 1.0));
+    // Past the levels copied, bids and asks are left alone.
+    assert_eq!(bids[2].price, 0.0);
+
+    unsafe { woox_feed_free(Some(feed)) };
+}
+
+#[test]
+fn rejects_a_null_symbol() {
+    assert!(unsafe { woox_feed_new(None, 5) }.is_none());
+}
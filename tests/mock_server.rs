@@ -0,0 +1,85 @@
+// Follows books served by MockWoox through the whole connect, snapshot and sync path, over
+// real local sockets. Run it with cargo test --features test-util --test mock_server.
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use woox::client::WooxClient;
+use woox::feed::FeedConfig;
+use woox::metrics::FeedMetrics;
+use woox::test_util::{snapshot, MockFrame, MockWoox, MockWooxServer};
+
+const SYMBOL: &str = "SPOT_BTC_USDT";
+// RECEIVED_TIMEOUT bounds how long the mock is waited on to read what the client sent.
+const RECEIVED_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn client(server: &MockWooxServer, metrics: Option<Arc<FeedMetrics>>) -> WooxClient {
+    let feed = FeedConfig { exchange: Arc::new(server.exchange()), metrics, ..FeedConfig::default() };
+    WooxClient::builder().symbol(SYMBOL).feed_config(feed).build()
+}
+
+// wait_received waits for the mock to have read a frame containing text from the client.
+fn wait_received(server: &MockWooxServer, text: &str) -> bool {
+    let deadline = Instant::now() + RECEIVED_TIMEOUT;
+    while Instant::now() < deadline {
+        if server.received().iter().any(|frame| frame.contains(text)) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    false
+}
+
+#[test]
+fn syncs_from_the_snapshot_and_applies_the_deltas_continuing_it() {
+    let server = MockWoox::new(snapshot(1000, &[(99.0, 1.0), (98.0, 2.0)], &[(101.0, 1.0)]))
+        .with_delta(900, 800, &[(97.0, 1.0)], &[])
+        .with_delta(1100, 1000, &[(100.0, 1.5)], &[])
+        .with_delta(1200, 1100, &[(99.0, 0.0)], &[(100.5, 3.0)])
+        .start()
+        .unwrap();
+
+    let mut last = None;
+    let result = client(&server, None).run_with(|book, event| last = Some((event.ts, book.best_bid(), book.best_ask(), book.bids().count())));
+    assert!(result.is_ok(), "{:?}", result.map_err(|e| e.to_string()));
+    let (ts, best_bid, best_ask, bids) = last.expect("no update was applied");
+    assert_eq!(ts, 1200);
+    assert_eq!(best_bid.map(|(price, quantity)| (price.value(), quantity.value())), Some((100.0, 1.5)));
+    assert_eq!(best_ask.map(|(price, quantity)| (price.value(), quantity.value())), Some((100.5, 3.0)));
+    // The delta behind the snapshot isn't applied, and the one at 1200 removes 99.
+    assert_eq!(bids, 2);
+    assert_eq!(server.snapshot_requests(), 1);
+    assert!(server.received()[0].contains("orderbookupdate@SPOT_BTC_USDT@50"), "{:?}", server.received());
+}
+
+#[test]
+fn answers_pings_with_pongs() {
+    let server = MockWoox::new(snapshot(1000, &[(99.0, 1.0)], &[(101.0, 1.0)]))
+        .with_frame(MockFrame::Ping)
+        .with_delta(1100, 1000, &[(100.0, 1.0)], &[])
+        .start()
+        .unwrap();
+
+    let result = client(&server, None).run();
+    assert!(result.is_ok(), "{:?}", result.map_err(|e| e.to_string()));
+    assert!(wait_received(&server, "PONG"), "{:?}", server.received());
+}
+
+#[test]
+fn ends_the_stream_once_the_connection_is_closed() {
+    let server = MockWoox::new(snapshot(1000, &[(99.0, 1.0)], &[(101.0, 1.0)]))
+        .with_delta(1100, 1000, &[(100.0, 1.0)], &[])
+        .with_frame(MockFrame::Pause(Duration::from_millis(100)))
+        .with_delta(1200, 1100, &[(100.5, 1.0)], &[])
+        .start()
+        .unwrap();
+
+    let metrics = Arc::new(FeedMetrics::default());
+    let mut applied = Vec::new();
+    let result = client(&server, Some(Arc::clone(&metrics))).run_with(|_, event| applied.push(event.ts));
+    assert!(result.is_ok(), "{:?}", result.map_err(|e| e.to_string()));
+    assert_eq!(applied.last(), Some(&1200));
+    assert_eq!(metrics.connects.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.open.load(Ordering::Relaxed), 0);
+}